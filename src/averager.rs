@@ -0,0 +1,441 @@
+//! A configurable bearing averager.
+//!
+//! [`average`](crate::average) and its variants each fix one set of choices.
+//! [`Averager`] gathers those choices in one place:
+//!
+//! ```
+//! use sdr_rust::averager::{AngleRange, Averager, Summation, Weighting};
+//!
+//! let mut averager = Averager::builder()
+//!     .weighting(Weighting::Uniform)
+//!     .trim(0.2)
+//!     .range(AngleRange::Unsigned)
+//!     .window(16)
+//!     .summation(Summation::Kahan)
+//!     .build()
+//!     .unwrap();
+//! averager.extend([(358.0, 1.0), (2.0, 0.5), (0.0, 1.0), (90.0, 1.0), (1.0, 1.0)]);
+//! let (angle, _) = averager.average().unwrap();
+//! assert!((angle - 0.25).abs() < 0.01);
+//! ```
+//!
+//! [`DecayingAverager`] instead fades timestamped readings out by age.
+
+use crate::error::{DspError, Result};
+use crate::reading::Reading;
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::f64::consts::{PI, TAU};
+
+/// How much each reading counts towards the average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Weighting {
+    /// Each reading is weighted by its magnitude, as in [`crate::average`].
+    #[default]
+    Magnitude,
+    /// Every reading counts equally; magnitudes are ignored.
+    Uniform,
+}
+
+/// The interval the averaged angle is reported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AngleRange {
+    /// `(-180, 180]` degrees, as [`crate::average`] returns.
+    #[default]
+    Signed,
+    /// `[0, 360)` degrees, as a compass bearing.
+    Unsigned,
+}
+
+/// How the readings' vectors are summed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Summation {
+    /// A plain running sum.
+    #[default]
+    Naive,
+    /// Kahan compensated summation, for long windows.
+    Kahan,
+    /// Pairwise summation, with error growing as `log n`.
+    Pairwise,
+}
+
+impl Summation {
+    fn sum(self, values: &[Complex<f64>]) -> Complex<f64> {
+        match self {
+            Summation::Naive => values.iter().sum(),
+            Summation::Kahan => {
+                let mut sum = Complex::new(0.0, 0.0);
+                let mut compensation = Complex::new(0.0, 0.0);
+                for &value in values {
+                    let corrected = value - compensation;
+                    let next = sum + corrected;
+                    compensation = (next - sum) - corrected;
+                    sum = next;
+                }
+                sum
+            }
+            Summation::Pairwise => {
+                if values.len() <= 8 {
+                    values.iter().sum()
+                } else {
+                    let (left, right) = values.split_at(values.len() / 2);
+                    self.sum(left) + self.sum(right)
+                }
+            }
+        }
+    }
+}
+
+/// Configures an [`Averager`].
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AveragerBuilder {
+    weighting: Weighting,
+    trim: f64,
+    range: AngleRange,
+    window: Option<usize>,
+    summation: Summation,
+}
+
+impl AveragerBuilder {
+    pub fn weighting(mut self, weighting: Weighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    /// Drops this fraction of the readings, those farthest from the
+    /// untrimmed mean, before averaging. Must be in `[0, 0.5)`.
+    pub fn trim(mut self, fraction: f64) -> Self {
+        self.trim = fraction;
+        self
+    }
+
+    pub fn range(mut self, range: AngleRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Keeps only the most recent `readings`. Unbounded by default.
+    pub fn window(mut self, readings: usize) -> Self {
+        self.window = Some(readings);
+        self
+    }
+
+    pub fn summation(mut self, summation: Summation) -> Self {
+        self.summation = summation;
+        self
+    }
+
+    pub fn build(self) -> Result<Averager> {
+        if !(0.0..0.5).contains(&self.trim) {
+            return Err(DspError::InvalidArgument(format!(
+                "trim fraction must be in [0, 0.5), got {}",
+                self.trim
+            ))
+            .into());
+        }
+        if self.window == Some(0) {
+            return Err(
+                DspError::InvalidArgument("window must hold at least one reading".into()).into(),
+            );
+        }
+        Ok(Averager {
+            config: self,
+            readings: VecDeque::new(),
+        })
+    }
+}
+
+/// Averages (angle, magnitude) readings with the options chosen through
+/// [`Averager::builder`], either one slice at a time or over a sliding
+/// window of pushed readings.
+#[derive(Debug, Clone)]
+pub struct Averager {
+    config: AveragerBuilder,
+    readings: VecDeque<(f64, f64)>,
+}
+
+impl Averager {
+    pub fn builder() -> AveragerBuilder {
+        AveragerBuilder::default()
+    }
+
+    /// Adds a reading, dropping the oldest if the window is full.
+    pub fn push(&mut self, reading: (f64, f64)) {
+        self.readings.push_back(reading);
+        if let Some(window) = self.config.window {
+            while self.readings.len() > window {
+                self.readings.pop_front();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.readings.clear();
+    }
+
+    /// Readings currently held, oldest first.
+    pub fn readings(&self) -> impl Iterator<Item = &(f64, f64)> {
+        self.readings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    /// The average of the held readings as (angle, magnitude), or `None` if
+    /// there are none.
+    pub fn average(&self) -> Option<(f64, f64)> {
+        let (front, back) = self.readings.as_slices();
+        if back.is_empty() {
+            self.average_all(front)
+        } else {
+            self.average_all(&self.readings.iter().copied().collect::<Vec<_>>())
+        }
+    }
+
+    /// The average of `readings`, ignoring the held ones. Only the last
+    /// `window` readings are used if a window is set.
+    pub fn average_of(&self, readings: &[(f64, f64)]) -> Option<(f64, f64)> {
+        let start = match self.config.window {
+            Some(window) => readings.len().saturating_sub(window),
+            None => 0,
+        };
+        self.average_all(&readings[start..])
+    }
+
+    fn average_all(&self, readings: &[(f64, f64)]) -> Option<(f64, f64)> {
+        if readings.is_empty() {
+            return None;
+        }
+        let mut vectors: Vec<Complex<f64>> = readings
+            .iter()
+            .map(|&(angle, magnitude)| {
+                let weight = match self.config.weighting {
+                    Weighting::Magnitude => magnitude,
+                    Weighting::Uniform => 1.0,
+                };
+                Complex::from_polar(weight, angle.to_radians())
+            })
+            .collect();
+        let mut total = self.config.summation.sum(&vectors);
+        let dropped = (self.config.trim * vectors.len() as f64) as usize;
+        if dropped > 0 {
+            let mean = total.arg();
+            let distance =
+                |vector: &Complex<f64>| ((vector.arg() - mean + PI).rem_euclid(TAU) - PI).abs();
+            // Nearest first, so truncating drops the farthest.
+            vectors.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+            vectors.truncate(vectors.len() - dropped);
+            total = self.config.summation.sum(&vectors);
+        }
+        let mean = total / vectors.len() as f64;
+        let angle = mean.arg().to_degrees();
+        let angle = match self.config.range {
+            AngleRange::Signed => angle,
+            AngleRange::Unsigned => {
+                // A tiny negative angle wraps to exactly 360 in floating point.
+                let bearing = angle.rem_euclid(360.0);
+                if bearing < 360.0 {
+                    bearing
+                } else {
+                    0.0
+                }
+            }
+        };
+        Some((angle, mean.norm()))
+    }
+}
+
+impl Extend<(f64, f64)> for Averager {
+    fn extend<I: IntoIterator<Item = (f64, f64)>>(&mut self, readings: I) {
+        readings.into_iter().for_each(|reading| self.push(reading));
+    }
+}
+
+/// Averages timestamped readings with weights that halve every
+/// `half_life` seconds, so the bearing follows new readings without
+/// forgetting recent ones all at once as a hard window does.
+///
+/// Each reading counts by its magnitude and quality. Only running sums are
+/// kept, so memory use is constant however many readings are pushed.
+#[derive(Debug, Clone)]
+pub struct DecayingAverager {
+    half_life: f64,
+    /// Decayed sum of the readings' vectors.
+    sum: Complex<f64>,
+    /// Decayed number of readings.
+    count: f64,
+    /// The newest timestamp seen, which the sums are decayed to.
+    latest: Option<f64>,
+}
+
+impl DecayingAverager {
+    pub fn new(half_life: f64) -> Result<Self> {
+        if half_life.is_nan() || half_life <= 0.0 {
+            return Err(DspError::InvalidArgument(format!(
+                "half-life must be positive, got {half_life}"
+            ))
+            .into());
+        }
+        Ok(DecayingAverager {
+            half_life,
+            sum: Complex::new(0.0, 0.0),
+            count: 0.0,
+            latest: None,
+        })
+    }
+
+    pub fn half_life(&self) -> f64 {
+        self.half_life
+    }
+
+    /// The factor a weight shrinks by over `age` seconds.
+    fn decay(&self, age: f64) -> f64 {
+        (-age / self.half_life).exp2()
+    }
+
+    /// Adds a reading. One older than the newest so far is decayed by its
+    /// age before being added.
+    pub fn push(&mut self, reading: impl Into<Reading>) {
+        let reading = reading.into();
+        let latest = self.latest.unwrap_or(reading.timestamp);
+        let factor = if reading.timestamp >= latest {
+            let shrink = self.decay(reading.timestamp - latest);
+            self.sum *= shrink;
+            self.count *= shrink;
+            self.latest = Some(reading.timestamp);
+            1.0
+        } else {
+            self.decay(latest - reading.timestamp)
+        };
+        self.sum += Complex::from_polar(factor * reading.weight(), reading.angle.to_radians());
+        self.count += factor;
+    }
+
+    pub fn clear(&mut self) {
+        self.sum = Complex::new(0.0, 0.0);
+        self.count = 0.0;
+        self.latest = None;
+    }
+
+    /// The weighted average as of the newest reading, as (angle, magnitude),
+    /// or `None` before the first. With equal timestamps it matches
+    /// [`crate::average`].
+    pub fn average(&self) -> Option<(f64, f64)> {
+        if self.count <= 0.0 {
+            return None;
+        }
+        let mean = self.sum / self.count;
+        Some((mean.arg().to_degrees(), mean.norm()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::average;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_defaults_match_average() {
+        let readings = [
+            (358.0, 1.0),
+            (1.0, 0.5),
+            (359.0, 2.0),
+            (355.0, 1.0),
+            (2.0, 1.0),
+        ];
+        let averager = Averager::builder().build().unwrap();
+        let (angle, magnitude) = averager.average_of(&readings).unwrap();
+        let (expected_angle, expected_magnitude) = average(&readings);
+        assert_relative_eq!(angle, expected_angle, epsilon = 1e-9);
+        assert_relative_eq!(magnitude, expected_magnitude, epsilon = 1e-12);
+        for summation in [Summation::Kahan, Summation::Pairwise] {
+            let averager = Averager::builder().summation(summation).build().unwrap();
+            let (angle, _) = averager.average_of(&readings).unwrap();
+            assert_relative_eq!(angle, expected_angle, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_trim_rejects_outliers_and_window_slides() {
+        let mut averager = Averager::builder()
+            .trim(0.25)
+            .range(AngleRange::Unsigned)
+            .window(4)
+            .build()
+            .unwrap();
+        assert_eq!(averager.average(), None);
+        averager.extend([
+            (180.0, 1.0),
+            (350.0, 1.0),
+            (10.0, 1.0),
+            (0.0, 1.0),
+            (120.0, 1.0),
+        ]);
+        assert_eq!(averager.len(), 4);
+        // The window holds 350, 10, 0 and 120; the trim drops 120.
+        let (angle, magnitude) = averager.average().unwrap();
+        assert_relative_eq!(angle, 0.0, epsilon = 1e-9);
+        assert_relative_eq!(
+            magnitude,
+            (1.0 + 2.0 * 10f64.to_radians().cos()) / 3.0,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_uniform_weighting_ignores_magnitude() {
+        let averager = Averager::builder()
+            .weighting(Weighting::Uniform)
+            .build()
+            .unwrap();
+        let (angle, magnitude) = averager.average_of(&[(0.0, 10.0), (90.0, 1.0)]).unwrap();
+        assert_relative_eq!(angle, 45.0, epsilon = 1e-9);
+        assert_relative_eq!(magnitude, 0.5f64.sqrt(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_decaying_average_follows_new_bearing() {
+        let mut averager = DecayingAverager::new(10.0).unwrap();
+        assert_eq!(averager.average(), None);
+        averager.push((0.0, 350.0, 1.0));
+        averager.push((0.0, 10.0, 1.0));
+        let (angle, magnitude) = averager.average().unwrap();
+        let (expected_angle, expected_magnitude) = average(&[(350.0, 1.0), (10.0, 1.0)]);
+        assert_relative_eq!(angle, expected_angle, epsilon = 1e-9);
+        assert_relative_eq!(magnitude, expected_magnitude, epsilon = 1e-12);
+
+        // One half-life on, the earlier two have halved and together count
+        // as much as a new reading at 90.
+        averager.clear();
+        averager.push((0.0, 0.0, 1.0));
+        averager.push((0.0, 0.0, 1.0));
+        averager.push((10.0, 90.0, 1.0));
+        let (angle, _) = averager.average().unwrap();
+        assert_relative_eq!(angle, 45.0, epsilon = 1e-9);
+        // A late reading is aged on arrival, here to half its weight.
+        averager.push(Reading::new(0.0, 0.0, 2.0));
+        let (angle, _) = averager.average().unwrap();
+        assert_relative_eq!(angle, 0.5f64.atan().to_degrees(), epsilon = 1e-9);
+        assert!(DecayingAverager::new(0.0).is_err());
+    }
+
+    #[test]
+    fn test_invalid_options_are_rejected() {
+        let error = Averager::builder().trim(0.5).build().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "DSP error: trim fraction must be in [0, 0.5), got 0.5"
+        );
+        assert!(Averager::builder().window(0).build().is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! The processing unit that flowgraphs are built from.
+
+use crate::param::{ParamError, ParamValue};
+use std::marker::PhantomData;
+
+pub mod probe;
+
+/// A processing step with a typed input and output port.
+pub trait Block {
+    /// The type of sample read from the input port.
+    type Input;
+    /// The type of sample written to the output port.
+    type Output;
+
+    /// Processes samples from `input`, appending any results to `output`, and
+    /// returns how many input samples were consumed. Unconsumed samples are
+    /// offered again on the next call together with newer input.
+    fn work(&mut self, input: &[Self::Input], output: &mut Vec<Self::Output>) -> usize;
+
+    /// Changes a parameter between calls to [`work`](Block::work). Blocks
+    /// without runtime parameters keep the default, which rejects every name.
+    fn set_parameter(&mut self, name: &str, _value: &ParamValue) -> Result<(), ParamError> {
+        Err(ParamError::Unknown(name.to_string()))
+    }
+}
+
+/// A block that applies a function to every sample.
+pub struct Map<F, I, O> {
+    function: F,
+    _types: PhantomData<fn(I) -> O>,
+}
+
+/// Creates a block that applies `function` to every sample.
+pub fn map<F, I, O>(function: F) -> Map<F, I, O>
+where
+    F: FnMut(&I) -> O,
+{
+    Map {
+        function,
+        _types: PhantomData,
+    }
+}
+
+impl<F, I, O> Block for Map<F, I, O>
+where
+    F: FnMut(&I) -> O,
+{
+    type Input = I;
+    type Output = O;
+
+    fn work(&mut self, input: &[I], output: &mut Vec<O>) -> usize {
+        output.extend(input.iter().map(&mut self.function));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map() {
+        let mut block = map(|x: &i32| x * 2);
+        let mut output = Vec::new();
+        assert_eq!(block.work(&[1, 2, 3], &mut output), 3);
+        assert_eq!(output, vec![2, 4, 6]);
+    }
+}
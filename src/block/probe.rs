@@ -0,0 +1,263 @@
+//! Level metering that leaves the signal untouched.
+//!
+//! A [`Probe`] sits anywhere in a chain and passes its input straight
+//! through, measuring as it goes. The figures live in atomics behind a
+//! [`ProbeHandle`], so a UI thread can poll an S-meter or a clip light
+//! without locks and without a parameter round trip through the flowgraph.
+
+use crate::block::Block;
+use crate::dsp::db::power_to_db;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::Float;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Level reported for a probe that has seen no signal, in dB.
+const FLOOR_DB: f64 = -200.0;
+
+/// A sample whose instantaneous power a [`Probe`] can measure: the square
+/// of a real amplitude, such as audio, or the squared magnitude of a
+/// complex sample. Unlike [`Power`](crate::dsp::db::Power), real values are
+/// amplitudes rather than powers, so a negative peak meters as a positive
+/// one.
+pub trait Amplitude: Copy {
+    fn squared(self) -> f64;
+}
+
+impl Amplitude for f32 {
+    fn squared(self) -> f64 {
+        f64::from(self) * f64::from(self)
+    }
+}
+
+impl Amplitude for f64 {
+    fn squared(self) -> f64 {
+        self * self
+    }
+}
+
+impl<F: Float> Amplitude for Complex<F> {
+    fn squared(self) -> f64 {
+        self.norm_sqr().to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProbeShared {
+    /// Linear powers as `f64` bits. Non-negative floats order the same way
+    /// as their bits, so the peak can be kept with `fetch_max`.
+    power: AtomicU64,
+    peak: AtomicU64,
+    samples: AtomicU64,
+    overflows: AtomicU64,
+}
+
+/// The reading side of a [`Probe`]. Clones share the same figures.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeHandle {
+    shared: Arc<ProbeShared>,
+}
+
+impl ProbeHandle {
+    /// The smoothed power, linear.
+    pub fn power(&self) -> f64 {
+        f64::from_bits(self.shared.power.load(Ordering::Relaxed))
+    }
+
+    /// The smoothed power in dB.
+    pub fn power_db(&self) -> f64 {
+        power_to_db(self.power(), FLOOR_DB)
+    }
+
+    /// The highest sample power since the peak was last taken, in dB.
+    pub fn peak_db(&self) -> f64 {
+        power_to_db(
+            f64::from_bits(self.shared.peak.load(Ordering::Relaxed)),
+            FLOOR_DB,
+        )
+    }
+
+    /// Returns the peak in dB and starts a new one, as a meter with a
+    /// peak-hold does each time it redraws.
+    pub fn take_peak_db(&self) -> f64 {
+        power_to_db(
+            f64::from_bits(self.shared.peak.swap(0, Ordering::Relaxed)),
+            FLOOR_DB,
+        )
+    }
+
+    /// Samples seen.
+    pub fn samples(&self) -> u64 {
+        self.shared.samples.load(Ordering::Acquire)
+    }
+
+    /// Samples at or above the clip level.
+    pub fn overflows(&self) -> u64 {
+        self.shared.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Clears the peak and the counts. The smoothed power carries on.
+    pub fn reset(&self) {
+        self.shared.peak.store(0, Ordering::Relaxed);
+        self.shared.samples.store(0, Ordering::Relaxed);
+        self.shared.overflows.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Passes samples through unchanged while measuring their power.
+///
+/// The power is an exponential average that moves `alpha` of the way to
+/// each sample's power, its [`Amplitude::squared`]. A sample overflows when
+/// its power reaches the clip level, by default 1.0: full scale either way
+/// for real samples scaled to `[-1, 1]`, and for complex ones of unit
+/// magnitude.
+#[derive(Debug, Clone)]
+pub struct Probe<T> {
+    alpha: f64,
+    clip_level: f64,
+    power: Option<f64>,
+    handle: ProbeHandle,
+    _samples: PhantomData<fn(T)>,
+}
+
+impl<T: Amplitude> Probe<T> {
+    /// Smooths with weight `alpha`, clamped to between 0 and 1.
+    pub fn new(alpha: f64) -> Self {
+        Probe {
+            alpha: alpha.clamp(0.0, 1.0),
+            clip_level: 1.0,
+            power: None,
+            handle: ProbeHandle::default(),
+            _samples: PhantomData,
+        }
+    }
+
+    /// Counts samples of at least `power` as overflows.
+    pub fn with_clip_level(mut self, power: f64) -> Self {
+        self.clip_level = power;
+        self
+    }
+
+    /// A handle for reading the figures once the probe has moved into a
+    /// flowgraph.
+    pub fn handle(&self) -> ProbeHandle {
+        self.handle.clone()
+    }
+}
+
+impl<T: Amplitude> Block for Probe<T> {
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        let mut peak = 0.0f64;
+        let mut overflows = 0;
+        for &sample in input {
+            let power = sample.squared();
+            if power.is_nan() {
+                continue;
+            }
+            peak = peak.max(power);
+            if power >= self.clip_level {
+                overflows += 1;
+            }
+            self.power = Some(match self.power {
+                Some(previous) => previous + (power - previous) * self.alpha,
+                None => power,
+            });
+        }
+        output.extend_from_slice(input);
+        let shared = &self.handle.shared;
+        if let Some(power) = self.power {
+            shared
+                .power
+                .store(power.max(0.0).to_bits(), Ordering::Relaxed);
+        }
+        shared.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        shared.overflows.fetch_add(overflows, Ordering::Relaxed);
+        shared
+            .samples
+            .fetch_add(input.len() as u64, Ordering::Release);
+        input.len()
+    }
+
+    /// Accepts `alpha`, between 0 and 1, and `clip_level`, a power.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("alpha", Some(alpha)) if (0.0..=1.0).contains(&alpha) => self.alpha = alpha,
+            ("clip_level", Some(level)) if level > 0.0 => self.clip_level = level,
+            ("alpha" | "clip_level", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use num_complex::Complex;
+
+    #[test]
+    fn test_passes_through_and_measures() {
+        let mut probe = Probe::new(0.5);
+        let handle = probe.handle();
+        let input = [
+            Complex::new(0.1, 0.0),
+            Complex::new(0.0, 0.1),
+            Complex::new(0.6, 0.8),
+            Complex::new(0.1, 0.0),
+        ];
+        let mut output = Vec::new();
+        assert_eq!(probe.work(&input, &mut output), 4);
+        assert_eq!(output, input);
+        assert_eq!(handle.samples(), 4);
+        // The 0.6 + 0.8j sample sits exactly at full scale.
+        assert_eq!(handle.overflows(), 1);
+        assert_relative_eq!(handle.peak_db(), 0.0, epsilon = 1e-9);
+        assert_relative_eq!(handle.power(), 0.2575, epsilon = 1e-12);
+
+        assert_relative_eq!(handle.take_peak_db(), 0.0, epsilon = 1e-9);
+        assert_eq!(handle.peak_db(), FLOOR_DB);
+        probe.work(&input[..1], &mut output);
+        assert_relative_eq!(handle.peak_db(), -20.0, epsilon = 1e-9);
+        handle.reset();
+        assert_eq!((handle.samples(), handle.overflows()), (0, 0));
+    }
+
+    #[test]
+    fn test_handle_reads_from_another_thread() {
+        let mut probe = Probe::new(1.0).with_clip_level(4.0);
+        let handle = probe.handle();
+        let meter = std::thread::spawn(move || {
+            while handle.samples() < 3 {
+                std::thread::yield_now();
+            }
+            (handle.power_db(), handle.overflows())
+        });
+        probe.work(&[1.0, -2.0, 10.0], &mut Vec::new());
+        let (power_db, overflows) = meter.join().unwrap();
+        assert_relative_eq!(power_db, 20.0, epsilon = 1e-9);
+        assert_eq!(overflows, 2);
+        assert!(probe.set_parameter("alpha", &2.0.into()).is_err());
+        assert!(probe.set_parameter("clip_level", &0.5.into()).is_ok());
+    }
+    #[test]
+    fn test_meters_real_audio() {
+        // A half-scale sine, then two samples clipped at either rail.
+        let mut audio: Vec<f32> = (0..4800)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * n as f32 / 48_000.0).sin())
+            .collect();
+        audio.extend([1.0, -1.0]);
+        let mut probe = Probe::new(1e-3);
+        let handle = probe.handle();
+        probe.work(&audio, &mut Vec::new());
+        // The mean square of a sine is half its peak squared, -9 dB here.
+        assert_relative_eq!(handle.power_db(), 10.0 * 0.125f64.log10(), epsilon = 0.2);
+        assert_relative_eq!(handle.peak_db(), 0.0, epsilon = 1e-9);
+        assert_eq!(handle.overflows(), 2);
+    }
+}
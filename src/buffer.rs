@@ -0,0 +1,301 @@
+//! Reusable sample buffers and bounded queues with explicit overrun policies.
+//!
+//! A [`BufferPool`] hands out [`PooledBuffer`]s that return to the pool when
+//! dropped, so a steady-state pipeline stops allocating once the pool has
+//! warmed up. A filled buffer can be frozen into a [`SampleBlock`], which is
+//! reference counted and can be shared with several consumers at once.
+//!
+//! A [`BoundedQueue`] decides what happens when the consumer falls behind
+//! according to its [`OverrunPolicy`], and counts anything it discards.
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+struct PoolInner<T> {
+    free: Mutex<Vec<Vec<T>>>,
+    buffer_capacity: usize,
+    allocations: AtomicU64,
+}
+
+/// A pool of sample buffers that are recycled instead of freed.
+pub struct BufferPool<T> {
+    inner: Arc<PoolInner<T>>,
+}
+
+impl<T> Clone for BufferPool<T> {
+    fn clone(&self) -> Self {
+        BufferPool {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> BufferPool<T> {
+    /// Creates a pool whose buffers are allocated with room for
+    /// `buffer_capacity` samples.
+    pub fn new(buffer_capacity: usize) -> Self {
+        BufferPool {
+            inner: Arc::new(PoolInner {
+                free: Mutex::new(Vec::new()),
+                buffer_capacity,
+                allocations: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Takes an empty buffer from the pool, allocating only if none is free.
+    pub fn get(&self) -> PooledBuffer<T> {
+        let recycled = self.inner.free.lock().unwrap().pop();
+        let buffer = recycled.unwrap_or_else(|| {
+            self.inner.allocations.fetch_add(1, Ordering::Relaxed);
+            Vec::with_capacity(self.inner.buffer_capacity)
+        });
+        PooledBuffer {
+            buffer,
+            pool: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Buffers currently waiting in the pool.
+    pub fn available(&self) -> usize {
+        self.inner.free.lock().unwrap().len()
+    }
+
+    /// Total buffers this pool has had to allocate.
+    pub fn allocations(&self) -> u64 {
+        self.inner.allocations.load(Ordering::Relaxed)
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`]; it goes back to the pool on drop.
+pub struct PooledBuffer<T> {
+    buffer: Vec<T>,
+    pool: Arc<PoolInner<T>>,
+}
+
+impl<T> PooledBuffer<T> {
+    /// Makes the buffer immutable and shareable.
+    pub fn freeze(self) -> SampleBlock<T> {
+        SampleBlock(Arc::new(self))
+    }
+}
+
+impl<T> Deref for PooledBuffer<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.buffer
+    }
+}
+
+impl<T> DerefMut for PooledBuffer<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.buffer
+    }
+}
+
+impl<T> Drop for PooledBuffer<T> {
+    fn drop(&mut self) {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        self.pool.free.lock().unwrap().push(buffer);
+    }
+}
+
+/// A reference-counted, read-only block of samples. Its buffer returns to the
+/// pool once the last clone is dropped.
+pub struct SampleBlock<T>(Arc<PooledBuffer<T>>);
+
+impl<T> Clone for SampleBlock<T> {
+    fn clone(&self) -> Self {
+        SampleBlock(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Deref for SampleBlock<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0.buffer
+    }
+}
+
+/// What a [`BoundedQueue`] does with new items when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverrunPolicy {
+    /// Wait until the consumer makes room.
+    #[default]
+    Block,
+    /// Discard the oldest queued item to make room.
+    DropOldest,
+    /// Discard the new item.
+    DropNewest,
+}
+
+/// The queue was closed, so the item could not be delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+/// A thread-safe FIFO with a fixed capacity and an [`OverrunPolicy`].
+pub struct BoundedQueue<T> {
+    state: Mutex<QueueState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverrunPolicy,
+    dropped: AtomicU64,
+}
+
+impl<T> BoundedQueue<T> {
+    /// Creates a queue holding at most `capacity` items (at least one).
+    pub fn new(capacity: usize, policy: OverrunPolicy) -> Self {
+        BoundedQueue {
+            state: Mutex::new(QueueState {
+                items: VecDeque::new(),
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn policy(&self) -> OverrunPolicy {
+        self.policy
+    }
+
+    /// Adds an item, applying the overrun policy if the queue is full.
+    pub fn push(&self, item: T) -> Result<(), Closed> {
+        let mut state = self.state.lock().unwrap();
+        if self.policy == OverrunPolicy::Block {
+            while state.items.len() >= self.capacity && !state.closed {
+                state = self.not_full.wait(state).unwrap();
+            }
+        }
+        if state.closed {
+            return Err(Closed);
+        }
+        if state.items.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            match self.policy {
+                OverrunPolicy::DropNewest => return Ok(()),
+                OverrunPolicy::DropOldest | OverrunPolicy::Block => {
+                    state.items.pop_front();
+                }
+            }
+        }
+        state.items.push_back(item);
+        drop(state);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Removes the oldest item, waiting for one to arrive. Returns `None` once
+    /// the queue is closed and empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                drop(state);
+                self.not_full.notify_one();
+                return Some(item);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.not_empty.wait(state).unwrap();
+        }
+    }
+
+    /// Removes the oldest item without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        let item = self.state.lock().unwrap().items.pop_front();
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+
+    /// Stops accepting items and wakes every waiting thread. Items already
+    /// queued can still be popped.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Items discarded by the overrun policy.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_recycles_buffers() {
+        let pool = BufferPool::new(16);
+        for _ in 0..10 {
+            let mut buffer = pool.get();
+            buffer.extend_from_slice(&[1.0, 2.0, 3.0]);
+            let block = buffer.freeze();
+            let shared = block.clone();
+            assert_eq!(&*shared, &[1.0, 2.0, 3.0]);
+        }
+        assert_eq!(pool.allocations(), 1);
+        assert_eq!(pool.available(), 1);
+        assert!(pool.get().is_empty());
+    }
+
+    #[test]
+    fn test_drop_policies() {
+        let oldest = BoundedQueue::new(2, OverrunPolicy::DropOldest);
+        let newest = BoundedQueue::new(2, OverrunPolicy::DropNewest);
+        for item in 1..=4 {
+            oldest.push(item).unwrap();
+            newest.push(item).unwrap();
+        }
+        assert_eq!((oldest.pop(), oldest.pop()), (Some(3), Some(4)));
+        assert_eq!((newest.pop(), newest.pop()), (Some(1), Some(2)));
+        assert_eq!(oldest.dropped(), 2);
+        assert_eq!(newest.dropped(), 2);
+    }
+
+    #[test]
+    fn test_blocking_policy_waits_for_consumer() {
+        let queue = Arc::new(BoundedQueue::new(1, OverrunPolicy::Block));
+        let producer = {
+            let queue = Arc::clone(&queue);
+            std::thread::spawn(move || {
+                for item in 0..100 {
+                    queue.push(item).unwrap();
+                }
+                queue.close();
+            })
+        };
+        let received: Vec<i32> = std::iter::from_fn(|| queue.pop()).collect();
+        producer.join().unwrap();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+        assert_eq!(queue.dropped(), 0);
+        assert_eq!(queue.push(1), Err(Closed));
+    }
+}
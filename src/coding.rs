@@ -0,0 +1,11 @@
+//! Line codes, framing and error-correcting codes shared by the decoders.
+
+pub mod convolutional;
+pub mod crc;
+pub mod differential;
+pub mod hdlc;
+pub mod interleave;
+pub mod manchester;
+pub mod nrzi;
+pub mod reed_solomon;
+pub mod scrambler;
@@ -0,0 +1,248 @@
+//! Table-driven cyclic redundancy checks.
+//!
+//! A CRC is the remainder of the message, as a polynomial over GF(2),
+//! divided by a generator, with a starting value, an optional final XOR,
+//! and a choice of bit order. Reflected CRCs take each byte least
+//! significant bit first, as UARTs and HDLC send them, and the rest most
+//! significant bit first. [`CrcParams`] holds the parameters as the usual
+//! catalogues give them, with the polynomial and starting value
+//! unreflected, and [`Crc`] builds its byte table at compile time, so the
+//! common ones below are `static`.
+
+/// A CRC's parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrcParams {
+    /// Bits in the CRC, from 8 to 32.
+    pub width: u32,
+    /// The generator without its top term, most significant bit highest.
+    pub polynomial: u32,
+    pub init: u32,
+    /// Whether bytes go in, and the CRC comes out, least significant bit
+    /// first.
+    pub reflected: bool,
+    pub xor_out: u32,
+}
+
+/// A CRC with its table.
+#[derive(Debug, Clone)]
+pub struct Crc {
+    params: CrcParams,
+    table: [u32; 256],
+}
+
+/// CRC-16 as X.25 and HDLC use it. Checks `123456789` as 0x906e.
+pub static CRC_16_X25: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x1021,
+    init: 0xffff,
+    reflected: true,
+    xor_out: 0xffff,
+});
+/// The CCITT polynomial reflected from zero, as Kermit uses it. Checks
+/// as 0x2189.
+pub static CRC_16_KERMIT: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x1021,
+    init: 0,
+    reflected: true,
+    xor_out: 0,
+});
+/// The CCITT polynomial unreflected from 0xffff, often called just
+/// CRC-16-CCITT. Checks as 0x29b1.
+pub static CRC_16_CCITT_FALSE: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x1021,
+    init: 0xffff,
+    reflected: false,
+    xor_out: 0,
+});
+/// The CCITT polynomial unreflected from zero, as XMODEM and LoRa use
+/// it. Checks as 0x31c3.
+pub static CRC_16_XMODEM: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x1021,
+    init: 0,
+    reflected: false,
+    xor_out: 0,
+});
+/// IBM's polynomial reflected from zero, also called CRC-16/ARC. Checks
+/// as 0xbb3d.
+pub static CRC_16_IBM: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x8005,
+    init: 0,
+    reflected: true,
+    xor_out: 0,
+});
+/// IBM's polynomial reflected from 0xffff, as Modbus uses it. Checks as
+/// 0x4b37.
+pub static CRC_16_MODBUS: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x8005,
+    init: 0xffff,
+    reflected: true,
+    xor_out: 0,
+});
+/// IBM's polynomial unreflected from zero, also called CRC-16/BUYPASS.
+/// Checks as 0xfee8.
+pub static CRC_16_IBM_UNREFLECTED: Crc = Crc::new(CrcParams {
+    width: 16,
+    polynomial: 0x8005,
+    init: 0,
+    reflected: false,
+    xor_out: 0,
+});
+/// The parity of Mode S replies and ADS-B squitters, over all but their
+/// last 24 bits.
+pub static CRC_24_MODE_S: Crc = Crc::new(CrcParams {
+    width: 24,
+    polynomial: 0xfff409,
+    init: 0,
+    reflected: false,
+    xor_out: 0,
+});
+/// The CRC-32 of Ethernet, zip and PNG. Checks as 0xcbf43926.
+pub static CRC_32: Crc = Crc::new(CrcParams {
+    width: 32,
+    polynomial: 0x04c1_1db7,
+    init: 0xffff_ffff,
+    reflected: true,
+    xor_out: 0xffff_ffff,
+});
+/// The same polynomial unreflected, as bzip2 and MPEG-2 use it with
+/// different final XORs. Checks as 0xfc891918.
+pub static CRC_32_BZIP2: Crc = Crc::new(CrcParams {
+    width: 32,
+    polynomial: 0x04c1_1db7,
+    init: 0xffff_ffff,
+    reflected: false,
+    xor_out: 0xffff_ffff,
+});
+
+/// The low `width` bits of `value` in reverse order.
+const fn reflect(value: u32, width: u32) -> u32 {
+    value.reverse_bits() >> (32 - width)
+}
+
+impl Crc {
+    /// Builds the table. Panics, at compile time for a constant, unless
+    /// the width is from 8 to 32.
+    pub const fn new(params: CrcParams) -> Self {
+        assert!(params.width >= 8 && params.width <= 32, "CRC width");
+        let top = 1 << (params.width - 1);
+        let polynomial = reflect(params.polynomial, params.width);
+        let mut table = [0; 256];
+        let mut byte = 0;
+        while byte < 256 {
+            let mut crc;
+            let mut bit = 0;
+            if params.reflected {
+                crc = byte as u32;
+                while bit < 8 {
+                    crc = if crc & 1 != 0 {
+                        crc >> 1 ^ polynomial
+                    } else {
+                        crc >> 1
+                    };
+                    bit += 1;
+                }
+            } else {
+                crc = (byte as u32) << (params.width - 8);
+                while bit < 8 {
+                    crc = if crc & top != 0 {
+                        (crc << 1 ^ params.polynomial) & mask(params.width)
+                    } else {
+                        crc << 1 & mask(params.width)
+                    };
+                    bit += 1;
+                }
+            }
+            table[byte] = crc;
+            byte += 1;
+        }
+        Crc { params, table }
+    }
+
+    pub fn params(&self) -> &CrcParams {
+        &self.params
+    }
+
+    /// The CRC of `bytes`.
+    pub fn checksum(&self, bytes: &[u8]) -> u32 {
+        self.finish(self.update(self.start(), bytes))
+    }
+
+    /// The register before any bytes, for [`update`](Crc::update) when a
+    /// message arrives in pieces.
+    pub fn start(&self) -> u32 {
+        match self.params.reflected {
+            true => reflect(self.params.init, self.params.width),
+            false => self.params.init,
+        }
+    }
+
+    /// The register after `bytes` more.
+    pub fn update(&self, register: u32, bytes: &[u8]) -> u32 {
+        let width = self.params.width;
+        bytes.iter().fold(register, |crc, &byte| {
+            if self.params.reflected {
+                crc >> 8 ^ self.table[((crc ^ u32::from(byte)) & 0xff) as usize]
+            } else {
+                let index = ((crc >> (width - 8)) ^ u32::from(byte)) & 0xff;
+                (crc << 8 ^ self.table[index as usize]) & mask(width)
+            }
+        })
+    }
+
+    /// The CRC from the register after the last byte.
+    pub fn finish(&self, register: u32) -> u32 {
+        register ^ self.params.xor_out
+    }
+}
+
+const fn mask(width: u32) -> u32 {
+    u32::MAX >> (32 - width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        let check = b"123456789";
+        let crcs = [
+            (&CRC_16_X25, 0x906e),
+            (&CRC_16_KERMIT, 0x2189),
+            (&CRC_16_CCITT_FALSE, 0x29b1),
+            (&CRC_16_XMODEM, 0x31c3),
+            (&CRC_16_IBM, 0xbb3d),
+            (&CRC_16_MODBUS, 0x4b37),
+            (&CRC_16_IBM_UNREFLECTED, 0xfee8),
+            (&CRC_32, 0xcbf4_3926),
+            (&CRC_32_BZIP2, 0xfc89_1918),
+        ];
+        for (crc, expected) in crcs {
+            assert_eq!(crc.checksum(check), expected, "{:?}", crc.params());
+            let (first, second) = check.split_at(4);
+            let register = crc.update(crc.update(crc.start(), first), second);
+            assert_eq!(crc.finish(register), expected);
+        }
+        let crc_8 = Crc::new(CrcParams {
+            width: 8,
+            polynomial: 0x07,
+            init: 0,
+            reflected: false,
+            xor_out: 0,
+        });
+        assert_eq!(crc_8.checksum(check), 0xf4);
+
+        // An ADS-B identification squitter, with its parity last.
+        let squitter = [
+            0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+        ];
+        assert_eq!(CRC_24_MODE_S.checksum(&squitter[..11]), 0x57_6098);
+        assert_eq!(CRC_24_MODE_S.checksum(&squitter), 0);
+    }
+}
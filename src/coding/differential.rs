@@ -0,0 +1,406 @@
+//! Differential coding, for demodulators without an absolute phase.
+//!
+//! A carrier recovery loop locks on to one of M equally good phases, so a
+//! PSK receiver without pilots or a known preamble cannot tell which
+//! symbol is which. Differential coding carries each symbol in the step
+//! from one to the next, which rotating everything leaves alone.
+//! [`DifferentialEncoder`] and [`DifferentialDecoder`] do so on symbol
+//! values, before mapping to a [`Constellation`](crate::constellation::Constellation)
+//! and after slicing, and [`DpskModulator`] and [`DpskDemodulator`] do so
+//! on phases directly for DBPSK, DQPSK and π/4-DQPSK, the demodulator
+//! comparing each sample with the last so it needs no carrier phase at all.
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+use num_complex::Complex;
+use std::f64::consts::{FRAC_PI_4, TAU};
+
+fn check_order(order: usize) -> Result<()> {
+    if order >= 2 {
+        Ok(())
+    } else {
+        Err(DspError::InvalidArgument(format!("order {order}, not at least 2")).into())
+    }
+}
+
+/// A [`DifferentialEncoder`] or [`DifferentialDecoder`] as serialized,
+/// checked by its `new` on the way in.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct CoderState {
+    order: usize,
+    previous: usize,
+}
+
+/// Sends each symbol as the sum, modulo the order, of it and the last
+/// symbol sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "CoderState")
+)]
+pub struct DifferentialEncoder {
+    order: usize,
+    previous: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CoderState> for DifferentialEncoder {
+    type Error = crate::error::SdrError;
+
+    fn try_from(state: CoderState) -> Result<Self> {
+        let mut encoder = DifferentialEncoder::new(state.order)?;
+        encoder.previous = state.previous % state.order;
+        Ok(encoder)
+    }
+}
+
+impl DifferentialEncoder {
+    /// Starts from symbol 0. Fails unless the order is at least 2.
+    pub fn new(order: usize) -> Result<Self> {
+        check_order(order)?;
+        Ok(DifferentialEncoder { order, previous: 0 })
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn push(&mut self, symbol: usize) -> usize {
+        self.previous = (self.previous + symbol) % self.order;
+        self.previous
+    }
+}
+
+impl Block for DifferentialEncoder {
+    type Input = usize;
+    type Output = usize;
+
+    fn work(&mut self, input: &[usize], output: &mut Vec<usize>) -> usize {
+        output.extend(input.iter().map(|&symbol| self.push(symbol)));
+        input.len()
+    }
+}
+
+/// Recovers each symbol as the difference, modulo the order, between it
+/// and the last symbol received. A constant offset, as from locking on
+/// the wrong phase, costs only the first symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "CoderState")
+)]
+pub struct DifferentialDecoder {
+    order: usize,
+    previous: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CoderState> for DifferentialDecoder {
+    type Error = crate::error::SdrError;
+
+    fn try_from(state: CoderState) -> Result<Self> {
+        let mut decoder = DifferentialDecoder::new(state.order)?;
+        decoder.previous = state.previous % state.order;
+        Ok(decoder)
+    }
+}
+
+impl DifferentialDecoder {
+    /// Starts from symbol 0. Fails unless the order is at least 2.
+    pub fn new(order: usize) -> Result<Self> {
+        check_order(order)?;
+        Ok(DifferentialDecoder { order, previous: 0 })
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn push(&mut self, symbol: usize) -> usize {
+        let difference = (symbol % self.order + self.order - self.previous) % self.order;
+        self.previous = symbol % self.order;
+        difference
+    }
+}
+
+impl Block for DifferentialDecoder {
+    type Input = usize;
+    type Output = usize;
+
+    fn work(&mut self, input: &[usize], output: &mut Vec<usize>) -> usize {
+        output.extend(input.iter().map(|&symbol| self.push(symbol)));
+        input.len()
+    }
+}
+
+/// The Gray code of `value`, so that neighbouring phase steps differ in
+/// one bit.
+fn gray(value: usize) -> usize {
+    value ^ value >> 1
+}
+
+fn gray_inverse(mut code: usize) -> usize {
+    let mut value = 0;
+    while code != 0 {
+        value ^= code;
+        code >>= 1;
+    }
+    value
+}
+
+/// The phase steps of M-ary DPSK: a Gray-coded symbol `s` turns the
+/// carrier by `k * 2π/M` plus the offset, where `gray(k) == s`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "MappingParameters")
+)]
+pub struct DpskMapping {
+    order: usize,
+    offset: f64,
+}
+
+/// A [`DpskMapping`] as serialized, checked by [`DpskMapping::new`] on the
+/// way in.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct MappingParameters {
+    order: usize,
+    offset: f64,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<MappingParameters> for DpskMapping {
+    type Error = crate::error::SdrError;
+
+    fn try_from(parameters: MappingParameters) -> Result<Self> {
+        DpskMapping::new(parameters.order, parameters.offset)
+    }
+}
+
+impl DpskMapping {
+    /// Fails unless the order is a power of two from 2 up.
+    pub fn new(order: usize, offset: f64) -> Result<Self> {
+        if !(order >= 2 && order.is_power_of_two()) {
+            return Err(DspError::InvalidArgument(format!(
+                "order {order}, not a power of two from 2 up"
+            ))
+            .into());
+        }
+        Ok(DpskMapping { order, offset })
+    }
+
+    /// Symbol 0 keeps the phase and 1 reverses it.
+    pub fn dbpsk() -> Self {
+        DpskMapping {
+            order: 2,
+            offset: 0.0,
+        }
+    }
+
+    /// Steps of 0, 90, 180 and 270 degrees for dibits 00, 01, 11 and 10.
+    pub fn dqpsk() -> Self {
+        DpskMapping {
+            order: 4,
+            offset: 0.0,
+        }
+    }
+
+    /// Steps of 45, 135, 225 and 315 degrees, as TETRA, NXDN and the
+    /// North American digital cellular systems use, so the carrier never
+    /// passes through zero.
+    pub fn pi4_dqpsk() -> Self {
+        DpskMapping {
+            order: 4,
+            offset: FRAC_PI_4,
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Bits per symbol.
+    pub fn bits(&self) -> u32 {
+        self.order.trailing_zeros()
+    }
+
+    /// The phase step, in radians, that carries `symbol`.
+    pub fn step(&self, symbol: usize) -> f64 {
+        let k = gray_inverse(symbol % self.order);
+        TAU * k as f64 / self.order as f64 + self.offset
+    }
+
+    /// The symbol whose step is nearest `step`, in radians.
+    pub fn symbol(&self, step: f64) -> usize {
+        let turns = (step - self.offset) / TAU * self.order as f64;
+        let k = turns.round().rem_euclid(self.order as f64) as usize;
+        gray(k)
+    }
+}
+
+/// Turns a unit carrier by each symbol's step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DpskModulator {
+    mapping: DpskMapping,
+    phase: f64,
+}
+
+impl DpskModulator {
+    /// Starts from a phase of zero.
+    pub fn new(mapping: DpskMapping) -> Self {
+        DpskModulator {
+            mapping,
+            phase: 0.0,
+        }
+    }
+
+    /// The next symbol's sample.
+    pub fn push(&mut self, symbol: usize) -> Complex<f64> {
+        self.phase = (self.phase + self.mapping.step(symbol)).rem_euclid(TAU);
+        Complex::from_polar(1.0, self.phase)
+    }
+}
+
+impl Block for DpskModulator {
+    type Input = usize;
+    type Output = Complex<f64>;
+
+    fn work(&mut self, input: &[usize], output: &mut Vec<Complex<f64>>) -> usize {
+        output.extend(input.iter().map(|&symbol| self.push(symbol)));
+        input.len()
+    }
+}
+
+/// Slices the phase step between each symbol-spaced sample and the last,
+/// so any fixed carrier phase cancels and a small frequency offset only
+/// biases the steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DpskDemodulator {
+    mapping: DpskMapping,
+    previous: Complex<f64>,
+}
+
+impl DpskDemodulator {
+    /// Measures the first sample's step from a phase of zero, where a
+    /// [`DpskModulator`] starts, so over the air only it is unreliable.
+    pub fn new(mapping: DpskMapping) -> Self {
+        DpskDemodulator {
+            mapping,
+            previous: Complex::new(1.0, 0.0),
+        }
+    }
+
+    /// The symbol carried into `sample`.
+    pub fn push(&mut self, sample: Complex<f64>) -> usize {
+        let step = (sample * self.previous.conj()).arg();
+        self.previous = sample;
+        self.mapping.symbol(step)
+    }
+}
+
+impl Block for DpskDemodulator {
+    type Input = Complex<f64>;
+    type Output = usize;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<usize>) -> usize {
+        output.extend(input.iter().map(|&sample| self.push(sample)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{complex_noise, real_noise};
+    use crate::constellation::Constellation;
+
+    fn symbols(order: usize, length: usize) -> Vec<usize> {
+        real_noise(length, 9)
+            .iter()
+            .map(|&x| ((x + 1.0) / 2.0 * order as f64) as usize % order)
+            .collect()
+    }
+
+    #[test]
+    fn test_differential_symbols_survive_a_phase_ambiguity() {
+        let data = symbols(8, 100);
+        let mut encoded = Vec::new();
+        DifferentialEncoder::new(8)
+            .unwrap()
+            .work(&data, &mut encoded);
+        // Through 8-PSK and a receiver locked three eighths of a turn out.
+        let psk8 = Constellation::psk8();
+        let slip = Complex::from_polar(1.0, 3.0 * FRAC_PI_4);
+        let sliced: Vec<usize> = encoded
+            .iter()
+            .map(|&s| psk8.nearest(psk8.points()[s] * slip).unwrap().0)
+            .collect();
+        assert_ne!(sliced, encoded);
+        let mut decoded = Vec::new();
+        DifferentialDecoder::new(8)
+            .unwrap()
+            .work(&sliced, &mut decoded);
+        assert_eq!(decoded[1..], data[1..]);
+        assert!(DifferentialEncoder::new(1).is_err());
+        assert!(DifferentialDecoder::new(0).is_err());
+    }
+
+    #[test]
+    fn test_dpsk_ignores_carrier_phase() {
+        for mapping in [
+            DpskMapping::dbpsk(),
+            DpskMapping::dqpsk(),
+            DpskMapping::pi4_dqpsk(),
+            DpskMapping::new(8, 0.0).unwrap(),
+        ] {
+            let data = symbols(mapping.order(), 300);
+            let mut samples = Vec::new();
+            DpskModulator::new(mapping).work(&data, &mut samples);
+            // An unknown carrier phase, a slow drift and a little noise.
+            let noise = complex_noise(samples.len(), 4);
+            let received: Vec<Complex<f64>> = samples
+                .iter()
+                .zip(&noise)
+                .enumerate()
+                .map(|(k, (&s, &n))| s * Complex::from_polar(0.7, 2.0 + 0.01 * k as f64) + 0.05 * n)
+                .collect();
+            let mut decoded = Vec::new();
+            DpskDemodulator::new(mapping).work(&received, &mut decoded);
+            assert_eq!(decoded[1..], data[1..], "{mapping:?}");
+        }
+    }
+
+    #[test]
+    fn test_neighbouring_steps_differ_by_one_bit() {
+        assert!(DpskMapping::new(6, 0.0).is_err());
+        let mapping = DpskMapping::new(8, 0.0).unwrap();
+        for k in 0..8 {
+            let a = mapping.symbol(TAU * k as f64 / 8.0);
+            let b = mapping.symbol(TAU * (k + 1) as f64 / 8.0);
+            assert_eq!((a ^ b).count_ones(), 1);
+            assert_eq!(mapping.symbol(mapping.step(a)), a);
+        }
+        assert_eq!(mapping.bits(), 3);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserializing_checks_the_order() {
+        let mut encoder = DifferentialEncoder::new(4).unwrap();
+        encoder.push(3);
+        let json = serde_json::to_string(&encoder).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DifferentialEncoder>(&json).unwrap(),
+            encoder
+        );
+        let zero = r#"{"order":0,"previous":0}"#;
+        assert!(serde_json::from_str::<DifferentialDecoder>(zero).is_err());
+        assert!(serde_json::from_str::<DpskMapping>(r#"{"order":3,"offset":0.0}"#).is_err());
+    }
+}
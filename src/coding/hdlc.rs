@@ -0,0 +1,184 @@
+//! HDLC framing, as AX.25, APRS and AIS use it.
+//!
+//! Frames are sent least significant bit first between flags, 0x7e, with a
+//! zero stuffed after every five ones inside a frame so that six ones only
+//! ever appear in a flag, and seven or more abort the frame. The last two
+//! bytes are a frame check sequence: the CRC-16 of ITU-T X.25 over the
+//! rest, low byte first.
+//!
+//! [`HdlcDeframer`] takes bits after NRZI decoding, strips the stuffing
+//! and emits each frame between flags that is a whole number of bytes
+//! within its length limits and whose check sequence matches. [`frame`]
+//! goes the other way, for transmitting.
+
+use crate::block::Block;
+use crate::coding::crc::CRC_16_X25;
+
+/// One frame that passed its check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HdlcFrame {
+    /// Index in the bit stream of the last bit of the closing flag.
+    pub end_bit: u64,
+    /// The frame's bytes, without the check sequence.
+    pub data: Vec<u8>,
+}
+
+/// The X.25 CRC-16 of `data`, as sent.
+fn fcs(data: &[u8]) -> u16 {
+    CRC_16_X25.checksum(data) as u16
+}
+
+/// The bits that send `data` and its check sequence, stuffed, between
+/// `leading` flags and `trailing` flags, at least one of each.
+pub fn frame(data: &[u8], leading: usize, trailing: usize) -> Vec<bool> {
+    let flags = |count: usize| (0..8 * count.max(1)).map(|k| 0x7e >> (k % 8) & 1 == 1);
+    let mut bits: Vec<bool> = flags(leading).collect();
+    let mut bytes = data.to_vec();
+    bytes.extend(fcs(data).to_le_bytes());
+    let mut ones = 0;
+    for k in 0..bytes.len() * 8 {
+        let bit = bytes[k / 8] >> (k % 8) & 1 == 1;
+        bits.push(bit);
+        ones = if bit { ones + 1 } else { 0 };
+        if ones == 5 {
+            bits.push(false);
+            ones = 0;
+        }
+    }
+    bits.extend(flags(trailing));
+    bits
+}
+
+/// Recovers frames from a stream of bits.
+#[derive(Debug, Clone)]
+pub struct HdlcDeframer {
+    min_length: usize,
+    max_length: usize,
+    /// The unstuffed bits since the last flag, or `None` after an abort.
+    bits: Option<Vec<bool>>,
+    ones: u32,
+    position: u64,
+    fcs_errors: u64,
+}
+
+impl HdlcDeframer {
+    /// Keeps frames of `min_length` to `max_length` bytes, not counting
+    /// the check sequence.
+    pub fn new(min_length: usize, max_length: usize) -> Self {
+        HdlcDeframer {
+            min_length,
+            max_length,
+            bits: None,
+            ones: 0,
+            position: 0,
+            fcs_errors: 0,
+        }
+    }
+
+    /// Whole frames dropped so far because their check sequence failed.
+    pub fn fcs_errors(&self) -> u64 {
+        self.fcs_errors
+    }
+
+    /// Accepts one bit, returning a frame if this bit ends one.
+    pub fn push(&mut self, bit: bool) -> Option<HdlcFrame> {
+        let position = self.position;
+        self.position += 1;
+        if bit {
+            self.ones += 1;
+            if self.ones >= 7 {
+                self.bits = None;
+                return None;
+            }
+        } else {
+            let ones = std::mem::replace(&mut self.ones, 0);
+            match ones {
+                6 => {
+                    let frame = self.bits.replace(Vec::new());
+                    return frame.and_then(|bits| self.finish(bits, position));
+                }
+                // Stuffed.
+                5 => return None,
+                _ => {}
+            }
+        }
+        let limit = (self.max_length + 2) * 8 + 7;
+        if let Some(bits) = &mut self.bits {
+            bits.push(bit);
+            if bits.len() > limit {
+                self.bits = None;
+            }
+        }
+        None
+    }
+
+    /// The frame in `bits`, which end with all of a flag but its last zero.
+    fn finish(&mut self, mut bits: Vec<bool>, position: u64) -> Option<HdlcFrame> {
+        bits.truncate(bits.len().saturating_sub(7));
+        if !bits.len().is_multiple_of(8) || bits.len() < (self.min_length + 2) * 8 {
+            return None;
+        }
+        let mut data: Vec<u8> = bits
+            .chunks(8)
+            .map(|bits| {
+                bits.iter()
+                    .rev()
+                    .fold(0, |byte, &bit| byte << 1 | u8::from(bit))
+            })
+            .collect();
+        let split = data.len() - 2;
+        let sent = u16::from_le_bytes([data[split], data[split + 1]]);
+        data.truncate(split);
+        if fcs(&data) != sent {
+            self.fcs_errors += 1;
+            trace_event!(trace, bytes = data.len(), "HDLC check sequence failed");
+            return None;
+        }
+        Some(HdlcFrame {
+            end_bit: position,
+            data,
+        })
+    }
+}
+
+impl Block for HdlcDeframer {
+    type Input = bool;
+    type Output = HdlcFrame;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<HdlcFrame>) -> usize {
+        output.extend(input.iter().filter_map(|&bit| self.push(bit)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deframes_stuffed_frames_and_checks_them() {
+        assert_eq!(fcs(b"123456789"), 0x906e);
+
+        let first = b"\x7e\xff\xfc\x1f hello".to_vec();
+        let second = vec![0xff; 20];
+        let mut bits = frame(&first, 3, 1);
+        bits.extend(frame(&second, 1, 1));
+        let mut corrupt = frame(b"corrupted", 1, 1);
+        corrupt[30] = !corrupt[30];
+        bits.extend(corrupt);
+        // An abort, then a frame too short to keep.
+        bits.extend(frame(b"aborted", 1, 1)[..40].iter().chain(&[true; 7]));
+        bits.extend(frame(b"a", 1, 1));
+
+        let mut deframer = HdlcDeframer::new(2, 64);
+        let mut frames = Vec::new();
+        deframer.work(&bits, &mut frames);
+        let data: Vec<&[u8]> = frames.iter().map(|frame| &frame.data[..]).collect();
+        assert_eq!(data, [&first[..], &second[..]]);
+        assert_eq!(deframer.fcs_errors(), 1);
+
+        let end = frame(&first, 3, 1).len() as u64 - 1;
+        assert_eq!(frames[0].end_bit, end);
+    }
+}
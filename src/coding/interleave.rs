@@ -0,0 +1,215 @@
+//! Block and convolutional interleavers.
+//!
+//! Fading and impulse noise wipe out runs of consecutive symbols, which
+//! overwhelm a decoder built for scattered errors. An interleaver shuffles
+//! symbols so that a run on the air lands spread out after the
+//! deinterleaver. A block interleaver writes a block into a grid by rows
+//! and reads it out by columns. A convolutional one, as DVB and several
+//! pager and satellite links use, passes symbols in turn through branches
+//! of growing delay, spreading as far with far less delay end to end.
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+use std::collections::VecDeque;
+
+/// Writes blocks into a grid by rows and reads them out by columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "GridSize")
+)]
+pub struct BlockInterleaver {
+    rows: usize,
+    columns: usize,
+}
+
+/// A [`BlockInterleaver`] as serialized, checked by
+/// [`BlockInterleaver::new`] on the way in.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct GridSize {
+    rows: usize,
+    columns: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GridSize> for BlockInterleaver {
+    type Error = crate::error::SdrError;
+
+    fn try_from(size: GridSize) -> Result<Self> {
+        BlockInterleaver::new(size.rows, size.columns)
+    }
+}
+
+impl BlockInterleaver {
+    /// Fails if either dimension is zero.
+    pub fn new(rows: usize, columns: usize) -> Result<Self> {
+        if rows == 0 || columns == 0 {
+            return Err(DspError::InvalidArgument(format!("{rows} by {columns} grid")).into());
+        }
+        Ok(BlockInterleaver { rows, columns })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Symbols per block.
+    pub fn len(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Never, as neither dimension may be zero.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Interleaves each block of `symbols`. Fails unless they are whole
+    /// blocks.
+    pub fn interleave<T: Copy>(&self, symbols: &[T]) -> Result<Vec<T>> {
+        self.transpose(symbols, self.rows, self.columns)
+    }
+
+    /// Undoes [`interleave`](BlockInterleaver::interleave).
+    pub fn deinterleave<T: Copy>(&self, symbols: &[T]) -> Result<Vec<T>> {
+        self.transpose(symbols, self.columns, self.rows)
+    }
+
+    /// Each block of `symbols`, read as `rows` by `columns`, transposed.
+    fn transpose<T: Copy>(&self, symbols: &[T], rows: usize, columns: usize) -> Result<Vec<T>> {
+        if !symbols.len().is_multiple_of(self.len()) {
+            return Err(DspError::InvalidArgument(format!(
+                "{} symbols in blocks of {}",
+                symbols.len(),
+                self.len()
+            ))
+            .into());
+        }
+        Ok(symbols
+            .chunks(self.len())
+            .flat_map(|block| {
+                (0..columns)
+                    .flat_map(move |column| (0..rows).map(move |row| block[row * columns + column]))
+            })
+            .collect())
+    }
+}
+
+/// Passes symbols in turn through branches delayed by whole multiples of
+/// a depth, the interleaver's delays growing and the deinterleaver's
+/// shrinking so that every symbol is delayed the same in all.
+#[derive(Debug, Clone)]
+pub struct ConvolutionalInterleaver<T> {
+    /// Each branch's delay line, starting full of `T::default()`.
+    branches: Vec<VecDeque<T>>,
+    depth: usize,
+    next: usize,
+}
+
+impl<T: Clone + Default> ConvolutionalInterleaver<T> {
+    /// An interleaver whose branch `i` delays by `i * depth` symbols.
+    /// Fails unless there is at least one branch.
+    pub fn new(branches: usize, depth: usize) -> Result<Self> {
+        Self::with_delays((0..branches).map(|i| i * depth), depth)
+    }
+
+    /// The deinterleaver for [`new`](ConvolutionalInterleaver::new) with
+    /// the same arguments.
+    pub fn deinterleaver(branches: usize, depth: usize) -> Result<Self> {
+        Self::with_delays((0..branches).rev().map(|i| i * depth), depth)
+    }
+
+    fn with_delays(delays: impl ExactSizeIterator<Item = usize>, depth: usize) -> Result<Self> {
+        if delays.len() == 0 {
+            return Err(DspError::InvalidArgument("no branches".to_string()).into());
+        }
+        let branches = delays
+            .map(|delay| std::iter::repeat_n(T::default(), delay).collect())
+            .collect();
+        Ok(ConvolutionalInterleaver {
+            branches,
+            depth,
+            next: 0,
+        })
+    }
+
+    /// Symbols from the first in until the first out of an interleaver
+    /// and its deinterleaver together.
+    pub fn end_to_end_delay(&self) -> usize {
+        let branches = self.branches.len();
+        branches * (branches - 1) * self.depth
+    }
+
+    /// Passes one symbol through the next branch.
+    pub fn push(&mut self, symbol: T) -> T {
+        let count = self.branches.len();
+        let branch = &mut self.branches[self.next];
+        self.next = (self.next + 1) % count;
+        branch.push_back(symbol);
+        branch.pop_front().unwrap_or_default()
+    }
+}
+
+impl<T: Clone + Default> Block for ConvolutionalInterleaver<T> {
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        output.extend(input.iter().map(|symbol| self.push(symbol.clone())));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_interleaver_spreads_bursts() {
+        let interleaver = BlockInterleaver::new(3, 4).unwrap();
+        let symbols: Vec<u32> = (0..24).collect();
+        let interleaved = interleaver.interleave(&symbols).unwrap();
+        assert_eq!(interleaved[..12], [0, 4, 8, 1, 5, 9, 2, 6, 10, 3, 7, 11]);
+        assert_eq!(interleaver.deinterleave(&interleaved).unwrap(), symbols);
+        assert!(interleaver.interleave(&symbols[..20]).is_err());
+        assert!(BlockInterleaver::new(0, 4).is_err());
+
+        // A burst of three on the air lands a row apart.
+        let mut hit = interleaved.clone();
+        for symbol in &mut hit[3..6] {
+            *symbol = 99;
+        }
+        let restored = interleaver.deinterleave(&hit).unwrap();
+        let positions: Vec<usize> = (0..24).filter(|&k| restored[k] == 99).collect();
+        assert_eq!(positions, [1, 5, 9]);
+    }
+
+    #[test]
+    fn test_convolutional_interleaver_round_trips() {
+        let (branches, depth) = (4, 3);
+        let mut interleaver = ConvolutionalInterleaver::new(branches, depth).unwrap();
+        let mut deinterleaver = ConvolutionalInterleaver::deinterleaver(branches, depth).unwrap();
+        assert!(ConvolutionalInterleaver::<u32>::new(0, depth).is_err());
+        let delay = interleaver.end_to_end_delay();
+        assert_eq!(delay, 36);
+        assert_eq!(deinterleaver.end_to_end_delay(), delay);
+
+        let symbols: Vec<u32> = (1..=200).collect();
+        let mut interleaved = Vec::new();
+        interleaver.work(&symbols, &mut interleaved);
+        assert_ne!(interleaved[delay..], symbols[..200 - delay]);
+        let mut output = Vec::new();
+        deinterleaver.work(&interleaved, &mut output);
+        assert!(output[..delay].iter().all(|&symbol| symbol == 0));
+        assert_eq!(output[delay..], symbols[..200 - delay]);
+
+        // Neighbours on the air come from symbols a branch's delay apart.
+        let k = interleaved.iter().position(|&s| s == 101).unwrap();
+        assert!(interleaved[k + 1].abs_diff(101) > depth as u32);
+    }
+}
@@ -0,0 +1,204 @@
+//! Manchester and differential Manchester coding.
+//!
+//! Both send each bit as two chips with a transition between them, so the
+//! signal has no DC and carries its own clock. Plain Manchester puts the
+//! bit in the direction of that transition, and the two conventions in use
+//! disagree on which way is a one. Differential Manchester puts it in
+//! whether there is also a transition at the start of the bit, so it comes
+//! through an inverted signal unchanged. A pair of equal chips is a
+//! violation: noise, a lost clock, or a decoder half a bit out of step,
+//! which [`phase`] puts right.
+//!
+//! Inverting a plain Manchester signal inverts every bit, and nothing in
+//! the bits shows it. [`find_sync`] looks for a known sync word both ways
+//! up to settle which.
+
+use crate::error::ProtocolError;
+
+/// Which way round plain Manchester sends its bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Convention {
+    /// IEEE 802.3's: a zero as high then low, and a one as low then high.
+    Ieee,
+    /// G. E. Thomas's original: a one as high then low.
+    Thomas,
+}
+
+impl Convention {
+    /// The first chip of `bit`.
+    fn first(self, bit: bool) -> bool {
+        match self {
+            Convention::Ieee => !bit,
+            Convention::Thomas => bit,
+        }
+    }
+}
+
+/// The chips of `bits`, two to a bit.
+pub fn encode(bits: &[bool], convention: Convention) -> Vec<bool> {
+    bits.iter()
+        .flat_map(|&bit| {
+            let first = convention.first(bit);
+            [first, !first]
+        })
+        .collect()
+}
+
+/// The bits of `chips`, taken in pairs from the first, with any odd chip at
+/// the end left over. Fails at the first pair without a transition.
+pub fn decode(chips: &[bool], convention: Convention) -> Result<Vec<bool>, ProtocolError> {
+    chips
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(k, pair)| match pair[0] != pair[1] {
+            true => Ok(convention.first(true) == pair[0]),
+            false => Err(ProtocolError::CodingViolation(k)),
+        })
+        .collect()
+}
+
+/// The differential Manchester chips of `bits`, after a line that was
+/// last at `previous`. A zero starts with a transition and a one does not.
+pub fn differential_encode(bits: &[bool], previous: bool) -> Vec<bool> {
+    let mut level = previous;
+    let mut chips = Vec::with_capacity(bits.len() * 2);
+    for &bit in bits {
+        let first = if bit { level } else { !level };
+        level = !first;
+        chips.extend([first, level]);
+    }
+    chips
+}
+
+/// The bits of differential Manchester `chips`, after a line that was last
+/// at `previous`. Fails at the first pair without a transition.
+pub fn differential_decode(chips: &[bool], previous: bool) -> Result<Vec<bool>, ProtocolError> {
+    let mut level = previous;
+    chips
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(k, pair)| {
+            if pair[0] == pair[1] {
+                return Err(ProtocolError::CodingViolation(k));
+            }
+            let bit = pair[0] == level;
+            level = pair[1];
+            Ok(bit)
+        })
+        .collect()
+}
+
+/// Which chip, 0 or 1, starts the pairs that break the rules least often,
+/// for either kind of Manchester.
+pub fn phase(chips: &[bool]) -> usize {
+    let violations = |start: usize| {
+        chips[start.min(chips.len())..]
+            .chunks_exact(2)
+            .filter(|pair| pair[0] == pair[1])
+            .count()
+    };
+    usize::from(violations(1) < violations(0))
+}
+
+/// Where a sync word ends in a stream of chips, and whether the stream is
+/// inverted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sync {
+    /// The index of the first chip after the sync word.
+    pub end: usize,
+    pub inverted: bool,
+}
+
+/// The first place `sync`, Manchester coded, appears in `chips` either way
+/// up.
+pub fn find_sync(chips: &[bool], sync: &[bool], convention: Convention) -> Option<Sync> {
+    let pattern = encode(sync, convention);
+    if pattern.is_empty() {
+        return None;
+    }
+    chips
+        .windows(pattern.len())
+        .enumerate()
+        .find_map(|(start, window)| {
+            let inverted = window[0] != pattern[0];
+            let matches = window
+                .iter()
+                .zip(&pattern)
+                .all(|(&c, &p)| (c != p) == inverted);
+            matches.then_some(Sync {
+                end: start + pattern.len(),
+                inverted,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(text: &str) -> Vec<bool> {
+        text.bytes().map(|b| b == b'1').collect()
+    }
+
+    #[test]
+    fn test_round_trips_and_reports_violations() {
+        let data = bits("1101001110");
+        for convention in [Convention::Ieee, Convention::Thomas] {
+            assert_eq!(
+                decode(&encode(&data, convention), convention),
+                Ok(data.clone())
+            );
+        }
+        assert_eq!(encode(&bits("01"), Convention::Ieee), bits("1001"));
+        assert_eq!(encode(&bits("01"), Convention::Thomas), bits("0110"));
+
+        let chips = differential_encode(&data, false);
+        assert_eq!(differential_decode(&chips, false), Ok(data.clone()));
+        // Inverted, only the first bit, judged against the wrong level,
+        // changes.
+        let inverted: Vec<bool> = chips.iter().map(|&c| !c).collect();
+        assert_eq!(
+            differential_decode(&inverted, false).unwrap()[1..],
+            data[1..]
+        );
+        assert_eq!(differential_decode(&inverted, true), Ok(data.clone()));
+
+        let mut broken = encode(&data, Convention::Ieee);
+        broken[7] = broken[6];
+        assert_eq!(
+            decode(&broken, Convention::Ieee),
+            Err(ProtocolError::CodingViolation(3))
+        );
+        assert_eq!(
+            differential_decode(&broken, false),
+            Err(ProtocolError::CodingViolation(3))
+        );
+    }
+
+    #[test]
+    fn test_finds_phase_and_polarity() {
+        let sync = bits("1110010");
+        let data = bits("0011010111");
+        let mut chips = bits("110");
+        chips.extend(encode(&sync, Convention::Ieee));
+        chips.extend(encode(&data, Convention::Ieee));
+        assert_eq!(phase(&chips), 1);
+
+        let inverted: Vec<bool> = chips.iter().map(|&c| !c).collect();
+        let found = find_sync(&inverted, &sync, Convention::Ieee).unwrap();
+        assert_eq!(
+            found,
+            Sync {
+                end: 17,
+                inverted: true
+            }
+        );
+        let rest: Vec<bool> = inverted[found.end..]
+            .iter()
+            .map(|&c| c != found.inverted)
+            .collect();
+        assert_eq!(decode(&rest, Convention::Ieee), Ok(data));
+        assert_eq!(find_sync(&chips[..16], &sync, Convention::Ieee), None);
+    }
+}
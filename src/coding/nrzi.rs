@@ -0,0 +1,125 @@
+//! Non-return-to-zero inverted (NRZI) coding.
+//!
+//! NRZI sends one kind of bit as a change of level and the other as no
+//! change, so a demodulator that cannot tell its tones or phases apart,
+//! only when they change, still recovers the bits. HDLC, and so AX.25 and
+//! AIS, changes on a zero, and leans on bit stuffing to keep long runs of
+//! ones from starving the clock. Some links change on a one instead.
+
+use crate::block::Block;
+
+/// Which bit NRZI sends as a change of level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transition {
+    /// As HDLC, AX.25, AIS and USB do.
+    OnZero,
+    OnOne,
+}
+
+impl Transition {
+    fn changes(self, bit: bool) -> bool {
+        match self {
+            Transition::OnZero => !bit,
+            Transition::OnOne => bit,
+        }
+    }
+}
+
+/// Turns bits into NRZI levels.
+#[derive(Debug, Clone)]
+pub struct NrziEncoder {
+    transition: Transition,
+    level: bool,
+}
+
+impl NrziEncoder {
+    /// Starts from a low line.
+    pub fn new(transition: Transition) -> Self {
+        NrziEncoder {
+            transition,
+            level: false,
+        }
+    }
+
+    /// The level that sends `bit`.
+    pub fn push(&mut self, bit: bool) -> bool {
+        self.level ^= self.transition.changes(bit);
+        self.level
+    }
+}
+
+impl Block for NrziEncoder {
+    type Input = bool;
+    type Output = bool;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<bool>) -> usize {
+        output.extend(input.iter().map(|&bit| self.push(bit)));
+        input.len()
+    }
+}
+
+/// Turns NRZI levels back into bits.
+#[derive(Debug, Clone)]
+pub struct NrziDecoder {
+    transition: Transition,
+    level: bool,
+}
+
+impl NrziDecoder {
+    /// Takes the line as low before the first level, so the first bit is a
+    /// guess on a line that may have started high.
+    pub fn new(transition: Transition) -> Self {
+        NrziDecoder {
+            transition,
+            level: false,
+        }
+    }
+
+    /// The bit that `level` sends.
+    pub fn push(&mut self, level: bool) -> bool {
+        let changed = std::mem::replace(&mut self.level, level) != level;
+        self.transition.changes(true) == changed
+    }
+}
+
+impl Block for NrziDecoder {
+    type Input = bool;
+    type Output = bool;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<bool>) -> usize {
+        output.extend(input.iter().map(|&level| self.push(level)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_either_way_up() {
+        let bits = [true, false, false, true, true, true, false, true, false];
+        let mut levels = Vec::new();
+        NrziEncoder::new(Transition::OnZero).work(&bits, &mut levels);
+        assert_eq!(
+            levels,
+            [false, true, false, false, false, false, true, true, false]
+        );
+
+        for transition in [Transition::OnZero, Transition::OnOne] {
+            let mut levels = Vec::new();
+            NrziEncoder::new(transition).work(&bits, &mut levels);
+            let mut decoded = Vec::new();
+            NrziDecoder::new(transition).work(&levels, &mut decoded);
+            assert_eq!(decoded, bits);
+
+            // Inverting the line loses only the first bit.
+            let inverted: Vec<bool> = levels.iter().map(|&level| !level).collect();
+            let mut decoded = Vec::new();
+            NrziDecoder::new(transition).work(&inverted, &mut decoded);
+            assert_ne!(decoded[0], bits[0]);
+            assert_eq!(decoded[1..], bits[1..]);
+        }
+    }
+}
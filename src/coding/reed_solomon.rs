@@ -0,0 +1,414 @@
+//! Reed-Solomon codes over GF(256).
+//!
+//! A codeword is the data followed by parity bytes chosen so that, read as
+//! a polynomial with the first byte highest, it vanishes at the generator's
+//! roots: consecutive powers, from the first consecutive root, of some
+//! primitive element. With 2t parity bytes the decoder finds and fixes up
+//! to t bytes in error, by the Berlekamp-Massey algorithm, a Chien search
+//! and Forney's formula, and otherwise usually reports the block as past
+//! correcting. Shorter blocks are codes shortened by leading zeros that are
+//! never sent.
+//!
+//! CCSDS, and so most weather and science satellites, sends RS(255, 223)
+//! with field polynomial x^8 + x^7 + x^2 + x + 1, roots from the 112th
+//! power of α^11, and each byte in Berlekamp's dual basis rather than the
+//! conventional one.
+
+use crate::error::{DspError, ProtocolError, Result};
+
+/// Rows of the matrix taking a byte in the conventional basis to
+/// Berlekamp's dual basis, for the CCSDS field.
+const CCSDS_DUAL_BASIS: [u8; 8] = [0x8d, 0xef, 0xec, 0x86, 0xfa, 0x99, 0xaf, 0x7b];
+
+/// Arithmetic in GF(256) through tables of powers and logarithms of α.
+#[derive(Debug, Clone)]
+struct Field {
+    /// α^i for i to 510, so that sums of two logarithms need no reducing.
+    exp: Vec<u8>,
+    /// The logarithm of each nonzero element.
+    log: Vec<usize>,
+}
+
+impl Field {
+    /// The field, unless `polynomial` is not primitive.
+    fn new(polynomial: u16) -> Option<Self> {
+        let mut exp = vec![0; 511];
+        let mut log = vec![0; 256];
+        let mut value: u16 = 1;
+        for (i, power) in exp.iter_mut().enumerate().take(255) {
+            *power = value as u8;
+            log[value as usize] = i;
+            value <<= 1;
+            if value & 0x100 != 0 {
+                value ^= polynomial;
+            }
+        }
+        // Powers of a primitive α reach every nonzero element.
+        if (1..256).any(|byte| exp[log[byte]] != byte as u8) {
+            return None;
+        }
+        for i in 255..511 {
+            exp[i] = exp[i - 255];
+        }
+        Some(Field { exp, log })
+    }
+
+    fn multiply(&self, a: u8, b: u8) -> u8 {
+        match (a, b) {
+            (0, _) | (_, 0) => 0,
+            _ => self.exp[self.log[a as usize] + self.log[b as usize]],
+        }
+    }
+
+    fn divide(&self, a: u8, b: u8) -> u8 {
+        match a {
+            0 => 0,
+            _ => self.exp[self.log[a as usize] + 255 - self.log[b as usize]],
+        }
+    }
+
+    /// α^power, for any power.
+    fn power(&self, power: usize) -> u8 {
+        self.exp[power % 255]
+    }
+
+    /// The polynomial's value at `x`, its first coefficient highest.
+    fn evaluate(&self, polynomial: &[u8], x: u8) -> u8 {
+        polynomial.iter().fold(0, |value, &coefficient| {
+            self.multiply(value, x) ^ coefficient
+        })
+    }
+}
+
+/// A Reed-Solomon code, ready to encode and decode.
+#[derive(Debug, Clone)]
+pub struct ReedSolomon {
+    field: Field,
+    /// The generator without its leading one, highest first.
+    generator: Vec<u8>,
+    first_root: usize,
+    primitive: usize,
+    /// Tables to and from the dual basis, if bytes are sent in it.
+    dual_basis: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl ReedSolomon {
+    /// A code with `parity` bytes whose generator's roots are powers
+    /// `first_root` onwards of α^`primitive`, α being a root of the field
+    /// polynomial. Fails unless the polynomial is primitive of degree
+    /// eight, `primitive` is prime to 255, and there are between 1 and 254
+    /// parity bytes.
+    pub fn new(
+        field_polynomial: u16,
+        first_root: usize,
+        primitive: usize,
+        parity: usize,
+    ) -> Result<Self> {
+        let invalid = |message: String| Err(DspError::InvalidArgument(message).into());
+        if [3, 5, 17].iter().any(|&p| primitive.is_multiple_of(p)) {
+            return invalid(format!("α^{primitive} is not primitive"));
+        }
+        if !(1..255).contains(&parity) {
+            return invalid(format!("{parity} parity bytes"));
+        }
+        let field = (0x100..0x200)
+            .contains(&field_polynomial)
+            .then(|| Field::new(field_polynomial))
+            .flatten();
+        let Some(field) = field else {
+            return invalid(format!(
+                "{field_polynomial:#x} is not a primitive polynomial of degree eight"
+            ));
+        };
+        // The product of (x - root), highest first.
+        let mut generator = vec![1];
+        for j in 0..parity {
+            let root = field.power(primitive * (first_root + j));
+            let mut product = generator.clone();
+            product.push(0);
+            for (k, &coefficient) in generator.iter().enumerate() {
+                product[k + 1] ^= field.multiply(coefficient, root);
+            }
+            generator = product;
+        }
+        generator.remove(0);
+        Ok(ReedSolomon {
+            field,
+            generator,
+            first_root,
+            primitive,
+            dual_basis: None,
+        })
+    }
+
+    /// CCSDS RS(255, 223), with bytes in the conventional basis.
+    pub fn ccsds() -> Self {
+        ReedSolomon::new(0x187, 112, 11, 32).expect("the CCSDS code is valid")
+    }
+
+    /// CCSDS RS(255, 223) as sent, with bytes in the dual basis.
+    pub fn ccsds_dual_basis() -> Self {
+        let to_dual: Vec<u8> = (0..256)
+            .map(|byte| {
+                (0..8)
+                    .filter(|k| byte >> k & 1 == 1)
+                    .fold(0, |dual, k| dual ^ CCSDS_DUAL_BASIS[7 - k])
+            })
+            .collect();
+        let mut from_dual = vec![0; 256];
+        for (byte, &dual) in to_dual.iter().enumerate() {
+            from_dual[dual as usize] = byte as u8;
+        }
+        ReedSolomon {
+            dual_basis: Some((to_dual, from_dual)),
+            ..ReedSolomon::ccsds()
+        }
+    }
+
+    /// Parity bytes per codeword, twice the errors it can correct.
+    pub fn parity_len(&self) -> usize {
+        self.generator.len()
+    }
+
+    /// The most data bytes a codeword can carry.
+    pub fn max_data_len(&self) -> usize {
+        255 - self.parity_len()
+    }
+
+    /// The parity bytes for `data`. Fails if `data` is longer than
+    /// [`max_data_len`](Self::max_data_len).
+    pub fn parity(&self, data: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+        if data.len() > self.max_data_len() {
+            return Err(ProtocolError::InvalidFrame(format!(
+                "{} data bytes",
+                data.len()
+            )));
+        }
+        let mut remainder = vec![0; self.parity_len()];
+        for &byte in data {
+            let feedback = self.conventional(byte) ^ remainder[0];
+            remainder.rotate_left(1);
+            remainder[self.parity_len() - 1] = 0;
+            for (r, &g) in remainder.iter_mut().zip(&self.generator) {
+                *r ^= self.field.multiply(feedback, g);
+            }
+        }
+        Ok(remainder.into_iter().map(|r| self.sent(r)).collect())
+    }
+
+    /// `data` followed by its parity. Fails if `data` is too long, as
+    /// [`parity`](Self::parity) does.
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, ProtocolError> {
+        let mut codeword = data.to_vec();
+        codeword.extend(self.parity(data)?);
+        Ok(codeword)
+    }
+
+    /// Corrects `codeword` in place, data then parity, returning how many
+    /// bytes were wrong. Fails, leaving it untouched, if there are more
+    /// errors than the code corrects and the decoder sees it.
+    pub fn decode(&self, codeword: &mut [u8]) -> Result<usize, ProtocolError> {
+        let n = codeword.len();
+        let parity = self.parity_len();
+        if n <= parity || n > 255 {
+            return Err(ProtocolError::InvalidFrame(format!("{n}-byte codeword")));
+        }
+        let field = &self.field;
+        let received: Vec<u8> = codeword.iter().map(|&b| self.conventional(b)).collect();
+        let syndromes: Vec<u8> = (0..parity)
+            .map(|j| field.evaluate(&received, self.root(j)))
+            .collect();
+        if syndromes.iter().all(|&s| s == 0) {
+            return Ok(0);
+        }
+
+        // Berlekamp-Massey, with polynomials lowest first.
+        let mut locator = vec![1u8];
+        let mut previous = vec![1u8];
+        let mut errors = 0;
+        let mut shift = 1;
+        let mut last_discrepancy = 1u8;
+        for step in 0..parity {
+            let discrepancy = (0..=errors.min(locator.len() - 1)).fold(0, |d, i| {
+                d ^ field.multiply(locator[i], syndromes[step - i])
+            });
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+            let scale = field.divide(discrepancy, last_discrepancy);
+            let mut updated = locator.clone();
+            updated.resize(updated.len().max(previous.len() + shift), 0);
+            for (i, &p) in previous.iter().enumerate() {
+                updated[i + shift] ^= field.multiply(scale, p);
+            }
+            if 2 * errors <= step {
+                previous = std::mem::replace(&mut locator, updated);
+                errors = step + 1 - errors;
+                last_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                locator = updated;
+                shift += 1;
+            }
+        }
+        locator.truncate(errors + 1);
+        if errors > parity / 2 {
+            return Err(ProtocolError::Uncorrectable);
+        }
+
+        // The evaluator, Ω = S Λ mod x^parity, lowest first.
+        let mut evaluator = vec![0u8; parity];
+        for (i, &l) in locator.iter().enumerate() {
+            for (j, &s) in syndromes.iter().enumerate().take(parity - i) {
+                evaluator[i + j] ^= field.multiply(l, s);
+            }
+        }
+        let lowest_first = |polynomial: &[u8], x: u8| {
+            polynomial
+                .iter()
+                .rev()
+                .fold(0, |value, &c| field.multiply(value, x) ^ c)
+        };
+
+        // The Chien search: an error at x^i has locator β^i, β being
+        // α^primitive, and makes Λ vanish at β^-i.
+        let mut corrections = Vec::new();
+        for i in 0..n {
+            let inverse = field.power(255 - self.primitive * i % 255);
+            if lowest_first(&locator, inverse) != 0 {
+                continue;
+            }
+            // Forney: X^(1 - first root) Ω(X^-1) / Λ'(X^-1).
+            let derivative: Vec<u8> = locator
+                .iter()
+                .enumerate()
+                .map(|(k, &l)| if k % 2 == 1 { l } else { 0 })
+                .skip(1)
+                .collect();
+            let denominator = lowest_first(&derivative, inverse);
+            if denominator == 0 {
+                return Err(ProtocolError::Uncorrectable);
+            }
+            let numerator = lowest_first(&evaluator, inverse);
+            let scale = field.power(self.primitive * i % 255 * (256 - self.first_root % 255));
+            let error = field.multiply(scale, field.divide(numerator, denominator));
+            corrections.push((n - 1 - i, error));
+        }
+        if corrections.len() != errors {
+            return Err(ProtocolError::Uncorrectable);
+        }
+        for &(index, error) in &corrections {
+            codeword[index] = self.sent(received[index] ^ error);
+        }
+        Ok(corrections.len())
+    }
+
+    /// The generator's root numbered `j` from zero.
+    fn root(&self, j: usize) -> u8 {
+        self.field.power(self.primitive * (self.first_root + j))
+    }
+
+    /// A byte as sent, in the conventional basis.
+    fn conventional(&self, byte: u8) -> u8 {
+        match &self.dual_basis {
+            Some((_, from_dual)) => from_dual[byte as usize],
+            None => byte,
+        }
+    }
+
+    /// A byte in the conventional basis, as sent.
+    fn sent(&self, byte: u8) -> u8 {
+        match &self.dual_basis {
+            Some((to_dual, _)) => to_dual[byte as usize],
+            None => byte,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(length: usize) -> Vec<u8> {
+        (0..length).map(|k| (k * 37 + k / 7) as u8).collect()
+    }
+
+    /// `count` bytes of `codeword` spread across it, each changed.
+    fn corrupt(codeword: &mut [u8], count: usize) {
+        for k in 0..count {
+            let index = k * codeword.len() / count;
+            codeword[index] ^= 1 + (k as u8).wrapping_mul(29);
+        }
+    }
+
+    #[test]
+    fn test_corrects_up_to_sixteen_errors() {
+        for rs in [ReedSolomon::ccsds(), ReedSolomon::ccsds_dual_basis()] {
+            let data = data(rs.max_data_len());
+            let codeword = rs.encode(&data).unwrap();
+            assert_eq!(codeword.len(), 255);
+            assert!(rs.encode(&[0; 224]).is_err());
+            let mut received = codeword.clone();
+            assert_eq!(rs.decode(&mut received), Ok(0));
+
+            for count in [1, 5, 16] {
+                let mut received = codeword.clone();
+                corrupt(&mut received, count);
+                assert_eq!(rs.decode(&mut received), Ok(count));
+                assert_eq!(received, codeword);
+            }
+            let mut received = codeword.clone();
+            corrupt(&mut received, 17);
+            let before = received.clone();
+            assert_eq!(rs.decode(&mut received), Err(ProtocolError::Uncorrectable));
+            assert_eq!(received, before);
+        }
+    }
+
+    #[test]
+    fn test_shortened_codes_and_bases() {
+        // CCSDS picked its roots in reciprocal pairs, so its generator reads
+        // the same both ways.
+        let mut generator = vec![1];
+        generator.extend(&ReedSolomon::ccsds().generator);
+        assert!(generator.iter().eq(generator.iter().rev()));
+
+        // Every byte has a dual-basis form, and the two codes agree once
+        // it is undone.
+        let dual = ReedSolomon::ccsds_dual_basis();
+        let (to_dual, from_dual) = dual.dual_basis.as_ref().unwrap();
+        assert!((0..=255u8).all(|b| from_dual[to_dual[b as usize] as usize] == b));
+        let data = data(100);
+        let conventional = ReedSolomon::ccsds().encode(&data).unwrap();
+        let sent: Vec<u8> = data.iter().map(|&b| to_dual[b as usize]).collect();
+        let from_sent: Vec<u8> = dual
+            .encode(&sent)
+            .unwrap()
+            .iter()
+            .map(|&b| from_dual[b as usize])
+            .collect();
+        assert_eq!(from_sent, conventional);
+
+        let mut received = conventional.clone();
+        corrupt(&mut received, 16);
+        assert_eq!(ReedSolomon::ccsds().decode(&mut received), Ok(16));
+        assert_eq!(received, conventional);
+
+        // A code with α itself as the primitive element, as DVB uses.
+        let rs = ReedSolomon::new(0x11d, 0, 1, 16).unwrap();
+        let codeword = rs.encode(&data[..50]).unwrap();
+        let mut received = codeword.clone();
+        corrupt(&mut received, 8);
+        assert_eq!(rs.decode(&mut received), Ok(8));
+        assert_eq!(received, codeword);
+        assert!(rs.decode(&mut [0; 10]).is_err());
+
+        // Non-primitive elements and polynomials, and impossible parity.
+        assert!(ReedSolomon::new(0x11d, 0, 3, 16).is_err());
+        assert!(ReedSolomon::new(0x11b, 0, 1, 16).is_err());
+        assert!(ReedSolomon::new(0x1d, 0, 1, 16).is_err());
+        assert!(ReedSolomon::new(0x11d, 0, 1, 0).is_err());
+        assert!(ReedSolomon::new(0x11d, 0, 1, 255).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Additive and multiplicative LFSR scramblers.
+//!
+//! Scrambling whitens long runs of ones or zeros so that the receiver's
+//! clock recovery always has transitions to lock on. Polynomials are
+//! written with bit k for the x^k term, so the G3RUH scrambler of
+//! 9600-baud packet, 1 + x^12 + x^17, is [`G3RUH`]: each output bit is the
+//! input XOR the bits 12 and 17 before it.
+//!
+//! An additive scrambler XORs the data with an LFSR running on its own, so
+//! the same [`AdditiveScrambler`] undoes it, but only from the same seed at
+//! the same bit. A multiplicative one feeds its own output back, so its
+//! descrambler needs no alignment and falls into step after as many bits
+//! as the polynomial's degree, at the cost of turning each line error into
+//! one more per tap.
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+
+/// 1 + x^12 + x^17, the G3RUH scrambler of 9600-baud packet radio.
+pub const G3RUH: u32 = 1 << 17 | 1 << 12 | 1;
+
+/// A shift register of past bits, the latest lowest, with the taps of a
+/// polynomial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Register {
+    taps: u32,
+    mask: u32,
+    state: u32,
+}
+
+impl Register {
+    /// Fails unless the polynomial is of degree 1 to 31 with its x^0 term.
+    fn new(polynomial: u32, seed: u32) -> Result<Self> {
+        if polynomial & 1 == 0 || polynomial == 1 || polynomial >> 31 != 0 {
+            return Err(DspError::InvalidArgument(format!(
+                "polynomial {polynomial:#x} needs degree 1 to 31 and an x^0 term"
+            ))
+            .into());
+        }
+        let degree = 31 - polynomial.leading_zeros();
+        let mask = (1 << degree) - 1;
+        Ok(Register {
+            taps: polynomial >> 1,
+            mask,
+            state: seed & mask,
+        })
+    }
+
+    /// The XOR of the tapped bits.
+    fn feedback(&self) -> bool {
+        (self.state & self.taps).count_ones() % 2 == 1
+    }
+
+    fn shift(&mut self, bit: bool) {
+        self.state = (self.state << 1 | u32::from(bit)) & self.mask;
+    }
+}
+
+/// XORs bits with a free-running LFSR; the same scrambler, from the same
+/// seed, descrambles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdditiveScrambler {
+    register: Register,
+    seed: u32,
+}
+
+impl AdditiveScrambler {
+    /// `seed` holds the register's first bits, the latest lowest, and must
+    /// not be all zeros or the sequence is too. Fails unless the
+    /// polynomial is of degree 1 to 31 with its x^0 term.
+    pub fn new(polynomial: u32, seed: u32) -> Result<Self> {
+        let register = Register::new(polynomial, seed)?;
+        Ok(AdditiveScrambler {
+            register,
+            seed: register.state,
+        })
+    }
+
+    /// Returns to the seed, as at the start of each frame.
+    pub fn reset(&mut self) {
+        self.register.state = self.seed;
+    }
+
+    /// The sequence's next bit.
+    pub fn next_bit(&mut self) -> bool {
+        let bit = self.register.feedback();
+        self.register.shift(bit);
+        bit
+    }
+
+    /// Scrambles or descrambles one bit.
+    pub fn push(&mut self, bit: bool) -> bool {
+        bit ^ self.next_bit()
+    }
+}
+
+impl Block for AdditiveScrambler {
+    type Input = bool;
+    type Output = bool;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<bool>) -> usize {
+        output.extend(input.iter().map(|&bit| self.push(bit)));
+        input.len()
+    }
+}
+
+/// Divides the bits by the polynomial, feeding back its own output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiplicativeScrambler {
+    register: Register,
+}
+
+impl MultiplicativeScrambler {
+    /// Starts from zeros. Fails unless the polynomial is of degree 1 to
+    /// 31 with its x^0 term.
+    pub fn new(polynomial: u32) -> Result<Self> {
+        Ok(MultiplicativeScrambler {
+            register: Register::new(polynomial, 0)?,
+        })
+    }
+
+    pub fn g3ruh() -> Self {
+        MultiplicativeScrambler::new(G3RUH).expect("G3RUH is a valid polynomial")
+    }
+
+    pub fn push(&mut self, bit: bool) -> bool {
+        let out = bit ^ self.register.feedback();
+        self.register.shift(out);
+        out
+    }
+}
+
+impl Block for MultiplicativeScrambler {
+    type Input = bool;
+    type Output = bool;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<bool>) -> usize {
+        output.extend(input.iter().map(|&bit| self.push(bit)));
+        input.len()
+    }
+}
+
+/// Multiplies the bits by the polynomial, undoing a
+/// [`MultiplicativeScrambler`] once it has seen the degree's worth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiplicativeDescrambler {
+    register: Register,
+}
+
+impl MultiplicativeDescrambler {
+    /// Fails unless the polynomial is of degree 1 to 31 with its x^0 term.
+    pub fn new(polynomial: u32) -> Result<Self> {
+        Ok(MultiplicativeDescrambler {
+            register: Register::new(polynomial, 0)?,
+        })
+    }
+
+    pub fn g3ruh() -> Self {
+        MultiplicativeDescrambler::new(G3RUH).expect("G3RUH is a valid polynomial")
+    }
+
+    pub fn push(&mut self, bit: bool) -> bool {
+        let out = bit ^ self.register.feedback();
+        self.register.shift(bit);
+        out
+    }
+}
+
+impl Block for MultiplicativeDescrambler {
+    type Input = bool;
+    type Output = bool;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<bool>) -> usize {
+        output.extend(input.iter().map(|&bit| self.push(bit)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+
+    fn data(length: usize) -> Vec<bool> {
+        real_noise(length, 3).iter().map(|&x| x > 0.0).collect()
+    }
+
+    #[test]
+    fn test_additive_sequence_is_maximal_and_reversible() {
+        // 1 + x^6 + x^7 is primitive, so every nonzero seed repeats after 127.
+        let mut scrambler = AdditiveScrambler::new(1 << 7 | 1 << 6 | 1, 0x7f).unwrap();
+        let sequence: Vec<bool> = (0..254).map(|_| scrambler.next_bit()).collect();
+        assert_eq!(sequence[..127], sequence[127..]);
+        assert_eq!(sequence[..127].iter().filter(|&&bit| bit).count(), 64);
+        assert!((1..127).all(|shift| sequence[shift..shift + 127] != sequence[..127]));
+
+        let data = data(300);
+        let mut scrambler = AdditiveScrambler::new(G3RUH, 1).unwrap();
+        let mut scrambled = Vec::new();
+        scrambler.work(&data, &mut scrambled);
+        assert_ne!(scrambled, data);
+        scrambler.reset();
+        let mut descrambled = Vec::new();
+        scrambler.work(&scrambled, &mut descrambled);
+        assert_eq!(descrambled, data);
+
+        for polynomial in [0, 1, 1 << 7, 1 << 31 | 1] {
+            assert!(
+                AdditiveScrambler::new(polynomial, 1).is_err(),
+                "{polynomial:#x}"
+            );
+        }
+        assert!(MultiplicativeScrambler::new(1 << 7).is_err());
+        assert!(MultiplicativeDescrambler::new(1 << 7).is_err());
+    }
+
+    #[test]
+    fn test_g3ruh_whitens_and_self_synchronises() {
+        let mut scrambler = MultiplicativeScrambler::g3ruh();
+        let ones: Vec<bool> = vec![true; 400];
+        let mut scrambled = Vec::new();
+        scrambler.work(&ones, &mut scrambled);
+        let transitions = scrambled.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(transitions > 100, "{transitions}");
+
+        let data = data(400);
+        let mut scrambled = Vec::new();
+        scrambler.work(&data, &mut scrambled);
+        // Joining 100 bits in, the descrambler is right after 17.
+        let mut descrambler = MultiplicativeDescrambler::g3ruh();
+        let mut descrambled = Vec::new();
+        descrambler.work(&scrambled[100..], &mut descrambled);
+        assert_eq!(descrambled[17..], data[117..]);
+
+        // One line error becomes three.
+        let mut hit = scrambled.clone();
+        hit[200] = !hit[200];
+        let mut descrambler = MultiplicativeDescrambler::g3ruh();
+        let mut descrambled = Vec::new();
+        descrambler.work(&hit, &mut descrambled);
+        let errors: Vec<usize> = (17..400).filter(|&k| descrambled[k] != data[k]).collect();
+        assert_eq!(errors, [200, 212, 217]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Ideal symbol constellations for PSK and QAM modulations.
+
+use num_complex::Complex;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+/// A set of ideal symbol positions. The index of a point is the symbol value
+/// it carries.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Constellation {
+    points: Vec<Complex<f64>>,
+}
+
+impl Constellation {
+    pub fn new(points: Vec<Complex<f64>>) -> Self {
+        Constellation { points }
+    }
+
+    /// Symbols 0 and 1 at +1 and -1.
+    pub fn bpsk() -> Self {
+        Self::new(vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)])
+    }
+
+    /// Gray-coded QPSK with unit average power: bit 0 selects the sign of I
+    /// and bit 1 the sign of Q.
+    pub fn qpsk() -> Self {
+        let a = FRAC_1_SQRT_2;
+        Self::new(vec![
+            Complex::new(a, a),
+            Complex::new(-a, a),
+            Complex::new(a, -a),
+            Complex::new(-a, -a),
+        ])
+    }
+
+    /// 8-PSK on the unit circle, symbol `k` at `k * 45` degrees.
+    pub fn psk8() -> Self {
+        Self::new(
+            (0..8)
+                .map(|k| Complex::from_polar(1.0, PI / 4.0 * k as f64))
+                .collect(),
+        )
+    }
+
+    /// Gray-coded square 16-QAM with unit average power. The upper two bits
+    /// select the I level and the lower two the Q level.
+    pub fn qam16() -> Self {
+        // Gray order of the levels: 00, 01, 11, 10.
+        let level = |bits: usize| [-3.0, -1.0, 3.0, 1.0][bits] / 10f64.sqrt();
+        Self::new(
+            (0..16)
+                .map(|symbol| Complex::new(level(symbol >> 2), level(symbol & 3)))
+                .collect(),
+        )
+    }
+
+    pub fn points(&self) -> &[Complex<f64>] {
+        &self.points
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Mean squared magnitude of the points.
+    pub fn average_power(&self) -> f64 {
+        self.points.iter().map(|p| p.norm_sqr()).sum::<f64>() / self.points.len().max(1) as f64
+    }
+
+    /// The symbol value and position of the point closest to `symbol`.
+    pub fn nearest(&self, symbol: Complex<f64>) -> Option<(usize, Complex<f64>)> {
+        self.points.iter().copied().enumerate().min_by(|a, b| {
+            (a.1 - symbol)
+                .norm_sqr()
+                .total_cmp(&(b.1 - symbol).norm_sqr())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_unit_average_power() {
+        for constellation in [
+            Constellation::bpsk(),
+            Constellation::qpsk(),
+            Constellation::psk8(),
+            Constellation::qam16(),
+        ] {
+            assert_relative_eq!(constellation.average_power(), 1.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_qam16_neighbours_differ_by_one_bit() {
+        let qam = Constellation::qam16();
+        let spacing = 2.0 / 10f64.sqrt();
+        for (a, pa) in qam.points().iter().enumerate() {
+            for (b, pb) in qam.points().iter().enumerate() {
+                if ((pa - pb).norm() - spacing).abs() < 1e-9 {
+                    assert_eq!((a ^ b).count_ones(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let qpsk = Constellation::qpsk();
+        assert_eq!(qpsk.nearest(Complex::new(-0.2, -0.9)).unwrap().0, 3);
+    }
+}
@@ -0,0 +1,15 @@
+//! Decoders for signalling and data protocols carried over the air.
+
+pub mod ax25;
+pub mod ctcss;
+pub mod dcs;
+pub mod flex;
+pub mod ft8;
+pub mod ism;
+pub mod lora;
+pub mod navtex;
+pub mod rtty;
+pub mod sstv;
+pub mod timecode;
+pub mod tpms;
+pub mod wspr;
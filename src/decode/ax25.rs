@@ -0,0 +1,290 @@
+//! AX.25 unnumbered information (UI) frames, as APRS sends them.
+//!
+//! A UI frame is a destination address, a source address, up to eight
+//! digipeaters, a control byte of 0x03, a protocol ID, usually 0xf0 for
+//! no layer 3, and the information field, all inside HDLC. Each address is
+//! seven bytes: six of callsign, upper case and padded with spaces, shifted
+//! left one bit, then one holding the SSID. That last byte's top bit is the
+//! command bit on the destination and source and the has-been-repeated
+//! bit on a digipeater, and its bottom bit marks the final address.
+//!
+//! [`UiFrame::parse`] reads frames from an
+//! [`HdlcDeframer`](crate::coding::hdlc::HdlcDeframer), and
+//! [`UiFrame::to_levels`] makes the NRZI line levels that send one, for an
+//! AFSK or GFSK modulator to key.
+
+use crate::coding::hdlc;
+use crate::coding::nrzi::{NrziEncoder, Transition};
+use crate::error::ProtocolError;
+use std::fmt;
+use std::str::FromStr;
+
+const CONTROL_UI: u8 = 0x03;
+/// The protocol ID for frames with no layer 3 protocol, which APRS uses.
+pub const PID_NO_LAYER3: u8 = 0xf0;
+const MAX_DIGIPEATERS: usize = 8;
+
+/// A station's callsign and secondary station ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Address {
+    callsign: String,
+    ssid: u8,
+}
+
+impl Address {
+    /// Fails unless `callsign` is one to six letters and digits and `ssid`
+    /// is below 16. Lower case is taken as upper.
+    pub fn new(callsign: &str, ssid: u8) -> Result<Self, ProtocolError> {
+        let valid = (1..=6).contains(&callsign.len())
+            && callsign.bytes().all(|b| b.is_ascii_alphanumeric());
+        if !valid || ssid > 15 {
+            return Err(ProtocolError::InvalidFrame(format!(
+                "invalid address {callsign}-{ssid}"
+            )));
+        }
+        Ok(Address {
+            callsign: callsign.to_ascii_uppercase(),
+            ssid,
+        })
+    }
+
+    pub fn callsign(&self) -> &str {
+        &self.callsign
+    }
+
+    pub fn ssid(&self) -> u8 {
+        self.ssid
+    }
+
+    /// The seven bytes of the address, with `flag` as the top bit of the
+    /// last and `last` marking the end of the address field.
+    fn encode(&self, flag: bool, last: bool) -> [u8; 7] {
+        let mut bytes = [b' ' << 1; 7];
+        for (byte, c) in bytes.iter_mut().zip(self.callsign.bytes()) {
+            *byte = c << 1;
+        }
+        bytes[6] = u8::from(flag) << 7 | 0x60 | self.ssid << 1 | u8::from(last);
+        bytes
+    }
+
+    /// The address in `bytes`, with its flag bit and whether it is last.
+    fn decode(bytes: &[u8]) -> Result<(Self, bool, bool), ProtocolError> {
+        let callsign: String = bytes[..6]
+            .iter()
+            .map(|&byte| char::from(byte >> 1))
+            .collect();
+        let address = Address::new(callsign.trim_end(), bytes[6] >> 1 & 0x0f)?;
+        Ok((address, bytes[6] & 0x80 != 0, bytes[6] & 1 != 0))
+    }
+}
+
+/// Leaves off an SSID of zero, as is usual.
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ssid {
+            0 => write!(f, "{}", self.callsign),
+            ssid => write!(f, "{}-{ssid}", self.callsign),
+        }
+    }
+}
+
+/// Reads `N0CALL` or `N0CALL-7`.
+impl FromStr for Address {
+    type Err = ProtocolError;
+
+    fn from_str(text: &str) -> Result<Self, ProtocolError> {
+        let (callsign, ssid) = match text.split_once('-') {
+            None => (text, 0),
+            Some((callsign, ssid)) => {
+                let ssid = ssid
+                    .parse()
+                    .map_err(|_| ProtocolError::InvalidFrame(format!("invalid address {text}")))?;
+                (callsign, ssid)
+            }
+        };
+        Address::new(callsign, ssid)
+    }
+}
+
+/// A digipeater in a frame's path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Digipeater {
+    pub address: Address,
+    /// Whether the frame has already been through it.
+    pub repeated: bool,
+}
+
+/// One UI frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UiFrame {
+    pub destination: Address,
+    pub source: Address,
+    pub path: Vec<Digipeater>,
+    pub pid: u8,
+    pub info: Vec<u8>,
+}
+
+impl UiFrame {
+    /// A frame straight from `source` with no layer 3 protocol, as APRS
+    /// sends.
+    pub fn new(destination: Address, source: Address, info: &[u8]) -> Self {
+        UiFrame {
+            destination,
+            source,
+            path: Vec::new(),
+            pid: PID_NO_LAYER3,
+            info: info.to_vec(),
+        }
+    }
+
+    /// Adds digipeaters not yet passed through, such as `WIDE1-1`.
+    pub fn with_path(mut self, path: &[Address]) -> Self {
+        self.path.extend(path.iter().map(|address| Digipeater {
+            address: address.clone(),
+            repeated: false,
+        }));
+        self
+    }
+
+    /// The frame's bytes, without the check sequence. Fails with more
+    /// than eight digipeaters.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ProtocolError> {
+        if self.path.len() > MAX_DIGIPEATERS {
+            return Err(ProtocolError::InvalidFrame(format!(
+                "{} digipeaters",
+                self.path.len()
+            )));
+        }
+        let mut bytes = Vec::with_capacity(16 + 7 * self.path.len() + self.info.len());
+        // A command frame, in AX.25 version 2.
+        bytes.extend(self.destination.encode(true, false));
+        bytes.extend(self.source.encode(false, self.path.is_empty()));
+        for (k, digipeater) in self.path.iter().enumerate() {
+            let last = k + 1 == self.path.len();
+            bytes.extend(digipeater.address.encode(digipeater.repeated, last));
+        }
+        bytes.extend([CONTROL_UI, self.pid]);
+        bytes.extend(&self.info);
+        Ok(bytes)
+    }
+
+    /// The NRZI line levels that send the frame, after `leading` flags to
+    /// let the receiver settle and before two more.
+    pub fn to_levels(&self, leading: usize) -> Result<Vec<bool>, ProtocolError> {
+        let bits = hdlc::frame(&self.to_bytes()?, leading, 2);
+        let mut nrzi = NrziEncoder::new(Transition::OnZero);
+        Ok(bits.into_iter().map(|bit| nrzi.push(bit)).collect())
+    }
+
+    /// Reads a frame's bytes, without the check sequence. Fails on frames
+    /// other than UI frames.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let invalid = |reason: &str| ProtocolError::InvalidFrame(reason.to_string());
+        let mut addresses = Vec::new();
+        let mut rest = bytes;
+        loop {
+            let Some((address, tail)) = rest.split_first_chunk::<7>() else {
+                return Err(invalid("address field runs off the end"));
+            };
+            let (address, flag, last) = Address::decode(address)?;
+            addresses.push((address, flag));
+            rest = tail;
+            if last {
+                break;
+            }
+            if addresses.len() == 2 + MAX_DIGIPEATERS {
+                return Err(invalid("too many digipeaters"));
+            }
+        }
+        if addresses.len() < 2 {
+            return Err(invalid("no source address"));
+        }
+        let [control, pid, info @ ..] = rest else {
+            return Err(invalid("no control or protocol ID"));
+        };
+        // The poll bit may be set.
+        if control & !0x10 != CONTROL_UI {
+            return Err(invalid("not a UI frame"));
+        }
+        let mut addresses = addresses.into_iter();
+        let (destination, _) = addresses.next().ok_or_else(|| invalid("empty"))?;
+        let (source, _) = addresses.next().ok_or_else(|| invalid("empty"))?;
+        Ok(UiFrame {
+            destination,
+            source,
+            path: addresses
+                .map(|(address, repeated)| Digipeater { address, repeated })
+                .collect(),
+            pid: *pid,
+            info: info.to_vec(),
+        })
+    }
+}
+
+/// The monitor format TNC2 and APRS-IS use, `N0CALL>APRS,WIDE1-1*:info`,
+/// with a star after the digipeaters passed through.
+impl fmt::Display for UiFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}>{}", self.source, self.destination)?;
+        for digipeater in &self.path {
+            let star = if digipeater.repeated { "*" } else { "" };
+            write!(f, ",{}{star}", digipeater.address)?;
+        }
+        write!(f, ":{}", String::from_utf8_lossy(&self.info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::coding::hdlc::HdlcDeframer;
+    use crate::coding::nrzi::NrziDecoder;
+
+    fn address(text: &str) -> Address {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_builds_a_beacon_that_decodes() {
+        let frame = UiFrame::new(
+            address("APRS"),
+            address("n0call-7"),
+            b"!4903.50N/07201.75W-",
+        )
+        .with_path(&[address("WIDE1-1"), address("WIDE2-2")]);
+        let bytes = frame.to_bytes().unwrap();
+        assert_eq!(bytes[..7], [0x82, 0xa0, 0xa4, 0xa6, 0x40, 0x40, 0xe0]);
+        assert_eq!(bytes[7..14], [0x9c, 0x60, 0x86, 0x82, 0x98, 0x98, 0x6e]);
+        assert_eq!(bytes[27], 0x65);
+        assert_eq!(bytes[28..30], [0x03, 0xf0]);
+        assert_eq!(
+            frame.to_string(),
+            "N0CALL-7>APRS,WIDE1-1,WIDE2-2:!4903.50N/07201.75W-"
+        );
+
+        let levels = frame.to_levels(16).unwrap();
+        let mut bits = Vec::new();
+        NrziDecoder::new(Transition::OnZero).work(&levels, &mut bits);
+        let mut deframer = HdlcDeframer::new(15, 330);
+        let mut frames = Vec::new();
+        deframer.work(&bits, &mut frames);
+        assert_eq!(frames.len(), 1);
+        let mut parsed = UiFrame::parse(&frames[0].data).unwrap();
+        assert_eq!(parsed, frame);
+
+        parsed.path[0].repeated = true;
+        let reparsed = UiFrame::parse(&parsed.to_bytes().unwrap()).unwrap();
+        assert!(reparsed.to_string().contains(",WIDE1-1*,WIDE2-2:"));
+
+        assert!(Address::new("TOOLONG", 0).is_err());
+        assert!("N0CALL-16".parse::<Address>().is_err());
+        assert!(UiFrame::parse(&bytes[..20]).is_err());
+        let mut connected = bytes.clone();
+        connected[28] = 0x3f;
+        assert!(UiFrame::parse(&connected).is_err());
+    }
+}
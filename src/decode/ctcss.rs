@@ -0,0 +1,309 @@
+//! Continuous Tone-Coded Squelch System (CTCSS) sub-audible tones.
+//!
+//! Users sharing a repeater channel each add a steady tone between 67 and
+//! 254 Hz to their audio, and a receiver set to one tone stays muted for
+//! everyone else. [`CtcssDetector`] measures all 50 standard tones over a
+//! sliding window with the Goertzel algorithm and reports which, if any, is
+//! present; [`ToneSquelch`] passes audio only while a chosen tone is.
+//!
+//! The closest standard tones are 2.4 Hz apart, so telling them apart needs
+//! a window of about 1 / 2.4 Hz: the default of 0.42 s puts each tone's
+//! neighbours on the first null of its response. Decisions are made every
+//! half window and a change is reported once two in a row agree, so
+//! detection takes about as long as a radio's.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::tone_power;
+use std::collections::VecDeque;
+
+/// The 50 standard CTCSS tones in Hz, from the EIA/TIA-603 table.
+pub const CTCSS_TONES: [f64; 50] = [
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5, 94.8, 97.4, 100.0, 103.5, 107.2,
+    110.9, 114.8, 118.8, 123.0, 127.3, 131.8, 136.5, 141.3, 146.2, 151.4, 156.7, 159.8, 162.2,
+    165.5, 167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5, 203.5,
+    206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+];
+
+/// The standard tone within 0.05 Hz of `frequency`, if there is one.
+pub fn standard_tone(frequency: f64) -> Option<f64> {
+    CTCSS_TONES
+        .iter()
+        .copied()
+        .find(|tone| (tone - frequency).abs() < 0.05)
+}
+
+/// Settings for [`CtcssDetector`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtcssConfig {
+    /// Audio sample rate in Hz.
+    pub sample_rate: f64,
+    /// Seconds of audio each decision looks at.
+    pub window: f64,
+    /// How far the strongest tone must rise above the median of all 50, in
+    /// dB, to count as present. It must also be 6 dB above the next
+    /// strongest.
+    pub margin_db: f64,
+}
+
+impl CtcssConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        CtcssConfig {
+            sample_rate,
+            window: 0.42,
+            margin_db: 10.0,
+        }
+    }
+}
+
+/// A change in the detected tone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToneChange {
+    /// Index in the stream of the sample at which the change was decided.
+    pub sample: u64,
+    /// The tone now present in Hz, or `None` once it has gone.
+    pub tone: Option<f64>,
+}
+
+/// Reports which CTCSS tone, if any, is present in demodulated audio.
+#[derive(Debug, Clone)]
+pub struct CtcssDetector {
+    config: CtcssConfig,
+    length: usize,
+    history: VecDeque<f64>,
+    /// Samples since the last decision.
+    since: usize,
+    /// The previous decision, which the next must match to change `tone`.
+    candidate: Option<Option<f64>>,
+    tone: Option<f64>,
+    position: u64,
+}
+
+impl CtcssDetector {
+    pub fn new(config: CtcssConfig) -> Self {
+        let length = ((config.window * config.sample_rate).round() as usize).max(2);
+        CtcssDetector {
+            config,
+            length,
+            history: VecDeque::with_capacity(length),
+            since: 0,
+            candidate: None,
+            tone: None,
+            position: 0,
+        }
+    }
+
+    pub fn config(&self) -> &CtcssConfig {
+        &self.config
+    }
+
+    /// The tone currently present, in Hz.
+    pub fn tone(&self) -> Option<f64> {
+        self.tone
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.since = 0;
+        self.candidate = None;
+        self.tone = None;
+    }
+
+    /// Accepts one audio sample, returning a change if this sample
+    /// completes one.
+    pub fn push(&mut self, sample: f64) -> Option<ToneChange> {
+        self.position += 1;
+        self.history.push_back(sample);
+        if self.history.len() > self.length {
+            self.history.pop_front();
+        }
+        self.since += 1;
+        if self.history.len() < self.length || self.since < self.length / 2 {
+            return None;
+        }
+        self.since = 0;
+        let decision = self.decide();
+        let confirmed = self.candidate == Some(decision);
+        self.candidate = Some(decision);
+        if !confirmed || decision == self.tone {
+            return None;
+        }
+        self.tone = decision;
+        trace_event!(debug, tone = ?decision, "CTCSS tone changed");
+        Some(ToneChange {
+            sample: self.position - 1,
+            tone: decision,
+        })
+    }
+
+    /// The tone present in the current window, if any.
+    fn decide(&self) -> Option<f64> {
+        let window: Vec<f64> = self.history.iter().copied().collect();
+        let powers: Vec<f64> = CTCSS_TONES
+            .iter()
+            .map(|&tone| tone_power(&window, tone, self.config.sample_rate))
+            .collect();
+        let (best, &strongest) = powers
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+        let runner_up = powers
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| index != best)
+            .map(|(_, &power)| power)
+            .fold(0.0, f64::max);
+        let mut sorted = powers.clone();
+        sorted.sort_by(f64::total_cmp);
+        let median = sorted[sorted.len() / 2];
+        let ratio = 10f64.powf(self.config.margin_db / 10.0);
+        (strongest > 0.0 && strongest > ratio * median && strongest > 4.0 * runner_up)
+            .then_some(CTCSS_TONES[best])
+    }
+}
+
+impl Block for CtcssDetector {
+    type Input = f64;
+    type Output = ToneChange;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<ToneChange>) -> usize {
+        output.extend(input.iter().filter_map(|&sample| self.push(sample)));
+        input.len()
+    }
+
+    /// Accepts `margin_db`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("margin_db", Some(margin)) => self.config.margin_db = margin,
+            ("margin_db", None) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+/// Mutes audio except while one chosen CTCSS tone is present.
+///
+/// Audio passes through undelayed, so the first fraction of a second of
+/// each over is lost while the tone is confirmed, as on a radio. The tone
+/// itself is passed too; follow with a high-pass filter at about 300 Hz to
+/// keep it out of the speaker.
+#[derive(Debug, Clone)]
+pub struct ToneSquelch {
+    detector: CtcssDetector,
+    tone: f64,
+}
+
+impl ToneSquelch {
+    /// A squelch opening on `tone`, which must be one of [`CTCSS_TONES`].
+    pub fn new(config: CtcssConfig, tone: f64) -> Option<Self> {
+        Some(ToneSquelch {
+            detector: CtcssDetector::new(config),
+            tone: standard_tone(tone)?,
+        })
+    }
+
+    pub fn tone(&self) -> f64 {
+        self.tone
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.detector.tone() == Some(self.tone)
+    }
+
+    pub fn detector(&self) -> &CtcssDetector {
+        &self.detector
+    }
+
+    /// Returns `sample` while the squelch is open and silence otherwise.
+    pub fn filter(&mut self, sample: f64) -> f64 {
+        self.detector.push(sample);
+        if self.is_open() {
+            sample
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Block for ToneSquelch {
+    type Input = f64;
+    type Output = f64;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<f64>) -> usize {
+        output.extend(input.iter().map(|&sample| self.filter(sample)));
+        input.len()
+    }
+
+    /// Accepts `tone`, one of the standard tones in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64().and_then(standard_tone)) {
+            ("tone", Some(tone)) => {
+                self.tone = tone;
+                Ok(())
+            }
+            ("tone", None) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use std::f64::consts::PI;
+
+    const RATE: f64 = 8_000.0;
+
+    /// Two seconds of loud voice-band audio over a quiet tone.
+    fn over(tone: f64) -> Vec<f64> {
+        real_noise(16_000, 21)
+            .iter()
+            .enumerate()
+            .map(|(n, noise)| {
+                let t = n as f64 / RATE;
+                0.1 * (2.0 * PI * tone * t).sin()
+                    + 0.5 * (2.0 * PI * 1_000.0 * t).sin()
+                    + 0.2 * noise
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_tone_and_its_end() {
+        for tone in [100.0, 165.5, 167.9, 254.1] {
+            let mut detector = CtcssDetector::new(CtcssConfig::new(RATE));
+            let mut changes = Vec::new();
+            detector.work(&over(tone), &mut changes);
+            assert_eq!(changes.len(), 1, "{tone}: {changes:?}");
+            assert_eq!(changes[0].tone, Some(tone));
+            // Confirmed by the second decision, 0.63 s in.
+            assert!(changes[0].sample < 6_000);
+
+            detector.work(&real_noise(8_000, 5), &mut changes);
+            assert_eq!(changes.last().unwrap().tone, None);
+        }
+    }
+
+    #[test]
+    fn test_squelch_opens_on_its_tone_only() {
+        let audio = over(100.0);
+        let mut matching = ToneSquelch::new(CtcssConfig::new(RATE), 100.0).unwrap();
+        let mut output = Vec::new();
+        matching.work(&audio, &mut output);
+        assert!(matching.is_open());
+        assert!(output[..3_000].iter().all(|&sample| sample == 0.0));
+        assert_eq!(output[15_000..], audio[15_000..]);
+
+        let mut other = ToneSquelch::new(CtcssConfig::new(RATE), 103.5).unwrap();
+        output.clear();
+        other.work(&audio, &mut output);
+        assert!(output.iter().all(|&sample| sample == 0.0));
+        assert!(other
+            .set_parameter("tone", &ParamValue::Float(101.0))
+            .is_err());
+        assert!(ToneSquelch::new(CtcssConfig::new(RATE), 60.0).is_none());
+    }
+}
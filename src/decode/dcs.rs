@@ -0,0 +1,321 @@
+//! Digital-Coded Squelch (DCS).
+//!
+//! The digital counterpart of [CTCSS](super::ctcss): instead of a tone, each
+//! user's audio carries a 23-bit word repeated continuously at 134.4 bit/s
+//! of NRZ data below 300 Hz. The word is a Golay (23, 12) codeword whose
+//! twelve data bits are the three octal digits of the code, sent least
+//! significant bit first, followed by the fixed digit 4. A code can be sent
+//! with either polarity, written `D023N` for normal and `D023I` for
+//! inverted.
+//!
+//! The code is cyclic, and every inverted word is some rotation of another
+//! code's normal word: `D023N` and `D047I` are the same bit stream and no
+//! receiver can tell them apart. Radios treat each such pair as one code,
+//! and so does this module, reporting the normal name and giving the other
+//! through [`DcsCode::equivalent`].
+//!
+//! [`DcsDetector`] low-passes the audio, recovers the bit clock from the
+//! data's zero crossings, and tries every 23-bit window as a codeword,
+//! correcting a single bit error. A code is reported once three words in a
+//! row agree, about half a second, and cleared after two words without it.
+
+use crate::block::Block;
+use crate::dsp::clock::ZeroCrossingClock;
+use crate::dsp::fir::{lowpass, Fir};
+use std::fmt;
+
+/// The bit rate of DCS data.
+pub const DCS_BAUD: f64 = 134.4;
+
+/// The 104 standard DCS codes, written in octal.
+pub const DCS_CODES: [u16; 104] = [
+    0o023, 0o025, 0o026, 0o031, 0o032, 0o036, 0o043, 0o047, 0o051, 0o053, 0o054, 0o065, 0o071,
+    0o072, 0o073, 0o074, 0o114, 0o115, 0o116, 0o122, 0o125, 0o131, 0o132, 0o134, 0o143, 0o145,
+    0o152, 0o155, 0o156, 0o162, 0o165, 0o172, 0o174, 0o205, 0o212, 0o223, 0o225, 0o226, 0o243,
+    0o244, 0o245, 0o246, 0o251, 0o252, 0o255, 0o261, 0o263, 0o265, 0o266, 0o271, 0o274, 0o306,
+    0o311, 0o315, 0o325, 0o331, 0o332, 0o343, 0o346, 0o351, 0o356, 0o364, 0o365, 0o371, 0o411,
+    0o412, 0o413, 0o423, 0o431, 0o432, 0o445, 0o446, 0o452, 0o454, 0o455, 0o462, 0o464, 0o465,
+    0o466, 0o503, 0o506, 0o516, 0o523, 0o526, 0o532, 0o546, 0o565, 0o606, 0o612, 0o624, 0o627,
+    0o631, 0o632, 0o654, 0o662, 0o664, 0o703, 0o712, 0o723, 0o731, 0o732, 0o734, 0o743, 0o754,
+];
+
+/// The Golay code's generator, x^11 + x^9 + x^7 + x^6 + x^5 + x + 1.
+const GENERATOR: u32 = 0xae3;
+const WORD_MASK: u32 = (1 << 23) - 1;
+
+/// The remainder of a 23-bit word divided by the generator; zero for a
+/// codeword.
+fn syndrome(mut word: u32) -> u32 {
+    for bit in (11..23).rev() {
+        if word & (1 << bit) != 0 {
+            word ^= GENERATOR << (bit - 11);
+        }
+    }
+    word
+}
+
+fn rotate(word: u32, shift: u32) -> u32 {
+    (word << shift | word >> (23 - shift)) & WORD_MASK
+}
+
+/// The codeword for `code` as a 23-bit value whose most significant bit is
+/// sent first, or `None` if `code` is not a standard code.
+pub fn dcs_codeword(code: u16) -> Option<u32> {
+    if !DCS_CODES.contains(&code) {
+        return None;
+    }
+    // Bit k of `sent` is the k-th bit on air: the code LSB first, then 0, 0, 1.
+    let sent = u32::from(code) | 1 << 11;
+    let data = (0..12).fold(0, |data, k| data | ((sent >> k) & 1) << (11 - k));
+    Some(data << 11 | syndrome(data << 11))
+}
+
+/// A code and its polarity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DcsCode {
+    /// The code, such as `0o023`.
+    pub code: u16,
+    pub inverted: bool,
+}
+
+impl DcsCode {
+    /// The same bit stream under its other name, such as `D047I` for
+    /// `D023N`, or `None` if `code` is not a standard code.
+    pub fn equivalent(&self) -> Option<DcsCode> {
+        let inverse = !dcs_codeword(self.code)? & WORD_MASK;
+        // Exact matches only, as a corrected one would be a different stream.
+        let code = (0..23)
+            .map(|shift| rotate(inverse, shift))
+            .filter(|&word| syndrome(word) == 0)
+            .find_map(decode_word)?;
+        Some(DcsCode {
+            code: code.code,
+            inverted: !self.inverted,
+        })
+    }
+}
+
+impl fmt::Display for DcsCode {
+    /// Formats as radios label codes, such as `D023N`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let polarity = if self.inverted { 'I' } else { 'N' };
+        write!(f, "D{:03o}{polarity}", self.code)
+    }
+}
+
+/// The code in a 23-bit window taken as a normal codeword, correcting one
+/// bit error, if it holds a standard code. Inverted codes need no separate
+/// search, being normal ones under another name.
+fn decode_word(mut word: u32) -> Option<DcsCode> {
+    let remainder = syndrome(word);
+    if remainder != 0 {
+        let error = (0..23).find(|&bit| syndrome(1 << bit) == remainder)?;
+        word ^= 1 << error;
+    }
+    let data = word >> 11;
+    let sent = (0..12).fold(0, |sent, k| sent | ((data >> (11 - k)) & 1) << k);
+    let code = (sent & 0o777) as u16;
+    (sent >> 9 == 0b100 && DCS_CODES.contains(&code)).then_some(DcsCode {
+        code,
+        inverted: false,
+    })
+}
+
+/// A change in the detected code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DcsChange {
+    /// Index in the stream of the sample at which the change was decided.
+    pub sample: u64,
+    /// The code now present, or `None` once it has gone.
+    pub code: Option<DcsCode>,
+}
+
+/// Reports which DCS code, if any, is present in demodulated audio.
+#[derive(Debug, Clone)]
+pub struct DcsDetector {
+    lowpass: Fir<f64>,
+    clock: ZeroCrossingClock,
+    /// The latest 23 bits, newest in bit 0.
+    register: u32,
+    bits: u64,
+    /// The last code decoded, the bit at which it was, and how many words
+    /// in a row it has been seen.
+    last: Option<(DcsCode, u64)>,
+    streak: usize,
+    code: Option<DcsCode>,
+    position: u64,
+}
+
+impl DcsDetector {
+    /// Words in a row needed to report a code.
+    const CONFIRM: usize = 3;
+
+    pub fn new(sample_rate: f64) -> Self {
+        // Cuts off at 300 Hz, with a transition band about 200 Hz wide.
+        let num_taps = (3.3 * sample_rate / 200.0).ceil() as usize | 1;
+        DcsDetector {
+            lowpass: Fir::new(&lowpass(num_taps, 300.0 / sample_rate)),
+            clock: ZeroCrossingClock::new(sample_rate / DCS_BAUD),
+            register: 0,
+            bits: 0,
+            last: None,
+            streak: 0,
+            code: None,
+            position: 0,
+        }
+    }
+
+    /// The code currently present.
+    pub fn code(&self) -> Option<DcsCode> {
+        self.code
+    }
+
+    /// Accepts one audio sample, returning a change if this sample
+    /// completes one.
+    pub fn push(&mut self, sample: f64) -> Option<DcsChange> {
+        self.position += 1;
+        let level = self.clock.push(self.lowpass.filter(sample))?;
+        self.register = (self.register << 1 | u32::from(level > 0.0)) & WORD_MASK;
+        self.bits += 1;
+        if self.bits < 23 {
+            return None;
+        }
+        let bit = self.bits;
+        match decode_word(self.register) {
+            Some(code) => {
+                self.streak = match self.last {
+                    Some((last, at)) if last == code && bit - at == 23 => self.streak + 1,
+                    Some((last, at)) if last == code && bit - at < 23 => return None,
+                    _ => 1,
+                };
+                self.last = Some((code, bit));
+                if self.streak < Self::CONFIRM || self.code == Some(code) {
+                    return None;
+                }
+                self.change(Some(code))
+            }
+            None => match self.last {
+                Some((_, at)) if self.code.is_some() && bit - at > 2 * 23 => {
+                    self.last = None;
+                    self.streak = 0;
+                    self.change(None)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn change(&mut self, code: Option<DcsCode>) -> Option<DcsChange> {
+        self.code = code;
+        trace_event!(debug, code = ?code, "DCS code changed");
+        Some(DcsChange {
+            sample: self.position - 1,
+            code,
+        })
+    }
+}
+
+impl Block for DcsDetector {
+    type Input = f64;
+    type Output = DcsChange;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<DcsChange>) -> usize {
+        output.extend(input.iter().filter_map(|&sample| self.push(sample)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use std::f64::consts::PI;
+
+    const RATE: f64 = 8_000.0;
+
+    /// `seconds` of voice-band audio over DCS data for `code`.
+    fn over(code: u16, inverted: bool, seconds: f64) -> Vec<f64> {
+        let word = dcs_codeword(code).unwrap();
+        let polarity = if inverted { -1.0 } else { 1.0 };
+        let length = (seconds * RATE) as usize;
+        real_noise(length, 31)
+            .iter()
+            .enumerate()
+            .map(|(n, noise)| {
+                let t = n as f64 / RATE;
+                let bit = (t * DCS_BAUD) as usize % 23;
+                let level = if word >> (22 - bit) & 1 == 1 {
+                    0.15
+                } else {
+                    -0.15
+                };
+                polarity * level + 0.5 * (2.0 * PI * 1_000.0 * t).sin() + 0.1 * noise
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_codewords_do_not_alias() {
+        for &code in &DCS_CODES {
+            let word = dcs_codeword(code).unwrap();
+            assert_eq!(syndrome(word), 0);
+            assert_eq!(
+                decode_word(word ^ 1 << 7),
+                Some(DcsCode {
+                    code,
+                    inverted: false
+                })
+            );
+            // No rotation of one code's word reads as another code.
+            for shift in 1..23 {
+                let rotated = rotate(word, shift);
+                assert_eq!(syndrome(rotated), 0);
+                assert_eq!(decode_word(rotated), None, "{code:o} rotated by {shift}");
+            }
+            // Each code has exactly one inverted alias, which maps back.
+            let normal = DcsCode {
+                code,
+                inverted: false,
+            };
+            let alias = normal.equivalent().unwrap();
+            assert!(alias.inverted);
+            assert_eq!(alias.equivalent(), Some(normal));
+        }
+        let d023 = DcsCode {
+            code: 0o023,
+            inverted: false,
+        };
+        assert_eq!(d023.equivalent().unwrap().to_string(), "D047I");
+        assert_eq!(dcs_codeword(0o024), None);
+    }
+
+    #[test]
+    fn test_detects_code_and_its_end() {
+        // D754I is reported by its normal name, D116N.
+        for (code, inverted, expected) in [
+            (0o023, false, 0o023),
+            (0o754, true, 0o116),
+            (0o411, false, 0o411),
+        ] {
+            let mut detector = DcsDetector::new(RATE);
+            let mut changes = Vec::new();
+            detector.work(&over(code, inverted, 2.0), &mut changes);
+            let expected = DcsCode {
+                code: expected,
+                inverted: false,
+            };
+            assert_eq!(changes.len(), 1, "{changes:?}");
+            assert_eq!(changes[0].code, Some(expected));
+            detector.work(&real_noise(8_000, 2), &mut changes);
+            assert_eq!(changes.last().unwrap().code, None);
+        }
+        let label = DcsCode {
+            code: 0o754,
+            inverted: true,
+        };
+        assert_eq!(label.to_string(), "D754I");
+        assert_eq!(label.equivalent().unwrap().code, 0o116);
+    }
+}
@@ -0,0 +1,666 @@
+//! FLEX, the paging protocol that followed POCSAG.
+//!
+//! A FLEX channel carries 128 frames of 1.875 s in each four-minute cycle.
+//! Every frame opens at 1600 bit/s with two-level FSK of ±4.8 kHz: bit
+//! sync, a 64-bit sync word whose outer 16 bits name the mode of the rest of
+//! the frame, and a frame information word giving the cycle and frame
+//! number. After a second sync of 25 ms, eleven blocks of data follow at
+//! 1600 or 3200 baud, with two or four levels. Each bit of data belongs to
+//! one of up to four phases, A to D, each an independent stream of 88
+//! words. Every word is a BCH (31, 21) codeword with a parity bit, its bits
+//! interleaved eight words at a time against fades.
+//!
+//! A phase opens with a block information word locating its address and
+//! vector fields. Each address names a pager, and the vector word beside it
+//! says what kind of page it is and which words hold the message.
+//!
+//! [`FlexDecoder`] takes complex baseband centred on the channel. It
+//! measures the instantaneous frequency, recovers a clock at 3200 Hz from
+//! the transitions, which serves both baud rates, and reads every phase of
+//! every frame it finds. It decodes short addresses, alphanumeric,
+//! numeric and tone-only pages, and reports the rest by type; long
+//! addresses and messages split across frames are left alone.
+
+mod bch;
+
+use crate::block::Block;
+use crate::dsp::clock::ZeroCrossingClock;
+use crate::dsp::fir::{lowpass, Fir};
+use crate::dsp::fm::InstantaneousFrequency;
+use bch::Bch;
+use num_complex::Complex;
+
+/// The length of a frame in seconds.
+pub const FLEX_FRAME: f64 = 1.875;
+/// The deviation of the outer tones, in Hz.
+pub const FLEX_DEVIATION: f64 = 4_800.0;
+
+/// The sync word's middle 32 bits, the same in every mode.
+const MARKER: u32 = 0xa6c6_aaaa;
+/// Words in each phase of a frame.
+const WORDS: usize = 88;
+/// The clock rate, twice the slower baud rate.
+const TICK_RATE: f64 = 3_200.0;
+/// Ticks of the second sync, 25 ms.
+const SYNC2_TICKS: usize = 80;
+/// Digits of numeric pages, by their four-bit codes.
+const DIGITS: &[u8; 16] = b"0123456789 U -][";
+/// The numeric digit that pads a message.
+const NUMERIC_FILL: u32 = 0xc;
+
+/// The speed and modulation of a frame's data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlexMode {
+    /// 1600 bit/s: 1600 baud, two levels, phase A.
+    TwoLevel1600,
+    /// 3200 bit/s: 1600 baud, four levels, phases A and B.
+    FourLevel1600,
+    /// 3200 bit/s: 3200 baud, two levels, phases A and C.
+    TwoLevel3200,
+    /// 6400 bit/s: 3200 baud, four levels, phases A to D.
+    FourLevel3200,
+}
+
+impl FlexMode {
+    const ALL: [FlexMode; 4] = [
+        FlexMode::TwoLevel1600,
+        FlexMode::FourLevel1600,
+        FlexMode::TwoLevel3200,
+        FlexMode::FourLevel3200,
+    ];
+
+    /// The sync word's outer 16 bits in this mode.
+    fn code(self) -> u16 {
+        match self {
+            FlexMode::TwoLevel1600 => 0x870c,
+            FlexMode::FourLevel1600 => 0xb068,
+            FlexMode::TwoLevel3200 => 0x7b18,
+            FlexMode::FourLevel3200 => 0xdea0,
+        }
+    }
+
+    pub fn baud(self) -> f64 {
+        match self {
+            FlexMode::TwoLevel1600 | FlexMode::FourLevel1600 => 1_600.0,
+            FlexMode::TwoLevel3200 | FlexMode::FourLevel3200 => 3_200.0,
+        }
+    }
+
+    pub fn levels(self) -> usize {
+        match self {
+            FlexMode::TwoLevel1600 | FlexMode::TwoLevel3200 => 2,
+            FlexMode::FourLevel1600 | FlexMode::FourLevel3200 => 4,
+        }
+    }
+
+    pub fn bits_per_second(self) -> f64 {
+        self.baud() * (self.levels() as f64).log2()
+    }
+
+    /// The phases carried, in the order each symbol's bits go to them: a
+    /// four-level symbol's sign bit to the first and its inner bit to the
+    /// second, and at 3200 baud alternate symbols to the first two and the
+    /// last two.
+    pub fn phases(self) -> &'static [char] {
+        match self {
+            FlexMode::TwoLevel1600 => &['A'],
+            FlexMode::FourLevel1600 => &['A', 'B'],
+            FlexMode::TwoLevel3200 => &['A', 'C'],
+            FlexMode::FourLevel3200 => &['A', 'B', 'C', 'D'],
+        }
+    }
+}
+
+/// What a page carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlexContent {
+    /// An alert with no message.
+    Tone,
+    Numeric(String),
+    Alphanumeric(String),
+    /// A page of another type, by its vector type code: secure, instruction,
+    /// special numeric, binary or numbered numeric.
+    Other(u8),
+}
+
+/// A page decoded from a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlexPage {
+    /// Index in the stream of the sample at which its frame ended.
+    pub sample: u64,
+    pub mode: FlexMode,
+    /// The cycle, from 0 to 14, and the frame within it, from 0 to 127.
+    pub cycle: u8,
+    pub frame: u8,
+    pub phase: char,
+    /// The pager's address.
+    pub capcode: u32,
+    pub content: FlexContent,
+}
+
+/// Whether an information word's four-bit checksum, its low bits, brings
+/// the sum of its nibbles and its top bit to 15.
+fn checksum_ok(data: u32) -> bool {
+    let nibbles: u32 = (0..5).map(|k| data >> (4 * k) & 0xf).sum();
+    (nibbles + (data >> 20)) & 0xf == 0xf
+}
+
+/// Where a frame is in being read.
+#[derive(Debug, Clone)]
+enum State {
+    /// Looking for a sync word.
+    Hunting,
+    /// Reading the frame information word bit by bit.
+    Fiw {
+        mode: FlexMode,
+        bits: u32,
+        word: u32,
+    },
+    /// Waiting out the second sync.
+    Sync2 {
+        mode: FlexMode,
+        fiw: u32,
+        ticks: usize,
+    },
+    Data {
+        mode: FlexMode,
+        fiw: u32,
+        /// Symbols received so far.
+        symbols: usize,
+        phases: Vec<[u32; WORDS]>,
+    },
+}
+
+/// Decodes FLEX pages from a paging channel's complex baseband.
+#[derive(Debug, Clone)]
+pub struct FlexDecoder {
+    sample_rate: f64,
+    channel: Fir<Complex<f64>>,
+    frequency: InstantaneousFrequency,
+    smoothing: Fir<f64>,
+    clock: ZeroCrossingClock,
+    bch: Bch,
+    /// Ticks of the clock so far, and the last tick's level.
+    ticks: u64,
+    previous: f64,
+    /// The latest 64 bits at 1600 bit/s, newest lowest, from pairs of ticks
+    /// ending on even and odd ticks.
+    registers: [u64; 2],
+    /// The average magnitude of each register's pairs.
+    openness: [f64; 2],
+    /// Which pairs of ticks make up the frame's 1600-baud symbols.
+    parity: usize,
+    /// The outer tones' deviation, as measured over the sync.
+    deviation: f64,
+    state: State,
+    position: u64,
+}
+
+impl FlexDecoder {
+    /// Bits that may differ from a sync word's marker, and from its code.
+    const SYNC_ERRORS: u32 = 3;
+
+    pub fn new(sample_rate: f64) -> Self {
+        // The channel filter passes the outer tones with the sidebands of
+        // 3200 baud, and the smoothing after the discriminator keeps to
+        // what a 3200-baud symbol needs.
+        let channel_taps = (3.3 * sample_rate / 4_000.0).ceil() as usize | 1;
+        let smoothing_taps = (3.3 * sample_rate / 3_200.0).ceil() as usize | 1;
+        FlexDecoder {
+            sample_rate,
+            channel: Fir::new(&lowpass(channel_taps, 8_000.0 / sample_rate)),
+            frequency: InstantaneousFrequency::new(sample_rate),
+            smoothing: Fir::new(&lowpass(smoothing_taps, 3_200.0 / sample_rate)),
+            clock: ZeroCrossingClock::new(sample_rate / TICK_RATE),
+            bch: Bch::new(),
+            ticks: 0,
+            previous: 0.0,
+            registers: [0; 2],
+            openness: [0.0; 2],
+            parity: 0,
+            deviation: FLEX_DEVIATION,
+            state: State::Hunting,
+            position: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Whether a frame is being read.
+    pub fn in_frame(&self) -> bool {
+        !matches!(self.state, State::Hunting)
+    }
+
+    /// Accepts one sample, adding to `pages` any this sample completes.
+    pub fn push(&mut self, sample: Complex<f64>, pages: &mut Vec<FlexPage>) {
+        self.position += 1;
+        let frequency = self.frequency.frequency(self.channel.filter(sample));
+        let Some(level) = self.clock.push(self.smoothing.filter(frequency)) else {
+            return;
+        };
+        let tick = self.ticks;
+        self.ticks += 1;
+        let previous = std::mem::replace(&mut self.previous, level);
+        // A 1600-baud symbol's level, if this tick ends one.
+        let sym1600 = ((tick as usize) % 2 == self.parity).then_some((previous + level) / 2.0);
+        match &mut self.state {
+            State::Hunting => {
+                self.deviation += 0.02 * (level.abs() - self.deviation);
+                let parity = tick as usize % 2;
+                let register = &mut self.registers[parity];
+                *register = *register << 1 | u64::from(previous + level > 0.0);
+                let openness = &mut self.openness[parity];
+                *openness += 0.05 * ((previous + level).abs() - *openness);
+                // Pairs straddling symbols can pass for the sync word too,
+                // but are weaker where the bits change.
+                if self.openness[parity] < self.openness[1 - parity] {
+                    return;
+                }
+                if let Some(mode) = self.sync(parity) {
+                    trace_event!(debug, ?mode, "FLEX sync");
+                    self.parity = parity;
+                    self.state = State::Fiw {
+                        mode,
+                        bits: 0,
+                        word: 0,
+                    };
+                }
+            }
+            State::Fiw { mode, bits, word } => {
+                let Some(level) = sym1600 else {
+                    return;
+                };
+                *word |= u32::from(level > 0.0) << *bits;
+                *bits += 1;
+                if *bits < 32 {
+                    return;
+                }
+                let mode = *mode;
+                self.state = match self.bch.decode(*word).filter(|&fiw| checksum_ok(fiw)) {
+                    Some(fiw) => State::Sync2 {
+                        mode,
+                        fiw,
+                        ticks: 0,
+                    },
+                    None => State::Hunting,
+                };
+            }
+            State::Sync2 { mode, fiw, ticks } => {
+                *ticks += 1;
+                if *ticks == SYNC2_TICKS {
+                    self.state = State::Data {
+                        mode: *mode,
+                        fiw: *fiw,
+                        symbols: 0,
+                        phases: vec![[0; WORDS]; mode.phases().len()],
+                    };
+                }
+            }
+            State::Data {
+                mode,
+                fiw,
+                symbols,
+                phases,
+            } => {
+                let level = match mode.baud() == TICK_RATE {
+                    true => level,
+                    false => match sym1600 {
+                        Some(level) => level,
+                        None => return,
+                    },
+                };
+                // The symbol's bits: its sign, and for four levels whether
+                // it is an inner tone.
+                let mut bits = vec![level > 0.0];
+                if mode.levels() == 4 {
+                    bits.push(level.abs() < 2.0 / 3.0 * self.deviation);
+                }
+                let per_phase = match mode.baud() == TICK_RATE {
+                    true => *symbols / 2,
+                    false => *symbols,
+                };
+                let first = match mode.baud() == TICK_RATE {
+                    true => *symbols % 2 * bits.len(),
+                    false => 0,
+                };
+                // Each block sends the first bits of its eight words, then
+                // their second bits, and so on.
+                let word = per_phase / 256 * 8 + per_phase % 8;
+                let bit = per_phase / 8 % 32;
+                for (k, value) in bits.into_iter().enumerate() {
+                    phases[first + k][word] |= u32::from(value) << bit;
+                }
+                *symbols += 1;
+                if *symbols < WORDS * 32 * phases.len() / mode.levels().ilog2() as usize {
+                    return;
+                }
+                let (mode, fiw) = (*mode, *fiw);
+                let phases = std::mem::take(phases);
+                self.state = State::Hunting;
+                self.registers = [0; 2];
+                for (words, &phase) in phases.iter().zip(mode.phases()) {
+                    self.phase(mode, fiw, phase, words, pages);
+                }
+            }
+        }
+    }
+
+    /// The mode, if the register of pairs ending on `parity` holds a sync
+    /// word.
+    fn sync(&self, parity: usize) -> Option<FlexMode> {
+        let register = self.registers[parity];
+        let marker = (register >> 16) as u32;
+        if (marker ^ MARKER).count_ones() > Self::SYNC_ERRORS {
+            return None;
+        }
+        let (high, low) = ((register >> 48) as u16, !(register as u16));
+        FlexMode::ALL.into_iter().find(|mode| {
+            (high ^ mode.code()).count_ones() + (low ^ mode.code()).count_ones()
+                <= Self::SYNC_ERRORS
+        })
+    }
+
+    /// Reads the pages in one phase of a frame.
+    fn phase(
+        &self,
+        mode: FlexMode,
+        fiw: u32,
+        phase: char,
+        words: &[u32; WORDS],
+        pages: &mut Vec<FlexPage>,
+    ) {
+        let word = |k: usize| words.get(k).and_then(|&word| self.bch.decode(word));
+        let Some(biw) = word(0).filter(|&biw| checksum_ok(biw)) else {
+            return;
+        };
+        let addresses = (biw >> 8 & 0x3) as usize + 1;
+        let vectors = (biw >> 10 & 0x3f) as usize;
+        for (address, vector) in (addresses..vectors).zip(vectors..) {
+            let (Some(address), Some(vector)) = (word(address), word(vector)) else {
+                continue;
+            };
+            // Short addresses only; the rest of the space is long ones.
+            if !(0x8001..=0x1e_0000).contains(&address) {
+                continue;
+            }
+            let start = (vector >> 7 & 0x7f) as usize;
+            let length = (vector >> 14 & 0x7f) as usize;
+            let message = (start..start + length).map(word);
+            let content = match vector >> 4 & 0x7 {
+                2 => FlexContent::Tone,
+                3 => match message.collect::<Option<Vec<_>>>() {
+                    Some(message) => FlexContent::Numeric(numeric(&message)),
+                    None => continue,
+                },
+                5 => match message.collect::<Option<Vec<_>>>() {
+                    Some(message) if !message.is_empty() => {
+                        FlexContent::Alphanumeric(alphanumeric(&message[1..]))
+                    }
+                    _ => continue,
+                },
+                other => FlexContent::Other(other as u8),
+            };
+            pages.push(FlexPage {
+                sample: self.position - 1,
+                mode,
+                cycle: (fiw >> 4 & 0xf) as u8,
+                frame: (fiw >> 8 & 0x7f) as u8,
+                phase,
+                capcode: address - 0x8000,
+                content,
+            });
+        }
+    }
+}
+
+/// The digits of a numeric message, four bits each from the third bit of
+/// its first word, dropping the fill.
+fn numeric(words: &[u32]) -> String {
+    let bits: Vec<u32> = words
+        .iter()
+        .flat_map(|&word| (0..21).map(move |k| word >> k & 1))
+        .skip(2)
+        .collect();
+    bits.chunks_exact(4)
+        .map(|bits| (0..4).fold(0, |digit, k| digit | bits[k] << k))
+        .filter(|&digit| digit != NUMERIC_FILL)
+        .map(|digit| char::from(DIGITS[digit as usize]))
+        .collect()
+}
+
+/// The text of an alphanumeric message after its header word, three
+/// seven-bit characters to a word, lowest first, dropping the ETX and NUL
+/// that pad it.
+fn alphanumeric(words: &[u32]) -> String {
+    words
+        .iter()
+        .flat_map(|&word| (0..3).map(move |k| (word >> (7 * k) & 0x7f) as u8))
+        .filter(|&c| c != 0x03 && c != 0)
+        .map(char::from)
+        .collect()
+}
+
+impl Block for FlexDecoder {
+    type Input = Complex<f64>;
+    type Output = FlexPage;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<FlexPage>) -> usize {
+        for &sample in input {
+            self.push(sample, output);
+        }
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use std::f64::consts::TAU;
+
+    const RATE: f64 = 38_400.0;
+
+    /// Sets an information word's checksum.
+    fn with_checksum(data: u32) -> u32 {
+        let data = data & !0xf;
+        let sum: u32 = (1..5).map(|k| data >> (4 * k) & 0xf).sum::<u32>() + (data >> 20);
+        data | (0xf - sum % 16) & 0xf
+    }
+
+    /// A page to send: capcode, vector type and message words.
+    type Page = (u32, u32, Vec<u32>);
+
+    /// The 88 words of a phase carrying `pages`, before encoding.
+    fn phase_words(pages: &[Page]) -> [u32; WORDS] {
+        let mut words = [0x1f_ffff; WORDS];
+        let vectors = 1 + pages.len();
+        words[0] = with_checksum((vectors as u32) << 10);
+        let mut next = vectors + pages.len();
+        for (k, (capcode, kind, message)) in pages.iter().enumerate() {
+            words[1 + k] = capcode + 0x8000;
+            words[vectors + k] = (message.len() as u32) << 14 | (next as u32) << 7 | kind << 4;
+            words[next..next + message.len()].copy_from_slice(message);
+            next += message.len();
+        }
+        words
+    }
+
+    fn alphanumeric_words(text: &str) -> Vec<u32> {
+        let mut chars: Vec<u32> = text.bytes().map(u32::from).collect();
+        chars.resize(chars.len().div_ceil(3) * 3, 0x03);
+        let mut words = vec![0x1800];
+        words.extend(chars.chunks(3).map(|c| c[0] | c[1] << 7 | c[2] << 14));
+        words
+    }
+
+    fn numeric_words(digits: &str) -> Vec<u32> {
+        let mut bits = vec![0, 0];
+        for c in digits.bytes() {
+            let digit = DIGITS.iter().position(|&d| d == c).unwrap() as u32;
+            bits.extend((0..4).map(|k| digit >> k & 1));
+        }
+        while bits.len() % 21 != 0 {
+            bits.extend((0..4).map(|k| NUMERIC_FILL >> k & 1));
+        }
+        bits.chunks(21)
+            .map(|bits| (0..21).fold(0, |word, k| word | bits[k] << k))
+            .collect()
+    }
+
+    /// Frequencies, one per tick, for a frame in `mode` carrying the words
+    /// of each of its phases.
+    fn frame(mode: FlexMode, cycle: u32, number: u32, phases: &[[u32; WORDS]]) -> Vec<f64> {
+        let mut symbols = Vec::new();
+        let mut two_level = |bits: &mut dyn Iterator<Item = bool>| {
+            for bit in bits {
+                let frequency = if bit { FLEX_DEVIATION } else { -FLEX_DEVIATION };
+                symbols.extend([frequency; 2]);
+            }
+        };
+        let sync = u64::from(mode.code()) << 48 | u64::from(MARKER) << 16 | u64::from(!mode.code());
+        let fiw = bch::encode(with_checksum(number << 8 | cycle << 4));
+        two_level(&mut (0..48).map(|k| k % 2 == 0));
+        two_level(&mut (0..64).rev().map(|k| sync >> k & 1 == 1));
+        two_level(&mut (0..32).map(|k| fiw >> k & 1 == 1));
+        symbols.extend((0..SYNC2_TICKS).map(|k| if k / 2 % 2 == 0 { 4_800.0 } else { -4_800.0 }));
+        let encoded: Vec<[u32; WORDS]> =
+            phases.iter().map(|words| words.map(bch::encode)).collect();
+        // Each phase's bits in the order sent.
+        let bit = |phase: usize, n: usize| {
+            let word = n / 256 * 8 + n % 8;
+            encoded[phase][word] >> (n / 8 % 32) & 1 == 1
+        };
+        let level = |bits: &[bool]| match bits {
+            [sign] => {
+                if *sign {
+                    4_800.0
+                } else {
+                    -4_800.0
+                }
+            }
+            [sign, inner] => match (sign, inner) {
+                (true, false) => 4_800.0,
+                (true, true) => 1_600.0,
+                (false, true) => -1_600.0,
+                (false, false) => -4_800.0,
+            },
+            _ => unreachable!(),
+        };
+        let per_symbol = (mode.levels() as f64).log2() as usize;
+        for n in 0..WORDS * 32 {
+            match mode.baud() == TICK_RATE {
+                true => {
+                    for group in 0..2 {
+                        let bits: Vec<bool> = (0..per_symbol)
+                            .map(|k| bit(group * per_symbol + k, n))
+                            .collect();
+                        symbols.push(level(&bits));
+                    }
+                }
+                false => {
+                    let bits: Vec<bool> = (0..per_symbol).map(|k| bit(k, n)).collect();
+                    symbols.extend([level(&bits); 2]);
+                }
+            }
+        }
+        symbols
+    }
+
+    /// FM baseband of `frequencies`, one per tick, with noise.
+    fn modulate(frequencies: &[f64], noise: f64, seed: u64) -> Vec<Complex<f64>> {
+        let per_tick = (RATE / TICK_RATE) as usize;
+        let mut phase = 0.0;
+        let mut samples = Vec::new();
+        for &frequency in [0.0; 64].iter().chain(frequencies).chain(&[0.0; 64]) {
+            for _ in 0..per_tick {
+                samples.push(Complex::from_polar(1.0, phase));
+                phase = (phase + TAU * frequency / RATE) % TAU;
+            }
+        }
+        let i = real_noise(samples.len(), seed);
+        let q = real_noise(samples.len(), seed + 1);
+        for ((sample, i), q) in samples.iter_mut().zip(i).zip(q) {
+            *sample += noise * Complex::new(i, q);
+        }
+        samples
+    }
+
+    #[test]
+    fn test_decodes_pages_at_1600_with_bit_errors() {
+        let words = phase_words(&[
+            (1_234_567, 5, alphanumeric_words("MEET AT GATE 7")),
+            (100_000, 3, numeric_words("555-1234")),
+            (42, 2, Vec::new()),
+        ]);
+        let mut frequencies = frame(FlexMode::TwoLevel1600, 3, 77, &[words]);
+        // A burst of eight wrong symbols early in the data, which
+        // interleaving spreads as one error in each of the first block's
+        // words.
+        let burst = 144 * 2 + SYNC2_TICKS + 200;
+        for f in &mut frequencies[burst..burst + 16] {
+            *f = -*f;
+        }
+        let samples = modulate(&frequencies, 0.3, 1);
+        let mut decoder = FlexDecoder::new(RATE);
+        let mut pages = Vec::new();
+        decoder.work(&samples, &mut pages);
+        assert_eq!(pages.len(), 3, "{pages:?}");
+        assert!(pages
+            .iter()
+            .all(|page| (page.cycle, page.frame, page.phase) == (3, 77, 'A')));
+        assert_eq!(pages[0].capcode, 1_234_567);
+        assert_eq!(
+            pages[0].content,
+            FlexContent::Alphanumeric("MEET AT GATE 7".into())
+        );
+        assert_eq!(pages[1].capcode, 100_000);
+        assert_eq!(pages[1].content, FlexContent::Numeric("555-1234".into()));
+        assert_eq!(
+            (pages[2].capcode, &pages[2].content),
+            (42, &FlexContent::Tone)
+        );
+        assert!(!decoder.in_frame());
+    }
+
+    #[test]
+    fn test_decodes_every_phase_at_each_speed() {
+        for mode in FlexMode::ALL {
+            let phases: Vec<[u32; WORDS]> = mode
+                .phases()
+                .iter()
+                .enumerate()
+                .map(|(k, phase)| {
+                    let text = format!("PHASE {phase}");
+                    phase_words(&[(1000 + k as u32, 5, alphanumeric_words(&text))])
+                })
+                .collect();
+            let samples = modulate(&frame(mode, 0, 5, &phases), 0.05, 3);
+            let mut decoder = FlexDecoder::new(RATE);
+            let mut pages = Vec::new();
+            decoder.work(&samples, &mut pages);
+            let found: Vec<_> = pages
+                .iter()
+                .map(|page| (page.phase, page.capcode, page.mode))
+                .collect();
+            let expected: Vec<_> = mode
+                .phases()
+                .iter()
+                .enumerate()
+                .map(|(k, &phase)| (phase, 1000 + k as u32, mode))
+                .collect();
+            assert_eq!(found, expected, "{mode:?}");
+            for page in &pages {
+                assert_eq!(
+                    page.content,
+                    FlexContent::Alphanumeric(format!("PHASE {}", page.phase))
+                );
+            }
+            assert_eq!(mode.bits_per_second(), mode.phases().len() as f64 * 1_600.0);
+        }
+    }
+}
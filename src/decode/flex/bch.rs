@@ -0,0 +1,95 @@
+//! FLEX's BCH (31, 21) code with an even parity bit.
+//!
+//! Every 32-bit word carries 21 information bits, sent first, then ten
+//! check bits and a parity bit over the rest. Taking the first bit sent
+//! as the highest power of x, a word is a multiple of the generator, and
+//! the remainder of a received word picks out any one or two bits in
+//! error. The parity bit then catches most words with three.
+
+/// The generator, x^10 + x^9 + x^8 + x^6 + x^5 + x^3 + 1.
+const GENERATOR: u32 = 0x769;
+pub(super) const DATA_MASK: u32 = (1 << 21) - 1;
+
+/// Reverses the first 31 bits, turning a word as sent, first bit lowest,
+/// into a polynomial with the first bit highest.
+fn polynomial(word: u32) -> u32 {
+    (word & 0x7fff_ffff).reverse_bits() >> 1
+}
+
+fn remainder(mut value: u32) -> u32 {
+    for bit in (10..31).rev() {
+        if value & (1 << bit) != 0 {
+            value ^= GENERATOR << (bit - 10);
+        }
+    }
+    value
+}
+
+fn syndrome(word: u32) -> u32 {
+    remainder(polynomial(word))
+}
+
+/// The word sending 21 bits of `data`.
+#[cfg(test)]
+pub(super) fn encode(data: u32) -> u32 {
+    let message = polynomial(data & DATA_MASK);
+    let word = polynomial(message | remainder(message));
+    word | (word.count_ones() & 1) << 31
+}
+
+/// Corrects up to two bit errors from a table of the patterns behind each
+/// syndrome.
+#[derive(Debug, Clone)]
+pub(super) struct Bch {
+    corrections: Vec<u32>,
+}
+
+impl Bch {
+    pub(super) fn new() -> Self {
+        let mut corrections = vec![0; 1 << 10];
+        for i in 0..31 {
+            for j in i..31 {
+                let error = 1 << i | 1 << j;
+                corrections[syndrome(error) as usize] = error;
+            }
+        }
+        Bch { corrections }
+    }
+
+    /// The information bits of `word`, or `None` if they are past
+    /// correcting.
+    pub(super) fn decode(&self, word: u32) -> Option<u32> {
+        let syndrome = syndrome(word);
+        let error = self.corrections[syndrome as usize];
+        if syndrome != 0 && error == 0 {
+            return None;
+        }
+        let corrected = word ^ error;
+        // With two bits corrected, odd parity shows a third.
+        if corrected.count_ones() % 2 == 1 && error.count_ones() == 2 {
+            return None;
+        }
+        Some(corrected & DATA_MASK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrects_two_errors_and_rejects_three() {
+        let bch = Bch::new();
+        for data in [0, 1, 0x15_5555, 0x12_3456, DATA_MASK] {
+            let word = encode(data);
+            assert_eq!(syndrome(word), 0);
+            assert_eq!(word.count_ones() % 2, 0);
+            assert_eq!(bch.decode(word), Some(data));
+            assert_eq!(bch.decode(word ^ 1 << 31), Some(data));
+            for (i, j) in [(0, 30), (3, 4), (20, 21), (12, 31)] {
+                assert_eq!(bch.decode(word ^ 1 << i ^ 1 << j), Some(data), "{i} {j}");
+            }
+            assert_eq!(bch.decode(word ^ 0b10101 << 5), None);
+        }
+    }
+}
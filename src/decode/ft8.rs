@@ -0,0 +1,469 @@
+//! FT8, the 15-second weak-signal mode.
+//!
+//! Stations transmit in slots 15 seconds long aligned to UTC, starting half
+//! a second in. Each sends 79 symbols of 8-FSK at 6.25 baud, with tones
+//! 6.25 Hz apart, for 12.64 s in all. Three 7×7 Costas arrays, at the start,
+//! middle and end, mark where each signal is in time and frequency, and the
+//! 58 symbols between them carry a 174-bit LDPC codeword, three Gray-coded
+//! bits to a tone, holding a 77-bit message and a CRC-14.
+//!
+//! [`Ft8Decoder`] gathers each slot, computes its spectrogram in steps of
+//! half a symbol and half a tone, ranks every time and frequency by how
+//! well the Costas arrays fit, and decodes the best candidates by belief
+//! propagation, as WSJT-X and ft8_lib do, though without their subtraction
+//! of decoded signals to find weaker ones underneath.
+
+mod ldpc;
+mod message;
+
+pub use message::Ft8Message;
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::fft;
+use num_complex::Complex;
+use std::collections::HashSet;
+
+/// The length of a slot in seconds.
+pub const FT8_SLOT: f64 = 15.0;
+/// The length of a symbol in seconds, the reciprocal of the tone spacing.
+pub const FT8_SYMBOL: f64 = 0.16;
+
+const SYMBOLS: usize = 79;
+const COSTAS: [usize; 7] = [3, 1, 4, 0, 6, 5, 2];
+/// The first symbol of each Costas array.
+const SYNC_AT: [usize; 3] = [0, 36, 72];
+/// The tone sending each three-bit value.
+const GRAY: [usize; 8] = [0, 1, 3, 2, 5, 6, 4, 7];
+/// When in its slot a signal nominally starts, in seconds.
+const START: f64 = 0.5;
+
+/// The symbols carrying the codeword.
+fn data_symbols() -> impl Iterator<Item = usize> {
+    (7..36).chain(43..72)
+}
+
+/// The 79 tones, each from 0 to 7, that send `message`, or `None` if it
+/// cannot be packed.
+pub fn ft8_tones(message: &Ft8Message) -> Option<[u8; SYMBOLS]> {
+    Some(tones(message.pack()?).map(|tone| tone as u8))
+}
+
+fn tones(payload: u128) -> [usize; SYMBOLS] {
+    let mut tones = [0; SYMBOLS];
+    for start in SYNC_AT {
+        tones[start..start + 7].copy_from_slice(&COSTAS);
+    }
+    for (symbol, bits) in data_symbols().zip(ldpc::encode(payload).chunks(3)) {
+        let value = bits
+            .iter()
+            .fold(0, |value, &bit| value << 1 | usize::from(bit));
+        tones[symbol] = GRAY[value];
+    }
+    tones
+}
+
+/// Settings for [`Ft8Decoder`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ft8Config {
+    /// Audio sample rate in Hz, such as the usual 12 kHz.
+    pub sample_rate: f64,
+    /// The range of lowest-tone frequencies searched, in Hz.
+    pub min_frequency: f64,
+    pub max_frequency: f64,
+    /// Candidates tried per slot, best first.
+    pub max_candidates: usize,
+    /// Belief-propagation iterations per candidate.
+    pub iterations: usize,
+    /// Seconds from the start of the stream to the first slot boundary.
+    pub slot_offset: f64,
+}
+
+impl Ft8Config {
+    pub fn new(sample_rate: f64) -> Self {
+        Ft8Config {
+            sample_rate,
+            min_frequency: 200.0,
+            max_frequency: 3_000.0,
+            max_candidates: 100,
+            iterations: 30,
+            slot_offset: 0.0,
+        }
+    }
+}
+
+/// A decoded message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ft8Decode {
+    /// The slot it was heard in, counting from zero at the first boundary.
+    pub slot: u64,
+    /// Seconds by which the signal started after its nominal half second
+    /// into the slot.
+    pub time_offset: f64,
+    /// Frequency of the lowest tone in Hz.
+    pub frequency: f64,
+    /// Signal-to-noise ratio in dB, in the 2500 Hz bandwidth WSJT-X uses.
+    pub snr: f64,
+    pub message: Ft8Message,
+}
+
+/// Power at steps of half a symbol in time and half a tone in frequency.
+struct Spectrogram {
+    steps: usize,
+    bins: usize,
+    power: Vec<f64>,
+}
+
+impl Spectrogram {
+    fn at(&self, step: usize, bin: usize) -> f64 {
+        self.power[step * self.bins + bin]
+    }
+}
+
+/// Decodes FT8 from audio, one slot at a time.
+#[derive(Debug, Clone)]
+pub struct Ft8Decoder {
+    config: Ft8Config,
+    /// Samples per symbol, even so that half-symbol steps are whole.
+    symbol_length: usize,
+    slot_length: usize,
+    samples: Vec<f64>,
+    /// Samples still to skip before the first slot boundary.
+    skip: usize,
+    slot: u64,
+}
+
+impl Ft8Decoder {
+    /// How far the Costas tones must stand out, as their mean share of the
+    /// power in all eight tones; noise alone gives an eighth.
+    const MIN_SYNC: f64 = 0.2;
+
+    pub fn new(config: Ft8Config) -> Self {
+        let symbol_length = ((FT8_SYMBOL * config.sample_rate / 2.0).round() as usize).max(1) * 2;
+        Ft8Decoder {
+            slot_length: (FT8_SLOT * config.sample_rate).round() as usize,
+            skip: (config.slot_offset * config.sample_rate).round().max(0.0) as usize,
+            config,
+            symbol_length,
+            samples: Vec::new(),
+            slot: 0,
+        }
+    }
+
+    pub fn config(&self) -> &Ft8Config {
+        &self.config
+    }
+
+    /// Discards the slot in progress. The slot count carries on, so the
+    /// next slot decoded is the one after.
+    pub fn reset(&mut self) {
+        if !self.samples.is_empty() {
+            self.samples.clear();
+            self.slot += 1;
+        }
+    }
+
+    /// Accepts one audio sample, appending the decodes of a slot each time
+    /// one completes.
+    pub fn push(&mut self, sample: f64, output: &mut Vec<Ft8Decode>) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        self.samples.push(sample);
+        if self.samples.len() == self.slot_length {
+            let slot = self.slot;
+            output.extend(
+                self.decode_slot(&self.samples)
+                    .into_iter()
+                    .map(|decode| Ft8Decode { slot, ..decode }),
+            );
+            trace_event!(debug, slot, "FT8 slot decoded");
+            self.samples.clear();
+            self.slot += 1;
+        }
+    }
+
+    /// Decodes one slot of audio starting on its boundary, such as a
+    /// recording. The decodes' slot numbers are zero.
+    pub fn decode_slot(&self, samples: &[f64]) -> Vec<Ft8Decode> {
+        let spectrogram = self.spectrogram(samples);
+        let (candidates, noise) = self.candidates(&spectrogram);
+        let rate = self.config.sample_rate;
+        let bin_width = rate / (2 * self.symbol_length) as f64;
+        let mut seen = HashSet::new();
+        let mut decodes = Vec::new();
+        for (step, bin) in candidates {
+            let Some(payload) = self.decode_candidate(&spectrogram, step, bin) else {
+                continue;
+            };
+            let Some(message) = Ft8Message::unpack(payload).filter(|_| seen.insert(payload)) else {
+                continue;
+            };
+            let tones = tones(payload);
+            let signal = (0..SYMBOLS)
+                .map(|k| spectrogram.at(step + 2 * k, bin + 2 * tones[k]))
+                .sum::<f64>()
+                / SYMBOLS as f64;
+            // A bin is as wide as one tone, so the noise in 2500 Hz is that
+            // many bins' worth.
+            let snr = 10.0 * (signal / noise - 1.0).max(1e-3).log10()
+                - 10.0 * (2_500.0 * FT8_SYMBOL).log10();
+            decodes.push(Ft8Decode {
+                slot: 0,
+                time_offset: (step * self.symbol_length / 2) as f64 / rate - START,
+                frequency: bin as f64 * bin_width,
+                snr,
+                message,
+            });
+        }
+        decodes
+    }
+
+    fn spectrogram(&self, samples: &[f64]) -> Spectrogram {
+        let length = self.symbol_length;
+        let hop = length / 2;
+        let bin_width = self.config.sample_rate / (2 * length) as f64;
+        let bins = ((self.config.max_frequency / bin_width).ceil() as usize + 16).min(length);
+        let steps =
+            samples.len().saturating_sub(length) / hop + usize::from(samples.len() >= length);
+        let mut power = Vec::with_capacity(steps * bins);
+        let mut buffer = vec![Complex::new(0.0, 0.0); 2 * length];
+        for step in 0..steps {
+            // Zero-padded to twice the symbol, for bins half a tone apart.
+            for (slot, &sample) in buffer.iter_mut().zip(&samples[step * hop..]) {
+                *slot = Complex::new(sample, 0.0);
+            }
+            buffer[length..]
+                .iter_mut()
+                .for_each(|slot| *slot = Complex::new(0.0, 0.0));
+            fft(&mut buffer);
+            power.extend(buffer[..bins].iter().map(|bin| bin.norm_sqr()));
+        }
+        Spectrogram { steps, bins, power }
+    }
+
+    /// Times and frequencies where the Costas arrays stand out, best first,
+    /// and the noise power per bin.
+    fn candidates(&self, spectrogram: &Spectrogram) -> (Vec<(usize, usize)>, f64) {
+        let bin_width = self.config.sample_rate / (2 * self.symbol_length) as f64;
+        let first_bin = (self.config.min_frequency / bin_width).floor() as usize;
+        let last_bin = ((self.config.max_frequency / bin_width).ceil() as usize)
+            .min(spectrogram.bins.saturating_sub(15));
+        let span = 2 * (SYMBOLS - 1) + 1;
+        if spectrogram.steps < span || last_bin <= first_bin {
+            return (Vec::new(), 1.0);
+        }
+        let starts = spectrogram.steps - span + 1;
+        let width = last_bin - first_bin;
+        let score = |step: usize, bin: usize| {
+            let mut total = 0.0;
+            for start in SYNC_AT {
+                for (k, &tone) in COSTAS.iter().enumerate() {
+                    let at = step + 2 * (start + k);
+                    let all: f64 = (0..8).map(|tone| spectrogram.at(at, bin + 2 * tone)).sum();
+                    total += spectrogram.at(at, bin + 2 * tone) / all.max(f64::MIN_POSITIVE);
+                }
+            }
+            total / 21.0
+        };
+        let scores: Vec<f64> = (0..starts)
+            .flat_map(|step| (first_bin..last_bin).map(move |bin| (step, bin)))
+            .map(|(step, bin)| score(step, bin))
+            .collect();
+        let at = |step: usize, bin: usize| scores[step * width + bin - first_bin];
+        let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+        for step in 0..starts {
+            for bin in first_bin..last_bin {
+                let here = at(step, bin);
+                let peak = (step.saturating_sub(1)..(step + 2).min(starts))
+                    .flat_map(|s| {
+                        (bin.saturating_sub(1).max(first_bin)..(bin + 2).min(last_bin))
+                            .map(move |b| (s, b))
+                    })
+                    .all(|(s, b)| at(s, b) <= here);
+                if here > Self::MIN_SYNC && peak {
+                    candidates.push((here, step, bin));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.truncate(self.config.max_candidates);
+
+        // The median of noise-like power is ln 2 of its mean.
+        let mut band: Vec<f64> = (0..spectrogram.steps)
+            .flat_map(|step| (first_bin..last_bin + 14).map(move |bin| spectrogram.at(step, bin)))
+            .collect();
+        let middle = band.len() / 2;
+        let median = *band.select_nth_unstable_by(middle, f64::total_cmp).1;
+        let noise = (median / std::f64::consts::LN_2).max(f64::MIN_POSITIVE);
+        (
+            candidates
+                .into_iter()
+                .map(|(_, step, bin)| (step, bin))
+                .collect(),
+            noise,
+        )
+    }
+
+    /// The message at a candidate, if it decodes and passes its CRC.
+    fn decode_candidate(&self, spectrogram: &Spectrogram, step: usize, bin: usize) -> Option<u128> {
+        let mut likelihoods = [0.0; ldpc::CODEWORD_BITS];
+        for (k, symbol) in data_symbols().enumerate() {
+            let levels: [f64; 8] = std::array::from_fn(|value| {
+                spectrogram
+                    .at(step + 2 * symbol, bin + 2 * GRAY[value])
+                    .max(f64::MIN_POSITIVE)
+                    .ln()
+            });
+            for (b, likelihood) in likelihoods[3 * k..3 * k + 3].iter_mut().enumerate() {
+                let mask = 4 >> b;
+                let best = |one: bool| {
+                    (0..8)
+                        .filter(|value| (value & mask != 0) == one)
+                        .map(|value| levels[value])
+                        .fold(f64::NEG_INFINITY, f64::max)
+                };
+                *likelihood = best(false) - best(true);
+            }
+        }
+        // Scaled to a fixed variance, as ft8_lib does, which suits belief
+        // propagation across signal strengths.
+        let count = likelihoods.len() as f64;
+        let mean = likelihoods.iter().sum::<f64>() / count;
+        let variance = likelihoods.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / count;
+        let scale = (24.0 / variance.max(f64::MIN_POSITIVE)).sqrt();
+        likelihoods
+            .iter_mut()
+            .for_each(|likelihood| *likelihood *= scale);
+        ldpc::decode(&likelihoods, self.config.iterations)
+    }
+}
+
+impl Block for Ft8Decoder {
+    type Input = f64;
+    type Output = Ft8Decode;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<Ft8Decode>) -> usize {
+        for &sample in input {
+            self.push(sample, output);
+        }
+        input.len()
+    }
+
+    /// Accepts `min_frequency` and `max_frequency`, in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("min_frequency", Some(frequency)) if frequency >= 0.0 => {
+                self.config.min_frequency = frequency
+            }
+            ("max_frequency", Some(frequency)) if frequency > 0.0 => {
+                self.config.max_frequency = frequency
+            }
+            ("min_frequency" | "max_frequency", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use std::f64::consts::PI;
+
+    const RATE: f64 = 12_000.0;
+
+    /// Adds `message` to `slot` at `snr` dB against noise of uniform samples.
+    fn transmit(slot: &mut [f64], message: &str, frequency: f64, delay: f64, snr: f64) {
+        let tones = ft8_tones(&Ft8Message::parse(message).unwrap()).unwrap();
+        // Uniform noise in [-1, 1) has variance 1/3, of which 2500 Hz is a
+        // fraction of the 6 kHz band.
+        let noise = 2_500.0 / 6_000.0 / 3.0;
+        let amplitude = (2.0 * noise * 10f64.powf(snr / 10.0)).sqrt();
+        let length = (FT8_SYMBOL * RATE) as usize;
+        let start = ((START + delay) * RATE) as usize;
+        let mut phase = 0.0;
+        for (k, &tone) in tones.iter().enumerate() {
+            let step = 2.0 * PI * (frequency + f64::from(tone) / FT8_SYMBOL) / RATE;
+            for sample in &mut slot[start + k * length..start + (k + 1) * length] {
+                *sample += amplitude * f64::sin(phase);
+                phase += step;
+            }
+        }
+    }
+
+    #[test]
+    fn test_decodes_weak_signals() {
+        let mut slot = real_noise(180_000, 3);
+        transmit(&mut slot, "CQ K1ABC FN42", 1_000.0, 0.0, -12.0);
+        transmit(&mut slot, "K1ABC W9XYZ -15", 1_503.0, 0.37, -17.0);
+        let decoder = Ft8Decoder::new(Ft8Config::new(RATE));
+        let mut decodes = decoder.decode_slot(&slot);
+        assert_eq!(decodes.len(), 2, "{decodes:?}");
+        decodes.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+        for (decode, (text, frequency, delay, snr)) in decodes.iter().zip([
+            ("CQ K1ABC FN42", 1_000.0, 0.0, -12.0),
+            ("K1ABC W9XYZ -15", 1_503.0, 0.37, -17.0),
+        ]) {
+            assert_eq!(decode.message.to_string(), text);
+            assert!((decode.frequency - frequency).abs() < 3.2, "{decode:?}");
+            assert!((decode.time_offset - delay).abs() < 0.08, "{decode:?}");
+            assert!((decode.snr - snr).abs() < 3.0, "{decode:?}");
+        }
+    }
+
+    #[test]
+    fn test_clean_signal_decodes_once() {
+        for rate in [8_000.0, RATE, 44_100.0, 48_000.0] {
+            let tones = ft8_tones(&Ft8Message::parse("CQ K1ABC FN42").unwrap()).unwrap();
+            let mut slot = vec![0.0; (15.0 * rate) as usize];
+            let length = (FT8_SYMBOL * rate) as usize;
+            let start = (START * rate) as usize;
+            let mut phase = 0.0;
+            for (k, &tone) in tones.iter().enumerate() {
+                let step = 2.0 * PI * (1_000.0 + f64::from(tone) / FT8_SYMBOL) / rate;
+                for sample in &mut slot[start + k * length..start + (k + 1) * length] {
+                    *sample = 0.1 * f64::sin(phase);
+                    phase += step;
+                }
+            }
+            let decodes = Ft8Decoder::new(Ft8Config::new(rate)).decode_slot(&slot);
+            assert_eq!(decodes.len(), 1, "{rate} Hz: {decodes:?}");
+            assert_eq!(decodes[0].message.to_string(), "CQ K1ABC FN42");
+        }
+    }
+
+    #[test]
+    fn test_streams_slots_from_an_offset() {
+        let mut stream = real_noise(12_000 + 2 * 180_000, 8);
+        transmit(
+            &mut stream[12_000..],
+            "CQ DX VK2DEF QF56",
+            750.0,
+            0.1,
+            -10.0,
+        );
+        let mut config = Ft8Config::new(RATE);
+        config.slot_offset = 1.0;
+        let mut decoder = Ft8Decoder::new(config);
+        let mut decodes = Vec::new();
+        decoder.work(&stream, &mut decodes);
+        assert_eq!(decodes.len(), 1, "{decodes:?}");
+        assert_eq!(decodes[0].slot, 0);
+        assert_eq!(
+            decodes[0].message,
+            Ft8Message::Standard {
+                to: "CQ DX".to_string(),
+                from: "VK2DEF".to_string(),
+                exchange: "QF56".to_string(),
+            }
+        );
+        assert!(decoder
+            .set_parameter("max_frequency", &ParamValue::Float(-1.0))
+            .is_err());
+    }
+}
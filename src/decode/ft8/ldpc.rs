@@ -0,0 +1,326 @@
+//! The (174, 91) LDPC code protecting FT8 messages, and the CRC-14 inside it.
+//!
+//! A 77-bit message gains a 14-bit CRC, and those 91 bits gain 83 parity
+//! bits. Bits are numbered from the first sent, and [`u128`] payloads hold
+//! the first bit in their most significant used position.
+
+/// Bits in a codeword.
+pub(super) const CODEWORD_BITS: usize = 174;
+const PAYLOAD_BITS: usize = 91;
+
+/// The parity part of the generator matrix, from WSJT-X: bit 91 of row `i`
+/// is the weight of payload bit 0 in parity bit `i`, and bit 0 is unused.
+const GENERATOR: [u128; 83] = [
+    0x8329ce11bf31eaf509f27fc,
+    0x761c264e25c259335493132,
+    0xdc265902fb277c6410a1bdc,
+    0x1b3f417858cd2dd33ec7f62,
+    0x09fda4fee04195fd034783a,
+    0x077cccc11b8873ed5c3d48a,
+    0x29b62afe3ca036f4fe1a9da,
+    0x6054faf5f35d96d3b0c8c3e,
+    0xe20798e4310eed27884ae90,
+    0x775c9c08e80e26ddae56318,
+    0xb0b811028c2bf997213487c,
+    0x18a0c9231fc60adf5c5ea32,
+    0x76471e8302a0721e01b12b8,
+    0xffbccb80ca8341fafb47b2e,
+    0x66a72a158f9325a2bf67170,
+    0xc4243689fe85b1c51363a18,
+    0x0dff739414d1a1b34b1c270,
+    0x15b48830636c8b99894972e,
+    0x29a89c0d3de81d665489b0e,
+    0x4f126f37fa51cbe61bd6b94,
+    0x99c47239d0d97d3c84e0940,
+    0x1919b75119765621bb4f1e8,
+    0x09db12d731faee0b86df6b8,
+    0x488fc33df43fbdeea4eafb4,
+    0x827423ee40b675f756eb5fe,
+    0xabe197c484cb74757144a9a,
+    0x2b500e4bc0ec5a6d2bdbdd0,
+    0xc474aa53d70218761669360,
+    0x8eba1a13db3390bd6718cec,
+    0x753844673a27782cc42012e,
+    0x06ff83a145c37035a5c1268,
+    0x3b37417858cc2dd33ec3f62,
+    0x9a4a5a28ee17ca9c324842c,
+    0xbc29f465309c977e89610a4,
+    0x2663ae6ddf8b5ce2bb29488,
+    0x46f231efe457034c1814418,
+    0x3fb2ce85abe9b0c72e06fbe,
+    0xde87481f282c153971a0a2e,
+    0xfcd7ccf23c69fa99bba1412,
+    0xf0261447e9490ca8e474cec,
+    0x4410115818196f95cdd7012,
+    0x088fc31df4bfbde2a4eafb4,
+    0xb8fef1b6307729fb0a078c0,
+    0x5afea7acccb77bbc9d99a90,
+    0x49a7016ac653f65ecdc9076,
+    0x1944d085be4e7da8d6cc7d0,
+    0x251f62adc4032f0ee714002,
+    0x56471f8702a0721e00b12b8,
+    0x2b8e4923f2dd51e2d537fa0,
+    0x6b550a40a66f4755de95c26,
+    0xa18ad28d4e27fe92a4f6c84,
+    0x10c2e586388cb82a3d80758,
+    0xef34a41817ee02133db2eb0,
+    0x7e9c0c54325a9c15836e000,
+    0x3693e572d1fde4cdf079e86,
+    0xbfb2cec5abe1b0c72e07fbe,
+    0x7ee18230c583cccc57d4b08,
+    0xa066cb2fedafc9f52664126,
+    0xbb23725abc47cc5f4cc4cd2,
+    0xded9dba3bee40c59b5609b4,
+    0xd9a7016ac653e6decdc9036,
+    0x9ad46aed5f707f280ab5fc4,
+    0xe5921c77822587316d7d3c2,
+    0x4f14da8242a8b86dca73352,
+    0x8b8b507ad467d4441df770e,
+    0x22831c9cf1169467ad04b68,
+    0x213b838fe2ae54c38ee7180,
+    0x5d926b6dd71f085181a4e12,
+    0x66ab79d4b29ee6e69509e56,
+    0x958148682d748a38dd68baa,
+    0xb8ce020cf069c32a723ab14,
+    0xf4331d6d461607e95752746,
+    0x6da23ba424b9596133cf9c8,
+    0xa636bcbc7b30c5fbeae67fe,
+    0x5cb0d86a07df654a9089a20,
+    0xf11f106848780fc9ecdd80a,
+    0x1fbb5364fb8d2c9d730d5ba,
+    0xfcb86bc70a50c9d02a5d034,
+    0xa534433029eac15f322e34c,
+    0xc989d9c7c3d3b8c55d75130,
+    0x7bb38b2f0186d46643ae962,
+    0x2644ebadeb44b9467d1f42c,
+    0x608cc857594bfbb55d69600,
+];
+
+/// The codeword bits in each parity check, numbered from 1 as in the WSJT-X
+/// sources, with 0 padding the 59 checks of six bits.
+const CHECKS: [[u8; 7]; 83] = [
+    [4, 31, 59, 91, 92, 96, 153],
+    [5, 32, 60, 93, 115, 146, 0],
+    [6, 24, 61, 94, 122, 151, 0],
+    [7, 33, 62, 95, 96, 143, 0],
+    [8, 25, 63, 83, 93, 96, 148],
+    [6, 32, 64, 97, 126, 138, 0],
+    [5, 34, 65, 78, 98, 107, 154],
+    [9, 35, 66, 99, 139, 146, 0],
+    [10, 36, 67, 100, 107, 126, 0],
+    [11, 37, 67, 87, 101, 139, 158],
+    [12, 38, 68, 102, 105, 155, 0],
+    [13, 39, 69, 103, 149, 162, 0],
+    [8, 40, 70, 82, 104, 114, 145],
+    [14, 41, 71, 88, 102, 123, 156],
+    [15, 42, 59, 106, 123, 159, 0],
+    [1, 33, 72, 106, 107, 157, 0],
+    [16, 43, 73, 108, 141, 160, 0],
+    [17, 37, 74, 81, 109, 131, 154],
+    [11, 44, 75, 110, 121, 166, 0],
+    [45, 55, 64, 111, 130, 161, 173],
+    [8, 46, 71, 112, 119, 166, 0],
+    [18, 36, 76, 89, 113, 114, 143],
+    [19, 38, 77, 104, 116, 163, 0],
+    [20, 47, 70, 92, 138, 165, 0],
+    [2, 48, 74, 113, 128, 160, 0],
+    [21, 45, 78, 83, 117, 121, 151],
+    [22, 47, 58, 118, 127, 164, 0],
+    [16, 39, 62, 112, 134, 158, 0],
+    [23, 43, 79, 120, 131, 145, 0],
+    [19, 35, 59, 73, 110, 125, 161],
+    [20, 36, 63, 94, 136, 161, 0],
+    [14, 31, 79, 98, 132, 164, 0],
+    [3, 44, 80, 124, 127, 169, 0],
+    [19, 46, 81, 117, 135, 167, 0],
+    [7, 49, 58, 90, 100, 105, 168],
+    [12, 50, 61, 118, 119, 144, 0],
+    [13, 51, 64, 114, 118, 157, 0],
+    [24, 52, 76, 129, 148, 149, 0],
+    [25, 53, 69, 90, 101, 130, 156],
+    [20, 46, 65, 80, 120, 140, 170],
+    [21, 54, 77, 100, 140, 171, 0],
+    [35, 82, 133, 142, 171, 174, 0],
+    [14, 30, 83, 113, 125, 170, 0],
+    [4, 29, 68, 120, 134, 173, 0],
+    [1, 4, 52, 57, 86, 136, 152],
+    [26, 51, 56, 91, 122, 137, 168],
+    [52, 84, 110, 115, 145, 168, 0],
+    [7, 50, 81, 99, 132, 173, 0],
+    [23, 55, 67, 95, 172, 174, 0],
+    [26, 41, 77, 109, 141, 148, 0],
+    [2, 27, 41, 61, 62, 115, 133],
+    [27, 40, 56, 124, 125, 126, 0],
+    [18, 49, 55, 124, 141, 167, 0],
+    [6, 33, 85, 108, 116, 156, 0],
+    [28, 48, 70, 85, 105, 129, 158],
+    [9, 54, 63, 131, 147, 155, 0],
+    [22, 53, 68, 109, 121, 174, 0],
+    [3, 13, 48, 78, 95, 123, 0],
+    [31, 69, 133, 150, 155, 169, 0],
+    [12, 43, 66, 89, 97, 135, 159],
+    [5, 39, 75, 102, 136, 167, 0],
+    [2, 54, 86, 101, 135, 164, 0],
+    [15, 56, 87, 108, 119, 171, 0],
+    [10, 44, 82, 91, 111, 144, 149],
+    [23, 34, 71, 94, 127, 153, 0],
+    [11, 49, 88, 92, 142, 157, 0],
+    [29, 34, 87, 97, 147, 162, 0],
+    [30, 50, 60, 86, 137, 142, 162],
+    [10, 53, 66, 84, 112, 128, 165],
+    [22, 57, 85, 93, 140, 159, 0],
+    [28, 32, 72, 103, 132, 166, 0],
+    [28, 29, 84, 88, 117, 143, 150],
+    [1, 26, 45, 80, 128, 147, 0],
+    [17, 27, 89, 103, 116, 153, 0],
+    [51, 57, 98, 163, 165, 172, 0],
+    [21, 37, 73, 138, 152, 169, 0],
+    [16, 47, 76, 130, 137, 154, 0],
+    [3, 24, 30, 72, 104, 139, 0],
+    [9, 40, 90, 106, 134, 151, 0],
+    [15, 58, 60, 74, 111, 150, 163],
+    [18, 42, 79, 144, 146, 152, 0],
+    [25, 38, 65, 99, 122, 160, 0],
+    [17, 42, 75, 129, 170, 172, 0],
+];
+
+/// CRC-14 with polynomial 0x2757 over the 77 bits of `message`, followed by
+/// five zeros.
+pub(super) fn crc14(message: u128) -> u16 {
+    let mut remainder: u16 = 0;
+    for k in (0..82).rev() {
+        let bit = if k >= 5 { (message >> (k - 5)) & 1 } else { 0 };
+        remainder ^= (bit as u16) << 13;
+        remainder = if remainder & 0x2000 != 0 {
+            (remainder << 1) ^ 0x2757
+        } else {
+            remainder << 1
+        } & 0x3fff;
+    }
+    remainder
+}
+
+/// The codeword for a 77-bit `message`.
+pub(super) fn encode(message: u128) -> [bool; CODEWORD_BITS] {
+    encode_payload(message << 14 | u128::from(crc14(message)))
+}
+
+/// The codeword for a 91-bit message and CRC.
+fn encode_payload(payload: u128) -> [bool; CODEWORD_BITS] {
+    let mut codeword = [false; CODEWORD_BITS];
+    for (k, bit) in codeword[..PAYLOAD_BITS].iter_mut().enumerate() {
+        *bit = (payload >> (PAYLOAD_BITS - 1 - k)) & 1 == 1;
+    }
+    for (bit, row) in codeword[PAYLOAD_BITS..].iter_mut().zip(&GENERATOR) {
+        *bit = (row >> 1 & payload).count_ones() % 2 == 1;
+    }
+    codeword
+}
+
+/// The bits of one parity check, numbered from zero.
+fn check_bits(check: &[u8; 7]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    check
+        .iter()
+        .enumerate()
+        .filter(|&(_, &bit)| bit != 0)
+        .map(|(edge, &bit)| (edge, usize::from(bit) - 1))
+}
+
+/// Decodes soft bits by belief propagation, returning the message once
+/// every parity check is satisfied, if its CRC matches. The all-zero
+/// codeword passes both, as its CRC is zero too, so it is rejected as WSJT-X
+/// rejects it: otherwise a clean signal would decode a second, empty message
+/// wherever the search lands on tones all reading zero.
+///
+/// `likelihoods` holds the log-likelihood ratio `ln(P(0) / P(1))` of each
+/// codeword bit.
+pub(super) fn decode(likelihoods: &[f64; CODEWORD_BITS], iterations: usize) -> Option<u128> {
+    // Messages from each check to each of its bits.
+    let mut to_bits = [[0.0; 7]; 83];
+    for _ in 0..=iterations {
+        let mut totals = *likelihoods;
+        for (check, messages) in CHECKS.iter().zip(&to_bits) {
+            for (edge, bit) in check_bits(check) {
+                totals[bit] += messages[edge];
+            }
+        }
+        let hard: Vec<bool> = totals.iter().map(|&total| total < 0.0).collect();
+        let satisfied = CHECKS
+            .iter()
+            .all(|check| check_bits(check).filter(|&(_, bit)| hard[bit]).count() % 2 == 0);
+        if satisfied {
+            let payload = hard[..PAYLOAD_BITS]
+                .iter()
+                .fold(0u128, |payload, &bit| payload << 1 | u128::from(bit));
+            if payload == 0 {
+                return None;
+            }
+            let message = payload >> 14;
+            return (crc14(message) == (payload & 0x3fff) as u16).then_some(message);
+        }
+        for (check, messages) in CHECKS.iter().zip(&mut to_bits) {
+            // What each bit tells this check, leaving out what it was told.
+            let halves: Vec<(usize, f64)> = check_bits(check)
+                .map(|(edge, bit)| (edge, ((totals[bit] - messages[edge]) / 2.0).tanh()))
+                .collect();
+            for &(edge, _) in &halves {
+                let product: f64 = halves
+                    .iter()
+                    .filter(|&&(other, _)| other != edge)
+                    .map(|&(_, half)| half)
+                    .product();
+                messages[edge] = 2.0 * product.clamp(-0.999_999, 0.999_999).atanh();
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codewords_satisfy_every_check() {
+        let mut message = 0x1234_5678_9abc_def0_1234u128 & ((1 << 77) - 1);
+        for _ in 0..20 {
+            let codeword = encode(message);
+            for check in &CHECKS {
+                assert_eq!(
+                    check_bits(check).filter(|&(_, bit)| codeword[bit]).count() % 2,
+                    0
+                );
+            }
+            message = (message * 0x2545_f491 + 7) & ((1 << 77) - 1);
+        }
+        // Each bit is in three checks.
+        let mut counts = [0; CODEWORD_BITS];
+        CHECKS
+            .iter()
+            .flat_map(check_bits)
+            .for_each(|(_, bit)| counts[bit] += 1);
+        assert!(counts.iter().all(|&count| count == 3));
+    }
+
+    #[test]
+    fn test_corrects_errors_and_checks_crc() {
+        let message = 0x0bad_cafe_f00d_1234_5678u128 & ((1 << 77) - 1);
+        let codeword = encode(message);
+        let mut likelihoods = codeword.map(|bit| if bit { -2.0 } else { 2.0 });
+        // Get a dozen bits wrong, though less surely than the rest, and
+        // leave others barely known.
+        for k in 0..12 {
+            likelihoods[k * 13 + 5] *= -0.5;
+            likelihoods[k * 6 + 100] *= 0.1;
+        }
+        assert_eq!(decode(&likelihoods, 30), Some(message));
+
+        // A valid codeword whose CRC does not match is rejected.
+        let forged = encode_payload(message << 14 | u128::from(crc14(message) ^ 1));
+        assert_eq!(
+            decode(&forged.map(|bit| if bit { -2.0 } else { 2.0 }), 30),
+            None
+        );
+        assert_eq!(decode(&[2.0; CODEWORD_BITS], 30), None);
+    }
+}
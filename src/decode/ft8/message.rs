@@ -0,0 +1,358 @@
+//! FT8's 77-bit messages.
+//!
+//! The last three bits, `i3`, give the message type, and for type 0 the
+//! three before them, `n3`, a subtype. Standard messages pack each callsign
+//! into 28 bits, along with a grid square, signal report or acknowledgement
+//! in 15; free text packs 13 characters in base 42.
+
+use std::fmt;
+
+/// Values of a 28-bit callsign field below this are tokens such as `CQ`.
+const TOKENS: u32 = 2_063_592;
+/// Values above the tokens and below this are hashes of callsigns too long
+/// to pack.
+const HASHES: u32 = TOKENS + 4_194_304;
+/// 15-bit exchange values below this are four-character grid squares.
+const GRIDS: u16 = 32_400;
+
+const ALPHANUMERIC: &[u8] = b" 0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LETTERS: &[u8] = b" ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const TEXT: &[u8] = b" 0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ+-./?";
+
+/// A decoded message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ft8Message {
+    /// A standard message, such as `CQ K1ABC FN42` or `K1ABC W9XYZ R-15`:
+    /// the station or call addressed, the sender, and a grid square, signal
+    /// report, acknowledgement or nothing. Callsigns sent as hashes read
+    /// `<...>`.
+    Standard {
+        to: String,
+        from: String,
+        exchange: String,
+    },
+    /// Up to 13 characters of free text.
+    FreeText(String),
+    /// Up to 18 hexadecimal digits of telemetry.
+    Telemetry(String),
+    /// A type not decoded here, such as contest exchanges.
+    Other { i3: u8, n3: u8 },
+}
+
+impl Ft8Message {
+    /// Reads the text a user would type, as a standard message where it
+    /// fits one and free text otherwise.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_ascii_uppercase();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        // A CQ may name who it calls, as in `CQ DX K1ABC FN42`.
+        let (to, rest) = match words.as_slice() {
+            ["CQ", modifier, _, ..] if pack_call(&format!("CQ {modifier}")).is_some() => {
+                (format!("CQ {modifier}"), &words[2..])
+            }
+            [to, rest @ ..] => (to.to_string(), rest),
+            [] => return None,
+        };
+        if let [from, exchange @ ..] = rest {
+            let standard = Ft8Message::Standard {
+                to,
+                from: from.to_string(),
+                exchange: exchange.join(" "),
+            };
+            if standard.pack().is_some() {
+                return Some(standard);
+            }
+        }
+        let free = Ft8Message::FreeText(text);
+        free.pack().map(|_| free)
+    }
+
+    /// The 77-bit payload for this message, or `None` if it cannot be packed.
+    pub fn pack(&self) -> Option<u128> {
+        match self {
+            Ft8Message::Standard { to, from, exchange } => {
+                let (to, to_suffix) = split_suffix(to);
+                let (from, from_suffix) = split_suffix(from);
+                // Type 1 carries `/R` suffixes and type 2 `/P`.
+                let i3 = match (to_suffix, from_suffix) {
+                    (Some("/P"), Some("/R")) | (Some("/R"), Some("/P")) => return None,
+                    (Some("/P"), _) | (_, Some("/P")) => 2,
+                    _ => 1,
+                };
+                let (acknowledged, exchange) = pack_exchange(exchange)?;
+                Some(
+                    u128::from(pack_call(to)?) << 49
+                        | u128::from(to_suffix.is_some()) << 48
+                        | u128::from(pack_call(from).filter(|&call| call >= HASHES)?) << 20
+                        | u128::from(from_suffix.is_some()) << 19
+                        | u128::from(acknowledged) << 18
+                        | u128::from(exchange) << 3
+                        | i3,
+                )
+            }
+            Ft8Message::FreeText(text) => {
+                let text = text.trim();
+                if text.len() > 13 {
+                    return None;
+                }
+                let value = format!("{text:<13}").bytes().try_fold(0u128, |value, c| {
+                    let index = TEXT.iter().position(|&t| t == c)?;
+                    Some(value * 42 + index as u128)
+                })?;
+                Some(value << 6)
+            }
+            Ft8Message::Telemetry(hex) => {
+                let value = u128::from_str_radix(hex, 16).ok()?;
+                (hex.len() <= 18 && value < 1 << 71).then_some(value << 6 | 5 << 3)
+            }
+            Ft8Message::Other { .. } => None,
+        }
+    }
+
+    /// Reads a 77-bit payload, or `None` if its fields are out of range.
+    pub fn unpack(payload: u128) -> Option<Self> {
+        let i3 = (payload & 7) as u8;
+        let n3 = ((payload >> 3) & 7) as u8;
+        match (i3, n3) {
+            (1 | 2, _) => {
+                let suffix = if i3 == 1 { "/R" } else { "/P" };
+                let call = |shift: u32| {
+                    let call = unpack_call(((payload >> shift) & ((1 << 28) - 1)) as u32)?;
+                    let suffixed = (payload >> (shift - 1)) & 1 == 1;
+                    Some(if suffixed { call + suffix } else { call })
+                };
+                Some(Ft8Message::Standard {
+                    to: call(49)?,
+                    from: call(20)?,
+                    exchange: unpack_exchange(
+                        (payload >> 18) & 1 == 1,
+                        ((payload >> 3) & 0x7fff) as u16,
+                    )?,
+                })
+            }
+            (0, 0) => {
+                let mut value = payload >> 6;
+                let mut text = [b' '; 13];
+                for c in text.iter_mut().rev() {
+                    *c = TEXT[(value % 42) as usize];
+                    value /= 42;
+                }
+                (value == 0).then(|| {
+                    Ft8Message::FreeText(String::from_utf8_lossy(&text).trim().to_string())
+                })
+            }
+            (0, 5) => Some(Ft8Message::Telemetry(format!("{:X}", payload >> 6))),
+            _ => Some(Ft8Message::Other { i3, n3 }),
+        }
+    }
+}
+
+impl fmt::Display for Ft8Message {
+    /// Formats as WSJT-X shows messages.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ft8Message::Standard { to, from, exchange } if exchange.is_empty() => {
+                write!(f, "{to} {from}")
+            }
+            Ft8Message::Standard { to, from, exchange } => write!(f, "{to} {from} {exchange}"),
+            Ft8Message::FreeText(text) | Ft8Message::Telemetry(text) => f.write_str(text),
+            Ft8Message::Other { i3, n3 } => write!(f, "<type {i3}.{n3}>"),
+        }
+    }
+}
+
+/// Splits a `/R` or `/P` suffix from `call`.
+fn split_suffix(call: &str) -> (&str, Option<&'static str>) {
+    ["/R", "/P"]
+        .into_iter()
+        .find_map(|suffix| Some((call.strip_suffix(suffix)?, Some(suffix))))
+        .unwrap_or((call, None))
+}
+
+/// Packs a token such as `CQ` or `CQ DX`, or a standard callsign.
+fn pack_call(call: &str) -> Option<u32> {
+    match call {
+        "DE" => return Some(0),
+        "QRZ" => return Some(1),
+        "CQ" => return Some(2),
+        _ => {}
+    }
+    if let Some(modifier) = call.strip_prefix("CQ ") {
+        if modifier.len() == 3 && modifier.bytes().all(|c| c.is_ascii_digit()) {
+            return Some(3 + modifier.parse::<u32>().ok()?);
+        }
+        if (1..=4).contains(&modifier.len()) && modifier.bytes().all(|c| c.is_ascii_uppercase()) {
+            let value = format!("{modifier:<4}")
+                .bytes()
+                .fold(0, |value, c| value * 27 + position(LETTERS, c).unwrap_or(0));
+            return Some(1003 + value);
+        }
+        return None;
+    }
+    // Standard callsigns have a digit third, or second after a space.
+    let bytes = call.as_bytes();
+    let padded = match bytes {
+        [_, _, digit, ..] if digit.is_ascii_digit() => format!("{call:<6}"),
+        [_, digit, ..] if digit.is_ascii_digit() => format!(" {call:<5}"),
+        _ => return None,
+    };
+    if padded.len() != 6 {
+        return None;
+    }
+    let c = padded.as_bytes();
+    let first = position(ALPHANUMERIC, c[0])?;
+    let second = position(&ALPHANUMERIC[1..], c[1])?;
+    let digit = u32::from(c[2] - b'0');
+    let suffix = c[3..]
+        .iter()
+        .try_fold(0, |value, &c| Some(value * 27 + position(LETTERS, c)?))?;
+    Some(HASHES + ((first * 36 + second) * 10 + digit) * 27 * 27 * 27 + suffix)
+}
+
+fn unpack_call(value: u32) -> Option<String> {
+    match value {
+        0 => Some("DE".to_string()),
+        1 => Some("QRZ".to_string()),
+        2 => Some("CQ".to_string()),
+        3..=1002 => Some(format!("CQ {:03}", value - 3)),
+        1003..=532_443 => {
+            let mut value = value - 1003;
+            let mut modifier = [b' '; 4];
+            for c in modifier.iter_mut().rev() {
+                *c = LETTERS[(value % 27) as usize];
+                value /= 27;
+            }
+            Some(format!("CQ {}", String::from_utf8_lossy(&modifier).trim()))
+        }
+        TOKENS..HASHES => Some("<...>".to_string()),
+        HASHES.. => {
+            let mut value = value - HASHES;
+            let mut call = [0; 6];
+            for (k, c) in call.iter_mut().enumerate().rev() {
+                let (alphabet, base): (&[u8], u32) = match k {
+                    3..=5 => (LETTERS, 27),
+                    2 => (b"0123456789", 10),
+                    1 => (&ALPHANUMERIC[1..], 36),
+                    _ => (ALPHANUMERIC, 37),
+                };
+                *c = alphabet[(value % base) as usize];
+                value /= base;
+            }
+            let call = String::from_utf8_lossy(&call).trim().to_string();
+            (value == 0 && !call.is_empty()).then_some(call)
+        }
+        _ => None,
+    }
+}
+
+/// Packs an exchange into its acknowledgement bit and 15-bit value.
+fn pack_exchange(exchange: &str) -> Option<(bool, u16)> {
+    let (acknowledged, rest) = match exchange.strip_prefix('R') {
+        Some(rest) if rest.starts_with(['+', '-', ' ']) => (true, rest.trim_start()),
+        _ => (false, exchange),
+    };
+    let value = match rest.as_bytes() {
+        [] if !acknowledged => GRIDS + 1,
+        b"RRR" if !acknowledged => GRIDS + 2,
+        b"RR73" if !acknowledged => GRIDS + 3,
+        b"73" if !acknowledged => GRIDS + 4,
+        [field @ b'A'..=b'R', square @ b'A'..=b'R', east @ b'0'..=b'9', north @ b'0'..=b'9'] => {
+            let field = u16::from(field - b'A') * 18 + u16::from(square - b'A');
+            (field * 10 + u16::from(east - b'0')) * 10 + u16::from(north - b'0')
+        }
+        [b'+' | b'-', ..] => {
+            let report: i16 = rest.parse().ok()?;
+            if !(-30..=99).contains(&report) {
+                return None;
+            }
+            GRIDS + (report + 35) as u16
+        }
+        _ => return None,
+    };
+    Some((acknowledged, value))
+}
+
+fn unpack_exchange(acknowledged: bool, value: u16) -> Option<String> {
+    let prefix = if acknowledged { "R" } else { "" };
+    if value < GRIDS {
+        let (field, square) = (value / 100 / 18, value / 100 % 18);
+        let grid = format!(
+            "{}{}{:02}",
+            char::from(b'A' + field as u8),
+            char::from(b'A' + square as u8),
+            value % 100
+        );
+        return Some(if acknowledged {
+            format!("R {grid}")
+        } else {
+            grid
+        });
+    }
+    let exchange = match value - GRIDS {
+        1 => String::new(),
+        2 => "RRR".to_string(),
+        3 => "RR73".to_string(),
+        4 => "73".to_string(),
+        report => format!("{:+03}", i32::from(report) - 35),
+    };
+    (!acknowledged || value - GRIDS > 4).then(|| format!("{prefix}{exchange}"))
+}
+
+fn position(alphabet: &[u8], c: u8) -> Option<u32> {
+    alphabet
+        .iter()
+        .position(|&a| a == c)
+        .map(|index| index as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_messages() {
+        for text in [
+            "CQ K1ABC FN42",
+            "CQ DX VK2DEF QF56",
+            "CQ 123 W9XYZ",
+            "K1ABC W9XYZ -15",
+            "W9XYZ K1ABC R-09",
+            "K1ABC W9XYZ RR73",
+            "K1ABC/R W9XYZ R EN37",
+            "G4ABC/P PA9XYZ 73",
+            "QRZ K1ABC",
+            "TNX BOB 73 GL",
+        ] {
+            let message = Ft8Message::parse(text).unwrap();
+            let payload = message.pack().unwrap();
+            assert!(payload < 1 << 77);
+            let unpacked = Ft8Message::unpack(payload).unwrap();
+            assert_eq!(unpacked, message);
+            assert_eq!(unpacked.to_string(), text);
+        }
+        assert!(matches!(
+            Ft8Message::parse("TNX BOB 73 GL"),
+            Some(Ft8Message::FreeText(_))
+        ));
+        let telemetry = Ft8Message::Telemetry("7DEADBEEF".to_string());
+        assert_eq!(
+            Ft8Message::unpack(telemetry.pack().unwrap()),
+            Some(telemetry)
+        );
+    }
+
+    #[test]
+    fn test_rejects_what_does_not_fit() {
+        // Too long for free text, and with a callsign too long to pack.
+        assert_eq!(Ft8Message::parse("CQ PJ4/K1ABC FK52"), None);
+        assert_eq!(Ft8Message::parse("THIS IS TOO LONG"), None);
+        assert_eq!(pack_exchange("SS12"), None);
+        assert_eq!(pack_exchange("R73"), None);
+        assert_eq!(unpack_call(HASHES - 1), Some("<...>".to_string()));
+        assert_eq!(
+            Ft8Message::unpack(3),
+            Some(Ft8Message::Other { i3: 3, n3: 0 })
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Simple OOK devices on the ISM bands, in the manner of rtl_433.
+//!
+//! Weather stations, doorbells, remotes and the like key a carrier on and
+//! off at 315, 433.92 or 868 MHz, with bits in the width of each pulse or
+//! of the gap after it, and repeat each message a few times with a longer
+//! gap between. Their makers agree on little else, so the work splits in
+//! two. [`IsmReceiver`] cuts complex baseband into bursts, slices each
+//! burst's envelope into a [`PulseTrain`], and has a [`Demodulator`] read
+//! the pulses as rows of bits, a row ending at each long gap. Each
+//! [`DeviceDecoder`] says how its device's pulses carry bits and turns the
+//! rows into a message, or declines the burst. [`devices`] holds some
+//! common ones.
+
+pub mod devices;
+
+use crate::block::Block;
+use crate::detect::burst::{Burst, BurstConfig, BurstExtractor};
+use crate::dsp::envelope::{EnvelopeDetector, Smoothing};
+use crate::param::ParamValue;
+use num_complex::Complex;
+use std::fmt::Debug;
+
+/// One pulse of carrier and the gap that follows it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pulse {
+    pub pulse_us: f64,
+    /// Up to the next pulse, or to the end of the burst after the last.
+    pub gap_us: f64,
+}
+
+/// The pulses of one burst.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PulseTrain {
+    /// Index in the stream of the first sample of the burst.
+    pub sample: u64,
+    pub pulses: Vec<Pulse>,
+}
+
+impl PulseTrain {
+    /// The pulses in `samples`, the first of which is index `sample` in the
+    /// stream. The envelope is the RMS over `smoothing` samples, read as on
+    /// above half its peak and as off again below a third.
+    pub fn extract(
+        samples: &[Complex<f64>],
+        sample_rate: f64,
+        sample: u64,
+        smoothing: usize,
+    ) -> Self {
+        let mut detector = EnvelopeDetector::new(Smoothing::Rms { window: smoothing });
+        let envelope: Vec<f64> = samples.iter().map(|&z| detector.envelope(z)).collect();
+        let peak = envelope.iter().copied().fold(0.0, f64::max);
+        let us = |samples: usize| samples as f64 * 1e6 / sample_rate;
+        let mut pulses = Vec::new();
+        let mut on = false;
+        // Samples in the current pulse and gap, from the first pulse.
+        let mut run = [0, 0];
+        for &level in &envelope {
+            if on && level < peak / 3.0 {
+                on = false;
+            } else if !on && level > peak / 2.0 {
+                on = true;
+                if run[0] > 0 {
+                    pulses.push(Pulse {
+                        pulse_us: us(run[0]),
+                        gap_us: us(run[1]),
+                    });
+                }
+                run = [0, 0];
+            }
+            if on {
+                run[0] += 1;
+            } else if run[0] > 0 {
+                run[1] += 1;
+            }
+        }
+        if run[0] > 0 {
+            pulses.push(Pulse {
+                pulse_us: us(run[0]),
+                gap_us: us(run[1]),
+            });
+        }
+        PulseTrain { sample, pulses }
+    }
+}
+
+/// How a device's pulses carry bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PulseCoding {
+    /// Pulses of one width, with a short gap after a zero and a long one
+    /// after a one. Also called pulse-position modulation.
+    PulseDistance { short_us: f64, long_us: f64 },
+    /// A short pulse for a zero and a long one for a one, whatever the gap.
+    PulseWidth { short_us: f64, long_us: f64 },
+}
+
+/// Reads a [`PulseTrain`] as rows of bits.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Demodulator {
+    pub coding: PulseCoding,
+    /// A gap at least this long ends a row, in µs.
+    pub reset_us: f64,
+    /// How far a width may stray from the nearer of its two nominal
+    /// widths, as a fraction of that width. A width further from both than
+    /// this also ends the row, without a bit.
+    pub tolerance: f64,
+}
+
+impl Demodulator {
+    /// Allows widths a third either side of nominal.
+    pub fn new(coding: PulseCoding, reset_us: f64) -> Self {
+        Demodulator {
+            coding,
+            reset_us,
+            tolerance: 1.0 / 3.0,
+        }
+    }
+
+    /// The rows of bits in `train`, leaving out empty ones.
+    pub fn rows(&self, train: &PulseTrain) -> Vec<Vec<bool>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        for pulse in &train.pulses {
+            let reset = pulse.gap_us >= self.reset_us;
+            let bit = match self.coding {
+                // The gap that ends a row carries no bit.
+                PulseCoding::PulseDistance { .. } if reset => None,
+                PulseCoding::PulseDistance { short_us, long_us } => {
+                    self.classify(pulse.gap_us, short_us, long_us)
+                }
+                PulseCoding::PulseWidth { short_us, long_us } => {
+                    self.classify(pulse.pulse_us, short_us, long_us)
+                }
+            };
+            match bit {
+                Some(bit) => row.push(bit),
+                None => reset_row(&mut rows, &mut row),
+            }
+            if reset {
+                reset_row(&mut rows, &mut row);
+            }
+        }
+        reset_row(&mut rows, &mut row);
+        rows
+    }
+
+    fn classify(&self, width: f64, short: f64, long: f64) -> Option<bool> {
+        let within = |nominal: f64| (width - nominal).abs() <= self.tolerance * nominal;
+        match (within(short), within(long)) {
+            (true, false) => Some(false),
+            (false, true) => Some(true),
+            (true, true) => Some((width - short).abs() > (width - long).abs()),
+            (false, false) => None,
+        }
+    }
+}
+
+fn reset_row(rows: &mut Vec<Vec<bool>>, row: &mut Vec<bool>) {
+    if !row.is_empty() {
+        rows.push(std::mem::take(row));
+    }
+}
+
+/// The `length` bits of `row` from `start`, oldest highest, if the row is
+/// long enough. For pulling fields out of rows.
+pub fn field(row: &[bool], start: usize, length: usize) -> Option<u64> {
+    let bits = row.get(start..start + length)?;
+    Some(
+        bits.iter()
+            .fold(0, |value, &bit| value << 1 | u64::from(bit)),
+    )
+}
+
+/// A decoder for one kind of device.
+pub trait DeviceDecoder: Debug + Send {
+    /// Names the device, as it appears in each [`IsmMessage`].
+    fn name(&self) -> &'static str;
+
+    /// How the device's pulses carry bits.
+    fn demodulator(&self) -> Demodulator;
+
+    /// The message's fields from the rows of one burst, or `None` if they
+    /// do not come from this device.
+    fn decode(&self, rows: &[Vec<bool>]) -> Option<Vec<(&'static str, ParamValue)>>;
+}
+
+/// One decoded message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IsmMessage {
+    /// Index in the stream of the first sample of the burst.
+    pub sample: u64,
+    pub device: &'static str,
+    pub fields: Vec<(&'static str, ParamValue)>,
+}
+
+impl IsmMessage {
+    pub fn field(&self, name: &str) -> Option<&ParamValue> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Decodes OOK bursts with whichever devices it has been given.
+#[derive(Debug)]
+pub struct IsmReceiver {
+    sample_rate: f64,
+    extractor: BurstExtractor,
+    devices: Vec<Box<dyn DeviceDecoder>>,
+}
+
+impl IsmReceiver {
+    /// A receiver with no devices.
+    pub fn new(sample_rate: f64) -> Self {
+        let mut config = BurstConfig::new(sample_rate);
+        // Spans the gaps between a message's repeats, so that they arrive
+        // together.
+        config.smoothing = (100e-6 * sample_rate).ceil() as usize;
+        config.post_samples = (20e-3 * sample_rate) as usize;
+        config.min_length = config.smoothing * 2;
+        IsmReceiver {
+            sample_rate,
+            extractor: BurstExtractor::new(config),
+            devices: Vec::new(),
+        }
+    }
+
+    /// A receiver with every device in [`devices`].
+    pub fn with_builtin_devices(sample_rate: f64) -> Self {
+        let mut receiver = IsmReceiver::new(sample_rate);
+        receiver.devices = devices::builtin();
+        receiver
+    }
+
+    pub fn add_device(&mut self, device: Box<dyn DeviceDecoder>) {
+        self.devices.push(device);
+    }
+
+    pub fn device_names(&self) -> Vec<&'static str> {
+        self.devices.iter().map(|device| device.name()).collect()
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Processes samples and returns the messages in the bursts that ended
+    /// within them.
+    pub fn process(&mut self, samples: &[Complex<f64>]) -> Vec<IsmMessage> {
+        let bursts = self.extractor.process(samples);
+        bursts.iter().flat_map(|burst| self.decode(burst)).collect()
+    }
+
+    /// Decodes the burst in progress, if any, as at the end of a recording.
+    pub fn flush(&mut self) -> Vec<IsmMessage> {
+        let burst = self.extractor.flush();
+        burst.iter().flat_map(|burst| self.decode(burst)).collect()
+    }
+
+    /// The burst's pulses, as the devices see them.
+    pub fn pulses(&self, burst: &Burst) -> PulseTrain {
+        let smoothing = (10e-6 * self.sample_rate).round().max(1.0) as usize;
+        PulseTrain::extract(
+            &burst.samples,
+            self.sample_rate,
+            burst.start_sample,
+            smoothing,
+        )
+    }
+
+    /// The messages in one burst, from whichever devices accept it.
+    pub fn decode(&self, burst: &Burst) -> Vec<IsmMessage> {
+        let train = self.pulses(burst);
+        self.devices
+            .iter()
+            .filter_map(|device| {
+                let fields = device.decode(&device.demodulator().rows(&train))?;
+                trace_event!(debug, device = device.name(), "ISM message");
+                Some(IsmMessage {
+                    sample: train.sample,
+                    device: device.name(),
+                    fields,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Block for IsmReceiver {
+    type Input = Complex<f64>;
+    type Output = IsmMessage;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<IsmMessage>) -> usize {
+        output.extend(self.process(input));
+        input.len()
+    }
+}
+
+/// Baseband keyed on for each pulse, within a little noise, for testing
+/// decoders.
+#[cfg(test)]
+pub(crate) fn modulate(pulses: &[Pulse], sample_rate: f64) -> Vec<Complex<f64>> {
+    let samples = |us: f64| (us * 1e-6 * sample_rate).round() as usize;
+    let mut baseband = vec![Complex::new(0.0, 0.0); 5_000];
+    for pulse in pulses {
+        baseband.extend(vec![Complex::new(0.7, 0.7); samples(pulse.pulse_us)]);
+        baseband.extend(vec![Complex::new(0.0, 0.0); samples(pulse.gap_us)]);
+    }
+    baseband.extend(vec![Complex::new(0.0, 0.0); 5_000]);
+    for (sample, noise) in baseband
+        .iter_mut()
+        .zip(crate::bench::complex_noise(1 << 20, 17))
+    {
+        *sample += 0.05 * noise;
+    }
+    baseband
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 250_000.0;
+
+    fn pulse(pulse_us: f64, gap_us: f64) -> Pulse {
+        Pulse { pulse_us, gap_us }
+    }
+
+    #[test]
+    fn test_extracts_and_demodulates_pulses() {
+        let sent = [
+            pulse(500.0, 1_000.0),
+            pulse(500.0, 2_000.0),
+            pulse(500.0, 2_000.0),
+            pulse(500.0, 6_000.0),
+            pulse(500.0, 1_000.0),
+            pulse(500.0, 2_000.0),
+        ];
+        let mut receiver = IsmReceiver::new(RATE);
+        let mut bursts = receiver.extractor.process(&modulate(&sent, RATE));
+        bursts.extend(receiver.extractor.flush());
+        assert_eq!(bursts.len(), 1);
+        let train = receiver.pulses(&bursts[0]);
+        assert_eq!(train.pulses.len(), sent.len());
+        for (got, sent) in train.pulses.iter().zip(&sent[..5]) {
+            assert!((got.pulse_us - sent.pulse_us).abs() < 20.0, "{got:?}");
+            assert!((got.gap_us - sent.gap_us).abs() < 20.0, "{got:?}");
+        }
+
+        let ppm = Demodulator::new(
+            PulseCoding::PulseDistance {
+                short_us: 1_000.0,
+                long_us: 2_000.0,
+            },
+            4_000.0,
+        );
+        // The last gap runs on into the burst's tail.
+        let rows = ppm.rows(&train);
+        assert_eq!(rows, [vec![false, true, true], vec![false]]);
+        assert_eq!(field(&rows[0], 1, 2), Some(0b11));
+        assert_eq!(field(&rows[1], 0, 2), None);
+
+        // By width, a pulse that fits neither ends the row.
+        let pwm = Demodulator::new(
+            PulseCoding::PulseWidth {
+                short_us: 300.0,
+                long_us: 900.0,
+            },
+            5_000.0,
+        );
+        let train = PulseTrain {
+            sample: 0,
+            pulses: vec![
+                pulse(300.0, 900.0),
+                pulse(900.0, 300.0),
+                pulse(500.0, 700.0),
+                pulse(280.0, 900.0),
+                pulse(950.0, 9_000.0),
+                pulse(310.0, 900.0),
+            ],
+        };
+        assert_eq!(
+            pwm.rows(&train),
+            [vec![false, true], vec![false, true], vec![false]]
+        );
+        assert!(IsmReceiver::new(RATE).decode(&bursts[0]).is_empty());
+    }
+}
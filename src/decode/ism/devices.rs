@@ -0,0 +1,198 @@
+//! Decoders for some common devices.
+
+use super::{field, Demodulator, DeviceDecoder, PulseCoding};
+use crate::param::ParamValue;
+
+/// Every decoder here, with its usual timings.
+pub fn builtin() -> Vec<Box<dyn DeviceDecoder>> {
+    vec![Box::new(Ev1527::new(350.0)), Box::new(Nexus)]
+}
+
+/// The row that appears at least twice among `rows`, cut to `length`
+/// bits, for devices without a checksum.
+fn repeated(rows: &[Vec<bool>], length: usize) -> Option<&[bool]> {
+    let rows: Vec<&[bool]> = rows.iter().filter_map(|row| row.get(..length)).collect();
+    rows.iter()
+        .enumerate()
+        .find(|(i, row)| rows[i + 1..].contains(row))
+        .map(|(_, row)| *row)
+}
+
+/// Remotes, doorbells and alarm sensors built on the EV1527 encoder and
+/// its clones: 24 bits by pulse width, a 20-bit ID burnt in at the factory
+/// and four key bits, each word led by a short pulse and a gap of 31 units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ev1527 {
+    /// The short pulse, in µs, set by the encoder's oscillator resistor.
+    pub unit_us: f64,
+}
+
+impl Ev1527 {
+    pub fn new(unit_us: f64) -> Self {
+        Ev1527 { unit_us }
+    }
+}
+
+impl DeviceDecoder for Ev1527 {
+    fn name(&self) -> &'static str {
+        "EV1527"
+    }
+
+    fn demodulator(&self) -> Demodulator {
+        Demodulator::new(
+            PulseCoding::PulseWidth {
+                short_us: self.unit_us,
+                long_us: 3.0 * self.unit_us,
+            },
+            8.0 * self.unit_us,
+        )
+    }
+
+    /// Each row holds a word, with the next word's leading pulse after it
+    /// but for the last.
+    fn decode(&self, rows: &[Vec<bool>]) -> Option<Vec<(&'static str, ParamValue)>> {
+        let rows: Vec<Vec<bool>> = rows
+            .iter()
+            .filter(|row| matches!(row.len(), 24 | 25))
+            .cloned()
+            .collect();
+        let word = repeated(&rows, 24)?;
+        Some(vec![
+            ("id", ParamValue::Int(field(word, 0, 20)? as i64)),
+            ("keys", ParamValue::Int(field(word, 20, 4)? as i64)),
+        ])
+    }
+}
+
+/// Nexus temperature and humidity sensors, also sold as Sencor, Rubicson
+/// and others: 36 bits by pulse distance, repeated a dozen times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nexus;
+
+impl DeviceDecoder for Nexus {
+    fn name(&self) -> &'static str {
+        "Nexus-TH"
+    }
+
+    fn demodulator(&self) -> Demodulator {
+        Demodulator::new(
+            PulseCoding::PulseDistance {
+                short_us: 1_000.0,
+                long_us: 2_000.0,
+            },
+            3_000.0,
+        )
+    }
+
+    /// An 8-bit ID picked at power up, a low battery bit, a zero, the
+    /// channel less one, the temperature in tenths of a degree as 12 bits
+    /// of two's complement, four ones and the relative humidity.
+    fn decode(&self, rows: &[Vec<bool>]) -> Option<Vec<(&'static str, ParamValue)>> {
+        let rows: Vec<Vec<bool>> = rows.iter().filter(|row| row.len() == 36).cloned().collect();
+        let word = repeated(&rows, 36)?;
+        if field(word, 24, 4)? != 0xf || word[9] {
+            return None;
+        }
+        let temperature = (field(word, 12, 12)? as i16) << 4 >> 4;
+        let humidity = field(word, 28, 8)?;
+        if humidity > 100 {
+            return None;
+        }
+        Some(vec![
+            ("id", ParamValue::Int(field(word, 0, 8)? as i64)),
+            ("channel", ParamValue::Int(field(word, 10, 2)? as i64 + 1)),
+            ("battery_ok", ParamValue::Bool(!word[8])),
+            (
+                "temperature_c",
+                ParamValue::Float(f64::from(temperature) / 10.0),
+            ),
+            ("humidity", ParamValue::Int(humidity as i64)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{modulate, IsmReceiver, Pulse};
+    use crate::block::Block;
+    use super::*;
+
+    const RATE: f64 = 250_000.0;
+
+    fn bits(value: u64, length: usize) -> impl Iterator<Item = bool> {
+        (0..length).rev().map(move |k| value >> k & 1 == 1)
+    }
+
+    fn ev1527(word: u32, unit: f64) -> Vec<Pulse> {
+        let mut pulses = Vec::new();
+        for _ in 0..4 {
+            pulses.push(Pulse {
+                pulse_us: unit,
+                gap_us: 31.0 * unit,
+            });
+            pulses.extend(bits(u64::from(word), 24).map(|bit| match bit {
+                true => Pulse {
+                    pulse_us: 3.0 * unit,
+                    gap_us: unit,
+                },
+                false => Pulse {
+                    pulse_us: unit,
+                    gap_us: 3.0 * unit,
+                },
+            }));
+        }
+        pulses
+    }
+
+    fn nexus(word: u64) -> Vec<Pulse> {
+        let mut pulses = Vec::new();
+        for _ in 0..12 {
+            pulses.extend(bits(word, 36).map(|bit| Pulse {
+                pulse_us: 500.0,
+                gap_us: if bit { 2_000.0 } else { 1_000.0 },
+            }));
+            pulses.push(Pulse {
+                pulse_us: 500.0,
+                gap_us: 4_000.0,
+            });
+        }
+        pulses
+    }
+
+    #[test]
+    fn test_decodes_a_remote_and_a_thermometer() {
+        let mut baseband = modulate(&ev1527(0xa5c392, 320.0), RATE);
+        // ID 0x3c, low battery, channel 2, -12.5 °C and 61 %.
+        let temperature = (-125i64 as u64) & 0xfff;
+        let word = 0x3c << 28 | 0b1001 << 24 | temperature << 12 | 0xf << 8 | 61;
+        baseband.extend(modulate(&nexus(word), RATE));
+        let mut receiver = IsmReceiver::with_builtin_devices(RATE);
+        let mut messages = Vec::new();
+        receiver.work(&baseband, &mut messages);
+        messages.extend(receiver.flush());
+        assert_eq!(messages.len(), 2, "{messages:?}");
+
+        assert_eq!(messages[0].device, "EV1527");
+        assert_eq!(messages[0].field("id"), Some(&ParamValue::Int(0xa5c39)));
+        assert_eq!(messages[0].field("keys"), Some(&ParamValue::Int(2)));
+
+        let thermometer = &messages[1];
+        assert_eq!(thermometer.device, "Nexus-TH");
+        assert!(thermometer.sample > messages[0].sample);
+        let expected = [
+            ("id", ParamValue::Int(0x3c)),
+            ("channel", ParamValue::Int(2)),
+            ("battery_ok", ParamValue::Bool(false)),
+            ("temperature_c", ParamValue::Float(-12.5)),
+            ("humidity", ParamValue::Int(61)),
+        ];
+        assert_eq!(thermometer.fields, expected);
+
+        // Heard once, a word with no checksum is not to be trusted.
+        let once = ev1527(0x123456, 350.0)[..25].to_vec();
+        let mut receiver = IsmReceiver::with_builtin_devices(RATE);
+        let mut messages = receiver.process(&modulate(&once, RATE));
+        messages.extend(receiver.flush());
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+}
@@ -0,0 +1,520 @@
+//! NAVTEX, the maritime safety broadcasts on 518 kHz, in English, and
+//! 490 kHz, in national languages.
+//!
+//! Stations send SITOR-B: FSK at 100 baud with a 170 Hz shift, each
+//! character a seven-bit CCIR 476 code with exactly four bits set, so that
+//! most corruptions leave an invalid code. Every character is sent twice,
+//! the repeat five character times after the first, with others between,
+//! to ride out fades of up to 280 ms. A transmission opens with phasing
+//! signals alternating between the two positions, and each message in it
+//! runs from `ZCZC` and a four-character header, naming the station, the
+//! subject and a serial number, to `NNNN`.
+//!
+//! [`NavtexReceiver`] takes audio from an SSB receiver, with the two tones
+//! either side of [`NavtexConfig::center`]. It demodulates and slices the
+//! audio, recovers the bit clock from its transitions, finds character
+//! boundaries from the phasing signals, and takes each character from
+//! whichever copy is valid, printing `*` where neither is.
+
+use crate::block::Block;
+use crate::dsp::clock::ZeroCrossingClock;
+use crate::dsp::fir::{lowpass, Fir};
+use crate::dsp::fm::InstantaneousFrequency;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use std::f64::consts::TAU;
+
+/// The international NAVTEX frequency, in Hz.
+pub const NAVTEX_INTERNATIONAL: f64 = 518e3;
+/// The national NAVTEX frequency, in Hz.
+pub const NAVTEX_NATIONAL: f64 = 490e3;
+/// The symbol rate, in baud.
+pub const NAVTEX_BAUD: f64 = 100.0;
+/// The distance between the two tones in Hz.
+pub const NAVTEX_SHIFT: f64 = 170.0;
+
+/// What a CCIR 476 code stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Code {
+    /// A character in letters shift, and in figures shift if it has one.
+    Text(char, Option<char>),
+    Letters,
+    Figures,
+    /// The phasing signals and idle signals, which print nothing.
+    Alpha,
+    Beta,
+    Repeat,
+    Unused,
+}
+
+/// The 35 CCIR 476 codes, their first bit lowest, with B, the lower tone, as
+/// one.
+const CODES: [(u8, Code); 35] = [
+    (0x0f, Code::Alpha),
+    (0x17, Code::Text('J', Some('\u{7}'))),
+    (0x1b, Code::Text('F', None)),
+    (0x1d, Code::Text('C', Some(':'))),
+    (0x1e, Code::Text('K', Some('('))),
+    (0x27, Code::Text('W', Some('2'))),
+    (0x2b, Code::Text('Y', Some('6'))),
+    (0x2d, Code::Text('P', Some('0'))),
+    (0x2e, Code::Text('Q', Some('1'))),
+    (0x33, Code::Beta),
+    (0x35, Code::Text('G', None)),
+    (0x36, Code::Figures),
+    (0x39, Code::Text('M', Some('.'))),
+    (0x3a, Code::Text('X', Some('/'))),
+    (0x3c, Code::Text('V', Some('='))),
+    (0x47, Code::Text('A', Some('-'))),
+    (0x4b, Code::Text('S', Some('\''))),
+    (0x4d, Code::Text('I', Some('8'))),
+    (0x4e, Code::Text('U', Some('7'))),
+    (0x53, Code::Text('D', None)),
+    (0x55, Code::Text('R', Some('4'))),
+    (0x56, Code::Text('E', Some('3'))),
+    (0x59, Code::Text('N', Some(','))),
+    (0x5a, Code::Letters),
+    (0x5c, Code::Text(' ', Some(' '))),
+    (0x63, Code::Text('Z', Some('+'))),
+    (0x65, Code::Text('L', Some(')'))),
+    (0x66, Code::Repeat),
+    (0x69, Code::Text('H', None)),
+    (0x6a, Code::Unused),
+    (0x6c, Code::Text('\n', Some('\n'))),
+    (0x71, Code::Text('O', Some('9'))),
+    (0x72, Code::Text('B', Some('?'))),
+    (0x74, Code::Text('T', Some('5'))),
+    (0x78, Code::Text('\r', Some('\r'))),
+];
+
+const ALPHA: u8 = 0x0f;
+const REPEAT: u8 = 0x66;
+
+fn lookup(code: u8) -> Option<Code> {
+    CODES
+        .iter()
+        .find(|&&(c, _)| c == code)
+        .map(|&(_, meaning)| meaning)
+}
+
+/// Settings for [`NavtexReceiver`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavtexConfig {
+    pub sample_rate: f64,
+    /// The audio frequency midway between the tones, in Hz.
+    pub center: f64,
+}
+
+impl NavtexConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        NavtexConfig {
+            sample_rate,
+            center: 1_000.0,
+        }
+    }
+}
+
+/// A received message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavtexMessage {
+    /// Index in the stream of the sample at which the message ended.
+    pub sample: u64,
+    /// The transmitting station's letter.
+    pub station: char,
+    /// The subject indicator, such as `A` for a navigational warning.
+    pub subject: char,
+    pub serial: u8,
+    /// The body, between the header and `NNNN`.
+    pub text: String,
+    /// Characters lost in both copies, or without a figure, printed as `*`.
+    pub errors: usize,
+    /// Whether the message ran to its `NNNN`, rather than being cut off by
+    /// losing the signal.
+    pub complete: bool,
+}
+
+impl NavtexMessage {
+    /// What the subject indicator means.
+    pub fn subject_description(&self) -> &'static str {
+        match self.subject {
+            'A' => "navigational warning",
+            'B' => "meteorological warning",
+            'C' => "ice report",
+            'D' => "search and rescue information or piracy warning",
+            'E' => "meteorological forecast",
+            'F' => "pilot service message",
+            'G' => "AIS message",
+            'H' => "LORAN message",
+            'J' => "satellite navigation message",
+            'K' => "other electronic navigation aid message",
+            'L' => "navigational warning, additional to A",
+            'V' | 'W' | 'X' | 'Y' => "special service",
+            'Z' => "no messages on hand",
+            _ => "unassigned",
+        }
+    }
+}
+
+/// A message being received: its header once read and its body.
+#[derive(Debug, Clone, Default)]
+struct Partial {
+    header: String,
+    body: String,
+    /// The length of the body up to the last character whose copies agreed,
+    /// past which it may be noise if the signal is lost.
+    agreed: usize,
+}
+
+/// Character alignment and the copies awaiting their repeats.
+#[derive(Debug, Clone)]
+struct Lock {
+    /// Bits into the current character.
+    bits: usize,
+    /// Whether the latest character was a first copy.
+    dx: bool,
+    /// The last three first copies, the oldest being the one the next
+    /// repeat repeats.
+    history: [u8; 3],
+    /// Rises by two for each character lost in both copies and falls by
+    /// one for each received.
+    lost: usize,
+    figures: bool,
+}
+
+/// Receives NAVTEX messages from SSB audio.
+#[derive(Debug, Clone)]
+pub struct NavtexReceiver {
+    config: NavtexConfig,
+    phase: f64,
+    lowpass: Fir<Complex<f64>>,
+    frequency: InstantaneousFrequency,
+    clock: ZeroCrossingClock,
+    /// The latest 28 bits, the newest highest.
+    register: u32,
+    lock: Option<Lock>,
+    /// The last characters outside a message, looking for `ZCZC`.
+    tail: String,
+    message: Option<Partial>,
+    position: u64,
+}
+
+impl NavtexReceiver {
+    /// How high [`Lock::lost`] rises before the signal is taken to have
+    /// gone: four characters lost in a row, or a run of mostly lost ones as
+    /// when only noise remains.
+    const MAX_LOST: usize = 8;
+
+    pub fn new(config: NavtexConfig) -> Self {
+        let rate = config.sample_rate;
+        // Passes both tones with their 100 baud sidebands, with a transition
+        // band about 100 Hz wide.
+        let num_taps = (3.3 * rate / 100.0).ceil() as usize | 1;
+        NavtexReceiver {
+            phase: 0.0,
+            lowpass: Fir::new(&lowpass(num_taps, 200.0 / rate)),
+            frequency: InstantaneousFrequency::new(rate),
+            clock: ZeroCrossingClock::new(rate / NAVTEX_BAUD),
+            register: 0,
+            lock: None,
+            tail: String::new(),
+            message: None,
+            position: 0,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &NavtexConfig {
+        &self.config
+    }
+
+    /// Whether character boundaries are known, from phasing signals.
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Accepts one audio sample, returning a message if this sample
+    /// completes one.
+    pub fn push(&mut self, sample: f64) -> Option<NavtexMessage> {
+        self.position += 1;
+        let mixed = Complex::from_polar(sample, -self.phase);
+        self.phase = (self.phase + TAU * self.config.center / self.config.sample_rate) % TAU;
+        let frequency = self.frequency.frequency(self.lowpass.filter(mixed));
+        // B, a one, is the lower tone.
+        let level = self.clock.push(-frequency)?;
+        self.register = self.register >> 1 | u32::from(level > 0.0) << 27;
+        self.bit()
+    }
+
+    fn bit(&mut self) -> Option<NavtexMessage> {
+        let character = |k: u32| (self.register >> (7 * k)) as u8 & 0x7f;
+        let Some(lock) = &mut self.lock else {
+            // Four phasing signals in turn: alphas as first copies and
+            // repeat signals between them.
+            let latest: [u8; 4] = std::array::from_fn(|k| character(k as u32));
+            if latest == [ALPHA, REPEAT, ALPHA, REPEAT] || latest == [REPEAT, ALPHA, REPEAT, ALPHA]
+            {
+                trace_event!(debug, "NAVTEX phasing found");
+                self.lock = Some(Lock {
+                    bits: 0,
+                    dx: latest[3] == ALPHA,
+                    history: [ALPHA; 3],
+                    lost: 0,
+                    figures: false,
+                });
+            }
+            return None;
+        };
+        lock.bits += 1;
+        if lock.bits < 7 {
+            return None;
+        }
+        lock.bits = 0;
+        let code = character(3);
+        lock.dx = !lock.dx;
+        if lock.dx {
+            // Its repeat comes five characters on.
+            lock.history = [lock.history[1], lock.history[2], code];
+            return None;
+        }
+        let agreed = lock.history[0] == code;
+        let meaning = lookup(lock.history[0]).or(lookup(code));
+        if meaning.is_some() {
+            lock.lost = lock.lost.saturating_sub(1);
+        } else {
+            lock.lost += 2;
+            if lock.lost >= Self::MAX_LOST {
+                trace_event!(debug, "NAVTEX signal lost");
+                self.lock = None;
+                self.tail.clear();
+                return self.finish(false);
+            }
+        }
+        let character = match meaning {
+            None => '*',
+            Some(Code::Letters) => {
+                lock.figures = false;
+                return None;
+            }
+            Some(Code::Figures) => {
+                lock.figures = true;
+                return None;
+            }
+            Some(Code::Text(letter, figure)) => match lock.figures {
+                true => figure.unwrap_or('*'),
+                false => letter,
+            },
+            Some(_) => return None,
+        };
+        let message = self.print(character);
+        if let Some(partial) = self.message.as_mut().filter(|_| agreed) {
+            partial.agreed = partial.body.len();
+        }
+        message
+    }
+
+    /// Frames each printed character into messages.
+    fn print(&mut self, character: char) -> Option<NavtexMessage> {
+        let Some(message) = &mut self.message else {
+            self.tail.push(character);
+            if self.tail.len() > 4 {
+                self.tail.remove(0);
+            }
+            if self.tail == "ZCZC" {
+                self.message = Some(Partial::default());
+            }
+            return None;
+        };
+        if message.header.len() < 4 {
+            if character != ' ' {
+                message.header.push(character);
+            }
+            let header = message.header.as_bytes();
+            let valid = header.iter().enumerate().all(|(k, &c)| match k {
+                0 | 1 => c.is_ascii_uppercase(),
+                _ => c.is_ascii_digit(),
+            });
+            if !valid {
+                self.message = None;
+                self.tail.clear();
+            }
+            return None;
+        }
+        message.body.push(character);
+        if message.body.ends_with("NNNN") {
+            message.body.truncate(message.body.len() - 4);
+            return self.finish(true);
+        }
+        None
+    }
+
+    /// Hands over the message being received, if its header was read. One
+    /// cut off loses what followed its last agreeing copies, as noise
+    /// decodes as characters in one copy or the other about half the time.
+    fn finish(&mut self, complete: bool) -> Option<NavtexMessage> {
+        let mut message = self.message.take()?;
+        if !complete {
+            message.body.truncate(message.agreed);
+        }
+        let header: Vec<char> = message.header.chars().collect();
+        let &[station, subject, tens, units] = &header[..] else {
+            return None;
+        };
+        let digit = |c: char| c.to_digit(10).unwrap_or(0) as u8;
+        trace_event!(debug, station = ?station, complete, "NAVTEX message");
+        Some(NavtexMessage {
+            sample: self.position - 1,
+            station,
+            subject,
+            serial: 10 * digit(tens) + digit(units),
+            text: message.body.trim().to_string(),
+            errors: message.body.matches('*').count(),
+            complete,
+        })
+    }
+}
+
+impl Block for NavtexReceiver {
+    type Input = f64;
+    type Output = NavtexMessage;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<NavtexMessage>) -> usize {
+        output.extend(input.iter().filter_map(|&sample| self.push(sample)));
+        input.len()
+    }
+
+    /// Accepts `center`, in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("center", Some(center)) if center > 0.0 => self.config.center = center,
+            ("center", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+
+    const RATE: f64 = 8_000.0;
+
+    /// The codes for `text`, shifting between letters and figures as needed.
+    fn encode(text: &str) -> Vec<u8> {
+        let mut codes = Vec::new();
+        let mut figures = false;
+        for c in text.chars() {
+            // The shift needed, if it matters.
+            let (code, shift) = CODES
+                .iter()
+                .find_map(|&(code, meaning)| match meaning {
+                    Code::Text(letter, figure) if letter == c => {
+                        Some((code, (figure != Some(c)).then_some(false)))
+                    }
+                    Code::Text(_, Some(figure)) if figure == c => Some((code, Some(true))),
+                    _ => None,
+                })
+                .unwrap();
+            if shift.is_some_and(|shift| shift != figures) {
+                figures = !figures;
+                codes.push(if figures { 0x36 } else { 0x5a });
+            }
+            codes.push(code);
+        }
+        codes
+    }
+
+    /// Audio for phasing then `codes` in both positions, with `corrupt`
+    /// choosing which copies to spoil.
+    fn transmit(codes: &[u8], corrupt: impl Fn(usize, bool) -> bool) -> Vec<f64> {
+        let mut slots = Vec::new();
+        for _ in 0..8 {
+            slots.extend([ALPHA, REPEAT]);
+        }
+        for i in 0..codes.len() + 2 {
+            // Each first copy, and the repeat of the one two before it.
+            let dx = codes.get(i).copied().unwrap_or(ALPHA);
+            let rx = i.checked_sub(2).map_or(REPEAT, |j| codes[j]);
+            slots.push(if corrupt(i, true) { 0 } else { dx });
+            slots.push(if i >= 2 && corrupt(i - 2, false) {
+                0x7f
+            } else {
+                rx
+            });
+        }
+        let bit = (RATE / NAVTEX_BAUD) as usize;
+        let mut phase = 0.0;
+        let mut audio = vec![0.0; 2_000];
+        for code in slots {
+            for k in 0..7 {
+                let one = code >> k & 1 == 1;
+                let frequency = 1_000.0 + if one { -85.0 } else { 85.0 };
+                for _ in 0..bit {
+                    audio.push(0.5 * f64::sin(phase));
+                    phase = (phase + TAU * frequency / RATE) % TAU;
+                }
+            }
+        }
+        audio.extend(vec![0.0; 4 * RATE as usize]);
+        for (sample, noise) in audio.iter_mut().zip(real_noise(1 << 20, 6)) {
+            *sample += 0.1 * noise;
+        }
+        audio
+    }
+
+    #[test]
+    fn test_codes_have_four_bits_and_both_shifts_encode() {
+        for (k, &(code, _)) in CODES.iter().enumerate() {
+            assert_eq!(code.count_ones(), 4);
+            assert!(CODES[k + 1..].iter().all(|&(other, _)| other != code));
+        }
+        assert_eq!(encode("A1"), [0x47, 0x36, 0x2e]);
+    }
+
+    #[test]
+    fn test_receives_a_message_through_errors() {
+        let body = "GALE WARNING 1200 UTC\r\nWIND NW 8, SEA ROUGH.";
+        let text = format!("ZCZC EA42\r\n{body}\r\nNNNN\r\n");
+        let codes = encode(&text);
+        let target = encode(&text[..text.find("OUGH").unwrap()]).len();
+        // Spoil alternate first copies and repeats, and both copies of the O
+        // of ROUGH.
+        let audio = transmit(&codes, |i, dx| match dx {
+            true => i % 5 == 1 || i == target,
+            false => i % 5 == 3 || i == target,
+        });
+        let mut receiver = NavtexReceiver::new(NavtexConfig::new(RATE));
+        let mut messages = Vec::new();
+        receiver.work(&audio, &mut messages);
+        assert_eq!(messages.len(), 1, "{messages:?}");
+        let message = &messages[0];
+        assert_eq!(
+            (message.station, message.subject, message.serial),
+            ('E', 'A', 42)
+        );
+        assert_eq!(message.subject_description(), "navigational warning");
+        assert!(message.complete);
+        assert_eq!(message.errors, 1);
+        assert_eq!(message.text, body.replace("ROUGH", "R*UGH"));
+        assert!(!receiver.is_locked());
+    }
+
+    #[test]
+    fn test_reports_a_message_cut_off() {
+        let codes = encode("ZCZC SB07\r\nSTORM FORCE 10 EXPECTED");
+        let audio = transmit(&codes, |_, _| false);
+        let mut receiver = NavtexReceiver::new(NavtexConfig::new(RATE));
+        let mut messages = Vec::new();
+        receiver.work(&audio, &mut messages);
+        assert_eq!(messages.len(), 1, "{messages:?}");
+        assert_eq!(messages[0].text, "STORM FORCE 10 EXPECTED");
+        assert!(!messages[0].complete);
+        assert_eq!(messages[0].subject_description(), "meteorological warning");
+        assert!(receiver
+            .set_parameter("center", &ParamValue::Float(0.0))
+            .is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Baudot radioteletype (RTTY).
+//!
+//! Each character is a five-bit ITA2 code sent asynchronously: a space
+//! start bit, the code least significant bit first with mark as one, and
+//! a mark stop bit, usually one and a half bits long. The line idles at
+//! mark. Two of the codes switch between letters and figures, and the other
+//! thirty print one character in each. Amateurs send 45.45 baud with a
+//! 170 Hz shift, while weather and news services use 50 baud and shifts up
+//! to 850 Hz.
+//!
+//! [`RttyDecoder`] takes audio from an SSB receiver. Like the NAVTEX
+//! receiver, it mixes the midpoint between the tones down to zero, measures
+//! the instantaneous frequency, and takes its sign as mark or space. It
+//! recovers a clock at twice the baud rate from the transitions, so that
+//! the edges of one-and-a-half-bit stop bits still fall on it, and frames
+//! characters from pairs of half bits.
+
+use crate::block::Block;
+use crate::dsp::clock::ZeroCrossingClock;
+use crate::dsp::fir::{lowpass, Fir};
+use crate::dsp::fm::InstantaneousFrequency;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use std::f64::consts::TAU;
+
+/// The amateur symbol rate, in baud.
+pub const RTTY_BAUD_AMATEUR: f64 = 45.45;
+/// The symbol rate of most commercial and weather services, in baud.
+pub const RTTY_BAUD_COMMERCIAL: f64 = 50.0;
+
+/// What each code prints in letters shift, with NUL for the codes that
+/// print nothing.
+const LETTERS: &[u8; 32] = b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0";
+/// What each code prints in figures shift, with the US teleprinter's
+/// choices where ITA2 leaves it to national use.
+const FIGURES: &[u8; 32] = b"\x003\n- \x0787\r$4',!:(5\")2#6019?&\0./;\0";
+const FIGS: u8 = 0x1b;
+const LTRS: u8 = 0x1f;
+const SPACE: u8 = 0x04;
+
+/// Settings for [`RttyDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RttyConfig {
+    pub sample_rate: f64,
+    pub baud: f64,
+    /// The audio frequency of the mark tone, in Hz.
+    pub mark: f64,
+    /// The space tone's distance above the mark tone, in Hz; negative for
+    /// a signal sent the other way up.
+    pub shift: f64,
+    /// Whether a space returns to letters shift, as most amateur stations
+    /// expect, so that a lost LTRS garbles only one word.
+    pub unshift_on_space: bool,
+}
+
+impl RttyConfig {
+    /// Amateur settings: 45.45 baud, with the usual 2125 Hz mark and a
+    /// 170 Hz shift.
+    pub fn new(sample_rate: f64) -> Self {
+        RttyConfig {
+            sample_rate,
+            baud: RTTY_BAUD_AMATEUR,
+            mark: 2_125.0,
+            shift: 170.0,
+            unshift_on_space: true,
+        }
+    }
+}
+
+/// Decodes Baudot RTTY audio into text.
+#[derive(Debug, Clone)]
+pub struct RttyDecoder {
+    config: RttyConfig,
+    phase: f64,
+    lowpass: Fir<Complex<f64>>,
+    frequency: InstantaneousFrequency,
+    clock: ZeroCrossingClock,
+    /// The half bits of the character being received, from its start bit,
+    /// as levels with mark positive; empty between characters.
+    halves: Vec<f64>,
+    previous: f64,
+    figures: bool,
+    framing_errors: u64,
+}
+
+impl RttyDecoder {
+    /// Half bits from the start bit to the end of the first stop bit.
+    const HALVES: usize = 14;
+
+    pub fn new(config: RttyConfig) -> Self {
+        let rate = config.sample_rate;
+        // Passes both tones with their first sidebands, with a transition
+        // band twice the baud rate wide.
+        let cutoff = config.shift.abs() / 2.0 + config.baud;
+        let num_taps = (3.3 * rate / (2.0 * config.baud)).ceil() as usize | 1;
+        RttyDecoder {
+            phase: 0.0,
+            lowpass: Fir::new(&lowpass(num_taps, cutoff / rate)),
+            frequency: InstantaneousFrequency::new(rate),
+            clock: ZeroCrossingClock::new(rate / (2.0 * config.baud)),
+            halves: Vec::with_capacity(Self::HALVES),
+            previous: 1.0,
+            figures: false,
+            framing_errors: 0,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &RttyConfig {
+        &self.config
+    }
+
+    /// Whether figures shift is in effect.
+    pub fn is_figures(&self) -> bool {
+        self.figures
+    }
+
+    /// Characters dropped so far for want of a mark stop bit.
+    pub fn framing_errors(&self) -> u64 {
+        self.framing_errors
+    }
+
+    /// Accepts one audio sample, returning a character if this sample
+    /// completes one that prints.
+    pub fn push(&mut self, sample: f64) -> Option<char> {
+        let center = self.config.mark + self.config.shift / 2.0;
+        let mixed = Complex::from_polar(sample, -self.phase);
+        self.phase = (self.phase + TAU * center / self.config.sample_rate) % TAU;
+        let frequency = self.frequency.frequency(self.lowpass.filter(mixed));
+        // Mark lies on the opposite side of the centre to the shift.
+        let level = self.clock.push(-frequency * self.config.shift.signum())?;
+        let previous = std::mem::replace(&mut self.previous, level);
+        if self.halves.is_empty() {
+            // A start bit begins with the line's first drop from mark.
+            if level < 0.0 && previous >= 0.0 {
+                self.halves.push(level);
+            }
+            return None;
+        }
+        self.halves.push(level);
+        match self.halves.len() {
+            // A start bit too short to be one.
+            2 if level >= 0.0 => {
+                self.halves.clear();
+                None
+            }
+            Self::HALVES => {
+                let bit = |k: usize| self.halves[2 * k] + self.halves[2 * k + 1] > 0.0;
+                let stop = bit(6);
+                let code = (0..5).fold(0, |code, k| code | u8::from(bit(k + 1)) << k);
+                self.halves.clear();
+                if !stop {
+                    self.framing_errors += 1;
+                    return None;
+                }
+                self.character(code)
+            }
+            _ => None,
+        }
+    }
+
+    fn character(&mut self, code: u8) -> Option<char> {
+        match code {
+            FIGS => self.figures = true,
+            LTRS => self.figures = false,
+            SPACE if self.config.unshift_on_space => self.figures = false,
+            _ => {}
+        }
+        let table = if self.figures { FIGURES } else { LETTERS };
+        let c = table[usize::from(code)];
+        (c != 0).then_some(char::from(c))
+    }
+}
+
+impl Block for RttyDecoder {
+    type Input = f64;
+    type Output = char;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<char>) -> usize {
+        output.extend(input.iter().filter_map(|&sample| self.push(sample)));
+        input.len()
+    }
+
+    /// Accepts `mark`, in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("mark", Some(mark)) if mark > 0.0 => self.config.mark = mark,
+            ("mark", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+
+    const RATE: f64 = 8_000.0;
+
+    /// The codes for `text`, opening with LTRS and shifting as needed.
+    fn encode(text: &str) -> Vec<u8> {
+        let mut codes = vec![LTRS];
+        let mut figures = false;
+        for c in text.bytes() {
+            let letter = LETTERS.iter().position(|&l| l == c);
+            let figure = FIGURES.iter().position(|&f| f == c);
+            let code = match (letter, figure) {
+                (Some(code), Some(_)) => code,
+                (Some(code), None) => {
+                    if figures {
+                        codes.push(LTRS);
+                        figures = false;
+                    }
+                    code
+                }
+                (None, Some(code)) => {
+                    if !figures {
+                        codes.push(FIGS);
+                        figures = true;
+                    }
+                    code
+                }
+                (None, None) => panic!("no code for {c}"),
+            };
+            codes.push(code as u8);
+        }
+        codes
+    }
+
+    /// Audio for `codes` between stretches of idle mark, with `stop` bits
+    /// after each.
+    fn transmit(codes: &[u8], config: &RttyConfig, stop: f64, noise: f64) -> Vec<f64> {
+        let mut levels = vec![(true, 0.3)];
+        for &code in codes {
+            levels.push((false, 1.0));
+            levels.extend((0..5).map(|k| (code >> k & 1 == 1, 1.0)));
+            levels.push((true, stop));
+        }
+        levels.push((true, 0.3 * config.baud));
+        let bit = RATE / config.baud;
+        let mut phase = 0.0;
+        let mut audio = Vec::new();
+        let mut end = 0.0;
+        for (mark, bits) in levels {
+            end += bits * bit;
+            let frequency = config.mark + if mark { 0.0 } else { config.shift };
+            while (audio.len() as f64) < end {
+                audio.push(0.5 * f64::sin(phase));
+                phase = (phase + TAU * frequency / RATE) % TAU;
+            }
+        }
+        for (sample, n) in audio.iter_mut().zip(real_noise(1 << 20, 3)) {
+            *sample += noise * n;
+        }
+        audio
+    }
+
+    #[test]
+    fn test_decodes_amateur_rtty_through_noise() {
+        let text = "RYRYRY CQ CQ DE N0CALL N0CALL 599 K\r\n";
+        let config = RttyConfig::new(RATE);
+        let audio = transmit(&encode(text), &config, 1.5, 0.4);
+        let mut decoder = RttyDecoder::new(config);
+        let mut output = Vec::new();
+        decoder.work(&audio, &mut output);
+        assert_eq!(output.iter().collect::<String>(), text);
+        assert_eq!(decoder.framing_errors(), 0);
+        assert!(decoder
+            .set_parameter("mark", &ParamValue::Float(-1.0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_decodes_reversed_commercial_rtty_and_shifts() {
+        let config = RttyConfig {
+            baud: RTTY_BAUD_COMMERCIAL,
+            mark: 1_700.0,
+            shift: -425.0,
+            unshift_on_space: false,
+            ..RttyConfig::new(RATE)
+        };
+        let text = "WIND 270/15 KT, 3 M/S";
+        let audio = transmit(&encode(text), &config, 1.0, 0.1);
+        let mut decoder = RttyDecoder::new(config);
+        let mut output = Vec::new();
+        decoder.work(&audio, &mut output);
+        assert_eq!(output.iter().collect::<String>(), text);
+        assert!(!decoder.is_figures());
+
+        // A lost LTRS after a figure garbles the word that follows unless a
+        // space unshifts.
+        let mut codes = encode("12 ABC");
+        codes.retain(|&code| code != LTRS);
+        let mut unshifting = RttyDecoder::new(RttyConfig::new(RATE));
+        let config = unshifting.config().clone();
+        let mut output = Vec::new();
+        unshifting.work(&transmit(&codes, &config, 1.5, 0.1), &mut output);
+        assert_eq!(output.iter().collect::<String>(), "12 ABC");
+    }
+}
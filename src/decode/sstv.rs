@@ -0,0 +1,723 @@
+//! Slow-scan television (SSTV).
+//!
+//! SSTV sends a still image as audio whose frequency tracks brightness,
+//! from 1500 Hz for black to 2300 Hz for white, with 1200 Hz sync pulses
+//! between lines. A transmission opens with a VIS header: a 1900 Hz leader,
+//! then seven bits of mode code and an even parity bit, 30 ms each, at
+//! 1100 Hz for a one and 1300 Hz for a zero, between 1200 Hz start and stop
+//! bits.
+//!
+//! Martin and Scottie modes scan each line in green, blue and red in turn.
+//! Robot36 sends each line's luminance followed by one of its two
+//! colour-difference signals, alternating, so that each pair of lines shares
+//! both.
+//!
+//! [`SstvDecoder`] demodulates the frequency and waits for a VIS header. It
+//! then looks for each line's sync pulse near where the last one predicts,
+//! which follows a sender whose clock runs fast or slow and so would
+//! otherwise slant the image, and reads out the line's pixels.
+
+use crate::block::Block;
+use crate::dsp::fir::{lowpass, Fir};
+use crate::dsp::fm::InstantaneousFrequency;
+use num_complex::Complex;
+use std::f64::consts::TAU;
+
+const BLACK: f64 = 1_500.0;
+const WHITE: f64 = 2_300.0;
+const SYNC: f64 = 1_200.0;
+const LEADER: f64 = 1_900.0;
+/// The length of a VIS bit in milliseconds.
+const VIS_BIT: f64 = 30.0;
+
+/// A transmission mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SstvMode {
+    Martin1,
+    Martin2,
+    Scottie1,
+    Scottie2,
+    ScottieDx,
+    Robot36,
+}
+
+const MODES: [SstvMode; 6] = [
+    SstvMode::Martin1,
+    SstvMode::Martin2,
+    SstvMode::Scottie1,
+    SstvMode::Scottie2,
+    SstvMode::ScottieDx,
+    SstvMode::Robot36,
+];
+
+/// A mode's timing, in milliseconds.
+struct Timing {
+    sync: f64,
+    line: f64,
+    /// From the end of the VIS header to the start of the first line's sync
+    /// pulse.
+    first_sync: f64,
+    /// Where each channel starts relative to the start of its line's sync
+    /// pulse, and how long it lasts: green, blue and red for Martin and
+    /// Scottie, and for Robot36 luminance, the separator whose tone says
+    /// which colour difference follows, and that colour difference.
+    channels: [(f64, f64); 3],
+}
+
+impl SstvMode {
+    /// The mode's VIS code.
+    pub fn vis(self) -> u8 {
+        match self {
+            SstvMode::Martin1 => 44,
+            SstvMode::Martin2 => 40,
+            SstvMode::Scottie1 => 60,
+            SstvMode::Scottie2 => 56,
+            SstvMode::ScottieDx => 76,
+            SstvMode::Robot36 => 8,
+        }
+    }
+
+    pub fn from_vis(code: u8) -> Option<Self> {
+        MODES.into_iter().find(|mode| mode.vis() == code)
+    }
+
+    /// Pixels per line.
+    pub fn width(self) -> usize {
+        320
+    }
+
+    pub fn lines(self) -> usize {
+        match self {
+            SstvMode::Robot36 => 240,
+            _ => 256,
+        }
+    }
+
+    /// The time each line takes, in seconds.
+    pub fn line_duration(self) -> f64 {
+        self.timing().line / 1_000.0
+    }
+
+    fn timing(self) -> Timing {
+        let martin = |scan: f64| {
+            let (sync, porch) = (4.862, 0.572);
+            Timing {
+                sync,
+                line: sync + 4.0 * porch + 3.0 * scan,
+                first_sync: 0.0,
+                channels: [0, 1, 2]
+                    .map(|k| (sync + (k + 1) as f64 * porch + k as f64 * scan, scan)),
+            }
+        };
+        // Scottie's sync comes before red, mid-line, with one extra sync
+        // pulse before the first line.
+        let scottie = |scan: f64| {
+            let (sync, porch) = (9.0, 1.5);
+            Timing {
+                sync,
+                line: sync + 3.0 * porch + 3.0 * scan,
+                first_sync: sync + 2.0 * porch + 2.0 * scan,
+                channels: [
+                    (-(porch + 2.0 * scan), scan),
+                    (-scan, scan),
+                    (sync + porch, scan),
+                ],
+            }
+        };
+        match self {
+            SstvMode::Martin1 => martin(146.432),
+            SstvMode::Martin2 => martin(73.216),
+            SstvMode::Scottie1 => scottie(138.24),
+            SstvMode::Scottie2 => scottie(88.064),
+            SstvMode::ScottieDx => scottie(345.6),
+            SstvMode::Robot36 => Timing {
+                sync: 9.0,
+                line: 150.0,
+                first_sync: 0.0,
+                channels: [(12.0, 88.0), (100.0, 4.5), (106.0, 44.0)],
+            },
+        }
+    }
+}
+
+/// What the decoder has found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SstvEvent {
+    /// A VIS header announced an image.
+    Start {
+        /// Index in the stream of the sample at which the header was read.
+        sample: u64,
+        mode: SstvMode,
+    },
+    /// A row of RGB pixels, counting from zero at the top.
+    Line { row: usize, pixels: Vec<[u8; 3]> },
+    /// The image ended, `complete` if with all its lines rather than by
+    /// losing sync.
+    End { rows: usize, complete: bool },
+}
+
+/// An image being received.
+#[derive(Debug, Clone)]
+struct Image {
+    mode: SstvMode,
+    row: usize,
+    /// Where the next sync pulse should start, in samples.
+    sync: f64,
+    /// Lines in a row whose sync pulse was not found.
+    missed: usize,
+    /// For Robot36, an even line's luminance and colour difference, and
+    /// whether that is blue's, awaiting the odd line's.
+    pending: Option<RobotLine>,
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    /// Counting samples of leader tone, of start bit after it, and of
+    /// anything else.
+    Idle {
+        leader: usize,
+        low: usize,
+        other: usize,
+    },
+    /// Reading a VIS header whose start bit began at this sample.
+    Header {
+        start: usize,
+    },
+    Image(Image),
+}
+
+impl State {
+    fn idle() -> Self {
+        State::Idle {
+            leader: 0,
+            low: 0,
+            other: 0,
+        }
+    }
+}
+
+/// Decodes SSTV images from demodulated audio.
+#[derive(Debug, Clone)]
+pub struct SstvDecoder {
+    /// Samples per millisecond.
+    ms: f64,
+    /// Mixes 1900 Hz to zero for [`Self::lowpass`].
+    phase: f64,
+    step: f64,
+    lowpass: Fir<Complex<f64>>,
+    frequency: InstantaneousFrequency,
+    /// Recent instantaneous frequencies, the first at sample `base`.
+    frequencies: Vec<f64>,
+    base: usize,
+    state: State,
+}
+
+impl SstvDecoder {
+    /// How far either way a sync pulse may be from where it was predicted,
+    /// in milliseconds.
+    const SEARCH: f64 = 3.0;
+    /// Lines in a row without sync before an image is abandoned.
+    const MAX_MISSED: usize = 5;
+
+    pub fn new(sample_rate: f64) -> Self {
+        // Passes the 1100 to 2300 Hz band, mixed to ±800 Hz, with a
+        // transition band about 300 Hz wide.
+        let num_taps = (3.3 * sample_rate / 300.0).ceil() as usize | 1;
+        SstvDecoder {
+            ms: sample_rate / 1_000.0,
+            phase: 0.0,
+            step: TAU * LEADER / sample_rate,
+            lowpass: Fir::new(&lowpass(num_taps, 1_000.0 / sample_rate)),
+            frequency: InstantaneousFrequency::new(sample_rate),
+            frequencies: Vec::new(),
+            base: 0,
+            state: State::idle(),
+        }
+    }
+
+    /// The mode of the image being received, if any.
+    pub fn mode(&self) -> Option<SstvMode> {
+        match &self.state {
+            State::Image(image) => Some(image.mode),
+            _ => None,
+        }
+    }
+
+    /// Accepts one audio sample, appending any events it completes.
+    pub fn push(&mut self, sample: f64, output: &mut Vec<SstvEvent>) {
+        let mixed = Complex::from_polar(sample, -self.phase);
+        self.phase = (self.phase + self.step) % TAU;
+        let frequency = LEADER + self.frequency.frequency(self.lowpass.filter(mixed));
+        self.frequencies.push(frequency);
+        let state = std::mem::replace(&mut self.state, State::idle());
+        self.state = match state {
+            State::Idle { leader, low, other } => self.idle(frequency, leader, low, other),
+            State::Header { start } => self.header(start, output),
+            State::Image(image) => self.image(image, output),
+        };
+    }
+
+    /// The sample index one past the latest.
+    fn end(&self) -> usize {
+        self.base + self.frequencies.len()
+    }
+
+    /// Drops frequencies before sample `keep`.
+    fn forget(&mut self, keep: usize) {
+        let drop = keep.saturating_sub(self.base).min(self.frequencies.len());
+        self.frequencies.drain(..drop);
+        self.base += drop;
+    }
+
+    /// The mean frequency from sample `from` to `to`, at least one sample.
+    fn mean(&self, from: f64, to: f64) -> f64 {
+        let last = self.frequencies.len() - 1;
+        let index = |at: f64| {
+            (at.round().max(0.0) as usize)
+                .saturating_sub(self.base)
+                .min(last)
+        };
+        let (a, b) = (index(from), index(to).max(index(from) + 1).min(last + 1));
+        self.frequencies[a..b].iter().sum::<f64>() / (b - a) as f64
+    }
+
+    fn idle(
+        &mut self,
+        frequency: f64,
+        mut leader: usize,
+        mut low: usize,
+        mut other: usize,
+    ) -> State {
+        if (frequency - LEADER).abs() < 100.0 {
+            // Including the 10 ms break partway through the leader.
+            leader += 1 + low;
+            low = 0;
+            other = 0;
+        } else if (frequency - SYNC).abs() < (LEADER - SYNC) / 2.0
+            && leader as f64 >= 200.0 * self.ms
+        {
+            // Counted from halfway down from the leader, whatever the filter
+            // does to the edge.
+            low += 1;
+            other = 0;
+            if low as f64 >= 20.0 * self.ms {
+                return State::Header {
+                    start: self.end() - low,
+                };
+            }
+        } else {
+            // A few milliseconds between tones are allowed for transitions.
+            other += 1;
+            if other as f64 > 5.0 * self.ms {
+                leader = 0;
+                low = 0;
+            }
+        }
+        if self.frequencies.len() as f64 > 2_000.0 * self.ms {
+            self.forget(self.end() - (1_000.0 * self.ms) as usize);
+        }
+        State::Idle { leader, low, other }
+    }
+
+    fn header(&mut self, start: usize, output: &mut Vec<SstvEvent>) -> State {
+        let bit = VIS_BIT * self.ms;
+        let start = start as f64;
+        if (self.end() as f64) < start + 10.0 * bit {
+            return State::Header {
+                start: start as usize,
+            };
+        }
+        // Each bit's middle half, after the start bit.
+        let tone = |k: usize| {
+            self.mean(
+                start + (k as f64 + 1.25) * bit,
+                start + (k as f64 + 1.75) * bit,
+            )
+        };
+        let bits = (0..8).fold(0u8, |bits, k| bits | u8::from(tone(k) < SYNC) << k);
+        let code = bits & 0x7f;
+        let stopped = (tone(8) - SYNC).abs() < 150.0;
+        let mode = SstvMode::from_vis(code).filter(|_| stopped && bits.count_ones() % 2 == 0);
+        let Some(mode) = mode else {
+            return State::idle();
+        };
+        trace_event!(debug, mode = ?mode, "SSTV image started");
+        output.push(SstvEvent::Start {
+            sample: self.end() as u64 - 1,
+            mode,
+        });
+        State::Image(Image {
+            mode,
+            row: 0,
+            sync: start + 10.0 * bit + mode.timing().first_sync * self.ms,
+            missed: 0,
+            pending: None,
+        })
+    }
+
+    fn image(&mut self, mut image: Image, output: &mut Vec<SstvEvent>) -> State {
+        let timing = image.mode.timing();
+        let ms = self.ms;
+        let search = Self::SEARCH * ms;
+        let latest = timing
+            .channels
+            .iter()
+            .map(|&(start, length)| start + length)
+            .fold(timing.sync, f64::max);
+        if (self.end() as f64) < image.sync + search + latest * ms {
+            return State::Image(image);
+        }
+
+        // The sync pulse is where the most samples of a pulse's length are
+        // below black.
+        let length = (timing.sync * ms).round() as usize;
+        let score = |offset: isize| {
+            let from = (image.sync.round() as isize + offset).max(self.base as isize) as usize;
+            (from..from + length)
+                .map(
+                    |at| match self.frequencies[at - self.base] < (SYNC + BLACK) / 2.0 {
+                        true => 1.0,
+                        false => -1.0,
+                    },
+                )
+                .sum::<f64>()
+                / length as f64
+        };
+        let reach = search as isize;
+        let (offset, best) = (-reach..=reach)
+            .map(|offset| (offset, score(offset)))
+            .fold((0, f64::NEG_INFINITY), |a, b| if b.1 > a.1 { b } else { a });
+        // A sync pulse straight after the header's stop bit, itself at the
+        // sync frequency, cannot be told from it, so the header's timing
+        // stands.
+        let after_header = image.row == 0 && timing.first_sync == 0.0;
+        if best < 0.5 {
+            image.missed += 1;
+        } else if !after_header {
+            image.sync += offset as f64;
+            image.missed = 0;
+        }
+
+        let width = image.mode.width();
+        // Each channel's pixels, read at `resolution` and repeated to fill the
+        // line.
+        let read = |(start, length): (f64, f64), resolution: usize| -> Vec<f64> {
+            let from = image.sync + start * ms;
+            let pixel = length * ms / resolution as f64;
+            (0..width)
+                .map(|p| p * resolution / width)
+                .map(|p| {
+                    let frequency =
+                        self.mean(from + p as f64 * pixel, from + (p + 1) as f64 * pixel);
+                    ((frequency - BLACK) / (WHITE - BLACK) * 255.0).clamp(0.0, 255.0)
+                })
+                .collect()
+        };
+        let [first, second, third] = timing.channels;
+        if image.mode == SstvMode::Robot36 {
+            let luminance = read(first, width);
+            let blue = self.mean(
+                image.sync + second.0 * ms,
+                image.sync + (second.0 + second.1) * ms,
+            ) > LEADER;
+            // Sent at half the luminance's resolution.
+            let difference = read(third, width / 2);
+            match image.pending.take() {
+                Some(pending) => {
+                    let current = (luminance, difference, blue);
+                    output.extend(robot_rows(image.row - 1, &pending, Some(&current)));
+                }
+                None => image.pending = Some((luminance, difference, blue)),
+            }
+        } else {
+            let [green, blue, red] = [first, second, third].map(|channel| read(channel, width));
+            let pixels = (0..width)
+                .map(|p| [red[p], green[p], blue[p]].map(|level| level.round() as u8))
+                .collect();
+            output.push(SstvEvent::Line {
+                row: image.row,
+                pixels,
+            });
+        }
+        image.row += 1;
+        image.sync += timing.line * ms;
+        let earliest = timing
+            .channels
+            .iter()
+            .map(|&(start, _)| start)
+            .fold(0.0, f64::min);
+        self.forget(((image.sync + earliest * ms - search).floor().max(0.0)) as usize);
+
+        let complete = image.row == image.mode.lines();
+        if complete || image.missed >= Self::MAX_MISSED {
+            if let Some(pending) = image.pending.take() {
+                output.extend(robot_rows(image.row - 1, &pending, None));
+            }
+            // Lines read while sync was lost are not counted.
+            let rows = image.row - image.missed;
+            trace_event!(debug, rows, complete, "SSTV image ended");
+            output.push(SstvEvent::End { rows, complete });
+            return State::idle();
+        }
+        State::Image(image)
+    }
+}
+
+/// A Robot36 line's luminance, its colour difference, and whether that is
+/// blue's rather than red's.
+type RobotLine = (Vec<f64>, Vec<f64>, bool);
+
+/// Rows `row` and the next from a pair of Robot36 lines sharing their colour
+/// differences, or `row` alone, with neutral colour for what it lacks.
+fn robot_rows(row: usize, even: &RobotLine, odd: Option<&RobotLine>) -> Vec<SstvEvent> {
+    let neutral = vec![128.0; even.0.len()];
+    let (mut red, mut blue) = (&neutral, &neutral);
+    for (_, difference, is_blue) in std::iter::once(even).chain(odd) {
+        match is_blue {
+            true => blue = difference,
+            false => red = difference,
+        }
+    }
+    std::iter::once(even)
+        .chain(odd)
+        .enumerate()
+        .map(|(k, (luminance, _, _))| SstvEvent::Line {
+            row: row + k,
+            pixels: luminance
+                .iter()
+                .zip(red.iter().zip(blue))
+                .map(|(&y, (&cr, &cb))| {
+                    [
+                        y + 1.402 * (cr - 128.0),
+                        y - 0.344_136 * (cb - 128.0) - 0.714_136 * (cr - 128.0),
+                        y + 1.772 * (cb - 128.0),
+                    ]
+                    .map(|level| level.round().clamp(0.0, 255.0) as u8)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+impl Block for SstvDecoder {
+    type Input = f64;
+    type Output = SstvEvent;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<SstvEvent>) -> usize {
+        for &sample in input {
+            self.push(sample, output);
+        }
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+
+    const RATE: f64 = 11_025.0;
+
+    /// Synthesizes SSTV audio, with a clock `scale` times as slow as it
+    /// should be.
+    struct Transmitter {
+        audio: Vec<f64>,
+        phase: f64,
+        time: f64,
+        scale: f64,
+    }
+
+    impl Transmitter {
+        fn new(scale: f64) -> Self {
+            Transmitter {
+                audio: Vec::new(),
+                phase: 0.0,
+                time: 0.0,
+                scale,
+            }
+        }
+
+        fn tone(&mut self, frequency: f64, ms: f64) {
+            self.time += ms * self.scale * RATE / 1_000.0;
+            while (self.audio.len() as f64) < self.time {
+                self.audio.push(0.5 * self.phase.sin());
+                self.phase = (self.phase + TAU * frequency / RATE) % TAU;
+            }
+        }
+
+        fn scan(&mut self, levels: &[u8], ms: f64) {
+            for &level in levels {
+                self.tone(
+                    BLACK + (WHITE - BLACK) * f64::from(level) / 255.0,
+                    ms / levels.len() as f64,
+                );
+            }
+        }
+
+        /// Sends a VIS header with these eight bits, parity last.
+        fn header(&mut self, bits: u8) {
+            self.tone(LEADER, 300.0);
+            self.tone(SYNC, 10.0);
+            self.tone(LEADER, 300.0);
+            self.tone(SYNC, VIS_BIT);
+            for k in 0..8 {
+                let one = bits >> k & 1 == 1;
+                self.tone(if one { 1_100.0 } else { 1_300.0 }, VIS_BIT);
+            }
+            self.tone(SYNC, VIS_BIT);
+        }
+
+        fn vis(&mut self, code: u8) {
+            self.header(code | (code.count_ones() as u8 % 2) << 7);
+        }
+
+        /// Sends the first `rows` lines of an image.
+        fn image(&mut self, mode: SstvMode, rows: usize, pixel: impl Fn(usize, usize) -> [u8; 3]) {
+            self.vis(mode.vis());
+            let line = |row: usize, channel: usize| -> Vec<u8> {
+                (0..mode.width()).map(|p| pixel(row, p)[channel]).collect()
+            };
+            if matches!(mode, SstvMode::Scottie1) {
+                self.tone(SYNC, 9.0);
+            }
+            for row in 0..rows {
+                match mode {
+                    SstvMode::Martin1 => {
+                        self.tone(SYNC, 4.862);
+                        for channel in [1, 2, 0] {
+                            self.tone(BLACK, 0.572);
+                            self.scan(&line(row, channel), 146.432);
+                        }
+                        self.tone(BLACK, 0.572);
+                    }
+                    SstvMode::Scottie1 => {
+                        for channel in [1, 2] {
+                            self.tone(BLACK, 1.5);
+                            self.scan(&line(row, channel), 138.24);
+                        }
+                        self.tone(SYNC, 9.0);
+                        self.tone(BLACK, 1.5);
+                        self.scan(&line(row, 0), 138.24);
+                    }
+                    SstvMode::Robot36 => {
+                        let ycc: Vec<[f64; 3]> = (0..mode.width())
+                            .map(|p| {
+                                let [r, g, b] = pixel(row, p).map(f64::from);
+                                let y = 0.299 * r + 0.587 * g + 0.114 * b;
+                                [y, 128.0 + 0.713 * (r - y), 128.0 + 0.564 * (b - y)]
+                            })
+                            .collect();
+                        let channel = |k: usize| -> Vec<u8> {
+                            ycc.iter()
+                                .map(|c| c[k].round().clamp(0.0, 255.0) as u8)
+                                .collect()
+                        };
+                        self.tone(SYNC, 9.0);
+                        self.tone(BLACK, 3.0);
+                        self.scan(&channel(0), 88.0);
+                        self.tone(if row % 2 == 0 { BLACK } else { WHITE }, 4.5);
+                        self.tone(LEADER, 1.5);
+                        let chroma: Vec<u8> = channel(1 + row % 2).into_iter().step_by(2).collect();
+                        self.scan(&chroma, 44.0);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    /// Colours varying along each line, and for Martin and Scottie down the
+    /// image too.
+    fn pattern(row: usize, column: usize) -> [u8; 3] {
+        let x = column as f64 / 319.0;
+        [
+            (40.0 + 180.0 * x) as u8,
+            (200.0 - 150.0 * x) as u8,
+            (60 + 8 * row) as u8,
+        ]
+    }
+
+    #[test]
+    fn test_decodes_each_kind_of_mode() {
+        for (mode, scale) in [
+            (SstvMode::Martin1, 1.001),
+            (SstvMode::Scottie1, 0.9995),
+            (SstvMode::Robot36, 1.0),
+        ] {
+            let pattern = |row: usize, column: usize| match mode {
+                // Robot36 shares colour between pairs of rows.
+                SstvMode::Robot36 => pattern(0, column),
+                _ => pattern(row, column),
+            };
+            let mut transmitter = Transmitter::new(scale);
+            transmitter.tone(LEADER, 50.0);
+            transmitter.image(mode, 12, pattern);
+            let mut audio = transmitter.audio;
+            audio.extend(vec![0.0; 3 * RATE as usize]);
+            let noise = real_noise(audio.len(), 4);
+            for (sample, noise) in audio.iter_mut().zip(noise) {
+                *sample += 0.01 * noise;
+            }
+
+            let mut decoder = SstvDecoder::new(RATE);
+            let mut events = Vec::new();
+            decoder.work(&audio, &mut events);
+            assert!(
+                matches!(events[0], SstvEvent::Start { mode: m, .. } if m == mode),
+                "{mode:?}"
+            );
+            assert_eq!(
+                events.last(),
+                Some(&SstvEvent::End {
+                    rows: 12,
+                    complete: false
+                }),
+                "{mode:?}"
+            );
+            let lines: Vec<_> = events
+                .iter()
+                .filter_map(|event| match event {
+                    SstvEvent::Line { row, pixels } => Some((*row, pixels)),
+                    _ => None,
+                })
+                .collect();
+            assert!(lines.len() >= 12, "{mode:?}");
+            for &(row, pixels) in &lines[..12] {
+                // Leaving out the edges, smeared by the demodulator's filter
+                // over Robot36's short colour scans.
+                for (column, pixel) in pixels.iter().enumerate().take(296).skip(24) {
+                    let expected = pattern(row, column);
+                    let error = (0..3)
+                        .map(|k| (i32::from(pixel[k]) - i32::from(expected[k])).abs())
+                        .max()
+                        .unwrap();
+                    assert!(
+                        error <= 16,
+                        "{mode:?} row {row} column {column}: {pixel:?} for {expected:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ignores_a_header_with_bad_parity() {
+        assert_eq!(
+            SstvMode::from_vis(SstvMode::Scottie2.vis()),
+            Some(SstvMode::Scottie2)
+        );
+        assert_eq!(SstvMode::from_vis(1), None);
+        let mut transmitter = Transmitter::new(1.0);
+        // Martin 1's code with the wrong parity.
+        let code = SstvMode::Martin1.vis();
+        transmitter.header(code | (1 - code.count_ones() as u8 % 2) << 7);
+        transmitter.tone(BLACK, 500.0);
+        let mut decoder = SstvDecoder::new(RATE);
+        let mut events = Vec::new();
+        decoder.work(&transmitter.audio, &mut events);
+        assert_eq!(events, []);
+        assert_eq!(decoder.mode(), None);
+    }
+}
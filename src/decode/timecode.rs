@@ -0,0 +1,572 @@
+//! The DCF77 and WWVB longwave time signals.
+//!
+//! Both stations cut their carrier at the start of every second, and the
+//! length of the cut carries one symbol of a frame that repeats each minute.
+//! DCF77, on 77.5 kHz from Mainflingen, drops to 15% for 100 ms for a zero
+//! and 200 ms for a one, and leaves out the cut in the last second to mark
+//! the minute. Its frame gives the German civil time, CET or CEST, of the
+//! minute about to start, with parity over each field. WWVB, on 60 kHz from
+//! Fort Collins, drops 17 dB for 200 ms, 500 ms, or 800 ms for a marker;
+//! markers fall every ten seconds and two in a row begin a frame, which
+//! gives the UTC time at which it began.
+//!
+//! [`TimecodeDecoder`] takes the carrier's envelope, such as from an
+//! [`EnvelopeDetector`](crate::dsp::envelope::EnvelopeDetector) after a
+//! narrow filter around the carrier, and tracks the carrier's level to find
+//! each cut. The amplitude cuts are all it reads: DCF77's phase-modulated
+//! pseudo-random code and WWVB's phase-modulated time code are left alone.
+
+use crate::block::Block;
+use std::fmt;
+
+/// A longwave time station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeStation {
+    Dcf77,
+    Wwvb,
+}
+
+impl TimeStation {
+    /// The carrier frequency, in Hz.
+    pub fn carrier(self) -> f64 {
+        match self {
+            TimeStation::Dcf77 => 77.5e3,
+            TimeStation::Wwvb => 60e3,
+        }
+    }
+
+    /// The symbol a cut of `width` seconds stands for.
+    fn symbol(self, width: f64) -> Option<Symbol> {
+        match (self, width) {
+            (TimeStation::Dcf77, w) if (0.05..0.15).contains(&w) => Some(Symbol::Zero),
+            (TimeStation::Dcf77, w) if (0.15..0.26).contains(&w) => Some(Symbol::One),
+            (TimeStation::Wwvb, w) if (0.1..0.35).contains(&w) => Some(Symbol::Zero),
+            (TimeStation::Wwvb, w) if (0.35..0.65).contains(&w) => Some(Symbol::One),
+            (TimeStation::Wwvb, w) if (0.65..0.95).contains(&w) => Some(Symbol::Marker),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TimeStation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeStation::Dcf77 => "DCF77",
+            TimeStation::Wwvb => "WWVB",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol {
+    Zero,
+    One,
+    Marker,
+}
+
+/// The time given by one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeFrame {
+    pub station: TimeStation,
+    /// Index in the stream of the sample at which the carrier cut began the
+    /// minute given, the one whose edge is on time.
+    pub sample: u64,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// How far the time given is ahead of UTC, in minutes: 60 or 120 for
+    /// DCF77 and zero for WWVB.
+    pub utc_offset: i16,
+    /// For DCF77, whether the time given is CEST. For WWVB, whether United
+    /// States daylight saving time was in effect at the start of the UTC
+    /// day.
+    pub summer_time: bool,
+    /// Whether summer time starts or ends soon: at the end of the hour for
+    /// DCF77 and during the UTC day for WWVB.
+    pub summer_time_change: bool,
+    /// Whether a leap second is to be inserted at the end of the month.
+    pub leap_second: bool,
+}
+
+impl TimeFrame {
+    /// The time given in seconds since the Unix epoch.
+    pub fn unix_time(&self) -> i64 {
+        // Days since 1970-01-01 of the civil date, counting years from
+        // March so that the leap day falls at the end.
+        let (month, day) = (i64::from(self.month), i64::from(self.day));
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        let minutes = i64::from(self.hour) * 60 + i64::from(self.minute);
+        days * 86_400 + (minutes - i64::from(self.utc_offset)) * 60
+    }
+}
+
+/// The digit given by `units`, bit positions and weights, plus the weights
+/// of whichever `tens` are set, or `None` if the units are not a digit.
+fn bcd(bits: &[bool], tens: &[(usize, u8)], units: &[(usize, u8)]) -> Option<u8> {
+    let sum = |fields: &[(usize, u8)]| -> u8 {
+        fields
+            .iter()
+            .filter(|&&(k, _)| bits[k])
+            .map(|&(_, weight)| weight)
+            .sum()
+    };
+    let units = sum(units);
+    (units <= 9).then(|| sum(tens) + units)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Reads a DCF77 frame of 59 seconds, or 60 with a leap second.
+fn decode_dcf77(bits: &[bool], sample: u64) -> Option<TimeFrame> {
+    // Each field's bits and its parity bit have even parity.
+    let even = |range: std::ops::RangeInclusive<usize>| {
+        bits[range].iter().filter(|&&b| b).count() % 2 == 0
+    };
+    if bits[0]
+        || !bits[20]
+        || bits[17] == bits[18]
+        || !even(21..=28)
+        || !even(29..=35)
+        || !even(36..=58)
+    {
+        return None;
+    }
+    const LOW: [u8; 4] = [1, 2, 4, 8];
+    let field = |start: usize, tens: &[u8]| {
+        let units: Vec<_> = (start..).zip(LOW).collect();
+        let tens: Vec<_> = (start + 4..).zip(tens.iter().copied()).collect();
+        bcd(bits, &tens, &units)
+    };
+    let minute = field(21, &[10, 20, 40]).filter(|&m| m < 60)?;
+    let hour = field(29, &[10, 20]).filter(|&h| h < 24)?;
+    let weekday = (42..45)
+        .zip(LOW)
+        .filter(|&(k, _)| bits[k])
+        .map(|(_, w)| w)
+        .sum::<u8>();
+    let month = field(45, &[10]).filter(|m| (1..=12).contains(m))?;
+    let year = 2000 + u16::from(field(50, &[10, 20, 40, 80])?);
+    let day = field(36, &[10, 20]).filter(|&d| d >= 1 && d <= days_in_month(year, month))?;
+    if !(1..=7).contains(&weekday) {
+        return None;
+    }
+    Some(TimeFrame {
+        station: TimeStation::Dcf77,
+        sample,
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        utc_offset: if bits[17] { 120 } else { 60 },
+        summer_time: bits[17],
+        summer_time_change: bits[16],
+        leap_second: bits[19],
+    })
+}
+
+/// Reads a WWVB frame of 60 seconds.
+fn decode_wwvb(symbols: &[Symbol], sample: u64) -> Option<TimeFrame> {
+    const MARKERS: [usize; 7] = [0, 9, 19, 29, 39, 49, 59];
+    const ZEROS: [usize; 11] = [4, 10, 11, 14, 20, 21, 24, 34, 35, 44, 54];
+    let markers_placed = symbols
+        .iter()
+        .enumerate()
+        .all(|(k, &symbol)| (symbol == Symbol::Marker) == MARKERS.contains(&k));
+    let bits: Vec<bool> = symbols
+        .iter()
+        .map(|&symbol| symbol == Symbol::One)
+        .collect();
+    if !markers_placed || ZEROS.iter().any(|&k| bits[k]) {
+        return None;
+    }
+    let minute = bcd(
+        &bits,
+        &[(1, 40), (2, 20), (3, 10)],
+        &[(5, 8), (6, 4), (7, 2), (8, 1)],
+    )
+    .filter(|&m| m < 60)?;
+    let hour = bcd(
+        &bits,
+        &[(12, 20), (13, 10)],
+        &[(15, 8), (16, 4), (17, 2), (18, 1)],
+    )
+    .filter(|&h| h < 24)?;
+    let hundreds = [(22, 200), (23, 100)]
+        .iter()
+        .filter(|&&(k, _)| bits[k])
+        .map(|&(_, weight)| weight)
+        .sum::<u16>();
+    let tens = [(25, 80), (26, 40), (27, 20), (28, 10)];
+    let day_of_year =
+        hundreds + u16::from(bcd(&bits, &tens, &[(30, 8), (31, 4), (32, 2), (33, 1)])?);
+    let tens = [(45, 80), (46, 40), (47, 20), (48, 10)];
+    let year = 2000 + u16::from(bcd(&bits, &tens, &[(50, 8), (51, 4), (52, 2), (53, 1)])?);
+    // The leap year bit must agree with the year.
+    if bits[55] != (days_in_month(year, 2) == 29) {
+        return None;
+    }
+    let mut day = day_of_year;
+    let mut month = 1;
+    while month <= 12 && day > u16::from(days_in_month(year, month)) {
+        day -= u16::from(days_in_month(year, month));
+        month += 1;
+    }
+    if day == 0 || month > 12 {
+        return None;
+    }
+    Some(TimeFrame {
+        station: TimeStation::Wwvb,
+        sample,
+        year,
+        month,
+        day: day as u8,
+        hour,
+        minute,
+        utc_offset: 0,
+        // Bit 58 gives the state at the start of the UTC day and bit 57 at
+        // its end.
+        summer_time: bits[58],
+        summer_time_change: bits[57] != bits[58],
+        leap_second: bits[56],
+    })
+}
+
+/// The seconds of a frame being received, each kept as the cut that began
+/// it.
+#[derive(Debug, Clone)]
+struct Frame {
+    /// The sample at which its first cut began.
+    start: u64,
+    symbols: Vec<Symbol>,
+}
+
+/// Decodes DCF77 or WWVB time frames from the carrier's envelope.
+#[derive(Debug, Clone)]
+pub struct TimecodeDecoder {
+    station: TimeStation,
+    sample_rate: f64,
+    /// Moves this far towards each input sample, for a time constant of
+    /// 5 ms.
+    alpha: f64,
+    level: f64,
+    /// The carrier's uncut level: its peak, decaying with a time constant
+    /// of ten seconds.
+    carrier: f64,
+    decay: f64,
+    /// The sample at which the current cut began.
+    cut: Option<u64>,
+    /// When the last cut began.
+    last_cut: Option<u64>,
+    previous: Option<Symbol>,
+    frame: Option<Frame>,
+    position: u64,
+}
+
+impl TimecodeDecoder {
+    pub fn new(station: TimeStation, sample_rate: f64) -> Self {
+        TimecodeDecoder {
+            station,
+            sample_rate,
+            alpha: 1.0 - (-1.0 / (0.005 * sample_rate)).exp(),
+            level: 0.0,
+            carrier: 0.0,
+            decay: (-1.0 / (10.0 * sample_rate)).exp(),
+            cut: None,
+            last_cut: None,
+            previous: None,
+            frame: None,
+            position: 0,
+        }
+    }
+
+    pub fn station(&self) -> TimeStation {
+        self.station
+    }
+
+    /// Whether the seconds are being followed through a frame, so that the
+    /// next minute's time will be read.
+    pub fn is_synchronized(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// Accepts one envelope sample, returning the time if this sample
+    /// completes a frame.
+    pub fn push(&mut self, envelope: f64) -> Option<TimeFrame> {
+        let position = self.position;
+        self.position += 1;
+        self.level += self.alpha * (envelope - self.level);
+        self.carrier = self.level.max(self.carrier * self.decay);
+        // Some hysteresis about half the carrier.
+        match self.cut {
+            None if self.level < 0.45 * self.carrier => {
+                self.cut = Some(position);
+                None
+            }
+            Some(start) if self.level > 0.55 * self.carrier => {
+                self.cut = None;
+                let width = (position - start) as f64 / self.sample_rate;
+                // Too short for any symbol, as a burst of noise.
+                if width < 0.03 {
+                    return None;
+                }
+                self.pulse(start, width)
+            }
+            _ => None,
+        }
+    }
+
+    /// Takes the cut that began at `start` as the next second.
+    fn pulse(&mut self, start: u64, width: f64) -> Option<TimeFrame> {
+        let seconds = self
+            .last_cut
+            .replace(start)
+            .map(|last| (start - last) as f64 / self.sample_rate)
+            .filter(|seconds| (seconds - seconds.round()).abs() < 0.1)
+            .map(|seconds| seconds.round() as u64);
+        let symbol = self.station.symbol(width);
+        let previous = std::mem::replace(&mut self.previous, symbol);
+        let (Some(symbol), Some(seconds)) = (symbol, seconds) else {
+            self.frame = None;
+            return None;
+        };
+        let minute = match (self.station, seconds) {
+            // The missing cut before the minute.
+            (TimeStation::Dcf77, 2) => true,
+            (TimeStation::Wwvb, 1) => symbol == Symbol::Marker && previous == Some(Symbol::Marker),
+            (_, 1) => false,
+            _ => {
+                self.frame = None;
+                return None;
+            }
+        };
+        if !minute {
+            if let Some(frame) = &mut self.frame {
+                frame.symbols.push(symbol);
+                if frame.symbols.len() > 61 {
+                    self.frame = None;
+                }
+            }
+            return None;
+        }
+        let frame = self.frame.replace(Frame {
+            start,
+            symbols: vec![symbol],
+        });
+        let time = match self.station {
+            // DCF77 gives the time of the minute it leads up to.
+            TimeStation::Dcf77 => frame
+                .map(|frame| {
+                    frame
+                        .symbols
+                        .iter()
+                        .map(|&s| s == Symbol::One)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|bits| bits.len() == 59 || bits.len() == 60)
+                .and_then(|bits| decode_dcf77(&bits, start)),
+            // The marker that began this frame came just after the last
+            // one's closing marker.
+            TimeStation::Wwvb => frame
+                .filter(|frame| frame.symbols.len() == 60)
+                .and_then(|frame| decode_wwvb(&frame.symbols, frame.start)),
+        };
+        trace_event!(debug, station = %self.station, valid = time.is_some(), "time frame");
+        time
+    }
+}
+
+impl Block for TimecodeDecoder {
+    type Input = f64;
+    type Output = TimeFrame;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<TimeFrame>) -> usize {
+        output.extend(input.iter().filter_map(|&envelope| self.push(envelope)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use crate::dsp::envelope::{EnvelopeDetector, Smoothing};
+    use num_complex::Complex;
+
+    const RATE: f64 = 1_000.0;
+
+    /// Sets `value`'s digits into `bits`, each weight at its position.
+    fn set_bcd(bits: &mut [bool; 60], value: u16, fields: &[(usize, u16)]) {
+        let (mut tens, mut units) = (value / 10 * 10, value % 10);
+        let mut fields = fields.to_vec();
+        fields.sort_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+        for (k, weight) in fields {
+            let remaining = if weight < 10 { &mut units } else { &mut tens };
+            if *remaining >= weight {
+                *remaining -= weight;
+                bits[k] = true;
+            }
+        }
+    }
+
+    /// The DCF77 frame sent in the minute before `hour:minute` CEST.
+    fn dcf77_frame(day: u16, hour: u16, minute: u16) -> [bool; 60] {
+        let mut bits = [false; 60];
+        bits[17] = true;
+        bits[20] = true;
+        let field = |bits: &mut [bool; 60], value, start, weights: &[u16]| {
+            set_bcd(
+                bits,
+                value,
+                &(start..).zip(weights.iter().copied()).collect::<Vec<_>>(),
+            )
+        };
+        field(&mut bits, minute, 21, &[1, 2, 4, 8, 10, 20, 40]);
+        field(&mut bits, hour, 29, &[1, 2, 4, 8, 10, 20]);
+        field(&mut bits, day, 36, &[1, 2, 4, 8, 10, 20]);
+        field(&mut bits, 3, 42, &[1, 2, 4]);
+        field(&mut bits, 10, 45, &[1, 2, 4, 8, 10]);
+        field(&mut bits, 26, 50, &[1, 2, 4, 8, 10, 20, 40, 80]);
+        for (range, parity) in [(21..28, 28), (29..35, 35), (36..58, 58)] {
+            bits[parity] = bits[range].iter().filter(|&&b| b).count() % 2 == 1;
+        }
+        bits
+    }
+
+    /// The envelope of `seconds`, each a cut length or `None` for none,
+    /// with the carrier cut to `depth` and noise added.
+    fn envelope(seconds: &[Option<f64>], depth: f64, noise: f64) -> Vec<f64> {
+        let mut detector = EnvelopeDetector::new(Smoothing::None);
+        let second = RATE as usize;
+        let i = real_noise(seconds.len() * second, 1);
+        let q = real_noise(seconds.len() * second, 2);
+        let mut output = Vec::new();
+        for (n, (i, q)) in i.into_iter().zip(q).enumerate() {
+            let cut = seconds[n / second].unwrap_or(0.0);
+            let amplitude = if ((n % second) as f64) < cut * RATE {
+                depth
+            } else {
+                1.0
+            };
+            output.push(detector.envelope(Complex::new(amplitude + noise * i, noise * q)));
+        }
+        output
+    }
+
+    #[test]
+    fn test_decodes_dcf77_minutes_and_rejects_bad_parity() {
+        let mut seconds = Vec::new();
+        for minute in 34..38 {
+            let mut bits = dcf77_frame(14, 12, minute);
+            if minute == 36 {
+                bits[30] = !bits[30];
+            }
+            seconds.extend(
+                bits[..59]
+                    .iter()
+                    .map(|&one| Some(if one { 0.2 } else { 0.1 })),
+            );
+            seconds.push(None);
+        }
+        seconds.push(Some(0.1));
+        let audio = envelope(&seconds, 0.15, 0.1);
+        let mut decoder = TimecodeDecoder::new(TimeStation::Dcf77, RATE);
+        let mut frames = Vec::new();
+        decoder.work(&audio, &mut frames);
+        // The first minute passes in finding the frame, and the third is
+        // spoiled.
+        assert_eq!(frames.len(), 2, "{frames:?}");
+        let frame = frames[0];
+        assert_eq!((frame.year, frame.month, frame.day), (2026, 10, 14));
+        assert_eq!((frame.hour, frame.minute), (12, 35));
+        assert!(frame.summer_time && !frame.leap_second);
+        assert_eq!(frame.unix_time(), 1_791_974_100);
+        assert!(frame.sample.abs_diff(120_000) < 20, "{}", frame.sample);
+        assert_eq!((frames[1].minute, frames[1].sample / 1000), (37, 240));
+        assert!(decoder.is_synchronized());
+    }
+
+    #[test]
+    fn test_decodes_wwvb_frame_on_leap_day() {
+        // 2024-02-29, day 60, at 23:59 UTC with a leap second warning.
+        let mut bits = [false; 60];
+        set_bcd(
+            &mut bits,
+            59,
+            &[(1, 40), (2, 20), (3, 10), (5, 8), (6, 4), (7, 2), (8, 1)],
+        );
+        set_bcd(
+            &mut bits,
+            23,
+            &[(12, 20), (13, 10), (15, 8), (16, 4), (17, 2), (18, 1)],
+        );
+        set_bcd(
+            &mut bits,
+            60,
+            &[
+                (25, 80),
+                (26, 40),
+                (27, 20),
+                (28, 10),
+                (30, 8),
+                (31, 4),
+                (32, 2),
+                (33, 1),
+            ],
+        );
+        set_bcd(
+            &mut bits,
+            24,
+            &[
+                (45, 80),
+                (46, 40),
+                (47, 20),
+                (48, 10),
+                (50, 8),
+                (51, 4),
+                (52, 2),
+                (53, 1),
+            ],
+        );
+        bits[55] = true;
+        bits[56] = true;
+        let frame: Vec<Option<f64>> = (0..60)
+            .map(|k| match k {
+                0 | 9 | 19 | 29 | 39 | 49 | 59 => Some(0.8),
+                k if bits[k] => Some(0.5),
+                _ => Some(0.2),
+            })
+            .collect();
+        let mut seconds = frame.clone();
+        seconds.extend(&frame);
+        seconds.push(Some(0.8));
+        let audio = envelope(&seconds, 0.14, 0.1);
+        let mut decoder = TimecodeDecoder::new(TimeStation::Wwvb, RATE);
+        let mut frames = Vec::new();
+        decoder.work(&audio, &mut frames);
+        assert_eq!(frames.len(), 1, "{frames:?}");
+        let frame = frames[0];
+        assert_eq!((frame.year, frame.month, frame.day), (2024, 2, 29));
+        assert_eq!((frame.hour, frame.minute, frame.utc_offset), (23, 59, 0));
+        assert!(frame.leap_second && !frame.summer_time && !frame.summer_time_change);
+        assert_eq!(frame.unix_time(), 1_709_251_140);
+        assert!(frame.sample.abs_diff(60_000) < 20, "{}", frame.sample);
+    }
+}
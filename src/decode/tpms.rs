@@ -0,0 +1,481 @@
+//! Tire-pressure monitoring system (TPMS) sensors.
+//!
+//! A sensor in each wheel sends a short burst every minute or so, and more
+//! often when the pressure changes, on 315 MHz in North America and
+//! 433.92 MHz elsewhere. Most bursts are FSK, some OOK, and all are
+//! Manchester or differential Manchester coded behind a preamble and a sync
+//! pattern, carrying the sensor's ID, the pressure, the temperature and
+//! some flags, closed by a CRC or a checksum. Each maker lays these out its
+//! own way.
+//!
+//! [`TpmsDecoder`] cuts complex baseband into bursts with a
+//! [`BurstExtractor`], demodulates each as FSK, from the sign of its
+//! instantaneous frequency about the burst's mean, and as OOK, from its
+//! envelope against half the peak. For each protocol it recovers the chip
+//! clock, finds the sync pattern in either polarity, decodes the bits and
+//! keeps what passes the check.
+
+use crate::block::Block;
+use crate::coding::crc::{Crc, CrcParams};
+use crate::coding::manchester::{self, Convention};
+use crate::detect::burst::{Burst, BurstConfig, BurstExtractor};
+use crate::dsp::clock::ZeroCrossingClock;
+use crate::dsp::fir::{lowpass, Fir};
+use crate::dsp::fm::InstantaneousFrequency;
+use num_complex::Complex;
+
+/// One pound per square inch in kPa.
+const PSI: f64 = 6.894_757;
+
+/// How a protocol keys its chips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TpmsModulation {
+    Fsk,
+    Ook,
+}
+
+/// How a protocol's bits become chips, two to a bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    /// A zero as high then low, and a one as low then high.
+    Manchester,
+    /// A transition in the middle of every bit, and another at its start
+    /// for a zero.
+    DifferentialManchester,
+}
+
+/// A sensor protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TpmsProtocol {
+    /// Ford's sensors: FSK at 19.2 kbit/s, Manchester, eight bytes with a
+    /// sum.
+    Ford,
+    /// Schrader's EG53MA4: OOK at 4 kbit/s, Manchester, eight bytes with a
+    /// sum.
+    Schrader,
+    /// Toyota's PMV-107J: FSK at 10 kbit/s, differential Manchester, nine
+    /// bytes with a CRC-8.
+    Toyota,
+}
+
+impl TpmsProtocol {
+    pub const ALL: [TpmsProtocol; 3] = [
+        TpmsProtocol::Ford,
+        TpmsProtocol::Schrader,
+        TpmsProtocol::Toyota,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TpmsProtocol::Ford => "Ford",
+            TpmsProtocol::Schrader => "Schrader EG53MA4",
+            TpmsProtocol::Toyota => "Toyota PMV-107J",
+        }
+    }
+
+    pub fn modulation(self) -> TpmsModulation {
+        match self {
+            TpmsProtocol::Schrader => TpmsModulation::Ook,
+            TpmsProtocol::Ford | TpmsProtocol::Toyota => TpmsModulation::Fsk,
+        }
+    }
+
+    /// The bit rate, in bit/s.
+    pub fn bit_rate(self) -> f64 {
+        match self {
+            TpmsProtocol::Ford => 19_200.0,
+            TpmsProtocol::Schrader => 4_000.0,
+            TpmsProtocol::Toyota => 10_000.0,
+        }
+    }
+
+    fn coding(self) -> Coding {
+        match self {
+            TpmsProtocol::Toyota => Coding::DifferentialManchester,
+            TpmsProtocol::Ford | TpmsProtocol::Schrader => Coding::Manchester,
+        }
+    }
+
+    /// The chips that end the preamble, oldest highest, and how many there
+    /// are.
+    fn sync(self) -> (u32, usize) {
+        match self {
+            TpmsProtocol::Ford => (0xaaa9, 16),
+            TpmsProtocol::Schrader => (0xfffe, 16),
+            TpmsProtocol::Toyota => (0xa9e, 12),
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            TpmsProtocol::Ford | TpmsProtocol::Schrader => 8,
+            TpmsProtocol::Toyota => 9,
+        }
+    }
+
+    /// The reading in `bytes`, if its check passes.
+    fn parse(self, bytes: &[u8], sample: u64) -> Option<TpmsReading> {
+        let sum = |bytes: &[u8]| bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        let (id, pressure_kpa, temperature_c, flags) = match self {
+            TpmsProtocol::Ford => {
+                if sum(&bytes[..7]) != bytes[7] {
+                    return None;
+                }
+                // The flags hold the pressure's ninth bit.
+                let pressure = u16::from(bytes[6] & 0x20) << 3 | u16::from(bytes[4]);
+                let id = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (
+                    id,
+                    f64::from(pressure) * 0.25 * PSI,
+                    f64::from(bytes[5]) - 56.0,
+                    bytes[6],
+                )
+            }
+            TpmsProtocol::Schrader => {
+                if sum(&bytes[..7]) != bytes[7] {
+                    return None;
+                }
+                let id = u32::from_be_bytes([0, bytes[1], bytes[2], bytes[3]]);
+                (
+                    id,
+                    f64::from(bytes[4]) * 2.5,
+                    f64::from(bytes[5]) - 50.0,
+                    bytes[0],
+                )
+            }
+            TpmsProtocol::Toyota => {
+                if TOYOTA_CRC.checksum(&bytes[..8]) != u32::from(bytes[8]) {
+                    return None;
+                }
+                // After the ID: a status bit, the pressure, the temperature,
+                // seven more status bits and the pressure inverted.
+                let bits = u64::from_be_bytes(bytes[..8].try_into().ok()?);
+                let pressure = (bits >> 23) as u8;
+                let temperature = (bits >> 15) as u8;
+                if (bits as u8) != !pressure {
+                    return None;
+                }
+                let flags = (bits >> 31 & 1) as u8 | ((bits >> 8) as u8 & 0x7f) << 1;
+                let pressure = (f64::from(pressure) * 0.25 - 7.0) * PSI;
+                (
+                    (bits >> 32) as u32,
+                    pressure,
+                    f64::from(temperature) - 40.0,
+                    flags,
+                )
+            }
+        };
+        Some(TpmsReading {
+            sample,
+            protocol: self,
+            id,
+            pressure_kpa,
+            temperature_c,
+            flags,
+        })
+    }
+}
+
+/// Toyota's CRC-8.
+static TOYOTA_CRC: Crc = Crc::new(CrcParams {
+    width: 8,
+    polynomial: 0x07,
+    init: 0x80,
+    reflected: false,
+    xor_out: 0,
+});
+
+/// One sensor's report.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TpmsReading {
+    /// Index in the stream of the first sample of the burst.
+    pub sample: u64,
+    pub protocol: TpmsProtocol,
+    /// The sensor's ID, unique to each wheel.
+    pub id: u32,
+    /// Gauge pressure, in kPa.
+    pub pressure_kpa: f64,
+    pub temperature_c: f64,
+    /// The protocol's status bits, as sent.
+    pub flags: u8,
+}
+
+/// Decodes TPMS bursts from complex baseband.
+#[derive(Debug, Clone)]
+pub struct TpmsDecoder {
+    sample_rate: f64,
+    extractor: BurstExtractor,
+}
+
+impl TpmsDecoder {
+    pub fn new(sample_rate: f64) -> Self {
+        let mut config = BurstConfig::new(sample_rate);
+        // Long enough to span a chip's worth of OOK off, and to turn away
+        // bursts under a millisecond.
+        config.smoothing = (sample_rate / 4_000.0).ceil() as usize;
+        config.post_samples = config.smoothing * 2;
+        config.min_length = (1e-3 * sample_rate) as usize;
+        TpmsDecoder {
+            sample_rate,
+            extractor: BurstExtractor::new(config),
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Processes samples and returns the readings from the bursts that
+    /// ended within them.
+    pub fn process(&mut self, samples: &[Complex<f64>]) -> Vec<TpmsReading> {
+        let bursts = self.extractor.process(samples);
+        bursts.iter().flat_map(|burst| self.decode(burst)).collect()
+    }
+
+    /// Decodes the burst in progress, if any, as at the end of a recording.
+    pub fn flush(&mut self) -> Vec<TpmsReading> {
+        let burst = self.extractor.flush();
+        burst.iter().flat_map(|burst| self.decode(burst)).collect()
+    }
+
+    /// The readings in one burst, from whichever protocols it decodes as.
+    pub fn decode(&self, burst: &Burst) -> Vec<TpmsReading> {
+        let fsk = self.fsk_levels(&burst.samples);
+        let ook = ook_levels(&burst.samples);
+        let mut readings: Vec<TpmsReading> = Vec::new();
+        for protocol in TpmsProtocol::ALL {
+            let levels = match protocol.modulation() {
+                TpmsModulation::Fsk => &fsk,
+                TpmsModulation::Ook => &ook,
+            };
+            let chips = self.chips(levels, protocol.bit_rate() * 2.0);
+            let Some(bytes) = find_bytes(&chips, protocol) else {
+                continue;
+            };
+            if let Some(reading) = protocol.parse(&bytes, burst.start_sample) {
+                trace_event!(
+                    debug,
+                    protocol = protocol.name(),
+                    id = reading.id,
+                    "TPMS reading"
+                );
+                readings.push(reading);
+            }
+        }
+        readings
+    }
+
+    /// Each sample's frequency less the mean over the burst's stronger
+    /// half, with noise beyond the fastest chip rate filtered out.
+    fn fsk_levels(&self, samples: &[Complex<f64>]) -> Vec<f64> {
+        let mut discriminator = InstantaneousFrequency::new(self.sample_rate);
+        let frequencies: Vec<f64> = samples
+            .iter()
+            .map(|&z| discriminator.frequency(z))
+            .collect();
+        let peak = samples.iter().map(|z| z.norm()).fold(0.0, f64::max);
+        let strong: Vec<f64> = samples
+            .iter()
+            .zip(&frequencies)
+            .filter(|(z, _)| z.norm() > peak / 2.0)
+            .map(|(_, &frequency)| frequency)
+            .collect();
+        let center = strong.iter().sum::<f64>() / strong.len().max(1) as f64;
+        let taps = (3.3 * self.sample_rate / 40_000.0).ceil() as usize | 1;
+        let mut smoothing = Fir::new(&lowpass(taps, (40_000.0 / self.sample_rate).min(0.45)));
+        frequencies
+            .iter()
+            .map(|&frequency| smoothing.filter(frequency - center))
+            .collect()
+    }
+
+    /// Chips sliced from `levels` at their centres, `rate` a second.
+    fn chips(&self, levels: &[f64], rate: f64) -> Vec<bool> {
+        let mut clock = ZeroCrossingClock::new(self.sample_rate / rate);
+        levels
+            .iter()
+            .filter_map(|&level| clock.push(level))
+            .map(|level| level > 0.0)
+            .collect()
+    }
+}
+
+/// Each sample's magnitude less half the burst's peak.
+fn ook_levels(samples: &[Complex<f64>]) -> Vec<f64> {
+    let peak = samples.iter().map(|z| z.norm()).fold(0.0, f64::max);
+    samples.iter().map(|z| z.norm() - peak / 2.0).collect()
+}
+
+/// The bytes after the first sync pattern in `chips`, in either polarity,
+/// that decode without a coding violation.
+fn find_bytes(chips: &[bool], protocol: TpmsProtocol) -> Option<Vec<u8>> {
+    let (sync, length) = protocol.sync();
+    let data = protocol.bytes() * 16;
+    let polarities: &[bool] = match protocol.modulation() {
+        TpmsModulation::Fsk => &[false, true],
+        // On is always on.
+        TpmsModulation::Ook => &[false],
+    };
+    for &invert in polarities {
+        let chip = |k: usize| chips[k] != invert;
+        for start in 0..(chips.len() + 1).saturating_sub(length + data) {
+            let matches =
+                (0..length).all(|k| chip(start + k) == (sync >> (length - 1 - k) & 1 == 1));
+            if !matches {
+                continue;
+            }
+            let first = start + length;
+            let coded: Vec<bool> = (first..first + data).map(chip).collect();
+            let bits = match protocol.coding() {
+                Coding::Manchester => manchester::decode(&coded, Convention::Ieee),
+                Coding::DifferentialManchester => {
+                    manchester::differential_decode(&coded, chip(first - 1))
+                }
+            };
+            if let Ok(bits) = bits {
+                return Some(
+                    bits.chunks(8)
+                        .map(|bits| bits.iter().fold(0, |byte, &bit| byte << 1 | u8::from(bit)))
+                        .collect(),
+                );
+            }
+        }
+    }
+    None
+}
+
+impl Block for TpmsDecoder {
+    type Input = Complex<f64>;
+    type Output = TpmsReading;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<TpmsReading>) -> usize {
+        output.extend(self.process(input));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use std::f64::consts::TAU;
+
+    const RATE: f64 = 250_000.0;
+
+    /// The chips of a burst: a preamble, the sync and `bytes` coded.
+    fn chips(protocol: TpmsProtocol, bytes: &[u8]) -> Vec<bool> {
+        let mut chips: Vec<bool> = match protocol.modulation() {
+            TpmsModulation::Fsk => (0..32).map(|k| k % 2 == 0).collect(),
+            TpmsModulation::Ook => vec![false; 8],
+        };
+        let (sync, length) = protocol.sync();
+        chips.extend((0..length).rev().map(|k| sync >> k & 1 == 1));
+        let bits: Vec<bool> = (0..bytes.len() * 8)
+            .map(|k| bytes[k / 8] >> (7 - k % 8) & 1 == 1)
+            .collect();
+        let coded = match protocol.coding() {
+            Coding::Manchester => manchester::encode(&bits, Convention::Ieee),
+            Coding::DifferentialManchester => {
+                manchester::differential_encode(&bits, *chips.last().unwrap())
+            }
+        };
+        chips.extend(coded);
+        chips.extend([false; 4]);
+        chips
+    }
+
+    /// Baseband of the chips at `rate` a second, FSK with a deviation of
+    /// 40 kHz about `offset` or OOK, within noise.
+    fn modulate(chips: &[bool], protocol: TpmsProtocol, offset: f64) -> Vec<Complex<f64>> {
+        let per_chip = RATE / (2.0 * protocol.bit_rate());
+        let mut samples = vec![Complex::new(0.0, 0.0); 2_000];
+        let mut phase = 0.0;
+        let length = (chips.len() as f64 * per_chip) as usize;
+        for n in 0..length {
+            let chip = chips[(n as f64 / per_chip) as usize];
+            match protocol.modulation() {
+                TpmsModulation::Fsk => {
+                    samples.push(Complex::from_polar(1.0, phase));
+                    let frequency = offset + if chip { 40e3 } else { -40e3 };
+                    phase = (phase + TAU * frequency / RATE) % TAU;
+                }
+                TpmsModulation::Ook => samples.push(Complex::new(f64::from(u8::from(chip)), 0.0)),
+            }
+        }
+        samples.extend(vec![Complex::new(0.0, 0.0); 2_000]);
+        for (sample, noise) in samples.iter_mut().zip(complex_noise(1 << 20, 9)) {
+            *sample += 0.05 * noise;
+        }
+        samples
+    }
+
+    fn ford() -> Vec<u8> {
+        let mut bytes = vec![0x12, 0x34, 0xab, 0xcd, 0x88, 0x4e, 0x20];
+        bytes.push(bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)));
+        bytes
+    }
+
+    fn toyota() -> Vec<u8> {
+        // ID 0xdeadbeef, 35 psi, 25 °C, status bits clear.
+        let pressure: u64 = 168;
+        let bits = 0xdead_beef_u64 << 32 | pressure << 23 | 65 << 15 | u64::from(!(pressure as u8));
+        let mut bytes = bits.to_be_bytes().to_vec();
+        bytes.push(TOYOTA_CRC.checksum(&bytes) as u8);
+        bytes
+    }
+
+    #[test]
+    fn test_decodes_each_protocol() {
+        let schrader = {
+            let mut bytes = vec![0x03, 0x0a, 0xbc, 0xde, 96, 70, 0x00];
+            bytes.push(bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)));
+            bytes
+        };
+        let mut samples = modulate(&chips(TpmsProtocol::Ford, &ford()), TpmsProtocol::Ford, 5e3);
+        let toyota_chips = chips(TpmsProtocol::Toyota, &toyota());
+        samples.extend(modulate(&toyota_chips, TpmsProtocol::Toyota, -12e3));
+        let schrader_chips = chips(TpmsProtocol::Schrader, &schrader);
+        samples.extend(modulate(&schrader_chips, TpmsProtocol::Schrader, 0.0));
+        let mut decoder = TpmsDecoder::new(RATE);
+        let mut readings = Vec::new();
+        decoder.work(&samples, &mut readings);
+        readings.extend(decoder.flush());
+        let found: Vec<_> = readings.iter().map(|r| (r.protocol, r.id)).collect();
+        assert_eq!(
+            found,
+            [
+                (TpmsProtocol::Ford, 0x1234_abcd),
+                (TpmsProtocol::Toyota, 0xdead_beef),
+                (TpmsProtocol::Schrader, 0x0a_bcde),
+            ]
+        );
+        // Ford: 0x188 quarter psi, or 98 psi, and 78 less 56 °C.
+        assert!((readings[0].pressure_kpa - 98.0 * PSI).abs() < 1e-9);
+        assert_eq!(readings[0].temperature_c, 22.0);
+        assert!((readings[1].pressure_kpa - 35.0 * PSI).abs() < 1e-9);
+        assert_eq!(readings[1].temperature_c, 25.0);
+        assert_eq!(
+            (readings[2].pressure_kpa, readings[2].temperature_c),
+            (240.0, 20.0)
+        );
+        assert_eq!(readings[2].flags, 0x03);
+    }
+
+    #[test]
+    fn test_rejects_a_failed_check() {
+        let mut bytes = toyota();
+        bytes[5] ^= 0x10;
+        let samples = modulate(
+            &chips(TpmsProtocol::Toyota, &bytes),
+            TpmsProtocol::Toyota,
+            0.0,
+        );
+        let mut decoder = TpmsDecoder::new(RATE);
+        let mut readings = decoder.process(&samples);
+        readings.extend(decoder.flush());
+        assert!(readings.is_empty(), "{readings:?}");
+    }
+}
@@ -0,0 +1,466 @@
+//! WSPR, the weak-signal propagation reporter.
+//!
+//! Beacons transmit in two-minute windows starting on even minutes, one
+//! second in. Each sends 162 symbols of 4-FSK at 1.46 baud with tones
+//! 1.46 Hz apart, for 110.6 s in all, in a 200 Hz band. The low bit of each
+//! tone follows a fixed pseudo-random sync vector and the high bit carries
+//! one coded bit: a 50-bit callsign, locator and power, convolutionally
+//! coded at rate 1/2 with constraint length 32 and interleaved by bit
+//! reversal.
+//!
+//! [`WsprDecoder`] gathers each window, computes its spectrogram in steps of
+//! half a symbol and half a tone, ranks every time and frequency by how
+//! well the sync vector fits, and decodes the best candidates with a Fano
+//! sequential decoder, as wsprd does, though without its search over
+//! frequency drift.
+
+mod fano;
+mod message;
+
+pub use message::WsprMessage;
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::fft;
+use num_complex::Complex;
+use std::collections::HashSet;
+
+/// The length of a window in seconds.
+pub const WSPR_WINDOW: f64 = 120.0;
+/// The length of a symbol in seconds, the reciprocal of the tone spacing.
+pub const WSPR_SYMBOL: f64 = 8_192.0 / 12_000.0;
+
+const SYMBOLS: usize = 162;
+/// The low bit of each symbol's tone.
+#[rustfmt::skip]
+const SYNC: [u8; SYMBOLS] = [
+    1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0,
+    0, 0, 1, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 0, 1, 1, 0, 1, 0,
+    0, 0, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 1, 0, 0, 0, 1, 1, 0, 1, 0, 1, 0,
+    0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 0, 1, 1, 1,
+    0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 1, 1, 0, 0, 0, 1, 1, 0,
+    0, 0,
+];
+/// When in its window a signal nominally starts, in seconds.
+const START: f64 = 1.0;
+
+/// The symbol carrying each coded bit: they go out in order of their
+/// indices' bit reversals, skipping reversals past the end.
+fn interleaving() -> [usize; SYMBOLS] {
+    let mut order = [0; SYMBOLS];
+    let reversed = (0..=255u8).map(|i| usize::from(i.reverse_bits()));
+    for (slot, symbol) in order.iter_mut().zip(reversed.filter(|&j| j < SYMBOLS)) {
+        *slot = symbol;
+    }
+    order
+}
+
+/// The 162 tones, each from 0 to 3, that send `message`, or `None` if it
+/// cannot be packed.
+pub fn wspr_tones(message: &WsprMessage) -> Option<[u8; SYMBOLS]> {
+    Some(tones(message.pack()?))
+}
+
+fn tones(payload: u64) -> [u8; SYMBOLS] {
+    let mut tones = SYNC;
+    for (&symbol, bit) in interleaving().iter().zip(fano::encode(payload)) {
+        tones[symbol] += 2 * u8::from(bit);
+    }
+    tones
+}
+
+/// Settings for [`WsprDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WsprConfig {
+    /// Audio sample rate in Hz, such as the usual 12 kHz.
+    pub sample_rate: f64,
+    /// The range of centre frequencies searched, in Hz.
+    pub min_frequency: f64,
+    pub max_frequency: f64,
+    /// Candidates tried per window, best first.
+    pub max_candidates: usize,
+    /// Steps the Fano decoder may take per candidate before giving up.
+    pub max_cycles: usize,
+    /// Seconds from the start of the stream to the first window boundary.
+    pub window_offset: f64,
+}
+
+impl WsprConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        WsprConfig {
+            sample_rate,
+            min_frequency: 1_400.0,
+            max_frequency: 1_600.0,
+            max_candidates: 20,
+            max_cycles: 10_000 * SYMBOLS,
+            window_offset: 0.0,
+        }
+    }
+}
+
+/// A decoded message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WsprDecode {
+    /// The window it was heard in, counting from zero at the first boundary.
+    pub window: u64,
+    /// Seconds by which the signal started after its nominal one second
+    /// into the window.
+    pub time_offset: f64,
+    /// The signal's centre frequency in Hz, midway between its middle two
+    /// tones.
+    pub frequency: f64,
+    /// Signal-to-noise ratio in dB, in a 2500 Hz bandwidth.
+    pub snr: f64,
+    pub message: WsprMessage,
+}
+
+/// Power at steps of half a symbol in time and half a tone in frequency.
+struct Spectrogram {
+    steps: usize,
+    bins: usize,
+    power: Vec<f64>,
+}
+
+impl Spectrogram {
+    fn at(&self, step: usize, bin: usize) -> f64 {
+        self.power[step * self.bins + bin]
+    }
+}
+
+/// ln I0(x), the modified Bessel function of the first kind and order zero.
+fn ln_bessel_i0(x: f64) -> f64 {
+    if x > 15.0 {
+        return x - 0.5 * (2.0 * std::f64::consts::PI * x).ln();
+    }
+    let quarter = x * x / 4.0;
+    let (mut term, mut sum) = (1.0, 1.0);
+    for k in 1..60 {
+        term *= quarter / (k * k) as f64;
+        sum += term;
+    }
+    sum.ln()
+}
+
+/// Decodes WSPR from audio, one window at a time.
+#[derive(Debug, Clone)]
+pub struct WsprDecoder {
+    config: WsprConfig,
+    /// Samples per symbol, even so that half-symbol steps are whole.
+    symbol_length: usize,
+    window_length: usize,
+    samples: Vec<f64>,
+    /// Samples still to skip before the first window boundary.
+    skip: usize,
+    window: u64,
+}
+
+impl WsprDecoder {
+    /// How well the sync vector must fit, as the mean share of each
+    /// symbol's power on the side it predicts, less the share off it; noise
+    /// alone gives zero.
+    const MIN_SYNC: f64 = 0.2;
+
+    pub fn new(config: WsprConfig) -> Self {
+        let symbol_length = ((WSPR_SYMBOL * config.sample_rate / 2.0).round() as usize).max(1) * 2;
+        WsprDecoder {
+            window_length: (WSPR_WINDOW * config.sample_rate).round() as usize,
+            skip: (config.window_offset * config.sample_rate).round().max(0.0) as usize,
+            config,
+            symbol_length,
+            samples: Vec::new(),
+            window: 0,
+        }
+    }
+
+    pub fn config(&self) -> &WsprConfig {
+        &self.config
+    }
+
+    /// Discards the window in progress. The window count carries on, so
+    /// the next window decoded is the one after.
+    pub fn reset(&mut self) {
+        if !self.samples.is_empty() {
+            self.samples.clear();
+            self.window += 1;
+        }
+    }
+
+    /// Accepts one audio sample, appending the decodes of a window each time
+    /// one completes.
+    pub fn push(&mut self, sample: f64, output: &mut Vec<WsprDecode>) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        self.samples.push(sample);
+        if self.samples.len() == self.window_length {
+            let window = self.window;
+            output.extend(
+                self.decode_window(&self.samples)
+                    .into_iter()
+                    .map(|decode| WsprDecode { window, ..decode }),
+            );
+            trace_event!(debug, window, "WSPR window decoded");
+            self.samples.clear();
+            self.window += 1;
+        }
+    }
+
+    /// Decodes one window of audio starting on its boundary, such as a
+    /// recording. The decodes' window numbers are zero.
+    pub fn decode_window(&self, samples: &[f64]) -> Vec<WsprDecode> {
+        let spectrogram = self.spectrogram(samples);
+        let (candidates, noise) = self.candidates(&spectrogram);
+        let rate = self.config.sample_rate;
+        let bin_width = rate / (2 * self.symbol_length) as f64;
+        let mut seen = HashSet::new();
+        let mut decodes = Vec::new();
+        for (step, bin) in candidates {
+            let Some(payload) = self.decode_candidate(&spectrogram, noise, step, bin) else {
+                continue;
+            };
+            let Some(message) = WsprMessage::unpack(payload).filter(|_| seen.insert(payload))
+            else {
+                continue;
+            };
+            let tones = tones(payload);
+            let signal = (0..SYMBOLS)
+                .map(|k| spectrogram.at(step + 2 * k, bin + 2 * usize::from(tones[k])))
+                .sum::<f64>()
+                / SYMBOLS as f64;
+            // A bin is as wide as one tone, so the noise in 2500 Hz is that
+            // many bins' worth.
+            let snr = 10.0 * (signal / noise - 1.0).max(1e-3).log10()
+                - 10.0 * (2_500.0 * WSPR_SYMBOL).log10();
+            decodes.push(WsprDecode {
+                window: 0,
+                time_offset: (step * self.symbol_length / 2) as f64 / rate - START,
+                frequency: (bin + 3) as f64 * bin_width,
+                snr,
+                message,
+            });
+        }
+        decodes
+    }
+
+    fn spectrogram(&self, samples: &[f64]) -> Spectrogram {
+        let length = self.symbol_length;
+        let hop = length / 2;
+        let bin_width = self.config.sample_rate / (2 * length) as f64;
+        let bins = ((self.config.max_frequency / bin_width).ceil() as usize + 8).min(length);
+        let steps =
+            samples.len().saturating_sub(length) / hop + usize::from(samples.len() >= length);
+        let mut power = Vec::with_capacity(steps * bins);
+        let mut buffer = vec![Complex::new(0.0, 0.0); 2 * length];
+        for step in 0..steps {
+            // Zero-padded to twice the symbol, for bins half a tone apart.
+            for (slot, &sample) in buffer.iter_mut().zip(&samples[step * hop..]) {
+                *slot = Complex::new(sample, 0.0);
+            }
+            buffer[length..]
+                .iter_mut()
+                .for_each(|slot| *slot = Complex::new(0.0, 0.0));
+            fft(&mut buffer);
+            power.extend(buffer[..bins].iter().map(|bin| bin.norm_sqr()));
+        }
+        Spectrogram { steps, bins, power }
+    }
+
+    /// Times and lowest-tone bins where the sync vector fits, best first,
+    /// and the noise power per bin.
+    fn candidates(&self, spectrogram: &Spectrogram) -> (Vec<(usize, usize)>, f64) {
+        let bin_width = self.config.sample_rate / (2 * self.symbol_length) as f64;
+        // Candidates are named by their lowest tone, three bins below centre.
+        let first_bin =
+            ((self.config.min_frequency / bin_width).floor() as usize).saturating_sub(3);
+        let last_bin = ((self.config.max_frequency / bin_width).ceil() as usize)
+            .saturating_sub(3)
+            .min(spectrogram.bins.saturating_sub(7));
+        let span = 2 * (SYMBOLS - 1) + 1;
+        if spectrogram.steps < span || last_bin <= first_bin {
+            return (Vec::new(), 1.0);
+        }
+        let starts = spectrogram.steps - span + 1;
+        let width = last_bin - first_bin;
+        let score = |step: usize, bin: usize| {
+            let mut total = 0.0;
+            for (k, &sync) in SYNC.iter().enumerate() {
+                let p: [f64; 4] =
+                    std::array::from_fn(|tone| spectrogram.at(step + 2 * k, bin + 2 * tone));
+                let ones = p[1] + p[3];
+                let zeros = p[0] + p[2];
+                let fit = (ones - zeros) / (ones + zeros).max(f64::MIN_POSITIVE);
+                total += if sync == 1 { fit } else { -fit };
+            }
+            total / SYMBOLS as f64
+        };
+        let scores: Vec<f64> = (0..starts)
+            .flat_map(|step| (first_bin..last_bin).map(move |bin| (step, bin)))
+            .map(|(step, bin)| score(step, bin))
+            .collect();
+        let at = |step: usize, bin: usize| scores[step * width + bin - first_bin];
+        let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+        for step in 0..starts {
+            for bin in first_bin..last_bin {
+                let here = at(step, bin);
+                let peak = (step.saturating_sub(1)..(step + 2).min(starts))
+                    .flat_map(|s| {
+                        (bin.saturating_sub(1).max(first_bin)..(bin + 2).min(last_bin))
+                            .map(move |b| (s, b))
+                    })
+                    .all(|(s, b)| at(s, b) <= here);
+                if here > Self::MIN_SYNC && peak {
+                    candidates.push((here, step, bin));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.truncate(self.config.max_candidates);
+
+        // The median of noise-like power is ln 2 of its mean.
+        let mut band: Vec<f64> = (0..spectrogram.steps)
+            .flat_map(|step| (first_bin..last_bin + 7).map(move |bin| spectrogram.at(step, bin)))
+            .collect();
+        let middle = band.len() / 2;
+        let median = *band.select_nth_unstable_by(middle, f64::total_cmp).1;
+        let noise = (median / std::f64::consts::LN_2).max(f64::MIN_POSITIVE);
+        (
+            candidates
+                .into_iter()
+                .map(|(_, step, bin)| (step, bin))
+                .collect(),
+            noise,
+        )
+    }
+
+    /// The message at a candidate, if the Fano decoder finds one.
+    fn decode_candidate(
+        &self,
+        spectrogram: &Spectrogram,
+        noise: f64,
+        step: usize,
+        bin: usize,
+    ) -> Option<u64> {
+        // For each symbol, the power in the tones for a coded 0 and a 1.
+        let pairs: Vec<[f64; 2]> = SYNC
+            .iter()
+            .enumerate()
+            .map(|(k, &sync)| {
+                let tone =
+                    |bit: u8| spectrogram.at(step + 2 * k, bin + 2 * usize::from(sync + 2 * bit));
+                [tone(0), tone(1)]
+            })
+            .collect();
+        // Each tone is a Rician magnitude given the signal and Rayleigh
+        // without it, which sets the likelihoods with the signal's power.
+        let signal = (pairs.iter().map(|&[zero, one]| zero.max(one)).sum::<f64>() / SYMBOLS as f64
+            - noise)
+            .max(noise * 1e-3);
+        let mut likelihoods = [0.0; fano::CODED_BITS];
+        for (likelihood, &symbol) in likelihoods.iter_mut().zip(interleaving().iter()) {
+            let [zero, one] =
+                pairs[symbol].map(|p| ln_bessel_i0(2.0 * (signal * p).sqrt() / noise));
+            *likelihood = zero - one;
+        }
+        fano::decode(&likelihoods, self.config.max_cycles)
+    }
+}
+
+impl Block for WsprDecoder {
+    type Input = f64;
+    type Output = WsprDecode;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<WsprDecode>) -> usize {
+        for &sample in input {
+            self.push(sample, output);
+        }
+        input.len()
+    }
+
+    /// Accepts `min_frequency` and `max_frequency`, in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("min_frequency", Some(frequency)) if frequency >= 0.0 => {
+                self.config.min_frequency = frequency
+            }
+            ("max_frequency", Some(frequency)) if frequency > 0.0 => {
+                self.config.max_frequency = frequency
+            }
+            ("min_frequency" | "max_frequency", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use std::f64::consts::PI;
+
+    const RATE: f64 = 6_000.0;
+
+    /// Adds `message` to `window` at `snr` dB against noise of uniform
+    /// samples.
+    fn transmit(window: &mut [f64], message: &str, frequency: f64, delay: f64, snr: f64) {
+        let tones = wspr_tones(&WsprMessage::parse(message).unwrap()).unwrap();
+        // Uniform noise in [-1, 1) has variance 1/3, of which 2500 Hz is a
+        // fraction of the 3 kHz band.
+        let noise = 2_500.0 / 3_000.0 / 3.0;
+        let amplitude = (2.0 * noise * 10f64.powf(snr / 10.0)).sqrt();
+        let length = (WSPR_SYMBOL * RATE).round() as usize;
+        let start = ((START + delay) * RATE) as usize;
+        let mut phase = 0.0;
+        for (k, &tone) in tones.iter().enumerate() {
+            let step = 2.0 * PI * (frequency + (f64::from(tone) - 1.5) / WSPR_SYMBOL) / RATE;
+            for sample in &mut window[start + k * length..start + (k + 1) * length] {
+                *sample += amplitude * f64::sin(phase);
+                phase += step;
+            }
+        }
+    }
+
+    #[test]
+    fn test_interleaving_is_a_permutation() {
+        let mut order = interleaving();
+        order.sort_unstable();
+        assert!(order.iter().enumerate().all(|(k, &symbol)| k == symbol));
+        let tones = wspr_tones(&WsprMessage::parse("K1ABC FN42 37").unwrap()).unwrap();
+        assert!(tones
+            .iter()
+            .zip(SYNC)
+            .all(|(&tone, sync)| tone & 1 == sync && tone < 4));
+    }
+
+    #[test]
+    fn test_decodes_weak_signals() {
+        // Half a second of another window, then this one.
+        let offset = 3_000;
+        let mut stream = real_noise(offset + 720_000, 12);
+        transmit(&mut stream[offset..], "K1ABC FN42 37", 1_500.0, 0.0, -22.0);
+        transmit(&mut stream[offset..], "G4XYZ IO91 23", 1_538.3, 1.2, -26.0);
+        let mut config = WsprConfig::new(RATE);
+        config.window_offset = 0.5;
+        let mut decoder = WsprDecoder::new(config);
+        let mut decodes = Vec::new();
+        decoder.work(&stream, &mut decodes);
+        assert_eq!(decodes.len(), 2, "{decodes:?}");
+        decodes.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+        for (decode, (text, frequency, delay, snr)) in decodes.iter().zip([
+            ("K1ABC FN42 37", 1_500.0, 0.0, -22.0),
+            ("G4XYZ IO91 23", 1_538.3, 1.2, -26.0),
+        ]) {
+            assert_eq!(decode.window, 0);
+            assert_eq!(decode.message.to_string(), text);
+            assert!((decode.frequency - frequency).abs() < 0.8, "{decode:?}");
+            assert!((decode.time_offset - delay).abs() < 0.35, "{decode:?}");
+            assert!((decode.snr - snr).abs() < 3.0, "{decode:?}");
+        }
+        assert!(decoder
+            .set_parameter("min_frequency", &ParamValue::Float(-1.0))
+            .is_err());
+    }
+}
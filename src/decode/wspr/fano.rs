@@ -0,0 +1,133 @@
+//! WSPR's convolutional code and its sequential decoder.
+//!
+//! The code has rate 1/2 and constraint length 32, far too long for a
+//! Viterbi decoder's 2^31 states. Fano's algorithm instead follows one path
+//! at a time, pressing on while the path's metric stays above a threshold
+//! and backing up to try the other branch when it falls below, raising the
+//! threshold as the path proves good and lowering it when every nearby path
+//! is bad.
+
+/// The taps for each of a bit's two coded bits.
+const POLYNOMIALS: [u32; 2] = [0xf2d0_5351, 0xe461_3c47];
+pub(super) const MESSAGE_BITS: usize = 50;
+/// The message and the 31 zeros that flush the encoder.
+const BITS: usize = MESSAGE_BITS + 31;
+pub(super) const CODED_BITS: usize = 2 * BITS;
+/// The step by which the threshold moves, in bits of metric.
+const DELTA: f64 = 2.0;
+
+/// The coded bits sent as a bit enters the encoder, whose latest bits are
+/// the low bits of `state`.
+fn branch(state: u64) -> [bool; 2] {
+    POLYNOMIALS.map(|taps| (state as u32 & taps).count_ones() % 2 == 1)
+}
+
+/// The 162 coded bits for a 50-bit message, in order before interleaving.
+pub(super) fn encode(message: u64) -> [bool; CODED_BITS] {
+    let mut coded = [false; CODED_BITS];
+    let mut state = 0;
+    for (i, pair) in coded.chunks_mut(2).enumerate() {
+        let bit = i < MESSAGE_BITS && message >> (MESSAGE_BITS - 1 - i) & 1 == 1;
+        state = state << 1 | u64::from(bit);
+        pair.copy_from_slice(&branch(state));
+    }
+    coded
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Node {
+    /// The path's metric on arriving here.
+    metric: f64,
+    /// The bits so far, the latest lowest.
+    state: u64,
+    /// What each branch adds to the metric, the better first.
+    branches: [f64; 2],
+    /// The bit for the better branch.
+    better: u64,
+    /// Which of the two branches is being tried.
+    tried: usize,
+}
+
+/// Decodes from each coded bit's log-likelihood ratio, ln P(0)/P(1),
+/// giving up after `max_cycles` steps forward or back.
+pub(super) fn decode(likelihoods: &[f64; CODED_BITS], max_cycles: usize) -> Option<u64> {
+    // Each coded bit adds its Fano metric: the log2 of its probability over
+    // one half, less the code rate.
+    let metrics: Vec<[f64; 2]> = likelihoods
+        .iter()
+        .map(|&likelihood| {
+            let zero = 1.0 / (1.0 + (-likelihood).exp());
+            [zero, 1.0 - zero].map(|p| (2.0 * p).max(1e-12).log2() - 0.5)
+        })
+        .collect();
+    let metric = |depth: usize, state: u64| -> f64 {
+        let [a, b] = branch(state);
+        metrics[2 * depth][usize::from(a)] + metrics[2 * depth + 1][usize::from(b)]
+    };
+    let prepare = |node: &mut Node, depth: usize| {
+        let zero = metric(depth, node.state << 1);
+        // The tail is known to be zeros.
+        let one = match depth < MESSAGE_BITS {
+            true => metric(depth, node.state << 1 | 1),
+            false => f64::NEG_INFINITY,
+        };
+        node.better = u64::from(one > zero);
+        node.branches = [zero.max(one), zero.min(one)];
+        node.tried = 0;
+    };
+
+    let mut nodes = [Node::default(); BITS + 1];
+    prepare(&mut nodes[0], 0);
+    let mut depth = 0;
+    let mut threshold = 0.0;
+    for _ in 0..max_cycles {
+        let node = nodes[depth];
+        let next = node.metric + node.branches[node.tried];
+        if next >= threshold {
+            // On a first visit, raise the threshold as far as the path allows.
+            if node.metric < threshold + DELTA {
+                while next >= threshold + DELTA {
+                    threshold += DELTA;
+                }
+            }
+            depth += 1;
+            nodes[depth].metric = next;
+            nodes[depth].state = node.state << 1 | (node.better ^ node.tried as u64);
+            if depth == BITS {
+                return Some(nodes[MESSAGE_BITS].state & ((1 << MESSAGE_BITS) - 1));
+            }
+            prepare(&mut nodes[depth], depth);
+            continue;
+        }
+        loop {
+            if depth == 0 || nodes[depth - 1].metric < threshold {
+                // Nowhere to go: lower the threshold and look forward again.
+                threshold -= DELTA;
+                nodes[depth].tried = 0;
+                break;
+            }
+            depth -= 1;
+            if depth < MESSAGE_BITS && nodes[depth].tried == 0 {
+                nodes[depth].tried = 1;
+                break;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrects_errors() {
+        let message = 0x2bad_cafe_f00d & ((1 << MESSAGE_BITS) - 1);
+        let coded = encode(message);
+        let mut likelihoods = coded.map(|bit| if bit { -3.0 } else { 3.0 });
+        for k in 0..14 {
+            likelihoods[k * 11 + 3] *= -0.3;
+        }
+        assert_eq!(decode(&likelihoods, 100_000), Some(message));
+    }
+}
@@ -0,0 +1,185 @@
+//! WSPR's 50-bit messages.
+//!
+//! A standard message packs a callsign into 28 bits, a four-character
+//! locator into 15 and the transmitter's power in dBm into 7. The other two
+//! types, for compound callsigns and six-character locators, announce
+//! themselves through power values a standard message cannot have, and are
+//! not decoded here.
+
+use std::fmt;
+
+/// Digits, letters and space. Each character of a packed callsign takes a
+/// slice of it: all of it, digits and letters, digits alone, or letters and
+/// space.
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ ";
+/// Packed locators run below this.
+const LOCATORS: u32 = 180 * 180;
+
+/// A standard WSPR message, such as `K1ABC FN42 37`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WsprMessage {
+    /// Up to six characters, with a digit second or third.
+    pub call: String,
+    /// A Maidenhead locator such as `FN42`.
+    pub locator: String,
+    /// Transmitter power in dBm, from 0 to 60 and ending in 0, 3 or 7.
+    pub power: u8,
+}
+
+impl WsprMessage {
+    /// Reads a message as written, such as `K1ABC FN42 37`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.to_ascii_uppercase();
+        let [call, locator, power] = text.split_whitespace().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let message = WsprMessage {
+            call: call.to_string(),
+            locator: locator.to_string(),
+            power: power.parse().ok()?,
+        };
+        message.pack().map(|_| message)
+    }
+
+    /// The 50-bit payload for this message, or `None` if it cannot be
+    /// packed.
+    pub fn pack(&self) -> Option<u64> {
+        let power = self.power;
+        if power > 60 || ![0, 3, 7].contains(&(power % 10)) {
+            return None;
+        }
+        let call = pack_call(&self.call)?;
+        let locator = pack_locator(&self.locator)?;
+        Some(u64::from(call) << 22 | u64::from(locator) << 7 | u64::from(power + 64))
+    }
+
+    /// Reads a 50-bit payload, or `None` if it is not a standard message.
+    pub fn unpack(payload: u64) -> Option<Self> {
+        let power = (payload & 0x7f).checked_sub(64)? as u8;
+        if power > 60 || ![0, 3, 7].contains(&(power % 10)) {
+            return None;
+        }
+        Some(WsprMessage {
+            call: unpack_call((payload >> 22) as u32)?,
+            locator: unpack_locator((payload >> 7 & 0x7fff) as u32)?,
+            power,
+        })
+    }
+}
+
+impl fmt::Display for WsprMessage {
+    /// Formats as WSJT-X shows messages: callsign, locator and power.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.call, self.locator, self.power)
+    }
+}
+
+fn pack_call(call: &str) -> Option<u32> {
+    let call = call.as_bytes();
+    // Placed so that the digit is third, and padded to six.
+    let start = usize::from(call.get(2).is_none_or(|c| !c.is_ascii_digit()));
+    if start + call.len() > 6 {
+        return None;
+    }
+    let mut padded = [b' '; 6];
+    padded[start..start + call.len()].copy_from_slice(call);
+    let index = |c: u8, alphabet: &[u8]| alphabet.iter().position(|&a| a == c).map(|i| i as u32);
+    let mut value = index(padded[0], ALPHANUMERIC)?;
+    value = value * 36 + index(padded[1], &ALPHANUMERIC[..36])?;
+    value = value * 10 + index(padded[2], &ALPHANUMERIC[..10])?;
+    for &c in &padded[3..] {
+        value = value * 27 + index(c, &ALPHANUMERIC[10..])?;
+    }
+    Some(value)
+}
+
+fn unpack_call(mut value: u32) -> Option<String> {
+    let mut call = [0; 6];
+    for c in call[3..].iter_mut().rev() {
+        *c = ALPHANUMERIC[10 + (value % 27) as usize];
+        value /= 27;
+    }
+    call[2] = ALPHANUMERIC[(value % 10) as usize];
+    value /= 10;
+    call[1] = ALPHANUMERIC[(value % 36) as usize];
+    value /= 36;
+    call[0] = *ALPHANUMERIC.get(value as usize)?;
+    // Spaces may only pad the ends.
+    let call = std::str::from_utf8(&call).ok()?.trim();
+    (!call.contains(' ')).then(|| call.to_string())
+}
+
+fn pack_locator(locator: &str) -> Option<u32> {
+    let &[a, b, c, d] = locator.as_bytes() else {
+        return None;
+    };
+    let field = |c: u8| (b'A'..=b'R').contains(&c).then(|| u32::from(c - b'A'));
+    let square = |c: u8| c.is_ascii_digit().then(|| u32::from(c - b'0'));
+    let longitude = 10 * field(a)? + square(c)?;
+    let latitude = 10 * field(b)? + square(d)?;
+    Some((179 - longitude) * 180 + latitude)
+}
+
+fn unpack_locator(value: u32) -> Option<String> {
+    if value >= LOCATORS {
+        return None;
+    }
+    let longitude = 179 - value / 180;
+    let latitude = value % 180;
+    let field = |v: u32| char::from(b'A' + (v / 10) as u8);
+    let square = |v: u32| char::from(b'0' + (v % 10) as u8);
+    Some(
+        [
+            field(longitude),
+            field(latitude),
+            square(longitude),
+            square(latitude),
+        ]
+        .iter()
+        .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_messages() {
+        for text in [
+            "K1ABC FN42 37",
+            "G4XYZ IO91 23",
+            "VK2DEF QF56 0",
+            "W9XY EN37 60",
+            "9A1A JN75 10",
+            "A1B AA00 7",
+        ] {
+            let message = WsprMessage::parse(text).unwrap();
+            let payload = message.pack().unwrap();
+            assert!(payload < 1 << 50);
+            assert_eq!(WsprMessage::unpack(payload), Some(message.clone()));
+            assert_eq!(message.to_string(), text);
+        }
+        assert_eq!(WsprMessage::parse("k1abc fn42 37").unwrap().call, "K1ABC");
+    }
+
+    #[test]
+    fn test_rejects_what_does_not_fit() {
+        for text in [
+            "K1ABCD FN42 37",
+            "KAB FN42 37",
+            "K1ABC FN42 25",
+            "K1ABC FN42 63",
+            "K1ABC SZ42 37",
+            "K1ABC FN4 37",
+            "K1ABC/P FN42 37",
+            "K1ABC FN42",
+        ] {
+            assert_eq!(WsprMessage::parse(text), None, "{text}");
+        }
+        // A power field of another type's, such as 1 dBm.
+        let payload = WsprMessage::parse("K1ABC FN42 0").unwrap().pack().unwrap();
+        assert_eq!(WsprMessage::unpack(payload + 1), None);
+    }
+}
@@ -0,0 +1,5 @@
+//! Finding signals in a stream before anything is known about them.
+
+pub mod burst;
+pub mod energy;
+pub mod scanner;
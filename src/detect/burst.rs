@@ -0,0 +1,283 @@
+//! Segmenting a stream into individual transmissions by power gating.
+//!
+//! The gate compares a moving average of the sample power with a running
+//! noise floor. The floor falls quickly and rises slowly, and only moves while
+//! the gate is closed, so it settles on the quiet between transmissions. Each
+//! burst is padded with the samples just before the gate opened and just after
+//! it closed, so a decoder also sees the ramp-up and the tail.
+
+use crate::block::Block;
+use crate::dsp::envelope::{EnvelopeDetector, Smoothing};
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use std::collections::VecDeque;
+
+/// Settings for [`BurstExtractor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurstConfig {
+    /// Complex sample rate in Hz, used for timestamps.
+    pub sample_rate: f64,
+    /// Power above the noise floor that opens the gate, in dB.
+    pub threshold_db: f64,
+    /// How far below the threshold the power must fall to close the gate.
+    pub hysteresis_db: f64,
+    /// Samples in the power moving average.
+    pub smoothing: usize,
+    /// Samples kept before the gate opened.
+    pub pre_samples: usize,
+    /// Samples kept after the gate closed.
+    pub post_samples: usize,
+    /// Bursts with fewer gated samples than this are discarded as glitches.
+    pub min_length: usize,
+    /// Bursts are cut off at this many samples, so a stuck carrier cannot grow
+    /// one without bound.
+    pub max_length: usize,
+}
+
+impl BurstConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        BurstConfig {
+            sample_rate,
+            threshold_db: 10.0,
+            hysteresis_db: 3.0,
+            smoothing: 16,
+            pre_samples: 64,
+            post_samples: 64,
+            min_length: 0,
+            max_length: 1 << 20,
+        }
+    }
+}
+
+/// One extracted transmission.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Burst {
+    /// Index in the stream of the first sample, including padding.
+    pub start_sample: u64,
+    /// Seconds from the start of the stream to the first sample.
+    pub start_time: f64,
+    /// Strongest smoothed power relative to the noise floor, in dB.
+    pub peak_snr_db: f64,
+    pub samples: Vec<Complex<f64>>,
+}
+
+impl Burst {
+    pub fn duration(&self, sample_rate: f64) -> f64 {
+        self.samples.len() as f64 / sample_rate
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Open {
+    burst: Burst,
+    gated: usize,
+    quiet: usize,
+    peak_power: f64,
+}
+
+/// Cuts a stream into [`Burst`]s.
+#[derive(Debug, Clone)]
+pub struct BurstExtractor {
+    config: BurstConfig,
+    envelope: EnvelopeDetector,
+    floor: Option<f64>,
+    history: VecDeque<Complex<f64>>,
+    position: u64,
+    open: Option<Open>,
+}
+
+impl BurstExtractor {
+    /// Per-sample rates at which the noise floor follows the power down and up.
+    const FLOOR_FALL: f64 = 0.1;
+    const FLOOR_RISE: f64 = 0.001;
+
+    pub fn new(config: BurstConfig) -> Self {
+        BurstExtractor {
+            envelope: EnvelopeDetector::new(Smoothing::Rms {
+                window: config.smoothing,
+            }),
+            config,
+            floor: None,
+            history: VecDeque::new(),
+            position: 0,
+            open: None,
+        }
+    }
+
+    pub fn config(&self) -> &BurstConfig {
+        &self.config
+    }
+
+    /// The current noise floor estimate, once the moving average has filled.
+    pub fn noise_floor(&self) -> Option<f64> {
+        self.floor
+    }
+
+    /// Processes samples and returns the bursts that ended within them.
+    pub fn process(&mut self, samples: &[Complex<f64>]) -> Vec<Burst> {
+        let open_ratio = 10f64.powf(self.config.threshold_db / 10.0);
+        let close_ratio = 10f64.powf((self.config.threshold_db - self.config.hysteresis_db) / 10.0);
+        let mut bursts = Vec::new();
+        for &sample in samples {
+            let power = self.envelope.envelope(sample).powi(2);
+            let index = self.position;
+            self.position += 1;
+            let Some(floor) = self.floor else {
+                if self.envelope.settled() {
+                    self.floor = Some(power);
+                }
+                self.remember(sample);
+                continue;
+            };
+            match &mut self.open {
+                None if power > floor * open_ratio => {
+                    let padding: Vec<Complex<f64>> = self.history.drain(..).collect();
+                    let start_sample = index - padding.len() as u64;
+                    let mut burst = Burst {
+                        start_sample,
+                        start_time: start_sample as f64 / self.config.sample_rate,
+                        peak_snr_db: 0.0,
+                        samples: padding,
+                    };
+                    burst.samples.push(sample);
+                    self.open = Some(Open {
+                        burst,
+                        gated: 1,
+                        quiet: 0,
+                        peak_power: power,
+                    });
+                }
+                None => {
+                    let rate = if power < floor {
+                        Self::FLOOR_FALL
+                    } else {
+                        Self::FLOOR_RISE
+                    };
+                    self.floor = Some(floor + rate * (power - floor));
+                    self.remember(sample);
+                }
+                Some(open) => {
+                    open.burst.samples.push(sample);
+                    open.peak_power = open.peak_power.max(power);
+                    if power < floor * close_ratio {
+                        open.quiet += 1;
+                    } else {
+                        open.gated += open.quiet + 1;
+                        open.quiet = 0;
+                    }
+                    if open.quiet >= self.config.post_samples
+                        || open.burst.samples.len() >= self.config.max_length
+                    {
+                        bursts.extend(self.close());
+                    }
+                }
+            }
+        }
+        bursts
+    }
+
+    /// Ends the burst in progress, if any, as at the end of a recording.
+    pub fn flush(&mut self) -> Option<Burst> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Option<Burst> {
+        let open = self.open.take()?;
+        let floor = self.floor.unwrap_or(0.0);
+        if open.gated < self.config.min_length {
+            trace_event!(trace, samples = open.gated, "burst too short, discarded");
+            return None;
+        }
+        let mut burst = open.burst;
+        burst.peak_snr_db = 10.0 * (open.peak_power / floor).log10();
+        trace_event!(
+            debug,
+            start = burst.start_sample,
+            samples = burst.samples.len(),
+            snr_db = burst.peak_snr_db,
+            "burst"
+        );
+        Some(burst)
+    }
+
+    fn remember(&mut self, sample: Complex<f64>) {
+        self.history.push_back(sample);
+        while self.history.len() > self.config.pre_samples {
+            self.history.pop_front();
+        }
+    }
+}
+
+impl Block for BurstExtractor {
+    type Input = Complex<f64>;
+    type Output = Burst;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Burst>) -> usize {
+        output.extend(self.process(input));
+        input.len()
+    }
+
+    /// Accepts `threshold_db`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("threshold_db", Some(threshold)) => self.config.threshold_db = threshold,
+            ("threshold_db", None) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+
+    fn bursty() -> Vec<Complex<f64>> {
+        complex_noise(5000, 5)
+            .into_iter()
+            .enumerate()
+            .map(|(n, noise)| {
+                let on = (1000..1500).contains(&n) || (3000..3200).contains(&n) || n == 4000;
+                noise * 0.01
+                    + if on {
+                        Complex::new(1.0, 0.0)
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_extracts_padded_bursts() {
+        let mut config = BurstConfig::new(1000.0);
+        config.pre_samples = 50;
+        config.post_samples = 50;
+        config.min_length = 50;
+        let mut extractor = BurstExtractor::new(config);
+        let samples = bursty();
+        let mut bursts = extractor.process(&samples[..1200]);
+        bursts.extend(extractor.process(&samples[1200..]));
+        bursts.extend(extractor.flush());
+        // The single-sample spike at 4000 is shorter than min_length.
+        assert_eq!(bursts.len(), 2);
+        assert_eq!(bursts[0].start_sample, 950);
+        assert_eq!(bursts[0].start_time, 0.95);
+        // 500 gated samples, the moving average's tail, and both paddings.
+        let length = bursts[0].samples.len();
+        assert!((600..=620).contains(&length), "length {length}");
+        assert_eq!(bursts[1].start_sample, 2950);
+        assert!(bursts[0].peak_snr_db > 30.0);
+    }
+
+    #[test]
+    fn test_flush_returns_unfinished_burst() {
+        let mut extractor = BurstExtractor::new(BurstConfig::new(1000.0));
+        assert!(extractor.process(&bursty()[..1100]).is_empty());
+        let burst = extractor.flush().unwrap();
+        assert_eq!(burst.start_sample, 936);
+        assert_eq!(burst.samples.len(), 164);
+    }
+}
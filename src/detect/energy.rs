@@ -0,0 +1,271 @@
+//! Energy detection against an estimated noise floor.
+//!
+//! The input is split into FFT frames. In each frame the noise floor is
+//! estimated from the median bin power, which stays put as long as signals
+//! occupy less than half the band, and every run of adjacent bins more than
+//! the margin above it is a candidate region. Regions that overlap from one
+//! frame to the next are merged into a single detection, which is emitted once
+//! a frame passes without it.
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::{bin_frequency, windowed_spectrum, Window};
+use num_complex::Complex;
+use std::f64::consts::LN_2;
+
+/// Settings for [`EnergyDetector`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyDetectorConfig {
+    /// Complex sample rate in Hz.
+    pub sample_rate: f64,
+    /// Points per FFT frame, which sets both the time and the frequency
+    /// resolution of detections.
+    pub fft_size: usize,
+    /// How far above the noise floor a bin must rise to count, in dB.
+    pub margin_db: f64,
+    pub window: Window,
+}
+
+impl EnergyDetectorConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        EnergyDetectorConfig {
+            sample_rate,
+            fft_size: 1024,
+            margin_db: 10.0,
+            window: Window::Hann,
+        }
+    }
+}
+
+/// A region of time and frequency where energy exceeded the margin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detection {
+    /// Seconds from the start of the stream to the first frame containing it.
+    pub start: f64,
+    /// Seconds from the start of the stream to the end of its last frame.
+    pub stop: f64,
+    /// Lower band edge in Hz, relative to the tuned frequency.
+    pub low_frequency: f64,
+    /// Upper band edge in Hz, relative to the tuned frequency.
+    pub high_frequency: f64,
+    /// Strongest bin power relative to the noise floor, in dB.
+    pub peak_snr_db: f64,
+}
+
+impl Detection {
+    pub fn duration(&self) -> f64 {
+        self.stop - self.start
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        self.high_frequency - self.low_frequency
+    }
+
+    pub fn center_frequency(&self) -> f64 {
+        (self.low_frequency + self.high_frequency) / 2.0
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Active {
+    low_bin: usize,
+    high_bin: usize,
+    first_frame: u64,
+    last_frame: u64,
+    peak_snr: f64,
+}
+
+/// Emits a [`Detection`] for every transmission that rises above the noise.
+#[derive(Debug, Clone)]
+pub struct EnergyDetector {
+    config: EnergyDetectorConfig,
+    window: Vec<f64>,
+    frame: u64,
+    active: Vec<Active>,
+}
+
+impl EnergyDetector {
+    pub fn new(config: EnergyDetectorConfig) -> Self {
+        EnergyDetector {
+            window: config.window.coefficients(config.fft_size),
+            config,
+            frame: 0,
+            active: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &EnergyDetectorConfig {
+        &self.config
+    }
+
+    /// Processes one frame of exactly `fft_size` samples and returns the
+    /// detections that ended before it. Fails, changing nothing, for a frame
+    /// of any other length.
+    pub fn process_frame(&mut self, frame: &[Complex<f64>]) -> Result<Vec<Detection>> {
+        let size = self.config.fft_size;
+        if frame.len() != size {
+            return Err(DspError::InvalidLength {
+                expected: size,
+                found: frame.len(),
+            }
+            .into());
+        }
+        let spectrum = windowed_spectrum(frame, &self.window);
+        // Reorder from the most negative frequency up, so runs can span DC.
+        let power: Vec<f64> = (0..size)
+            .map(|index| spectrum[(index + size / 2) % size].norm_sqr())
+            .collect();
+        let mut sorted = power.clone();
+        sorted.sort_by(f64::total_cmp);
+        // The median of exponentially distributed noise power is ln 2 of its mean.
+        let floor = sorted[size / 2] / LN_2;
+        let threshold = floor * 10f64.powf(self.config.margin_db / 10.0);
+
+        let mut runs: Vec<(usize, usize, f64)> = Vec::new();
+        for (bin, &p) in power.iter().enumerate() {
+            if floor <= 0.0 || p <= threshold {
+                continue;
+            }
+            let snr = p / floor;
+            match runs.last_mut() {
+                Some(run) if run.1 + 1 == bin => {
+                    run.1 = bin;
+                    run.2 = run.2.max(snr);
+                }
+                _ => runs.push((bin, bin, snr)),
+            }
+        }
+
+        let frame_index = self.frame;
+        self.frame += 1;
+        let mut continued: Vec<Active> = Vec::new();
+        for (low, high, snr) in runs {
+            let mut merged = Active {
+                low_bin: low,
+                high_bin: high,
+                first_frame: frame_index,
+                last_frame: frame_index,
+                peak_snr: snr,
+            };
+            for list in [&mut self.active, &mut continued] {
+                let mut index = 0;
+                while index < list.len() {
+                    let other = &list[index];
+                    if other.low_bin <= merged.high_bin && merged.low_bin <= other.high_bin {
+                        let other = list.swap_remove(index);
+                        merged.low_bin = merged.low_bin.min(other.low_bin);
+                        merged.high_bin = merged.high_bin.max(other.high_bin);
+                        merged.first_frame = merged.first_frame.min(other.first_frame);
+                        merged.peak_snr = merged.peak_snr.max(other.peak_snr);
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+            continued.push(merged);
+        }
+        let ended = std::mem::replace(&mut self.active, continued);
+        Ok(ended.iter().map(|active| self.detection(active)).collect())
+    }
+
+    /// Ends every detection still in progress, as at the end of a recording.
+    pub fn finish(&mut self) -> Vec<Detection> {
+        let active = std::mem::take(&mut self.active);
+        active.iter().map(|active| self.detection(active)).collect()
+    }
+
+    fn detection(&self, active: &Active) -> Detection {
+        let size = self.config.fft_size;
+        let rate = self.config.sample_rate;
+        let frame_duration = size as f64 / rate;
+        let bin_width = rate / size as f64;
+        let frequency = |bin: usize| bin_frequency(((bin + size / 2) % size) as f64, size, rate);
+        let detection = Detection {
+            start: active.first_frame as f64 * frame_duration,
+            stop: (active.last_frame + 1) as f64 * frame_duration,
+            low_frequency: frequency(active.low_bin) - bin_width / 2.0,
+            high_frequency: frequency(active.high_bin) + bin_width / 2.0,
+            peak_snr_db: 10.0 * active.peak_snr.log10(),
+        };
+        trace_event!(
+            debug,
+            start = detection.start,
+            center_frequency = detection.center_frequency(),
+            snr_db = detection.peak_snr_db,
+            "detection"
+        );
+        detection
+    }
+}
+
+impl Block for EnergyDetector {
+    type Input = Complex<f64>;
+    type Output = Detection;
+
+    /// Consumes whole frames, leaving any remainder for the next call.
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Detection>) -> usize {
+        let size = self.config.fft_size.max(1);
+        let frames = input.len() / size * size;
+        for frame in input[..frames].chunks_exact(size) {
+            output.extend(self.process_frame(frame).into_iter().flatten());
+        }
+        frames
+    }
+
+    /// Accepts `margin_db`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("margin_db", Some(margin)) => self.config.margin_db = margin,
+            ("margin_db", None) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_detects_tone_burst() {
+        let rate = 102_400.0;
+        let mut config = EnergyDetectorConfig::new(rate);
+        config.margin_db = 15.0;
+        let mut detector = EnergyDetector::new(config);
+        let mut samples = complex_noise(1024 * 10, 7);
+        // A -20 kHz tone present in frames 3 to 5.
+        for (n, sample) in samples.iter_mut().enumerate().take(6 * 1024).skip(3 * 1024) {
+            *sample += Complex::from_polar(1.0, -2.0 * PI * 20_000.0 * n as f64 / rate);
+        }
+        let mut detections = Vec::new();
+        assert_eq!(detector.work(&samples[..5000], &mut detections), 4096);
+        detector.work(&samples[4096..], &mut detections);
+        detections.extend(detector.finish());
+        assert_eq!(detections.len(), 1);
+        let detection = detections[0];
+        assert_relative_eq!(detection.start, 0.03);
+        assert_relative_eq!(detection.duration(), 0.03, epsilon = 1e-12);
+        assert_relative_eq!(detection.center_frequency(), -20_000.0, epsilon = 200.0);
+        assert!(detection.bandwidth() < 1000.0);
+        assert!(detection.peak_snr_db > 25.0);
+    }
+
+    #[test]
+    fn test_noise_alone_is_quiet() {
+        let mut config = EnergyDetectorConfig::new(1.0);
+        config.margin_db = 15.0;
+        let mut detector = EnergyDetector::new(config);
+        let samples = complex_noise(1024 * 20, 3);
+        assert!(detector.process_frame(&samples[..1000]).is_err());
+        let mut detections = Vec::new();
+        detector.work(&samples, &mut detections);
+        detections.extend(detector.finish());
+        assert!(detections.is_empty());
+    }
+}
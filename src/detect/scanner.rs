@@ -0,0 +1,483 @@
+//! Stepping a receiver across channels and reporting the busy ones.
+//!
+//! The scanner tunes to each channel in turn, throws away the samples taken
+//! while the tuner settles, and averages the power over a short dwell. A
+//! channel whose power clears the squelch holds the scanner there until it
+//! has been quiet for the hang time, and then goes out as one
+//! [`Activity`] report, the way a scanner on a trunked or conventional
+//! system stops on a transmission and resumes after it.
+//!
+//! [`Scanner`] only counts samples and says where to tune, so it can be fed
+//! from a recording as well as a radio; [`Scanner::step`] drives a device
+//! that is both an [`SdrDevice`] and a [`Source`].
+
+use crate::error::{DspError, Result, SdrError};
+use crate::source::control::SdrDevice;
+use crate::source::Source;
+use num_complex::Complex;
+
+/// Channel centres from `start` to `stop` Hz inclusive, `step` apart.
+pub fn channel_range(start: f64, stop: f64, step: f64) -> Result<Vec<f64>> {
+    if !(step > 0.0 && start.is_finite() && stop.is_finite()) {
+        return Err(DspError::InvalidArgument(format!(
+            "channels from {start} to {stop} Hz need a positive step, got {step}"
+        ))
+        .into());
+    }
+    let count = ((stop - start) / step + 1e-9).floor().max(-1.0) as i64 + 1;
+    Ok((0..count).map(|n| start + n as f64 * step).collect())
+}
+
+/// When a channel counts as active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Squelch {
+    /// Mean power above this level, in dB relative to full scale.
+    Level(f64),
+    /// Mean power this many dB above the median of the last quiet
+    /// measurement on every channel. That follows the noise floor as the
+    /// gain or the band changes, as long as most channels are quiet.
+    AboveFloor(f64),
+}
+
+/// Settings for [`Scanner`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannerConfig {
+    /// Complex sample rate in Hz, used for times.
+    pub sample_rate: f64,
+    /// Samples discarded after each retune while the tuner settles.
+    pub settle_samples: usize,
+    /// Samples averaged for each measurement.
+    pub dwell_samples: usize,
+    pub squelch: Squelch,
+    /// Seconds an active channel must stay quiet before scanning resumes.
+    pub hang_time: f64,
+    /// Seconds after which an active channel is given up on anyway, so a
+    /// stuck carrier cannot stop the scan.
+    pub max_hold: f64,
+}
+
+impl ScannerConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        ScannerConfig {
+            sample_rate,
+            settle_samples: (sample_rate * 0.005) as usize,
+            dwell_samples: (sample_rate * 0.02).max(1.0) as usize,
+            squelch: Squelch::AboveFloor(10.0),
+            hang_time: 2.0,
+            max_hold: 60.0,
+        }
+    }
+}
+
+/// One transmission heard by the scanner.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Activity {
+    /// Channel centre in Hz.
+    pub frequency: f64,
+    /// Seconds from the start of the scan to the first active measurement.
+    pub start_time: f64,
+    /// Seconds from then to the end of the last active measurement.
+    pub duration: f64,
+    /// Strongest measured mean power, in dB relative to full scale.
+    pub peak_power_db: f64,
+    /// The peak relative to the noise floor, when [`Squelch::AboveFloor`]
+    /// is in use.
+    pub snr_db: Option<f64>,
+}
+
+/// What the caller should do after [`Scanner::process`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanStep {
+    /// Tune to this frequency before reading more samples.
+    pub retune: Option<f64>,
+    /// Transmissions that ended.
+    pub activity: Vec<Activity>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Hold {
+    activity: Activity,
+    /// Seconds of quiet since the last active measurement.
+    quiet: f64,
+}
+
+/// Steps through a list of channels, stopping on active ones.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    config: ScannerConfig,
+    channels: Vec<f64>,
+    index: usize,
+    /// The last quiet power on each channel, in dB.
+    floors: Vec<Option<f64>>,
+    /// Samples read since the scan began.
+    position: u64,
+    /// Settling samples still to discard.
+    settling: usize,
+    sum: f64,
+    count: usize,
+    hold: Option<Hold>,
+}
+
+impl Scanner {
+    /// A scanner over `channels`, in the order given, which starts on the
+    /// first.
+    pub fn new(config: ScannerConfig, channels: Vec<f64>) -> Result<Self> {
+        if channels.is_empty() {
+            return Err(DspError::InvalidArgument("no channels to scan".into()).into());
+        }
+        if config.dwell_samples == 0 {
+            return Err(
+                DspError::InvalidArgument("dwell must be at least one sample".into()).into(),
+            );
+        }
+        if !(config.sample_rate.is_finite() && config.sample_rate > 0.0) {
+            return Err(DspError::InvalidArgument(format!(
+                "sample rate must be positive, got {}",
+                config.sample_rate
+            ))
+            .into());
+        }
+        let floors = vec![None; channels.len()];
+        Ok(Scanner {
+            settling: config.settle_samples,
+            config,
+            channels,
+            index: 0,
+            floors,
+            position: 0,
+            sum: 0.0,
+            count: 0,
+            hold: None,
+        })
+    }
+
+    pub fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    pub fn channels(&self) -> &[f64] {
+        &self.channels
+    }
+
+    /// The channel the receiver should be on now.
+    pub fn frequency(&self) -> f64 {
+        self.channels[self.index]
+    }
+
+    /// Whether the scanner is holding on an active channel.
+    pub fn is_holding(&self) -> bool {
+        self.hold.is_some()
+    }
+
+    /// The median of the channels' quiet powers in dB, once at least half
+    /// of them have been measured.
+    pub fn noise_floor_db(&self) -> Option<f64> {
+        let mut known: Vec<f64> = self.floors.iter().flatten().copied().collect();
+        if known.len() * 2 < self.channels.len() {
+            return None;
+        }
+        known.sort_by(f64::total_cmp);
+        Some(known[known.len() / 2])
+    }
+
+    /// Takes samples received on [`frequency`](Scanner::frequency). When
+    /// the step asks for a retune, the rest of `samples` was taken on the
+    /// old channel and is only counted for time; read afresh after tuning.
+    pub fn process(&mut self, samples: &[Complex<f64>]) -> ScanStep {
+        let mut step = ScanStep::default();
+        for (n, sample) in samples.iter().enumerate() {
+            self.position += 1;
+            if self.settling > 0 {
+                self.settling -= 1;
+                continue;
+            }
+            self.sum += sample.norm_sqr();
+            self.count += 1;
+            if self.count < self.config.dwell_samples {
+                continue;
+            }
+            let power_db = 10.0 * (self.sum / self.count as f64).max(1e-30).log10();
+            self.sum = 0.0;
+            self.count = 0;
+            if let Some(activity) = self.measure(power_db, &mut step) {
+                step.activity.push(activity);
+            }
+            if step.retune.is_some() {
+                self.position += (samples.len() - n - 1) as u64;
+                return step;
+            }
+        }
+        step
+    }
+
+    /// Ends the transmission being held on, if any, as at the end of a
+    /// recording.
+    pub fn flush(&mut self) -> Option<Activity> {
+        self.hold.take().map(|hold| hold.activity)
+    }
+
+    fn measure(&mut self, power_db: f64, step: &mut ScanStep) -> Option<Activity> {
+        let now = self.position as f64 / self.config.sample_rate;
+        let dwell = self.config.dwell_samples as f64 / self.config.sample_rate;
+        let floor = self.noise_floor_db();
+        let active = match self.config.squelch {
+            Squelch::Level(level) => power_db > level,
+            Squelch::AboveFloor(margin) => floor.is_some_and(|floor| power_db > floor + margin),
+        };
+        let snr_db = match self.config.squelch {
+            Squelch::Level(_) => None,
+            Squelch::AboveFloor(_) => floor.map(|floor| power_db - floor),
+        };
+        match &mut self.hold {
+            None if active => {
+                trace_event!(
+                    debug,
+                    frequency = self.frequency(),
+                    power_db,
+                    "channel active"
+                );
+                self.hold = Some(Hold {
+                    activity: Activity {
+                        frequency: self.frequency(),
+                        start_time: now - dwell,
+                        duration: dwell,
+                        peak_power_db: power_db,
+                        snr_db,
+                    },
+                    quiet: 0.0,
+                });
+                None
+            }
+            None => {
+                self.floors[self.index] = Some(power_db);
+                self.advance(step);
+                None
+            }
+            Some(hold) => {
+                if active {
+                    hold.quiet = 0.0;
+                    hold.activity.duration = now - hold.activity.start_time;
+                    if power_db > hold.activity.peak_power_db {
+                        hold.activity.peak_power_db = power_db;
+                        hold.activity.snr_db = snr_db;
+                    }
+                } else {
+                    hold.quiet += dwell;
+                }
+                let held = now - hold.activity.start_time;
+                if hold.quiet < self.config.hang_time && held < self.config.max_hold {
+                    return None;
+                }
+                let activity = self.hold.take().map(|hold| hold.activity);
+                self.advance(step);
+                activity
+            }
+        }
+    }
+
+    fn advance(&mut self, step: &mut ScanStep) {
+        self.index = (self.index + 1) % self.channels.len();
+        self.settling = self.config.settle_samples;
+        self.sum = 0.0;
+        self.count = 0;
+        if self.channels.len() > 1 {
+            step.retune = Some(self.frequency());
+        }
+    }
+
+    /// Reads one buffer from `radio`, tuning it as the scan moves on, and
+    /// returns the transmissions that ended. Tune the radio to
+    /// [`frequency`](Scanner::frequency) before the first call. An empty
+    /// read ends the scan, flushing any transmission still held.
+    pub fn step<R>(
+        &mut self,
+        radio: &mut R,
+        buffer: &mut [Complex<f64>],
+    ) -> Result<Vec<Activity>, SdrError>
+    where
+        R: SdrDevice + Source<Sample = Complex<f64>>,
+        R::Error: Into<SdrError>,
+    {
+        let read = radio.read(buffer).map_err(Into::into)?;
+        if read == 0 {
+            return Ok(self.flush().into_iter().collect());
+        }
+        let step = self.process(&buffer[..read]);
+        if let Some(frequency) = step.retune {
+            radio.set_frequency(frequency)?;
+        }
+        Ok(step.activity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use crate::error::DeviceError;
+    use crate::source::control::GainStage;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_channel_range() {
+        assert_eq!(channel_range(100.0, 100.5, 0.125).unwrap().len(), 5);
+        assert_eq!(channel_range(1.0, 1.0, 1.0).unwrap(), [1.0]);
+        assert!(channel_range(2.0, 1.0, 1.0).unwrap().is_empty());
+        assert!(channel_range(1.0, 2.0, 0.0).is_err());
+        let channels = channel_range(446.00625e6, 446.09375e6, 12.5e3).unwrap();
+        assert_eq!(channels.len(), 8);
+        assert_relative_eq!(channels[7], 446.09375e6, epsilon = 1e-3);
+    }
+
+    /// A band of quiet channels with one carrier on `busy` for the first
+    /// `on_until` seconds, read back from whatever channel is tuned.
+    struct Band {
+        rate: f64,
+        frequency: f64,
+        busy: f64,
+        on_until: f64,
+        position: u64,
+        seed: u64,
+        retunes: usize,
+    }
+
+    impl Source for Band {
+        type Sample = Complex<f64>;
+        type Error = SdrError;
+
+        fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, SdrError> {
+            if self.position as f64 / self.rate > 30.0 {
+                return Ok(0);
+            }
+            self.seed += 1;
+            let noise = complex_noise(buffer.len(), self.seed);
+            for (slot, noise) in buffer.iter_mut().zip(noise) {
+                let t = self.position as f64 / self.rate;
+                let on = self.frequency == self.busy && t < self.on_until;
+                *slot = noise * 0.01
+                    + if on {
+                        Complex::new(0.3, 0.0)
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    };
+                self.position += 1;
+            }
+            Ok(buffer.len())
+        }
+    }
+
+    impl SdrDevice for Band {
+        fn name(&self) -> String {
+            "test band".to_string()
+        }
+
+        fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+            vec![(0.0, 1e9)]
+        }
+
+        fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+            self.frequency = frequency;
+            self.retunes += 1;
+            Ok(frequency)
+        }
+
+        fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+            Ok(rate)
+        }
+
+        fn gain_stages(&self) -> Vec<GainStage> {
+            Vec::new()
+        }
+
+        fn set_gain(&mut self, stage: &str, _gain: f64) -> Result<(), DeviceError> {
+            Err(DeviceError::Unsupported(stage.to_string()))
+        }
+
+        fn set_ppm(&mut self, _ppm: f64) -> Result<(), DeviceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_holds_on_active_channel_until_quiet() {
+        let mut config = ScannerConfig::new(10_000.0);
+        config.hang_time = 0.5;
+        let channels = channel_range(1e6, 1.01e6, 1e3).unwrap();
+        let mut scanner = Scanner::new(config, channels).unwrap();
+        let mut band = Band {
+            rate: 10_000.0,
+            frequency: scanner.frequency(),
+            busy: 1.005e6,
+            on_until: 8.0,
+            position: 0,
+            seed: 0,
+            retunes: 0,
+        };
+        let mut buffer = vec![Complex::new(0.0, 0.0); 256];
+        let mut heard = Vec::new();
+        loop {
+            let position = band.position;
+            heard.extend(scanner.step(&mut band, &mut buffer).unwrap());
+            if band.position == position {
+                break;
+            }
+        }
+        assert_eq!(heard.len(), 1, "{heard:?}");
+        let activity = &heard[0];
+        assert_eq!(activity.frequency, 1.005e6);
+        assert!(activity.start_time < 2.0, "{activity:?}");
+        assert!((activity.start_time + activity.duration - 8.0).abs() < 0.1);
+        assert_relative_eq!(
+            activity.peak_power_db,
+            10.0 * 0.09f64.log10(),
+            epsilon = 0.5
+        );
+        assert!(activity.snr_db.unwrap() > 30.0);
+        // Scanning went on after the carrier dropped.
+        assert!(band.retunes > 100);
+    }
+
+    #[test]
+    fn test_level_squelch_and_max_hold() {
+        let mut config = ScannerConfig::new(1_000.0);
+        config.settle_samples = 10;
+        config.dwell_samples = 20;
+        config.squelch = Squelch::Level(-20.0);
+        config.max_hold = 1.0;
+        assert!(Scanner::new(config.clone(), Vec::new()).is_err());
+        assert!(Scanner::new(
+            ScannerConfig {
+                dwell_samples: 0,
+                ..config.clone()
+            },
+            vec![1.0]
+        )
+        .is_err());
+        let mut scanner = Scanner::new(config, vec![1.0, 2.0]).unwrap();
+        let loud = vec![Complex::new(0.5, 0.0); 30];
+        let quiet = vec![Complex::new(0.01, 0.0); 30];
+
+        // The first channel is quiet, and the scanner moves on.
+        let step = scanner.process(&quiet);
+        assert_eq!(step.retune, Some(2.0));
+        assert!(step.activity.is_empty());
+
+        // The second is stuck on and is given up after a second.
+        let mut ended = Vec::new();
+        for _ in 0..100 {
+            let step = scanner.process(&loud);
+            ended.extend(step.activity);
+            if step.retune.is_some() {
+                assert_eq!(step.retune, Some(1.0));
+                break;
+            }
+            assert!(scanner.is_holding());
+        }
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].frequency, 2.0);
+        assert_eq!(ended[0].snr_db, None);
+        assert_relative_eq!(ended[0].duration, 1.0, epsilon = 0.03);
+        assert!(!scanner.is_holding());
+    }
+}
@@ -0,0 +1,5 @@
+//! Direction-finding front ends that turn receiver output into readings.
+
+pub mod coherent;
+pub mod pseudo_doppler;
+pub mod switcher;
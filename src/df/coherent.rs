@@ -0,0 +1,397 @@
+//! Coherent capture from several receivers sharing a clock.
+//!
+//! Receivers driven from one reference oscillator and one sample clock
+//! still start streaming at different moments, and each tuner's synthesiser
+//! locks with its own phase. Both offsets hold until a receiver is retuned
+//! or restarted. They are measured by feeding every input the same
+//! reference signal, through a splitter or from a noise source switched in
+//! ahead of the antennas, and cross-correlating each channel against the
+//! first. Once the channels are aligned by those offsets, phase differences
+//! between them come from the signal's arrival alone, which is what
+//! interferometric direction finding and TDOA need.
+
+use crate::dsp::complex::conj_multiply;
+use crate::error::{DspError, Result, SdrError};
+use crate::source::Source;
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// How one channel stands against the first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelOffset {
+    /// Samples by which the channel lags the first; negative if it leads.
+    pub delay: i64,
+    /// The part of a sample more, from interpolating the correlation peak,
+    /// between -0.5 and 0.5.
+    pub fraction: f64,
+    /// Phase of the channel relative to the first at the peak, in radians.
+    pub phase: f64,
+    /// The normalised correlation peak, near one for a clean reference and
+    /// near zero for channels that do not share it.
+    pub coherence: f64,
+}
+
+impl ChannelOffset {
+    /// The lag in seconds, fraction included.
+    pub fn delay_seconds(&self, sample_rate: f64) -> f64 {
+        (self.delay as f64 + self.fraction) / sample_rate
+    }
+}
+
+/// The offsets of every channel, the first being the reference.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    pub offsets: Vec<ChannelOffset>,
+}
+
+impl Calibration {
+    /// Measures the offsets from blocks captured at the same moment with a
+    /// common reference signal on every input, searching lags up to
+    /// `max_lag` samples either way. Fails if there are no channels or a
+    /// block holds `max_lag` samples or fewer.
+    pub fn estimate(channels: &[&[Complex<f64>]], max_lag: usize) -> Result<Self> {
+        let reference = channels
+            .first()
+            .ok_or_else(|| DspError::InvalidArgument("no channels to calibrate".into()))?;
+        if channels
+            .iter()
+            .any(|channel| channel.len().min(reference.len()) <= max_lag)
+        {
+            return Err(DspError::InvalidArgument(format!(
+                "calibration blocks must be longer than the {max_lag} sample lag searched"
+            ))
+            .into());
+        }
+        let offsets = channels
+            .iter()
+            .map(|channel| offset(reference, channel, max_lag))
+            .collect();
+        Ok(Calibration { offsets })
+    }
+
+    /// No correction, for `channels` channels already aligned.
+    pub fn identity(channels: usize) -> Self {
+        let aligned = ChannelOffset {
+            delay: 0,
+            fraction: 0.0,
+            phase: 0.0,
+            coherence: 1.0,
+        };
+        Calibration {
+            offsets: vec![aligned; channels],
+        }
+    }
+
+    /// The lowest coherence of any channel, to check that they all shared
+    /// the reference.
+    pub fn min_coherence(&self) -> f64 {
+        self.offsets.iter().map(|o| o.coherence).fold(1.0, f64::min)
+    }
+}
+
+/// The cross-correlation of `channel` against `reference` at `lag`,
+/// `Σ channel[n + lag] · conj(reference[n])`.
+fn correlate(reference: &[Complex<f64>], channel: &[Complex<f64>], lag: i64) -> Complex<f64> {
+    let start = (-lag).max(0) as usize;
+    let end = reference.len().min((channel.len() as i64 - lag) as usize);
+    (start..end)
+        .map(|n| conj_multiply(channel[(n as i64 + lag) as usize], reference[n]))
+        .sum()
+}
+
+fn offset(reference: &[Complex<f64>], channel: &[Complex<f64>], max_lag: usize) -> ChannelOffset {
+    let max_lag = max_lag as i64;
+    let correlations: Vec<Complex<f64>> = (-max_lag..=max_lag)
+        .map(|lag| correlate(reference, channel, lag))
+        .collect();
+    let (peak, best) = correlations
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))
+        .map(|(index, &value)| (index, value))
+        .unwrap_or_default();
+    let fraction = match (peak.checked_sub(1), correlations.get(peak + 1)) {
+        (Some(before), Some(after)) => {
+            let (a, b, c) = (correlations[before].norm(), best.norm(), after.norm());
+            let curvature = a - 2.0 * b + c;
+            if curvature < 0.0 {
+                (0.5 * (a - c) / curvature).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            }
+        }
+        _ => 0.0,
+    };
+    let energy = |samples: &[Complex<f64>]| samples.iter().map(|x| x.norm_sqr()).sum::<f64>();
+    let scale = (energy(reference) * energy(channel)).sqrt();
+    ChannelOffset {
+        delay: peak as i64 - max_lag,
+        fraction,
+        phase: best.arg(),
+        coherence: if scale > 0.0 {
+            best.norm() / scale
+        } else {
+            0.0
+        },
+    }
+}
+
+/// One block of samples from each channel, in channel order.
+pub type Blocks = Vec<Vec<Complex<f64>>>;
+
+/// Reads equal, aligned blocks from a set of sources on a common clock.
+///
+/// Each source is read on its own, so they may return different amounts
+/// at a time; what has arrived is queued per channel until every channel
+/// can fill the block.
+#[derive(Debug)]
+pub struct CoherentCapture<S> {
+    sources: Vec<S>,
+    queues: Vec<VecDeque<Complex<f64>>>,
+    /// Samples still to drop from each queue.
+    skips: Vec<usize>,
+    /// The conjugated phase of each channel, as a unit rotation.
+    rotations: Vec<Complex<f64>>,
+    calibration: Calibration,
+    buffer: Vec<Complex<f64>>,
+}
+
+impl<S: Source<Sample = Complex<f64>>> CoherentCapture<S> {
+    /// Capture from `sources`, uncorrected until calibrated. Fails if
+    /// `sources` is empty.
+    pub fn new(sources: Vec<S>) -> Result<Self> {
+        if sources.is_empty() {
+            return Err(DspError::InvalidArgument("no sources to capture from".into()).into());
+        }
+        let channels = sources.len();
+        Ok(CoherentCapture {
+            sources,
+            queues: vec![VecDeque::new(); channels],
+            skips: vec![0; channels],
+            rotations: vec![Complex::new(1.0, 0.0); channels],
+            calibration: Calibration::identity(channels),
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn sources_mut(&mut self) -> &mut [S] {
+        &mut self.sources
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    /// Applies `calibration`, the offsets of the raw streams, from the
+    /// next samples queued. Only the change from the calibration already
+    /// applied is dropped from the queues, so it can be replaced while
+    /// streaming. Fails if it is for a different number of channels.
+    pub fn set_calibration(&mut self, calibration: Calibration) -> Result<()> {
+        if calibration.offsets.len() != self.channels() {
+            return Err(DspError::InvalidLength {
+                expected: self.channels(),
+                found: calibration.offsets.len(),
+            }
+            .into());
+        }
+        let changes: Vec<i64> = calibration
+            .offsets
+            .iter()
+            .zip(&self.calibration.offsets)
+            .map(|(new, old)| new.delay - old.delay)
+            .collect();
+        let earliest = changes.iter().copied().min().unwrap_or(0);
+        for (skip, change) in self.skips.iter_mut().zip(changes) {
+            *skip += (change - earliest) as usize;
+        }
+        self.rotations = calibration
+            .offsets
+            .iter()
+            .map(|o| Complex::from_polar(1.0, -o.phase))
+            .collect();
+        self.calibration = calibration;
+        Ok(())
+    }
+
+    /// Reads `length` samples from every channel, aligned and phase
+    /// corrected, or `None` once any source is exhausted.
+    pub fn read(&mut self, length: usize) -> Result<Option<Blocks>, S::Error> {
+        for channel in 0..self.sources.len() {
+            let queue = &mut self.queues[channel];
+            let skip = &mut self.skips[channel];
+            loop {
+                let drop = (*skip).min(queue.len());
+                queue.drain(..drop);
+                *skip -= drop;
+                if *skip == 0 && queue.len() >= length {
+                    break;
+                }
+                let wanted = (length.saturating_sub(queue.len()) + *skip).max(1);
+                self.buffer.resize(wanted, Complex::new(0.0, 0.0));
+                let read = self.sources[channel].read(&mut self.buffer)?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                queue.extend(&self.buffer[..read]);
+            }
+        }
+        Ok(Some(
+            self.queues
+                .iter_mut()
+                .zip(&self.rotations)
+                .map(|(queue, &rotation)| queue.drain(..length).map(|x| x * rotation).collect())
+                .collect(),
+        ))
+    }
+
+    /// Reads a block of `length` samples with the reference signal on
+    /// every input, measures the offsets with [`Calibration::estimate`]
+    /// and applies them on top of the current calibration. Returns the
+    /// offsets measured, relative to the alignment before, or `None` if a
+    /// source ran out.
+    pub fn calibrate(&mut self, length: usize, max_lag: usize) -> Result<Option<Calibration>>
+    where
+        S::Error: Into<SdrError>,
+    {
+        let Some(blocks) = self.read(length).map_err(Into::into)? else {
+            return Ok(None);
+        };
+        let views: Vec<&[Complex<f64>]> = blocks.iter().map(Vec::as_slice).collect();
+        let measured = Calibration::estimate(&views, max_lag)?;
+        trace_event!(
+            info,
+            coherence = measured.min_coherence(),
+            "coherent capture calibrated"
+        );
+        // The blocks were already corrected, so what was measured adds to
+        // the calibration in place.
+        let combined = Calibration {
+            offsets: self
+                .calibration
+                .offsets
+                .iter()
+                .zip(&measured.offsets)
+                .map(|(old, new)| ChannelOffset {
+                    delay: old.delay + new.delay,
+                    fraction: new.fraction,
+                    phase: (old.phase + new.phase + PI).rem_euclid(2.0 * PI) - PI,
+                    coherence: new.coherence,
+                })
+                .collect(),
+        };
+        self.set_calibration(combined)?;
+        Ok(Some(measured))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use approx::assert_relative_eq;
+
+    /// Plays back samples a few at a time, as an unsynchronised receiver
+    /// would hand them over.
+    struct Playback {
+        samples: Vec<Complex<f64>>,
+        position: usize,
+        chunk: usize,
+    }
+
+    impl Source for Playback {
+        type Sample = Complex<f64>;
+        type Error = std::convert::Infallible;
+
+        fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, Self::Error> {
+            let count = buffer
+                .len()
+                .min(self.chunk)
+                .min(self.samples.len() - self.position);
+            buffer[..count].copy_from_slice(&self.samples[self.position..][..count]);
+            self.position += count;
+            Ok(count)
+        }
+    }
+
+    /// A common signal as seen by receivers that started `starts` samples
+    /// into it with tuner phases `phases`.
+    fn receivers(starts: &[usize], phases: &[f64]) -> (Vec<Complex<f64>>, Vec<Playback>) {
+        let signal = complex_noise(20_000, 7);
+        let sources = starts
+            .iter()
+            .zip(phases)
+            .enumerate()
+            .map(|(n, (&start, &phase))| Playback {
+                samples: signal[start..]
+                    .iter()
+                    .map(|&x| x * Complex::from_polar(1.0, phase))
+                    .collect(),
+                position: 0,
+                chunk: 300 + 170 * n,
+            })
+            .collect();
+        (signal, sources)
+    }
+
+    #[test]
+    fn test_estimates_delay_and_phase() {
+        let signal = complex_noise(2000, 3);
+        let late: Vec<Complex<f64>> = std::iter::repeat_n(Complex::new(0.0, 0.0), 5)
+            .chain(signal.iter().map(|&x| x * Complex::from_polar(1.0, 1.0)))
+            .collect();
+        let early: Vec<Complex<f64>> = signal[3..]
+            .iter()
+            .map(|&x| x * Complex::from_polar(1.0, -2.0))
+            .collect();
+        let calibration = Calibration::estimate(&[&signal, &late, &early], 16).unwrap();
+        let delays: Vec<i64> = calibration.offsets.iter().map(|o| o.delay).collect();
+        assert_eq!(delays, [0, 5, -3]);
+        assert_relative_eq!(calibration.offsets[1].phase, 1.0, epsilon = 1e-9);
+        assert_relative_eq!(calibration.offsets[2].phase, -2.0, epsilon = 1e-9);
+        assert!(calibration.offsets[1].fraction.abs() < 0.05);
+        assert!(calibration.min_coherence() > 0.99);
+        let unrelated = complex_noise(2000, 4);
+        let calibration = Calibration::estimate(&[&signal, &unrelated], 16).unwrap();
+        assert!(calibration.min_coherence() < 0.2);
+        assert!(Calibration::estimate(&[], 16).is_err());
+        assert!(Calibration::estimate(&[&signal, &signal[..16]], 16).is_err());
+    }
+
+    #[test]
+    fn test_capture_aligns_after_calibration() {
+        let (signal, sources) = receivers(&[40, 12, 33], &[0.3, -1.2, 2.5]);
+        let mut capture = CoherentCapture::new(sources).unwrap();
+        let measured = capture.calibrate(4096, 64).unwrap().unwrap();
+        let delays: Vec<i64> = measured.offsets.iter().map(|o| o.delay).collect();
+        assert_eq!(delays, [0, 28, 7]);
+
+        let blocks = capture.read(1000).unwrap().unwrap();
+        // Every channel now carries the first's view of the signal, which
+        // reached it 40 samples in and has been read 4096 samples since.
+        let expected = signal[4136] * Complex::from_polar(1.0, 0.3);
+        assert_relative_eq!(blocks[0][0].re, expected.re, epsilon = 1e-12);
+        for block in &blocks[1..] {
+            for (x, y) in block.iter().zip(&blocks[0]) {
+                assert_relative_eq!(x.re, y.re, epsilon = 1e-9);
+                assert_relative_eq!(x.im, y.im, epsilon = 1e-9);
+            }
+        }
+        // A second calibration finds nothing left to correct.
+        let again = capture.calibrate(2048, 16).unwrap().unwrap();
+        assert!(again
+            .offsets
+            .iter()
+            .all(|o| o.delay == 0 && o.phase.abs() < 1e-9));
+        assert_eq!(capture.calibration().offsets[1].delay, 28);
+        assert!(capture.set_calibration(Calibration::identity(2)).is_err());
+        assert!(capture.read(20_000).unwrap().is_none());
+        assert!(CoherentCapture::<Playback>::new(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Pseudo-Doppler direction finding from stereo soundcard input.
+//!
+//! The classic homebrew setup feeds the receiver's audio into one soundcard
+//! channel and the antenna switcher's sync pulse into the other. Every rising
+//! edge of the sync pulse marks the start of one antenna rotation, and the
+//! phase of the Doppler tone within that rotation gives the bearing.
+
+use crate::average;
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// A soundcard input channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Left => 0,
+            Channel::Right => 1,
+        }
+    }
+}
+
+/// Settings for [`PseudoDoppler`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PseudoDopplerConfig {
+    /// Soundcard sample rate in Hz.
+    pub sample_rate: f64,
+    /// Channel carrying the receiver audio; the other carries the sync pulse.
+    pub audio_channel: Channel,
+    /// Sync level that registers a rising edge.
+    pub sync_threshold: f64,
+    /// Degrees added to every reading to align the array with north.
+    pub calibration: f64,
+    /// Number of recent readings averaged into the bearing.
+    pub readings_to_average: usize,
+}
+
+impl PseudoDopplerConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        PseudoDopplerConfig {
+            sample_rate,
+            audio_channel: Channel::Left,
+            sync_threshold: 0.5,
+            calibration: 0.0,
+            readings_to_average: 16,
+        }
+    }
+}
+
+/// Extracts one (angle, magnitude) reading per antenna rotation.
+#[derive(Debug, Clone)]
+pub struct PseudoDoppler {
+    config: PseudoDopplerConfig,
+    sync_high: bool,
+    synchronized: bool,
+    rotation: Vec<f64>,
+    last_rotation: Option<usize>,
+    recent: VecDeque<(f64, f64)>,
+}
+
+impl PseudoDoppler {
+    /// Rotations shorter than this are treated as sync glitches.
+    const MIN_ROTATION_SAMPLES: usize = 4;
+
+    pub fn new(config: PseudoDopplerConfig) -> Self {
+        PseudoDoppler {
+            config,
+            sync_high: false,
+            synchronized: false,
+            rotation: Vec::new(),
+            last_rotation: None,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PseudoDopplerConfig {
+        &self.config
+    }
+
+    /// Processes interleaved stereo frames and returns the readings for every
+    /// rotation completed in them.
+    pub fn process(&mut self, interleaved: &[f32]) -> Vec<(f64, f64)> {
+        let audio_index = self.config.audio_channel.index();
+        let sync_index = 1 - audio_index;
+        let mut readings = Vec::new();
+        for frame in interleaved.chunks_exact(2) {
+            let sync = f64::from(frame[sync_index]);
+            let rising = !self.sync_high && sync >= self.config.sync_threshold;
+            if rising {
+                self.sync_high = true;
+                if self.synchronized {
+                    if let Some(reading) = self.finish_rotation() {
+                        readings.push(reading);
+                    }
+                }
+                self.synchronized = true;
+                self.rotation.clear();
+            } else if self.sync_high && sync < self.config.sync_threshold / 2.0 {
+                // Half the threshold as hysteresis keeps noisy edges from retriggering.
+                self.sync_high = false;
+            }
+            if self.synchronized {
+                self.rotation.push(f64::from(frame[audio_index]));
+            }
+        }
+        readings
+    }
+
+    fn finish_rotation(&mut self) -> Option<(f64, f64)> {
+        let length = self.rotation.len();
+        if length < Self::MIN_ROTATION_SAMPLES {
+            return None;
+        }
+        self.last_rotation = Some(length);
+        // Single DFT bin at one cycle per rotation.
+        let step = -2.0 * PI / length as f64;
+        let bin: Complex<f64> = self
+            .rotation
+            .iter()
+            .enumerate()
+            .map(|(n, &sample)| Complex::from_polar(sample, step * n as f64))
+            .sum();
+        let angle = (bin.arg().to_degrees() + self.config.calibration).rem_euclid(360.0);
+        let magnitude = 2.0 * bin.norm() / length as f64;
+        let reading = (angle, magnitude);
+        self.recent.push_back(reading);
+        while self.recent.len() > self.config.readings_to_average.max(1) {
+            self.recent.pop_front();
+        }
+        Some(reading)
+    }
+
+    /// The rotation rate measured from the last complete rotation, in Hz.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        let length = self.last_rotation?;
+        Some(self.config.sample_rate / length as f64)
+    }
+
+    /// Readings currently held by the averager, oldest first.
+    pub fn readings(&self) -> impl Iterator<Item = &(f64, f64)> {
+        self.recent.iter()
+    }
+
+    /// The average of the most recent readings as (angle, magnitude), the
+    /// angle in [0, 360) like each reading's, or `None` before the first
+    /// complete rotation.
+    pub fn bearing(&self) -> Option<(f64, f64)> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let readings: Vec<(f64, f64)> = self.recent.iter().copied().collect();
+        let (angle, magnitude) = average(&readings);
+        Some((angle.rem_euclid(360.0), magnitude))
+    }
+}
+
+impl Block for PseudoDoppler {
+    type Input = f32;
+    type Output = (f64, f64);
+
+    /// Consumes whole stereo frames, leaving a trailing half frame for later.
+    fn work(&mut self, input: &[f32], output: &mut Vec<(f64, f64)>) -> usize {
+        let frames = input.len() / 2 * 2;
+        output.extend(self.process(&input[..frames]));
+        frames
+    }
+
+    /// Accepts `calibration` and `sync_threshold`, so the array can be
+    /// aligned with a known transmitter without restarting.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        let number = value.as_f64();
+        match (name, number) {
+            ("calibration", Some(calibration)) => self.config.calibration = calibration,
+            ("sync_threshold", Some(threshold)) => self.config.sync_threshold = threshold,
+            ("calibration" | "sync_threshold", None) => {
+                return Err(ParamError::invalid(name, value))
+            }
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    /// Interleaved (audio, sync) frames for a Doppler tone whose phase is
+    /// `phase` degrees at the start of each rotation.
+    fn frames(samples_per_rotation: usize, rotations: usize, phase: f64) -> Vec<f32> {
+        (0..samples_per_rotation * rotations)
+            .flat_map(|n| {
+                let position = (n % samples_per_rotation) as f64 / samples_per_rotation as f64;
+                let audio = 0.4 * (2.0 * PI * position + phase.to_radians()).cos();
+                let sync = if n % samples_per_rotation < 8 {
+                    1.0
+                } else {
+                    0.0
+                };
+                [audio as f32, sync]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bearing_from_tone_phase() {
+        let mut df = PseudoDoppler::new(PseudoDopplerConfig::new(48_000.0));
+        let readings = df.process(&frames(96, 10, 40.0));
+        // The first edge only synchronizes, and the last rotation is still open.
+        assert_eq!(readings.len(), 9);
+        for (angle, magnitude) in readings {
+            assert_relative_eq!(angle, 40.0, epsilon = 1e-3);
+            assert_relative_eq!(magnitude, 0.4, epsilon = 1e-3);
+        }
+        assert_relative_eq!(df.rotation_rate().unwrap(), 500.0);
+        let (angle, _) = df.bearing().unwrap();
+        assert_relative_eq!(angle, 40.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_calibration_and_channel_swap() {
+        let mut config = PseudoDopplerConfig::new(48_000.0);
+        config.audio_channel = Channel::Right;
+        config.calibration = 30.0;
+        let mut df = PseudoDoppler::new(config);
+        let swapped: Vec<f32> = frames(96, 4, 350.0)
+            .chunks_exact(2)
+            .flat_map(|frame| [frame[1], frame[0]])
+            .collect();
+        let readings = df.process(&swapped);
+        assert_eq!(readings.len(), 3);
+        assert_relative_eq!(readings[0].0, 20.0, epsilon = 1e-3);
+
+        // Past 180 degrees the average keeps the readings' convention.
+        let mut df = PseudoDoppler::new(PseudoDopplerConfig::new(48_000.0));
+        df.process(&frames(96, 4, 300.0));
+        assert_relative_eq!(df.bearing().unwrap().0, 300.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_no_bearing_before_sync() {
+        let mut df = PseudoDoppler::new(PseudoDopplerConfig::new(48_000.0));
+        assert!(df.process(&[0.1, 0.0, 0.2, 0.0]).is_empty());
+        assert_eq!(df.bearing(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_readings_round_trip_through_json() {
+        let mut df = PseudoDoppler::new(PseudoDopplerConfig::new(48_000.0));
+        let readings = df.process(&frames(96, 4, 123.0));
+        let json = serde_json::to_string(&readings).unwrap();
+        let replayed: Vec<(f64, f64)> = serde_json::from_str(&json).unwrap();
+        assert_eq!(replayed, readings);
+        let config = serde_json::to_string(df.config()).unwrap();
+        assert!(config.contains("\"audio_channel\":\"Left\""));
+        assert_eq!(
+            &serde_json::from_str::<PseudoDopplerConfig>(&config).unwrap(),
+            df.config()
+        );
+    }
+
+    #[test]
+    fn test_block_leaves_half_frame() {
+        let mut df = PseudoDoppler::new(PseudoDopplerConfig::new(48_000.0));
+        let input = frames(96, 3, 10.0);
+        let mut output = Vec::new();
+        assert_eq!(df.work(&input[..101], &mut output), 100);
+        assert_eq!(df.work(&input[100..], &mut output), input.len() - 100);
+        assert_eq!(output.len(), 2);
+    }
+}
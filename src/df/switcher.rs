@@ -0,0 +1,444 @@
+//! Serial antenna switch controllers for pseudo-Doppler arrays.
+//!
+//! An Arduino or similar board switches the antennas in turn and, instead of
+//! a sync tone on a second audio channel, reports over serial when each
+//! rotation starts, stamped with its own microsecond clock. [`SwitchClock`]
+//! fits those stamps to the sample stream, so the antenna in use at any
+//! sample is known and a sync channel for [`PseudoDoppler`] can be made up
+//! from it.
+//!
+//! The protocol is line-based ASCII at 115200 baud. The host sends:
+//!
+//! | Line     | Meaning                                                   |
+//! |----------|-----------------------------------------------------------|
+//! | `N<n>`   | rotate through `n` antennas, 2 to 16                      |
+//! | `R<hz>`  | rotate `hz` times a second                                |
+//! | `D<k>`   | report every `k`th rotation, to spare the serial line     |
+//! | `S`      | start rotating                                            |
+//! | `X`      | stop, leaving antenna 0 switched in                       |
+//! | `A<n>`   | stop and hold antenna `n`, for calibration                |
+//! | `?`      | report the settings                                       |
+//!
+//! and the controller answers each with `OK` or `ERR <reason>`, `?` with
+//! `C <antennas> <hz> <running>` before its `OK`, and reports
+//! `T <rotation> <micros>` when antenna 0 is switched in, giving the
+//! rotation count and its `micros()`, both wrapping at 2³².
+//!
+//! The port itself is `AntennaSwitcher`, which needs the `serial` feature.
+//!
+//! [`PseudoDoppler`]: crate::df::pseudo_doppler::PseudoDoppler
+
+use std::collections::VecDeque;
+use std::fmt;
+
+#[cfg(feature = "serial")]
+mod device;
+#[cfg(feature = "serial")]
+pub use device::AntennaSwitcher;
+
+/// The controller's baud rate.
+pub const BAUD_RATE: u32 = 115_200;
+/// Most antennas a controller switches.
+pub const MAX_ANTENNAS: u8 = 16;
+
+/// A command to the controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    SetAntennas(u8),
+    /// Rotations a second.
+    SetRate(f64),
+    /// Reports every this many rotations.
+    SetReportInterval(u32),
+    Start,
+    Stop,
+    /// Holds one antenna.
+    Hold(u8),
+    Query,
+}
+
+impl Command {
+    /// Checks the command against the controller's limits.
+    pub fn validate(self) -> Result<Self, SwitcherError> {
+        match self {
+            Command::SetAntennas(n) if !(2..=MAX_ANTENNAS).contains(&n) => {
+                Err(SwitcherError::InvalidSetting(format!("{n} antennas")))
+            }
+            Command::Hold(n) if n >= MAX_ANTENNAS => {
+                Err(SwitcherError::InvalidSetting(format!("antenna {n}")))
+            }
+            Command::SetRate(hz) if !(hz > 0.0 && hz.is_finite()) => {
+                Err(SwitcherError::InvalidSetting(format!("rate {hz} Hz")))
+            }
+            Command::SetReportInterval(0) => {
+                Err(SwitcherError::InvalidSetting("report interval 0".into()))
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+/// The command line, without its newline.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::SetAntennas(n) => write!(f, "N{n}"),
+            Command::SetRate(hz) => write!(f, "R{hz}"),
+            Command::SetReportInterval(k) => write!(f, "D{k}"),
+            Command::Start => write!(f, "S"),
+            Command::Stop => write!(f, "X"),
+            Command::Hold(n) => write!(f, "A{n}"),
+            Command::Query => write!(f, "?"),
+        }
+    }
+}
+
+/// The start of a rotation, as the controller reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotationReport {
+    pub rotation: u32,
+    pub micros: u32,
+}
+
+/// A line from the controller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Ok,
+    Error(String),
+    Status {
+        antennas: u8,
+        rate: f64,
+        running: bool,
+    },
+    Rotation(RotationReport),
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Result<Self, SwitcherError> {
+        let line = line.trim();
+        let invalid = || SwitcherError::InvalidMessage(line.to_string());
+        let mut fields = line.split_whitespace();
+        let message = match fields.next() {
+            Some("OK") => Message::Ok,
+            Some("ERR") => {
+                return Ok(Message::Error(
+                    line.strip_prefix("ERR").unwrap_or("").trim().to_string(),
+                ))
+            }
+            Some("C") => Message::Status {
+                antennas: fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?,
+                rate: fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?,
+                running: match fields.next() {
+                    Some("1") => true,
+                    Some("0") => false,
+                    _ => return Err(invalid()),
+                },
+            },
+            Some("T") => Message::Rotation(RotationReport {
+                rotation: fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?,
+                micros: fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?,
+            }),
+            _ => return Err(invalid()),
+        };
+        match fields.next() {
+            None => Ok(message),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+/// Errors raised by antenna switch controllers.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SwitcherError {
+    /// Opening, reading or writing the port failed.
+    #[error("serial port: {0}")]
+    Port(String),
+    #[error("unrecognised line from the switcher: {0:?}")]
+    InvalidMessage(String),
+    /// The controller answered `ERR`.
+    #[error("the switcher rejected {command}: {reason}")]
+    Rejected { command: String, reason: String },
+    #[error("invalid switcher setting: {0}")]
+    InvalidSetting(String),
+    /// No answer came before the port's timeout.
+    #[error("no answer from the switcher")]
+    Timeout,
+}
+
+/// Undoes the wrapping of a 32-bit counter, given its last unwrapped value.
+fn unwrap_counter(last: Option<i64>, value: u32) -> i64 {
+    match last {
+        None => i64::from(value),
+        Some(last) => last + i64::from(value.wrapping_sub(last as u32) as i32),
+    }
+}
+
+/// Places the controller's rotations on the sample stream.
+///
+/// Each report is observed with the number of samples read when it arrived.
+/// Serial delivery adds a latency, set with
+/// [`set_latency`](SwitchClock::set_latency), and a millisecond or so of
+/// jitter, which averaging over recent reports takes out; the controller's
+/// own stamps fix the rotation period. Readings still come out
+/// turned by whatever latency is left, which the bearing calibration takes
+/// up.
+#[derive(Debug, Clone)]
+pub struct SwitchClock {
+    sample_rate: f64,
+    latency: f64,
+    window: usize,
+    /// Unwrapped (rotation, micros, sample) of recent reports.
+    reports: VecDeque<(i64, i64, f64)>,
+    /// Micros and sample of the first report since the last reset.
+    origin: Option<(i64, f64)>,
+}
+
+impl SwitchClock {
+    /// Reports the fit is made over by default.
+    pub const DEFAULT_WINDOW: usize = 64;
+    /// Seconds of reports needed before the clocks' rates are compared.
+    pub const MIN_BASELINE: f64 = 10.0;
+
+    pub fn new(sample_rate: f64) -> Self {
+        SwitchClock {
+            sample_rate,
+            latency: 0.0,
+            window: Self::DEFAULT_WINDOW,
+            reports: VecDeque::new(),
+            origin: None,
+        }
+    }
+
+    /// Seconds from a rotation starting to its report being read.
+    pub fn set_latency(&mut self, seconds: f64) {
+        self.latency = seconds;
+    }
+
+    /// Fits over at most `reports` recent reports, at least two.
+    pub fn set_window(&mut self, reports: usize) {
+        self.window = reports.max(2);
+        while self.reports.len() > self.window {
+            self.reports.pop_front();
+        }
+    }
+
+    /// Forgets every report, as after the controller restarts.
+    pub fn reset(&mut self) {
+        self.reports.clear();
+        self.origin = None;
+    }
+
+    /// Takes a report that arrived once `sample` samples had been read.
+    pub fn observe(&mut self, report: RotationReport, sample: u64) {
+        let last = self.reports.back().copied();
+        let rotation = unwrap_counter(last.map(|r| r.0), report.rotation);
+        let micros = unwrap_counter(last.map(|r| r.1), report.micros);
+        if last.is_some_and(|last| rotation <= last.0 || micros <= last.1) {
+            trace_event!(warn, ?report, "switcher restarted, clock reset");
+            self.reset();
+            return self.observe(report, sample);
+        }
+        let sample = sample as f64 - self.latency * self.sample_rate;
+        self.origin.get_or_insert((micros, sample));
+        self.reports.push_back((rotation, micros, sample));
+        if self.reports.len() > self.window {
+            self.reports.pop_front();
+        }
+    }
+
+    /// The rotation period in the controller's microseconds.
+    pub fn period_micros(&self) -> Option<f64> {
+        let (first, last) = (self.reports.front()?, self.reports.back()?);
+        let rotations = last.0 - first.0;
+        (rotations > 0).then(|| (last.1 - first.1) as f64 / rotations as f64)
+    }
+
+    /// The rotation rate in Hz of the sample clock.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        let (slope, _) = self.fit()?;
+        Some(self.sample_rate / (slope * self.period_micros()?))
+    }
+
+    /// Samples per controller microsecond, and the sample at which the
+    /// last reported rotation started. The two clocks' rates are taken to
+    /// match until reports span [`MIN_BASELINE`](Self::MIN_BASELINE), and
+    /// from then on are measured across the whole span, which the jitter
+    /// hardly moves; the start is averaged over the recent reports.
+    fn fit(&self) -> Option<(f64, f64)> {
+        if self.reports.len() < 2 {
+            return None;
+        }
+        let last = *self.reports.back()?;
+        let (origin_micros, origin_sample) = self.origin?;
+        let span = (last.1 - origin_micros) as f64;
+        let slope = if span >= Self::MIN_BASELINE * 1e6 {
+            (last.2 - origin_sample) / span
+        } else {
+            self.sample_rate / 1e6
+        };
+        let anchor = self
+            .reports
+            .iter()
+            .map(|&(_, micros, sample)| sample - slope * (micros - last.1) as f64)
+            .sum::<f64>()
+            / self.reports.len() as f64;
+        Some((slope, anchor))
+    }
+
+    /// How far through its rotation the switch is at `sample`, from 0 up
+    /// to 1, once two reports have been seen.
+    pub fn phase_at(&self, sample: u64) -> Option<f64> {
+        let (slope, anchor) = self.fit()?;
+        let period = self.period_micros()? * slope;
+        Some(((sample as f64 - anchor) / period).rem_euclid(1.0))
+    }
+
+    /// The antenna switched in at `sample`, of `antennas` in turn.
+    pub fn antenna_at(&self, sample: u64, antennas: u8) -> Option<u8> {
+        let phase = self.phase_at(sample)?;
+        Some(((phase * f64::from(antennas)) as u8).min(antennas - 1))
+    }
+
+    /// Interleaves `audio`, which starts at stream sample `first_sample`,
+    /// with a made-up sync channel rising as each rotation starts, ready
+    /// for a [`PseudoDoppler`] taking its audio on the left.
+    ///
+    /// [`PseudoDoppler`]: crate::df::pseudo_doppler::PseudoDoppler
+    pub fn interleave_sync(&self, audio: &[f32], first_sample: u64) -> Option<Vec<f32>> {
+        let (slope, anchor) = self.fit()?;
+        let period = self.period_micros()? * slope;
+        Some(
+            audio
+                .iter()
+                .enumerate()
+                .flat_map(|(n, &level)| {
+                    let sample = (first_sample + n as u64) as f64;
+                    let phase = ((sample - anchor) / period).rem_euclid(1.0);
+                    [level, if phase < 0.5 { 1.0 } else { 0.0 }]
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::df::pseudo_doppler::{PseudoDoppler, PseudoDopplerConfig};
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_commands_and_messages() {
+        assert_eq!(Command::SetAntennas(4).to_string(), "N4");
+        assert_eq!(Command::SetRate(500.0).to_string(), "R500");
+        assert_eq!(Command::Hold(2).to_string(), "A2");
+        assert!(Command::SetAntennas(1).validate().is_err());
+        assert!(Command::SetRate(-1.0).validate().is_err());
+        assert!(Command::SetReportInterval(0).validate().is_err());
+
+        assert_eq!(Message::parse("OK\r\n"), Ok(Message::Ok));
+        assert_eq!(
+            Message::parse("ERR bad rate"),
+            Ok(Message::Error("bad rate".to_string()))
+        );
+        assert_eq!(
+            Message::parse("C 8 312.5 1"),
+            Ok(Message::Status {
+                antennas: 8,
+                rate: 312.5,
+                running: true
+            })
+        );
+        assert_eq!(
+            Message::parse("T 17 4294967000"),
+            Ok(Message::Rotation(RotationReport {
+                rotation: 17,
+                micros: 4_294_967_000
+            }))
+        );
+        assert!(Message::parse("T 17").is_err());
+        assert!(Message::parse("T 1 2 3").is_err());
+        assert!(Message::parse("hello").is_err());
+    }
+
+    /// Reports of a controller rotating at `rate` Hz with its clock `ppm`
+    /// fast, starting at `micros`, each observed `latency` samples late
+    /// plus some jitter, on a stream at `sample_rate`.
+    fn reports(
+        rate: f64,
+        ppm: f64,
+        micros: u32,
+        sample_rate: f64,
+        count: u32,
+    ) -> Vec<(RotationReport, u64)> {
+        let jitter = crate::bench::real_noise(count as usize, 9);
+        (0..count)
+            .map(|n| {
+                let seconds = f64::from(n) / rate;
+                let stamp = micros.wrapping_add((seconds * 1e6 * (1.0 + ppm * 1e-6)) as u32);
+                let arrival = (seconds + 0.002 + jitter[n as usize] * 0.0002) * sample_rate;
+                let report = RotationReport {
+                    rotation: (u32::MAX - 3).wrapping_add(n),
+                    micros: stamp,
+                };
+                (report, arrival as u64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_clock_tracks_rotations_across_wraps() {
+        let sample_rate = 48_000.0;
+        let mut clock = SwitchClock::new(sample_rate);
+        clock.set_latency(0.002);
+        for (report, sample) in reports(500.0, 40.0, u32::MAX - 3_000_000, sample_rate, 20_000) {
+            clock.observe(report, sample);
+        }
+        assert_relative_eq!(clock.period_micros().unwrap(), 2000.08, epsilon = 0.01);
+        assert_relative_eq!(clock.rotation_rate().unwrap(), 500.0, epsilon = 0.01);
+        // Rotation 20000 starts at forty seconds.
+        let start = 40 * 48_000;
+        let phase = clock.phase_at(start).unwrap();
+        assert!(phase.min(1.0 - phase) < 0.05, "phase {phase}");
+        assert_eq!(clock.antenna_at(start + 50, 4), Some(2));
+    }
+
+    #[test]
+    fn test_sync_drives_pseudo_doppler() {
+        let sample_rate = 48_000.0;
+        let mut clock = SwitchClock::new(sample_rate);
+        clock.set_latency(0.002);
+        for (report, sample) in reports(500.0, 0.0, 0, sample_rate, 100) {
+            clock.observe(report, sample);
+        }
+        // A Doppler tone at the rotation rate, 60 degrees behind the sync.
+        let first = 48_000;
+        let audio: Vec<f32> = (0..9600)
+            .map(|n| {
+                let t = (first + n) as f64 / sample_rate;
+                (2.0 * PI * 500.0 * t - 60f64.to_radians()).cos() as f32
+            })
+            .collect();
+        let frames = clock.interleave_sync(&audio, first as u64).unwrap();
+        let mut doppler = PseudoDoppler::new(PseudoDopplerConfig::new(sample_rate));
+        doppler.process(&frames);
+        let (angle, _) = doppler.bearing().unwrap();
+        assert!(
+            (angle.rem_euclid(360.0) - 300.0).abs() < 10.0,
+            "angle {angle}"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use super::{Command, Message, RotationReport, SwitcherError, BAUD_RATE};
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+/// How long to wait for the controller to answer a command.
+const ANSWER_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl From<serialport::Error> for SwitcherError {
+    fn from(error: serialport::Error) -> Self {
+        SwitcherError::Port(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for SwitcherError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::TimedOut => SwitcherError::Timeout,
+            _ => SwitcherError::Port(error.to_string()),
+        }
+    }
+}
+
+/// An antenna switch controller on a serial port.
+pub struct AntennaSwitcher {
+    port: Box<dyn SerialPort>,
+    /// Bytes of a line not yet ended.
+    partial: Vec<u8>,
+    /// Rotation reports read while waiting for an answer.
+    reports: VecDeque<RotationReport>,
+}
+
+impl AntennaSwitcher {
+    /// Opens the controller at `path`, such as `/dev/ttyACM0` or `COM3`.
+    /// Opening resets most Arduinos, so give it a couple of seconds before
+    /// the first command.
+    pub fn open(path: &str) -> Result<Self, SwitcherError> {
+        let port = serialport::new(path, BAUD_RATE)
+            .timeout(ANSWER_TIMEOUT)
+            .open()?;
+        trace_event!(info, path, "antenna switcher opened");
+        Ok(AntennaSwitcher {
+            port,
+            partial: Vec::new(),
+            reports: VecDeque::new(),
+        })
+    }
+
+    /// Sends `command` and waits for its answer, returning the status line
+    /// for [`Command::Query`] and nothing otherwise.
+    pub fn command(&mut self, command: Command) -> Result<Option<Message>, SwitcherError> {
+        let command = command.validate()?;
+        self.port.write_all(format!("{command}\n").as_bytes())?;
+        let mut status = None;
+        loop {
+            match self.read_line(true)? {
+                Some(Message::Ok) => return Ok(status),
+                Some(Message::Error(reason)) => {
+                    return Err(SwitcherError::Rejected {
+                        command: command.to_string(),
+                        reason,
+                    })
+                }
+                Some(Message::Rotation(report)) => self.reports.push_back(report),
+                Some(message @ Message::Status { .. }) => status = Some(message),
+                None => return Err(SwitcherError::Timeout),
+            }
+        }
+    }
+
+    /// Sets the antennas and rate and starts rotating, with every rotation
+    /// reported.
+    pub fn start(&mut self, antennas: u8, rate: f64) -> Result<(), SwitcherError> {
+        self.command(Command::SetAntennas(antennas))?;
+        self.command(Command::SetRate(rate))?;
+        self.command(Command::SetReportInterval(1))?;
+        self.command(Command::Start).map(drop)
+    }
+
+    /// The rotation reports that have arrived, without waiting for more.
+    /// Give each to [`SwitchClock::observe`](super::SwitchClock::observe)
+    /// with the count of samples read by now.
+    pub fn poll(&mut self) -> Result<Vec<RotationReport>, SwitcherError> {
+        while self.port.bytes_to_read()? > 0 {
+            match self.read_line(false)? {
+                Some(Message::Rotation(report)) => self.reports.push_back(report),
+                // Late answers to commands that timed out.
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(self.reports.drain(..).collect())
+    }
+
+    /// Reads until a whole line has arrived and parses it. Without `wait`,
+    /// gives up with `None` once the bytes waiting run out.
+    fn read_line(&mut self, wait: bool) -> Result<Option<Message>, SwitcherError> {
+        let mut byte = [0u8];
+        loop {
+            if !wait && self.port.bytes_to_read()? == 0 {
+                return Ok(None);
+            }
+            match self.port.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::TimedOut => return Ok(None),
+                Err(error) => return Err(error.into()),
+            }
+            if byte[0] != b'\n' {
+                self.partial.push(byte[0]);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.partial).into_owned();
+            self.partial.clear();
+            if !line.trim().is_empty() {
+                return Message::parse(&line).map(Some);
+            }
+        }
+    }
+}
+
+impl Drop for AntennaSwitcher {
+    fn drop(&mut self) {
+        let _ = self
+            .port
+            .write_all(format!("{}\n", Command::Stop).as_bytes());
+    }
+}
@@ -0,0 +1,21 @@
+//! Signal-processing blocks.
+
+pub mod birdie;
+pub mod clock;
+pub mod complex;
+pub mod db;
+pub mod dc;
+pub mod decimate;
+pub mod delay;
+pub mod denoise;
+pub mod envelope;
+pub mod farrow;
+pub mod fir;
+pub mod fixed;
+pub mod fm;
+pub mod iter;
+pub mod lms;
+pub mod unwrap;
+pub mod upsample;
+
+pub use iter::DspIteratorExt;
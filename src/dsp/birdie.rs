@@ -0,0 +1,325 @@
+//! Automatic removal of stable carriers.
+//!
+//! Tuners and nearby electronics put unmodulated spurs, or birdies, into the
+//! band: a PLL harmonic, a USB clock, the tuner's own reference. A spur that
+//! lands near the signal of interest can be stronger than it and capture a
+//! tone detector outright. [`BirdieCanceller`] watches the input spectrum
+//! for carriers that stay narrow and in place for several frames, and
+//! cancels each with an adaptive notch: a local oscillator at the carrier's
+//! frequency whose complex gain is adapted by LMS to match the carrier, and
+//! whose frequency follows the carrier's slow drift.
+//!
+//! Anything narrow and steady is treated as a birdie, including a genuine
+//! unmodulated carrier, so persistence should be set longer than the
+//! wanted signal ever stays put.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::{bin_frequency, windowed_spectrum, Window};
+use num_complex::Complex;
+use std::f64::consts::{LN_2, TAU};
+
+/// Settings for [`BirdieCanceller`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BirdieConfig {
+    /// Complex sample rate in Hz.
+    pub sample_rate: f64,
+    /// Points per analysis frame. Carriers closer together than a few bins
+    /// are seen as one.
+    pub fft_size: usize,
+    /// How far above the noise floor a carrier must rise, in dB. Bins four
+    /// away from it must also be this far below it, which rules out
+    /// modulated signals.
+    pub margin_db: f64,
+    /// Consecutive frames a carrier must stay within a bin before it is
+    /// notched, and must be absent before its notch is removed.
+    pub persistence: usize,
+    /// The most carriers cancelled at once.
+    pub max_notches: usize,
+    /// LMS step size. Larger values lock on faster and widen each notch.
+    pub adaptation: f64,
+}
+
+impl BirdieConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        BirdieConfig {
+            sample_rate,
+            fft_size: 1024,
+            margin_db: 15.0,
+            persistence: 8,
+            max_notches: 4,
+            adaptation: 0.01,
+        }
+    }
+}
+
+/// A carrier being cancelled.
+#[derive(Debug, Clone)]
+struct Notch {
+    /// Cycles per sample.
+    frequency: f64,
+    phase: f64,
+    /// The oscillator's value for the current sample.
+    reference: Complex<f64>,
+    /// The carrier's estimated complex amplitude, and its value at the
+    /// start of the frame, whose rotation measures the frequency error.
+    weight: Complex<f64>,
+    frame_weight: Complex<f64>,
+    /// Consecutive frames without the carrier in the input.
+    missed: usize,
+}
+
+/// A carrier seen in recent frames but not yet notched.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    frequency: f64,
+    frames: usize,
+}
+
+/// Finds and cancels stable narrowband carriers in a complex stream.
+#[derive(Debug, Clone)]
+pub struct BirdieCanceller {
+    config: BirdieConfig,
+    window: Vec<f64>,
+    frame: Vec<Complex<f64>>,
+    notches: Vec<Notch>,
+    candidates: Vec<Candidate>,
+}
+
+impl BirdieCanceller {
+    pub fn new(config: BirdieConfig) -> Self {
+        let size = config.fft_size.max(16);
+        BirdieCanceller {
+            window: Window::Hann.coefficients(size),
+            frame: Vec::with_capacity(size),
+            config: BirdieConfig {
+                fft_size: size,
+                ..config
+            },
+            notches: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &BirdieConfig {
+        &self.config
+    }
+
+    /// The frequencies in Hz being cancelled, relative to the tuned
+    /// frequency.
+    pub fn notches(&self) -> Vec<f64> {
+        let rate = self.config.sample_rate;
+        self.notches
+            .iter()
+            .map(|notch| notch.frequency * rate)
+            .collect()
+    }
+
+    /// Removes every notch and forgets the carriers seen so far.
+    pub fn clear(&mut self) {
+        self.notches.clear();
+        self.candidates.clear();
+        self.frame.clear();
+    }
+
+    /// Returns `sample` with the cancelled carriers subtracted.
+    pub fn cancel(&mut self, sample: Complex<f64>) -> Complex<f64> {
+        let mut error = sample;
+        for notch in &mut self.notches {
+            notch.reference = Complex::from_polar(1.0, notch.phase);
+            error -= notch.weight * notch.reference;
+        }
+        for notch in &mut self.notches {
+            notch.weight += self.config.adaptation * error * notch.reference.conj();
+            notch.phase = (notch.phase + TAU * notch.frequency) % TAU;
+        }
+        self.frame.push(sample);
+        if self.frame.len() == self.config.fft_size {
+            self.analyse();
+            self.frame.clear();
+        }
+        error
+    }
+
+    /// Looks for carriers in the frame just collected and updates the
+    /// notches to match.
+    fn analyse(&mut self) {
+        let size = self.config.fft_size;
+        let spectrum = windowed_spectrum(&self.frame, &self.window);
+        let power: Vec<f64> = spectrum.iter().map(|bin| bin.norm_sqr()).collect();
+        let mut sorted = power.clone();
+        sorted.sort_by(f64::total_cmp);
+        // The median of exponentially distributed noise power is ln 2 of its mean.
+        let floor = sorted[size / 2] / LN_2;
+        let ratio = 10f64.powf(self.config.margin_db / 10.0);
+        let at = |bin: usize, offset: isize| {
+            power[(bin as isize + offset).rem_euclid(size as isize) as usize]
+        };
+        let carriers: Vec<f64> = (0..size)
+            .filter(|&bin| {
+                let peak = power[bin];
+                floor > 0.0
+                    && peak > floor * ratio
+                    && peak >= at(bin, -1)
+                    && peak > at(bin, 1)
+                    && at(bin, -4) * ratio < peak
+                    && at(bin, 4) * ratio < peak
+            })
+            .map(|bin| {
+                let magnitude = |offset| at(bin, offset).max(f64::MIN_POSITIVE).ln();
+                let (left, center, right) = (magnitude(-1), magnitude(0), magnitude(1));
+                let curvature = left - 2.0 * center + right;
+                let offset = if curvature < 0.0 {
+                    (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+                } else {
+                    0.0
+                };
+                bin_frequency((bin as f64 + offset).rem_euclid(size as f64), size, 1.0)
+            })
+            .collect();
+
+        let bin_width = 1.0 / size as f64;
+        let near = |a: f64, b: f64| ((a - b + 0.5).rem_euclid(1.0) - 0.5).abs() <= 1.5 * bin_width;
+        let persistence = self.config.persistence.max(1);
+        for notch in &mut self.notches {
+            // The weight's rotation over the frame measures the frequency
+            // error. Correcting only half of it keeps noise from jittering
+            // the frequency.
+            let rotation = (notch.weight * notch.frame_weight.conj()).arg();
+            if notch.frame_weight.norm_sqr() > 0.0 {
+                notch.frequency += 0.5 * rotation / (TAU * size as f64);
+            }
+            notch.frame_weight = notch.weight;
+            if carriers
+                .iter()
+                .any(|&carrier| near(carrier, notch.frequency))
+            {
+                notch.missed = 0;
+            } else {
+                notch.missed += 1;
+            }
+        }
+        self.notches.retain(|notch| {
+            let keep = notch.missed < persistence;
+            if !keep {
+                trace_event!(debug, frequency = notch.frequency, "birdie notch removed");
+            }
+            keep
+        });
+
+        let mut candidates = Vec::new();
+        for carrier in carriers {
+            if self
+                .notches
+                .iter()
+                .any(|notch| near(carrier, notch.frequency))
+            {
+                continue;
+            }
+            let frames = self
+                .candidates
+                .iter()
+                .find(|candidate| near(carrier, candidate.frequency))
+                .map_or(1, |candidate| candidate.frames + 1);
+            if frames >= persistence && self.notches.len() < self.config.max_notches {
+                trace_event!(debug, frequency = carrier, "birdie notched");
+                self.notches.push(Notch {
+                    frequency: carrier,
+                    phase: 0.0,
+                    reference: Complex::new(1.0, 0.0),
+                    weight: Complex::new(0.0, 0.0),
+                    frame_weight: Complex::new(0.0, 0.0),
+                    missed: 0,
+                });
+            } else {
+                candidates.push(Candidate {
+                    frequency: carrier,
+                    frames,
+                });
+            }
+        }
+        self.candidates = candidates;
+    }
+}
+
+impl Block for BirdieCanceller {
+    type Input = Complex<f64>;
+    type Output = Complex<f64>;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Complex<f64>>) -> usize {
+        output.extend(input.iter().map(|&sample| self.cancel(sample)));
+        input.len()
+    }
+
+    /// Accepts `margin_db` and `adaptation`, between 0 and 1.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("margin_db", Some(margin)) => self.config.margin_db = margin,
+            ("adaptation", Some(step)) if step > 0.0 && step < 1.0 => self.config.adaptation = step,
+            ("margin_db" | "adaptation", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use crate::dsp::complex::mean_power;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_cancels_drifting_birdie_and_keeps_noise() {
+        let rate = 48_000.0;
+        let noise: Vec<Complex<f64>> = complex_noise(48_000, 3)
+            .into_iter()
+            .map(|sample| sample * 0.1)
+            .collect();
+        // A spur at 5123.4 Hz drifting up by 2 Hz over the second.
+        let mut phase = 0.0;
+        let birdie: Vec<Complex<f64>> = (0..48_000)
+            .map(|n| {
+                phase += TAU * (5_123.4 + 2.0 * n as f64 / 48_000.0) / rate;
+                Complex::from_polar(1.0, phase)
+            })
+            .collect();
+        let input: Vec<Complex<f64>> = noise.iter().zip(&birdie).map(|(a, b)| a + b).collect();
+
+        let mut canceller = BirdieCanceller::new(BirdieConfig::new(rate));
+        let mut output = Vec::new();
+        canceller.work(&input, &mut output);
+        let notches = canceller.notches();
+        assert_eq!(notches.len(), 1);
+        assert_relative_eq!(notches[0], 5_125.4, epsilon = 2.0);
+
+        let tail = 24_000..48_000;
+        let leakage = output[tail.clone()]
+            .iter()
+            .zip(&birdie[tail.clone()])
+            .map(|(out, tone)| out * tone.conj())
+            .sum::<Complex<f64>>()
+            .norm()
+            / tail.len() as f64;
+        assert!(leakage < 0.02, "leakage {leakage}");
+        assert_relative_eq!(
+            mean_power(&output[tail.clone()]),
+            mean_power(&noise[tail]),
+            max_relative = 0.1
+        );
+    }
+
+    #[test]
+    fn test_leaves_noise_alone() {
+        let input = complex_noise(20_000, 9);
+        let mut canceller = BirdieCanceller::new(BirdieConfig::new(48_000.0));
+        let mut output = Vec::new();
+        canceller.work(&input, &mut output);
+        assert!(canceller.notches().is_empty());
+        assert_eq!(output, input);
+        assert!(canceller
+            .set_parameter("adaptation", &ParamValue::Float(0.0))
+            .is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Complex-sample arithmetic shared by the demodulators, direction finders
+//! and measurements.
+//!
+//! Everything is generic over the float type, so it works on single- and
+//! double-precision samples alike.
+
+use num_complex::Complex;
+use num_traits::Float;
+
+/// `a` times the conjugate of `b`. Its argument is the phase of `a` relative
+/// to `b` and its magnitude the product of theirs, which makes it the basic
+/// step of FM demodulation and interferometry.
+pub fn conj_multiply<F: Float>(a: Complex<F>, b: Complex<F>) -> Complex<F> {
+    a * b.conj()
+}
+
+/// The phase of `a` relative to `b` in radians, between -π and π.
+pub fn phase_difference<F: Float>(a: Complex<F>, b: Complex<F>) -> F {
+    conj_multiply(a, b).arg()
+}
+
+/// `x` rotated anticlockwise by `degrees`.
+pub fn rotate_by_degrees<F: Float>(x: Complex<F>, degrees: F) -> Complex<F> {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    x * Complex::new(cos, sin)
+}
+
+/// The squared magnitude, or instantaneous power, of each sample.
+pub fn magnitude_squared<F: Float>(samples: &[Complex<F>]) -> Vec<F> {
+    samples.iter().map(Complex::norm_sqr).collect()
+}
+
+/// Writes the squared magnitude of each sample to `output`, which must be
+/// at least as long as `samples`.
+pub fn magnitude_squared_into<F: Float>(samples: &[Complex<F>], output: &mut [F]) {
+    for (power, sample) in output.iter_mut().zip(samples) {
+        *power = sample.norm_sqr();
+    }
+}
+
+/// The average squared magnitude of `samples`, or zero for none.
+pub fn mean_power<F: Float>(samples: &[Complex<F>]) -> F {
+    let total = samples
+        .iter()
+        .fold(F::zero(), |total, sample| total + sample.norm_sqr());
+    match F::from(samples.len()) {
+        Some(count) if !samples.is_empty() => total / count,
+        _ => F::zero(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_phase_helpers() {
+        let a = Complex::from_polar(2.0, 0.5);
+        let b = Complex::from_polar(3.0, -0.25);
+        let product = conj_multiply(a, b);
+        assert_relative_eq!(product.norm(), 6.0, epsilon = 1e-12);
+        assert_relative_eq!(phase_difference(a, b), 0.75, epsilon = 1e-12);
+        // Wraps rather than exceeding π.
+        let c = Complex::from_polar(1.0, -3.0);
+        assert_relative_eq!(phase_difference(a, c), 3.5 - 2.0 * std::f64::consts::PI);
+
+        let rotated = rotate_by_degrees(Complex::new(1.0f32, 0.0), 90.0);
+        assert_relative_eq!(rotated.re, 0.0, epsilon = 1e-6);
+        assert_relative_eq!(rotated.im, 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_powers() {
+        let samples = [Complex::new(3.0, 4.0), Complex::new(0.0, -1.0)];
+        assert_eq!(magnitude_squared(&samples), [25.0, 1.0]);
+        let mut output = [0.0; 2];
+        magnitude_squared_into(&samples, &mut output);
+        assert_eq!(output, [25.0, 1.0]);
+        assert_eq!(mean_power(&samples), 13.0);
+        assert_eq!(mean_power::<f64>(&[]), 0.0);
+    }
+}
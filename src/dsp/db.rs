@@ -0,0 +1,210 @@
+//! Conversion to decibels.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::{Float, One, Zero};
+
+/// A sample whose power can be taken: real values are already powers, and
+/// complex samples give their squared magnitude.
+pub trait Power: Copy {
+    type Real: Float;
+
+    fn power(self) -> Self::Real;
+}
+
+impl Power for f32 {
+    type Real = f32;
+
+    fn power(self) -> f32 {
+        self
+    }
+}
+
+impl Power for f64 {
+    type Real = f64;
+
+    fn power(self) -> f64 {
+        self
+    }
+}
+
+impl<F: Float> Power for Complex<F> {
+    type Real = F;
+
+    fn power(self) -> F {
+        self.norm_sqr()
+    }
+}
+
+/// `10 log10(power)`, clamped below at `floor_db`. Zero, negative and NaN
+/// powers give the floor rather than minus infinity or NaN.
+pub fn power_to_db<F: Float>(power: F, floor_db: F) -> F {
+    let ten = F::from(10.0).unwrap_or_else(F::one);
+    if power.is_nan() || power <= F::zero() {
+        floor_db
+    } else {
+        (ten * power.log10()).max(floor_db)
+    }
+}
+
+/// Converts powers or complex samples to dB, optionally averaging first.
+///
+/// Input is treated as back-to-back vectors of `length` values, such as
+/// successive power spectra, and each position is averaged with the same
+/// position in earlier vectors by an exponential average of the linear
+/// power that moves `alpha` of the way towards each new value. A `length`
+/// of one suits a level meter, and an `alpha` of one turns averaging off.
+#[derive(Debug, Clone)]
+pub struct Decibels<T: Power> {
+    floor_db: T::Real,
+    alpha: T::Real,
+    /// The running linear averages, one per position in the vector.
+    averages: Vec<Option<T::Real>>,
+    /// Position in the vector of the next input.
+    position: usize,
+}
+
+impl<T: Power> Decibels<T> {
+    /// The longest vector [`set_parameter`](Block::set_parameter) accepts,
+    /// ample for any FFT this crate takes. Parameter messages arrive from
+    /// outside the flowgraph, so an unchecked length could allocate without
+    /// bound.
+    pub const MAX_LENGTH: usize = 1 << 20;
+
+    /// Converts without averaging, clamping at `floor_db`.
+    pub fn new(floor_db: T::Real) -> Self {
+        Decibels {
+            floor_db,
+            alpha: T::Real::one(),
+            averages: vec![None],
+            position: 0,
+        }
+    }
+
+    /// Averages vectors of `length` values with weight `alpha`, clamped to
+    /// between 0 and 1.
+    pub fn with_averaging(mut self, length: usize, alpha: T::Real) -> Self {
+        self.set_length(length);
+        self.alpha = alpha.max(T::Real::zero()).min(T::Real::one());
+        self
+    }
+
+    pub fn floor_db(&self) -> T::Real {
+        self.floor_db
+    }
+
+    pub fn length(&self) -> usize {
+        self.averages.len()
+    }
+
+    /// Changes the vector length, at least one, restarting the averages.
+    pub fn set_length(&mut self, length: usize) {
+        self.averages = vec![None; length.max(1)];
+        self.position = 0;
+    }
+
+    /// Restarts the averages, keeping the settings.
+    pub fn reset(&mut self) {
+        self.averages.iter_mut().for_each(|average| *average = None);
+        self.position = 0;
+    }
+
+    /// Accepts the next value and returns its averaged level in dB.
+    pub fn convert(&mut self, sample: T) -> T::Real {
+        let power = sample.power();
+        let average = &mut self.averages[self.position];
+        let smoothed = match *average {
+            // A NaN restarts the average rather than poisoning it.
+            Some(previous) if !power.is_nan() => previous + (power - previous) * self.alpha,
+            _ => power,
+        };
+        *average = Some(smoothed);
+        self.position = (self.position + 1) % self.averages.len();
+        power_to_db(smoothed, self.floor_db)
+    }
+}
+
+impl<T: Power> Block for Decibels<T> {
+    type Input = T;
+    type Output = T::Real;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T::Real>) -> usize {
+        output.extend(input.iter().map(|&sample| self.convert(sample)));
+        input.len()
+    }
+
+    /// Accepts `floor_db`, `alpha`, between 0 and 1, and `length`, in
+    /// values, up to [`Decibels::MAX_LENGTH`].
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        let number = value.as_f64().and_then(num_traits::cast::<f64, T::Real>);
+        match (name, number) {
+            ("floor_db", Some(floor)) if floor.is_finite() => self.floor_db = floor,
+            ("alpha", Some(alpha)) if alpha >= T::Real::zero() && alpha <= T::Real::one() => {
+                self.alpha = alpha
+            }
+            ("length", _) => match value {
+                ParamValue::Int(length) if (1..=Self::MAX_LENGTH as i64).contains(length) => {
+                    self.set_length(*length as usize)
+                }
+                _ => return Err(ParamError::invalid(name, value)),
+            },
+            ("floor_db" | "alpha", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_edge_cases_clamp_to_floor() {
+        assert_relative_eq!(power_to_db(100.0, -120.0), 20.0);
+        assert_eq!(power_to_db(0.0, -120.0), -120.0);
+        assert_eq!(power_to_db(-1.0, -120.0), -120.0);
+        assert_eq!(power_to_db(f64::NAN, -120.0), -120.0);
+        assert_eq!(power_to_db(1e-20f32, -120.0), -120.0);
+
+        let mut meter = Decibels::new(-100.0);
+        let mut output = Vec::new();
+        meter.work(
+            &[Complex::new(3.0, 4.0), Complex::new(0.0, 0.0)],
+            &mut output,
+        );
+        assert_relative_eq!(output[0], 10.0 * 25f64.log10());
+        assert_eq!(output[1], -100.0);
+    }
+
+    #[test]
+    fn test_averages_each_bin_separately() {
+        let mut spectra = Decibels::new(-200.0).with_averaging(2, 0.5);
+        let mut output = Vec::new();
+        spectra.work(&[1.0, 100.0, 3.0, 100.0, f64::NAN, 100.0], &mut output);
+        assert_relative_eq!(output[2], 10.0 * 2f64.log10());
+        assert_relative_eq!(output[3], 20.0);
+        // A NaN restarts the average and reads as the floor.
+        assert_eq!(output[4], -200.0);
+
+        spectra
+            .set_parameter("length", &ParamValue::Int(4))
+            .unwrap();
+        assert_eq!(spectra.length(), 4);
+        for length in [Decibels::<f64>::MAX_LENGTH as i64 + 1, i64::MAX] {
+            assert_eq!(
+                spectra.set_parameter("length", &ParamValue::Int(length)),
+                Err(ParamError::invalid("length", &ParamValue::Int(length)))
+            );
+        }
+        assert_eq!(spectra.length(), 4);
+        assert!(spectra
+            .set_parameter("alpha", &ParamValue::Float(2.0))
+            .is_err());
+        assert!(spectra
+            .set_parameter("floor_db", &ParamValue::Float(f64::NAN))
+            .is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Adaptive DC offset removal.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::Float;
+
+/// Tracks the DC offset of an IQ stream with a slow complex average and
+/// subtracts it.
+///
+/// Direct-conversion tuners leave a spike at the centre frequency that drifts
+/// with temperature and gain. The current estimate is kept readable, so a
+/// large or changing offset can be reported as a hardware problem instead of
+/// silently removed.
+///
+/// Works in `f64` unless built with an `f32` argument.
+#[derive(Debug, Clone)]
+pub struct DcTracker<F = f64> {
+    offset: Complex<F>,
+    alpha: F,
+}
+
+impl<F: Float> DcTracker<F> {
+    /// Creates a tracker that moves `alpha` of the way towards each new sample.
+    pub fn new(alpha: F) -> Self {
+        DcTracker {
+            offset: Complex::new(F::zero(), F::zero()),
+            alpha: alpha.max(F::zero()).min(F::one()),
+        }
+    }
+
+    /// Creates a tracker whose estimate settles with a time constant of
+    /// `seconds`.
+    pub fn with_time_constant(sample_rate: F, seconds: F) -> Self {
+        Self::new(F::one() - (-F::one() / (sample_rate * seconds)).exp())
+    }
+
+    /// The current DC offset estimate.
+    pub fn offset(&self) -> Complex<F> {
+        self.offset
+    }
+
+    pub fn reset(&mut self) {
+        self.offset = Complex::new(F::zero(), F::zero());
+    }
+
+    /// Updates the estimate and returns `sample` with it removed.
+    pub fn correct(&mut self, sample: Complex<F>) -> Complex<F> {
+        self.offset = self.offset + (sample - self.offset) * self.alpha;
+        sample - self.offset
+    }
+}
+
+impl<F: Float> Block for DcTracker<F> {
+    type Input = Complex<F>;
+    type Output = Complex<F>;
+
+    fn work(&mut self, input: &[Complex<F>], output: &mut Vec<Complex<F>>) -> usize {
+        output.extend(input.iter().map(|&sample| self.correct(sample)));
+        input.len()
+    }
+
+    /// Accepts `alpha`, between 0 and 1.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("alpha", Some(alpha)) if (0.0..=1.0).contains(&alpha) => {
+                self.alpha =
+                    num_traits::cast(alpha).ok_or_else(|| ParamError::invalid(name, value))?
+            }
+            ("alpha", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_tracks_and_removes_offset() {
+        let dc = Complex::new(0.2, -0.1);
+        let mut tracker = DcTracker::with_time_constant(48_000.0, 0.1);
+        let output: Vec<Complex<f64>> = (0..48_000)
+            .map(|n| dc + Complex::from_polar(0.5, 2.0 * PI * 1000.0 * n as f64 / 48_000.0))
+            .map(|sample| tracker.correct(sample))
+            .collect();
+        assert_relative_eq!((tracker.offset() - dc).norm(), 0.0, epsilon = 2e-3);
+        let tail = &output[24_000..];
+        let mean = tail.iter().sum::<Complex<f64>>() / tail.len() as f64;
+        assert_relative_eq!(mean.norm(), 0.0, epsilon = 1e-3);
+        assert!(tracker
+            .set_parameter("alpha", &ParamValue::Float(2.0))
+            .is_err());
+    }
+}
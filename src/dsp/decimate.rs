@@ -0,0 +1,154 @@
+//! Integer-factor rate reduction.
+
+use super::fir::{lowpass, Fir, FirSample};
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_traits::Float;
+
+/// Keeps one sample in every `factor`, optionally low-pass filtering first
+/// so that energy above the new Nyquist rate does not alias.
+///
+/// The filter only computes the outputs that are kept. The position within
+/// each group of `factor` samples carries across calls, so a stream split
+/// into arbitrary chunks decimates exactly as if processed whole.
+#[derive(Debug, Clone)]
+pub struct Decimator<T: FirSample<Tap>, Tap = f64> {
+    factor: usize,
+    /// Samples to skip before the next one kept.
+    skip: usize,
+    filter: Option<Fir<T, Tap>>,
+}
+
+impl<T, Tap> Decimator<T, Tap>
+where
+    T: FirSample<Tap>,
+    Tap: Float,
+{
+    /// A decimator without an anti-alias filter, for signals already band
+    /// limited.
+    pub fn new(factor: usize) -> Self {
+        Decimator {
+            factor: factor.max(1),
+            skip: 0,
+            filter: None,
+        }
+    }
+
+    /// A decimator that filters with `taps` first.
+    pub fn with_taps(factor: usize, taps: &[Tap]) -> Self {
+        Decimator {
+            filter: Some(Fir::new(taps)),
+            ..Self::new(factor)
+        }
+    }
+
+    /// A decimator with a `num_taps` Hamming-windowed low-pass filter cut
+    /// off at the new Nyquist rate.
+    pub fn with_lowpass(factor: usize, num_taps: usize) -> Self {
+        let taps: Vec<Tap> = lowpass(num_taps, 0.5 / factor.max(1) as f64)
+            .into_iter()
+            .map(|tap| num_traits::cast(tap).unwrap_or_else(Tap::zero))
+            .collect();
+        Self::with_taps(factor, &taps)
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Changes the factor, starting a new group with the next sample.
+    pub fn set_factor(&mut self, factor: usize) {
+        self.factor = factor.max(1);
+        self.skip = 0;
+    }
+
+    /// Accepts one input sample and returns the output sample, if this one
+    /// is kept.
+    pub fn decimate(&mut self, sample: T) -> Option<T> {
+        let keep = self.skip == 0;
+        self.skip = if keep { self.factor - 1 } else { self.skip - 1 };
+        match &mut self.filter {
+            Some(filter) if keep => Some(filter.filter(sample)),
+            Some(filter) => {
+                filter.push(sample);
+                None
+            }
+            None => keep.then_some(sample),
+        }
+    }
+}
+
+impl<T, Tap> Block for Decimator<T, Tap>
+where
+    T: FirSample<Tap>,
+    Tap: Float,
+{
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        output.extend(input.iter().filter_map(|&sample| self.decimate(sample)));
+        input.len()
+    }
+
+    /// Accepts `factor`, a positive integer.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("factor", ParamValue::Int(factor)) if *factor >= 1 => {
+                self.set_factor(*factor as usize);
+                Ok(())
+            }
+            ("factor", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use num_complex::Complex;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_chunked_stream_matches_whole() {
+        let input: Vec<f64> = (0..100).map(|n| n as f64).collect();
+        let mut plain = Decimator::new(3);
+        let mut output = Vec::new();
+        plain.work(&input, &mut output);
+        assert_eq!(output[..4], [0.0, 3.0, 6.0, 9.0]);
+
+        let mut filtered = Decimator::with_lowpass(4, 31);
+        let mut whole = Vec::new();
+        filtered.work(&input, &mut whole);
+        let mut filtered = Decimator::with_lowpass(4, 31);
+        let mut chunked = Vec::new();
+        for chunk in input.chunks(7) {
+            filtered.work(chunk, &mut chunked);
+        }
+        assert_eq!(whole.len(), 25);
+        assert_eq!(chunked, whole);
+        assert!(filtered
+            .set_parameter("factor", &ParamValue::Int(0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_filter_rejects_tones_that_would_alias() {
+        let mut decimator = Decimator::with_lowpass(8, 127);
+        // 0.4 of the sample rate would fold to 0.2 of the new rate.
+        let output: Vec<Complex<f64>> = (0..4000)
+            .filter_map(|n| decimator.decimate(Complex::from_polar(1.0, 2.0 * PI * 0.4 * n as f64)))
+            .collect();
+        let peak = output[50..].iter().map(|x| x.norm()).fold(0.0, f64::max);
+        assert!(peak < 0.01, "aliased {peak}");
+        let mut decimator = Decimator::with_lowpass(8, 127);
+        let output: Vec<Complex<f64>> = (0..4000)
+            .filter_map(|n| {
+                decimator.decimate(Complex::from_polar(1.0, 2.0 * PI * 0.01 * n as f64))
+            })
+            .collect();
+        assert_relative_eq!(output[100].norm(), 1.0, epsilon = 0.01);
+    }
+}
@@ -0,0 +1,119 @@
+//! Whole-sample delays.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_traits::Zero;
+use std::collections::VecDeque;
+
+/// Delays a stream, real or complex, by a whole number of samples.
+///
+/// Used to line up channels whose paths differ in length, such as the
+/// reference and measurement channels of an interferometer. Output starts
+/// with `delay` zeros.
+#[derive(Debug, Clone)]
+pub struct Delay<T> {
+    /// Exactly `delay` samples, oldest first.
+    line: VecDeque<T>,
+}
+
+impl<T: Copy + Zero> Delay<T> {
+    /// The longest delay [`set_parameter`](Block::set_parameter) accepts,
+    /// about seven seconds at 2.4 MS/s. Parameter messages arrive from
+    /// outside the flowgraph, so an unchecked value could allocate without
+    /// bound.
+    pub const MAX_DELAY: usize = 1 << 24;
+
+    pub fn new(delay: usize) -> Self {
+        Delay {
+            line: std::iter::repeat_n(T::zero(), delay).collect(),
+        }
+    }
+
+    pub fn delay(&self) -> usize {
+        self.line.len()
+    }
+
+    /// Changes the delay from the next sample on. Lengthening it repeats
+    /// zeros; shortening it drops the oldest held samples.
+    pub fn set_delay(&mut self, delay: usize) {
+        while self.line.len() < delay {
+            self.line.push_front(T::zero());
+        }
+        let excess = self.line.len() - delay;
+        self.line.drain(..excess);
+    }
+
+    /// Refills the line with zeros.
+    pub fn reset(&mut self) {
+        self.line.iter_mut().for_each(|sample| *sample = T::zero());
+    }
+
+    /// Accepts one sample and returns the one from `delay` samples ago.
+    pub fn delay_sample(&mut self, sample: T) -> T {
+        if self.line.is_empty() {
+            return sample;
+        }
+        self.line.push_back(sample);
+        self.line.pop_front().unwrap_or(sample)
+    }
+}
+
+impl<T: Copy + Zero> Block for Delay<T> {
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        output.extend(input.iter().map(|&sample| self.delay_sample(sample)));
+        input.len()
+    }
+
+    /// Accepts `delay`, in samples, up to [`Delay::MAX_DELAY`].
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("delay", ParamValue::Int(delay)) if (0..=Self::MAX_DELAY as i64).contains(delay) => {
+                self.set_delay(*delay as usize);
+                Ok(())
+            }
+            ("delay", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_complex::Complex;
+
+    #[test]
+    fn test_delays_across_chunks() {
+        let mut delay = Delay::new(3);
+        let mut output = Vec::new();
+        delay.work(&[1.0, 2.0], &mut output);
+        delay.work(&[3.0, 4.0, 5.0], &mut output);
+        assert_eq!(output, [0.0, 0.0, 0.0, 1.0, 2.0]);
+
+        let mut zero = Delay::new(0);
+        let sample = Complex::new(1.0, -1.0);
+        assert_eq!(zero.delay_sample(sample), sample);
+    }
+
+    #[test]
+    fn test_adjusting_delay() {
+        let mut delay = Delay::new(2);
+        let mut output = Vec::new();
+        delay.work(&[1, 2, 3, 4], &mut output);
+        delay.set_delay(1);
+        delay.work(&[5, 6], &mut output);
+        delay.set_parameter("delay", &ParamValue::Int(3)).unwrap();
+        delay.work(&[7, 8, 9], &mut output);
+        assert_eq!(output, [0, 0, 1, 2, 4, 5, 0, 0, 6]);
+        assert!(delay.set_parameter("delay", &ParamValue::Int(-1)).is_err());
+        let too_long = ParamValue::Int(Delay::<i32>::MAX_DELAY as i64 + 1);
+        assert_eq!(
+            delay.set_parameter("delay", &too_long),
+            Err(ParamError::invalid("delay", &too_long))
+        );
+        assert_eq!(delay.delay(), 3);
+    }
+}
@@ -0,0 +1,285 @@
+//! Noise reduction for demodulated audio by spectral subtraction.
+//!
+//! The audio is cut into half-overlapping frames, each transformed, and the
+//! estimated noise power subtracted from every bin before the frames are
+//! transformed back and overlapped again. Square-root Hann windows on both
+//! sides make the frames add back up exactly when nothing is subtracted.
+//!
+//! The noise profile follows the quietest level of each bin: it falls quickly
+//! when a bin drops below it and rises only slowly, so speech passes over it
+//! without pulling it up, though a tone held for more than a few seconds is
+//! taken for noise. Where the noise is known to be present alone, such as
+//! between overs on a net, it can be learned outright instead, and is then
+//! held until learned again or reset.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::spectrum::{fft, ifft};
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+/// Settings for [`SpectralSubtractor`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralSubtractionConfig {
+    /// Samples per frame, at least 4 and rounded down to even. Longer frames
+    /// resolve the noise more finely but smear speech in time. Output is
+    /// delayed by half a frame.
+    pub fft_size: usize,
+    /// Multiple of the noise estimate subtracted from each bin. Values above
+    /// one remove more noise at the cost of more distortion.
+    pub over_subtraction: f64,
+    /// The smallest fraction of a bin's power that is kept however much is
+    /// subtracted. A small floor masks the isolated tonal remnants, or
+    /// musical noise, that bare subtraction leaves behind.
+    pub spectral_floor: f64,
+}
+
+impl Default for SpectralSubtractionConfig {
+    fn default() -> Self {
+        SpectralSubtractionConfig {
+            fft_size: 512,
+            over_subtraction: 2.0,
+            spectral_floor: 0.02,
+        }
+    }
+}
+
+/// Removes steady background noise from real audio.
+#[derive(Debug, Clone)]
+pub struct SpectralSubtractor {
+    config: SpectralSubtractionConfig,
+    window: Vec<f64>,
+    /// The latest frame of input, oldest first.
+    input: Vec<f64>,
+    /// Overlap-added output, of which the first half frame is complete.
+    overlap: Vec<f64>,
+    /// Input samples since the last frame was processed.
+    pending: usize,
+    /// Per-bin noise power, empty until the first frame.
+    noise: Vec<f64>,
+    learning: bool,
+    /// Whether `noise` was learned, rather than tracked.
+    learned: bool,
+}
+
+impl SpectralSubtractor {
+    /// Per-frame rates at which the noise tracker follows a bin down and up.
+    const NOISE_FALL: f64 = 0.05;
+    const NOISE_RISE: f64 = 0.01;
+    /// Where those rates leave the tracker on noise alone, as a fraction of
+    /// the mean power: the root of `1 - n = (1 - RISE / FALL) e^-n` for
+    /// exponentially distributed bin powers.
+    const TRACKING_BIAS: f64 = 0.529;
+    /// Per-frame rate at which a learned profile follows the input.
+    const LEARN_RATE: f64 = 0.1;
+
+    pub fn new(config: SpectralSubtractionConfig) -> Self {
+        let size = (config.fft_size.max(4)) & !1;
+        // Periodic, so that squared it overlaps to exactly one at half-frame hops.
+        let window = (0..size)
+            .map(|n| (0.5 - 0.5 * (2.0 * PI * n as f64 / size as f64).cos()).sqrt())
+            .collect();
+        SpectralSubtractor {
+            config: SpectralSubtractionConfig {
+                fft_size: size,
+                ..config
+            },
+            window,
+            input: vec![0.0; size],
+            overlap: vec![0.0; size],
+            pending: 0,
+            noise: Vec::new(),
+            learning: false,
+            learned: false,
+        }
+    }
+
+    pub fn config(&self) -> &SpectralSubtractionConfig {
+        &self.config
+    }
+
+    /// The per-bin noise power estimate, in FFT bin order, once the first
+    /// frame has been seen.
+    pub fn noise_profile(&self) -> &[f64] {
+        &self.noise
+    }
+
+    /// While learning, the noise profile averages every frame as noise, so
+    /// it should be on only while no wanted signal is present. Nothing is
+    /// subtracted from frames learned from. Learning again starts a new
+    /// profile.
+    pub fn set_learning(&mut self, learning: bool) {
+        if learning && !self.learning {
+            self.learned = false;
+        }
+        self.learning = learning;
+    }
+
+    /// Forgets the noise profile and any audio in flight.
+    pub fn reset(&mut self) {
+        self.input.iter_mut().for_each(|sample| *sample = 0.0);
+        self.overlap.iter_mut().for_each(|sample| *sample = 0.0);
+        self.pending = 0;
+        self.noise.clear();
+        self.learned = false;
+    }
+
+    /// Accepts one sample, appending half a frame of output each time one
+    /// completes.
+    pub fn process(&mut self, sample: f64, output: &mut Vec<f64>) {
+        let hop = self.config.fft_size / 2;
+        self.input.copy_within(1.., 0);
+        *self.input.last_mut().unwrap_or(&mut 0.0) = sample;
+        self.pending += 1;
+        if self.pending == hop {
+            self.pending = 0;
+            self.process_frame();
+            output.extend_from_slice(&self.overlap[..hop]);
+            self.overlap.copy_within(hop.., 0);
+            self.overlap[hop..]
+                .iter_mut()
+                .for_each(|sample| *sample = 0.0);
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let mut spectrum: Vec<Complex<f64>> = self
+            .input
+            .iter()
+            .zip(&self.window)
+            .map(|(&sample, &weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        fft(&mut spectrum);
+        if self.noise.is_empty() || (self.learning && !self.learned) {
+            self.noise = spectrum.iter().map(|bin| bin.norm_sqr()).collect();
+        }
+        self.learned |= self.learning;
+        for (bin, noise) in spectrum.iter_mut().zip(&mut self.noise) {
+            let power = bin.norm_sqr();
+            if self.learning {
+                *noise += Self::LEARN_RATE * (power - *noise);
+                continue;
+            }
+            if !self.learned {
+                let tracked = *noise * Self::TRACKING_BIAS;
+                let rate = if power < tracked {
+                    Self::NOISE_FALL
+                } else {
+                    Self::NOISE_RISE
+                };
+                *noise = (tracked + rate * (power - tracked)) / Self::TRACKING_BIAS;
+            }
+            if power > 0.0 {
+                let cleaned = (power - self.config.over_subtraction * *noise)
+                    .max(self.config.spectral_floor * power);
+                *bin *= (cleaned / power).sqrt();
+            }
+        }
+        ifft(&mut spectrum);
+        for ((total, bin), &weight) in self.overlap.iter_mut().zip(&spectrum).zip(&self.window) {
+            *total += bin.re * weight;
+        }
+    }
+}
+
+impl Block for SpectralSubtractor {
+    type Input = f64;
+    type Output = f64;
+
+    fn work(&mut self, input: &[f64], output: &mut Vec<f64>) -> usize {
+        for &sample in input {
+            self.process(sample, output);
+        }
+        input.len()
+    }
+
+    /// Accepts `over_subtraction`, at least zero, `spectral_floor`, between
+    /// 0 and 1, and `learn`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("over_subtraction", Some(factor)) if factor >= 0.0 => {
+                self.config.over_subtraction = factor
+            }
+            ("spectral_floor", Some(floor)) if (0.0..=1.0).contains(&floor) => {
+                self.config.spectral_floor = floor
+            }
+            ("learn", _) => {
+                let learning = value
+                    .as_bool()
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                self.set_learning(learning);
+            }
+            ("over_subtraction" | "spectral_floor", _) => {
+                return Err(ParamError::invalid(name, value))
+            }
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::real_noise;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_reconstructs_without_subtraction() {
+        let mut subtractor = SpectralSubtractor::new(SpectralSubtractionConfig {
+            fft_size: 64,
+            over_subtraction: 0.0,
+            spectral_floor: 0.0,
+        });
+        let input = real_noise(1_000, 2);
+        let mut output = Vec::new();
+        subtractor.work(&input, &mut output);
+        assert_eq!(output.len(), 992);
+        // Delayed by half a frame once the first full frame is in.
+        for (n, &sample) in output.iter().enumerate().skip(64) {
+            assert_relative_eq!(sample, input[n - 32], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_reduces_noise_and_keeps_tone() {
+        let tone = |n: usize| 0.3 * (2.0 * PI * 0.05 * n as f64).sin();
+        let noise: Vec<f64> = real_noise(40_000, 6).iter().map(|x| 0.1 * x).collect();
+        let input: Vec<f64> = noise.iter().enumerate().map(|(n, x)| tone(n) + x).collect();
+
+        let mut subtractor = SpectralSubtractor::new(SpectralSubtractionConfig::default());
+        subtractor
+            .set_parameter("learn", &ParamValue::Bool(true))
+            .unwrap();
+        let mut output = Vec::new();
+        // A whole number of hops, so the output stays aligned with `input`.
+        subtractor.work(&noise[..10_240], &mut output);
+        subtractor.set_learning(false);
+        output.clear();
+        subtractor.work(&input, &mut output);
+
+        let range = 20_000..output.len();
+        let residual = range
+            .clone()
+            .map(|n| (output[n] - tone(n - 256)).powi(2))
+            .sum::<f64>()
+            / range.len() as f64;
+        let noise_power = noise.iter().map(|x| x * x).sum::<f64>() / noise.len() as f64;
+        assert!(
+            residual < noise_power / 4.0,
+            "residual {residual}, noise {noise_power}"
+        );
+        assert!(subtractor
+            .set_parameter("spectral_floor", &ParamValue::Float(1.5))
+            .is_err());
+
+        // Without learning, the tracked profile settles on the noise too.
+        let mut tracking = SpectralSubtractor::new(SpectralSubtractionConfig::default());
+        output.clear();
+        tracking.work(&noise, &mut output);
+        let remaining =
+            output[20_000..].iter().map(|x| x * x).sum::<f64>() / (output.len() - 20_000) as f64;
+        assert!(remaining < noise_power / 4.0, "remaining {remaining}");
+    }
+}
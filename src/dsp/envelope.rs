@@ -0,0 +1,202 @@
+//! Envelope detection.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::Float;
+use std::collections::VecDeque;
+
+/// How [`EnvelopeDetector`] smooths the sample magnitudes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Smoothing {
+    /// The magnitude of each sample on its own.
+    None,
+    /// An exponential average of the magnitude, moving `alpha` of the way
+    /// towards each new sample. Suits AM demodulation and squelch.
+    SinglePole { alpha: f64 },
+    /// The root mean square over the last `window` samples. Rises and falls
+    /// in a fixed time, which suits gating bursts.
+    Rms { window: usize },
+}
+
+/// The magnitude envelope of a complex stream.
+///
+/// Works in `f64` unless built with `f32` samples.
+#[derive(Debug, Clone)]
+pub struct EnvelopeDetector<F = f64> {
+    smoothing: Smoothing,
+    alpha: F,
+    /// The single-pole average, once there has been a sample.
+    average: Option<F>,
+    /// Squared magnitudes in the RMS window and their sum.
+    powers: VecDeque<F>,
+    power_sum: F,
+}
+
+impl<F: Float> EnvelopeDetector<F> {
+    pub fn new(smoothing: Smoothing) -> Self {
+        let mut detector = EnvelopeDetector {
+            smoothing: Smoothing::None,
+            alpha: F::one(),
+            average: None,
+            powers: VecDeque::new(),
+            power_sum: F::zero(),
+        };
+        detector.set_smoothing(smoothing);
+        detector
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    /// Switches the smoothing and starts it afresh. The single-pole `alpha`
+    /// is clamped to between 0 and 1, and an RMS window to at least one
+    /// sample.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = match smoothing {
+            Smoothing::SinglePole { alpha } => {
+                let alpha = alpha.clamp(0.0, 1.0);
+                self.alpha = num_traits::cast(alpha).unwrap_or_else(F::one);
+                Smoothing::SinglePole { alpha }
+            }
+            Smoothing::Rms { window } => Smoothing::Rms {
+                window: window.max(1),
+            },
+            Smoothing::None => Smoothing::None,
+        };
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.average = None;
+        self.powers.clear();
+        self.power_sum = F::zero();
+    }
+
+    /// Whether the smoothing has seen enough samples for its output to mean
+    /// what it says: a full window for RMS, any sample otherwise.
+    pub fn settled(&self) -> bool {
+        match self.smoothing {
+            Smoothing::Rms { window } => self.powers.len() == window,
+            Smoothing::SinglePole { .. } => self.average.is_some(),
+            Smoothing::None => true,
+        }
+    }
+
+    /// Accepts one sample and returns the smoothed envelope. The first
+    /// output of the single-pole average is the first sample's magnitude,
+    /// and an RMS window averages what it has until it fills.
+    pub fn envelope(&mut self, sample: Complex<F>) -> F {
+        match self.smoothing {
+            Smoothing::None => sample.norm(),
+            Smoothing::SinglePole { .. } => {
+                let magnitude = sample.norm();
+                let average = self.average.map_or(magnitude, |average| {
+                    average + (magnitude - average) * self.alpha
+                });
+                self.average = Some(average);
+                average
+            }
+            Smoothing::Rms { window } => {
+                let power = sample.norm_sqr();
+                self.powers.push_back(power);
+                self.power_sum = self.power_sum + power;
+                if self.powers.len() > window {
+                    self.power_sum =
+                        self.power_sum - self.powers.pop_front().unwrap_or_else(F::zero);
+                }
+                let count = F::from(self.powers.len()).unwrap_or_else(F::one);
+                (self.power_sum.max(F::zero()) / count).sqrt()
+            }
+        }
+    }
+}
+
+impl<F: Float> Block for EnvelopeDetector<F> {
+    type Input = Complex<F>;
+    type Output = F;
+
+    fn work(&mut self, input: &[Complex<F>], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&sample| self.envelope(sample)));
+        input.len()
+    }
+
+    /// Accepts `alpha`, between 0 and 1, switching to single-pole
+    /// smoothing, and `window`, in samples, switching to RMS.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("alpha", _) => match value.as_f64() {
+                Some(alpha) if (0.0..=1.0).contains(&alpha) => {
+                    self.set_smoothing(Smoothing::SinglePole { alpha })
+                }
+                _ => return Err(ParamError::invalid(name, value)),
+            },
+            ("window", ParamValue::Int(window)) if *window >= 1 => {
+                self.set_smoothing(Smoothing::Rms {
+                    window: *window as usize,
+                })
+            }
+            ("window", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    /// A 1 kHz carrier at 48 kHz, 50% amplitude-modulated by a 100 Hz tone.
+    fn am(n: usize) -> Complex<f64> {
+        let t = n as f64 / 48_000.0;
+        Complex::from_polar(
+            1.0 + 0.5 * (2.0 * PI * 100.0 * t).sin(),
+            2.0 * PI * 1000.0 * t,
+        )
+    }
+
+    #[test]
+    fn test_smoothing_modes() {
+        let mut plain = EnvelopeDetector::new(Smoothing::None);
+        assert_relative_eq!(plain.envelope(Complex::new(3.0, 4.0)), 5.0);
+
+        // The single-pole average follows the modulation with a small lag.
+        let mut detector = EnvelopeDetector::new(Smoothing::SinglePole { alpha: 0.2 });
+        let output: Vec<f64> = (0..4800).map(|n| detector.envelope(am(n))).collect();
+        let peak = output[2400..]
+            .iter()
+            .fold(0.0f64, |peak, &value| peak.max(value));
+        assert_relative_eq!(peak, 1.5, epsilon = 1e-3);
+
+        // A window of a whole modulation cycle levels it to the RMS.
+        let mut rms = EnvelopeDetector::new(Smoothing::Rms { window: 480 });
+        let output: Vec<f64> = (0..4800).map(|n| rms.envelope(am(n))).collect();
+        assert!(rms.settled());
+        assert_relative_eq!(output[4799], (1.0f64 + 0.125).sqrt(), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_parameters_switch_smoothing() {
+        let mut detector = EnvelopeDetector::<f32>::new(Smoothing::None);
+        detector
+            .set_parameter("window", &ParamValue::Int(4))
+            .unwrap();
+        assert_eq!(detector.smoothing(), Smoothing::Rms { window: 4 });
+        assert!(!detector.settled());
+        detector
+            .set_parameter("alpha", &ParamValue::Float(0.5))
+            .unwrap();
+        assert_eq!(detector.smoothing(), Smoothing::SinglePole { alpha: 0.5 });
+        assert!(detector
+            .set_parameter("window", &ParamValue::Int(0))
+            .is_err());
+        assert!(detector
+            .set_parameter("alpha", &ParamValue::Float(1.5))
+            .is_err());
+    }
+}
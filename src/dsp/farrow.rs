@@ -0,0 +1,147 @@
+//! Arbitrary-ratio resampling.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_traits::Zero;
+use std::ops::{Add, Mul, Sub};
+
+/// Resamples by any ratio, including irrational and slowly varying ones,
+/// with a Farrow-structure cubic Lagrange interpolator.
+///
+/// Meant for small corrections such as tracking the sample-clock offset
+/// between two unsynchronized receivers: the interpolator is not a low-pass
+/// filter, so reducing the rate by much more than a few percent aliases.
+/// Output is delayed by two input samples.
+#[derive(Debug, Clone)]
+pub struct FarrowResampler<T> {
+    /// Input samples advanced per output sample, the inverse of the ratio.
+    step: f64,
+    /// Position of the next output between `history[1]` and `history[2]`,
+    /// in input samples.
+    mu: f64,
+    /// The last four input samples, oldest first.
+    history: [T; 4],
+}
+
+impl<T> FarrowResampler<T>
+where
+    T: Copy + Zero + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    /// A resampler producing `ratio` output samples per input sample.
+    pub fn new(ratio: f64) -> Self {
+        let mut resampler = FarrowResampler {
+            step: 1.0,
+            mu: 0.0,
+            history: [T::zero(); 4],
+        };
+        resampler.set_ratio(ratio);
+        resampler
+    }
+
+    pub fn ratio(&self) -> f64 {
+        1.0 / self.step
+    }
+
+    /// Changes the ratio from the next output on, without a glitch. Ratios
+    /// that are not positive and finite are ignored.
+    pub fn set_ratio(&mut self, ratio: f64) {
+        if ratio > 0.0 && ratio.is_finite() {
+            self.step = 1.0 / ratio;
+        }
+    }
+
+    /// The value `mu` of the way from `history[1]` to `history[2]`.
+    fn interpolate(&self, mu: f64) -> T {
+        let [before, x0, x1, x2] = self.history;
+        // The cubic through the four samples, as a polynomial in `mu`.
+        let c1 = x1 - before * (1.0 / 3.0) - x0 * 0.5 - x2 * (1.0 / 6.0);
+        let c2 = (before + x1) * 0.5 - x0;
+        let c3 = (x0 - x1) * 0.5 + (x2 - before) * (1.0 / 6.0);
+        ((c3 * mu + c2) * mu + c1) * mu + x0
+    }
+
+    /// Accepts one input sample and appends the output samples that fall
+    /// before the next.
+    pub fn resample(&mut self, sample: T, output: &mut Vec<T>) {
+        self.history.rotate_left(1);
+        self.history[3] = sample;
+        while self.mu < 1.0 {
+            output.push(self.interpolate(self.mu));
+            self.mu += self.step;
+        }
+        self.mu -= 1.0;
+    }
+}
+
+impl<T> Block for FarrowResampler<T>
+where
+    T: Copy + Zero + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        for &sample in input {
+            self.resample(sample, output);
+        }
+        input.len()
+    }
+
+    /// Accepts `ratio`, output samples per input sample.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("ratio", Some(ratio)) if ratio > 0.0 && ratio.is_finite() => {
+                self.set_ratio(ratio);
+                Ok(())
+            }
+            ("ratio", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use num_complex::Complex;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_cubics_are_reproduced_exactly() {
+        let cubic = |t: f64| 0.5 * t * t * t - 2.0 * t * t + t - 3.0;
+        let mut resampler = FarrowResampler::new(1.0 / 0.37);
+        let mut output = Vec::new();
+        let input: Vec<f64> = (0..40).map(|n| cubic(n as f64)).collect();
+        resampler.work(&input, &mut output);
+        // The first outputs interpolate the zeros before the stream.
+        for (k, &value) in output.iter().enumerate().skip(10) {
+            assert_relative_eq!(value, cubic(k as f64 * 0.37 - 2.0), max_relative = 1e-9);
+        }
+        assert_relative_eq!(output.len() as f64, 40.0 / 0.37, epsilon = 1.0);
+    }
+
+    #[test]
+    fn test_clock_offset_scales_tone_frequency() {
+        // 100 ppm fast, as between two free-running receivers.
+        let ratio = 1.0001;
+        let mut resampler = FarrowResampler::new(ratio);
+        let mut output = Vec::new();
+        for n in 0..20_000 {
+            resampler.resample(
+                Complex::from_polar(1.0, 2.0 * PI * 0.05 * n as f64),
+                &mut output,
+            );
+        }
+        assert!((20_002..=20_003).contains(&output.len()));
+        let turns: f64 = output[100..]
+            .windows(2)
+            .map(|pair| (pair[1] * pair[0].conj()).arg())
+            .sum::<f64>()
+            / (2.0 * PI * (output.len() - 101) as f64);
+        assert_relative_eq!(turns, 0.05 / ratio, max_relative = 1e-6);
+        assert!(resampler
+            .set_parameter("ratio", &ParamValue::Float(-1.0))
+            .is_err());
+    }
+}
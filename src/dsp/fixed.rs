@@ -0,0 +1,430 @@
+//! Fixed-point blocks for targets without an FPU.
+//!
+//! Samples are Q15: an `i16` `n` stands for `n / 32768`, so full scale is
+//! just under ±1. Products and sums are kept in `i32` or `i64` and rounded
+//! back to Q15 with saturation, never wrapping. Phases are binary angles in
+//! a `u32` or `i32`, where a full turn is 2^32, so they wrap for free.
+//!
+//! Floating point is only used to set blocks up — converting taps, filling
+//! the NCO's sine table — never per sample.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use std::f64::consts::TAU;
+
+/// Converts `value` to Q15, saturating outside `[-1, 1)`.
+pub fn to_q15(value: f64) -> i16 {
+    (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+pub fn from_q15(value: i16) -> f64 {
+    f64::from(value) / 32768.0
+}
+
+/// Converts `value` to Q31, saturating outside `[-1, 1)`.
+pub fn to_q31(value: f64) -> i32 {
+    (value * 2_147_483_648.0)
+        .round()
+        .clamp(-2_147_483_648.0, 2_147_483_647.0) as i32
+}
+
+pub fn from_q31(value: i32) -> f64 {
+    f64::from(value) / 2_147_483_648.0
+}
+
+/// Rounds a Q30 product (or a sum of them) back to Q15, saturating.
+fn round_q30(value: i64) -> i16 {
+    ((value + (1 << 14)) >> 15).clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16
+}
+
+/// A FIR filter with Q15 taps over Q15 IQ samples, accumulating in `i64` so
+/// that long filters cannot overflow before the final rounding.
+#[derive(Debug, Clone)]
+pub struct FirQ15 {
+    /// Taps in reverse order so they line up with the oldest-first history.
+    reversed: Vec<i16>,
+    /// Every sample is stored twice so the last `taps` samples are contiguous.
+    history: Vec<Complex<i16>>,
+    position: usize,
+}
+
+impl FirQ15 {
+    pub fn new(taps: &[i16]) -> Self {
+        let taps = if taps.is_empty() {
+            &[i16::MAX][..]
+        } else {
+            taps
+        };
+        FirQ15 {
+            reversed: taps.iter().rev().copied().collect(),
+            history: vec![Complex::new(0, 0); taps.len() * 2],
+            position: 0,
+        }
+    }
+
+    /// Creates a filter from floating-point taps, such as those from
+    /// [`lowpass`](super::fir::lowpass).
+    pub fn from_taps(taps: &[f64]) -> Self {
+        Self::new(&taps.iter().map(|&tap| to_q15(tap)).collect::<Vec<_>>())
+    }
+
+    pub fn taps(&self) -> impl Iterator<Item = i16> + '_ {
+        self.reversed.iter().rev().copied()
+    }
+
+    pub fn filter(&mut self, sample: Complex<i16>) -> Complex<i16> {
+        let length = self.reversed.len();
+        self.history[self.position] = sample;
+        self.history[self.position + length] = sample;
+        self.position = (self.position + 1) % length;
+        let window = &self.history[self.position..self.position + length];
+        let (re, im) =
+            window
+                .iter()
+                .zip(&self.reversed)
+                .fold((0i64, 0i64), |(re, im), (sample, &tap)| {
+                    (
+                        re + i64::from(i32::from(sample.re) * i32::from(tap)),
+                        im + i64::from(i32::from(sample.im) * i32::from(tap)),
+                    )
+                });
+        Complex::new(round_q30(re), round_q30(im))
+    }
+}
+
+impl Block for FirQ15 {
+    type Input = Complex<i16>;
+    type Output = Complex<i16>;
+
+    fn work(&mut self, input: &[Complex<i16>], output: &mut Vec<Complex<i16>>) -> usize {
+        output.extend(input.iter().map(|&sample| self.filter(sample)));
+        input.len()
+    }
+
+    /// Accepts `taps` as a list of numbers, converted to Q15. The history is
+    /// kept if the length is unchanged.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_floats()) {
+            ("taps", Some(taps)) if !taps.is_empty() => {
+                if taps.len() == self.reversed.len() {
+                    self.reversed = taps.iter().rev().map(|&tap| to_q15(tap)).collect();
+                } else {
+                    *self = Self::from_taps(taps);
+                }
+                Ok(())
+            }
+            ("taps", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// Entries in the NCO's sine table; the top bits of the phase index it.
+const SINE_TABLE_BITS: u32 = 10;
+
+/// A numerically controlled oscillator with a 32-bit phase accumulator and
+/// Q15 output, which can also shift a Q15 IQ stream in frequency.
+///
+/// The phase increment is the frequency as a fraction of the sample rate
+/// times 2^32, so the frequency resolution is `sample_rate / 2^32`. The
+/// table lookup truncates the phase to 10 bits, which puts spurs around
+/// -60 dBc.
+#[derive(Debug, Clone)]
+pub struct NcoQ15 {
+    phase: u32,
+    increment: u32,
+    sine: Vec<i16>,
+}
+
+impl NcoQ15 {
+    pub fn new(phase_increment: u32) -> Self {
+        let size = 1 << SINE_TABLE_BITS;
+        NcoQ15 {
+            phase: 0,
+            increment: phase_increment,
+            sine: (0..size)
+                .map(|index| to_q15((TAU * index as f64 / size as f64).sin()))
+                .collect(),
+        }
+    }
+
+    /// Creates an oscillator at `frequency` Hz, which may be negative.
+    pub fn with_frequency(frequency: f64, sample_rate: f64) -> Self {
+        Self::new(Self::phase_increment(frequency, sample_rate))
+    }
+
+    /// The phase increment for `frequency` Hz at `sample_rate`.
+    pub fn phase_increment(frequency: f64, sample_rate: f64) -> u32 {
+        ((frequency / sample_rate).rem_euclid(1.0) * 4_294_967_296.0).round() as u64 as u32
+    }
+
+    pub fn set_phase_increment(&mut self, phase_increment: u32) {
+        self.increment = phase_increment;
+    }
+
+    /// The current phase, where a full turn is 2^32.
+    pub fn phase(&self) -> u32 {
+        self.phase
+    }
+
+    /// The oscillator's next sample, `e^(i phase)` in Q15.
+    pub fn next_sample(&mut self) -> Complex<i16> {
+        let shift = 32 - SINE_TABLE_BITS;
+        let index = (self.phase >> shift) as usize;
+        let quarter = 1 << (SINE_TABLE_BITS - 2);
+        let mask = self.sine.len() - 1;
+        let sample = Complex::new(self.sine[(index + quarter) & mask], self.sine[index]);
+        self.phase = self.phase.wrapping_add(self.increment);
+        sample
+    }
+
+    /// Multiplies `sample` by the oscillator, shifting it up by the NCO's
+    /// frequency.
+    pub fn mix(&mut self, sample: Complex<i16>) -> Complex<i16> {
+        let lo = self.next_sample();
+        let (a, b) = (i32::from(sample.re), i32::from(sample.im));
+        let (c, d) = (i32::from(lo.re), i32::from(lo.im));
+        // Each difference of two Q30 products stays inside i32.
+        Complex::new(
+            round_q30(i64::from(a * c - b * d)),
+            round_q30(i64::from(a * d + b * c)),
+        )
+    }
+}
+
+impl Block for NcoQ15 {
+    type Input = Complex<i16>;
+    type Output = Complex<i16>;
+
+    fn work(&mut self, input: &[Complex<i16>], output: &mut Vec<Complex<i16>>) -> usize {
+        output.extend(input.iter().map(|&sample| self.mix(sample)));
+        input.len()
+    }
+
+    /// Accepts `phase_increment` as an integer.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("phase_increment", &ParamValue::Int(increment)) => {
+                let increment =
+                    u32::try_from(increment).map_err(|_| ParamError::invalid(name, value))?;
+                self.set_phase_increment(increment);
+                Ok(())
+            }
+            ("phase_increment", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// `atan(2^-i)` as binary angles, where π is 2^31.
+const CORDIC_ANGLES: [i32; 31] = [
+    536_870_912,
+    316_933_406,
+    167_458_907,
+    85_004_756,
+    42_667_331,
+    21_354_465,
+    10_679_838,
+    5_340_245,
+    2_670_163,
+    1_335_087,
+    667_544,
+    333_772,
+    166_886,
+    83_443,
+    41_722,
+    20_861,
+    10_430,
+    5_215,
+    2_608,
+    1_304,
+    652,
+    326,
+    163,
+    81,
+    41,
+    20,
+    10,
+    5,
+    3,
+    1,
+    1,
+];
+
+/// The angle of `(x, y)` as a binary angle, where π is 2^31, by CORDIC
+/// vectoring. Accurate to a few parts in 2^31 of a turn.
+pub fn atan2_q31(y: i64, x: i64) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    // Scale to 29 bits: as many as possible for precision, while leaving
+    // room for the CORDIC gain of 1.65.
+    let bits = 64 - x.unsigned_abs().max(y.unsigned_abs()).leading_zeros();
+    let (mut x, mut y) = if bits > 29 {
+        ((x >> (bits - 29)) as i32, (y >> (bits - 29)) as i32)
+    } else {
+        ((x << (29 - bits)) as i32, (y << (29 - bits)) as i32)
+    };
+    // Rotate into the right half-plane, where the iteration converges.
+    let mut angle: i32 = 0;
+    if x < 0 {
+        (x, y, angle) = if y >= 0 {
+            (y, -x, 1 << 30)
+        } else {
+            (-y, x, -(1 << 30))
+        };
+    }
+    for (i, &step) in CORDIC_ANGLES.iter().enumerate() {
+        let (dx, dy) = (y >> i, x >> i);
+        if y > 0 {
+            (x, y) = (x + dx, y - dy);
+            angle = angle.wrapping_add(step);
+        } else {
+            (x, y) = (x - dx, y + dy);
+            angle = angle.wrapping_sub(step);
+        }
+    }
+    angle
+}
+
+/// Quadrature FM demodulator on Q15 IQ samples.
+///
+/// The output is the phase step between consecutive samples in Q15 of π:
+/// 32767 is just under half a turn per sample. A tone at `f` Hz sampled at
+/// `fs` therefore demodulates to `2 f / fs`, and a deviation of
+/// `fs / 2 / 2^k` is full scale after shifting the output left by `k`, as
+/// [`FmDemodQ15::with_gain_shift`] does.
+#[derive(Debug, Clone)]
+pub struct FmDemodQ15 {
+    previous: Complex<i16>,
+    shift: u32,
+}
+
+impl FmDemodQ15 {
+    pub fn new() -> Self {
+        Self::with_gain_shift(0)
+    }
+
+    /// Creates a demodulator whose output is scaled up by `2^shift`,
+    /// saturating.
+    pub fn with_gain_shift(shift: u32) -> Self {
+        FmDemodQ15 {
+            previous: Complex::new(0, 0),
+            shift: shift.min(15),
+        }
+    }
+
+    /// The phase step in Q31 of π, before any gain.
+    pub fn demodulate_q31(&mut self, sample: Complex<i16>) -> i32 {
+        let (a, b) = (i64::from(sample.re), i64::from(sample.im));
+        let (c, d) = (i64::from(self.previous.re), i64::from(self.previous.im));
+        self.previous = sample;
+        // sample * conj(previous)
+        atan2_q31(b * c - a * d, a * c + b * d)
+    }
+
+    pub fn demodulate(&mut self, sample: Complex<i16>) -> i16 {
+        let step = i64::from(self.demodulate_q31(sample)) << self.shift;
+        ((step + (1 << 15)) >> 16).clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16
+    }
+}
+
+impl Default for FmDemodQ15 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Block for FmDemodQ15 {
+    type Input = Complex<i16>;
+    type Output = i16;
+
+    fn work(&mut self, input: &[Complex<i16>], output: &mut Vec<i16>) -> usize {
+        output.extend(input.iter().map(|&sample| self.demodulate(sample)));
+        input.len()
+    }
+
+    /// Accepts `gain_shift`, an integer from 0 to 15.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("gain_shift", &ParamValue::Int(shift @ 0..=15)) => {
+                self.shift = shift as u32;
+                Ok(())
+            }
+            ("gain_shift", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::fir::{lowpass, Fir};
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    fn q15_tone(frequency: f64, amplitude: f64, length: usize) -> Vec<Complex<i16>> {
+        (0..length)
+            .map(|n| {
+                let sample = Complex::from_polar(amplitude, TAU * frequency * n as f64);
+                Complex::new(to_q15(sample.re), to_q15(sample.im))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fir_tracks_floating_point() {
+        let taps = lowpass(31, 0.1);
+        let mut fixed = FirQ15::from_taps(&taps);
+        let mut float = Fir::new(&taps);
+        for sample in q15_tone(0.03, 0.9, 300) {
+            let expected = float.filter(Complex::new(from_q15(sample.re), from_q15(sample.im)));
+            let filtered = fixed.filter(sample);
+            assert_relative_eq!(from_q15(filtered.re), expected.re, epsilon = 1e-3);
+            assert_relative_eq!(from_q15(filtered.im), expected.im, epsilon = 1e-3);
+        }
+        // Full-scale input on unity taps saturates rather than wrapping.
+        let mut gain = FirQ15::new(&[i16::MAX, i16::MAX]);
+        gain.filter(Complex::new(i16::MAX, i16::MIN));
+        assert_eq!(
+            gain.filter(Complex::new(i16::MAX, i16::MIN)),
+            Complex::new(i16::MAX, i16::MIN)
+        );
+    }
+
+    #[test]
+    fn test_nco_shifts_a_tone() {
+        let sample_rate = 48_000.0;
+        let mut nco = NcoQ15::with_frequency(-6_000.0, sample_rate);
+        assert_eq!(nco.increment, 0xe000_0000);
+        let mut demod = FmDemodQ15::new();
+        let steps: Vec<i16> = q15_tone(9_000.0 / sample_rate, 0.5, 200)
+            .into_iter()
+            .map(|sample| demod.demodulate(nco.mix(sample)))
+            .collect();
+        // 3 kHz at 48 kHz is 2 * 3000 / 48000 of π per sample.
+        for &step in &steps[1..] {
+            assert_relative_eq!(from_q15(step), 0.125, epsilon = 5e-3);
+        }
+    }
+
+    #[test]
+    fn test_cordic_angles() {
+        for degrees in (-179..=180).step_by(7) {
+            let angle = f64::from(degrees).to_radians();
+            let (y, x) = ((angle.sin() * 1e6) as i64, (angle.cos() * 1e6) as i64);
+            let estimate = from_q31(atan2_q31(y, x)) * PI;
+            let error = (estimate - angle + PI).rem_euclid(TAU) - PI;
+            assert!(error.abs() < 1e-5, "{degrees}: {estimate}");
+        }
+        assert_eq!(atan2_q31(0, 0), 0);
+        let mut demod = FmDemodQ15::with_gain_shift(2);
+        assert!(demod
+            .set_parameter("gain_shift", &ParamValue::Int(16))
+            .is_err());
+        demod.demodulate(Complex::new(1000, 0));
+        assert_eq!(demod.demodulate(Complex::new(0, 1000)), i16::MAX);
+    }
+}
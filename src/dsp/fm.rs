@@ -0,0 +1,402 @@
+//! Frequency demodulation and measurement.
+
+use crate::block::Block;
+use crate::dsp::complex::phase_difference;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::{Float, FloatConst};
+
+/// Quadrature FM demodulator: the phase step between consecutive samples,
+/// scaled by a gain.
+///
+/// Works in `f64` unless built with `f32` arguments, in which case samples,
+/// state and output are all single precision.
+#[derive(Debug, Clone)]
+pub struct FmDemod<F = f64> {
+    previous: Complex<F>,
+    gain: F,
+}
+
+impl<F: Float + FloatConst> FmDemod<F> {
+    /// Creates a demodulator whose output is `gain` times the phase step in
+    /// radians per sample.
+    pub fn new(gain: F) -> Self {
+        FmDemod {
+            previous: Complex::new(F::zero(), F::zero()),
+            gain,
+        }
+    }
+
+    /// Creates a demodulator whose output is 1.0 at the peak `deviation` Hz.
+    pub fn with_deviation(sample_rate: F, deviation: F) -> Self {
+        Self::new(sample_rate / (F::TAU() * deviation))
+    }
+
+    pub fn demodulate(&mut self, sample: Complex<F>) -> F {
+        let step = phase_difference(sample, self.previous);
+        self.previous = sample;
+        step * self.gain
+    }
+}
+
+impl<F: Float + FloatConst> Default for FmDemod<F> {
+    fn default() -> Self {
+        Self::new(F::one())
+    }
+}
+
+impl<F: Float + FloatConst> Block for FmDemod<F> {
+    type Input = Complex<F>;
+    type Output = F;
+
+    fn work(&mut self, input: &[Complex<F>], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&sample| self.demodulate(sample)));
+        input.len()
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match name {
+            "gain" => {
+                self.gain = value
+                    .as_f64()
+                    .and_then(num_traits::cast)
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                Ok(())
+            }
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// The frequency in Hz of each complex sample relative to the centre: the
+/// phase step from the previous sample scaled by the sample rate.
+///
+/// Unlike [`FmDemod`] the output is in absolute units, for measuring FSK
+/// shifts, carrier offsets and the frequency transients of a transmitter
+/// keying up. Frequencies beyond half the sample rate alias. The first
+/// output is zero.
+#[derive(Debug, Clone)]
+pub struct InstantaneousFrequency<F = f64> {
+    previous: Option<Complex<F>>,
+    sample_rate: F,
+}
+
+impl<F: Float + FloatConst> InstantaneousFrequency<F> {
+    pub fn new(sample_rate: F) -> Self {
+        InstantaneousFrequency {
+            previous: None,
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> F {
+        self.sample_rate
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn frequency(&mut self, sample: Complex<F>) -> F {
+        let step = self
+            .previous
+            .map_or(F::zero(), |previous| phase_difference(sample, previous));
+        self.previous = Some(sample);
+        step / F::TAU() * self.sample_rate
+    }
+}
+
+impl<F: Float + FloatConst> Block for InstantaneousFrequency<F> {
+    type Input = Complex<F>;
+    type Output = F;
+
+    fn work(&mut self, input: &[Complex<F>], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&sample| self.frequency(sample)));
+        input.len()
+    }
+
+    /// Accepts `sample_rate`, in Hz.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("sample_rate", Some(rate)) if rate > 0.0 => {
+                self.sample_rate =
+                    num_traits::cast(rate).ok_or_else(|| ParamError::invalid(name, value))?;
+                Ok(())
+            }
+            ("sample_rate", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// The time constant of broadcast FM's treble emphasis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Emphasis {
+    /// 50 µs, used in Europe, Asia, Africa and Australia.
+    Tau50,
+    /// 75 µs, used in the Americas and South Korea.
+    Tau75,
+    /// Any other time constant, in seconds.
+    Custom(f64),
+}
+
+impl Emphasis {
+    /// The time constant in seconds.
+    pub fn time_constant(self) -> f64 {
+        match self {
+            Emphasis::Tau50 => 50e-6,
+            Emphasis::Tau75 => 75e-6,
+            Emphasis::Custom(seconds) => seconds,
+        }
+    }
+
+    /// The single-pole coefficient at `sample_rate`, by the matched z
+    /// transform.
+    fn alpha<F: Float>(self, sample_rate: F) -> F {
+        let tau = F::from(self.time_constant()).unwrap_or_else(F::zero);
+        F::one() - (-F::one() / (sample_rate * tau)).exp()
+    }
+}
+
+/// Undoes broadcast FM's treble boost with a single-pole low-pass whose
+/// corner is set by the time constant: 3.2 kHz for 50 µs, 2.1 kHz for
+/// 75 µs. Apply it to the demodulated audio, before any decimation.
+#[derive(Debug, Clone)]
+pub struct DeEmphasis<F = f64> {
+    emphasis: Emphasis,
+    sample_rate: F,
+    alpha: F,
+    previous: F,
+}
+
+impl<F: Float> DeEmphasis<F> {
+    pub fn new(sample_rate: F, emphasis: Emphasis) -> Self {
+        DeEmphasis {
+            emphasis,
+            sample_rate,
+            alpha: emphasis.alpha(sample_rate),
+            previous: F::zero(),
+        }
+    }
+
+    pub fn emphasis(&self) -> Emphasis {
+        self.emphasis
+    }
+
+    pub fn set_emphasis(&mut self, emphasis: Emphasis) {
+        self.emphasis = emphasis;
+        self.alpha = emphasis.alpha(self.sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = F::zero();
+    }
+
+    pub fn filter(&mut self, sample: F) -> F {
+        self.previous = self.previous + (sample - self.previous) * self.alpha;
+        self.previous
+    }
+}
+
+impl<F: Float> Block for DeEmphasis<F> {
+    type Input = F;
+    type Output = F;
+
+    fn work(&mut self, input: &[F], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&sample| self.filter(sample)));
+        input.len()
+    }
+
+    /// Accepts `time_constant`, in seconds.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("time_constant", Some(seconds)) if seconds > 0.0 => {
+                self.set_emphasis(Emphasis::Custom(seconds));
+                Ok(())
+            }
+            ("time_constant", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// Boosts treble ahead of an FM modulator, the exact inverse of
+/// [`DeEmphasis`] with the same time constant, so a transmitted test signal
+/// comes out of a standard receiver flat.
+///
+/// The boost keeps rising up to the Nyquist frequency: at 15 kHz it is about
+/// 14 dB for 50 µs and 17 dB for 75 µs, so audio should be band-limited and
+/// its level reduced first to keep the peak deviation in bounds.
+#[derive(Debug, Clone)]
+pub struct PreEmphasis<F = f64> {
+    emphasis: Emphasis,
+    sample_rate: F,
+    alpha: F,
+    previous: F,
+}
+
+impl<F: Float> PreEmphasis<F> {
+    pub fn new(sample_rate: F, emphasis: Emphasis) -> Self {
+        PreEmphasis {
+            emphasis,
+            sample_rate,
+            alpha: emphasis.alpha(sample_rate),
+            previous: F::zero(),
+        }
+    }
+
+    pub fn emphasis(&self) -> Emphasis {
+        self.emphasis
+    }
+
+    pub fn set_emphasis(&mut self, emphasis: Emphasis) {
+        self.emphasis = emphasis;
+        self.alpha = emphasis.alpha(self.sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = F::zero();
+    }
+
+    pub fn filter(&mut self, sample: F) -> F {
+        let output = (sample - self.previous * (F::one() - self.alpha)) / self.alpha;
+        self.previous = sample;
+        output
+    }
+}
+
+impl<F: Float> Block for PreEmphasis<F> {
+    type Input = F;
+    type Output = F;
+
+    fn work(&mut self, input: &[F], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&sample| self.filter(sample)));
+        input.len()
+    }
+
+    /// Accepts `time_constant`, in seconds.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64()) {
+            ("time_constant", Some(seconds)) if seconds > 0.0 => {
+                self.set_emphasis(Emphasis::Custom(seconds));
+                Ok(())
+            }
+            ("time_constant", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_constant_tone_demodulates_to_its_deviation() {
+        let sample_rate = 48_000.0;
+        let mut demod = FmDemod::with_deviation(sample_rate, 5_000.0);
+        let output: Vec<f64> = (0..100)
+            .map(|n| Complex::from_polar(1.0, 2.0 * PI * 2_500.0 * n as f64 / sample_rate))
+            .map(|sample| demod.demodulate(sample))
+            .collect();
+        for value in &output[1..] {
+            assert_relative_eq!(*value, 0.5, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_single_precision() {
+        let mut demod = FmDemod::with_deviation(48_000f32, 5_000.0);
+        let output: Vec<f32> = (0..100)
+            .map(|n| {
+                Complex::from_polar(
+                    1.0,
+                    2.0 * std::f32::consts::PI * 2_500.0 * n as f32 / 48_000.0,
+                )
+            })
+            .map(|sample| demod.demodulate(sample))
+            .collect();
+        for value in &output[1..] {
+            assert_relative_eq!(*value, 0.5, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_instantaneous_frequency_follows_fsk_shifts() {
+        let sample_rate = 9_600.0;
+        let mut phase = 0.0;
+        let input: Vec<Complex<f64>> = (0..200)
+            .map(|n| {
+                phase += 2.0 * PI * if n < 100 { 1_200.0 } else { -2_200.0 } / sample_rate;
+                Complex::from_polar(0.3, phase)
+            })
+            .collect();
+        let mut estimator = InstantaneousFrequency::new(sample_rate);
+        let mut output = Vec::new();
+        estimator.work(&input, &mut output);
+        assert_eq!(output[0], 0.0);
+        assert_relative_eq!(output[50], 1_200.0, epsilon = 1e-6);
+        assert_relative_eq!(output[150], -2_200.0, epsilon = 1e-6);
+        assert!(estimator
+            .set_parameter("sample_rate", &ParamValue::Float(0.0))
+            .is_err());
+    }
+
+    /// The steady-state gain of `block` for a tone of `frequency` Hz.
+    fn tone_gain<B: Block<Input = f64, Output = f64>>(block: &mut B, frequency: f64) -> f64 {
+        let rate = 48_000.0;
+        let input: Vec<f64> = (0..9_600)
+            .map(|n| (2.0 * PI * frequency * n as f64 / rate).sin())
+            .collect();
+        let mut output = Vec::new();
+        block.work(&input, &mut output);
+        let rms = |samples: &[f64]| {
+            (samples.iter().map(|x| x * x).sum::<f64>() / samples.len() as f64).sqrt()
+        };
+        rms(&output[4_800..]) / rms(&input[4_800..])
+    }
+
+    #[test]
+    fn test_de_emphasis_corner() {
+        let mut eu = DeEmphasis::new(48_000.0, Emphasis::Tau50);
+        let corner = 1.0 / (2.0 * PI * 50e-6);
+        // Close to the analogue -3 dB, as the corner is well below Nyquist.
+        assert_relative_eq!(tone_gain(&mut eu, corner), 0.5f64.sqrt(), epsilon = 0.02);
+        eu.reset();
+        assert_relative_eq!(tone_gain(&mut eu, 50.0), 1.0, epsilon = 1e-3);
+
+        let mut us = DeEmphasis::new(48_000.0, Emphasis::Tau75);
+        let eu_treble = tone_gain(&mut eu, 10_000.0);
+        assert!(tone_gain(&mut us, 10_000.0) < eu_treble);
+        us.set_parameter("time_constant", &ParamValue::Float(50e-6))
+            .unwrap();
+        assert_eq!(us.emphasis(), Emphasis::Custom(50e-6));
+        assert!(us
+            .set_parameter("time_constant", &ParamValue::Float(0.0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_pre_emphasis_inverts_de_emphasis() {
+        let mut pre = PreEmphasis::new(48_000.0, Emphasis::Tau75);
+        let corner = 1.0 / (2.0 * PI * 75e-6);
+        assert_relative_eq!(tone_gain(&mut pre, corner), 2f64.sqrt(), epsilon = 0.03);
+        assert!(pre
+            .set_parameter("time_constant", &ParamValue::Float(-1.0))
+            .is_err());
+
+        pre.reset();
+        let mut de = DeEmphasis::new(48_000.0, Emphasis::Tau75);
+        let audio = crate::bench::real_noise(1_000, 12);
+        let mut emphasized = Vec::new();
+        pre.work(&audio, &mut emphasized);
+        let mut restored = Vec::new();
+        de.work(&emphasized, &mut restored);
+        for (restored, original) in restored.iter().zip(&audio) {
+            assert_relative_eq!(restored, original, epsilon = 1e-9);
+        }
+    }
+}
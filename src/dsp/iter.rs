@@ -0,0 +1,176 @@
+//! Iterator adapters for simple offline pipelines.
+//!
+//! ```
+//! use num_complex::Complex;
+//! use sdr_rust::dsp::fir::lowpass;
+//! use sdr_rust::dsp::DspIteratorExt;
+//!
+//! let taps = lowpass(31, 0.05);
+//! let samples = (0..1000).map(|n| Complex::from_polar(1.0, 0.01 * n as f64));
+//! let audio: Vec<f64> = samples.fir(&taps).decimate(10).fm_demod().collect();
+//! assert_eq!(audio.len(), 100);
+//! ```
+
+use super::fir::{Fir, FirSample};
+use super::fm::FmDemod;
+use crate::average;
+use num_complex::Complex;
+use std::iter::StepBy;
+
+/// Adapters that chain the crate's DSP blocks onto any iterator of samples.
+/// Each adapter allocates its state once and processes samples lazily.
+pub trait DspIteratorExt: Iterator + Sized {
+    /// Filters the samples with the given real taps.
+    fn fir(self, taps: &[f64]) -> FirIter<Self>
+    where
+        Self::Item: FirSample<f64>,
+    {
+        FirIter {
+            inner: self,
+            fir: Fir::new(taps),
+        }
+    }
+
+    /// Keeps the first of every `factor` samples.
+    fn decimate(self, factor: usize) -> StepBy<Self> {
+        self.step_by(factor.max(1))
+    }
+
+    /// Demodulates FM into the phase step in radians per sample.
+    fn fm_demod(self) -> FmDemodIter<Self>
+    where
+        Self: Iterator<Item = Complex<f64>>,
+    {
+        FmDemodIter {
+            inner: self,
+            demod: FmDemod::default(),
+        }
+    }
+
+    /// Averages every `size` (angle, magnitude) readings with [`average`].
+    /// A shorter final chunk is averaged too.
+    fn chunks_avg(self, size: usize) -> ChunksAvg<Self>
+    where
+        Self: Iterator<Item = (f64, f64)>,
+    {
+        let size = size.max(1);
+        ChunksAvg {
+            inner: self,
+            size,
+            chunk: Vec::with_capacity(size),
+        }
+    }
+}
+
+impl<I: Iterator> DspIteratorExt for I {}
+
+/// Iterator returned by [`DspIteratorExt::fir`].
+pub struct FirIter<I>
+where
+    I: Iterator,
+    I::Item: FirSample<f64>,
+{
+    inner: I,
+    fir: Fir<I::Item>,
+}
+
+impl<I> Iterator for FirIter<I>
+where
+    I: Iterator,
+    I::Item: FirSample<f64>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.inner.next().map(|sample| self.fir.filter(sample))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterator returned by [`DspIteratorExt::fm_demod`].
+pub struct FmDemodIter<I> {
+    inner: I,
+    demod: FmDemod,
+}
+
+impl<I: Iterator<Item = Complex<f64>>> Iterator for FmDemodIter<I> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        self.inner
+            .next()
+            .map(|sample| self.demod.demodulate(sample))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterator returned by [`DspIteratorExt::chunks_avg`].
+pub struct ChunksAvg<I> {
+    inner: I,
+    size: usize,
+    chunk: Vec<(f64, f64)>,
+}
+
+impl<I: Iterator<Item = (f64, f64)>> Iterator for ChunksAvg<I> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<(f64, f64)> {
+        self.chunk.clear();
+        self.chunk.extend(self.inner.by_ref().take(self.size));
+        (!self.chunk.is_empty()).then(|| average(&self.chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_chain_matches_blocks() {
+        let taps = [0.25, 0.5, 0.25];
+        let samples: Vec<Complex<f64>> = (0..50)
+            .map(|n| Complex::from_polar(1.0, 0.1 * n as f64))
+            .collect();
+        let chained: Vec<f64> = samples
+            .iter()
+            .copied()
+            .fir(&taps)
+            .decimate(5)
+            .fm_demod()
+            .collect();
+
+        let mut fir = Fir::new(&taps);
+        let mut demod = FmDemod::default();
+        let expected: Vec<f64> = samples
+            .iter()
+            .map(|&sample| fir.filter(sample))
+            .step_by(5)
+            .map(|sample| demod.demodulate(sample))
+            .collect();
+        assert_eq!(chained, expected);
+        assert_relative_eq!(chained[3], 0.5, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_chunks_avg() {
+        let readings = [
+            (10.0, 1.0),
+            (20.0, 1.0),
+            (350.0, 1.0),
+            (10.0, 1.0),
+            (90.0, 1.0),
+        ];
+        let averaged: Vec<(f64, f64)> = readings.iter().copied().chunks_avg(2).collect();
+        assert_eq!(averaged.len(), 3);
+        assert_relative_eq!(averaged[0].0, 15.0, epsilon = 1e-9);
+        assert_relative_eq!(averaged[1].0, 0.0, epsilon = 1e-9);
+        assert_relative_eq!(averaged[2].0, 90.0, epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,224 @@
+//! Adaptive filtering by normalized least mean squares.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_complex::Complex;
+use num_traits::Zero;
+use std::collections::VecDeque;
+use std::ops::{Add, Mul, Sub};
+
+/// A sample type [`Nlms`] can adapt over: real or complex `f64`.
+pub trait LmsSample:
+    Copy + Zero + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Mul<f64, Output = Self>
+{
+    fn conj(self) -> Self;
+
+    fn norm_sqr(self) -> f64;
+}
+
+impl LmsSample for f64 {
+    fn conj(self) -> f64 {
+        self
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self * self
+    }
+}
+
+impl LmsSample for Complex<f64> {
+    fn conj(self) -> Complex<f64> {
+        Complex::conj(&self)
+    }
+
+    fn norm_sqr(self) -> f64 {
+        Complex::norm_sqr(&self)
+    }
+}
+
+/// A normalized-LMS adaptive FIR filter.
+///
+/// Each step filters the reference through the current weights, subtracts
+/// that from the input and nudges the weights to shrink the difference, by
+/// a step scaled by the reference power so convergence does not depend on
+/// level. What comes out is the error: the part of the input the reference
+/// cannot explain. With a noise-only reference that is noise cancellation,
+/// with the far-end signal as reference it is echo cancellation, and with a
+/// known training sequence as the input the weights become an equalizer.
+///
+/// As a [`Block`] it takes `(reference, input)` pairs.
+#[derive(Debug, Clone)]
+pub struct Nlms<T = f64> {
+    weights: Vec<T>,
+    /// The newest reference samples, newest first, and their total power.
+    history: VecDeque<T>,
+    power: f64,
+    step: f64,
+    adapting: bool,
+}
+
+impl<T: LmsSample> Nlms<T> {
+    /// Keeps adaptation from blowing up while the reference is silent.
+    const REGULARIZATION: f64 = 1e-9;
+
+    /// A filter of `num_taps` weights, at least one, starting from zero,
+    /// with step size `step`. Steps between 0 and 2 converge; around 0.1
+    /// balances speed against misadjustment.
+    pub fn new(num_taps: usize, step: f64) -> Self {
+        let num_taps = num_taps.max(1);
+        Nlms {
+            weights: vec![T::zero(); num_taps],
+            history: std::iter::repeat_n(T::zero(), num_taps).collect(),
+            power: 0.0,
+            step,
+            adapting: true,
+        }
+    }
+
+    pub fn weights(&self) -> &[T] {
+        &self.weights
+    }
+
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Freezes the weights, or lets them adapt again. A frozen filter keeps
+    /// cancelling with what it has learned, as when the wanted signal is
+    /// present and would otherwise be adapted away.
+    pub fn set_adapting(&mut self, adapting: bool) {
+        self.adapting = adapting;
+    }
+
+    /// Zeroes the weights and the reference history.
+    pub fn reset(&mut self) {
+        self.weights
+            .iter_mut()
+            .for_each(|weight| *weight = T::zero());
+        self.history
+            .iter_mut()
+            .for_each(|sample| *sample = T::zero());
+        self.power = 0.0;
+    }
+
+    /// Accepts one reference and one input sample and returns the error.
+    pub fn filter(&mut self, reference: T, input: T) -> T {
+        if let Some(oldest) = self.history.pop_back() {
+            self.power -= oldest.norm_sqr();
+        }
+        self.history.push_front(reference);
+        self.power = (self.power + reference.norm_sqr()).max(0.0);
+
+        let estimate = self
+            .weights
+            .iter()
+            .zip(&self.history)
+            .fold(T::zero(), |total, (&weight, &sample)| {
+                total + weight * sample
+            });
+        let error = input - estimate;
+        if self.adapting {
+            let gain = error * (self.step / (self.power + Self::REGULARIZATION));
+            for (weight, &sample) in self.weights.iter_mut().zip(&self.history) {
+                *weight = *weight + gain * sample.conj();
+            }
+        }
+        error
+    }
+}
+
+impl<T: LmsSample> Block for Nlms<T> {
+    type Input = (T, T);
+    type Output = T;
+
+    fn work(&mut self, input: &[(T, T)], output: &mut Vec<T>) -> usize {
+        output.extend(
+            input
+                .iter()
+                .map(|&(reference, sample)| self.filter(reference, sample)),
+        );
+        input.len()
+    }
+
+    /// Accepts `step`, between 0 and 2, and `adapt`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match name {
+            "step" => match value.as_f64() {
+                Some(step) if step > 0.0 && step < 2.0 => self.step = step,
+                _ => return Err(ParamError::invalid(name, value)),
+            },
+            "adapt" => {
+                let adapting = value
+                    .as_bool()
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                self.set_adapting(adapting);
+            }
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{complex_noise, real_noise};
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_cancels_correlated_noise() {
+        // The noise reaches the input through a short echo path.
+        let path = [0.5, -0.3, 0.2];
+        let noise = real_noise(20_000, 4);
+        let speech = |n: usize| 0.1 * (2.0 * PI * 0.01 * n as f64).sin();
+        let input: Vec<(f64, f64)> = (0..noise.len())
+            .map(|n| {
+                let echo: f64 = (0..path.len())
+                    .filter(|&k| k <= n)
+                    .map(|k| path[k] * noise[n - k])
+                    .sum();
+                (noise[n], speech(n) + echo)
+            })
+            .collect();
+        let mut filter = Nlms::new(8, 0.02);
+        let mut output = Vec::new();
+        filter.work(&input, &mut output);
+        for (weight, expected) in filter.weights().iter().zip(path.iter().chain(&[0.0; 5])) {
+            assert_relative_eq!(weight, expected, epsilon = 0.02);
+        }
+        // What is left is the speech, with residual noise well below it.
+        let residual = output
+            .iter()
+            .enumerate()
+            .skip(15_000)
+            .map(|(n, &error)| (error - speech(n)).powi(2))
+            .sum::<f64>()
+            / 5_000.0;
+        assert!(residual.sqrt() < 0.01, "residual {}", residual.sqrt());
+    }
+
+    #[test]
+    fn test_learns_complex_channel() {
+        let channel = [Complex::new(0.8, 0.3), Complex::new(-0.2, 0.1)];
+        let symbols = complex_noise(5_000, 8);
+        let mut filter = Nlms::new(2, 0.5);
+        for n in 1..symbols.len() {
+            let received = channel[0] * symbols[n] + channel[1] * symbols[n - 1];
+            filter.filter(symbols[n], received);
+        }
+        for (weight, expected) in filter.weights().iter().zip(&channel) {
+            assert_relative_eq!((weight - expected).norm(), 0.0, epsilon = 1e-6);
+        }
+
+        let frozen = filter.weights().to_vec();
+        filter
+            .set_parameter("adapt", &ParamValue::Bool(false))
+            .unwrap();
+        filter.filter(Complex::new(1.0, 0.0), Complex::new(5.0, 0.0));
+        assert_eq!(filter.weights(), frozen);
+        assert!(filter
+            .set_parameter("step", &ParamValue::Float(2.5))
+            .is_err());
+    }
+}
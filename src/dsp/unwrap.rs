@@ -0,0 +1,156 @@
+//! Phase unwrapping.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use num_traits::{Float, FloatConst};
+
+/// Removes the 2π jumps from a stream of phases in radians, so a steadily
+/// advancing phase keeps advancing instead of wrapping at ±π.
+///
+/// A step between consecutive phases larger than the threshold, π unless
+/// set otherwise, is taken to be a wrap and corrected by whole turns.
+/// Thresholds below π are treated as π, since a smaller step can never be
+/// made smaller by adding turns. Raise the threshold for noisy phases whose
+/// genuine steps approach π.
+///
+/// Works in `f64` unless built with `f32` arguments.
+#[derive(Debug, Clone)]
+pub struct PhaseUnwrapper<F = f64> {
+    threshold: F,
+    /// The last unwrapped output, if any.
+    previous: Option<F>,
+}
+
+impl<F: Float + FloatConst> PhaseUnwrapper<F> {
+    pub fn new() -> Self {
+        Self::with_threshold(F::PI())
+    }
+
+    pub fn with_threshold(threshold: F) -> Self {
+        PhaseUnwrapper {
+            threshold: threshold.max(F::PI()),
+            previous: None,
+        }
+    }
+
+    pub fn threshold(&self) -> F {
+        self.threshold
+    }
+
+    /// Forgets the running phase, so the next input is passed through as is.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Returns `phase` plus however many turns bring it closest to the
+    /// previous output.
+    pub fn unwrap(&mut self, phase: F) -> F {
+        let unwrapped = match self.previous {
+            Some(previous) => {
+                let step = phase - previous;
+                if step.abs() > self.threshold {
+                    phase - (step / F::TAU()).round() * F::TAU()
+                } else {
+                    phase
+                }
+            }
+            None => phase,
+        };
+        self.previous = Some(unwrapped);
+        unwrapped
+    }
+}
+
+impl<F: Float + FloatConst> Default for PhaseUnwrapper<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float + FloatConst> Block for PhaseUnwrapper<F> {
+    type Input = F;
+    type Output = F;
+
+    fn work(&mut self, input: &[F], output: &mut Vec<F>) -> usize {
+        output.extend(input.iter().map(|&phase| self.unwrap(phase)));
+        input.len()
+    }
+
+    /// Accepts `threshold`, in radians.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value.as_f64().and_then(num_traits::cast::<f64, F>)) {
+            ("threshold", Some(threshold)) if threshold.is_finite() => {
+                self.threshold = threshold.max(F::PI());
+                Ok(())
+            }
+            ("threshold", _) => Err(ParamError::invalid(name, value)),
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// Unwraps `phases` in place with the default threshold of π.
+pub fn unwrap<F: Float + FloatConst>(phases: &mut [F]) {
+    unwrap_with_threshold(phases, F::PI());
+}
+
+/// Unwraps `phases` in place, treating steps larger than `threshold` as
+/// wraps.
+pub fn unwrap_with_threshold<F: Float + FloatConst>(phases: &mut [F], threshold: F) {
+    let mut unwrapper = PhaseUnwrapper::with_threshold(threshold);
+    for phase in phases {
+        *phase = unwrapper.unwrap(*phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_recovers_advancing_phase() {
+        let phase = |n: usize| 0.9 * n as f64 - 2.0;
+        let mut phases: Vec<f64> = (0..50)
+            .map(|n| {
+                let wrapped = phase(n).rem_euclid(2.0 * PI);
+                if wrapped > PI {
+                    wrapped - 2.0 * PI
+                } else {
+                    wrapped
+                }
+            })
+            .collect();
+        unwrap(&mut phases);
+        for (n, &unwrapped) in phases.iter().enumerate() {
+            assert_relative_eq!(unwrapped, phase(n), epsilon = 1e-9);
+        }
+
+        let mut single = [3.0f32, -3.0, 3.0];
+        unwrap(&mut single);
+        assert_relative_eq!(single[1], 2.0 * std::f32::consts::PI - 3.0, epsilon = 1e-6);
+        assert_relative_eq!(single[2], 3.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_threshold() {
+        // A genuine step of 3.5 rad is kept only with a larger threshold.
+        let mut phases = [0.0, 3.5, 3.6];
+        unwrap(&mut phases);
+        assert_relative_eq!(phases[1], 3.5 - 2.0 * PI);
+        let mut phases = [0.0, 3.5];
+        unwrap_with_threshold(&mut phases, 4.0);
+        assert_eq!(phases, [0.0, 3.5]);
+
+        let mut unwrapper = PhaseUnwrapper::<f64>::with_threshold(1.0);
+        assert_eq!(unwrapper.threshold(), PI);
+        unwrapper
+            .set_parameter("threshold", &ParamValue::Float(5.0))
+            .unwrap();
+        assert_eq!(unwrapper.threshold(), 5.0);
+        assert!(unwrapper
+            .set_parameter("threshold", &ParamValue::Bool(true))
+            .is_err());
+    }
+}
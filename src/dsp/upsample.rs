@@ -0,0 +1,150 @@
+//! Integer-factor rate increase.
+
+use super::fir::{lowpass, FirSample};
+use crate::block::Block;
+use num_traits::Float;
+
+/// Raises the sample rate by `factor`: conceptually inserts `factor - 1`
+/// zeros after each sample and low-pass filters, removing the spectral
+/// images.
+///
+/// The filter runs in polyphase form, so the zeros are never multiplied:
+/// each input sample produces `factor` outputs, one from each branch of
+/// every `factor`th tap. Taps are scaled by `factor` on construction, so a
+/// filter with unity DC gain keeps the signal's level.
+#[derive(Debug, Clone)]
+pub struct Upsampler<T: FirSample<Tap>, Tap = f64> {
+    /// Each phase's taps, reversed to line up with the oldest-first history.
+    phases: Vec<(Vec<Tap>, T::Kernel)>,
+    /// Every sample is stored twice so the last `length` are contiguous.
+    history: Vec<T>,
+    length: usize,
+    position: usize,
+}
+
+impl<T, Tap> Upsampler<T, Tap>
+where
+    T: FirSample<Tap>,
+    Tap: Float,
+{
+    /// An upsampler with the interpolation filter `taps`, designed for the
+    /// output rate.
+    pub fn new(factor: usize, taps: &[Tap]) -> Self {
+        let factor = factor.max(1);
+        let unity = [Tap::one()];
+        let taps = if taps.is_empty() { &unity[..] } else { taps };
+        let length = taps.len().div_ceil(factor);
+        let gain = Tap::from(factor).unwrap_or_else(Tap::one);
+        let phases = (0..factor)
+            .map(|phase| {
+                let mut reversed: Vec<Tap> = (0..length)
+                    .map(|k| {
+                        taps.get(phase + factor * k)
+                            .map_or(Tap::zero(), |&tap| tap * gain)
+                    })
+                    .collect();
+                reversed.reverse();
+                let kernel = T::kernel(&reversed);
+                (reversed, kernel)
+            })
+            .collect();
+        Upsampler {
+            phases,
+            history: vec![T::zero(); length * 2],
+            length,
+            position: 0,
+        }
+    }
+
+    /// An upsampler with a Hamming-windowed low-pass filter of
+    /// `taps_per_phase * factor` taps, cut off at the input's Nyquist rate.
+    pub fn with_lowpass(factor: usize, taps_per_phase: usize) -> Self {
+        let factor = factor.max(1);
+        let taps: Vec<Tap> = lowpass(taps_per_phase.max(1) * factor, 0.5 / factor as f64)
+            .into_iter()
+            .map(|tap| num_traits::cast(tap).unwrap_or_else(Tap::zero))
+            .collect();
+        Self::new(factor, &taps)
+    }
+
+    pub fn factor(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Accepts one input sample and appends its `factor` output samples.
+    pub fn upsample(&mut self, sample: T, output: &mut Vec<T>) {
+        self.history[self.position] = sample;
+        self.history[self.position + self.length] = sample;
+        self.position = (self.position + 1) % self.length;
+        let window = &self.history[self.position..self.position + self.length];
+        output.extend(
+            self.phases
+                .iter()
+                .map(|(reversed, kernel)| T::dot(window, reversed, kernel)),
+        );
+    }
+}
+
+impl<T, Tap> Block for Upsampler<T, Tap>
+where
+    T: FirSample<Tap>,
+    Tap: Float,
+{
+    type Input = T;
+    type Output = T;
+
+    fn work(&mut self, input: &[T], output: &mut Vec<T>) -> usize {
+        output.reserve(input.len() * self.factor());
+        for &sample in input {
+            self.upsample(sample, output);
+        }
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::fir::Fir;
+    use approx::assert_relative_eq;
+    use num_complex::Complex;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_matches_zero_stuffing_then_filtering() {
+        let taps = lowpass(24, 0.125);
+        let input: Vec<f64> = (0..50).map(|n| (0.3 * n as f64).sin()).collect();
+        let mut upsampler = Upsampler::new(4, &taps);
+        let mut output = Vec::new();
+        upsampler.work(&input, &mut output);
+        assert_eq!(output.len(), 200);
+        let scaled: Vec<f64> = taps.iter().map(|tap| tap * 4.0).collect();
+        let mut fir = Fir::new(&scaled);
+        for (n, &actual) in output.iter().enumerate() {
+            let stuffed = if n % 4 == 0 { input[n / 4] } else { 0.0 };
+            assert_relative_eq!(actual, fir.filter(stuffed), epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_interpolated_tone_is_clean() {
+        // A tone at 0.1 of the input rate is 0.025 of the output rate, with
+        // its images suppressed.
+        let mut upsampler = Upsampler::with_lowpass(4, 16);
+        let mut output = Vec::new();
+        for n in 0..500 {
+            upsampler.upsample(
+                Complex::from_polar(1.0, 2.0 * PI * 0.1 * n as f64),
+                &mut output,
+            );
+        }
+        let tail = &output[200..];
+        for pair in tail.windows(2) {
+            let step = (pair[1] * pair[0].conj()).arg();
+            assert_relative_eq!(step, 2.0 * PI * 0.025, epsilon = 0.01);
+        }
+        for sample in tail {
+            assert_relative_eq!(sample.norm(), 1.0, epsilon = 0.02);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! The crate-wide error type.
+//!
+//! Errors are grouped by where they come from: signal processing, file and
+//! OS input/output, radio hardware, and protocol decoding. Modules keep their
+//! own specific error types, such as [`ReadingsError`] or [`AirspyError`],
+//! and each converts into its group and from there into [`SdrError`], so
+//! code that mixes several modules can use `?` throughout.
+
+use crate::df::switcher::SwitcherError;
+use crate::flowgraph::FlowgraphError;
+use crate::io::readings::ReadingsError;
+use crate::param::ParamError;
+#[cfg(feature = "satellite")]
+use crate::satellite::SatelliteError;
+use crate::scheduler::{Disconnected, SchedulerError};
+use crate::source::airspy::AirspyError;
+use crate::source::bladerf::BladeRfError;
+use crate::source::funcube::FuncubeError;
+use crate::source::kiwisdr::KiwiError;
+use crate::source::pluto::PlutoError;
+#[cfg(feature = "viz")]
+use crate::viz::VizError;
+
+/// A `Result` whose error defaults to [`SdrError`].
+pub type Result<T, E = SdrError> = std::result::Result<T, E>;
+
+/// Any error raised by this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum SdrError {
+    #[error("DSP error: {0}")]
+    Dsp(#[from] DspError),
+    #[error("I/O error: {0}")]
+    Io(#[from] IoError),
+    #[error("device error: {0}")]
+    Device(#[from] DeviceError),
+    #[error("protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+    #[error(transparent)]
+    Param(#[from] ParamError),
+    #[error(transparent)]
+    Flowgraph(#[from] FlowgraphError),
+    #[error(transparent)]
+    Scheduler(#[from] SchedulerError),
+}
+
+/// Errors from processing samples.
+#[derive(Debug, thiserror::Error)]
+pub enum DspError {
+    /// A buffer or frame had the wrong number of samples.
+    #[error("expected {expected} samples, found {found}")]
+    InvalidLength { expected: usize, found: usize },
+    /// An argument was out of range or otherwise unusable.
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[cfg(feature = "satellite")]
+    #[error(transparent)]
+    Satellite(#[from] SatelliteError),
+}
+
+/// Errors from reading or writing files and streams.
+#[derive(Debug, thiserror::Error)]
+pub enum IoError {
+    #[error(transparent)]
+    Os(#[from] std::io::Error),
+    #[error(transparent)]
+    Readings(#[from] ReadingsError),
+    #[cfg(feature = "viz")]
+    #[error(transparent)]
+    Plot(#[from] VizError),
+}
+
+/// Errors from radio hardware.
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceError {
+    #[error(transparent)]
+    Airspy(#[from] AirspyError),
+    #[error(transparent)]
+    BladeRf(#[from] BladeRfError),
+    #[error(transparent)]
+    Pluto(#[from] PlutoError),
+    #[error(transparent)]
+    Funcube(#[from] FuncubeError),
+    #[error(transparent)]
+    Kiwi(#[from] KiwiError),
+    #[error(transparent)]
+    Switcher(#[from] SwitcherError),
+    /// A driver call returned an error code.
+    #[error("{operation} failed with error {code}")]
+    Failed { operation: &'static str, code: i32 },
+    /// No matching device is attached.
+    #[error("no device found")]
+    NotFound,
+    /// The device cannot do what was asked of it.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// A setting is outside what the device allows.
+    #[error("{setting} out of range: {value}")]
+    OutOfRange { setting: &'static str, value: f64 },
+    /// A driver or graphics API call failed.
+    #[error("driver error: {0}")]
+    Driver(String),
+}
+
+/// Errors from decoding framed data.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProtocolError {
+    /// The decoder lost synchronization with the stream.
+    #[error("synchronization lost")]
+    SyncLost,
+    /// A frame's checksum did not match its contents.
+    #[error("checksum mismatch: expected {expected:#x}, found {found:#x}")]
+    Checksum { expected: u64, found: u64 },
+    /// A frame was malformed.
+    #[error("invalid frame: {0}")]
+    InvalidFrame(String),
+    /// A line code's chips broke its rules, at this bit.
+    #[error("coding violation at bit {0}")]
+    CodingViolation(usize),
+    /// A codeword had more errors than the code can correct.
+    #[error("uncorrectable errors")]
+    Uncorrectable,
+}
+
+/// Converts a module's error straight into [`SdrError`] through its group.
+macro_rules! nested_from {
+    ($($error:ty => $group:ident,)*) => {
+        $(
+            impl From<$error> for SdrError {
+                fn from(error: $error) -> Self {
+                    SdrError::$group(error.into())
+                }
+            }
+        )*
+    };
+}
+
+nested_from! {
+    std::io::Error => Io,
+    ReadingsError => Io,
+    AirspyError => Device,
+    BladeRfError => Device,
+    PlutoError => Device,
+    FuncubeError => Device,
+    KiwiError => Device,
+    SwitcherError => Device,
+    Disconnected => Scheduler,
+}
+
+/// Lets sources that cannot fail, such as test vectors and channels between
+/// branches, be used wherever a fallible one is expected.
+impl From<std::convert::Infallible> for SdrError {
+    fn from(error: std::convert::Infallible) -> Self {
+        match error {}
+    }
+}
+
+#[cfg(feature = "satellite")]
+nested_from! {
+    SatelliteError => Dsp,
+}
+
+#[cfg(feature = "viz")]
+nested_from! {
+    VizError => Io,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    fn load(text: &str) -> Result<usize> {
+        Ok(crate::io::readings::read_csv(text.as_bytes())?.len())
+    }
+
+    #[test]
+    fn test_module_errors_convert_through_their_group() {
+        let error = load("time,angle\n1,north\n").unwrap_err();
+        assert!(matches!(error, SdrError::Io(IoError::Readings(_))));
+        assert_eq!(
+            error.to_string(),
+            "I/O error: line 2: invalid angle 'north'"
+        );
+        assert!(error.source().is_some());
+
+        let error = SdrError::from(AirspyError::NotStreaming);
+        assert!(matches!(
+            error,
+            SdrError::Device(DeviceError::Airspy(AirspyError::NotStreaming))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "device error: the device is not streaming"
+        );
+
+        let error = SdrError::from(PlutoError::InvalidGain(80.0));
+        assert!(matches!(error, SdrError::Device(DeviceError::Pluto(_))));
+    }
+
+    #[test]
+    fn test_messages() {
+        let error = SdrError::from(ProtocolError::Checksum {
+            expected: 0x1d0f,
+            found: 0xbeef,
+        });
+        assert_eq!(
+            error.to_string(),
+            "protocol error: checksum mismatch: expected 0x1d0f, found 0xbeef"
+        );
+        let error = SdrError::from(crate::scheduler::Disconnected);
+        assert!(matches!(error, SdrError::Scheduler(_)));
+        assert_eq!(error.to_string(), "the receiving branch has disconnected");
+        let error = SdrError::from(ParamError::Unknown("gain".into()));
+        assert_eq!(error.to_string(), "unknown parameter gain");
+        let error = SdrError::from(DspError::InvalidLength {
+            expected: 1024,
+            found: 1000,
+        });
+        assert_eq!(
+            error.to_string(),
+            "DSP error: expected 1024 samples, found 1000"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! A high-precision reference for validating the fast averaging paths.
+//!
+//! Sums are carried in double-double arithmetic, which keeps about 106 bits
+//! of mantissa, and angles are reduced to within 45 degrees of an axis exactly
+//! before conversion to radians, so even huge or unreduced angles lose no
+//! accuracy. Only the final conversion back to `f64` rounds. This is far
+//! slower than [`crate::average`] and exists to test against, not to use in
+//! a pipeline.
+
+use std::ops::{Add, Mul};
+
+/// An unevaluated sum `hi + lo` with `|lo| <= ulp(hi) / 2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+/// π / 180 to double-double precision.
+const DEGREE: DoubleDouble = DoubleDouble {
+    hi: 0.017453292519943295,
+    lo: 2.9486522708701687e-19,
+};
+
+impl DoubleDouble {
+    const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+
+    fn from_f64(value: f64) -> Self {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    /// Sum with its exact rounding error, for any `a` and `b`.
+    fn two_sum(a: f64, b: f64) -> Self {
+        let hi = a + b;
+        let b_virtual = hi - a;
+        let lo = (a - (hi - b_virtual)) + (b - b_virtual);
+        DoubleDouble { hi, lo }
+    }
+
+    /// Renormalizes, assuming `|a| >= |b|`.
+    fn quick_two_sum(a: f64, b: f64) -> Self {
+        let hi = a + b;
+        DoubleDouble {
+            hi,
+            lo: b - (hi - a),
+        }
+    }
+
+    fn div_f64(self, divisor: f64) -> Self {
+        let first = self.hi / divisor;
+        let remainder = self + DoubleDouble::from_f64(first) * DoubleDouble::from_f64(-divisor);
+        Self::quick_two_sum(first, remainder.hi / divisor)
+    }
+
+    /// Sine and cosine for `|self| <= π/4` by Taylor series.
+    fn sin_cos(self) -> (Self, Self) {
+        let square = self * self;
+        let (mut sin, mut cos) = (self, DoubleDouble::from_f64(1.0));
+        let (mut sin_term, mut cos_term) = (self, DoubleDouble::from_f64(1.0));
+        let mut n = 1.0;
+        while cos_term.hi.abs() > 1e-36 || sin_term.hi.abs() > 1e-36 {
+            cos_term = (cos_term * square).div_f64(-(n * (n + 1.0)));
+            cos = cos + cos_term;
+            sin_term = (sin_term * square).div_f64(-((n + 1.0) * (n + 2.0)));
+            sin = sin + sin_term;
+            n += 2.0;
+        }
+        (sin, cos)
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let sum = Self::two_sum(self.hi, other.hi);
+        let errors = Self::two_sum(self.lo, other.lo);
+        let hi = Self::quick_two_sum(sum.hi, sum.lo + errors.hi);
+        Self::quick_two_sum(hi.hi, hi.lo + errors.lo)
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let hi = self.hi * other.hi;
+        let lo = self.hi.mul_add(other.hi, -hi);
+        Self::quick_two_sum(hi, lo + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+/// Sine and cosine of `degrees` to double-double precision.
+fn sin_cos_degrees(degrees: f64) -> (DoubleDouble, DoubleDouble) {
+    // Both steps are exact: the remainder and the distance to the nearest
+    // multiple of 90 are representable in the input's precision.
+    let reduced = degrees.rem_euclid(360.0);
+    let quadrant = (reduced / 90.0).round();
+    let offset = reduced - 90.0 * quadrant;
+    let (sin, cos) = (DoubleDouble::from_f64(offset) * DEGREE).sin_cos();
+    let negate = |x: DoubleDouble| DoubleDouble {
+        hi: -x.hi,
+        lo: -x.lo,
+    };
+    match quadrant as i64 % 4 {
+        0 => (sin, cos),
+        1 => (cos, negate(sin)),
+        2 => (negate(sin), negate(cos)),
+        _ => (negate(cos), sin),
+    }
+}
+
+/// The average of `(angle, magnitude)` readings, with the same meaning as
+/// [`crate::average`], correct to within an ulp or two of the exact result.
+pub fn average_exact(readings: &[(f64, f64)]) -> (f64, f64) {
+    let (mut x, mut y) = (DoubleDouble::ZERO, DoubleDouble::ZERO);
+    for &(angle, magnitude) in readings {
+        let (sin, cos) = sin_cos_degrees(angle);
+        let magnitude = DoubleDouble::from_f64(magnitude);
+        x = x + cos * magnitude;
+        y = y + sin * magnitude;
+    }
+    let count = readings.len() as f64;
+    let (x, y) = (x.div_f64(count).to_f64(), y.div_f64(count).to_f64());
+    (y.atan2(x).to_degrees(), x.hypot(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{average, average_with_trig};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_agrees_with_fast_paths() {
+        let readings = [(10.0, 1.0), (20.0, 2.0), (350.0, 0.5), (-170.0, 0.1)];
+        let (angle, magnitude) = average_exact(&readings);
+        let (fast_angle, fast_magnitude) = average(&readings);
+        assert_relative_eq!(angle, fast_angle, epsilon = 1e-12);
+        assert_relative_eq!(magnitude, fast_magnitude, epsilon = 1e-12);
+        assert_relative_eq!(angle, average_with_trig(&readings).0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_exact_at_axes_and_for_unreduced_angles() {
+        let (sin, cos) = sin_cos_degrees(90.0);
+        assert_eq!((sin.to_f64(), cos.to_f64()), (1.0, 0.0));
+        let (sin, _) = sin_cos_degrees(30.0 + 360.0 * 1e9);
+        assert_relative_eq!(sin.to_f64(), 0.5, epsilon = 1e-16);
+        let (angle, magnitude) = average_exact(&[(-45.0 + 360.0 * 1e6, 2.0)]);
+        assert_eq!(angle, -45.0);
+        assert_relative_eq!(magnitude, 2.0, epsilon = 1e-15);
+    }
+
+    #[test]
+    fn test_cancellation_keeps_small_residue() {
+        // Large opposing readings leave a tiny resultant that a naive f64 sum
+        // would swamp with rounding error.
+        let mut readings = vec![(0.0, 1e16), (180.0, 1e16)];
+        readings.push((90.0, 3.0));
+        let (angle, magnitude) = average_exact(&readings);
+        assert_eq!(angle, 90.0);
+        assert_relative_eq!(magnitude, 1.0, epsilon = 1e-15);
+    }
+}
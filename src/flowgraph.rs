@@ -0,0 +1,596 @@
+//! Connects sources, blocks and sinks and moves samples between them.
+//!
+//! Every connection is a typed [`Port`]. A port can feed any number of
+//! downstream blocks; each one gets its own buffer and sees every sample.
+//!
+//! Parameters of a running graph are changed through a [`Controller`], which
+//! addresses nodes by the [`NodeId`] returned when they were added. Changes
+//! are applied between steps, so a block never sees one mid-`work`.
+
+use crate::block::Block;
+use crate::param::{ParamError, ParamValue};
+use crate::sink::Sink;
+use crate::source::Source;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Errors raised while running a flowgraph.
+#[derive(Debug, thiserror::Error)]
+pub enum FlowgraphError {
+    /// A source failed to produce samples.
+    #[error("source failed: {0}")]
+    Source(#[source] Box<dyn Error + Send + Sync>),
+    /// A sink failed to accept samples.
+    #[error("sink failed: {0}")]
+    Sink(#[source] Box<dyn Error + Send + Sync>),
+}
+
+/// Samples waiting between two nodes.
+struct Edge<T> {
+    samples: VecDeque<T>,
+    closed: bool,
+}
+
+type SharedEdge<T> = Arc<Mutex<Edge<T>>>;
+
+/// Identifies a node within the flowgraph that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// The output port of a source or block, used to connect downstream nodes.
+pub struct Port<T> {
+    node: NodeId,
+    consumers: Arc<Mutex<Vec<SharedEdge<T>>>>,
+}
+
+impl<T> Clone for Port<T> {
+    fn clone(&self) -> Self {
+        Port {
+            node: self.node,
+            consumers: Arc::clone(&self.consumers),
+        }
+    }
+}
+
+impl<T> Port<T> {
+    fn new(node: NodeId) -> Self {
+        Port {
+            node,
+            consumers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The source or block that writes to this port.
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    fn subscribe(&self) -> SharedEdge<T> {
+        let edge = Arc::new(Mutex::new(Edge {
+            samples: VecDeque::new(),
+            closed: false,
+        }));
+        self.consumers.lock().unwrap().push(Arc::clone(&edge));
+        edge
+    }
+}
+
+impl<T: Clone> Port<T> {
+    fn publish(&self, samples: &mut Vec<T>) {
+        let consumers = self.consumers.lock().unwrap();
+        if let Some((last, others)) = consumers.split_last() {
+            for edge in others {
+                edge.lock().unwrap().samples.extend(samples.iter().cloned());
+            }
+            last.lock().unwrap().samples.extend(samples.drain(..));
+        } else {
+            samples.clear();
+        }
+    }
+
+    fn close(&self) {
+        for edge in self.consumers.lock().unwrap().iter() {
+            edge.lock().unwrap().closed = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    Worked,
+    Idle,
+    Done,
+}
+
+trait Node: Send {
+    fn step(&mut self) -> Result<Progress, FlowgraphError>;
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError>;
+}
+
+struct ParamMessage {
+    node: NodeId,
+    name: String,
+    value: ParamValue,
+    reply: Sender<Result<(), ParamError>>,
+}
+
+/// Sends parameter changes to a flowgraph, from any thread.
+#[derive(Clone)]
+pub struct Controller {
+    sender: Sender<ParamMessage>,
+}
+
+impl Controller {
+    /// Queues a change to parameter `name` of `node`. It is applied before the
+    /// flowgraph's next step.
+    pub fn set(&self, node: NodeId, name: &str, value: impl Into<ParamValue>) -> ParamUpdate {
+        let (reply, result) = mpsc::channel();
+        let message = ParamMessage {
+            node,
+            name: name.to_string(),
+            value: value.into(),
+            reply,
+        };
+        // If the flowgraph is gone the reply sender is dropped with the
+        // message, and waiting on the update reports the disconnection.
+        let _ = self.sender.send(message);
+        ParamUpdate { result }
+    }
+}
+
+/// The outcome of a parameter change queued with [`Controller::set`].
+pub struct ParamUpdate {
+    result: Receiver<Result<(), ParamError>>,
+}
+
+impl ParamUpdate {
+    /// Blocks until the flowgraph has applied or rejected the change.
+    pub fn wait(self) -> Result<(), ParamError> {
+        self.result.recv().unwrap_or(Err(ParamError::Disconnected))
+    }
+
+    /// The outcome if the change has been handled already.
+    pub fn try_result(&self) -> Option<Result<(), ParamError>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(ParamError::Disconnected)),
+        }
+    }
+}
+
+struct SourceNode<S: Source> {
+    source: S,
+    buffer: Vec<S::Sample>,
+    output: Port<S::Sample>,
+    scratch: Vec<S::Sample>,
+    samples: u64,
+}
+
+impl<S> Node for SourceNode<S>
+where
+    S: Source + Send,
+    S::Sample: Clone + Send,
+    S::Error: Error + Send + Sync + 'static,
+{
+    fn step(&mut self) -> Result<Progress, FlowgraphError> {
+        let count = self.source.read(&mut self.buffer).map_err(|error| {
+            trace_event!(error, node = self.output.node().0, %error, "source failed");
+            FlowgraphError::Source(Box::new(error))
+        })?;
+        if count == 0 {
+            trace_event!(
+                debug,
+                node = self.output.node().0,
+                samples = self.samples,
+                "source finished"
+            );
+            self.output.close();
+            return Ok(Progress::Done);
+        }
+        self.samples += count as u64;
+        self.scratch.extend_from_slice(&self.buffer[..count]);
+        self.output.publish(&mut self.scratch);
+        Ok(Progress::Worked)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        self.source.set_parameter(name, value)
+    }
+}
+
+/// Takes everything waiting on `edge`, returning the samples and whether the
+/// upstream node has finished.
+fn take_input<T>(edge: &SharedEdge<T>, pending: &mut Vec<T>) -> bool {
+    let mut edge = edge.lock().unwrap();
+    pending.extend(edge.samples.drain(..));
+    edge.closed
+}
+
+struct BlockNode<B: Block> {
+    block: B,
+    input: SharedEdge<B::Input>,
+    pending: Vec<B::Input>,
+    output: Port<B::Output>,
+    produced: Vec<B::Output>,
+    consumed: u64,
+}
+
+impl<B> Node for BlockNode<B>
+where
+    B: Block + Send,
+    B::Input: Send,
+    B::Output: Clone + Send,
+{
+    fn step(&mut self) -> Result<Progress, FlowgraphError> {
+        let closed = take_input(&self.input, &mut self.pending);
+        let consumed = if self.pending.is_empty() {
+            0
+        } else {
+            self.block.work(&self.pending, &mut self.produced)
+        };
+        self.pending.drain(..consumed.min(self.pending.len()));
+        self.consumed += consumed as u64;
+        let produced = !self.produced.is_empty();
+        self.output.publish(&mut self.produced);
+        if consumed > 0 || produced {
+            Ok(Progress::Worked)
+        } else if closed {
+            trace_event!(
+                debug,
+                node = self.output.node().0,
+                samples = self.consumed,
+                unconsumed = self.pending.len(),
+                "block finished"
+            );
+            self.output.close();
+            Ok(Progress::Done)
+        } else {
+            Ok(Progress::Idle)
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        self.block.set_parameter(name, value)
+    }
+}
+
+struct SinkNode<K: Sink> {
+    sink: K,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    node: NodeId,
+    input: SharedEdge<K::Input>,
+    pending: Vec<K::Input>,
+    samples: u64,
+}
+
+impl<K> Node for SinkNode<K>
+where
+    K: Sink + Send,
+    K::Input: Send,
+    K::Error: Error + Send + Sync + 'static,
+{
+    fn step(&mut self) -> Result<Progress, FlowgraphError> {
+        let closed = take_input(&self.input, &mut self.pending);
+        if !self.pending.is_empty() {
+            self.sink.write(&self.pending).map_err(|error| {
+                trace_event!(error, node = self.node.0, %error, "sink failed");
+                FlowgraphError::Sink(Box::new(error))
+            })?;
+            self.samples += self.pending.len() as u64;
+            self.pending.clear();
+            Ok(Progress::Worked)
+        } else if closed {
+            trace_event!(
+                debug,
+                node = self.node.0,
+                samples = self.samples,
+                "sink finished"
+            );
+            Ok(Progress::Done)
+        } else {
+            Ok(Progress::Idle)
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        self.sink.set_parameter(name, value)
+    }
+}
+
+/// A graph of sources, blocks and sinks run on the calling thread.
+pub struct Flowgraph {
+    nodes: Vec<Box<dyn Node>>,
+    chunk_size: usize,
+    messages: Receiver<ParamMessage>,
+    controller: Controller,
+}
+
+impl Flowgraph {
+    /// Samples read from each source per step unless configured otherwise.
+    pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+    pub fn new() -> Self {
+        Self::with_chunk_size(Self::DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a flowgraph whose sources are read `chunk_size` samples at a time.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        let (sender, messages) = mpsc::channel();
+        Flowgraph {
+            nodes: Vec::new(),
+            chunk_size: chunk_size.max(1),
+            messages,
+            controller: Controller { sender },
+        }
+    }
+
+    /// A handle for changing node parameters while the graph runs.
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    fn next_id(&self) -> NodeId {
+        NodeId(self.nodes.len())
+    }
+
+    /// Applies every queued parameter change.
+    fn apply_messages(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            let result = match self.nodes.get_mut(message.node.0) {
+                Some(node) => node.set_parameter(&message.name, &message.value),
+                None => Err(ParamError::UnknownNode),
+            };
+            let _ = message.reply.send(result);
+        }
+    }
+
+    pub fn add_source<S>(&mut self, source: S) -> Port<S::Sample>
+    where
+        S: Source + Send + 'static,
+        S::Sample: Clone + Default + Send + 'static,
+        S::Error: Error + Send + Sync + 'static,
+    {
+        let output = Port::new(self.next_id());
+        self.nodes.push(Box::new(SourceNode {
+            source,
+            buffer: vec![S::Sample::default(); self.chunk_size],
+            output: output.clone(),
+            scratch: Vec::with_capacity(self.chunk_size),
+            samples: 0,
+        }));
+        output
+    }
+
+    /// Adds `block`, fed from `input`, and returns its output port.
+    pub fn add_block<B>(&mut self, block: B, input: &Port<B::Input>) -> Port<B::Output>
+    where
+        B: Block + Send + 'static,
+        B::Input: Send + 'static,
+        B::Output: Clone + Send + 'static,
+    {
+        let output = Port::new(self.next_id());
+        self.nodes.push(Box::new(BlockNode {
+            block,
+            input: input.subscribe(),
+            pending: Vec::new(),
+            output: output.clone(),
+            produced: Vec::new(),
+            consumed: 0,
+        }));
+        output
+    }
+
+    pub fn add_sink<K>(&mut self, sink: K, input: &Port<K::Input>) -> NodeId
+    where
+        K: Sink + Send + 'static,
+        K::Input: Send + 'static,
+        K::Error: Error + Send + Sync + 'static,
+    {
+        let id = self.next_id();
+        self.nodes.push(Box::new(SinkNode {
+            sink,
+            node: id,
+            input: input.subscribe(),
+            pending: Vec::new(),
+            samples: 0,
+        }));
+        id
+    }
+
+    /// Runs until every source is exhausted and all samples have drained
+    /// through to the sinks.
+    pub fn run(&mut self) -> Result<(), FlowgraphError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("flowgraph", nodes = self.nodes.len()).entered();
+        let mut done = vec![false; self.nodes.len()];
+        loop {
+            self.apply_messages();
+            let mut progressed = false;
+            for (node, done) in self.nodes.iter_mut().zip(done.iter_mut()) {
+                if *done {
+                    continue;
+                }
+                match node.step()? {
+                    Progress::Worked => progressed = true,
+                    Progress::Idle => {}
+                    Progress::Done => {
+                        *done = true;
+                        progressed = true;
+                    }
+                }
+            }
+            // Also stops a graph without sources, whose blocks would wait forever.
+            if !progressed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Default for Flowgraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::map;
+    use std::convert::Infallible;
+
+    struct VecSource(VecDeque<i32>);
+
+    impl Source for VecSource {
+        type Sample = i32;
+        type Error = Infallible;
+
+        fn read(&mut self, buffer: &mut [i32]) -> Result<usize, Infallible> {
+            let count = buffer.len().min(self.0.len());
+            for (slot, sample) in buffer.iter_mut().zip(self.0.drain(..count)) {
+                *slot = sample;
+            }
+            Ok(count)
+        }
+    }
+
+    struct SharedSink(Arc<Mutex<Vec<i32>>>);
+
+    impl Sink for SharedSink {
+        type Input = i32;
+        type Error = Infallible;
+
+        fn write(&mut self, input: &[i32]) -> Result<(), Infallible> {
+            self.0.lock().unwrap().extend_from_slice(input);
+            Ok(())
+        }
+    }
+
+    /// Sums consecutive pairs, leaving an odd sample for the next call.
+    struct PairSum;
+
+    impl Block for PairSum {
+        type Input = i32;
+        type Output = i32;
+
+        fn work(&mut self, input: &[i32], output: &mut Vec<i32>) -> usize {
+            output.extend(input.chunks_exact(2).map(|pair| pair[0] + pair[1]));
+            input.len() / 2 * 2
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let mut flowgraph = Flowgraph::with_chunk_size(3);
+        let samples = flowgraph.add_source(VecSource((1..=8).collect()));
+        let sums = flowgraph.add_block(PairSum, &samples);
+        let scaled = flowgraph.add_block(map(|x: &i32| x * 10), &sums);
+        flowgraph.add_sink(SharedSink(Arc::clone(&collected)), &scaled);
+        flowgraph.run().unwrap();
+        assert_eq!(*collected.lock().unwrap(), vec![30, 70, 110, 150]);
+    }
+
+    struct Gain(i32);
+
+    impl Block for Gain {
+        type Input = i32;
+        type Output = i32;
+
+        fn work(&mut self, input: &[i32], output: &mut Vec<i32>) -> usize {
+            output.extend(input.iter().map(|x| x * self.0));
+            input.len()
+        }
+
+        fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+            match (name, value) {
+                ("gain", ParamValue::Int(gain)) => {
+                    self.0 = *gain as i32;
+                    Ok(())
+                }
+                ("gain", _) => Err(ParamError::invalid(name, value)),
+                _ => Err(ParamError::Unknown(name.to_string())),
+            }
+        }
+    }
+
+    /// Changes the gain of a downstream block after its first read.
+    struct Retuning {
+        samples: VecSource,
+        controller: Controller,
+        target: Arc<Mutex<Option<NodeId>>>,
+        updates: Vec<ParamUpdate>,
+    }
+
+    impl Source for Retuning {
+        type Sample = i32;
+        type Error = Infallible;
+
+        fn read(&mut self, buffer: &mut [i32]) -> Result<usize, Infallible> {
+            if let Some(node) = self.target.lock().unwrap().take() {
+                self.updates.push(self.controller.set(node, "gain", 10i64));
+            }
+            self.samples.read(buffer)
+        }
+    }
+
+    #[test]
+    fn test_parameter_change_while_running() {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let target = Arc::new(Mutex::new(None));
+        let mut flowgraph = Flowgraph::with_chunk_size(2);
+        let samples = flowgraph.add_source(Retuning {
+            samples: VecSource((1..=4).collect()),
+            controller: flowgraph.controller(),
+            target: Arc::clone(&target),
+            updates: Vec::new(),
+        });
+        let scaled = flowgraph.add_block(Gain(1), &samples);
+        flowgraph.add_sink(SharedSink(Arc::clone(&collected)), &scaled);
+        *target.lock().unwrap() = Some(scaled.node());
+        flowgraph.run().unwrap();
+        // The change lands after the first chunk has been read.
+        assert_eq!(*collected.lock().unwrap(), vec![1, 2, 30, 40]);
+    }
+
+    #[test]
+    fn test_rejected_parameter_changes() {
+        let mut flowgraph = Flowgraph::new();
+        let samples = flowgraph.add_source(VecSource((1..=4).collect()));
+        let scaled = flowgraph.add_block(Gain(1), &samples);
+        let controller = flowgraph.controller();
+        let unknown = controller.set(scaled.node(), "cutoff", 1.0);
+        let invalid = controller.set(scaled.node(), "gain", true);
+        let source = controller.set(samples.node(), "gain", 2i64);
+        flowgraph.run().unwrap();
+        assert_eq!(unknown.wait(), Err(ParamError::Unknown("cutoff".into())));
+        assert!(matches!(
+            invalid.wait(),
+            Err(ParamError::InvalidValue { .. })
+        ));
+        assert!(matches!(source.wait(), Err(ParamError::Unknown(_))));
+        drop(flowgraph);
+        assert_eq!(
+            controller.set(scaled.node(), "gain", 2i64).wait(),
+            Err(ParamError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let mut flowgraph = Flowgraph::new();
+        let samples = flowgraph.add_source(VecSource((1..=4).collect()));
+        flowgraph.add_sink(SharedSink(Arc::clone(&first)), &samples);
+        let negated = flowgraph.add_block(map(|x: &i32| -x), &samples);
+        flowgraph.add_sink(SharedSink(Arc::clone(&second)), &negated);
+        flowgraph.run().unwrap();
+        assert_eq!(*first.lock().unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(*second.lock().unwrap(), vec![-1, -2, -3, -4]);
+    }
+}
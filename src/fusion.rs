@@ -0,0 +1,217 @@
+//! Merging the readings of several direction-finding receivers.
+//!
+//! Each receiver is registered with a [`SensorConfig`] giving its bearing
+//! offset, how far it is trusted and its priority. [`Fusion::push`] corrects
+//! each incoming reading, and [`Fusion::fused`] averages the recent readings
+//! into one bearing. Every receiver first averages its own readings, so one
+//! reporting ten times a second does not outvote one reporting once.
+
+use crate::average_fast;
+use crate::reading::Reading;
+use std::collections::VecDeque;
+
+/// How a receiver's readings are corrected and weighed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorConfig {
+    /// Degrees added to every bearing, such as an antenna mounting error.
+    pub offset: f64,
+    /// Multiplies each reading's quality, from 0 (ignored) to 1.
+    pub reliability: f64,
+    /// Only the receivers of the highest priority with recent readings are
+    /// fused; lower priorities are a fallback for when they fall silent.
+    pub priority: i32,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        SensorConfig {
+            offset: 0.0,
+            reliability: 1.0,
+            priority: 0,
+        }
+    }
+}
+
+/// Identifies a receiver registered with [`Fusion::add_sensor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SensorId(usize);
+
+#[derive(Debug, Clone)]
+struct Sensor {
+    config: SensorConfig,
+    /// Corrected readings, oldest first.
+    readings: VecDeque<Reading>,
+}
+
+/// Fuses the readings of several receivers over a sliding time window.
+#[derive(Debug, Clone)]
+pub struct Fusion {
+    span: f64,
+    sensors: Vec<Sensor>,
+}
+
+impl Fusion {
+    /// Fuses readings no more than `span` seconds old.
+    pub fn new(span: f64) -> Self {
+        Fusion {
+            span,
+            sensors: Vec::new(),
+        }
+    }
+
+    pub fn add_sensor(&mut self, config: SensorConfig) -> SensorId {
+        self.sensors.push(Sensor {
+            config,
+            readings: VecDeque::new(),
+        });
+        SensorId(self.sensors.len() - 1)
+    }
+
+    pub fn config(&self, sensor: SensorId) -> &SensorConfig {
+        &self.sensors[sensor.0].config
+    }
+
+    /// Changes a receiver's settings; readings already pushed keep the
+    /// corrections they were given.
+    pub fn set_config(&mut self, sensor: SensorId, config: SensorConfig) {
+        self.sensors[sensor.0].config = config;
+    }
+
+    /// Corrects `reading` for `sensor` and adds it to the window. The
+    /// corrected reading is returned, for passing on as one merged stream.
+    /// Each receiver's readings are expected in time order.
+    pub fn push(&mut self, sensor: SensorId, reading: impl Into<Reading>) -> Reading {
+        let span = self.span;
+        let sensor = &mut self.sensors[sensor.0];
+        let reading = reading.into();
+        let corrected = Reading {
+            angle: (reading.angle + sensor.config.offset).rem_euclid(360.0),
+            quality: reading.quality * sensor.config.reliability,
+            ..reading
+        };
+        sensor.readings.push_back(corrected);
+        while let Some(oldest) = sensor.readings.front() {
+            if corrected.timestamp - oldest.timestamp <= span {
+                break;
+            }
+            sensor.readings.pop_front();
+        }
+        corrected
+    }
+
+    /// The fused bearing at `timestamp` from readings taken in the
+    /// preceding `span` seconds, or `None` if there are none with any
+    /// weight.
+    ///
+    /// Each receiver's readings are averaged, weighted by magnitude and
+    /// quality, and the receivers' averages are then averaged in turn. The
+    /// result's magnitude is that of the final average, and its quality 1.
+    pub fn fused(&self, timestamp: f64) -> Option<Reading> {
+        let recent = |sensor: &Sensor| -> Vec<(f64, f64)> {
+            sensor
+                .readings
+                .iter()
+                .filter(|reading| {
+                    reading.timestamp <= timestamp && timestamp - reading.timestamp <= self.span
+                })
+                .map(Reading::as_pair)
+                .collect()
+        };
+        let active: Vec<(i32, Vec<(f64, f64)>)> = self
+            .sensors
+            .iter()
+            .map(|sensor| (sensor.config.priority, recent(sensor)))
+            // A receiver whose recent readings all carry zero weight, as
+            // with a reliability of 0, has nothing to say and must not hide
+            // a fallback or dilute its peers.
+            .filter(|(_, readings)| readings.iter().map(|&(_, weight)| weight).sum::<f64>() > 0.0)
+            .collect();
+        let priority = active.iter().map(|&(priority, _)| priority).max()?;
+        let means: Vec<(f64, f64)> = active
+            .iter()
+            .filter(|&&(sensor_priority, _)| sensor_priority == priority)
+            .map(|(_, readings)| average_fast(readings))
+            .collect();
+        let (angle, magnitude) = average_fast(&means);
+        Some(Reading::new(timestamp, angle.rem_euclid(360.0), magnitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_fast_sensor_does_not_outvote_slow_one() {
+        let mut fusion = Fusion::new(5.0);
+        let fast = fusion.add_sensor(SensorConfig {
+            offset: -2.0,
+            ..SensorConfig::default()
+        });
+        let slow = fusion.add_sensor(SensorConfig::default());
+        let fallback = fusion.add_sensor(SensorConfig {
+            priority: -1,
+            ..SensorConfig::default()
+        });
+        assert_eq!(fusion.fused(0.0), None);
+        for n in 0..100 {
+            let corrected = fusion.push(fast, (n as f64 * 0.1, 102.0, 1.0));
+            assert_relative_eq!(corrected.angle, 100.0);
+        }
+        for n in 0..10 {
+            fusion.push(slow, (n as f64, 110.0, 1.0));
+            fusion.push(fallback, (n as f64, 200.0, 1.0));
+        }
+        let fused = fusion.fused(9.9).unwrap();
+        assert_relative_eq!(fused.angle, 105.0, epsilon = 1e-9);
+
+        // Halving the slow receiver's reliability halves its pull.
+        fusion.set_config(
+            slow,
+            SensorConfig {
+                reliability: 0.5,
+                ..SensorConfig::default()
+            },
+        );
+        fusion.push(slow, Reading::new(10.0, 110.0, 1.0));
+        let fused = fusion.fused(10.0).unwrap();
+        assert!(fused.angle > 100.0 && fused.angle < 105.0, "{fused:?}");
+
+        // Once the preferred receivers fall silent, the fallback takes over.
+        fusion.push(fallback, (30.0, 200.0, 1.0));
+        assert_relative_eq!(fusion.fused(30.0).unwrap().angle, 200.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_zero_weight_sensor_is_ignored() {
+        let mut fusion = Fusion::new(5.0);
+        let muted = fusion.add_sensor(SensorConfig {
+            reliability: 0.0,
+            priority: 1,
+            ..SensorConfig::default()
+        });
+        let fallback = fusion.add_sensor(SensorConfig::default());
+        fusion.push(muted, (0.0, 10.0, 1.0));
+        assert_eq!(fusion.fused(0.0), None);
+        fusion.push(fallback, (0.0, 90.0, 1.0));
+        let fused = fusion.fused(0.0).unwrap();
+        assert_relative_eq!(fused.angle, 90.0, epsilon = 1e-9);
+        assert_relative_eq!(fused.magnitude, 1.0, epsilon = 1e-9);
+
+        // At the same priority it is not averaged in either.
+        fusion.set_config(
+            muted,
+            SensorConfig {
+                reliability: 0.0,
+                ..SensorConfig::default()
+            },
+        );
+        fusion.push(muted, (1.0, 10.0, 1.0));
+        fusion.push(fallback, (1.0, 90.0, 1.0));
+        let fused = fusion.fused(1.0).unwrap();
+        assert_relative_eq!(fused.angle, 90.0, epsilon = 1e-9);
+        assert_relative_eq!(fused.magnitude, 1.0, epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,407 @@
+//! Batch processing on compute shaders through wgpu.
+//!
+//! Meant for survey-scale offline jobs: averaging millions of bearing sets,
+//! filtering long captures and running large batches of FFTs. GPUs work in
+//! single precision, so the results agree with [`crate::average_f32`] and
+//! the f32 [`Fir`](crate::dsp::fir::Fir) to within rounding, not with the
+//! f64 paths. Inputs larger than the device's buffer limits are split across
+//! several dispatches.
+//!
+//! Uploading and reading back cost more than the arithmetic for small jobs;
+//! below a few hundred thousand samples the CPU is usually faster.
+
+use crate::error::{DeviceError, DspError, Result};
+use num_complex::Complex;
+use std::sync::mpsc;
+use wgpu::util::DeviceExt;
+
+/// Invocations per workgroup. Must match `@workgroup_size` in the shaders.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Workgroups in one dispatch dimension guaranteed by every adapter.
+const MAX_WORKGROUPS: u32 = 65_535;
+
+/// An open GPU with the crate's compute pipelines compiled.
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    name: String,
+    average: wgpu::ComputePipeline,
+    fir: wgpu::ComputePipeline,
+    fft: wgpu::ComputePipeline,
+    /// The largest storage buffer binding, in bytes.
+    max_binding: u64,
+}
+
+impl Gpu {
+    /// Opens the highest-performance adapter available. Fails with
+    /// [`DeviceError::NotFound`] if there is none.
+    pub fn new() -> Result<Self> {
+        pollster::block_on(Self::open())
+    }
+
+    async fn open() -> Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .map_err(|_| DeviceError::NotFound)?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("sdr-rust"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|error| DeviceError::Driver(error.to_string()))?;
+        let pipeline = |label: &str, source: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let limits = device.limits();
+        Ok(Gpu {
+            average: pipeline("average", include_str!("gpu/average.wgsl")),
+            fir: pipeline("fir", include_str!("gpu/fir.wgsl")),
+            fft: pipeline("fft", include_str!("gpu/fft.wgsl")),
+            name: adapter.get_info().name,
+            max_binding: limits
+                .max_storage_buffer_binding_size
+                .min(limits.max_buffer_size),
+            device,
+            queue,
+        })
+    }
+
+    /// The adapter's name, as reported by the driver.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Averages consecutive sets of `set_length` (angle, magnitude) readings,
+    /// returning one (angle, magnitude) per set as [`crate::average`] would.
+    pub fn average_sets(
+        &self,
+        readings: &[(f32, f32)],
+        set_length: usize,
+    ) -> Result<Vec<(f32, f32)>> {
+        if set_length == 0 || !readings.len().is_multiple_of(set_length) {
+            return Err(DspError::InvalidArgument(format!(
+                "{} readings do not divide into sets of {set_length}",
+                readings.len()
+            ))
+            .into());
+        }
+        let per_chunk = self.elements_per_binding(set_length * 8)?;
+        let mut averages = Vec::with_capacity(readings.len() / set_length);
+        for chunk in readings.chunks(per_chunk * set_length) {
+            let sets = chunk.len() / set_length;
+            let input: Vec<[f32; 2]> = chunk
+                .iter()
+                .map(|&(angle, magnitude)| [angle, magnitude])
+                .collect();
+            let input = self.storage(bytemuck::cast_slice(&input));
+            let output = self.output(sets as u64 * 8);
+            let params = self.uniform([sets as u32, set_length as u32, 0, 0]);
+            let bind_group = self.bind_group(&self.average, &[&input, &output, &params]);
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                dispatch(&mut pass, &self.average, &bind_group, sets as u32);
+            }
+            let result: Vec<[f32; 2]> = self.read_back(encoder, &output)?;
+            averages.extend(
+                result
+                    .into_iter()
+                    .map(|[angle, magnitude]| (angle, magnitude)),
+            );
+        }
+        Ok(averages)
+    }
+
+    /// Filters `samples` with `taps`, from zero history, giving the same
+    /// output as feeding them one at a time through a fresh f32 FIR.
+    pub fn fir(&self, samples: &[Complex<f32>], taps: &[f32]) -> Result<Vec<Complex<f32>>> {
+        let taps = if taps.is_empty() { &[1.0][..] } else { taps };
+        let history = taps.len() - 1;
+        let per_chunk = self
+            .elements_per_binding(8)?
+            .checked_sub(history)
+            .filter(|&outputs| outputs > 0)
+            .ok_or_else(|| DspError::InvalidArgument("too many taps for the device".into()))?;
+        let tap_buffer = self.storage(bytemuck::cast_slice(taps));
+        let mut filtered = Vec::with_capacity(samples.len());
+        for start in (0..samples.len()).step_by(per_chunk) {
+            let end = (start + per_chunk).min(samples.len());
+            let mut input = vec![Complex::new(0.0, 0.0); history.saturating_sub(start)];
+            input.extend_from_slice(&samples[start.saturating_sub(history)..end]);
+            let outputs = end - start;
+            let input = self.storage(bytemuck::cast_slice(&input));
+            let output = self.output(outputs as u64 * 8);
+            let params = self.uniform([outputs as u32, taps.len() as u32, 0, 0]);
+            let bind_group = self.bind_group(&self.fir, &[&input, &tap_buffer, &output, &params]);
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                dispatch(&mut pass, &self.fir, &bind_group, outputs as u32);
+            }
+            filtered.extend(self.read_back::<Complex<f32>>(encoder, &output)?);
+        }
+        Ok(filtered)
+    }
+
+    /// Transforms `data` in place with forward, unnormalized FFTs of `size`
+    /// points each, as [`crate::spectrum::fft`] would one at a time. `size`
+    /// must be a power of two dividing the length of `data`.
+    pub fn fft_batch(&self, data: &mut [Complex<f32>], size: usize) -> Result<()> {
+        if !size.is_power_of_two() || !data.len().is_multiple_of(size) {
+            return Err(DspError::InvalidArgument(format!(
+                "{} samples do not divide into power-of-two transforms of {size}",
+                data.len()
+            ))
+            .into());
+        }
+        if size == 1 {
+            return Ok(());
+        }
+        let per_chunk = self.elements_per_binding(size * 8)?;
+        let stages = size.trailing_zeros();
+        for chunk in data.chunks_mut(per_chunk * size) {
+            let butterflies = (chunk.len() / 2) as u32;
+            let bytes = chunk.len() as u64 * 8;
+            let buffers = [
+                self.storage(bytemuck::cast_slice(chunk)),
+                self.output(bytes),
+            ];
+            let bind_groups: Vec<wgpu::BindGroup> = (0..stages)
+                .map(|stage| {
+                    let params = self.uniform([size as u32, 1 << stage, butterflies, 0]);
+                    let (source, destination) = if stage % 2 == 0 { (0, 1) } else { (1, 0) };
+                    self.bind_group(
+                        &self.fft,
+                        &[&buffers[source], &buffers[destination], &params],
+                    )
+                })
+                .collect();
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                // Dispatches in one pass are ordered, so each stage sees the last.
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                for bind_group in &bind_groups {
+                    dispatch(&mut pass, &self.fft, bind_group, butterflies);
+                }
+            }
+            let result = &buffers[stages as usize % 2];
+            chunk.copy_from_slice(&self.read_back::<Complex<f32>>(encoder, result)?);
+        }
+        Ok(())
+    }
+
+    /// Transforms `data` in place with one FFT over its whole length, which
+    /// must be a power of two.
+    pub fn fft(&self, data: &mut [Complex<f32>]) -> Result<()> {
+        self.fft_batch(data, data.len().max(1))
+    }
+
+    /// How many items of `item_bytes` fit in one storage binding.
+    fn elements_per_binding(&self, item_bytes: usize) -> Result<usize> {
+        let limit = (self.max_binding / item_bytes as u64)
+            .min(u64::from(MAX_WORKGROUPS) * u64::from(MAX_WORKGROUPS));
+        match limit {
+            0 => Err(DspError::InvalidArgument(format!(
+                "{item_bytes} bytes do not fit in one device buffer"
+            ))
+            .into()),
+            limit => Ok(limit as usize),
+        }
+    }
+
+    fn storage(&self, contents: &[u8]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            })
+    }
+
+    fn output(&self, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn uniform(&self, params: [u32; 4]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+    }
+
+    fn bind_group(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+    ) -> wgpu::BindGroup {
+        let entries: Vec<wgpu::BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        })
+    }
+
+    /// Submits `encoder` with a copy of `buffer` appended, and waits for it.
+    fn read_back<T: bytemuck::Pod>(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+    ) -> Result<Vec<T>> {
+        let driver = |error: &dyn std::fmt::Display| DeviceError::Driver(error.to_string());
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        self.queue.submit([encoder.finish()]);
+        let slice = staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|error| driver(&error))?;
+        receiver
+            .recv()
+            .map_err(|error| driver(&error))?
+            .map_err(|error| driver(&error))?;
+        let data = bytemuck::cast_slice(&slice.get_mapped_range().map_err(|error| driver(&error))?)
+            .to_vec();
+        staging.unmap();
+        Ok(data)
+    }
+}
+
+/// Dispatches one invocation per item, spreading the workgroups over two
+/// dimensions when one would exceed the limit.
+fn dispatch(
+    pass: &mut wgpu::ComputePass,
+    pipeline: &wgpu::ComputePipeline,
+    bind_group: &wgpu::BindGroup,
+    invocations: u32,
+) {
+    let groups = invocations.div_ceil(WORKGROUP_SIZE);
+    let x = groups.clamp(1, MAX_WORKGROUPS);
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.dispatch_workgroups(x, groups.div_ceil(x).max(1), 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::fir::{lowpass, Fir};
+    use crate::{average, spectrum};
+    use approx::assert_relative_eq;
+
+    /// The GPU, or `None` on machines without one, where the tests pass
+    /// vacuously.
+    fn gpu() -> Option<Gpu> {
+        match Gpu::new() {
+            Ok(gpu) => Some(gpu),
+            Err(error) => {
+                eprintln!("skipping GPU test: {error}");
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_average_sets_match_cpu() {
+        let Some(gpu) = gpu() else { return };
+        let readings: Vec<(f32, f32)> = (0..1000)
+            .map(|n| ((n * 37 % 360) as f32, 0.5 + (n % 7) as f32 / 10.0))
+            .collect();
+        let averages = gpu.average_sets(&readings, 10).unwrap();
+        assert_eq!(averages.len(), 100);
+        for (set, &(angle, magnitude)) in readings.chunks(10).zip(&averages) {
+            let set: Vec<(f64, f64)> = set
+                .iter()
+                .map(|&(a, m)| (f64::from(a), f64::from(m)))
+                .collect();
+            let (expected_angle, expected_magnitude) = average(&set);
+            let difference = (f64::from(angle) - expected_angle + 180.0).rem_euclid(360.0) - 180.0;
+            assert!(difference.abs() < 1e-2, "{angle} vs {expected_angle}");
+            assert_relative_eq!(f64::from(magnitude), expected_magnitude, epsilon = 1e-4);
+        }
+        assert!(gpu.average_sets(&readings, 3).is_err());
+    }
+
+    #[test]
+    fn test_fir_matches_cpu() {
+        let Some(gpu) = gpu() else { return };
+        let taps: Vec<f32> = lowpass(31, 0.2).iter().map(|&tap| tap as f32).collect();
+        let samples: Vec<Complex<f32>> = (0..5000)
+            .map(|n| Complex::from_polar(1.0, 0.37 * n as f32))
+            .collect();
+        let filtered = gpu.fir(&samples, &taps).unwrap();
+        let mut fir = Fir::new(&taps);
+        for (sample, gpu_output) in samples.iter().zip(&filtered) {
+            assert_relative_eq!(
+                (fir.filter(*sample) - gpu_output).norm(),
+                0.0,
+                epsilon = 1e-5
+            );
+        }
+    }
+
+    #[test]
+    fn test_fft_batch_matches_cpu() {
+        let Some(gpu) = gpu() else { return };
+        let size = 1024;
+        let mut data: Vec<Complex<f32>> = (0..size * 4)
+            .map(|n| Complex::from_polar(1.0, 0.01 * (n * n % 977) as f32))
+            .collect();
+        let original = data.clone();
+        gpu.fft_batch(&mut data, size).unwrap();
+        for (transform, input) in data.chunks(size).zip(original.chunks(size)) {
+            let mut expected: Vec<Complex<f64>> = input
+                .iter()
+                .map(|x| Complex::new(f64::from(x.re), f64::from(x.im)))
+                .collect();
+            spectrum::fft(&mut expected);
+            for (actual, expected) in transform.iter().zip(&expected) {
+                let actual = Complex::new(f64::from(actual.re), f64::from(actual.im));
+                assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-2);
+            }
+        }
+        assert!(gpu.fft_batch(&mut data, 1000).is_err());
+    }
+}
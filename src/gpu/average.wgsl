@@ -0,0 +1,29 @@
+// One invocation per set of readings: the vector mean of `set_length`
+// (angle in degrees, magnitude) pairs, as (angle, magnitude).
+
+struct Params {
+    sets: u32,
+    set_length: u32,
+    _padding: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> readings: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read_write> averages: array<vec2<f32>>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let set_index = id.x + id.y * groups.x * 64u;
+    if set_index >= params.sets {
+        return;
+    }
+    let start = set_index * params.set_length;
+    var sum = vec2<f32>(0.0, 0.0);
+    for (var i = 0u; i < params.set_length; i++) {
+        let reading = readings[start + i];
+        let angle = radians(reading.x);
+        sum += reading.y * vec2<f32>(cos(angle), sin(angle));
+    }
+    let mean = sum / f32(params.set_length);
+    averages[set_index] = vec2<f32>(degrees(atan2(mean.y, mean.x)), length(mean));
+}
@@ -0,0 +1,37 @@
+// One radix-2 Stockham stage over a batch of transforms: one invocation per
+// butterfly. `span` is the length of the sub-transforms already combined,
+// doubling each stage from 1; the output of the last stage is in natural
+// order.
+
+struct Params {
+    size: u32,
+    span: u32,
+    butterflies: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<storage, read> source: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read_write> destination: array<vec2<f32>>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+const TAU: f32 = 6.283185307179586;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let t = id.x + id.y * groups.x * 64u;
+    if t >= params.butterflies {
+        return;
+    }
+    let half_size = params.size / 2u;
+    let base = (t / half_size) * params.size;
+    let j = t % half_size;
+    let k = j % params.span;
+    let angle = -TAU * f32(k) / f32(2u * params.span);
+    let twiddle = vec2<f32>(cos(angle), sin(angle));
+    let a = source[base + j];
+    let b = source[base + j + half_size];
+    let rotated = vec2<f32>(b.x * twiddle.x - b.y * twiddle.y, b.x * twiddle.y + b.y * twiddle.x);
+    let index = base + (j / params.span) * params.span * 2u + k;
+    destination[index] = a + rotated;
+    destination[index + params.span] = a - rotated;
+}
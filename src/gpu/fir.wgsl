@@ -0,0 +1,27 @@
+// One invocation per output sample. `samples` starts with the `taps - 1`
+// samples of history before the first output.
+
+struct Params {
+    outputs: u32,
+    taps: u32,
+    _padding: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> samples: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read> taps: array<f32>;
+@group(0) @binding(2) var<storage, read_write> filtered: array<vec2<f32>>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let n = id.x + id.y * groups.x * 64u;
+    if n >= params.outputs {
+        return;
+    }
+    let newest = n + params.taps - 1u;
+    var sum = vec2<f32>(0.0, 0.0);
+    for (var k = 0u; k < params.taps; k++) {
+        sum += taps[k] * samples[newest - k];
+    }
+    filtered[n] = sum;
+}
@@ -0,0 +1,4 @@
+//! Reading and writing data files.
+
+pub mod iq;
+pub mod readings;
@@ -0,0 +1,298 @@
+//! Streaming raw IQ capture files.
+//!
+//! Captures such as those written by `rtl_sdr` or `hackrf_transfer` run to
+//! gigabytes, so nothing here loads a file whole. [`IqFile`] is a [`Source`]
+//! that decodes one caller-sized chunk at a time and can be added to a
+//! [`Flowgraph`](crate::flowgraph::Flowgraph) like any receiver, and
+//! [`process_file`] pumps a file through a single [`Block`] with buffers
+//! bounded by the chunk size:
+//!
+//! ```no_run
+//! use sdr_rust::dsp::dc::DcTracker;
+//! use sdr_rust::io::iq::{process_file, IqFormat};
+//!
+//! let mut peak = 0.0f64;
+//! let samples = process_file(
+//!     "capture.cu8",
+//!     IqFormat::Cu8,
+//!     65536,
+//!     &mut DcTracker::new(0.001),
+//!     |output| peak = output.iter().fold(peak, |peak, sample| peak.max(sample.norm())),
+//! )
+//! .unwrap();
+//! println!("{samples} samples, peak {peak}");
+//! ```
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+use crate::source::Source;
+use num_complex::Complex;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+/// The on-disk encoding of interleaved I and Q samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IqFormat {
+    /// Unsigned 8-bit, offset by 127.5, as written by `rtl_sdr`.
+    Cu8,
+    /// Signed 8-bit, as written by `hackrf_transfer`.
+    Cs8,
+    /// Signed 16-bit little-endian.
+    Cs16,
+    /// 32-bit little-endian floats, as written by GNU Radio's file sink.
+    Cf32,
+}
+
+impl IqFormat {
+    /// Guesses the format from a file extension: `.cu8`, `.cs8`, `.cs16`, or
+    /// `.cf32` and `.cfile`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<IqFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "cu8" => Some(IqFormat::Cu8),
+            "cs8" => Some(IqFormat::Cs8),
+            "cs16" => Some(IqFormat::Cs16),
+            "cf32" | "cfile" => Some(IqFormat::Cf32),
+            _ => None,
+        }
+    }
+
+    /// Bytes taken by one complex sample.
+    pub fn sample_size(self) -> usize {
+        match self {
+            IqFormat::Cu8 | IqFormat::Cs8 => 2,
+            IqFormat::Cs16 => 4,
+            IqFormat::Cf32 => 8,
+        }
+    }
+
+    /// Decodes one sample from exactly [`sample_size`](IqFormat::sample_size)
+    /// bytes, scaled to roughly `[-1, 1]` for the integer formats.
+    fn decode(self, bytes: &[u8]) -> Complex<f64> {
+        match self {
+            IqFormat::Cu8 => Complex::new(
+                (bytes[0] as f64 - 127.5) / 127.5,
+                (bytes[1] as f64 - 127.5) / 127.5,
+            ),
+            IqFormat::Cs8 => {
+                Complex::new(bytes[0] as i8 as f64 / 128.0, bytes[1] as i8 as f64 / 128.0)
+            }
+            IqFormat::Cs16 => Complex::new(
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0,
+                i16::from_le_bytes([bytes[2], bytes[3]]) as f64 / 32768.0,
+            ),
+            IqFormat::Cf32 => Complex::new(
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64,
+            ),
+        }
+    }
+}
+
+/// Reads IQ samples from a capture, decoding only as many as each
+/// [`read`](Source::read) asks for.
+#[derive(Debug)]
+pub struct IqFile<R: Read = BufReader<File>> {
+    reader: R,
+    format: IqFormat,
+    bytes: Vec<u8>,
+    samples: u64,
+}
+
+impl IqFile {
+    pub fn open(path: impl AsRef<Path>, format: IqFormat) -> Result<Self> {
+        Ok(IqFile::new(BufReader::new(File::open(path)?), format))
+    }
+}
+
+impl<R: Read> IqFile<R> {
+    /// Reads samples from any byte stream, such as standard input.
+    pub fn new(reader: R, format: IqFormat) -> Self {
+        IqFile {
+            reader,
+            format,
+            bytes: Vec::new(),
+            samples: 0,
+        }
+    }
+
+    pub fn format(&self) -> IqFormat {
+        self.format
+    }
+
+    /// Samples read so far.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+impl<R: Read> Source for IqFile<R> {
+    type Sample = Complex<f64>;
+    type Error = std::io::Error;
+
+    /// Fills as much of `buffer` as the file allows. A trailing partial
+    /// sample at the end of the file is discarded.
+    fn read(&mut self, buffer: &mut [Complex<f64>]) -> std::io::Result<usize> {
+        let size = self.format.sample_size();
+        self.bytes.resize(buffer.len() * size, 0);
+        let mut filled = 0;
+        while filled < self.bytes.len() {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        let count = filled / size;
+        for (sample, bytes) in buffer
+            .iter_mut()
+            .zip(self.bytes[..count * size].chunks_exact(size))
+        {
+            *sample = self.format.decode(bytes);
+        }
+        self.samples += count as u64;
+        Ok(count)
+    }
+}
+
+/// Streams the capture at `path` through `block`, `chunk` samples at a time,
+/// handing each batch of output to `sink`. Returns the number of samples
+/// read from the file.
+///
+/// Input the block leaves unconsumed is carried into the next chunk, so
+/// blocks that work on whole frames see every sample; memory use stays
+/// proportional to `chunk` plus one frame however large the file is.
+pub fn process_file<B, F>(
+    path: impl AsRef<Path>,
+    format: IqFormat,
+    chunk: usize,
+    block: &mut B,
+    sink: F,
+) -> Result<u64>
+where
+    B: Block<Input = Complex<f64>>,
+    F: FnMut(&[B::Output]),
+{
+    process(&mut IqFile::open(path, format)?, chunk, block, sink)
+}
+
+/// [`process_file`] for an already open [`IqFile`], such as one reading
+/// standard input.
+pub fn process<R, B, F>(
+    file: &mut IqFile<R>,
+    chunk: usize,
+    block: &mut B,
+    mut sink: F,
+) -> Result<u64>
+where
+    R: Read,
+    B: Block<Input = Complex<f64>>,
+    F: FnMut(&[B::Output]),
+{
+    if chunk == 0 {
+        return Err(DspError::InvalidArgument("chunk must hold at least one sample".into()).into());
+    }
+    let start = file.samples();
+    let mut pending = Vec::with_capacity(chunk);
+    let mut output = Vec::new();
+    loop {
+        let held = pending.len();
+        pending.resize(held + chunk, Complex::new(0.0, 0.0));
+        let read = file.read(&mut pending[held..])?;
+        pending.truncate(held + read);
+        if read == 0 {
+            break;
+        }
+        let consumed = block.work(&pending, &mut output);
+        pending.drain(..consumed);
+        if !output.is_empty() {
+            sink(&output);
+            output.clear();
+        }
+    }
+    Ok(file.samples() - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::map;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_formats_decode() {
+        let samples = |format, bytes: &[u8]| {
+            let mut file = IqFile::new(bytes, format);
+            let mut buffer = vec![Complex::new(0.0, 0.0); 4];
+            let count = file.read(&mut buffer).unwrap();
+            buffer.truncate(count);
+            buffer
+        };
+        assert_eq!(
+            samples(IqFormat::Cu8, &[255, 0, 127]),
+            [Complex::new(1.0, -1.0)]
+        );
+        assert_eq!(
+            samples(IqFormat::Cs8, &[0x80, 0x40]),
+            [Complex::new(-1.0, 0.5)]
+        );
+        assert_eq!(
+            samples(IqFormat::Cs16, &[0x00, 0x40, 0x00, 0xc0]),
+            [Complex::new(0.5, -0.5)]
+        );
+        let mut bytes = 0.25f32.to_le_bytes().to_vec();
+        bytes.extend((-2.0f32).to_le_bytes());
+        assert_eq!(samples(IqFormat::Cf32, &bytes), [Complex::new(0.25, -2.0)]);
+        assert_eq!(IqFormat::from_path("capture.CS16"), Some(IqFormat::Cs16));
+        assert_eq!(IqFormat::from_path("capture.wav"), None);
+    }
+
+    /// Sums pairs of samples, leaving an odd one for the next call.
+    struct Pairs;
+
+    impl Block for Pairs {
+        type Input = Complex<f64>;
+        type Output = Complex<f64>;
+
+        fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Complex<f64>>) -> usize {
+            output.extend(input.chunks_exact(2).map(|pair| pair[0] + pair[1]));
+            input.len() / 2 * 2
+        }
+    }
+
+    #[test]
+    fn test_chunks_match_whole_file() {
+        let path = std::env::temp_dir().join(format!("sdr-rust-iq-{}.cs16", std::process::id()));
+        let bytes: Vec<u8> = (0..1001i16)
+            .flat_map(|n| [n * 7, -n].into_iter().flat_map(i16::to_le_bytes))
+            .collect();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut whole = Vec::new();
+        let read = process_file(&path, IqFormat::Cs16, 4096, &mut Pairs, |output| {
+            whole.extend_from_slice(output)
+        })
+        .unwrap();
+        assert_eq!(read, 1001);
+        assert_eq!(whole.len(), 500);
+
+        let mut chunked = Vec::new();
+        let mut largest = 0;
+        process_file(&path, IqFormat::Cs16, 7, &mut Pairs, |output| {
+            largest = largest.max(output.len());
+            chunked.extend_from_slice(output)
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(chunked, whole);
+        assert!(largest <= 4);
+        assert_relative_eq!(whole[1].re, 35.0 / 32768.0);
+
+        let mut file = IqFile::new(&bytes[..], IqFormat::Cs16);
+        let mut doubled = map(|sample: &Complex<f64>| sample * 2.0);
+        assert!(process(&mut file, 0, &mut doubled, |_| {}).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Loading timestamped bearing logs.
+//!
+//! Two formats are accepted:
+//!
+//! * CSV with a header row naming the columns, in any order. `timestamp` (or
+//!   `time`) and `angle` (or `bearing`) are required; `magnitude` (or
+//!   `strength`) defaults to 1.
+//! * JSON lines, one object per line with the same field names. Requires the
+//!   `serde` feature.
+//!
+//! Timestamps are either seconds as a number, typically since the UNIX epoch,
+//! or RFC 3339 date-times such as `2024-05-01T12:34:56.5Z`, which are
+//! converted to UNIX seconds.
+
+use std::io::BufRead;
+use std::path::Path;
+
+/// A bearing reading with the time it was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoggedReading {
+    /// Seconds, typically since the UNIX epoch.
+    pub timestamp: f64,
+    /// Degrees.
+    pub angle: f64,
+    pub magnitude: f64,
+}
+
+impl LoggedReading {
+    /// The reading as the `(angle, magnitude)` pair taken by [`crate::average`].
+    pub fn as_pair(&self) -> (f64, f64) {
+        (self.angle, self.magnitude)
+    }
+}
+
+/// Errors from loading a log.
+#[derive(Debug, thiserror::Error)]
+pub enum ReadingsError {
+    #[error("read failed: {0}")]
+    Io(#[from] std::io::Error),
+    /// A line could not be parsed. Lines are numbered from 1.
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+    /// The file's format could not be handled.
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+impl ReadingsError {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        ReadingsError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+const TIMESTAMP_NAMES: [&str; 2] = ["timestamp", "time"];
+const ANGLE_NAMES: [&str; 2] = ["angle", "bearing"];
+const MAGNITUDE_NAMES: [&str; 2] = ["magnitude", "strength"];
+
+/// Loads a log, choosing the format from the extension: `.jsonl` and
+/// `.ndjson` are JSON lines, anything else is CSV.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<LoggedReading>, ReadingsError> {
+    let path = path.as_ref();
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("jsonl" | "ndjson") => read_json_lines_or_unsupported(reader),
+        _ => read_csv(reader),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn read_json_lines_or_unsupported(
+    reader: impl BufRead,
+) -> Result<Vec<LoggedReading>, ReadingsError> {
+    read_json_lines(reader)
+}
+
+#[cfg(not(feature = "serde"))]
+fn read_json_lines_or_unsupported(
+    _reader: impl BufRead,
+) -> Result<Vec<LoggedReading>, ReadingsError> {
+    Err(ReadingsError::Unsupported(
+        "JSON lines logs need the `serde` feature".to_string(),
+    ))
+}
+
+/// Parses a CSV log with a header row. Blank lines and lines starting with
+/// `#` are skipped.
+pub fn read_csv(reader: impl BufRead) -> Result<Vec<LoggedReading>, ReadingsError> {
+    let mut columns: Option<(usize, usize, Option<usize>)> = None;
+    let mut readings = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let Some((timestamp, angle, magnitude)) = columns else {
+            columns = Some(header(&fields, number)?);
+            continue;
+        };
+        let field = |column: usize, name: &str| {
+            fields.get(column).copied().ok_or_else(|| {
+                ReadingsError::parse(number, format!("missing {name} in column {}", column + 1))
+            })
+        };
+        let timestamp = field(timestamp, "timestamp")?;
+        let angle = field(angle, "angle")?;
+        readings.push(LoggedReading {
+            timestamp: parse_timestamp(timestamp)
+                .map_err(|error| ReadingsError::parse(number, error))?,
+            angle: parse_number(angle, "angle")
+                .map_err(|error| ReadingsError::parse(number, error))?,
+            magnitude: match magnitude {
+                Some(column) => parse_number(field(column, "magnitude")?, "magnitude")
+                    .map_err(|error| ReadingsError::parse(number, error))?,
+                None => 1.0,
+            },
+        });
+    }
+    Ok(readings)
+}
+
+fn header(fields: &[&str], line: usize) -> Result<(usize, usize, Option<usize>), ReadingsError> {
+    let find = |names: &[&str]| {
+        fields
+            .iter()
+            .position(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)))
+    };
+    let require = |names: &[&str]| {
+        find(names).ok_or_else(|| {
+            ReadingsError::parse(
+                line,
+                format!(
+                    "header has no '{}' column (found: {})",
+                    names.join("' or '"),
+                    fields.join(", ")
+                ),
+            )
+        })
+    };
+    Ok((
+        require(&TIMESTAMP_NAMES)?,
+        require(&ANGLE_NAMES)?,
+        find(&MAGNITUDE_NAMES),
+    ))
+}
+
+fn parse_number(text: &str, name: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("invalid {name} '{text}'")),
+    }
+}
+
+/// Parses seconds or an RFC 3339 date-time into seconds.
+fn parse_timestamp(text: &str) -> Result<f64, String> {
+    if let Ok(seconds) = text.parse::<f64>() {
+        if seconds.is_finite() {
+            return Ok(seconds);
+        }
+    }
+    parse_rfc3339(text).ok_or_else(|| {
+        format!("invalid timestamp '{text}', expected seconds or an RFC 3339 date-time")
+    })
+}
+
+fn parse_rfc3339(text: &str) -> Option<f64> {
+    let (date, time) = text.split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+    let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let split = time.rfind(['+', '-'])?;
+        let (clock, zone) = time.split_at(split);
+        let sign = if zone.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = zone[1..].split_once(':')?;
+        (
+            clock,
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60),
+        )
+    };
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: f64 = clock_parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || !(0.0..61.0).contains(&second)
+    {
+        return None;
+    }
+    // Days from 1970-01-01 in the proleptic Gregorian calendar.
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some((days * 86_400 + hour * 3600 + minute * 60 - offset) as f64 + second)
+}
+
+/// Parses a JSON lines log. Blank lines are skipped.
+#[cfg(feature = "serde")]
+pub fn read_json_lines(reader: impl BufRead) -> Result<Vec<LoggedReading>, ReadingsError> {
+    use serde_json::Value;
+
+    let mut readings = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)
+            .map_err(|error| ReadingsError::parse(number, format!("invalid JSON: {error}")))?;
+        let Value::Object(object) = value else {
+            return Err(ReadingsError::parse(number, "expected a JSON object"));
+        };
+        let get = |names: &[&str]| names.iter().find_map(|name| object.get(*name));
+        let number_field = |names: &[&str], value: Option<&Value>| {
+            match value {
+                Some(Value::Number(n)) => n.as_f64().ok_or(()),
+                _ => Err(()),
+            }
+            .map_err(|_| ReadingsError::parse(number, format!("'{}' must be a number", names[0])))
+        };
+        let timestamp = match get(&TIMESTAMP_NAMES) {
+            Some(Value::String(text)) => {
+                parse_timestamp(text).map_err(|error| ReadingsError::parse(number, error))?
+            }
+            Some(value) => number_field(&TIMESTAMP_NAMES, Some(value))?,
+            None => return Err(ReadingsError::parse(number, "missing 'timestamp'")),
+        };
+        let angle = match get(&ANGLE_NAMES) {
+            Some(value) => number_field(&ANGLE_NAMES, Some(value))?,
+            None => return Err(ReadingsError::parse(number, "missing 'angle'")),
+        };
+        let magnitude = match get(&MAGNITUDE_NAMES) {
+            Some(value) => number_field(&MAGNITUDE_NAMES, Some(value))?,
+            None => 1.0,
+        };
+        readings.push(LoggedReading {
+            timestamp,
+            angle,
+            magnitude,
+        });
+    }
+    Ok(readings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_with_reordered_columns() {
+        let log = "# station 3\nBearing, Strength, Time\n45.0, 0.8, 1714566896\n\n50, 0.6, 2024-05-01T12:34:57.5Z\n";
+        let readings = read_csv(log.as_bytes()).unwrap();
+        assert_eq!(
+            readings,
+            vec![
+                LoggedReading {
+                    timestamp: 1_714_566_896.0,
+                    angle: 45.0,
+                    magnitude: 0.8
+                },
+                LoggedReading {
+                    timestamp: 1_714_566_897.5,
+                    angle: 50.0,
+                    magnitude: 0.6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_messages_name_the_line() {
+        let missing = read_csv("time,magnitude\n1,2\n".as_bytes()).unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "line 1: header has no 'angle' or 'bearing' column (found: time, magnitude)"
+        );
+        let bad = read_csv("time,angle\n1,2\n2,north\n".as_bytes()).unwrap_err();
+        assert_eq!(bad.to_string(), "line 3: invalid angle 'north'");
+        assert_eq!(parse_rfc3339("1970-01-02T01:00:00+01:00"), Some(86_400.0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_lines() {
+        let log = "{\"timestamp\": \"2024-05-01T12:34:56Z\", \"angle\": 10}\n{\"time\": 5, \"bearing\": 20, \"magnitude\": 0.5}\n";
+        let readings = read_json_lines(log.as_bytes()).unwrap();
+        assert_eq!(readings[0].timestamp, 1_714_566_896.0);
+        assert_eq!(readings[0].magnitude, 1.0);
+        assert_eq!(readings[1].as_pair(), (20.0, 0.5));
+        let bad = read_json_lines("{\"timestamp\": 1, \"angle\": \"x\"}".as_bytes()).unwrap_err();
+        assert_eq!(bad.to_string(), "line 1: 'angle' must be a number");
+    }
+}
@@ -1,6 +1,62 @@
+// The original tests build their readings with `vec!`, kept as written.
+#![cfg_attr(test, allow(clippy::useless_vec))]
+
 use num_complex::Complex;
 use std::f64::consts::PI;
 
+/// Emits a `tracing` event at `$level` when the `tracing` feature is
+/// enabled and compiles to nothing otherwise, so instrumented code needs no
+/// `cfg` of its own. Arguments are not evaluated when the feature is off.
+macro_rules! trace_event {
+    ($level:ident, $($arguments:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arguments)+);
+    };
+}
+
+pub mod averager;
+pub mod bench;
+pub mod block;
+pub mod buffer;
+pub mod coding;
+pub mod constellation;
+pub mod decode;
+pub mod detect;
+pub mod df;
+pub mod dsp;
+pub mod error;
+#[cfg(feature = "exact")]
+pub mod exact;
+pub mod flowgraph;
+pub mod fusion;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod io;
+pub mod measure;
+pub mod param;
+pub mod reading;
+#[cfg(feature = "satellite")]
+pub mod satellite;
+pub mod scheduler;
+pub mod sink;
+pub mod source;
+pub mod spectrum;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod timing;
+pub mod track;
+#[cfg(feature = "viz")]
+pub mod viz;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use averager::Averager;
+pub use error::SdrError;
+#[cfg(feature = "exact")]
+pub use exact::average_exact;
+pub use reading::Reading;
+
 /// Creates a base complex number for degree angle calculations
 pub fn create_degrees_base() -> Complex<f64> {
     //    base = cmath.e ** (1j * tau / 360)
@@ -47,20 +103,36 @@ pub fn average_with_trig(readings: &[(f64, f64)]) -> (f64, f64) {
     (angle_degrees, magnitude)
 }
 
-pub fn average_optimized(readings: &[(f64, f64)]) -> (f64, f64) {
-    // Calculate constants once at runtime
-    let base = create_degrees_base();
-
-    // Single pass accumulation with direct complex multiplication
+/// Like [`average`], but builds each reading's vector with `from_polar`
+/// and takes the result's angle with `arg`, avoiding the log/exp pair that
+/// `powf` costs per reading.
+pub fn average_fast(readings: &[(f64, f64)]) -> (f64, f64) {
     let total: Complex<f64> = readings
         .iter()
         .fold(Complex::new(0.0, 0.0), |acc, &(angle, magnitude)| {
-            acc + magnitude * base.powf(angle)
+            acc + Complex::from_polar(magnitude, angle.to_radians())
         });
 
     let result = total / readings.len() as f64;
-    let angle = result.ln() / base.ln();
-    (angle.re, result.norm())
+    (result.arg().to_degrees(), result.norm())
+}
+
+/// Like [`average_fast`] in single precision throughout, for very large
+/// sets of readings where the memory saved matters more than the digits.
+pub fn average_f32(readings: &[(f32, f32)]) -> (f32, f32) {
+    let total: Complex<f32> = readings
+        .iter()
+        .fold(Complex::new(0.0, 0.0), |acc, &(angle, magnitude)| {
+            acc + Complex::from_polar(magnitude, angle.to_radians())
+        });
+
+    let result = total / readings.len() as f32;
+    (result.arg().to_degrees(), result.norm())
+}
+
+/// Kept for compatibility; the same as [`average_fast`].
+pub fn average_optimized(readings: &[(f64, f64)]) -> (f64, f64) {
+    average_fast(readings)
 }
 
 #[cfg(test)]
@@ -150,6 +222,40 @@ mod tests {
         assert_relative_eq!(magnitude, 0.106, epsilon = 0.1);
     }
 
+    #[test]
+    fn test_average_fast_matches_average() {
+        let readings = [
+            (210.0, 0.5),
+            (290.0, 1.0),
+            (10.0, 2.0),
+            (90.0, 1.0),
+            (170.0, 1.5),
+        ];
+        let (angle, magnitude) = average_fast(&readings);
+        let (expected_angle, expected_magnitude) = average(&readings);
+        assert_relative_eq!(angle, expected_angle, epsilon = 1e-9);
+        assert_relative_eq!(magnitude, expected_magnitude, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_average_f32_matches_average() {
+        let readings = [
+            (358.0, 1.0),
+            (1.0, 0.5),
+            (359.0, 2.0),
+            (355.0, 1.0),
+            (2.0, 1.0),
+        ];
+        let single: Vec<(f32, f32)> = readings
+            .iter()
+            .map(|&(angle, magnitude)| (angle as f32, magnitude as f32))
+            .collect();
+        let (angle, magnitude) = average_f32(&single);
+        let (expected_angle, expected_magnitude) = average(&readings);
+        assert_relative_eq!(f64::from(angle), expected_angle, epsilon = 1e-4);
+        assert_relative_eq!(f64::from(magnitude), expected_magnitude, epsilon = 1e-6);
+    }
+
     #[test]
     fn test_reading_to_axis_and_angle_mag_to_complex_are_the_same() {
         let base = create_degrees_base();
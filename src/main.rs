@@ -1,3 +1,147 @@
-fn main() {
-    println!("Hello, world!");
+//! Summarizes bearings read from CSV.
+//!
+//! Each row is `angle[,magnitude][,weight]`, with the angle in degrees. A
+//! reading's weight in the average is its magnitude times its weight, both
+//! defaulting to 1. Blank lines, `#` comments and a header row are skipped.
+//!
+//! ```text
+//! sdr-rust [--confidence LEVEL] [FILE]
+//! ```
+//!
+//! Reads standard input when no file is given.
+
+use sdr_rust::stats::summarize;
+use std::io::{self, BufRead, BufReader};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: sdr-rust [--confidence LEVEL] [FILE]";
+
+struct Options {
+    confidence: f64,
+    path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        confidence: 0.95,
+        path: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            "-c" | "--confidence" => {
+                let value = args.next().ok_or("--confidence needs a value")?;
+                options.confidence = match value.parse::<f64>() {
+                    Ok(level) if level > 0.0 && level < 1.0 => level,
+                    Ok(percent) if percent > 1.0 && percent < 100.0 => percent / 100.0,
+                    _ => return Err(format!("invalid confidence level '{value}'")),
+                };
+            }
+            _ if options.path.is_none() && !arg.starts_with('-') => options.path = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'\n{USAGE}")),
+        }
+    }
+    Ok(options)
+}
+
+/// Parses one row into `(angle, weight)`, or `None` for a row to skip.
+fn parse_row(line: &str, number: usize) -> Result<Option<(f64, f64)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() > 3 {
+        return Err(format!(
+            "line {number}: expected angle[,magnitude][,weight], found {} fields",
+            fields.len()
+        ));
+    }
+    let mut values = [0.0, 1.0, 1.0];
+    for (index, (field, value)) in fields.iter().zip(&mut values).enumerate() {
+        *value = match field.parse::<f64>() {
+            Ok(parsed) if parsed.is_finite() => parsed,
+            // A non-numeric first row is a header.
+            _ if number == 1 => return Ok(None),
+            _ => {
+                let name = ["angle", "magnitude", "weight"][index];
+                return Err(format!("line {number}: invalid {name} '{field}'"));
+            }
+        };
+    }
+    if values[1] < 0.0 || values[2] < 0.0 {
+        return Err(format!(
+            "line {number}: magnitude and weight must not be negative"
+        ));
+    }
+    Ok(Some((values[0], values[1] * values[2])))
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(std::env::args().skip(1))?;
+    let input: Box<dyn BufRead> = match &options.path {
+        Some(path) => Box::new(BufReader::new(
+            std::fs::File::open(path).map_err(|error| format!("{path}: {error}"))?,
+        )),
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut readings = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.map_err(|error| format!("read failed: {error}"))?;
+        readings.extend(parse_row(&line, index + 1)?);
+    }
+    let summary = summarize(&readings).ok_or("no readings with a positive weight")?;
+    let level = format!("{}% interval:", options.confidence * 100.0);
+    let interval = match summary.confidence_interval(options.confidence) {
+        Some(half_width) => format!(
+            "{:.2} to {:.2} (±{half_width:.2})",
+            (summary.mean - half_width).rem_euclid(360.0),
+            (summary.mean + half_width).rem_euclid(360.0),
+        ),
+        None => "undefined, readings too dispersed".to_string(),
+    };
+    for (label, value) in [
+        ("readings:", summary.count.to_string()),
+        ("mean:", format!("{:.2}", summary.mean)),
+        (
+            "resultant length:",
+            format!("{:.4}", summary.resultant_length),
+        ),
+        ("circular variance:", format!("{:.4}", summary.variance())),
+        (
+            "circular std dev:",
+            format!("{:.2}", summary.standard_deviation()),
+        ),
+        (level.as_str(), interval),
+    ] {
+        println!("{label:<20}{value}");
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rows() {
+        assert_eq!(parse_row("angle,magnitude", 1), Ok(None));
+        assert_eq!(parse_row(" 45.5 ", 2), Ok(Some((45.5, 1.0))));
+        assert_eq!(parse_row("90, 0.5, 4", 3), Ok(Some((90.0, 2.0))));
+        assert_eq!(parse_row("# comment", 4), Ok(None));
+        assert_eq!(
+            parse_row("north", 5),
+            Err("line 5: invalid angle 'north'".to_string())
+        );
+    }
 }
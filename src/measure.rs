@@ -0,0 +1,5 @@
+//! Link-quality measurements for validating modulator and demodulator chains.
+
+pub mod ber;
+pub mod cn0;
+pub mod evm;
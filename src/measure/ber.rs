@@ -0,0 +1,310 @@
+//! Bit error rate testing against pseudo-random bit sequences.
+//!
+//! The transmitter sends a standard PRBS and the tester synchronizes to it
+//! from the received bits alone, so no knowledge of the starting point is
+//! needed. An inverted link (a swapped FSK pair, a 180 degree PSK ambiguity)
+//! is detected and corrected during synchronization.
+
+use crate::block::Block;
+
+/// Standard PRBS generator polynomials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrbsPattern {
+    /// x^7 + x^6 + 1
+    Prbs7,
+    /// x^9 + x^5 + 1
+    Prbs9,
+    /// x^15 + x^14 + 1
+    Prbs15,
+    /// x^23 + x^18 + 1
+    Prbs23,
+    /// x^31 + x^28 + 1
+    Prbs31,
+}
+
+impl PrbsPattern {
+    /// The polynomial's two exponents, highest first.
+    fn taps(self) -> (u32, u32) {
+        match self {
+            PrbsPattern::Prbs7 => (7, 6),
+            PrbsPattern::Prbs9 => (9, 5),
+            PrbsPattern::Prbs15 => (15, 14),
+            PrbsPattern::Prbs23 => (23, 18),
+            PrbsPattern::Prbs31 => (31, 28),
+        }
+    }
+
+    /// Register length, which is also the number of bits needed to seed it.
+    pub fn order(self) -> u32 {
+        self.taps().0
+    }
+
+    /// Length of the sequence before it repeats.
+    pub fn period(self) -> u64 {
+        (1 << self.order()) - 1
+    }
+}
+
+/// A PRBS generator.
+#[derive(Debug, Clone)]
+pub struct Prbs {
+    pattern: PrbsPattern,
+    state: u64,
+}
+
+impl Prbs {
+    /// Starts the sequence from the all-ones state.
+    pub fn new(pattern: PrbsPattern) -> Self {
+        Prbs {
+            pattern,
+            state: Self::mask(pattern),
+        }
+    }
+
+    fn mask(pattern: PrbsPattern) -> u64 {
+        (1 << pattern.order()) - 1
+    }
+
+    fn predict(&self) -> bool {
+        let (a, b) = self.pattern.taps();
+        ((self.state >> (a - 1)) ^ (self.state >> (b - 1))) & 1 == 1
+    }
+
+    fn shift(&mut self, bit: bool) {
+        self.state = ((self.state << 1) | u64::from(bit)) & Self::mask(self.pattern);
+    }
+}
+
+impl Iterator for Prbs {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        let bit = self.predict();
+        self.shift(bit);
+        Some(bit)
+    }
+}
+
+/// Error counts over a span of bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BerWindow {
+    pub bits: u64,
+    pub errors: u64,
+}
+
+impl BerWindow {
+    /// The bit error rate, or 0 for an empty window.
+    pub fn ber(&self) -> f64 {
+        if self.bits == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.bits as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Loading received bits into the register.
+    Seeding {
+        loaded: u32,
+    },
+    /// Checking that the received bits follow the loaded register.
+    Verifying {
+        checked: u32,
+        mismatches: u32,
+    },
+    Locked,
+}
+
+/// Compares a received bitstream with a known PRBS, reporting the error
+/// rate over consecutive windows.
+///
+/// Once locked, the reference comes from the tester's own generator, so a
+/// received error is counted once instead of corrupting the prediction. A
+/// window whose error rate exceeds [`BerTester::LOSS_THRESHOLD`] is still
+/// reported, after which the tester drops back to synchronizing.
+#[derive(Debug, Clone)]
+pub struct BerTester {
+    reference: Prbs,
+    window_bits: u64,
+    state: State,
+    inverted: bool,
+    window: BerWindow,
+    total: BerWindow,
+}
+
+impl BerTester {
+    /// Bits checked after seeding before the tester declares lock.
+    pub const VERIFY_BITS: u32 = 64;
+    /// Window error rate above which lock is considered lost.
+    pub const LOSS_THRESHOLD: f64 = 0.2;
+
+    /// Creates a tester reporting one [`BerWindow`] every `window_bits` bits.
+    pub fn new(pattern: PrbsPattern, window_bits: u64) -> Self {
+        BerTester {
+            reference: Prbs::new(pattern),
+            window_bits: window_bits.max(1),
+            state: State::Seeding { loaded: 0 },
+            inverted: false,
+            window: BerWindow::default(),
+            total: BerWindow::default(),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state == State::Locked
+    }
+
+    /// Whether the received bits were found to be inverted.
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Error counts over every bit checked while locked.
+    pub fn total(&self) -> BerWindow {
+        self.total
+    }
+
+    /// Processes received bits and returns the windows completed in them.
+    pub fn process(&mut self, bits: &[bool]) -> Vec<BerWindow> {
+        let mut windows = Vec::new();
+        for &bit in bits {
+            match self.state {
+                State::Seeding { loaded } => {
+                    self.reference.shift(bit);
+                    let loaded = loaded + 1;
+                    self.state = if loaded == self.reference.pattern.order() {
+                        State::Verifying {
+                            checked: 0,
+                            mismatches: 0,
+                        }
+                    } else {
+                        State::Seeding { loaded }
+                    };
+                }
+                State::Verifying {
+                    checked,
+                    mismatches,
+                } => {
+                    // Keeps following the received bits. An inverted stream
+                    // fails every check, because the two inverted taps cancel.
+                    let mismatches = mismatches + u32::from(self.reference.predict() != bit);
+                    self.reference.shift(bit);
+                    let checked = checked + 1;
+                    self.state = State::Verifying {
+                        checked,
+                        mismatches,
+                    };
+                    if checked == Self::VERIFY_BITS {
+                        self.finish_verification(mismatches);
+                    }
+                }
+                State::Locked => {
+                    let expected = self.reference.next().unwrap_or_default();
+                    let error = u64::from(expected != (bit ^ self.inverted));
+                    self.window.bits += 1;
+                    self.window.errors += error;
+                    self.total.bits += 1;
+                    self.total.errors += error;
+                    if self.window.bits == self.window_bits {
+                        let window = std::mem::take(&mut self.window);
+                        if window.ber() > Self::LOSS_THRESHOLD {
+                            trace_event!(warn, ber = window.ber(), "BER tester lost sync");
+                            self.state = State::Seeding { loaded: 0 };
+                        }
+                        windows.push(window);
+                    }
+                }
+            }
+        }
+        windows
+    }
+
+    fn finish_verification(&mut self, mismatches: u32) {
+        let tolerance = Self::VERIFY_BITS / 8;
+        if mismatches <= tolerance {
+            self.inverted = false;
+            self.state = State::Locked;
+        } else if mismatches >= Self::VERIFY_BITS - tolerance {
+            self.inverted = true;
+            self.reference.state ^= Prbs::mask(self.reference.pattern);
+            self.state = State::Locked;
+        } else {
+            self.state = State::Seeding { loaded: 0 };
+        }
+        if self.is_locked() {
+            trace_event!(debug, inverted = self.inverted, "BER tester locked");
+        }
+    }
+}
+
+impl Block for BerTester {
+    type Input = bool;
+    type Output = BerWindow;
+
+    fn work(&mut self, input: &[bool], output: &mut Vec<BerWindow>) -> usize {
+        output.extend(self.process(input));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prbs_period() {
+        let sequence: Vec<bool> = Prbs::new(PrbsPattern::Prbs7).take(254).collect();
+        assert_eq!(sequence[..127], sequence[127..]);
+        assert_eq!(sequence[..127].iter().filter(|&&bit| bit).count(), 64);
+    }
+
+    #[test]
+    fn test_locks_mid_sequence_and_counts_errors() {
+        let mut received: Vec<bool> = Prbs::new(PrbsPattern::Prbs9).skip(37).take(2073).collect();
+        // One error in each 1000-bit window after the 73 synchronization bits.
+        for position in [500, 1500] {
+            received[position] = !received[position];
+        }
+        let mut tester = BerTester::new(PrbsPattern::Prbs9, 1000);
+        let windows = tester.process(&received);
+        assert!(tester.is_locked());
+        assert!(!tester.is_inverted());
+        assert_eq!(
+            windows,
+            vec![
+                BerWindow {
+                    bits: 1000,
+                    errors: 1
+                };
+                2
+            ]
+        );
+        assert_eq!(tester.total().ber(), 0.001);
+    }
+
+    #[test]
+    fn test_resolves_inverted_polarity() {
+        let received: Vec<bool> = Prbs::new(PrbsPattern::Prbs15)
+            .skip(1000)
+            .take(5079)
+            .map(|bit| !bit)
+            .collect();
+        let mut tester = BerTester::new(PrbsPattern::Prbs15, 1000);
+        let windows = tester.process(&received);
+        assert!(tester.is_inverted());
+        assert_eq!(windows.len(), 5);
+        assert!(windows.iter().all(|window| window.errors == 0));
+    }
+
+    #[test]
+    fn test_random_bits_never_lock() {
+        let mut noise = Prbs::new(PrbsPattern::Prbs31);
+        let mut tester = BerTester::new(PrbsPattern::Prbs7, 100);
+        let bits: Vec<bool> = (&mut noise).take(10_000).collect();
+        assert!(tester.process(&bits).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! Carrier-to-noise-density estimation for narrowband carriers.
+//!
+//! C/N0 compares the carrier power with the noise power in one hertz, so
+//! unlike an SNR it does not depend on the bandwidth it was measured in. The
+//! carrier is taken as the strongest spectral peak; the noise density comes
+//! from the median bin power, so a handful of other signals does not disturb
+//! it.
+
+use crate::block::Block;
+use crate::error::{DspError, Result};
+use crate::spectrum::{bin_frequency, windowed_spectrum, Window};
+use num_complex::Complex;
+use std::f64::consts::LN_2;
+
+/// One C/N0 measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cn0Estimate {
+    /// Carrier-to-noise density in dB-Hz.
+    pub cn0_db_hz: f64,
+    /// Carrier power in the input's units squared.
+    pub carrier_power: f64,
+    /// Noise power per hertz.
+    pub noise_density: f64,
+    /// Frequency of the carrier in Hz, to the nearest bin.
+    pub carrier_frequency: f64,
+}
+
+impl Cn0Estimate {
+    /// The SNR the carrier would have in a receiver of `bandwidth` Hz, in dB.
+    pub fn snr_db_in(&self, bandwidth: f64) -> f64 {
+        self.cn0_db_hz - 10.0 * bandwidth.log10()
+    }
+}
+
+/// Averages power spectra over several frames and reports C/N0 for each
+/// group of frames.
+#[derive(Debug, Clone)]
+pub struct Cn0Estimator {
+    sample_rate: f64,
+    fft_size: usize,
+    averages: usize,
+    window: Vec<f64>,
+    accumulated: Vec<f64>,
+    frames: usize,
+}
+
+impl Cn0Estimator {
+    /// Bins either side of the peak counted as carrier, enough to hold the
+    /// Hann main lobe of a tone that falls between bins.
+    const CARRIER_BINS: usize = 3;
+
+    /// Creates an estimator using `fft_size`-point frames and reporting once
+    /// every `averages` frames.
+    pub fn new(sample_rate: f64, fft_size: usize, averages: usize) -> Self {
+        Cn0Estimator {
+            sample_rate,
+            fft_size,
+            averages: averages.max(1),
+            window: Window::Hann.coefficients(fft_size),
+            accumulated: vec![0.0; fft_size],
+            frames: 0,
+        }
+    }
+
+    /// Adds a frame of exactly `fft_size` samples, returning an estimate when
+    /// it completes a group. Fails, changing nothing, for a frame of any
+    /// other length.
+    pub fn process_frame(&mut self, frame: &[Complex<f64>]) -> Result<Option<Cn0Estimate>> {
+        if frame.len() != self.fft_size {
+            return Err(DspError::InvalidLength {
+                expected: self.fft_size,
+                found: frame.len(),
+            }
+            .into());
+        }
+        let spectrum = windowed_spectrum(frame, &self.window);
+        for (total, bin) in self.accumulated.iter_mut().zip(&spectrum) {
+            *total += bin.norm_sqr();
+        }
+        self.frames += 1;
+        if self.frames < self.averages {
+            return Ok(None);
+        }
+        let estimate = self.estimate();
+        self.accumulated.iter_mut().for_each(|total| *total = 0.0);
+        self.frames = 0;
+        Ok(estimate)
+    }
+
+    fn estimate(&self) -> Option<Cn0Estimate> {
+        let size = self.fft_size;
+        if size < 4 * Self::CARRIER_BINS {
+            return None;
+        }
+        // Scaled so a tone's bins sum to its power and each bin holds the
+        // noise power in one bin width.
+        let gain = size as f64 * self.window.iter().map(|w| w * w).sum::<f64>();
+        let power: Vec<f64> = self
+            .accumulated
+            .iter()
+            .map(|total| total / (gain * self.frames as f64))
+            .collect();
+        let (peak, _) = power.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+        let mut sorted = power.clone();
+        sorted.sort_by(f64::total_cmp);
+        // The median of averaged noise power sits below its mean: by ln 2 for
+        // a single frame, and by about 1 - 1/(3K) for an average of K.
+        let correction = if self.frames == 1 {
+            LN_2
+        } else {
+            1.0 - 1.0 / (3.0 * self.frames as f64)
+        };
+        let noise_bin = sorted[size / 2] / correction;
+        let carrier_bins = 2 * Self::CARRIER_BINS + 1;
+        let in_carrier: f64 = (0..carrier_bins)
+            .map(|offset| power[(peak + size + offset - Self::CARRIER_BINS) % size])
+            .sum();
+        let carrier_power = in_carrier - noise_bin * carrier_bins as f64;
+        let noise_density = noise_bin * size as f64 / self.sample_rate;
+        if carrier_power <= 0.0 || noise_density <= 0.0 {
+            return None;
+        }
+        Some(Cn0Estimate {
+            cn0_db_hz: 10.0 * (carrier_power / noise_density).log10(),
+            carrier_power,
+            noise_density,
+            carrier_frequency: bin_frequency(peak as f64, size, self.sample_rate),
+        })
+    }
+}
+
+impl Block for Cn0Estimator {
+    type Input = Complex<f64>;
+    type Output = Cn0Estimate;
+
+    /// Consumes whole frames, leaving any remainder for the next call.
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Cn0Estimate>) -> usize {
+        let size = self.fft_size.max(1);
+        let frames = input.len() / size * size;
+        for frame in input[..frames].chunks_exact(size) {
+            output.extend(self.process_frame(frame).ok().flatten());
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_tone_in_known_noise() {
+        let rate = 10_000.0;
+        let samples: Vec<Complex<f64>> = complex_noise(1024 * 32, 11)
+            .into_iter()
+            .enumerate()
+            .map(|(n, noise)| noise + Complex::from_polar(1.0, 2.0 * PI * 1234.5 * n as f64 / rate))
+            .collect();
+        let mut estimator = Cn0Estimator::new(rate, 1024, 32);
+        assert!(estimator.process_frame(&samples[..1000]).is_err());
+        let mut estimates = Vec::new();
+        estimator.work(&samples, &mut estimates);
+        assert_eq!(estimates.len(), 1);
+        let estimate = estimates[0];
+        // Uniform I and Q in [-1, 1) give a noise power of 2/3 over 10 kHz.
+        let expected = 10.0 * (1.0 / (2.0 / 3.0 / rate)).log10();
+        assert_relative_eq!(estimate.cn0_db_hz, expected, epsilon = 0.5);
+        assert_relative_eq!(estimate.carrier_power, 1.0, epsilon = 0.1);
+        assert_relative_eq!(estimate.carrier_frequency, 1234.5, epsilon = 10.0);
+        assert_relative_eq!(estimate.snr_db_in(rate), expected - 40.0, epsilon = 0.5);
+    }
+}
@@ -0,0 +1,127 @@
+//! Error vector magnitude.
+
+use crate::constellation::Constellation;
+use crate::dsp::complex::mean_power;
+use num_complex::Complex;
+
+/// EVM over a block of symbols, as fractions of the reference RMS amplitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvmReport {
+    /// Root-mean-square error vector magnitude.
+    pub rms: f64,
+    /// Largest single error vector magnitude.
+    pub peak: f64,
+    /// Number of symbols measured.
+    pub symbols: usize,
+}
+
+impl EvmReport {
+    pub fn rms_percent(&self) -> f64 {
+        self.rms * 100.0
+    }
+
+    pub fn peak_percent(&self) -> f64 {
+        self.peak * 100.0
+    }
+
+    /// RMS EVM in dB, the form most standards specify limits in.
+    pub fn rms_db(&self) -> f64 {
+        20.0 * self.rms.log10()
+    }
+}
+
+fn report(
+    pairs: impl Iterator<Item = (Complex<f64>, Complex<f64>)>,
+    reference_power: f64,
+) -> Option<EvmReport> {
+    let (mut error_power, mut peak, mut symbols) = (0.0, 0.0f64, 0);
+    for (received, ideal) in pairs {
+        let error = (received - ideal).norm_sqr();
+        error_power += error;
+        peak = peak.max(error);
+        symbols += 1;
+    }
+    if symbols == 0 || reference_power <= 0.0 {
+        return None;
+    }
+    let reference_rms = reference_power.sqrt();
+    Some(EvmReport {
+        rms: (error_power / symbols as f64).sqrt() / reference_rms,
+        peak: peak.sqrt() / reference_rms,
+        symbols,
+    })
+}
+
+/// Decision-directed EVM: each received symbol is compared with its nearest
+/// constellation point.
+///
+/// The received symbols are first scaled so their average power matches the
+/// constellation's, as a measuring receiver's AGC would do. Symbols must
+/// already be timing- and carrier-recovered.
+pub fn evm(received: &[Complex<f64>], constellation: &Constellation) -> Option<EvmReport> {
+    if received.is_empty() || constellation.is_empty() {
+        return None;
+    }
+    let received_power = mean_power(received);
+    if received_power <= 0.0 {
+        return None;
+    }
+    let gain = (constellation.average_power() / received_power).sqrt();
+    let pairs = received.iter().map(|&symbol| {
+        let scaled = symbol * gain;
+        (
+            scaled,
+            constellation
+                .nearest(scaled)
+                .map_or(scaled, |(_, point)| point),
+        )
+    });
+    report(pairs, constellation.average_power())
+}
+
+/// Data-aided EVM against the known transmitted symbols, without any gain
+/// normalization. Extra symbols in the longer slice are ignored.
+pub fn evm_against(received: &[Complex<f64>], reference: &[Complex<f64>]) -> Option<EvmReport> {
+    let count = received.len().min(reference.len());
+    let reference_power = mean_power(&reference[..count]);
+    report(
+        received.iter().copied().zip(reference.iter().copied()),
+        reference_power,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_known_error_vectors() {
+        let qpsk = Constellation::qpsk();
+        let ideal: Vec<Complex<f64>> = (0..400).map(|n| qpsk.points()[n % 4]).collect();
+        // Push every other group of symbols outward and the rest inward by
+        // 0.1 on I, so the average power barely moves.
+        let received: Vec<Complex<f64>> = ideal
+            .iter()
+            .enumerate()
+            .map(|(n, &s)| s + Complex::new(if (n / 4) % 2 == 0 { 0.1 } else { -0.1 }, 0.0))
+            .collect();
+        let against = evm_against(&received, &ideal).unwrap();
+        assert_relative_eq!(against.rms, 0.1, epsilon = 1e-12);
+        assert_relative_eq!(against.peak, 0.1, epsilon = 1e-12);
+        assert_relative_eq!(against.rms_db(), -20.0, epsilon = 1e-9);
+        let decided = evm(&received, &qpsk).unwrap();
+        assert_relative_eq!(decided.rms, 0.1, epsilon = 5e-3);
+    }
+
+    #[test]
+    fn test_gain_is_normalized() {
+        let qam = Constellation::qam16();
+        let received: Vec<Complex<f64>> = qam.points().iter().map(|p| p * 3.7).collect();
+        let report = evm(&received, &qam).unwrap();
+        assert_relative_eq!(report.rms, 0.0, epsilon = 1e-12);
+        assert_eq!(report.symbols, 16);
+        assert_eq!(evm(&[], &qam), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Parameters that can be changed while a pipeline is running.
+
+/// A new value for a named parameter.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParamValue {
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+    Text(String),
+    /// A list of numbers, such as replacement filter taps.
+    Floats(Vec<f64>),
+}
+
+impl ParamValue {
+    /// The value as a number, converting integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ParamValue::Float(value) => Some(*value),
+            ParamValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ParamValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_floats(&self) -> Option<&[f64]> {
+        match self {
+            ParamValue::Floats(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<f64> for ParamValue {
+    fn from(value: f64) -> Self {
+        ParamValue::Float(value)
+    }
+}
+
+impl From<i64> for ParamValue {
+    fn from(value: i64) -> Self {
+        ParamValue::Int(value)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(value: bool) -> Self {
+        ParamValue::Bool(value)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(value: &str) -> Self {
+        ParamValue::Text(value.to_string())
+    }
+}
+
+impl From<Vec<f64>> for ParamValue {
+    fn from(values: Vec<f64>) -> Self {
+        ParamValue::Floats(values)
+    }
+}
+
+/// Why a parameter change was rejected.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ParamError {
+    /// The target has no parameter with this name.
+    #[error("unknown parameter {0}")]
+    Unknown(String),
+    /// The value has the wrong type or is out of range.
+    #[error("invalid value for {name}: {value:?}")]
+    InvalidValue { name: String, value: ParamValue },
+    /// The target accepted the value but could not apply it.
+    #[error("failed to set {name}: {reason}")]
+    Failed { name: String, reason: String },
+    /// The message was addressed to a node that does not exist.
+    #[error("no such flowgraph node")]
+    UnknownNode,
+    /// The flowgraph stopped before the change was applied.
+    #[error("the flowgraph is no longer running")]
+    Disconnected,
+}
+
+impl ParamError {
+    /// Shorthand for rejecting `value` for parameter `name`.
+    pub fn invalid(name: &str, value: &ParamValue) -> Self {
+        ParamError::InvalidValue {
+            name: name.to_string(),
+            value: value.clone(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! A bearing reading with the context most workflows need attached.
+
+use crate::io::readings::LoggedReading;
+
+/// A timestamped bearing with a quality score.
+///
+/// Plain `(angle, magnitude)` pairs, as taken by [`crate::average`], convert
+/// with a timestamp of zero and full quality, and `(timestamp, angle,
+/// magnitude)` triples with full quality.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reading {
+    /// Seconds, typically since the UNIX epoch.
+    pub timestamp: f64,
+    /// Degrees.
+    pub angle: f64,
+    pub magnitude: f64,
+    /// How far the reading can be trusted, from 0 (not at all) to 1, such as
+    /// from the SNR or the receiver's own lock indicator.
+    pub quality: f64,
+}
+
+impl Reading {
+    pub fn new(timestamp: f64, angle: f64, magnitude: f64) -> Self {
+        Reading {
+            timestamp,
+            angle,
+            magnitude,
+            quality: 1.0,
+        }
+    }
+
+    pub fn with_quality(mut self, quality: f64) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// The weight the reading carries in an average: its magnitude scaled by
+    /// its quality.
+    pub fn weight(&self) -> f64 {
+        self.magnitude * self.quality
+    }
+
+    /// The reading as the `(angle, weight)` pair taken by [`crate::average`]
+    /// and [`crate::stats`].
+    pub fn as_pair(&self) -> (f64, f64) {
+        (self.angle, self.weight())
+    }
+}
+
+impl From<(f64, f64)> for Reading {
+    fn from((angle, magnitude): (f64, f64)) -> Self {
+        Reading::new(0.0, angle, magnitude)
+    }
+}
+
+impl From<(f64, f64, f64)> for Reading {
+    fn from((timestamp, angle, magnitude): (f64, f64, f64)) -> Self {
+        Reading::new(timestamp, angle, magnitude)
+    }
+}
+
+impl From<LoggedReading> for Reading {
+    fn from(reading: LoggedReading) -> Self {
+        Reading::new(reading.timestamp, reading.angle, reading.magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions_and_weight() {
+        assert_eq!(Reading::from((90.0, 2.0)), Reading::new(0.0, 90.0, 2.0));
+        let reading = Reading::from((12.5, 90.0, 2.0)).with_quality(0.25);
+        assert_eq!(reading.timestamp, 12.5);
+        assert_eq!(reading.as_pair(), (90.0, 0.5));
+        let logged = LoggedReading {
+            timestamp: 3.0,
+            angle: 45.0,
+            magnitude: 1.0,
+        };
+        assert_eq!(Reading::from(logged).quality, 1.0);
+    }
+}
@@ -0,0 +1,321 @@
+//! Doppler prediction and correction for low-earth-orbit satellites.
+//!
+//! The orbit is propagated from a two-line element set with SGP4, rotated
+//! into the earth-fixed frame and compared with a fixed observer to give the
+//! range rate, and from it the Doppler shift at any carrier frequency. A
+//! [`DopplerCorrector`] then removes the predicted shift from a stream as it
+//! changes over the pass, so the downlink stays centred for the demodulators.
+//!
+//! Times are UNIX timestamps in seconds, ignoring leap seconds as SGP4 does.
+
+use crate::block::Block;
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+/// Speed of light in metres per second.
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+/// Earth's rotation rate in radians per second.
+const EARTH_ROTATION: f64 = 7.292_115e-5;
+/// WGS84 equatorial radius in kilometres and first eccentricity squared.
+const WGS84_A: f64 = 6378.137;
+const WGS84_E2: f64 = 6.694_379_990_14e-3;
+
+/// Errors from orbit prediction.
+#[derive(Debug, thiserror::Error)]
+pub enum SatelliteError {
+    /// The two-line element set could not be parsed.
+    #[error("invalid TLE: {0}")]
+    Tle(#[source] sgp4::TleError),
+    /// The elements are outside the range SGP4 can handle.
+    #[error("unusable orbital elements: {0}")]
+    Elements(#[source] sgp4::ElementsError),
+    /// Propagation to the requested time failed, typically because the orbit
+    /// has decayed by then.
+    #[error("orbit propagation failed: {0}")]
+    Propagation(#[source] sgp4::Error),
+}
+
+/// A ground station position on the WGS84 ellipsoid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Observer {
+    /// Degrees north.
+    pub latitude: f64,
+    /// Degrees east.
+    pub longitude: f64,
+    /// Metres above the ellipsoid.
+    pub altitude: f64,
+}
+
+impl Observer {
+    pub fn new(latitude: f64, longitude: f64, altitude: f64) -> Self {
+        Observer {
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    /// Earth-fixed position in kilometres.
+    fn position(&self) -> [f64; 3] {
+        let (lat, lon) = (self.latitude.to_radians(), self.longitude.to_radians());
+        let height = self.altitude / 1000.0;
+        let n = WGS84_A / (1.0 - WGS84_E2 * lat.sin().powi(2)).sqrt();
+        [
+            (n + height) * lat.cos() * lon.cos(),
+            (n + height) * lat.cos() * lon.sin(),
+            (n * (1.0 - WGS84_E2) + height) * lat.sin(),
+        ]
+    }
+
+    /// Unit vector pointing straight up from the observer.
+    fn up(&self) -> [f64; 3] {
+        let (lat, lon) = (self.latitude.to_radians(), self.longitude.to_radians());
+        [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+    }
+}
+
+/// Where a satellite is relative to the observer at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Track {
+    /// Distance in metres.
+    pub range: f64,
+    /// Rate of change of the distance in metres per second, positive when
+    /// the satellite is receding.
+    pub range_rate: f64,
+    /// Degrees above the horizon.
+    pub elevation: f64,
+}
+
+impl Track {
+    /// The Doppler shift in Hz of a carrier transmitted at `frequency` Hz.
+    pub fn doppler(&self, frequency: f64) -> f64 {
+        -self.range_rate / SPEED_OF_LIGHT * frequency
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Predicts a satellite's track and Doppler shift as seen by an observer.
+pub struct DopplerPredictor {
+    constants: sgp4::Constants,
+    epoch: f64,
+    epoch_years: f64,
+    observer: [f64; 3],
+    up: [f64; 3],
+}
+
+impl DopplerPredictor {
+    /// Creates a predictor from the two lines of a TLE.
+    pub fn from_tle(line1: &str, line2: &str, observer: Observer) -> Result<Self, SatelliteError> {
+        let elements = sgp4::Elements::from_tle(None, line1.as_bytes(), line2.as_bytes())
+            .map_err(SatelliteError::Tle)?;
+        let constants =
+            sgp4::Constants::from_elements(&elements).map_err(SatelliteError::Elements)?;
+        Ok(DopplerPredictor {
+            constants,
+            epoch: elements.datetime.and_utc().timestamp_micros() as f64 / 1e6,
+            epoch_years: elements.epoch(),
+            observer: observer.position(),
+            up: observer.up(),
+        })
+    }
+
+    /// The TLE epoch as a UNIX timestamp.
+    pub fn epoch(&self) -> f64 {
+        self.epoch
+    }
+
+    /// The satellite's track at `time`.
+    pub fn track(&self, time: f64) -> Result<Track, SatelliteError> {
+        let seconds = time - self.epoch;
+        let prediction = self
+            .constants
+            .propagate(sgp4::MinutesSinceEpoch(seconds / 60.0))
+            .map_err(SatelliteError::Propagation)?;
+        // Rotate from the true-equator frame SGP4 works in to earth-fixed.
+        let theta =
+            sgp4::iau_epoch_to_sidereal_time(self.epoch_years + seconds / (365.25 * 86_400.0));
+        let (sin, cos) = theta.sin_cos();
+        let [x, y, z] = prediction.position;
+        let [vx, vy, vz] = prediction.velocity;
+        let position = [cos * x + sin * y, -sin * x + cos * y, z];
+        let velocity = [
+            cos * vx + sin * vy + EARTH_ROTATION * position[1],
+            -sin * vx + cos * vy - EARTH_ROTATION * position[0],
+            vz,
+        ];
+        let relative = [
+            position[0] - self.observer[0],
+            position[1] - self.observer[1],
+            position[2] - self.observer[2],
+        ];
+        let range = dot(relative, relative).sqrt();
+        Ok(Track {
+            range: range * 1000.0,
+            range_rate: dot(relative, velocity) / range * 1000.0,
+            elevation: (dot(relative, self.up) / range).asin().to_degrees(),
+        })
+    }
+
+    /// The Doppler shift in Hz of a `frequency` Hz carrier at `time`.
+    pub fn doppler(&self, time: f64, frequency: f64) -> Result<f64, SatelliteError> {
+        Ok(self.track(time)?.doppler(frequency))
+    }
+
+    /// (time, shift) pairs every `step` seconds from `start` up to `end`.
+    pub fn doppler_curve(
+        &self,
+        start: f64,
+        end: f64,
+        step: f64,
+        frequency: f64,
+    ) -> Result<Vec<(f64, f64)>, SatelliteError> {
+        let count = ((end - start) / step).floor().max(0.0) as usize + 1;
+        (0..count)
+            .map(|n| {
+                let time = start + n as f64 * step;
+                Ok((time, self.doppler(time, frequency)?))
+            })
+            .collect()
+    }
+}
+
+/// Removes the predicted Doppler shift from a complex baseband stream centred
+/// on the satellite's nominal carrier.
+///
+/// The shift is recomputed every `update_interval` samples and held in
+/// between, which at LEO rates of change of a few tens of Hz per second is
+/// far below any demodulator's tracking range.
+pub struct DopplerCorrector {
+    predictor: DopplerPredictor,
+    carrier: f64,
+    sample_rate: f64,
+    start_time: f64,
+    position: u64,
+    update_interval: u64,
+    shift: f64,
+    phase: f64,
+}
+
+impl DopplerCorrector {
+    /// Creates a corrector for a `carrier` Hz downlink whose first sample was
+    /// taken at `start_time`.
+    pub fn new(
+        predictor: DopplerPredictor,
+        carrier: f64,
+        sample_rate: f64,
+        start_time: f64,
+    ) -> Self {
+        DopplerCorrector {
+            predictor,
+            carrier,
+            sample_rate,
+            start_time,
+            position: 0,
+            update_interval: (sample_rate / 100.0).max(1.0) as u64,
+            shift: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// Sets how many samples pass between Doppler updates.
+    pub fn set_update_interval(&mut self, samples: u64) {
+        self.update_interval = samples.max(1);
+    }
+
+    /// The shift currently being removed, in Hz.
+    pub fn shift(&self) -> f64 {
+        self.shift
+    }
+
+    /// Corrects one sample. If prediction fails the last shift is kept.
+    pub fn correct(&mut self, sample: Complex<f64>) -> Complex<f64> {
+        if self.position.is_multiple_of(self.update_interval) {
+            let time = self.start_time + self.position as f64 / self.sample_rate;
+            if let Ok(shift) = self.predictor.doppler(time, self.carrier) {
+                self.shift = shift;
+            }
+        }
+        self.position += 1;
+        let corrected = sample * Complex::from_polar(1.0, -self.phase);
+        self.phase = (self.phase + 2.0 * PI * self.shift / self.sample_rate).rem_euclid(2.0 * PI);
+        corrected
+    }
+}
+
+impl Block for DopplerCorrector {
+    type Input = Complex<f64>;
+    type Output = Complex<f64>;
+
+    fn work(&mut self, input: &[Complex<f64>], output: &mut Vec<Complex<f64>>) -> usize {
+        output.extend(input.iter().map(|&sample| self.correct(sample)));
+        input.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    const ISS_LINE1: &str = "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927";
+    const ISS_LINE2: &str = "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+
+    fn predictor() -> DopplerPredictor {
+        let observer = Observer::new(38.9, -77.0, 100.0);
+        DopplerPredictor::from_tle(ISS_LINE1, ISS_LINE2, observer).unwrap()
+    }
+
+    #[test]
+    fn test_range_rate_matches_change_in_range() {
+        let predictor = predictor();
+        for minutes in [0.0, 17.0, 45.0, 300.0] {
+            let time = predictor.epoch() + minutes * 60.0;
+            let before = predictor.track(time - 0.5).unwrap();
+            let after = predictor.track(time + 0.5).unwrap();
+            let track = predictor.track(time).unwrap();
+            assert_relative_eq!(track.range_rate, after.range - before.range, epsilon = 1.0);
+            // No LEO satellite closes faster than its orbital speed.
+            assert!(track.range_rate.abs() < 8000.0);
+        }
+    }
+
+    #[test]
+    fn test_shift_is_bounded_over_an_orbit() {
+        let predictor = predictor();
+        let start = predictor.epoch();
+        let curve = predictor
+            .doppler_curve(start, start + 5400.0, 10.0, 145.8e6)
+            .unwrap();
+        assert_eq!(curve.len(), 541);
+        assert!(curve.iter().all(|&(_, shift)| shift.abs() < 3_900.0));
+        assert!(curve.iter().any(|&(_, shift)| shift > 0.0));
+        assert!(curve.iter().any(|&(_, shift)| shift < 0.0));
+        assert!(
+            DopplerPredictor::from_tle("1 garbage", ISS_LINE2, Observer::new(0.0, 0.0, 0.0))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_corrector_removes_predicted_shift() {
+        let rate = 48_000.0;
+        let start = predictor().epoch() + 600.0;
+        let shift = predictor().doppler(start, 437e6).unwrap();
+        let mut corrector = DopplerCorrector::new(predictor(), 437e6, rate, start);
+        let mut output = Vec::new();
+        // A carrier arriving with the predicted shift, over 10 ms.
+        let input: Vec<Complex<f64>> = (0..480)
+            .map(|n| Complex::from_polar(1.0, 2.0 * PI * shift * n as f64 / rate))
+            .collect();
+        corrector.work(&input, &mut output);
+        assert_relative_eq!(corrector.shift(), shift);
+        for sample in &output {
+            assert_relative_eq!((sample - output[0]).norm(), 0.0, epsilon = 1e-6);
+        }
+    }
+}
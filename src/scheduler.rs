@@ -0,0 +1,351 @@
+//! Runs independent flowgraph branches on their own threads.
+//!
+//! Split a pipeline wherever it fans out into heavy, independent work — a
+//! channelizer feeding many demodulators, say — by ending one branch with a
+//! [`ChannelSink`] and starting the next with the matching [`ChannelSource`].
+//! Each branch is an ordinary [`Flowgraph`]; the [`Scheduler`] gives each one
+//! a thread and the bounded channel between them provides backpressure, or
+//! drops chunks under an explicit [`OverrunPolicy`] for real-time sources.
+
+use crate::buffer::{BoundedQueue, BufferPool, OverrunPolicy, SampleBlock};
+use crate::flowgraph::{Flowgraph, FlowgraphError};
+use crate::sink::Sink;
+use crate::source::Source;
+use std::sync::Arc;
+use std::thread;
+
+/// Creates a connection between two branches that holds at most `capacity`
+/// chunks in flight, blocking the sender while it is full.
+pub fn channel<T>(capacity: usize) -> (ChannelSink<T>, ChannelSource<T>) {
+    channel_with_policy(capacity, OverrunPolicy::Block)
+}
+
+/// Creates a connection between two branches that applies `policy` once
+/// `capacity` chunks are in flight. Chunks travel in pooled buffers, so a
+/// running connection does not allocate.
+pub fn channel_with_policy<T>(
+    capacity: usize,
+    policy: OverrunPolicy,
+) -> (ChannelSink<T>, ChannelSource<T>) {
+    let queue = Arc::new(BoundedQueue::new(capacity, policy));
+    (
+        ChannelSink {
+            queue: Arc::clone(&queue),
+            pool: BufferPool::new(0),
+        },
+        ChannelSource {
+            queue,
+            block: None,
+            offset: 0,
+        },
+    )
+}
+
+/// The downstream branch was dropped, so nothing will read the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("the receiving branch has disconnected")]
+pub struct Disconnected;
+
+/// Sends samples to another branch according to the channel's overrun policy.
+pub struct ChannelSink<T> {
+    queue: Arc<BoundedQueue<SampleBlock<T>>>,
+    pool: BufferPool<T>,
+}
+
+impl<T> ChannelSink<T> {
+    /// Chunks discarded because the receiving branch fell behind.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+}
+
+impl<T: Clone> Sink for ChannelSink<T> {
+    type Input = T;
+    type Error = Disconnected;
+
+    fn write(&mut self, input: &[T]) -> Result<(), Disconnected> {
+        let mut buffer = self.pool.get();
+        buffer.extend_from_slice(input);
+        let dropped = self.queue.dropped();
+        self.queue.push(buffer.freeze()).map_err(|_| {
+            trace_event!(warn, "channel receiver disconnected");
+            Disconnected
+        })?;
+        if self.queue.dropped() > dropped {
+            trace_event!(
+                warn,
+                total = self.queue.dropped(),
+                "channel overrun, dropped a chunk"
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<T> Drop for ChannelSink<T> {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+/// Receives samples from another branch. The source is exhausted once the
+/// sending branch finishes.
+pub struct ChannelSource<T> {
+    queue: Arc<BoundedQueue<SampleBlock<T>>>,
+    block: Option<SampleBlock<T>>,
+    offset: usize,
+}
+
+impl<T: Clone> Source for ChannelSource<T> {
+    type Sample = T;
+    type Error = std::convert::Infallible;
+
+    fn read(&mut self, buffer: &mut [T]) -> Result<usize, Self::Error> {
+        let block = loop {
+            match &self.block {
+                Some(block) if self.offset < block.len() => break block,
+                _ => match self.queue.pop() {
+                    Some(block) => {
+                        self.block = Some(block);
+                        self.offset = 0;
+                    }
+                    None => return Ok(0),
+                },
+            }
+        };
+        let count = buffer.len().min(block.len() - self.offset);
+        buffer[..count].clone_from_slice(&block[self.offset..self.offset + count]);
+        self.offset += count;
+        Ok(count)
+    }
+}
+
+impl<T> Drop for ChannelSource<T> {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+/// Where a branch's thread would prefer to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    /// Let the operating system choose.
+    Any,
+    /// Pin the thread to the given core index, if the platform allows it.
+    Core(usize),
+}
+
+/// Errors raised by a scheduled branch.
+#[derive(Debug, thiserror::Error)]
+pub enum SchedulerError {
+    /// A branch's flowgraph returned an error.
+    #[error("branch {name} failed: {error}")]
+    Branch {
+        name: String,
+        #[source]
+        error: FlowgraphError,
+    },
+    /// A branch's thread panicked.
+    #[error("branch {name} panicked")]
+    Panicked { name: String },
+    /// The operating system would not start a thread for a branch.
+    #[error("could not start branch {name}: {error}")]
+    Spawn {
+        name: String,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error(transparent)]
+    Disconnected(#[from] Disconnected),
+}
+
+struct Branch {
+    name: String,
+    flowgraph: Flowgraph,
+    affinity: Affinity,
+}
+
+/// Runs a set of flowgraph branches concurrently, one thread per branch.
+#[derive(Default)]
+pub struct Scheduler {
+    branches: Vec<Branch>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_branch(
+        &mut self,
+        name: impl Into<String>,
+        flowgraph: Flowgraph,
+        affinity: Affinity,
+    ) {
+        self.branches.push(Branch {
+            name: name.into(),
+            flowgraph,
+            affinity,
+        });
+    }
+
+    /// Runs every branch to completion and returns the first error, if any.
+    /// Affinity hints that the platform cannot honour are ignored.
+    ///
+    /// A branch whose thread cannot be started is dropped, closing its
+    /// channels, so the branches that did start still run to an end.
+    pub fn run(self) -> Result<(), SchedulerError> {
+        let cores = core_affinity::get_core_ids().unwrap_or_default();
+        let handles: Vec<_> = self
+            .branches
+            .into_iter()
+            .map(|branch| {
+                let core = match branch.affinity {
+                    Affinity::Any => None,
+                    Affinity::Core(index) => cores.get(index).copied(),
+                };
+                let Branch {
+                    name,
+                    mut flowgraph,
+                    ..
+                } = branch;
+                let spawned = thread::Builder::new().name(name.clone()).spawn(move || {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::info_span!(
+                        "branch",
+                        name = thread::current().name().unwrap_or_default()
+                    )
+                    .entered();
+                    if let Some(core) = core {
+                        core_affinity::set_for_current(core);
+                    }
+                    flowgraph.run()
+                });
+                (name, spawned)
+            })
+            .collect();
+
+        let mut first_error = None;
+        for (name, spawned) in handles {
+            let handle = match spawned {
+                Ok(handle) => handle,
+                Err(error) => {
+                    trace_event!(error, branch = %name, %error, "branch did not start");
+                    first_error.get_or_insert(SchedulerError::Spawn { name, error });
+                    continue;
+                }
+            };
+            let error = match handle.join() {
+                Ok(Ok(())) => {
+                    trace_event!(debug, branch = %name, "branch finished");
+                    continue;
+                }
+                Ok(Err(error)) => SchedulerError::Branch { name, error },
+                Err(_) => SchedulerError::Panicked { name },
+            };
+            trace_event!(error, %error, "branch failed");
+            first_error.get_or_insert(error);
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::map;
+    use std::sync::{Arc, Mutex};
+
+    struct Counter(u32);
+
+    impl Source for Counter {
+        type Sample = u32;
+        type Error = std::convert::Infallible;
+
+        fn read(&mut self, buffer: &mut [u32]) -> Result<usize, Self::Error> {
+            let count = buffer.len().min(self.0 as usize);
+            for slot in &mut buffer[..count] {
+                self.0 -= 1;
+                *slot = self.0;
+            }
+            Ok(count)
+        }
+    }
+
+    struct Collect(Arc<Mutex<Vec<u32>>>);
+
+    impl Sink for Collect {
+        type Input = u32;
+        type Error = std::convert::Infallible;
+
+        fn write(&mut self, input: &[u32]) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().extend_from_slice(input);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_branches_run_concurrently_through_channels() {
+        let (to_even, from_even) = channel(1);
+        let (to_odd, from_odd) = channel(1);
+        let mut front = Flowgraph::with_chunk_size(7);
+        let samples = front.add_source(Counter(100));
+        front.add_sink(to_even, &samples);
+        front.add_sink(to_odd, &samples);
+
+        let evens = Arc::new(Mutex::new(Vec::new()));
+        let mut even_branch = Flowgraph::new();
+        let doubled = even_branch.add_source(from_even);
+        let doubled = even_branch.add_block(map(|x: &u32| x * 2), &doubled);
+        even_branch.add_sink(Collect(Arc::clone(&evens)), &doubled);
+
+        let odds = Arc::new(Mutex::new(Vec::new()));
+        let mut odd_branch = Flowgraph::new();
+        let plus_one = odd_branch.add_source(from_odd);
+        let plus_one = odd_branch.add_block(map(|x: &u32| x * 2 + 1), &plus_one);
+        odd_branch.add_sink(Collect(Arc::clone(&odds)), &plus_one);
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add_branch("front", front, Affinity::Core(0));
+        scheduler.add_branch("even", even_branch, Affinity::Any);
+        scheduler.add_branch("odd", odd_branch, Affinity::Any);
+        scheduler.run().unwrap();
+
+        let evens = evens.lock().unwrap();
+        let odds = odds.lock().unwrap();
+        assert_eq!(evens.len(), 100);
+        assert_eq!(evens[0], 198);
+        assert_eq!(odds[99], 1);
+    }
+
+    #[test]
+    fn test_dropped_receiver_fails_the_sending_branch() {
+        let (sink, source) = channel::<u32>(1);
+        drop(source);
+        let mut flowgraph = Flowgraph::new();
+        let samples = flowgraph.add_source(Counter(10));
+        flowgraph.add_sink(sink, &samples);
+        let mut scheduler = Scheduler::new();
+        scheduler.add_branch("orphan", flowgraph, Affinity::Any);
+        assert!(matches!(
+            scheduler.run(),
+            Err(SchedulerError::Branch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_drop_oldest_channel_keeps_latest_chunks() {
+        let (mut sink, mut source) = channel_with_policy(2, OverrunPolicy::DropOldest);
+        for chunk in [[1, 2], [3, 4], [5, 6]] {
+            sink.write(&chunk).unwrap();
+        }
+        assert_eq!(sink.dropped(), 1);
+        drop(sink);
+        let mut buffer = [0u32; 8];
+        let mut received = Vec::new();
+        while let Ok(count @ 1..) = source.read(&mut buffer) {
+            received.extend_from_slice(&buffer[..count]);
+        }
+        assert_eq!(received, vec![3, 4, 5, 6]);
+    }
+}
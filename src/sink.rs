@@ -0,0 +1,23 @@
+//! Consumers at the end of a processing chain.
+
+use crate::param::{ParamError, ParamValue};
+
+pub mod constellation;
+pub mod null;
+pub mod vector;
+
+/// A consumer of samples, such as an audio device, a file or a display.
+pub trait Sink {
+    /// The type of sample this sink accepts.
+    type Input;
+    /// The error returned when the sink fails.
+    type Error;
+
+    /// Accepts the next samples.
+    fn write(&mut self, input: &[Self::Input]) -> Result<(), Self::Error>;
+
+    /// Changes a parameter such as an output gain while running.
+    fn set_parameter(&mut self, name: &str, _value: &ParamValue) -> Result<(), ParamError> {
+        Err(ParamError::Unknown(name.to_string()))
+    }
+}
@@ -0,0 +1,182 @@
+//! Capture of recovered symbols for constellation plots.
+
+use crate::dsp::complex::mean_power;
+use crate::param::{ParamError, ParamValue};
+use crate::sink::Sink;
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Settings for [`ConstellationSink`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstellationConfig {
+    /// Points in each batch handed to the display.
+    pub batch_size: usize,
+    /// Keep one symbol in every `decimation`, so the plot can keep up with a
+    /// fast symbol rate.
+    pub decimation: usize,
+    /// Scale each batch to unit average power, so it lines up with the ideal
+    /// points of a [`Constellation`](crate::constellation::Constellation)
+    /// whatever the receiver gain.
+    pub normalize: bool,
+    /// Completed batches kept for the display; older ones are discarded.
+    pub max_batches: usize,
+}
+
+impl ConstellationConfig {
+    pub fn new(batch_size: usize) -> Self {
+        ConstellationConfig {
+            batch_size,
+            decimation: 1,
+            normalize: false,
+            max_batches: 4,
+        }
+    }
+}
+
+/// The display side of a [`ConstellationSink`]. Clones share the same batches.
+#[derive(Debug, Clone, Default)]
+pub struct ConstellationBatches {
+    batches: Arc<Mutex<VecDeque<Vec<Complex<f64>>>>>,
+}
+
+impl ConstellationBatches {
+    /// Removes and returns every completed batch, oldest first.
+    pub fn drain(&self) -> Vec<Vec<Complex<f64>>> {
+        self.batches.lock().unwrap().drain(..).collect()
+    }
+
+    /// The most recent batch, leaving the queue untouched.
+    pub fn latest(&self) -> Option<Vec<Complex<f64>>> {
+        self.batches.lock().unwrap().back().cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.batches.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Collects symbols after timing recovery into decimated batches for
+/// plotting. Place it wherever a chain produces one sample per symbol.
+#[derive(Debug)]
+pub struct ConstellationSink {
+    config: ConstellationConfig,
+    skip: usize,
+    pending: Vec<Complex<f64>>,
+    batches: ConstellationBatches,
+}
+
+impl ConstellationSink {
+    pub fn new(config: ConstellationConfig) -> Self {
+        ConstellationSink {
+            pending: Vec::with_capacity(config.batch_size),
+            config,
+            skip: 0,
+            batches: ConstellationBatches::default(),
+        }
+    }
+
+    /// A handle for reading batches once the sink has moved into a flowgraph.
+    pub fn batches(&self) -> ConstellationBatches {
+        self.batches.clone()
+    }
+
+    fn finish_batch(&mut self) {
+        let mut batch = std::mem::replace(
+            &mut self.pending,
+            Vec::with_capacity(self.config.batch_size),
+        );
+        if self.config.normalize {
+            let power = mean_power(&batch);
+            if power > 0.0 {
+                let scale = power.sqrt().recip();
+                batch.iter_mut().for_each(|s| *s *= scale);
+            }
+        }
+        let mut batches = self.batches.batches.lock().unwrap();
+        batches.push_back(batch);
+        while batches.len() > self.config.max_batches.max(1) {
+            batches.pop_front();
+        }
+    }
+}
+
+impl Sink for ConstellationSink {
+    type Input = Complex<f64>;
+    type Error = Infallible;
+
+    fn write(&mut self, input: &[Complex<f64>]) -> Result<(), Infallible> {
+        for &symbol in input {
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            self.skip = self.config.decimation.max(1) - 1;
+            self.pending.push(symbol);
+            if self.pending.len() >= self.config.batch_size.max(1) {
+                self.finish_batch();
+            }
+        }
+        Ok(())
+    }
+
+    /// Accepts `decimation` and `normalize`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match (name, value) {
+            ("decimation", ParamValue::Int(decimation)) if *decimation >= 1 => {
+                self.config.decimation = *decimation as usize
+            }
+            ("normalize", ParamValue::Bool(normalize)) => self.config.normalize = *normalize,
+            ("decimation" | "normalize", _) => return Err(ParamError::invalid(name, value)),
+            _ => return Err(ParamError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_decimated_batches() {
+        let mut config = ConstellationConfig::new(4);
+        config.decimation = 3;
+        let mut sink = ConstellationSink::new(config);
+        let batches = sink.batches();
+        let symbols: Vec<Complex<f64>> = (0..30).map(|n| Complex::new(n as f64, 0.0)).collect();
+        sink.write(&symbols[..10]).unwrap();
+        sink.write(&symbols[10..]).unwrap();
+        let drained = batches.drain();
+        assert_eq!(drained.len(), 2);
+        let first: Vec<f64> = drained[0].iter().map(|s| s.re).collect();
+        assert_eq!(first, vec![0.0, 3.0, 6.0, 9.0]);
+        assert_eq!(drained[1][0].re, 12.0);
+        assert!(batches.is_empty());
+    }
+
+    #[test]
+    fn test_normalized_batches_have_unit_power() {
+        let mut config = ConstellationConfig::new(8);
+        config.normalize = true;
+        config.max_batches = 1;
+        let mut sink = ConstellationSink::new(config);
+        let symbols: Vec<Complex<f64>> = (0..24)
+            .map(|n| Complex::new(if n % 2 == 0 { 5.0 } else { -5.0 }, 5.0))
+            .collect();
+        sink.write(&symbols).unwrap();
+        assert_eq!(sink.batches().len(), 1);
+        let batch = sink.batches().latest().unwrap();
+        let power = batch.iter().map(|s| s.norm_sqr()).sum::<f64>() / batch.len() as f64;
+        assert_relative_eq!(power, 1.0, epsilon = 1e-12);
+        assert!(sink
+            .set_parameter("decimation", &ParamValue::Int(0))
+            .is_err());
+    }
+}
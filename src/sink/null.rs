@@ -0,0 +1,70 @@
+//! A sink that discards everything, for ports whose output is not wanted.
+
+use crate::sink::Sink;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+/// Accepts samples and drops them, counting how many it saw.
+///
+/// Connect it to a port that nothing else reads, such as a side output of a
+/// decoder, or to the end of a chain being benchmarked.
+#[derive(Debug, Clone)]
+pub struct NullSink<T> {
+    samples: u64,
+    _samples: PhantomData<fn(T)>,
+}
+
+impl<T> NullSink<T> {
+    pub fn new() -> Self {
+        NullSink {
+            samples: 0,
+            _samples: PhantomData,
+        }
+    }
+
+    /// Samples discarded so far.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+impl<T> Default for NullSink<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Sink for NullSink<T> {
+    type Input = T;
+    type Error = Infallible;
+
+    fn write(&mut self, input: &[T]) -> Result<(), Infallible> {
+        self.samples += input.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::probe::Probe;
+    use crate::flowgraph::Flowgraph;
+    use crate::source::null::NullSource;
+    use num_complex::Complex;
+
+    #[test]
+    fn test_null_chain_runs_to_completion() {
+        let mut flowgraph = Flowgraph::with_chunk_size(1000);
+        let zeros = flowgraph.add_source(NullSource::<Complex<f64>>::with_limit(10_000));
+        let probe = Probe::new(0.1);
+        let handle = probe.handle();
+        let probed = flowgraph.add_block(probe, &zeros);
+        flowgraph.add_sink(NullSink::new(), &probed);
+        flowgraph.run().unwrap();
+        assert_eq!(handle.samples(), 10_000);
+
+        let mut sink = NullSink::new();
+        sink.write(&[1, 2, 3]).unwrap();
+        assert_eq!(sink.samples(), 3);
+    }
+}
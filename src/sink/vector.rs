@@ -0,0 +1,118 @@
+//! Capturing a chain's output, for comparison in tests.
+
+use crate::sink::Sink;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// The reading side of a [`VectorSink`]. Clones share the same samples.
+#[derive(Debug)]
+pub struct VectorData<T> {
+    samples: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T> Clone for VectorData<T> {
+    fn clone(&self) -> Self {
+        VectorData {
+            samples: Arc::clone(&self.samples),
+        }
+    }
+}
+
+impl<T: Clone> VectorData<T> {
+    /// Every sample written so far.
+    pub fn samples(&self) -> Vec<T> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    /// Removes and returns every sample written so far.
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut *self.samples.lock().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keeps every sample written to it.
+#[derive(Debug)]
+pub struct VectorSink<T> {
+    data: VectorData<T>,
+}
+
+impl<T> VectorSink<T> {
+    pub fn new() -> Self {
+        VectorSink {
+            data: VectorData {
+                samples: Arc::new(Mutex::new(Vec::new())),
+            },
+        }
+    }
+
+    /// A handle for reading the samples once the sink has moved into a
+    /// flowgraph.
+    pub fn data(&self) -> VectorData<T> {
+        self.data.clone()
+    }
+}
+
+impl<T> Default for VectorSink<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Sink for VectorSink<T> {
+    type Input = T;
+    type Error = Infallible;
+
+    fn write(&mut self, input: &[T]) -> Result<(), Infallible> {
+        self.data.samples.lock().unwrap().extend_from_slice(input);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::map;
+    use crate::dsp::fir::Fir;
+    use crate::flowgraph::Flowgraph;
+    use crate::source::vector::{ImpulseSource, VectorSource};
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_captures_flowgraph_output() {
+        let sink = VectorSink::new();
+        let data = sink.data();
+        let mut flowgraph = Flowgraph::with_chunk_size(3);
+        let samples = flowgraph.add_source(VectorSource::new((1..=8).collect::<Vec<i32>>()));
+        let doubled = flowgraph.add_block(map(|x: &i32| x * 2), &samples);
+        flowgraph.add_sink(sink, &doubled);
+        flowgraph.run().unwrap();
+        assert_eq!(data.samples(), vec![2, 4, 6, 8, 10, 12, 14, 16]);
+        assert_eq!(data.take().len(), 8);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_impulse_response_is_the_taps() {
+        let taps = [0.25, 0.5, 0.25];
+        let sink = VectorSink::new();
+        let data = sink.data();
+        let mut flowgraph = Flowgraph::new();
+        let impulse = flowgraph.add_source(ImpulseSource::<f64>::new(5));
+        let filtered = flowgraph.add_block(Fir::<f64, f64>::new(&taps), &impulse);
+        flowgraph.add_sink(sink, &filtered);
+        flowgraph.run().unwrap();
+        let response = data.samples();
+        assert_eq!(response.len(), 5);
+        for (output, expected) in response.iter().zip(taps.iter().chain(&[0.0, 0.0])) {
+            assert_relative_eq!(output, expected);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Sample sources that feed IQ data into the crate's processing functions.
+
+use crate::param::{ParamError, ParamValue};
+
+pub mod airspy;
+pub mod bladerf;
+pub mod control;
+pub mod funcube;
+pub mod kiwisdr;
+pub mod null;
+pub mod pluto;
+pub mod tee;
+pub mod throttle;
+pub mod vector;
+
+/// A producer of samples, such as an SDR receiver or a capture file.
+pub trait Source {
+    /// The type of sample this source produces.
+    type Sample;
+    /// The error returned when the source fails.
+    type Error;
+
+    /// Fills `buffer` with the next samples and returns how many were written.
+    /// A return value of zero means the source is exhausted.
+    fn read(&mut self, buffer: &mut [Self::Sample]) -> Result<usize, Self::Error>;
+
+    /// Changes a parameter such as the tuner frequency while streaming.
+    fn set_parameter(&mut self, name: &str, _value: &ParamValue) -> Result<(), ParamError> {
+        Err(ParamError::Unknown(name.to_string()))
+    }
+}
@@ -0,0 +1,277 @@
+//! Airspy and Airspy Mini receivers.
+//!
+//! The gain, sample-rate and 12-bit sample handling is always compiled so raw
+//! captures can be converted without hardware. The device itself needs the
+//! `airspy` feature and libairspy.
+
+use crate::dsp::fir::{lowpass, Fir};
+use num_complex::Complex;
+
+#[cfg(feature = "airspy")]
+mod device;
+#[cfg(feature = "airspy")]
+pub use device::Airspy;
+
+/// Highest LNA gain step.
+pub const MAX_LNA_GAIN: u8 = 14;
+/// Highest mixer gain step.
+pub const MAX_MIXER_GAIN: u8 = 15;
+/// Highest VGA (IF) gain step.
+pub const MAX_VGA_GAIN: u8 = 15;
+/// Highest step of the linearity and sensitivity gain presets.
+pub const MAX_PRESET_GAIN: u8 = 21;
+
+/// Airspy hardware variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Airspy,
+    AirspyMini,
+}
+
+impl Model {
+    /// IQ sample rates supported by the stock firmware, highest first.
+    pub fn sample_rates(self) -> &'static [u32] {
+        match self {
+            Model::Airspy => &[10_000_000, 2_500_000],
+            Model::AirspyMini => &[6_000_000, 3_000_000],
+        }
+    }
+
+    /// Identifies the model from the sample rates reported by the device.
+    pub fn from_sample_rates(rates: &[u32]) -> Model {
+        if rates
+            .iter()
+            .any(|rate| Model::AirspyMini.sample_rates().contains(rate))
+        {
+            Model::AirspyMini
+        } else {
+            Model::Airspy
+        }
+    }
+}
+
+/// Picks the lowest supported rate that is at least `requested`, falling back
+/// to the highest supported rate when `requested` exceeds them all.
+pub fn select_sample_rate(supported: &[u32], requested: u32) -> Option<u32> {
+    supported
+        .iter()
+        .copied()
+        .filter(|&rate| rate >= requested)
+        .min()
+        .or_else(|| supported.iter().copied().max())
+}
+
+/// How the receiver's gain stages are controlled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainMode {
+    /// LNA and mixer AGC enabled, VGA at the given step.
+    Agc { vga: u8 },
+    /// Preset favouring strong-signal handling, `0..=21`.
+    Linearity(u8),
+    /// Preset favouring weak-signal reception, `0..=21`.
+    Sensitivity(u8),
+    /// Individual gain steps for each stage.
+    Manual { lna: u8, mixer: u8, vga: u8 },
+}
+
+impl GainMode {
+    /// Checks that every gain step is within the range the hardware accepts.
+    pub fn validate(self) -> Result<Self, AirspyError> {
+        let valid = match self {
+            GainMode::Agc { vga } => vga <= MAX_VGA_GAIN,
+            GainMode::Linearity(gain) | GainMode::Sensitivity(gain) => gain <= MAX_PRESET_GAIN,
+            GainMode::Manual { lna, mixer, vga } => {
+                lna <= MAX_LNA_GAIN && mixer <= MAX_MIXER_GAIN && vga <= MAX_VGA_GAIN
+            }
+        };
+        if valid {
+            Ok(self)
+        } else {
+            Err(AirspyError::InvalidGain(self))
+        }
+    }
+}
+
+/// Sample format requested from the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Complex float samples converted by libairspy.
+    Float32Iq,
+    /// Native 12-bit real ADC samples at twice the IQ rate, converted here.
+    Raw(Packing),
+}
+
+/// How raw 12-bit ADC samples are laid out in a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packing {
+    /// One sample per 16-bit word.
+    Unpacked,
+    /// Eight samples per three 32-bit words, which saves USB bandwidth.
+    Packed,
+}
+
+/// Errors raised by the Airspy source.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AirspyError {
+    /// A libairspy call returned an error code.
+    #[error("{operation} failed with libairspy error {code}")]
+    Device { operation: &'static str, code: i32 },
+    /// A gain step is out of range.
+    #[error("gain out of range: {0:?}")]
+    InvalidGain(GainMode),
+    /// The device supports none of the requested sample rates.
+    #[error("unsupported sample rate: {0} Hz")]
+    UnsupportedSampleRate(u32),
+    /// Samples were requested before streaming was started.
+    #[error("the device is not streaming")]
+    NotStreaming,
+    /// libairspy stopped streaming on its own, as when the device is
+    /// unplugged. `Airspy::stop` still has to be called, or the receiver
+    /// dropped, to clean up.
+    #[error("the device stopped streaming")]
+    StreamEnded,
+}
+
+/// Unpacks 12-bit samples stored eight to every three 32-bit words.
+/// Returns the number of samples written to `output`.
+pub fn unpack_12bit(input: &[u32], output: &mut [u16]) -> usize {
+    let mut written = 0;
+    for (words, samples) in input.chunks_exact(3).zip(output.chunks_exact_mut(8)) {
+        let (a, b, c) = (words[0], words[1], words[2]);
+        samples[0] = ((a >> 20) & 0xfff) as u16;
+        samples[1] = ((a >> 8) & 0xfff) as u16;
+        samples[2] = (((a & 0xff) << 4) | ((b >> 28) & 0xf)) as u16;
+        samples[3] = ((b >> 16) & 0xfff) as u16;
+        samples[4] = ((b >> 4) & 0xfff) as u16;
+        samples[5] = (((b & 0xf) << 8) | ((c >> 24) & 0xff)) as u16;
+        samples[6] = ((c >> 12) & 0xfff) as u16;
+        samples[7] = (c & 0xfff) as u16;
+        written += 8;
+    }
+    written
+}
+
+/// Converts an unsigned 12-bit ADC code to a value in `[-1, 1)`.
+pub fn raw_to_real(code: u16) -> f64 {
+    (f64::from(code & 0xfff) - 2048.0) / 2048.0
+}
+
+/// Converts the real ADC stream to complex baseband at half the rate.
+///
+/// The tuner places its IF at a quarter of the ADC rate, so the stream is
+/// shifted down by fs/4, low-pass filtered and decimated by two.
+#[derive(Debug, Clone)]
+pub struct RealToIq {
+    fir: Fir<Complex<f64>>,
+    phase: usize,
+}
+
+impl RealToIq {
+    const TAPS: usize = 31;
+
+    pub fn new() -> Self {
+        RealToIq {
+            fir: Fir::new(&lowpass(Self::TAPS, 0.25)),
+            phase: 0,
+        }
+    }
+
+    /// Converts `input` real samples, appending the resulting IQ samples to `output`.
+    pub fn process(
+        &mut self,
+        input: impl IntoIterator<Item = f64>,
+        output: &mut Vec<Complex<f64>>,
+    ) {
+        for sample in input {
+            // Multiplying by e^(-j*pi*n/2) cycles through 1, -j, -1, j.
+            let mixed = match self.phase % 4 {
+                0 => Complex::new(sample, 0.0),
+                1 => Complex::new(0.0, -sample),
+                2 => Complex::new(-sample, 0.0),
+                _ => Complex::new(0.0, sample),
+            };
+            self.fir.push(mixed);
+            self.phase = (self.phase + 1) % 4;
+            if self.phase.is_multiple_of(2) {
+                output.push(self.fir.output());
+            }
+        }
+    }
+}
+
+impl Default for RealToIq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_unpack_12bit() {
+        let samples: [u16; 8] = [0x123, 0x456, 0x789, 0xabc, 0xdef, 0x012, 0x345, 0xfff];
+        let mut words = [0u32; 3];
+        let mut bits: u128 = 0;
+        for sample in samples {
+            bits = (bits << 12) | u128::from(sample);
+        }
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = (bits >> (64 - 32 * i)) as u32;
+        }
+        let mut unpacked = [0u16; 8];
+        assert_eq!(unpack_12bit(&words, &mut unpacked), 8);
+        assert_eq!(unpacked, samples);
+    }
+
+    #[test]
+    fn test_raw_to_real() {
+        assert_relative_eq!(raw_to_real(2048), 0.0);
+        assert_relative_eq!(raw_to_real(0), -1.0);
+        assert_relative_eq!(raw_to_real(4095), 2047.0 / 2048.0);
+    }
+
+    #[test]
+    fn test_select_sample_rate() {
+        let rates = Model::Airspy.sample_rates();
+        assert_eq!(select_sample_rate(rates, 2_000_000), Some(2_500_000));
+        assert_eq!(select_sample_rate(rates, 3_000_000), Some(10_000_000));
+        assert_eq!(select_sample_rate(rates, 20_000_000), Some(10_000_000));
+        assert_eq!(select_sample_rate(&[], 1_000_000), None);
+        assert_eq!(
+            Model::from_sample_rates(&[6_000_000, 3_000_000]),
+            Model::AirspyMini
+        );
+    }
+
+    #[test]
+    fn test_gain_validation() {
+        assert!(GainMode::Linearity(21).validate().is_ok());
+        assert!(GainMode::Sensitivity(22).validate().is_err());
+        assert!(GainMode::Manual {
+            lna: 15,
+            mixer: 0,
+            vga: 0
+        }
+        .validate()
+        .is_err());
+        assert!(GainMode::Agc { vga: 15 }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_real_to_iq_shifts_quarter_rate_if_to_baseband() {
+        // A tone 0.05 * fs above the fs/4 IF lands at 0.1 of the IQ rate.
+        let input = (0..2000).map(|n| (2.0 * PI * 0.3 * n as f64).cos());
+        let mut output = Vec::new();
+        let mut converter = RealToIq::new();
+        converter.process(input, &mut output);
+        assert_eq!(output.len(), 1000);
+        for pair in output[100..].windows(2) {
+            let step = (pair[1] * pair[0].conj()).arg();
+            assert_relative_eq!(step, 2.0 * PI * 0.1, epsilon = 1e-3);
+        }
+    }
+}
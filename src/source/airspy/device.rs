@@ -0,0 +1,531 @@
+use super::{
+    select_sample_rate, AirspyError, GainMode, Model, Packing, RealToIq, SampleFormat,
+    MAX_LNA_GAIN, MAX_MIXER_GAIN, MAX_VGA_GAIN,
+};
+use crate::error::DeviceError;
+use crate::param::{ParamError, ParamValue};
+use crate::source::control::{
+    check_frequency, correct_frequency, find_stage, uncorrect_frequency, GainStage, SdrDevice,
+};
+use crate::source::Source;
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::os::raw::{c_int, c_void};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How long a read waits for samples before checking that libairspy is
+/// still streaming.
+const STREAM_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+mod ffi {
+    use std::os::raw::{c_int, c_void};
+
+    #[repr(C)]
+    pub struct AirspyDevice {
+        _private: [u8; 0],
+    }
+
+    pub const TRUE: c_int = 1;
+    pub const SAMPLE_FLOAT32_IQ: c_int = 0;
+    pub const SAMPLE_RAW: c_int = 5;
+
+    #[repr(C)]
+    pub struct AirspyTransfer {
+        pub device: *mut AirspyDevice,
+        pub ctx: *mut c_void,
+        pub samples: *mut c_void,
+        pub sample_count: c_int,
+        pub dropped_samples: u64,
+        pub sample_type: c_int,
+    }
+
+    pub type SampleBlockCallback = extern "C" fn(transfer: *mut AirspyTransfer) -> c_int;
+
+    #[link(name = "airspy")]
+    extern "C" {
+        pub fn airspy_open(device: *mut *mut AirspyDevice) -> c_int;
+        pub fn airspy_open_sn(device: *mut *mut AirspyDevice, serial_number: u64) -> c_int;
+        pub fn airspy_close(device: *mut AirspyDevice) -> c_int;
+        pub fn airspy_get_samplerates(
+            device: *mut AirspyDevice,
+            buffer: *mut u32,
+            len: u32,
+        ) -> c_int;
+        pub fn airspy_set_samplerate(device: *mut AirspyDevice, samplerate: u32) -> c_int;
+        pub fn airspy_set_sample_type(device: *mut AirspyDevice, sample_type: c_int) -> c_int;
+        pub fn airspy_set_packing(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_freq(device: *mut AirspyDevice, freq_hz: u32) -> c_int;
+        pub fn airspy_set_lna_gain(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_mixer_gain(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_vga_gain(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_lna_agc(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_mixer_agc(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_linearity_gain(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_sensitivity_gain(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_set_rf_bias(device: *mut AirspyDevice, value: u8) -> c_int;
+        pub fn airspy_start_rx(
+            device: *mut AirspyDevice,
+            callback: SampleBlockCallback,
+            rx_ctx: *mut c_void,
+        ) -> c_int;
+        pub fn airspy_stop_rx(device: *mut AirspyDevice) -> c_int;
+        pub fn airspy_is_streaming(device: *mut AirspyDevice) -> c_int;
+    }
+}
+
+fn check(operation: &'static str, code: c_int) -> Result<(), AirspyError> {
+    if code == 0 {
+        Ok(())
+    } else {
+        Err(AirspyError::Device { operation, code })
+    }
+}
+
+/// Samples handed from the libairspy thread to the reader.
+struct Buffered {
+    samples: VecDeque<Complex<f64>>,
+    capacity: usize,
+    dropped: u64,
+    format: SampleFormat,
+    converter: RealToIq,
+    unpacked: Vec<u16>,
+}
+
+struct Shared {
+    state: Mutex<Buffered>,
+    ready: Condvar,
+}
+
+/// An open Airspy or Airspy Mini receiver.
+pub struct Airspy {
+    device: *mut ffi::AirspyDevice,
+    model: Model,
+    sample_rates: Vec<u32>,
+    sample_rate: u32,
+    format: SampleFormat,
+    streaming: bool,
+    shared: Arc<Shared>,
+    /// Dropped samples already reported as an overrun.
+    reported_dropped: u64,
+    /// The reference oscillator's error, for [`SdrDevice::set_frequency`].
+    ppm: f64,
+}
+
+// libairspy allows a device handle to be driven from any one thread at a time.
+unsafe impl Send for Airspy {}
+
+impl Airspy {
+    /// Opens the first available device.
+    pub fn open() -> Result<Self, AirspyError> {
+        let mut device = std::ptr::null_mut();
+        check("airspy_open", unsafe { ffi::airspy_open(&mut device) })?;
+        Self::from_handle(device)
+    }
+
+    /// Opens the device with the given serial number.
+    pub fn open_serial(serial_number: u64) -> Result<Self, AirspyError> {
+        let mut device = std::ptr::null_mut();
+        check("airspy_open_sn", unsafe {
+            ffi::airspy_open_sn(&mut device, serial_number)
+        })?;
+        Self::from_handle(device)
+    }
+
+    fn from_handle(device: *mut ffi::AirspyDevice) -> Result<Self, AirspyError> {
+        let mut count = 0u32;
+        let sample_rates = unsafe {
+            check(
+                "airspy_get_samplerates",
+                ffi::airspy_get_samplerates(device, &mut count, 0),
+            )
+            .and_then(|()| {
+                let mut rates = vec![0u32; count as usize];
+                check(
+                    "airspy_get_samplerates",
+                    ffi::airspy_get_samplerates(device, rates.as_mut_ptr(), count),
+                )
+                .map(|()| rates)
+            })
+        };
+        let sample_rates = match sample_rates {
+            Ok(rates) => rates,
+            Err(error) => {
+                unsafe { ffi::airspy_close(device) };
+                return Err(error);
+            }
+        };
+        let model = Model::from_sample_rates(&sample_rates);
+        let sample_rate = sample_rates
+            .first()
+            .copied()
+            .unwrap_or(model.sample_rates()[0]);
+        let format = SampleFormat::Float32Iq;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(Buffered {
+                samples: VecDeque::new(),
+                capacity: sample_rate as usize,
+                dropped: 0,
+                format,
+                converter: RealToIq::new(),
+                unpacked: Vec::new(),
+            }),
+            ready: Condvar::new(),
+        });
+        let mut airspy = Airspy {
+            device,
+            model,
+            sample_rates,
+            sample_rate,
+            format,
+            streaming: false,
+            shared,
+            reported_dropped: 0,
+            ppm: 0.0,
+        };
+        airspy.set_sample_rate(sample_rate)?;
+        Ok(airspy)
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// IQ sample rates reported by the device.
+    pub fn sample_rates(&self) -> &[u32] {
+        &self.sample_rates
+    }
+
+    /// The current IQ sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Selects the closest supported rate at or above `requested` and returns it.
+    pub fn set_sample_rate(&mut self, requested: u32) -> Result<u32, AirspyError> {
+        let rate = select_sample_rate(&self.sample_rates, requested)
+            .ok_or(AirspyError::UnsupportedSampleRate(requested))?;
+        check("airspy_set_samplerate", unsafe {
+            ffi::airspy_set_samplerate(self.device, rate)
+        })?;
+        self.sample_rate = rate;
+        // Buffer up to one second of samples before dropping the oldest.
+        self.shared.state.lock().unwrap().capacity = rate as usize;
+        Ok(rate)
+    }
+
+    pub fn set_sample_format(&mut self, format: SampleFormat) -> Result<(), AirspyError> {
+        let (sample_type, packing) = match format {
+            SampleFormat::Float32Iq => (ffi::SAMPLE_FLOAT32_IQ, 0),
+            SampleFormat::Raw(Packing::Unpacked) => (ffi::SAMPLE_RAW, 0),
+            SampleFormat::Raw(Packing::Packed) => (ffi::SAMPLE_RAW, 1),
+        };
+        unsafe {
+            check(
+                "airspy_set_sample_type",
+                ffi::airspy_set_sample_type(self.device, sample_type),
+            )?;
+            check(
+                "airspy_set_packing",
+                ffi::airspy_set_packing(self.device, packing),
+            )?;
+        }
+        self.format = format;
+        let mut state = self.shared.state.lock().unwrap();
+        state.format = format;
+        state.converter = RealToIq::new();
+        Ok(())
+    }
+
+    /// Tunes the receiver to `frequency` Hz.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<(), AirspyError> {
+        check("airspy_set_freq", unsafe {
+            ffi::airspy_set_freq(self.device, frequency)
+        })
+    }
+
+    pub fn set_gain(&mut self, mode: GainMode) -> Result<(), AirspyError> {
+        let device = self.device;
+        unsafe {
+            match mode.validate()? {
+                GainMode::Agc { vga } => {
+                    check("airspy_set_lna_agc", ffi::airspy_set_lna_agc(device, 1))?;
+                    check("airspy_set_mixer_agc", ffi::airspy_set_mixer_agc(device, 1))?;
+                    check("airspy_set_vga_gain", ffi::airspy_set_vga_gain(device, vga))
+                }
+                GainMode::Linearity(gain) => check(
+                    "airspy_set_linearity_gain",
+                    ffi::airspy_set_linearity_gain(device, gain),
+                ),
+                GainMode::Sensitivity(gain) => check(
+                    "airspy_set_sensitivity_gain",
+                    ffi::airspy_set_sensitivity_gain(device, gain),
+                ),
+                GainMode::Manual { lna, mixer, vga } => {
+                    check("airspy_set_lna_agc", ffi::airspy_set_lna_agc(device, 0))?;
+                    check("airspy_set_mixer_agc", ffi::airspy_set_mixer_agc(device, 0))?;
+                    check("airspy_set_lna_gain", ffi::airspy_set_lna_gain(device, lna))?;
+                    check(
+                        "airspy_set_mixer_gain",
+                        ffi::airspy_set_mixer_gain(device, mixer),
+                    )?;
+                    check("airspy_set_vga_gain", ffi::airspy_set_vga_gain(device, vga))
+                }
+            }
+        }
+    }
+
+    /// Switches the antenna-port bias tee on or off.
+    pub fn set_bias_tee(&mut self, enabled: bool) -> Result<(), AirspyError> {
+        check("airspy_set_rf_bias", unsafe {
+            ffi::airspy_set_rf_bias(self.device, u8::from(enabled))
+        })
+    }
+
+    pub fn start(&mut self) -> Result<(), AirspyError> {
+        if self.streaming {
+            return Ok(());
+        }
+        let context = Arc::as_ptr(&self.shared) as *mut c_void;
+        check("airspy_start_rx", unsafe {
+            ffi::airspy_start_rx(self.device, rx_callback, context)
+        })?;
+        self.streaming = true;
+        trace_event!(info, model = ?self.model, sample_rate = self.sample_rate, "Airspy streaming");
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<(), AirspyError> {
+        if !self.streaming {
+            return Ok(());
+        }
+        self.streaming = false;
+        self.shared.ready.notify_all();
+        check("airspy_stop_rx", unsafe {
+            ffi::airspy_stop_rx(self.device)
+        })
+    }
+
+    /// Samples discarded because the reader fell behind, including those
+    /// dropped inside libairspy.
+    pub fn dropped_samples(&self) -> u64 {
+        self.shared.state.lock().unwrap().dropped
+    }
+}
+
+impl Source for Airspy {
+    type Sample = Complex<f64>;
+    type Error = AirspyError;
+
+    /// Blocks until at least one sample is available. Fails if libairspy
+    /// stops streaming meanwhile, as when the device is unplugged.
+    fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, AirspyError> {
+        if !self.streaming {
+            return Err(AirspyError::NotStreaming);
+        }
+        let mut state = self.shared.state.lock().unwrap();
+        while state.samples.is_empty() {
+            let (next, timeout) = self
+                .shared
+                .ready
+                .wait_timeout(state, STREAM_CHECK_INTERVAL)
+                .unwrap();
+            state = next;
+            if timeout.timed_out()
+                && state.samples.is_empty()
+                && unsafe { ffi::airspy_is_streaming(self.device) } != ffi::TRUE
+            {
+                return Err(AirspyError::StreamEnded);
+            }
+        }
+        if state.dropped > self.reported_dropped {
+            trace_event!(
+                warn,
+                dropped = state.dropped - self.reported_dropped,
+                total = state.dropped,
+                "Airspy overrun"
+            );
+            self.reported_dropped = state.dropped;
+        }
+        let count = buffer.len().min(state.samples.len());
+        for (slot, sample) in buffer.iter_mut().zip(state.samples.drain(..count)) {
+            *slot = sample;
+        }
+        Ok(count)
+    }
+
+    /// Accepts `frequency` in Hz and `bias_tee` as a boolean.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        let failed = |error: AirspyError| ParamError::Failed {
+            name: name.to_string(),
+            reason: error.to_string(),
+        };
+        match name {
+            "frequency" => {
+                let frequency = value
+                    .as_f64()
+                    .filter(|frequency| (0.0..=u32::MAX as f64).contains(frequency))
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                self.set_frequency(frequency as u32).map_err(failed)
+            }
+            "bias_tee" => {
+                let enabled = value
+                    .as_bool()
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                self.set_bias_tee(enabled).map_err(failed)
+            }
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// Gains are in the device's steps: 0 to 14 for the LNA and 0 to 15 for
+/// the mixer and VGA.
+impl SdrDevice for Airspy {
+    fn name(&self) -> String {
+        match self.model {
+            Model::Airspy => "Airspy".to_string(),
+            Model::AirspyMini => "Airspy Mini".to_string(),
+        }
+    }
+
+    fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+        vec![(24e6, 1.8e9)]
+    }
+
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+        check_frequency(&self.frequency_ranges(), frequency)?;
+        let tuned = correct_frequency(frequency, self.ppm).round() as u32;
+        Airspy::set_frequency(self, tuned)?;
+        Ok(uncorrect_frequency(f64::from(tuned), self.ppm))
+    }
+
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+        Ok(f64::from(Airspy::set_sample_rate(
+            self,
+            rate.round() as u32,
+        )?))
+    }
+
+    fn gain_stages(&self) -> Vec<GainStage> {
+        vec![
+            GainStage::new("lna", 0.0, f64::from(MAX_LNA_GAIN), 1.0),
+            GainStage::new("mixer", 0.0, f64::from(MAX_MIXER_GAIN), 1.0),
+            GainStage::new("vga", 0.0, f64::from(MAX_VGA_GAIN), 1.0),
+        ]
+    }
+
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError> {
+        let step = find_stage(&self.gain_stages(), stage)?.quantize(gain)? as u8;
+        let device = self.device;
+        unsafe {
+            match stage {
+                "lna" => {
+                    check("airspy_set_lna_agc", ffi::airspy_set_lna_agc(device, 0))?;
+                    check(
+                        "airspy_set_lna_gain",
+                        ffi::airspy_set_lna_gain(device, step),
+                    )?;
+                }
+                "mixer" => {
+                    check("airspy_set_mixer_agc", ffi::airspy_set_mixer_agc(device, 0))?;
+                    check(
+                        "airspy_set_mixer_gain",
+                        ffi::airspy_set_mixer_gain(device, step),
+                    )?;
+                }
+                _ => check(
+                    "airspy_set_vga_gain",
+                    ffi::airspy_set_vga_gain(device, step),
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches the LNA and mixer AGC; the VGA keeps its setting.
+    fn set_agc(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        let value = u8::from(enabled);
+        unsafe {
+            check(
+                "airspy_set_lna_agc",
+                ffi::airspy_set_lna_agc(self.device, value),
+            )?;
+            check(
+                "airspy_set_mixer_agc",
+                ffi::airspy_set_mixer_agc(self.device, value),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn set_bias_tee(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        Ok(Airspy::set_bias_tee(self, enabled)?)
+    }
+
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+}
+
+impl Drop for Airspy {
+    fn drop(&mut self) {
+        let _ = self.stop();
+        unsafe { ffi::airspy_close(self.device) };
+    }
+}
+
+extern "C" fn rx_callback(transfer: *mut ffi::AirspyTransfer) -> c_int {
+    let transfer = unsafe { &*transfer };
+    let shared = unsafe { &*(transfer.ctx as *const Shared) };
+    let count = transfer.sample_count.max(0) as usize;
+    let mut guard = shared.state.lock().unwrap();
+    let state = &mut *guard;
+    state.dropped += transfer.dropped_samples;
+    match state.format {
+        SampleFormat::Float32Iq => {
+            let iq =
+                unsafe { std::slice::from_raw_parts(transfer.samples as *const f32, count * 2) };
+            state.samples.extend(
+                iq.chunks_exact(2)
+                    .map(|pair| Complex::new(f64::from(pair[0]), f64::from(pair[1]))),
+            );
+        }
+        SampleFormat::Raw(packing) => {
+            // `sample_count` counts ADC samples, however they are packed.
+            let mut converted = Vec::with_capacity(count / 2);
+            match packing {
+                Packing::Unpacked => {
+                    let raw = unsafe {
+                        std::slice::from_raw_parts(transfer.samples as *const u16, count)
+                    };
+                    state.converter.process(
+                        raw.iter().map(|&code| super::raw_to_real(code)),
+                        &mut converted,
+                    );
+                }
+                Packing::Packed => {
+                    let words = unsafe {
+                        std::slice::from_raw_parts(transfer.samples as *const u32, count * 3 / 8)
+                    };
+                    state.unpacked.resize(count, 0);
+                    let unpacked = super::unpack_12bit(words, &mut state.unpacked);
+                    let codes = state.unpacked[..unpacked]
+                        .iter()
+                        .map(|&code| super::raw_to_real(code));
+                    state.converter.process(codes, &mut converted);
+                }
+            }
+            state.samples.extend(converted);
+        }
+    }
+    let excess = state.samples.len().saturating_sub(state.capacity);
+    if excess > 0 {
+        state.samples.drain(..excess);
+        state.dropped += excess as u64;
+    }
+    drop(guard);
+    shared.ready.notify_one();
+    0
+}
@@ -0,0 +1,183 @@
+//! Nuand bladeRF transceivers, receiving and transmitting at once.
+//!
+//! The channel numbering, stream settings and SC16 Q11 sample conversion
+//! are always compiled, so captures in the bladeRF's own format can be
+//! read without hardware. The device itself needs the `bladerf` feature and
+//! libbladeRF. `BladeRf` configures the radio, and its receiver and
+//! transmitter each hold the open device, so the two can stream from
+//! separate threads for full duplex.
+
+use num_complex::Complex;
+
+#[cfg(feature = "bladerf")]
+mod device;
+#[cfg(feature = "bladerf")]
+pub use device::{BladeRf, BladeRfReceiver, BladeRfTransmitter};
+
+/// Full scale of the 12-bit converters in SC16 Q11 samples.
+pub const FULL_SCALE: f64 = 2048.0;
+
+/// A receive or transmit channel. The bladeRF 2.0 micro has two of each
+/// and the original bladeRF one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Rx(u8),
+    Tx(u8),
+}
+
+impl Channel {
+    /// The channel number libbladeRF uses, receive channels even and
+    /// transmit channels odd.
+    pub fn index(self) -> i32 {
+        match self {
+            Channel::Rx(n) => i32::from(n) << 1,
+            Channel::Tx(n) => i32::from(n) << 1 | 1,
+        }
+    }
+
+    pub fn is_rx(self) -> bool {
+        matches!(self, Channel::Rx(_))
+    }
+}
+
+/// How a channel's gain is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gain {
+    /// The receive AGC, or the transmit default.
+    Auto,
+    /// Overall gain in dB, which libbladeRF spreads over the stages.
+    Manual(f64),
+}
+
+/// Buffering for the synchronous streaming interface, as
+/// `bladerf_sync_config` takes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamConfig {
+    /// Buffers in the ring.
+    pub buffers: u32,
+    /// Samples per buffer, a multiple of 1024.
+    pub buffer_size: u32,
+    /// USB transfers in flight, fewer than the buffers.
+    pub transfers: u32,
+    /// How long a read or write may block, in milliseconds.
+    pub timeout_ms: u32,
+}
+
+impl Default for StreamConfig {
+    /// libbladeRF's suggested settings for moderate rates.
+    fn default() -> Self {
+        StreamConfig {
+            buffers: 16,
+            buffer_size: 8192,
+            transfers: 8,
+            timeout_ms: 3500,
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn validate(self) -> Result<Self, BladeRfError> {
+        let valid = self.buffer_size > 0
+            && self.buffer_size.is_multiple_of(1024)
+            && self.transfers > 0
+            && self.transfers < self.buffers;
+        if valid {
+            Ok(self)
+        } else {
+            Err(BladeRfError::InvalidStreamConfig(self))
+        }
+    }
+}
+
+/// Errors raised by the bladeRF source and sink.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BladeRfError {
+    /// A libbladeRF call returned an error code.
+    #[error("{operation} failed: {message} ({code})")]
+    Device {
+        operation: &'static str,
+        code: i32,
+        message: String,
+    },
+    /// The stream settings are ones libbladeRF rejects.
+    #[error("invalid stream configuration: {0:?}")]
+    InvalidStreamConfig(StreamConfig),
+    /// A receive channel was given where a transmit one was needed, or the
+    /// other way round.
+    #[error("wrong direction for {0:?}")]
+    WrongDirection(Channel),
+    #[error("device identifier contains a NUL byte")]
+    InvalidIdentifier,
+}
+
+/// Converts interleaved SC16 Q11 I and Q values to complex samples in
+/// `[-1, 1)`, appending them to `output`. An odd value at the end is
+/// ignored.
+pub fn sc16q11_to_complex(input: &[i16], output: &mut Vec<Complex<f64>>) {
+    output.extend(input.chunks_exact(2).map(|pair| {
+        Complex::new(
+            f64::from(pair[0]) / FULL_SCALE,
+            f64::from(pair[1]) / FULL_SCALE,
+        )
+    }));
+}
+
+/// Converts complex samples to interleaved SC16 Q11, clipping each part to
+/// the converters' range, and appends them to `output`.
+pub fn complex_to_sc16q11(input: &[Complex<f64>], output: &mut Vec<i16>) {
+    let convert = |x: f64| {
+        (x * FULL_SCALE)
+            .round()
+            .clamp(-FULL_SCALE, FULL_SCALE - 1.0) as i16
+    };
+    output.extend(
+        input
+            .iter()
+            .flat_map(|sample| [convert(sample.re), convert(sample.im)]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_indices() {
+        assert_eq!(Channel::Rx(0).index(), 0);
+        assert_eq!(Channel::Tx(0).index(), 1);
+        assert_eq!(Channel::Rx(1).index(), 2);
+        assert_eq!(Channel::Tx(1).index(), 3);
+        assert!(!Channel::Tx(1).is_rx());
+    }
+
+    #[test]
+    fn test_stream_config_validation() {
+        assert!(StreamConfig::default().validate().is_ok());
+        let uneven = StreamConfig {
+            buffer_size: 1000,
+            ..StreamConfig::default()
+        };
+        assert!(uneven.validate().is_err());
+        let busy = StreamConfig {
+            transfers: 16,
+            ..StreamConfig::default()
+        };
+        assert!(busy.validate().is_err());
+    }
+
+    #[test]
+    fn test_sc16q11_round_trip_clips() {
+        let samples = [
+            Complex::new(0.5, -0.25),
+            Complex::new(-1.0, 0.0),
+            Complex::new(1.5, -2.0),
+        ];
+        let mut raw = Vec::new();
+        complex_to_sc16q11(&samples, &mut raw);
+        assert_eq!(raw, [1024, -512, -2048, 0, 2047, -2048]);
+        let mut back = Vec::new();
+        sc16q11_to_complex(&raw, &mut back);
+        assert_eq!(back[..2], samples[..2]);
+        assert_eq!(back[2], Complex::new(2047.0 / 2048.0, -1.0));
+    }
+}
@@ -0,0 +1,562 @@
+use super::{complex_to_sc16q11, sc16q11_to_complex, BladeRfError, Channel, Gain, StreamConfig};
+use crate::error::DeviceError;
+use crate::param::{ParamError, ParamValue};
+use crate::sink::Sink;
+use crate::source::control::{
+    check_frequency, correct_frequency, find_stage, uncorrect_frequency, GainStage, SdrDevice,
+};
+use crate::source::Source;
+use num_complex::Complex;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::sync::Arc;
+
+mod ffi {
+    use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+    #[repr(C)]
+    pub struct Bladerf {
+        _private: [u8; 0],
+    }
+
+    /// A setting's range, in units of `scale`.
+    #[repr(C)]
+    pub struct Range {
+        pub min: i64,
+        pub max: i64,
+        pub step: i64,
+        pub scale: f32,
+    }
+
+    pub const RX_X1: c_int = 0;
+    pub const TX_X1: c_int = 1;
+    pub const FORMAT_SC16_Q11: c_int = 0;
+    pub const GAIN_DEFAULT: c_int = 0;
+    pub const GAIN_MGC: c_int = 1;
+
+    #[link(name = "bladeRF")]
+    extern "C" {
+        pub fn bladerf_open(device: *mut *mut Bladerf, identifier: *const c_char) -> c_int;
+        pub fn bladerf_close(device: *mut Bladerf);
+        pub fn bladerf_strerror(error: c_int) -> *const c_char;
+        pub fn bladerf_get_board_name(device: *mut Bladerf) -> *const c_char;
+        pub fn bladerf_get_frequency_range(
+            device: *mut Bladerf,
+            channel: c_int,
+            range: *mut *const Range,
+        ) -> c_int;
+        pub fn bladerf_get_gain_stages(
+            device: *mut Bladerf,
+            channel: c_int,
+            stages: *mut *const c_char,
+            count: usize,
+        ) -> c_int;
+        pub fn bladerf_get_gain_stage_range(
+            device: *mut Bladerf,
+            channel: c_int,
+            stage: *const c_char,
+            range: *mut *const Range,
+        ) -> c_int;
+        pub fn bladerf_set_gain_stage(
+            device: *mut Bladerf,
+            channel: c_int,
+            stage: *const c_char,
+            gain: c_int,
+        ) -> c_int;
+        pub fn bladerf_set_frequency(device: *mut Bladerf, channel: c_int, frequency: u64)
+            -> c_int;
+        pub fn bladerf_set_sample_rate(
+            device: *mut Bladerf,
+            channel: c_int,
+            rate: c_uint,
+            actual: *mut c_uint,
+        ) -> c_int;
+        pub fn bladerf_set_bandwidth(
+            device: *mut Bladerf,
+            channel: c_int,
+            bandwidth: c_uint,
+            actual: *mut c_uint,
+        ) -> c_int;
+        pub fn bladerf_set_gain_mode(device: *mut Bladerf, channel: c_int, mode: c_int) -> c_int;
+        pub fn bladerf_set_gain(device: *mut Bladerf, channel: c_int, gain: c_int) -> c_int;
+        pub fn bladerf_set_bias_tee(device: *mut Bladerf, channel: c_int, enable: bool) -> c_int;
+        pub fn bladerf_sync_config(
+            device: *mut Bladerf,
+            layout: c_int,
+            format: c_int,
+            num_buffers: c_uint,
+            buffer_size: c_uint,
+            num_transfers: c_uint,
+            stream_timeout: c_uint,
+        ) -> c_int;
+        pub fn bladerf_enable_module(device: *mut Bladerf, channel: c_int, enable: bool) -> c_int;
+        pub fn bladerf_sync_rx(
+            device: *mut Bladerf,
+            samples: *mut c_void,
+            num_samples: c_uint,
+            metadata: *mut c_void,
+            timeout_ms: c_uint,
+        ) -> c_int;
+        pub fn bladerf_sync_tx(
+            device: *mut Bladerf,
+            samples: *const c_void,
+            num_samples: c_uint,
+            metadata: *mut c_void,
+            timeout_ms: c_uint,
+        ) -> c_int;
+    }
+}
+
+fn check(operation: &'static str, code: c_int) -> Result<(), BladeRfError> {
+    if code == 0 {
+        return Ok(());
+    }
+    let message = unsafe { CStr::from_ptr(ffi::bladerf_strerror(code)) }
+        .to_string_lossy()
+        .into_owned();
+    Err(BladeRfError::Device {
+        operation,
+        code,
+        message,
+    })
+}
+
+/// The open device, closed when the last of the radio, receiver and
+/// transmitter lets go of it.
+struct Handle(*mut ffi::Bladerf);
+
+// libbladeRF locks the device around each control call and keeps the
+// receive and transmit streams apart, so one thread may receive while
+// another transmits.
+unsafe impl Send for Handle {}
+unsafe impl Sync for Handle {}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { ffi::bladerf_close(self.0) };
+    }
+}
+
+// Control shared by the radio, its receivers and its transmitters.
+
+fn set_frequency(handle: &Handle, channel: Channel, frequency: u64) -> Result<(), BladeRfError> {
+    check("bladerf_set_frequency", unsafe {
+        ffi::bladerf_set_frequency(handle.0, channel.index(), frequency)
+    })
+}
+
+fn set_sample_rate(handle: &Handle, channel: Channel, rate: u32) -> Result<u32, BladeRfError> {
+    let mut actual = 0;
+    check("bladerf_set_sample_rate", unsafe {
+        ffi::bladerf_set_sample_rate(handle.0, channel.index(), rate, &mut actual)
+    })?;
+    Ok(actual)
+}
+
+fn set_gain(handle: &Handle, channel: Channel, gain: Gain) -> Result<(), BladeRfError> {
+    let device = handle.0;
+    let channel = channel.index();
+    unsafe {
+        match gain {
+            Gain::Auto => check(
+                "bladerf_set_gain_mode",
+                ffi::bladerf_set_gain_mode(device, channel, ffi::GAIN_DEFAULT),
+            ),
+            Gain::Manual(db) => {
+                check(
+                    "bladerf_set_gain_mode",
+                    ffi::bladerf_set_gain_mode(device, channel, ffi::GAIN_MGC),
+                )?;
+                check(
+                    "bladerf_set_gain",
+                    ffi::bladerf_set_gain(device, channel, db.round() as c_int),
+                )
+            }
+        }
+    }
+}
+
+fn set_bias_tee(handle: &Handle, channel: Channel, enabled: bool) -> Result<(), BladeRfError> {
+    check("bladerf_set_bias_tee", unsafe {
+        ffi::bladerf_set_bias_tee(handle.0, channel.index(), enabled)
+    })
+}
+
+/// Accepts `frequency` in Hz, `gain` in dB or `"auto"`, and `bias_tee` as
+/// a boolean.
+fn set_parameter(
+    handle: &Handle,
+    channel: Channel,
+    name: &str,
+    value: &ParamValue,
+) -> Result<(), ParamError> {
+    let failed = |error: BladeRfError| ParamError::Failed {
+        name: name.to_string(),
+        reason: error.to_string(),
+    };
+    match name {
+        "frequency" => {
+            let frequency = value
+                .as_f64()
+                .filter(|frequency| *frequency >= 0.0)
+                .ok_or_else(|| ParamError::invalid(name, value))?;
+            set_frequency(handle, channel, frequency as u64).map_err(failed)
+        }
+        "gain" => {
+            let gain = match value {
+                ParamValue::Text(text) if text == "auto" => Gain::Auto,
+                _ => Gain::Manual(
+                    value
+                        .as_f64()
+                        .ok_or_else(|| ParamError::invalid(name, value))?,
+                ),
+            };
+            set_gain(handle, channel, gain).map_err(failed)
+        }
+        "bias_tee" => {
+            let enabled = value
+                .as_bool()
+                .ok_or_else(|| ParamError::invalid(name, value))?;
+            set_bias_tee(handle, channel, enabled).map_err(failed)
+        }
+        _ => Err(ParamError::Unknown(name.to_string())),
+    }
+}
+
+/// An open bladeRF.
+pub struct BladeRf {
+    handle: Arc<Handle>,
+}
+
+impl BladeRf {
+    /// Opens the first available device.
+    pub fn open() -> Result<Self, BladeRfError> {
+        Self::open_raw(std::ptr::null())
+    }
+
+    /// Opens the device matching a libbladeRF identifier, such as
+    /// `"*:serial=f12ce1"`.
+    pub fn open_identifier(identifier: &str) -> Result<Self, BladeRfError> {
+        let identifier = CString::new(identifier).map_err(|_| BladeRfError::InvalidIdentifier)?;
+        Self::open_raw(identifier.as_ptr())
+    }
+
+    fn open_raw(identifier: *const c_char) -> Result<Self, BladeRfError> {
+        let mut device = std::ptr::null_mut();
+        check("bladerf_open", unsafe {
+            ffi::bladerf_open(&mut device, identifier)
+        })?;
+        Ok(BladeRf {
+            handle: Arc::new(Handle(device)),
+        })
+    }
+
+    /// Tunes `channel` to `frequency` Hz.
+    pub fn set_frequency(&mut self, channel: Channel, frequency: u64) -> Result<(), BladeRfError> {
+        set_frequency(&self.handle, channel, frequency)
+    }
+
+    /// Sets `channel`'s sample rate and returns the rate the device chose.
+    pub fn set_sample_rate(&mut self, channel: Channel, rate: u32) -> Result<u32, BladeRfError> {
+        set_sample_rate(&self.handle, channel, rate)
+    }
+
+    /// Sets `channel`'s analog filter bandwidth and returns the one chosen.
+    pub fn set_bandwidth(&mut self, channel: Channel, bandwidth: u32) -> Result<u32, BladeRfError> {
+        let mut actual = 0;
+        check("bladerf_set_bandwidth", unsafe {
+            ffi::bladerf_set_bandwidth(self.handle.0, channel.index(), bandwidth, &mut actual)
+        })?;
+        Ok(actual)
+    }
+
+    pub fn set_gain(&mut self, channel: Channel, gain: Gain) -> Result<(), BladeRfError> {
+        set_gain(&self.handle, channel, gain)
+    }
+
+    /// Switches the bias tee on `channel`'s port, on the bladeRF 2.0 micro.
+    pub fn set_bias_tee(&mut self, channel: Channel, enabled: bool) -> Result<(), BladeRfError> {
+        set_bias_tee(&self.handle, channel, enabled)
+    }
+
+    /// Starts streaming from a receive channel.
+    pub fn receiver(
+        &self,
+        channel: Channel,
+        config: StreamConfig,
+    ) -> Result<BladeRfReceiver, BladeRfError> {
+        if !channel.is_rx() {
+            return Err(BladeRfError::WrongDirection(channel));
+        }
+        self.start(channel, ffi::RX_X1, config)?;
+        trace_event!(info, ?channel, "bladeRF receiving");
+        Ok(BladeRfReceiver {
+            handle: Arc::clone(&self.handle),
+            channel,
+            config,
+            raw: Vec::new(),
+            converted: Vec::new(),
+            ppm: 0.0,
+        })
+    }
+
+    /// Starts streaming to a transmit channel.
+    pub fn transmitter(
+        &self,
+        channel: Channel,
+        config: StreamConfig,
+    ) -> Result<BladeRfTransmitter, BladeRfError> {
+        if channel.is_rx() {
+            return Err(BladeRfError::WrongDirection(channel));
+        }
+        self.start(channel, ffi::TX_X1, config)?;
+        trace_event!(info, ?channel, "bladeRF transmitting");
+        Ok(BladeRfTransmitter {
+            handle: Arc::clone(&self.handle),
+            channel,
+            config,
+            raw: Vec::new(),
+        })
+    }
+
+    fn start(
+        &self,
+        channel: Channel,
+        layout: c_int,
+        config: StreamConfig,
+    ) -> Result<(), BladeRfError> {
+        let config = config.validate()?;
+        unsafe {
+            check(
+                "bladerf_sync_config",
+                ffi::bladerf_sync_config(
+                    self.handle.0,
+                    layout,
+                    ffi::FORMAT_SC16_Q11,
+                    config.buffers,
+                    config.buffer_size,
+                    config.transfers,
+                    config.timeout_ms,
+                ),
+            )?;
+            check(
+                "bladerf_enable_module",
+                ffi::bladerf_enable_module(self.handle.0, channel.index(), true),
+            )
+        }
+    }
+}
+
+/// Samples from one receive channel. Tuning and gain changes through
+/// [`Source::set_parameter`] apply to it alone.
+pub struct BladeRfReceiver {
+    handle: Arc<Handle>,
+    channel: Channel,
+    config: StreamConfig,
+    raw: Vec<i16>,
+    converted: Vec<Complex<f64>>,
+    /// The reference oscillator's error, for [`SdrDevice::set_frequency`].
+    ppm: f64,
+}
+
+impl BladeRfReceiver {
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl Source for BladeRfReceiver {
+    type Sample = Complex<f64>;
+    type Error = BladeRfError;
+
+    /// Blocks until `buffer` is full, or a stream buffer's worth if it is
+    /// longer.
+    fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, BladeRfError> {
+        let count = buffer.len().min(self.config.buffer_size as usize);
+        self.raw.resize(2 * count, 0);
+        check("bladerf_sync_rx", unsafe {
+            ffi::bladerf_sync_rx(
+                self.handle.0,
+                self.raw.as_mut_ptr() as *mut c_void,
+                count as c_uint,
+                std::ptr::null_mut(),
+                self.config.timeout_ms,
+            )
+        })?;
+        self.converted.clear();
+        sc16q11_to_complex(&self.raw, &mut self.converted);
+        buffer[..count].copy_from_slice(&self.converted);
+        Ok(count)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_parameter(&self.handle, self.channel, name, value)
+    }
+}
+
+/// A range in the units libbladeRF reports it in, scaled.
+fn scaled(range: &ffi::Range) -> (f64, f64, f64) {
+    let scale = f64::from(range.scale);
+    (
+        range.min as f64 * scale,
+        range.max as f64 * scale,
+        range.step as f64 * scale,
+    )
+}
+
+/// The receive channel's controls, with gain stages as libbladeRF names
+/// them, such as `lna`, `rxvga1` and `rxvga2` on the original bladeRF and
+/// `full` on the 2.0 micro.
+impl SdrDevice for BladeRfReceiver {
+    fn name(&self) -> String {
+        let board = unsafe { ffi::bladerf_get_board_name(self.handle.0) };
+        match board.is_null() {
+            true => "bladeRF".to_string(),
+            false => unsafe { CStr::from_ptr(board) }
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+
+    fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+        let mut range = std::ptr::null();
+        let code = unsafe {
+            ffi::bladerf_get_frequency_range(self.handle.0, self.channel.index(), &mut range)
+        };
+        if code != 0 || range.is_null() {
+            return Vec::new();
+        }
+        let (min, max, _) = scaled(unsafe { &*range });
+        vec![(min, max)]
+    }
+
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+        check_frequency(&self.frequency_ranges(), frequency)?;
+        let tuned = correct_frequency(frequency, self.ppm).round() as u64;
+        set_frequency(&self.handle, self.channel, tuned)?;
+        Ok(uncorrect_frequency(tuned as f64, self.ppm))
+    }
+
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+        Ok(f64::from(set_sample_rate(
+            &self.handle,
+            self.channel,
+            rate.round() as u32,
+        )?))
+    }
+
+    fn gain_stages(&self) -> Vec<GainStage> {
+        let (device, channel) = (self.handle.0, self.channel.index());
+        let mut names = [std::ptr::null(); 8];
+        let count = unsafe {
+            ffi::bladerf_get_gain_stages(device, channel, names.as_mut_ptr(), names.len())
+        };
+        let count = usize::try_from(count).unwrap_or(0).min(names.len());
+        names[..count]
+            .iter()
+            .filter_map(|&name| {
+                let mut range = std::ptr::null();
+                let code =
+                    unsafe { ffi::bladerf_get_gain_stage_range(device, channel, name, &mut range) };
+                if code != 0 || range.is_null() {
+                    return None;
+                }
+                let (min, max, step) = scaled(unsafe { &*range });
+                let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+                Some(GainStage::new(&name, min, max, step.max(1.0)))
+            })
+            .collect()
+    }
+
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError> {
+        let gain = find_stage(&self.gain_stages(), stage)?.quantize(gain)?;
+        let name = CString::new(stage).map_err(|_| BladeRfError::InvalidIdentifier)?;
+        let (device, channel) = (self.handle.0, self.channel.index());
+        unsafe {
+            check(
+                "bladerf_set_gain_mode",
+                ffi::bladerf_set_gain_mode(device, channel, ffi::GAIN_MGC),
+            )?;
+            check(
+                "bladerf_set_gain_stage",
+                ffi::bladerf_set_gain_stage(device, channel, name.as_ptr(), gain.round() as c_int),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn set_agc(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        let mode = if enabled {
+            ffi::GAIN_DEFAULT
+        } else {
+            ffi::GAIN_MGC
+        };
+        check("bladerf_set_gain_mode", unsafe {
+            ffi::bladerf_set_gain_mode(self.handle.0, self.channel.index(), mode)
+        })?;
+        Ok(())
+    }
+
+    fn set_bias_tee(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        Ok(set_bias_tee(&self.handle, self.channel, enabled)?)
+    }
+
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+}
+
+impl Drop for BladeRfReceiver {
+    fn drop(&mut self) {
+        unsafe { ffi::bladerf_enable_module(self.handle.0, self.channel.index(), false) };
+    }
+}
+
+/// Samples to one transmit channel, which should stay within `[-1, 1)`;
+/// larger ones are clipped.
+pub struct BladeRfTransmitter {
+    handle: Arc<Handle>,
+    channel: Channel,
+    config: StreamConfig,
+    raw: Vec<i16>,
+}
+
+impl BladeRfTransmitter {
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+}
+
+impl Sink for BladeRfTransmitter {
+    type Input = Complex<f64>;
+    type Error = BladeRfError;
+
+    /// Blocks until every sample is queued for the device.
+    fn write(&mut self, input: &[Complex<f64>]) -> Result<(), BladeRfError> {
+        for chunk in input.chunks(self.config.buffer_size as usize) {
+            self.raw.clear();
+            complex_to_sc16q11(chunk, &mut self.raw);
+            check("bladerf_sync_tx", unsafe {
+                ffi::bladerf_sync_tx(
+                    self.handle.0,
+                    self.raw.as_ptr() as *const c_void,
+                    chunk.len() as c_uint,
+                    std::ptr::null_mut(),
+                    self.config.timeout_ms,
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_parameter(&self.handle, self.channel, name, value)
+    }
+}
+
+impl Drop for BladeRfTransmitter {
+    fn drop(&mut self) {
+        unsafe { ffi::bladerf_enable_module(self.handle.0, self.channel.index(), false) };
+    }
+}
@@ -0,0 +1,160 @@
+//! One control interface for every receiver.
+//!
+//! Each hardware backend has its own way of naming gains, rates and ports.
+//! [`SdrDevice`] covers what they have in common, so an application can
+//! take a `Box<dyn SdrDevice>` and let the user pick the radio. Controls a
+//! device lacks return [`DeviceError::Unsupported`], and its gain stages and
+//! antennas are listed by name so a user interface can offer them.
+//!
+//! Few backends can trim their reference oscillator, so PPM correction is
+//! done the same way for all of them, by scaling the frequency asked of
+//! the tuner.
+
+use crate::error::DeviceError;
+
+/// A gain stage a device offers.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GainStage {
+    pub name: String,
+    /// The lowest and highest settings, in dB where the device uses dB and
+    /// in its own steps where it does not.
+    pub min: f64,
+    pub max: f64,
+    /// The resolution of the setting.
+    pub step: f64,
+}
+
+impl GainStage {
+    pub fn new(name: &str, min: f64, max: f64, step: f64) -> Self {
+        GainStage {
+            name: name.to_string(),
+            min,
+            max,
+            step,
+        }
+    }
+
+    /// `gain` rounded to the stage's step, or an error if out of range.
+    pub fn quantize(&self, gain: f64) -> Result<f64, DeviceError> {
+        if !(self.min..=self.max).contains(&gain) {
+            return Err(DeviceError::OutOfRange {
+                setting: "gain",
+                value: gain,
+            });
+        }
+        let steps = ((gain - self.min) / self.step).round();
+        Ok((self.min + steps * self.step).min(self.max))
+    }
+}
+
+/// The named stage among `stages`, or [`DeviceError::Unsupported`].
+pub fn find_stage<'a>(stages: &'a [GainStage], name: &str) -> Result<&'a GainStage, DeviceError> {
+    stages
+        .iter()
+        .find(|stage| stage.name == name)
+        .ok_or_else(|| DeviceError::Unsupported(format!("gain stage {name}")))
+}
+
+/// The frequency to ask of a tuner whose reference runs `ppm` parts per
+/// million fast, so that it lands on `frequency`.
+pub fn correct_frequency(frequency: f64, ppm: f64) -> f64 {
+    frequency / (1.0 + ppm * 1e-6)
+}
+
+/// The frequency a tuner fast by `ppm` lands on when asked for `tuned`.
+pub fn uncorrect_frequency(tuned: f64, ppm: f64) -> f64 {
+    tuned * (1.0 + ppm * 1e-6)
+}
+
+/// Checks `frequency` against a device's tuning ranges.
+pub fn check_frequency(ranges: &[(f64, f64)], frequency: f64) -> Result<f64, DeviceError> {
+    if ranges
+        .iter()
+        .any(|&(low, high)| (low..=high).contains(&frequency))
+    {
+        Ok(frequency)
+    } else {
+        Err(DeviceError::OutOfRange {
+            setting: "frequency",
+            value: frequency,
+        })
+    }
+}
+
+/// Tuning, rate, gain and front-end control of a receiver.
+pub trait SdrDevice: Send {
+    /// The device's name, such as `"Airspy Mini"`, for logs and menus.
+    fn name(&self) -> String;
+
+    /// The ranges it tunes over, in Hz.
+    fn frequency_ranges(&self) -> Vec<(f64, f64)>;
+
+    /// Tunes to `frequency` Hz, allowing for the PPM correction, and
+    /// returns the frequency it is now on as near as the device can say.
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError>;
+
+    /// Sets the sample rate, or the nearest the device allows, and returns
+    /// the rate it chose.
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError>;
+
+    fn gain_stages(&self) -> Vec<GainStage>;
+
+    /// Sets one of the [`gain_stages`](SdrDevice::gain_stages), turning off
+    /// automatic gain control for it where that is on.
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError>;
+
+    fn set_agc(&mut self, _enabled: bool) -> Result<(), DeviceError> {
+        Err(DeviceError::Unsupported("AGC".to_string()))
+    }
+
+    /// Switches DC power to the antenna port on or off.
+    fn set_bias_tee(&mut self, _enabled: bool) -> Result<(), DeviceError> {
+        Err(DeviceError::Unsupported("bias tee".to_string()))
+    }
+
+    /// Sets how many parts per million fast the reference oscillator runs,
+    /// taking effect from the next [`set_frequency`](SdrDevice::set_frequency).
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError>;
+
+    /// The antenna ports it can switch between, or none if it has one.
+    fn antennas(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn set_antenna(&mut self, name: &str) -> Result<(), DeviceError> {
+        Err(DeviceError::Unsupported(format!("antenna {name}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_ppm_correction_round_trips() {
+        let tuned = correct_frequency(100e6, 50.0);
+        assert!(tuned < 100e6);
+        assert_relative_eq!(100e6 - tuned, 5_000.0, epsilon = 1.0);
+        assert_relative_eq!(uncorrect_frequency(tuned, 50.0), 100e6, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_gain_stages_and_ranges() {
+        let stages = [
+            GainStage::new("lna", 0.0, 14.0, 1.0),
+            GainStage::new("if", -3.0, 71.0, 0.5),
+        ];
+        let stage = find_stage(&stages, "if").unwrap();
+        assert_eq!(stage.quantize(20.3).unwrap(), 20.5);
+        assert!(stage.quantize(72.0).is_err());
+        assert!(matches!(
+            find_stage(&stages, "mixer"),
+            Err(DeviceError::Unsupported(_))
+        ));
+        let ranges = [(150e3, 240e6), (420e6, 1.9e9)];
+        assert!(check_frequency(&ranges, 7.1e6).is_ok());
+        assert!(check_frequency(&ranges, 300e6).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! FUNcube Dongle Pro and Pro+ receivers.
+//!
+//! The dongle is two USB devices in one: a sound card carrying IQ as a
+//! stereo stream, I on the left channel, and a HID device that takes
+//! commands for tuning, gain and filtering. The audio goes through whatever
+//! captures audio, into [`StereoToIq`]; the HID commands, their encoding and
+//! their checks are always compiled, and `FuncubeDongle`, which sends
+//! them, needs the `funcube` feature and the system hidapi.
+
+use crate::block::Block;
+use num_complex::Complex;
+
+#[cfg(feature = "funcube")]
+mod device;
+#[cfg(feature = "funcube")]
+pub use device::FuncubeDongle;
+
+/// Microchip's vendor ID, which the dongles use.
+pub const VENDOR_ID: u16 = 0x04d8;
+/// Bytes in a HID report, not counting the report ID.
+pub const REPORT_LEN: usize = 64;
+/// Highest IF gain of the Pro+, in dB.
+pub const MAX_IF_GAIN: u8 = 59;
+
+/// Dongle hardware variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Pro,
+    ProPlus,
+}
+
+impl Model {
+    pub fn product_id(self) -> u16 {
+        match self {
+            Model::Pro => 0xfb56,
+            Model::ProPlus => 0xfb31,
+        }
+    }
+
+    /// The IQ sample rate of its sound card.
+    pub fn sample_rate(self) -> u32 {
+        match self {
+            Model::Pro => 96_000,
+            Model::ProPlus => 192_000,
+        }
+    }
+
+    /// The tuner's coverage in Hz, with the gap around 300 MHz on the Pro+.
+    pub fn frequency_ranges(self) -> &'static [(u32, u32)] {
+        match self {
+            Model::Pro => &[(64_000_000, 1_700_000_000)],
+            Model::ProPlus => &[(150_000, 240_000_000), (420_000_000, 1_900_000_000)],
+        }
+    }
+
+    pub fn covers(self, frequency: u32) -> bool {
+        self.frequency_ranges()
+            .iter()
+            .any(|&(low, high)| (low..=high).contains(&frequency))
+    }
+}
+
+/// A command to the dongle's application firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Asks for the firmware version string.
+    Query,
+    SetFrequency(u32),
+    GetFrequency,
+    /// Switches the LNA in or out.
+    SetLnaGain(bool),
+    /// Switches the mixer gain in or out.
+    SetMixerGain(bool),
+    /// Sets the IF gain, from 0 to 59 dB.
+    SetIfGain(u8),
+    SetBiasTee(bool),
+}
+
+impl Command {
+    /// The command byte.
+    pub fn code(self) -> u8 {
+        match self {
+            Command::Query => 1,
+            Command::SetFrequency(_) => 101,
+            Command::GetFrequency => 102,
+            Command::SetLnaGain(_) => 110,
+            Command::SetMixerGain(_) => 114,
+            Command::SetIfGain(_) => 117,
+            Command::SetBiasTee(_) => 126,
+        }
+    }
+
+    /// The output report, led by the report ID of zero hidapi expects.
+    pub fn report(self) -> [u8; REPORT_LEN + 1] {
+        let mut report = [0; REPORT_LEN + 1];
+        report[1] = self.code();
+        match self {
+            Command::SetFrequency(hz) => report[2..6].copy_from_slice(&hz.to_le_bytes()),
+            Command::SetLnaGain(on) | Command::SetMixerGain(on) | Command::SetBiasTee(on) => {
+                report[2] = u8::from(on)
+            }
+            Command::SetIfGain(db) => report[2] = db,
+            Command::Query | Command::GetFrequency => {}
+        }
+        report
+    }
+
+    /// Checks the dongle's reply, which echoes the command byte and then
+    /// a one for success, and returns the data after them.
+    pub fn check(self, reply: &[u8]) -> Result<&[u8], FuncubeError> {
+        match reply {
+            [code, 1, data @ ..] if *code == self.code() => Ok(data),
+            _ => Err(FuncubeError::Rejected(self)),
+        }
+    }
+
+    /// Checks that the dongle's limits allow this command.
+    pub fn validate(self, model: Model) -> Result<Self, FuncubeError> {
+        match self {
+            Command::SetFrequency(hz) if !model.covers(hz) => {
+                Err(FuncubeError::InvalidFrequency(hz))
+            }
+            Command::SetIfGain(db) if db > MAX_IF_GAIN => Err(FuncubeError::InvalidGain(db)),
+            _ => Ok(self),
+        }
+    }
+}
+
+/// Reads the frequency from the reply to [`Command::GetFrequency`].
+pub fn parse_frequency(data: &[u8]) -> Result<u32, FuncubeError> {
+    data.first_chunk::<4>()
+        .map(|bytes| u32::from_le_bytes(*bytes))
+        .ok_or(FuncubeError::Rejected(Command::GetFrequency))
+}
+
+/// Errors raised by the FUNcube Dongle.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FuncubeError {
+    #[error("no FUNcube Dongle found")]
+    NotFound,
+    /// A hidapi call failed.
+    #[error("{0} failed")]
+    Hid(&'static str),
+    /// The dongle answered without success, or not at all.
+    #[error("the dongle rejected {0:?}")]
+    Rejected(Command),
+    #[error("frequency out of range: {0} Hz")]
+    InvalidFrequency(u32),
+    #[error("IF gain out of range: {0} dB")]
+    InvalidGain(u8),
+}
+
+/// Turns the sound card's interleaved left and right samples into IQ,
+/// consuming whole frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StereoToIq {
+    swap: bool,
+}
+
+impl StereoToIq {
+    pub fn new() -> Self {
+        StereoToIq { swap: false }
+    }
+
+    /// Takes I from the right channel instead, for sound systems that
+    /// enumerate the channels the other way round. It mirrors the spectrum.
+    pub fn swapped() -> Self {
+        StereoToIq { swap: true }
+    }
+}
+
+impl Block for StereoToIq {
+    type Input = f32;
+    type Output = Complex<f64>;
+
+    fn work(&mut self, input: &[f32], output: &mut Vec<Complex<f64>>) -> usize {
+        output.extend(input.chunks_exact(2).map(|frame| {
+            let (left, right) = (f64::from(frame[0]), f64::from(frame[1]));
+            match self.swap {
+                false => Complex::new(left, right),
+                true => Complex::new(right, left),
+            }
+        }));
+        input.len() / 2 * 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_reports() {
+        let report = Command::SetFrequency(145_800_000).report();
+        assert_eq!(report[..6], [0, 101, 0x40, 0xbb, 0xb0, 0x08]);
+        assert_eq!(Command::SetIfGain(30).report()[..3], [0, 117, 30]);
+        assert_eq!(Command::SetBiasTee(true).report()[..3], [0, 126, 1]);
+
+        let reply = [102, 1, 0x40, 0xbb, 0xb0, 0x08, 0, 0];
+        let data = Command::GetFrequency.check(&reply).unwrap();
+        assert_eq!(parse_frequency(data), Ok(145_800_000));
+        assert!(Command::SetIfGain(30).check(&[117, 0]).is_err());
+        assert!(Command::SetIfGain(30).check(&[101, 1]).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        let plus = Model::ProPlus;
+        assert!(Command::SetFrequency(7_100_000).validate(plus).is_ok());
+        assert!(Command::SetFrequency(300_000_000).validate(plus).is_err());
+        assert!(Command::SetFrequency(7_100_000)
+            .validate(Model::Pro)
+            .is_err());
+        assert!(Command::SetIfGain(60).validate(plus).is_err());
+        assert_eq!(plus.sample_rate(), 192_000);
+    }
+
+    #[test]
+    fn test_stereo_to_iq() {
+        let mut output = Vec::new();
+        let consumed = StereoToIq::new().work(&[0.5, -0.25, 0.125], &mut output);
+        assert_eq!(consumed, 2);
+        assert_eq!(output, [Complex::new(0.5, -0.25)]);
+        output.clear();
+        StereoToIq::swapped().work(&[0.5, -0.25], &mut output);
+        assert_eq!(output, [Complex::new(-0.25, 0.5)]);
+    }
+}
@@ -0,0 +1,212 @@
+use super::{parse_frequency, Command, FuncubeError, Model, MAX_IF_GAIN, REPORT_LEN};
+use crate::error::DeviceError;
+use crate::source::control::{
+    correct_frequency, find_stage, uncorrect_frequency, GainStage, SdrDevice,
+};
+use std::os::raw::{c_int, c_uchar};
+
+mod ffi {
+    use std::os::raw::{c_int, c_uchar};
+
+    #[repr(C)]
+    pub struct HidDevice {
+        _private: [u8; 0],
+    }
+
+    #[cfg_attr(target_os = "linux", link(name = "hidapi-hidraw"))]
+    #[cfg_attr(not(target_os = "linux"), link(name = "hidapi"))]
+    extern "C" {
+        pub fn hid_init() -> c_int;
+        pub fn hid_open(vendor_id: u16, product_id: u16, serial: *const i32) -> *mut HidDevice;
+        pub fn hid_close(device: *mut HidDevice);
+        pub fn hid_write(device: *mut HidDevice, data: *const c_uchar, length: usize) -> c_int;
+        pub fn hid_read_timeout(
+            device: *mut HidDevice,
+            data: *mut c_uchar,
+            length: usize,
+            milliseconds: c_int,
+        ) -> c_int;
+    }
+}
+
+/// How long to wait for a reply to a command.
+const REPLY_TIMEOUT_MS: c_int = 1000;
+
+/// The HID control side of an open dongle.
+pub struct FuncubeDongle {
+    device: *mut ffi::HidDevice,
+    model: Model,
+    /// The reference oscillator's error, for [`SdrDevice::set_frequency`].
+    ppm: f64,
+}
+
+// hidapi allows a device handle to be driven from any one thread at a time.
+unsafe impl Send for FuncubeDongle {}
+
+impl FuncubeDongle {
+    /// Opens the first Pro+ found, or failing that the first Pro.
+    pub fn open() -> Result<Self, FuncubeError> {
+        if unsafe { ffi::hid_init() } != 0 {
+            return Err(FuncubeError::Hid("hid_init"));
+        }
+        for model in [Model::ProPlus, Model::Pro] {
+            let device =
+                unsafe { ffi::hid_open(super::VENDOR_ID, model.product_id(), std::ptr::null()) };
+            if !device.is_null() {
+                trace_event!(info, ?model, "FUNcube Dongle opened");
+                return Ok(FuncubeDongle {
+                    device,
+                    model,
+                    ppm: 0.0,
+                });
+            }
+        }
+        Err(FuncubeError::NotFound)
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Sends `command` and returns the data in the dongle's reply.
+    pub fn command(&mut self, command: Command) -> Result<Vec<u8>, FuncubeError> {
+        let command = command.validate(self.model)?;
+        let report = command.report();
+        let written = unsafe { ffi::hid_write(self.device, report.as_ptr(), report.len()) };
+        if written < 0 {
+            return Err(FuncubeError::Hid("hid_write"));
+        }
+        let mut reply = [0 as c_uchar; REPORT_LEN];
+        let read = unsafe {
+            ffi::hid_read_timeout(
+                self.device,
+                reply.as_mut_ptr(),
+                reply.len(),
+                REPLY_TIMEOUT_MS,
+            )
+        };
+        if read < 0 {
+            return Err(FuncubeError::Hid("hid_read_timeout"));
+        }
+        command
+            .check(&reply[..read as usize])
+            .map(|data| data.to_vec())
+    }
+
+    /// The firmware version, such as `FCDAPP 20.03`.
+    pub fn firmware_version(&mut self) -> Result<String, FuncubeError> {
+        let data = self.command(Command::Query)?;
+        let end = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(data.len());
+        Ok(String::from_utf8_lossy(&data[..end]).into_owned())
+    }
+
+    /// Tunes to `frequency` Hz and returns the frequency the tuner chose.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<u32, FuncubeError> {
+        self.command(Command::SetFrequency(frequency))?;
+        self.frequency()
+    }
+
+    pub fn frequency(&mut self) -> Result<u32, FuncubeError> {
+        parse_frequency(&self.command(Command::GetFrequency)?)
+    }
+
+    pub fn set_lna_gain(&mut self, enabled: bool) -> Result<(), FuncubeError> {
+        self.command(Command::SetLnaGain(enabled)).map(drop)
+    }
+
+    pub fn set_mixer_gain(&mut self, enabled: bool) -> Result<(), FuncubeError> {
+        self.command(Command::SetMixerGain(enabled)).map(drop)
+    }
+
+    /// Sets the IF gain, from 0 to 59 dB.
+    pub fn set_if_gain(&mut self, gain: u8) -> Result<(), FuncubeError> {
+        self.command(Command::SetIfGain(gain)).map(drop)
+    }
+
+    /// Switches the antenna-port bias tee on or off.
+    pub fn set_bias_tee(&mut self, enabled: bool) -> Result<(), FuncubeError> {
+        self.command(Command::SetBiasTee(enabled)).map(drop)
+    }
+}
+
+/// The dongle's tuner. Its samples come from its sound card, at the one
+/// rate that runs at. The gain stages are the Pro+'s: the LNA and mixer
+/// switched in at 1 and out at 0, and the IF gain in dB.
+impl SdrDevice for FuncubeDongle {
+    fn name(&self) -> String {
+        match self.model {
+            Model::Pro => "FUNcube Dongle Pro".to_string(),
+            Model::ProPlus => "FUNcube Dongle Pro+".to_string(),
+        }
+    }
+
+    fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+        self.model
+            .frequency_ranges()
+            .iter()
+            .map(|&(low, high)| (f64::from(low), f64::from(high)))
+            .collect()
+    }
+
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+        let tuned = correct_frequency(frequency, self.ppm).round();
+        if !(0.0..=f64::from(u32::MAX)).contains(&tuned) {
+            return Err(DeviceError::OutOfRange {
+                setting: "frequency",
+                value: frequency,
+            });
+        }
+        let tuned = FuncubeDongle::set_frequency(self, tuned as u32)?;
+        Ok(uncorrect_frequency(f64::from(tuned), self.ppm))
+    }
+
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+        let fixed = f64::from(self.model.sample_rate());
+        if rate != fixed {
+            return Err(DeviceError::OutOfRange {
+                setting: "sample rate",
+                value: rate,
+            });
+        }
+        Ok(fixed)
+    }
+
+    fn gain_stages(&self) -> Vec<GainStage> {
+        match self.model {
+            Model::Pro => Vec::new(),
+            Model::ProPlus => vec![
+                GainStage::new("lna", 0.0, 1.0, 1.0),
+                GainStage::new("mixer", 0.0, 1.0, 1.0),
+                GainStage::new("if", 0.0, f64::from(MAX_IF_GAIN), 1.0),
+            ],
+        }
+    }
+
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError> {
+        let gain = find_stage(&self.gain_stages(), stage)?.quantize(gain)?;
+        match stage {
+            "lna" => self.set_lna_gain(gain > 0.0)?,
+            "mixer" => self.set_mixer_gain(gain > 0.0)?,
+            _ => self.set_if_gain(gain as u8)?,
+        }
+        Ok(())
+    }
+
+    fn set_bias_tee(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        Ok(FuncubeDongle::set_bias_tee(self, enabled)?)
+    }
+
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+}
+
+impl Drop for FuncubeDongle {
+    fn drop(&mut self) {
+        unsafe { ffi::hid_close(self.device) };
+    }
+}
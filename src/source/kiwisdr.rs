@@ -0,0 +1,330 @@
+//! KiwiSDR receivers, shared across the internet.
+//!
+//! A KiwiSDR serves each listener a websocket at
+//! `ws://host:8073/<timestamp>/SND`. The client sends `SET` commands as
+//! text: its password, the mode, passband and frequency, the gain, and a
+//! keepalive every few seconds. The server answers with binary messages led
+//! by a three-letter tag: `MSG` carries `key=value` settings such as the
+//! sample rate, and `SND` the audio, which in IQ mode is a header, a GPS
+//! timestamp and big-endian 16-bit I and Q pairs at about 12 kHz.
+//!
+//! The commands and frame parsing are always compiled. The client,
+//! `KiwiSdr`, needs the `kiwisdr` feature.
+
+use num_complex::Complex;
+
+#[cfg(feature = "kiwisdr")]
+mod device;
+#[cfg(feature = "kiwisdr")]
+pub use device::KiwiSdr;
+
+/// The port KiwiSDRs listen on unless their owner moved it.
+pub const DEFAULT_PORT: u16 = 8073;
+/// The tuning range in Hz.
+pub const FREQUENCY_RANGE: (f64, f64) = (0.0, 30e6);
+/// Highest manual gain in dB.
+pub const MAX_MANUAL_GAIN: f64 = 120.0;
+/// Seconds between keepalives; the server drops a client after about
+/// a minute without one.
+pub const KEEPALIVE_INTERVAL: f64 = 5.0;
+/// Bytes of the IQ GPS timestamp before the samples.
+const GPS_LEN: usize = 10;
+
+/// The stream URL, `timestamp` being any number unique to the connection,
+/// by convention the UNIX time.
+pub fn stream_url(host: &str, port: u16, timestamp: u64) -> String {
+    format!("ws://{host}:{port}/{timestamp}/SND")
+}
+
+/// What to listen to and how.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KiwiConfig {
+    /// Centre frequency in Hz.
+    pub frequency: f64,
+    /// Passband edges relative to the centre, in Hz.
+    pub low_cut: f64,
+    pub high_cut: f64,
+    /// The server's or a time-limit-exempt password, if it has one.
+    pub password: Option<String>,
+    /// The name shown in the server's user list.
+    pub user: String,
+    /// AGC, or a manual gain in dB.
+    pub agc: bool,
+    pub manual_gain: f64,
+}
+
+impl KiwiConfig {
+    pub fn new(frequency: f64) -> Self {
+        KiwiConfig {
+            frequency,
+            low_cut: -5000.0,
+            high_cut: 5000.0,
+            password: None,
+            user: "sdr-rust".to_string(),
+            agc: true,
+            manual_gain: 50.0,
+        }
+    }
+
+    /// Checks the frequency, passband and gain.
+    pub fn validate(&self) -> Result<(), KiwiError> {
+        let (low, high) = FREQUENCY_RANGE;
+        if !(low..=high).contains(&self.frequency) {
+            return Err(KiwiError::InvalidSetting(format!(
+                "frequency {} Hz",
+                self.frequency
+            )));
+        }
+        if self.low_cut >= self.high_cut {
+            return Err(KiwiError::InvalidSetting(format!(
+                "passband {} to {} Hz",
+                self.low_cut, self.high_cut
+            )));
+        }
+        if !(0.0..=MAX_MANUAL_GAIN).contains(&self.manual_gain) {
+            return Err(KiwiError::InvalidSetting(format!(
+                "gain {} dB",
+                self.manual_gain
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `SET` commands, as the server expects them.
+pub mod command {
+    /// Logs in, with the password if there is one.
+    pub fn auth(password: Option<&str>) -> String {
+        format!("SET auth t=kiwi p={}", password.unwrap_or(""))
+    }
+
+    /// Accepts the server's audio rate, as it waits for before streaming.
+    pub fn audio_rate(rate: u32) -> String {
+        format!("SET AR OK in={rate} out=48000")
+    }
+
+    /// Tunes, in IQ mode, to `frequency` Hz with the given passband.
+    pub fn tune(frequency: f64, low_cut: f64, high_cut: f64) -> String {
+        format!(
+            "SET mod=iq low_cut={} high_cut={} freq={:.3}",
+            low_cut.round(),
+            high_cut.round(),
+            frequency / 1e3
+        )
+    }
+
+    /// Turns AGC on, or off with the manual gain in dB.
+    pub fn agc(enabled: bool, manual_gain: f64) -> String {
+        format!(
+            "SET agc={} hang=0 thresh=-100 slope=6 decay=1000 manGain={}",
+            u8::from(enabled),
+            manual_gain.round()
+        )
+    }
+
+    /// Names the listener in the server's user list.
+    pub fn ident(user: &str) -> String {
+        format!("SET ident_user={}", user.replace(' ', "%20"))
+    }
+
+    pub const COMPRESSION_OFF: &str = "SET compression=0";
+    pub const SQUELCH_OFF: &str = "SET squelch=0 max=0";
+    pub const KEEPALIVE: &str = "SET keepalive";
+}
+
+/// A GPS timestamp on an IQ frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpsStamp {
+    /// Minutes since the server's last GPS fix, 255 if it has none.
+    pub last_solution: u8,
+    /// Seconds into the GPS week of the frame's first sample.
+    pub seconds: u32,
+    pub nanoseconds: u32,
+}
+
+/// One `SND` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundFrame {
+    pub flags: u8,
+    pub sequence: u32,
+    /// The signal strength, in dBm.
+    pub rssi: f64,
+    pub gps: GpsStamp,
+    /// Samples scaled to `[-1, 1)`.
+    pub samples: Vec<Complex<f64>>,
+}
+
+/// A message from the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// `MSG` settings, with their values percent-decoded.
+    Settings(Vec<(String, String)>),
+    Sound(SoundFrame),
+    /// Other tags, such as waterfall data for another client.
+    Other(String),
+}
+
+/// Decodes `%xx` escapes.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut n = 0;
+    while n < bytes.len() {
+        let escaped = bytes
+            .get(n + 1..n + 3)
+            .filter(|_| bytes[n] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                n += 3;
+            }
+            None => {
+                decoded.push(bytes[n]);
+                n += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parses a binary message from the server.
+pub fn parse_message(data: &[u8]) -> Result<ServerMessage, KiwiError> {
+    let invalid = |what: &str| KiwiError::InvalidFrame(what.to_string());
+    let (tag, body) = data
+        .split_at_checked(3)
+        .ok_or_else(|| invalid("short message"))?;
+    match tag {
+        b"MSG" => {
+            let text = String::from_utf8_lossy(body);
+            let settings = text
+                .split_whitespace()
+                .map(|item| match item.split_once('=') {
+                    Some((key, value)) => (key.to_string(), percent_decode(value)),
+                    None => (item.to_string(), String::new()),
+                })
+                .collect();
+            Ok(ServerMessage::Settings(settings))
+        }
+        b"SND" => {
+            let header = body
+                .get(..7 + GPS_LEN)
+                .ok_or_else(|| invalid("short SND header"))?;
+            let le32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let smeter = u16::from_be_bytes([header[5], header[6]]);
+            let gps = &header[7..];
+            let samples = &body[7 + GPS_LEN..];
+            if samples.len() % 4 != 0 {
+                return Err(invalid("partial IQ pair"));
+            }
+            Ok(ServerMessage::Sound(SoundFrame {
+                flags: header[0],
+                sequence: le32(&header[1..5]),
+                rssi: 0.1 * f64::from(smeter) - 127.0,
+                gps: GpsStamp {
+                    last_solution: gps[0],
+                    seconds: le32(&gps[2..6]),
+                    nanoseconds: le32(&gps[6..10]),
+                },
+                samples: samples
+                    .chunks_exact(4)
+                    .map(|pair| {
+                        let i = i16::from_be_bytes([pair[0], pair[1]]);
+                        let q = i16::from_be_bytes([pair[2], pair[3]]);
+                        Complex::new(f64::from(i) / 32768.0, f64::from(q) / 32768.0)
+                    })
+                    .collect(),
+            }))
+        }
+        _ => Ok(ServerMessage::Other(
+            String::from_utf8_lossy(tag).into_owned(),
+        )),
+    }
+}
+
+/// Errors raised by the KiwiSDR client.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum KiwiError {
+    /// Connecting, or the websocket, failed.
+    #[error("connection failed: {0}")]
+    Connection(String),
+    /// Every channel is in use.
+    #[error("the KiwiSDR is busy")]
+    TooBusy,
+    #[error("the KiwiSDR rejected the password")]
+    BadPassword,
+    /// The server closed the stream, as after its time limit.
+    #[error("the KiwiSDR closed the connection")]
+    Closed,
+    #[error("invalid message from the KiwiSDR: {0}")]
+    InvalidFrame(String),
+    #[error("invalid KiwiSDR setting: {0}")]
+    InvalidSetting(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            stream_url("kiwi.example.net", DEFAULT_PORT, 1_700_000_000),
+            "ws://kiwi.example.net:8073/1700000000/SND"
+        );
+        assert_eq!(command::auth(None), "SET auth t=kiwi p=");
+        assert_eq!(
+            command::tune(7_074_000.0, -3000.0, 3000.0),
+            "SET mod=iq low_cut=-3000 high_cut=3000 freq=7074.000"
+        );
+        assert_eq!(
+            command::agc(false, 40.0),
+            "SET agc=0 hang=0 thresh=-100 slope=6 decay=1000 manGain=40"
+        );
+        assert_eq!(command::ident("my station"), "SET ident_user=my%20station");
+        assert!(KiwiConfig::new(7e6).validate().is_ok());
+        assert!(KiwiConfig::new(50e6).validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let message = parse_message(b"MSGsample_rate=12001.135 load_cfg=a%20b badp=0").unwrap();
+        assert_eq!(
+            message,
+            ServerMessage::Settings(vec![
+                ("sample_rate".into(), "12001.135".into()),
+                ("load_cfg".into(), "a b".into()),
+                ("badp".into(), "0".into()),
+            ])
+        );
+
+        let mut frame = b"SND".to_vec();
+        frame.push(0x08);
+        frame.extend(7u32.to_le_bytes());
+        frame.extend(770u16.to_be_bytes());
+        frame.extend([3, 0]);
+        frame.extend(345_600u32.to_le_bytes());
+        frame.extend(500_000_000u32.to_le_bytes());
+        for value in [16384i16, -32768, 0, 8192] {
+            frame.extend(value.to_be_bytes());
+        }
+        let ServerMessage::Sound(sound) = parse_message(&frame).unwrap() else {
+            panic!("not a sound frame");
+        };
+        assert_eq!(sound.sequence, 7);
+        assert!((sound.rssi + 50.0).abs() < 1e-9);
+        assert_eq!(sound.gps.seconds, 345_600);
+        assert_eq!(sound.gps.nanoseconds, 500_000_000);
+        assert_eq!(
+            sound.samples,
+            [Complex::new(0.5, -1.0), Complex::new(0.0, 0.25)]
+        );
+        assert!(parse_message(&frame[..frame.len() - 1]).is_err());
+        assert!(parse_message(b"SN").is_err());
+        assert_eq!(
+            parse_message(b"W/F\x00").unwrap(),
+            ServerMessage::Other("W/F".into())
+        );
+    }
+}
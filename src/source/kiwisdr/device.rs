@@ -0,0 +1,273 @@
+use super::{
+    command, parse_message, stream_url, KiwiConfig, KiwiError, ServerMessage, FREQUENCY_RANGE,
+    KEEPALIVE_INTERVAL, MAX_MANUAL_GAIN,
+};
+use crate::error::DeviceError;
+use crate::param::{ParamError, ParamValue};
+use crate::source::control::{
+    check_frequency, correct_frequency, find_stage, uncorrect_frequency, GainStage, SdrDevice,
+};
+use crate::source::Source;
+use num_complex::Complex;
+use std::collections::VecDeque;
+use std::net::TcpStream;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// How long a read waits before checking whether a keepalive is due.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl From<tungstenite::Error> for KiwiError {
+    fn from(error: tungstenite::Error) -> Self {
+        match error {
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+                KiwiError::Closed
+            }
+            error => KiwiError::Connection(error.to_string()),
+        }
+    }
+}
+
+/// An IQ stream from one channel of a KiwiSDR.
+pub struct KiwiSdr {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    host: String,
+    config: KiwiConfig,
+    /// The IQ rate the server reported, a little off its nominal rate.
+    sample_rate: Option<f64>,
+    pending: VecDeque<Complex<f64>>,
+    last_keepalive: Instant,
+    /// The server's reference error, for [`SdrDevice::set_frequency`].
+    ppm: f64,
+}
+
+impl KiwiSdr {
+    /// Connects to `host` on `port`, logs in and starts the stream.
+    pub fn connect(host: &str, port: u16, config: KiwiConfig) -> Result<Self, KiwiError> {
+        config.validate()?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let (socket, _) = tungstenite::connect(stream_url(host, port, timestamp))?;
+        if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(|error| KiwiError::Connection(error.to_string()))?;
+        }
+        let mut kiwi = KiwiSdr {
+            socket,
+            host: host.to_string(),
+            config,
+            sample_rate: None,
+            pending: VecDeque::new(),
+            last_keepalive: Instant::now(),
+            ppm: 0.0,
+        };
+        kiwi.send(&command::auth(kiwi.config.password.as_deref()))?;
+        kiwi.send(&command::ident(&kiwi.config.user.clone()))?;
+        kiwi.send(command::COMPRESSION_OFF)?;
+        kiwi.send(command::SQUELCH_OFF)?;
+        kiwi.retune()?;
+        kiwi.send(&command::agc(kiwi.config.agc, kiwi.config.manual_gain))?;
+        trace_event!(info, host, port, "KiwiSDR connected");
+        Ok(kiwi)
+    }
+
+    /// The IQ sample rate, once the server has said.
+    pub fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+
+    pub fn config(&self) -> &KiwiConfig {
+        &self.config
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), KiwiError> {
+        Ok(self.socket.send(Message::text(text))?)
+    }
+
+    fn retune(&mut self) -> Result<(), KiwiError> {
+        let frequency = correct_frequency(self.config.frequency, self.ppm);
+        let tune = command::tune(frequency, self.config.low_cut, self.config.high_cut);
+        self.send(&tune)
+    }
+
+    /// Tunes to `frequency` Hz, keeping the passband.
+    pub fn set_frequency(&mut self, frequency: f64) -> Result<(), KiwiError> {
+        let config = KiwiConfig {
+            frequency,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        self.config = config;
+        self.retune()
+    }
+
+    /// Sets the passband edges relative to the centre, in Hz.
+    pub fn set_passband(&mut self, low_cut: f64, high_cut: f64) -> Result<(), KiwiError> {
+        let config = KiwiConfig {
+            low_cut,
+            high_cut,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        self.config = config;
+        self.retune()
+    }
+
+    /// Turns AGC on, or off with the manual gain in dB.
+    pub fn set_gain(&mut self, agc: bool, manual_gain: f64) -> Result<(), KiwiError> {
+        let config = KiwiConfig {
+            agc,
+            manual_gain,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        self.config = config;
+        self.send(&command::agc(agc, manual_gain))
+    }
+
+    /// Reads one message and acts on it, queueing any samples.
+    fn receive(&mut self) -> Result<(), KiwiError> {
+        if self.last_keepalive.elapsed().as_secs_f64() >= KEEPALIVE_INTERVAL {
+            self.send(command::KEEPALIVE)?;
+            self.last_keepalive = Instant::now();
+        }
+        let data = match self.socket.read() {
+            Ok(Message::Binary(data)) => data,
+            Ok(Message::Close(_)) => return Err(KiwiError::Closed),
+            Ok(_) => return Ok(()),
+            Err(tungstenite::Error::Io(error))
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(())
+            }
+            Err(error) => return Err(error.into()),
+        };
+        match parse_message(&data)? {
+            ServerMessage::Sound(frame) => self.pending.extend(frame.samples),
+            ServerMessage::Settings(settings) => {
+                for (key, value) in settings {
+                    match key.as_str() {
+                        "too_busy" => return Err(KiwiError::TooBusy),
+                        "badp" if value == "1" => return Err(KiwiError::BadPassword),
+                        "audio_rate" => {
+                            let rate = value.parse().map_err(|_| {
+                                KiwiError::InvalidFrame(format!("audio_rate={value}"))
+                            })?;
+                            self.send(&command::audio_rate(rate))?;
+                        }
+                        "sample_rate" => self.sample_rate = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            ServerMessage::Other(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl Source for KiwiSdr {
+    type Sample = Complex<f64>;
+    type Error = KiwiError;
+
+    /// Blocks until a frame arrives when none is queued, sending
+    /// keepalives meanwhile.
+    fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, KiwiError> {
+        while self.pending.is_empty() && !buffer.is_empty() {
+            self.receive()?;
+        }
+        let count = buffer.len().min(self.pending.len());
+        for (slot, sample) in buffer.iter_mut().zip(self.pending.drain(..count)) {
+            *slot = sample;
+        }
+        Ok(count)
+    }
+
+    /// Accepts `frequency` and `gain`, a number in dB or `"auto"`.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        let failed = |error: KiwiError| ParamError::Failed {
+            name: name.to_string(),
+            reason: error.to_string(),
+        };
+        match (name, value) {
+            ("frequency", _) => {
+                let frequency = value
+                    .as_f64()
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                KiwiSdr::set_frequency(self, frequency).map_err(failed)
+            }
+            ("gain", ParamValue::Text(text)) if text == "auto" => {
+                let gain = self.config.manual_gain;
+                KiwiSdr::set_gain(self, true, gain).map_err(failed)
+            }
+            ("gain", _) => {
+                let gain = value
+                    .as_f64()
+                    .ok_or_else(|| ParamError::invalid(name, value))?;
+                KiwiSdr::set_gain(self, false, gain).map_err(failed)
+            }
+            _ => Err(ParamError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// The channel's controls, with one gain stage, `rf`, the manual gain the
+/// AGC replaces. The server fixes the sample rate.
+impl SdrDevice for KiwiSdr {
+    fn name(&self) -> String {
+        format!("KiwiSDR {}", self.host)
+    }
+
+    fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+        vec![FREQUENCY_RANGE]
+    }
+
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+        check_frequency(&self.frequency_ranges(), frequency)?;
+        KiwiSdr::set_frequency(self, frequency)?;
+        // The server tunes in steps of a thousandth of a kHz.
+        let tuned = (correct_frequency(frequency, self.ppm) * 1e3).round() / 1e3;
+        Ok(uncorrect_frequency(tuned, self.ppm))
+    }
+
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+        match self.sample_rate {
+            Some(actual) if (rate - actual).abs() <= actual * 0.01 => Ok(actual),
+            _ => Err(DeviceError::OutOfRange {
+                setting: "sample rate",
+                value: rate,
+            }),
+        }
+    }
+
+    fn gain_stages(&self) -> Vec<GainStage> {
+        vec![GainStage::new("rf", 0.0, MAX_MANUAL_GAIN, 1.0)]
+    }
+
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError> {
+        let gain = find_stage(&self.gain_stages(), stage)?.quantize(gain)?;
+        Ok(KiwiSdr::set_gain(self, false, gain)?)
+    }
+
+    fn set_agc(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        let gain = self.config.manual_gain;
+        Ok(KiwiSdr::set_gain(self, enabled, gain)?)
+    }
+
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+}
+
+impl Drop for KiwiSdr {
+    fn drop(&mut self) {
+        let _ = self.socket.close(None);
+    }
+}
@@ -0,0 +1,76 @@
+//! A source of zeros, for benchmarking and for inputs nothing should feed.
+
+use crate::source::Source;
+use num_traits::Zero;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+/// Produces zeros as fast as they are read, forever or up to a limit.
+///
+/// Run through a chain into a [`NullSink`](crate::sink::null::NullSink), it
+/// measures what the flowgraph itself costs per sample, with no device or
+/// file in the way.
+#[derive(Debug, Clone)]
+pub struct NullSource<T> {
+    remaining: Option<u64>,
+    _samples: PhantomData<fn() -> T>,
+}
+
+impl<T: Zero + Clone> NullSource<T> {
+    /// A source that never runs out.
+    pub fn new() -> Self {
+        NullSource {
+            remaining: None,
+            _samples: PhantomData,
+        }
+    }
+
+    /// A source exhausted after `samples` zeros.
+    pub fn with_limit(samples: u64) -> Self {
+        NullSource {
+            remaining: Some(samples),
+            _samples: PhantomData,
+        }
+    }
+}
+
+impl<T: Zero + Clone> Default for NullSource<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Zero + Clone> Source for NullSource<T> {
+    type Sample = T;
+    type Error = Infallible;
+
+    fn read(&mut self, buffer: &mut [T]) -> Result<usize, Infallible> {
+        let count = match &mut self.remaining {
+            Some(remaining) => {
+                let count = (buffer.len() as u64).min(*remaining) as usize;
+                *remaining -= count as u64;
+                count
+            }
+            None => buffer.len(),
+        };
+        buffer[..count].fill(T::zero());
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_complex::Complex;
+
+    #[test]
+    fn test_limit() {
+        let mut source = NullSource::<Complex<f32>>::with_limit(5);
+        let mut buffer = [Complex::new(1.0, 1.0); 4];
+        assert_eq!(source.read(&mut buffer).unwrap(), 4);
+        assert!(buffer.iter().all(|sample| sample.is_zero()));
+        assert_eq!(source.read(&mut buffer).unwrap(), 1);
+        assert_eq!(source.read(&mut buffer).unwrap(), 0);
+        assert_eq!(NullSource::<f64>::new().read(&mut [1.0; 8]).unwrap(), 8);
+    }
+}
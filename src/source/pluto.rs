@@ -0,0 +1,217 @@
+//! Analog Devices ADALM-Pluto transceivers, through libiio.
+//!
+//! The connection URIs, gain settings and sample conversion are always
+//! compiled. The device itself needs the `pluto` feature and libiio. As with
+//! the bladeRF, `Pluto` configures the AD9361 and its receiver and
+//! transmitter each hold the open context, so one thread can receive while
+//! another transmits. The receive and transmit sample rates share a clock
+//! and are set together.
+
+use num_complex::Complex;
+
+#[cfg(feature = "pluto")]
+mod device;
+#[cfg(feature = "pluto")]
+pub use device::{Pluto, PlutoReceiver, PlutoTransmitter};
+
+/// Where the Pluto answers when plugged in, over its USB network gadget.
+pub const DEFAULT_HOST: &str = "192.168.2.1";
+/// Local oscillator range in Hz, as the AD9364 firmware most units are
+/// switched to allows; the AD9363 they ship as is rated 325 MHz to 3.8 GHz.
+pub const FREQUENCY_RANGE: (u64, u64) = (70_000_000, 6_000_000_000);
+/// Sample rate range in Hz, the lowest needing the FIR decimator.
+pub const SAMPLE_RATE_RANGE: (u32, u32) = (520_833, 61_440_000);
+/// Manual receive gain range in dB. The top of it falls above 4 GHz.
+pub const RX_GAIN_RANGE: (f64, f64) = (-3.0, 71.0);
+/// Highest transmit attenuation in dB, set in quarter-dB steps.
+pub const MAX_TX_ATTENUATION: f64 = 89.75;
+/// Full scale of the 12-bit converters.
+pub const FULL_SCALE: f64 = 2048.0;
+
+/// How to reach the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Connection {
+    /// A host name or address, for the USB network gadget or a Pluto on
+    /// Ethernet.
+    Network(String),
+    /// A USB address such as `1.2.5`, as `iio_info -s` lists them.
+    Usb(String),
+}
+
+impl Connection {
+    /// The libiio context URI.
+    pub fn uri(&self) -> String {
+        match self {
+            Connection::Network(host) => format!("ip:{host}"),
+            Connection::Usb(address) => format!("usb:{address}"),
+        }
+    }
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Connection::Network(DEFAULT_HOST.to_string())
+    }
+}
+
+/// The AD9361's receive gain control.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GainControl {
+    /// A fixed gain in dB.
+    Manual(f64),
+    /// AGC for slowly varying signals.
+    SlowAttack,
+    /// AGC for bursts, such as TDD or packet signals.
+    FastAttack,
+    /// Slow attack with the gain held between bursts.
+    Hybrid,
+}
+
+impl GainControl {
+    /// The value of the `gain_control_mode` attribute.
+    pub fn mode(self) -> &'static str {
+        match self {
+            GainControl::Manual(_) => "manual",
+            GainControl::SlowAttack => "slow_attack",
+            GainControl::FastAttack => "fast_attack",
+            GainControl::Hybrid => "hybrid",
+        }
+    }
+
+    /// Reads a mode name, or a manual gain in dB.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "slow_attack" | "auto" => Some(GainControl::SlowAttack),
+            "fast_attack" => Some(GainControl::FastAttack),
+            "hybrid" => Some(GainControl::Hybrid),
+            _ => text.parse().ok().map(GainControl::Manual),
+        }
+    }
+
+    pub fn validate(self) -> Result<Self, PlutoError> {
+        match self {
+            GainControl::Manual(db) if !(RX_GAIN_RANGE.0..=RX_GAIN_RANGE.1).contains(&db) => {
+                Err(PlutoError::InvalidGain(db))
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+/// The `hardwaregain` value for `attenuation` dB of transmit attenuation,
+/// rounded to the quarter dB the AD9361 sets it in.
+pub fn tx_hardware_gain(attenuation: f64) -> Result<f64, PlutoError> {
+    if !(0.0..=MAX_TX_ATTENUATION).contains(&attenuation) {
+        return Err(PlutoError::InvalidAttenuation(attenuation));
+    }
+    Ok(-(attenuation * 4.0).round() / 4.0)
+}
+
+/// Errors raised by the Pluto source and sink.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PlutoError {
+    /// A libiio call failed, with the errno it gave.
+    #[error("{operation} failed: {message} ({code})")]
+    Device {
+        operation: &'static str,
+        code: i32,
+        message: String,
+    },
+    /// The context has no device or channel of this name, so it is
+    /// probably not a Pluto.
+    #[error("no {0} in the IIO context")]
+    Missing(&'static str),
+    #[error("receive gain out of range: {0} dB")]
+    InvalidGain(f64),
+    #[error("transmit attenuation out of range: {0} dB")]
+    InvalidAttenuation(f64),
+    #[error("unsupported sample rate: {0} Hz")]
+    UnsupportedSampleRate(u32),
+    #[error("frequency out of range: {0} Hz")]
+    InvalidFrequency(u64),
+    /// A URI or attribute value contains a NUL byte.
+    #[error("string contains a NUL byte")]
+    InvalidString,
+}
+
+/// Checks a local oscillator frequency against [`FREQUENCY_RANGE`].
+pub fn validate_frequency(frequency: u64) -> Result<u64, PlutoError> {
+    if (FREQUENCY_RANGE.0..=FREQUENCY_RANGE.1).contains(&frequency) {
+        Ok(frequency)
+    } else {
+        Err(PlutoError::InvalidFrequency(frequency))
+    }
+}
+
+/// Checks a sample rate against [`SAMPLE_RATE_RANGE`].
+pub fn validate_sample_rate(rate: u32) -> Result<u32, PlutoError> {
+    if (SAMPLE_RATE_RANGE.0..=SAMPLE_RATE_RANGE.1).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(PlutoError::UnsupportedSampleRate(rate))
+    }
+}
+
+/// Converts interleaved received I and Q values, 12-bit and sign-extended,
+/// to complex samples in `[-1, 1)`, appending them to `output`.
+pub fn rx_to_complex(input: &[i16], output: &mut Vec<Complex<f64>>) {
+    output.extend(input.chunks_exact(2).map(|pair| {
+        Complex::new(
+            f64::from(pair[0]) / FULL_SCALE,
+            f64::from(pair[1]) / FULL_SCALE,
+        )
+    }));
+}
+
+/// Converts complex samples to interleaved transmit I and Q values, which
+/// the DAC takes in the top 12 bits, clipping each part to its range.
+pub fn complex_to_tx(input: &[Complex<f64>], output: &mut Vec<i16>) {
+    let convert = |x: f64| {
+        ((x * FULL_SCALE)
+            .round()
+            .clamp(-FULL_SCALE, FULL_SCALE - 1.0) as i16)
+            << 4
+    };
+    output.extend(
+        input
+            .iter()
+            .flat_map(|sample| [convert(sample.re), convert(sample.im)]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_uris() {
+        assert_eq!(Connection::default().uri(), "ip:192.168.2.1");
+        assert_eq!(Connection::Usb("1.2.5".to_string()).uri(), "usb:1.2.5");
+    }
+
+    #[test]
+    fn test_gain_settings() {
+        assert_eq!(GainControl::parse("auto"), Some(GainControl::SlowAttack));
+        assert_eq!(GainControl::parse("40.5"), Some(GainControl::Manual(40.5)));
+        assert_eq!(GainControl::parse("loud"), None);
+        assert_eq!(GainControl::Hybrid.mode(), "hybrid");
+        assert!(GainControl::Manual(72.0).validate().is_err());
+        assert_eq!(tx_hardware_gain(10.3), Ok(-10.25));
+        assert!(tx_hardware_gain(90.0).is_err());
+        assert!(validate_frequency(2_400_000_000).is_ok());
+        assert!(validate_frequency(50_000_000).is_err());
+        assert!(validate_sample_rate(100_000).is_err());
+    }
+
+    #[test]
+    fn test_sample_conversion() {
+        let samples = [Complex::new(0.5, -0.25), Complex::new(1.5, -1.0)];
+        let mut raw = Vec::new();
+        complex_to_tx(&samples, &mut raw);
+        assert_eq!(raw, [1024 << 4, -512 << 4, 2047 << 4, -2048 << 4]);
+        let mut back = Vec::new();
+        rx_to_complex(&raw.iter().map(|&x| x >> 4).collect::<Vec<_>>(), &mut back);
+        assert_eq!(back[0], samples[0]);
+        assert_eq!(back[1], Complex::new(2047.0 / 2048.0, -1.0));
+    }
+}
@@ -0,0 +1,580 @@
+use super::{
+    complex_to_tx, rx_to_complex, tx_hardware_gain, validate_frequency, validate_sample_rate,
+    Connection, GainControl, PlutoError, FREQUENCY_RANGE, RX_GAIN_RANGE,
+};
+use crate::error::DeviceError;
+use crate::param::{ParamError, ParamValue};
+use crate::sink::Sink;
+use crate::source::control::{
+    correct_frequency, find_stage, uncorrect_frequency, GainStage, SdrDevice,
+};
+use crate::source::Source;
+use num_complex::Complex;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_longlong};
+use std::sync::{Arc, Mutex};
+
+mod ffi {
+    use std::os::raw::{c_char, c_double, c_int, c_longlong, c_void};
+
+    #[repr(C)]
+    pub struct IioContext {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct IioDevice {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct IioChannel {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct IioBuffer {
+        _private: [u8; 0],
+    }
+
+    #[link(name = "iio")]
+    extern "C" {
+        pub fn iio_create_context_from_uri(uri: *const c_char) -> *mut IioContext;
+        pub fn iio_context_destroy(context: *mut IioContext);
+        pub fn iio_context_find_device(
+            context: *const IioContext,
+            name: *const c_char,
+        ) -> *mut IioDevice;
+        pub fn iio_device_find_channel(
+            device: *const IioDevice,
+            name: *const c_char,
+            output: bool,
+        ) -> *mut IioChannel;
+        pub fn iio_channel_enable(channel: *mut IioChannel);
+        pub fn iio_channel_attr_write(
+            channel: *const IioChannel,
+            attribute: *const c_char,
+            value: *const c_char,
+        ) -> isize;
+        pub fn iio_channel_attr_write_longlong(
+            channel: *const IioChannel,
+            attribute: *const c_char,
+            value: c_longlong,
+        ) -> c_int;
+        pub fn iio_channel_attr_write_double(
+            channel: *const IioChannel,
+            attribute: *const c_char,
+            value: c_double,
+        ) -> c_int;
+        pub fn iio_device_create_buffer(
+            device: *const IioDevice,
+            samples: usize,
+            cyclic: bool,
+        ) -> *mut IioBuffer;
+        pub fn iio_buffer_destroy(buffer: *mut IioBuffer);
+        pub fn iio_buffer_refill(buffer: *mut IioBuffer) -> isize;
+        pub fn iio_buffer_push(buffer: *mut IioBuffer) -> isize;
+        pub fn iio_buffer_start(buffer: *const IioBuffer) -> *mut c_void;
+        pub fn iio_buffer_end(buffer: *const IioBuffer) -> *mut c_void;
+        pub fn iio_strerror(error: c_int, buffer: *mut c_char, length: usize);
+    }
+}
+
+/// The error for a libiio call that returned `-errno`.
+fn error(operation: &'static str, code: i32) -> PlutoError {
+    let mut message = [0 as c_char; 256];
+    unsafe { ffi::iio_strerror(code, message.as_mut_ptr(), message.len()) };
+    let message = unsafe { CStr::from_ptr(message.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+    PlutoError::Device {
+        operation,
+        code,
+        message,
+    }
+}
+
+fn check(operation: &'static str, result: isize) -> Result<usize, PlutoError> {
+    usize::try_from(result).map_err(|_| error(operation, -result as i32))
+}
+
+/// The errno left by a libiio call that returned null.
+fn last_error(operation: &'static str) -> PlutoError {
+    let code = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    error(operation, code)
+}
+
+/// The open context and the AD9361's three IIO devices: the PHY that holds
+/// the radio's settings and the converters' streaming cores.
+struct Context {
+    context: *mut ffi::IioContext,
+    phy: *mut ffi::IioDevice,
+    rx: *mut ffi::IioDevice,
+    tx: *mut ffi::IioDevice,
+    /// Serialises attribute writes, which share the context's connection.
+    control: Mutex<()>,
+}
+
+// Attribute writes take `control`, and each buffer belongs to one
+// streaming device, which libiio gives a connection of its own.
+unsafe impl Send for Context {}
+unsafe impl Sync for Context {}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { ffi::iio_context_destroy(self.context) };
+    }
+}
+
+/// The selectors for the PHY's channels: the receive and transmit paths
+/// and their local oscillators.
+const RX_PATH: (&CStr, bool) = (c"voltage0", false);
+const TX_PATH: (&CStr, bool) = (c"voltage0", true);
+const RX_LO: (&CStr, bool) = (c"altvoltage0", true);
+const TX_LO: (&CStr, bool) = (c"altvoltage1", true);
+
+impl Context {
+    fn open(connection: &Connection) -> Result<Self, PlutoError> {
+        let uri = CString::new(connection.uri()).map_err(|_| PlutoError::InvalidString)?;
+        let context = unsafe { ffi::iio_create_context_from_uri(uri.as_ptr()) };
+        if context.is_null() {
+            return Err(last_error("iio_create_context_from_uri"));
+        }
+        let find = |name: &CStr, what| {
+            let device = unsafe { ffi::iio_context_find_device(context, name.as_ptr()) };
+            if device.is_null() {
+                Err(PlutoError::Missing(what))
+            } else {
+                Ok(device)
+            }
+        };
+        let devices = (|| {
+            Ok((
+                find(c"ad9361-phy", "ad9361-phy")?,
+                find(c"cf-ad9361-lpc", "cf-ad9361-lpc")?,
+                find(c"cf-ad9361-dds-core-lpc", "cf-ad9361-dds-core-lpc")?,
+            ))
+        })();
+        let (phy, rx, tx) = match devices {
+            Ok(devices) => devices,
+            Err(error) => {
+                unsafe { ffi::iio_context_destroy(context) };
+                return Err(error);
+            }
+        };
+        Ok(Context {
+            context,
+            phy,
+            rx,
+            tx,
+            control: Mutex::new(()),
+        })
+    }
+
+    fn channel(
+        device: *mut ffi::IioDevice,
+        (name, output): (&CStr, bool),
+    ) -> Result<*mut ffi::IioChannel, PlutoError> {
+        let channel = unsafe { ffi::iio_device_find_channel(device, name.as_ptr(), output) };
+        if channel.is_null() {
+            Err(PlutoError::Missing("AD9361 channel"))
+        } else {
+            Ok(channel)
+        }
+    }
+
+    fn write_longlong(
+        &self,
+        path: (&CStr, bool),
+        attribute: &CStr,
+        value: i64,
+    ) -> Result<(), PlutoError> {
+        let channel = Self::channel(self.phy, path)?;
+        let _control = self.control.lock().unwrap();
+        let result = unsafe {
+            ffi::iio_channel_attr_write_longlong(channel, attribute.as_ptr(), value as c_longlong)
+        };
+        check("iio_channel_attr_write_longlong", result as isize).map(drop)
+    }
+
+    fn write_double(
+        &self,
+        path: (&CStr, bool),
+        attribute: &CStr,
+        value: f64,
+    ) -> Result<(), PlutoError> {
+        let channel = Self::channel(self.phy, path)?;
+        let _control = self.control.lock().unwrap();
+        let result =
+            unsafe { ffi::iio_channel_attr_write_double(channel, attribute.as_ptr(), value) };
+        check("iio_channel_attr_write_double", result as isize).map(drop)
+    }
+
+    fn write_str(
+        &self,
+        path: (&CStr, bool),
+        attribute: &CStr,
+        value: &str,
+    ) -> Result<(), PlutoError> {
+        let channel = Self::channel(self.phy, path)?;
+        let value = CString::new(value).map_err(|_| PlutoError::InvalidString)?;
+        let _control = self.control.lock().unwrap();
+        let result =
+            unsafe { ffi::iio_channel_attr_write(channel, attribute.as_ptr(), value.as_ptr()) };
+        check("iio_channel_attr_write", result).map(drop)
+    }
+
+    fn set_frequency(&self, lo: (&CStr, bool), frequency: u64) -> Result<(), PlutoError> {
+        let frequency = validate_frequency(frequency)?;
+        self.write_longlong(lo, c"frequency", frequency as i64)
+    }
+
+    fn set_sample_rate(&self, rate: u32) -> Result<(), PlutoError> {
+        let rate = validate_sample_rate(rate)?;
+        self.write_longlong(RX_PATH, c"sampling_frequency", i64::from(rate))
+    }
+
+    fn set_bandwidth(&self, path: (&CStr, bool), bandwidth: u32) -> Result<(), PlutoError> {
+        self.write_longlong(path, c"rf_bandwidth", i64::from(bandwidth))
+    }
+
+    fn set_rx_gain(&self, gain: GainControl) -> Result<(), PlutoError> {
+        let gain = gain.validate()?;
+        self.write_str(RX_PATH, c"gain_control_mode", gain.mode())?;
+        match gain {
+            GainControl::Manual(db) => self.write_double(RX_PATH, c"hardwaregain", db),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_tx_attenuation(&self, attenuation: f64) -> Result<(), PlutoError> {
+        self.write_double(TX_PATH, c"hardwaregain", tx_hardware_gain(attenuation)?)
+    }
+
+    /// Enables the I and Q channels of a streaming device and makes a
+    /// buffer of `samples` for them.
+    fn buffer(
+        &self,
+        device: *mut ffi::IioDevice,
+        output: bool,
+        samples: usize,
+    ) -> Result<Buffer, PlutoError> {
+        for name in [c"voltage0", c"voltage1"] {
+            let channel = Self::channel(device, (name, output))?;
+            unsafe { ffi::iio_channel_enable(channel) };
+        }
+        let buffer = unsafe { ffi::iio_device_create_buffer(device, samples, false) };
+        if buffer.is_null() {
+            Err(last_error("iio_device_create_buffer"))
+        } else {
+            Ok(Buffer(buffer))
+        }
+    }
+}
+
+/// A streaming buffer, holding interleaved 16-bit I and Q values.
+struct Buffer(*mut ffi::IioBuffer);
+
+// Only its one receiver or transmitter touches it.
+unsafe impl Send for Buffer {}
+
+impl Buffer {
+    fn values(&mut self) -> &mut [i16] {
+        unsafe {
+            let start = ffi::iio_buffer_start(self.0) as *mut i16;
+            let end = ffi::iio_buffer_end(self.0) as *mut i16;
+            std::slice::from_raw_parts_mut(start, end.offset_from(start) as usize)
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { ffi::iio_buffer_destroy(self.0) };
+    }
+}
+
+/// Accepts `frequency` in Hz and `bandwidth` in Hz for either direction,
+/// `gain` in dB or a gain control mode such as `"slow_attack"` for
+/// receiving, and `attenuation` in dB for transmitting.
+fn set_parameter(
+    context: &Context,
+    transmit: bool,
+    name: &str,
+    value: &ParamValue,
+) -> Result<(), ParamError> {
+    let failed = |error: PlutoError| ParamError::Failed {
+        name: name.to_string(),
+        reason: error.to_string(),
+    };
+    let invalid = || ParamError::invalid(name, value);
+    let (lo, path) = if transmit {
+        (TX_LO, TX_PATH)
+    } else {
+        (RX_LO, RX_PATH)
+    };
+    match name {
+        "frequency" => {
+            let frequency = value
+                .as_f64()
+                .filter(|frequency| *frequency >= 0.0)
+                .ok_or_else(invalid)?;
+            context.set_frequency(lo, frequency as u64).map_err(failed)
+        }
+        "bandwidth" => {
+            let bandwidth = value
+                .as_f64()
+                .filter(|bandwidth| (0.0..=u32::MAX as f64).contains(bandwidth))
+                .ok_or_else(invalid)?;
+            context
+                .set_bandwidth(path, bandwidth as u32)
+                .map_err(failed)
+        }
+        "gain" if !transmit => {
+            let gain = match value {
+                ParamValue::Text(text) => GainControl::parse(text),
+                _ => value.as_f64().map(GainControl::Manual),
+            }
+            .ok_or_else(invalid)?;
+            context.set_rx_gain(gain).map_err(failed)
+        }
+        "attenuation" if transmit => {
+            let attenuation = value.as_f64().ok_or_else(invalid)?;
+            context.set_tx_attenuation(attenuation).map_err(failed)
+        }
+        _ => Err(ParamError::Unknown(name.to_string())),
+    }
+}
+
+/// An open ADALM-Pluto.
+pub struct Pluto {
+    context: Arc<Context>,
+}
+
+impl Pluto {
+    /// Opens the Pluto at `connection`, such as [`Connection::default`] for
+    /// one plugged in over USB.
+    pub fn open(connection: &Connection) -> Result<Self, PlutoError> {
+        Ok(Pluto {
+            context: Arc::new(Context::open(connection)?),
+        })
+    }
+
+    /// Tunes the receive local oscillator to `frequency` Hz.
+    pub fn set_rx_frequency(&mut self, frequency: u64) -> Result<(), PlutoError> {
+        self.context.set_frequency(RX_LO, frequency)
+    }
+
+    /// Tunes the transmit local oscillator to `frequency` Hz.
+    pub fn set_tx_frequency(&mut self, frequency: u64) -> Result<(), PlutoError> {
+        self.context.set_frequency(TX_LO, frequency)
+    }
+
+    /// Sets the sample rate of both directions.
+    pub fn set_sample_rate(&mut self, rate: u32) -> Result<(), PlutoError> {
+        self.context.set_sample_rate(rate)
+    }
+
+    /// Sets the receive analog filter bandwidth in Hz.
+    pub fn set_rx_bandwidth(&mut self, bandwidth: u32) -> Result<(), PlutoError> {
+        self.context.set_bandwidth(RX_PATH, bandwidth)
+    }
+
+    /// Sets the transmit analog filter bandwidth in Hz.
+    pub fn set_tx_bandwidth(&mut self, bandwidth: u32) -> Result<(), PlutoError> {
+        self.context.set_bandwidth(TX_PATH, bandwidth)
+    }
+
+    pub fn set_rx_gain(&mut self, gain: GainControl) -> Result<(), PlutoError> {
+        self.context.set_rx_gain(gain)
+    }
+
+    /// Sets the transmit attenuation, from 0 to 89.75 dB.
+    pub fn set_tx_attenuation(&mut self, attenuation: f64) -> Result<(), PlutoError> {
+        self.context.set_tx_attenuation(attenuation)
+    }
+
+    /// Starts receiving in blocks of `buffer_size` samples.
+    pub fn receiver(&self, buffer_size: usize) -> Result<PlutoReceiver, PlutoError> {
+        let buffer = self.context.buffer(self.context.rx, false, buffer_size)?;
+        trace_event!(info, buffer_size, "Pluto receiving");
+        Ok(PlutoReceiver {
+            context: Arc::clone(&self.context),
+            buffer,
+            pending: Vec::new(),
+            position: 0,
+            ppm: 0.0,
+        })
+    }
+
+    /// Starts transmitting in blocks of `buffer_size` samples.
+    pub fn transmitter(&self, buffer_size: usize) -> Result<PlutoTransmitter, PlutoError> {
+        let buffer = self.context.buffer(self.context.tx, true, buffer_size)?;
+        trace_event!(info, buffer_size, "Pluto transmitting");
+        Ok(PlutoTransmitter {
+            context: Arc::clone(&self.context),
+            buffer,
+            buffer_size,
+            staged: Vec::new(),
+            raw: Vec::new(),
+        })
+    }
+}
+
+/// Received samples. Tuning and gain changes through
+/// [`Source::set_parameter`] apply to the receive side.
+pub struct PlutoReceiver {
+    // Declared before the context so it is destroyed first.
+    buffer: Buffer,
+    context: Arc<Context>,
+    /// The last block refilled, and how much of it has been read.
+    pending: Vec<Complex<f64>>,
+    position: usize,
+    /// The reference oscillator's error, for [`SdrDevice::set_frequency`].
+    ppm: f64,
+}
+
+impl Source for PlutoReceiver {
+    type Sample = Complex<f64>;
+    type Error = PlutoError;
+
+    /// Blocks until the next block arrives when the last is used up.
+    fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, PlutoError> {
+        if self.position == self.pending.len() {
+            check("iio_buffer_refill", unsafe {
+                ffi::iio_buffer_refill(self.buffer.0)
+            })?;
+            self.pending.clear();
+            rx_to_complex(self.buffer.values(), &mut self.pending);
+            self.position = 0;
+        }
+        let count = buffer.len().min(self.pending.len() - self.position);
+        buffer[..count].copy_from_slice(&self.pending[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_parameter(&self.context, false, name, value)
+    }
+}
+
+/// The receive side's controls, with one gain stage, `rx`, and the
+/// AD9361's three receive ports as antennas.
+impl SdrDevice for PlutoReceiver {
+    fn name(&self) -> String {
+        "ADALM-Pluto".to_string()
+    }
+
+    fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+        vec![(FREQUENCY_RANGE.0 as f64, FREQUENCY_RANGE.1 as f64)]
+    }
+
+    fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+        let tuned = correct_frequency(frequency, self.ppm).round().max(0.0) as u64;
+        self.context.set_frequency(RX_LO, tuned)?;
+        Ok(uncorrect_frequency(tuned as f64, self.ppm))
+    }
+
+    /// Sets both directions' rate, which the AD9361 shares.
+    fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+        let rate = rate.round() as u32;
+        self.context.set_sample_rate(rate)?;
+        Ok(f64::from(rate))
+    }
+
+    fn gain_stages(&self) -> Vec<GainStage> {
+        vec![GainStage::new("rx", RX_GAIN_RANGE.0, RX_GAIN_RANGE.1, 1.0)]
+    }
+
+    fn set_gain(&mut self, stage: &str, gain: f64) -> Result<(), DeviceError> {
+        let gain = find_stage(&self.gain_stages(), stage)?.quantize(gain)?;
+        Ok(self.context.set_rx_gain(GainControl::Manual(gain))?)
+    }
+
+    /// Switches between slow attack AGC and manual gain, keeping the
+    /// gain the AGC last chose.
+    fn set_agc(&mut self, enabled: bool) -> Result<(), DeviceError> {
+        let mode = if enabled { "slow_attack" } else { "manual" };
+        Ok(self
+            .context
+            .write_str(RX_PATH, c"gain_control_mode", mode)?)
+    }
+
+    fn set_ppm(&mut self, ppm: f64) -> Result<(), DeviceError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+
+    fn antennas(&self) -> Vec<String> {
+        ["A_BALANCED", "B_BALANCED", "C_BALANCED"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn set_antenna(&mut self, name: &str) -> Result<(), DeviceError> {
+        if !self.antennas().iter().any(|antenna| antenna == name) {
+            return Err(DeviceError::Unsupported(format!("antenna {name}")));
+        }
+        Ok(self.context.write_str(RX_PATH, c"rf_port_select", name)?)
+    }
+}
+
+/// Samples to transmit, sent a whole block at a time. Samples should stay
+/// within `[-1, 1)`; larger ones are clipped.
+pub struct PlutoTransmitter {
+    buffer: Buffer,
+    context: Arc<Context>,
+    buffer_size: usize,
+    /// Samples short of a whole block.
+    staged: Vec<Complex<f64>>,
+    raw: Vec<i16>,
+}
+
+impl PlutoTransmitter {
+    /// Sends any staged samples, padded with silence to a whole block.
+    pub fn flush(&mut self) -> Result<(), PlutoError> {
+        if self.staged.is_empty() {
+            return Ok(());
+        }
+        self.staged.resize(self.buffer_size, Complex::new(0.0, 0.0));
+        self.push()
+    }
+
+    /// Sends the first block of staged samples.
+    fn push(&mut self) -> Result<(), PlutoError> {
+        self.raw.clear();
+        complex_to_tx(&self.staged[..self.buffer_size], &mut self.raw);
+        self.staged.drain(..self.buffer_size);
+        self.buffer.values().copy_from_slice(&self.raw);
+        check("iio_buffer_push", unsafe {
+            ffi::iio_buffer_push(self.buffer.0)
+        })
+        .map(drop)
+    }
+}
+
+impl Sink for PlutoTransmitter {
+    type Input = Complex<f64>;
+    type Error = PlutoError;
+
+    /// Blocks while whole blocks are sent, keeping the rest for the next
+    /// write or [`flush`](PlutoTransmitter::flush).
+    fn write(&mut self, input: &[Complex<f64>]) -> Result<(), PlutoError> {
+        self.staged.extend_from_slice(input);
+        while self.staged.len() >= self.buffer_size {
+            self.push()?;
+        }
+        Ok(())
+    }
+
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_parameter(&self.context, true, name, value)
+    }
+}
+
+impl Drop for PlutoTransmitter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,217 @@
+//! Splitting one source between several consumers.
+//!
+//! Inside a [`Flowgraph`](crate::flowgraph::Flowgraph) a port already feeds
+//! every block connected to it. Code that reads sources directly, such as a
+//! display loop that pulls spectra while a demodulator pulls audio, uses
+//! [`tee`] instead: each [`TeeOutput`] is a [`Source`] with its own read
+//! position, and sees every sample from the start.
+//!
+//! Samples are kept until the slowest output has read them, so an output
+//! that is never read holds the whole stream; drop outputs that are no
+//! longer wanted. Outputs are `Send` when the source is, so they can be read
+//! from different threads.
+
+use crate::param::{ParamError, ParamValue};
+use crate::source::Source;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+struct TeeState<S: Source> {
+    source: S,
+    /// Samples read from the source that some output has not seen yet.
+    samples: VecDeque<S::Sample>,
+    /// The stream index of `samples[0]`.
+    first: u64,
+    /// The next stream index of each output, `None` once it is dropped.
+    positions: Vec<Option<u64>>,
+    exhausted: bool,
+}
+
+impl<S: Source> TeeState<S> {
+    /// Drops samples every remaining output has read.
+    fn trim(&mut self) {
+        let slowest = self.positions.iter().flatten().min().copied();
+        let end = self.first + self.samples.len() as u64;
+        let keep_from = slowest.unwrap_or(end).min(end);
+        self.samples.drain(..(keep_from - self.first) as usize);
+        self.first = keep_from;
+    }
+}
+
+/// Splits `source` into `outputs` readers of the same stream.
+pub fn tee<S>(source: S, outputs: usize) -> Vec<TeeOutput<S>>
+where
+    S: Source,
+    S::Sample: Clone + Default,
+{
+    let state = Arc::new(Mutex::new(TeeState {
+        source,
+        samples: VecDeque::new(),
+        first: 0,
+        positions: vec![Some(0); outputs],
+        exhausted: false,
+    }));
+    (0..outputs)
+        .map(|index| TeeOutput {
+            state: Arc::clone(&state),
+            index,
+            scratch: Vec::new(),
+        })
+        .collect()
+}
+
+/// One reader of a [`tee`]d source.
+pub struct TeeOutput<S: Source> {
+    state: Arc<Mutex<TeeState<S>>>,
+    index: usize,
+    /// Room for reads from the source, kept between calls.
+    scratch: Vec<S::Sample>,
+}
+
+impl<S: Source> TeeOutput<S> {
+    /// Samples held for this output that it has not read yet.
+    pub fn backlog(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        let position = state.positions[self.index].unwrap_or(state.first);
+        (state.first + state.samples.len() as u64 - position) as usize
+    }
+
+    /// Samples held for all outputs together.
+    pub fn buffered(&self) -> usize {
+        self.state.lock().unwrap().samples.len()
+    }
+}
+
+impl<S> Source for TeeOutput<S>
+where
+    S: Source,
+    S::Sample: Clone + Default,
+{
+    type Sample = S::Sample;
+    type Error = S::Error;
+
+    /// Returns held samples first. An output that has caught up reads the
+    /// source, up to `buffer.len()` samples, on behalf of every output.
+    fn read(&mut self, buffer: &mut [S::Sample]) -> Result<usize, S::Error> {
+        let mut state = self.state.lock().unwrap();
+        let Some(position) = state.positions[self.index] else {
+            return Ok(0);
+        };
+        let end = state.first + state.samples.len() as u64;
+        if position == end && !state.exhausted && !buffer.is_empty() {
+            self.scratch.resize(buffer.len(), S::Sample::default());
+            let count = state.source.read(&mut self.scratch)?;
+            if count == 0 {
+                state.exhausted = true;
+            }
+            state.samples.extend(self.scratch.drain(..count));
+        }
+        let offset = (position - state.first) as usize;
+        let count = buffer.len().min(state.samples.len() - offset);
+        for (slot, sample) in buffer.iter_mut().zip(state.samples.range(offset..)) {
+            *slot = sample.clone();
+        }
+        state.positions[self.index] = Some(position + count as u64);
+        state.trim();
+        Ok(count)
+    }
+
+    /// Changes a parameter of the shared source, for every output.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        self.state.lock().unwrap().source.set_parameter(name, value)
+    }
+}
+
+impl<S: Source> Drop for TeeOutput<S> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.positions[self.index] = None;
+            state.trim();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts up from zero, failing once it reaches `fail_at`.
+    struct Counter {
+        next: u32,
+        end: u32,
+        fail_at: Option<u32>,
+    }
+
+    impl Source for Counter {
+        type Sample = u32;
+        type Error = &'static str;
+
+        fn read(&mut self, buffer: &mut [u32]) -> Result<usize, &'static str> {
+            if Some(self.next) == self.fail_at {
+                return Err("failed");
+            }
+            let count = buffer.len().min((self.end - self.next) as usize);
+            for slot in &mut buffer[..count] {
+                *slot = self.next;
+                self.next += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    fn read_all<S: Source<Sample = u32>>(source: &mut S, chunk: usize) -> Vec<u32>
+    where
+        S::Error: std::fmt::Debug,
+    {
+        let mut buffer = vec![0; chunk];
+        let mut samples = Vec::new();
+        loop {
+            match source.read(&mut buffer).unwrap() {
+                0 => return samples,
+                count => samples.extend_from_slice(&buffer[..count]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_outputs_read_independently() {
+        let counter = Counter {
+            next: 0,
+            end: 100,
+            fail_at: None,
+        };
+        let mut outputs = tee(counter, 3);
+        let mut buffer = [0; 7];
+        assert_eq!(outputs[0].read(&mut buffer).unwrap(), 7);
+        assert_eq!(outputs[1].backlog(), 7);
+        assert_eq!(outputs[1].read(&mut buffer[..3]).unwrap(), 3);
+        assert_eq!(buffer[..3], [0, 1, 2]);
+        assert_eq!(outputs[2].read(&mut buffer).unwrap(), 7);
+        // Everything up to the slowest output's position is released.
+        assert_eq!(outputs[0].buffered(), 4);
+
+        let expected: Vec<u32> = (0..100).collect();
+        assert_eq!(read_all(&mut outputs[0], 13), expected[7..]);
+        assert_eq!(read_all(&mut outputs[1], 5), expected[3..]);
+        let last = outputs.pop().unwrap();
+        assert_eq!(last.backlog(), 93);
+        drop(last);
+        assert_eq!(outputs[0].buffered(), 0);
+    }
+
+    #[test]
+    fn test_errors_reach_the_reading_output() {
+        let counter = Counter {
+            next: 0,
+            end: 100,
+            fail_at: Some(4),
+        };
+        let mut outputs = tee(counter, 2);
+        let mut buffer = [0; 4];
+        assert_eq!(outputs[0].read(&mut buffer).unwrap(), 4);
+        assert_eq!(outputs[0].read(&mut buffer), Err("failed"));
+        // The other output still gets what was read before the failure.
+        assert_eq!(outputs[1].read(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer, [0, 1, 2, 3]);
+    }
+}
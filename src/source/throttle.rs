@@ -0,0 +1,176 @@
+//! Pacing a source to a sample rate in wall-clock time.
+//!
+//! A capture file reads as fast as the disk allows, which is right for
+//! batch processing but not for an audio sink or a live display. Wrapping it
+//! in a [`Throttle`] makes it deliver samples no faster than the rate it was
+//! recorded at, as a receiver would.
+
+use crate::error::{DspError, Result};
+use crate::param::{ParamError, ParamValue};
+use crate::source::Source;
+use std::time::{Duration, Instant};
+
+/// Wraps a source so that it delivers at most `sample_rate` samples per
+/// second.
+pub struct Throttle<S> {
+    source: S,
+    sample_rate: f64,
+    /// Longest stretch of samples handed over in one read, in seconds.
+    max_chunk: f64,
+    /// When pacing began, and samples delivered since.
+    start: Option<Instant>,
+    samples: u64,
+}
+
+impl<S: Source> Throttle<S> {
+    /// Reads at most a fiftieth of a second of samples at a time, so
+    /// downstream sees a steady trickle rather than large bursts.
+    pub const DEFAULT_MAX_CHUNK: f64 = 0.02;
+    /// How far behind the consumer may fall before pacing restarts from
+    /// now, rather than catching up in a burst.
+    pub const MAX_LAG: f64 = 1.0;
+
+    /// Fails unless `sample_rate` is positive.
+    pub fn new(source: S, sample_rate: f64) -> Result<Self> {
+        check_rate(sample_rate)?;
+        Ok(Throttle {
+            source,
+            sample_rate,
+            max_chunk: Self::DEFAULT_MAX_CHUNK,
+            start: None,
+            samples: 0,
+        })
+    }
+
+    /// Caps each read at `seconds` worth of samples.
+    pub fn set_max_chunk(&mut self, seconds: f64) {
+        self.max_chunk = seconds.max(0.0);
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Changes the rate from the next read on. Fails unless `sample_rate`
+    /// is positive.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) -> Result<()> {
+        check_rate(sample_rate)?;
+        self.sample_rate = sample_rate;
+        self.start = None;
+        Ok(())
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.source
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+fn check_rate(sample_rate: f64) -> Result<()> {
+    if sample_rate.is_finite() && sample_rate > 0.0 {
+        Ok(())
+    } else {
+        Err(
+            DspError::InvalidArgument(format!("sample rate must be positive, got {sample_rate}"))
+                .into(),
+        )
+    }
+}
+
+impl<S: Source> Source for Throttle<S> {
+    type Sample = S::Sample;
+    type Error = S::Error;
+
+    /// Reads from the source, then waits until the samples are due.
+    fn read(&mut self, buffer: &mut [S::Sample]) -> Result<usize, S::Error> {
+        let limit = ((self.sample_rate * self.max_chunk) as usize).max(1);
+        let length = buffer.len().min(limit);
+        let count = self.source.read(&mut buffer[..length])?;
+        let now = Instant::now();
+        let start = match self.start {
+            Some(start)
+                if now.duration_since(start).as_secs_f64()
+                    - self.samples as f64 / self.sample_rate
+                    <= Self::MAX_LAG =>
+            {
+                start
+            }
+            _ => {
+                self.samples = 0;
+                *self.start.insert(now)
+            }
+        };
+        self.samples += count as u64;
+        let due = start + Duration::from_secs_f64(self.samples as f64 / self.sample_rate);
+        if let Some(wait) = due.checked_duration_since(now) {
+            std::thread::sleep(wait);
+        }
+        Ok(count)
+    }
+
+    /// Accepts `sample_rate` and passes every other name to the source.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        match name {
+            "sample_rate" => value
+                .as_f64()
+                .and_then(|rate| self.set_sample_rate(rate).ok())
+                .ok_or_else(|| ParamError::invalid(name, value)),
+            _ => self.source.set_parameter(name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    struct Zeros;
+
+    impl Source for Zeros {
+        type Sample = f32;
+        type Error = Infallible;
+
+        fn read(&mut self, buffer: &mut [f32]) -> Result<usize, Infallible> {
+            buffer.fill(0.0);
+            Ok(buffer.len())
+        }
+    }
+
+    #[test]
+    fn test_paces_reads() {
+        let mut throttle = Throttle::new(Zeros, 10_000.0).unwrap();
+        let mut buffer = vec![0.0; 4096];
+        let start = Instant::now();
+        let mut samples = 0;
+        while samples < 1000 {
+            let count = throttle.read(&mut buffer).unwrap();
+            // Each read is capped at 20 ms of samples.
+            assert!(count <= 200);
+            samples += count;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(95));
+    }
+
+    #[test]
+    fn test_parameters() {
+        assert!(Throttle::new(Zeros, 0.0).is_err());
+        let mut throttle = Throttle::new(Zeros, 1e3).unwrap();
+        throttle.set_parameter("sample_rate", &2e3.into()).unwrap();
+        assert_eq!(throttle.sample_rate(), 2e3);
+        assert!(throttle
+            .set_parameter("sample_rate", &(-1.0).into())
+            .is_err());
+        assert_eq!(
+            throttle.set_parameter("gain", &1.0.into()),
+            Err(ParamError::Unknown("gain".into()))
+        );
+    }
+}
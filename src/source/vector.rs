@@ -0,0 +1,248 @@
+//! Sources of fixed, known samples, for testing blocks and flowgraphs.
+//!
+//! A [`VectorSource`] plays back samples given to it, once or on repeat.
+//! [`ImpulseSource`] and [`StepSource`] produce the inputs whose responses
+//! characterise a filter: a unit impulse gives its taps and a unit step its
+//! settling. Paired with a [`VectorSink`](crate::sink::vector::VectorSink),
+//! they let a test compare a chain's exact output against expected samples.
+
+use crate::param::{ParamError, ParamValue};
+use crate::source::Source;
+use num_traits::{One, Zero};
+use std::convert::Infallible;
+
+/// Plays back a vector of samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorSource<T> {
+    samples: Vec<T>,
+    repeat: bool,
+    position: usize,
+}
+
+impl<T: Clone> VectorSource<T> {
+    /// Plays `samples` once, then is exhausted.
+    pub fn new(samples: Vec<T>) -> Self {
+        VectorSource {
+            samples,
+            repeat: false,
+            position: 0,
+        }
+    }
+
+    /// Plays `samples` over and over, never running out unless they are
+    /// empty.
+    pub fn repeating(samples: Vec<T>) -> Self {
+        VectorSource {
+            repeat: true,
+            ..VectorSource::new(samples)
+        }
+    }
+
+    /// Starts again from the first sample.
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    /// Samples left before the source is exhausted, or `None` on repeat.
+    pub fn remaining(&self) -> Option<usize> {
+        (!self.repeat).then(|| self.samples.len() - self.position)
+    }
+}
+
+impl<T: Clone> Source for VectorSource<T> {
+    type Sample = T;
+    type Error = Infallible;
+
+    fn read(&mut self, buffer: &mut [T]) -> Result<usize, Infallible> {
+        let mut filled = 0;
+        while filled < buffer.len() && !self.samples.is_empty() {
+            if self.position == self.samples.len() {
+                if !self.repeat {
+                    break;
+                }
+                self.position = 0;
+            }
+            let count = (buffer.len() - filled).min(self.samples.len() - self.position);
+            buffer[filled..filled + count]
+                .clone_from_slice(&self.samples[self.position..self.position + count]);
+            self.position += count;
+            filled += count;
+        }
+        Ok(filled)
+    }
+}
+
+/// Zeros with one sample of `amplitude`, by default one, after `delay`
+/// samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpulseSource<T> {
+    amplitude: T,
+    delay: usize,
+    length: usize,
+    position: usize,
+}
+
+impl<T: Copy + Zero + One> ImpulseSource<T> {
+    /// A unit impulse at the first sample, followed by zeros up to `length`
+    /// samples in all.
+    pub fn new(length: usize) -> Self {
+        ImpulseSource {
+            amplitude: T::one(),
+            delay: 0,
+            length,
+            position: 0,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: usize) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: T) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+}
+
+impl<T: Copy + Zero + One> Source for ImpulseSource<T> {
+    type Sample = T;
+    type Error = Infallible;
+
+    fn read(&mut self, buffer: &mut [T]) -> Result<usize, Infallible> {
+        let count = buffer.len().min(self.length - self.position);
+        for (slot, index) in buffer.iter_mut().zip(self.position..self.position + count) {
+            *slot = if index == self.delay {
+                self.amplitude
+            } else {
+                T::zero()
+            };
+        }
+        self.position += count;
+        Ok(count)
+    }
+
+    /// Accepts `delay`, restarting the source.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_delay(name, value, &mut self.delay, &mut self.position)
+    }
+}
+
+/// Zeros for `delay` samples, then `amplitude`, by default one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepSource<T> {
+    amplitude: T,
+    delay: usize,
+    length: usize,
+    position: usize,
+}
+
+impl<T: Copy + Zero + One> StepSource<T> {
+    /// A unit step at the first sample, held for `length` samples.
+    pub fn new(length: usize) -> Self {
+        StepSource {
+            amplitude: T::one(),
+            delay: 0,
+            length,
+            position: 0,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: usize) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: T) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+}
+
+impl<T: Copy + Zero + One> Source for StepSource<T> {
+    type Sample = T;
+    type Error = Infallible;
+
+    fn read(&mut self, buffer: &mut [T]) -> Result<usize, Infallible> {
+        let count = buffer.len().min(self.length - self.position);
+        for (slot, index) in buffer.iter_mut().zip(self.position..self.position + count) {
+            *slot = if index >= self.delay {
+                self.amplitude
+            } else {
+                T::zero()
+            };
+        }
+        self.position += count;
+        Ok(count)
+    }
+
+    /// Accepts `delay`, restarting the source.
+    fn set_parameter(&mut self, name: &str, value: &ParamValue) -> Result<(), ParamError> {
+        set_delay(name, value, &mut self.delay, &mut self.position)
+    }
+}
+
+fn set_delay(
+    name: &str,
+    value: &ParamValue,
+    delay: &mut usize,
+    position: &mut usize,
+) -> Result<(), ParamError> {
+    match (name, value) {
+        ("delay", ParamValue::Int(samples)) if *samples >= 0 => {
+            *delay = *samples as usize;
+            *position = 0;
+            Ok(())
+        }
+        ("delay", _) => Err(ParamError::invalid(name, value)),
+        _ => Err(ParamError::Unknown(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_complex::Complex;
+
+    #[test]
+    fn test_vector_source() {
+        let mut once = VectorSource::new(vec![1, 2, 3]);
+        let mut buffer = [0; 2];
+        assert_eq!(once.read(&mut buffer).unwrap(), 2);
+        assert_eq!(once.remaining(), Some(1));
+        assert_eq!(once.read(&mut buffer).unwrap(), 1);
+        assert_eq!(buffer[0], 3);
+        assert_eq!(once.read(&mut buffer).unwrap(), 0);
+
+        let mut looped = VectorSource::repeating(vec![1, 2, 3]);
+        let mut buffer = [0; 7];
+        assert_eq!(looped.read(&mut buffer).unwrap(), 7);
+        assert_eq!(buffer, [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(
+            VectorSource::<i32>::repeating(Vec::new()).read(&mut buffer),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_impulse_and_step() {
+        let mut impulse = ImpulseSource::new(6).with_delay(2).with_amplitude(0.5);
+        let mut buffer = [9.0; 4];
+        assert_eq!(impulse.read(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer, [0.0, 0.0, 0.5, 0.0]);
+        assert_eq!(impulse.read(&mut buffer).unwrap(), 2);
+        assert_eq!(impulse.read(&mut buffer).unwrap(), 0);
+
+        let mut step = StepSource::<Complex<f64>>::new(4).with_delay(1);
+        let mut buffer = [Complex::new(9.0, 9.0); 4];
+        assert_eq!(step.read(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer[0], Complex::new(0.0, 0.0));
+        assert!(buffer[1..]
+            .iter()
+            .all(|&sample| sample == Complex::new(1.0, 0.0)));
+        step.set_parameter("delay", &ParamValue::Int(3)).unwrap();
+        assert_eq!(step.read(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer[2], Complex::new(0.0, 0.0));
+        assert!(step.set_parameter("delay", &ParamValue::Int(-1)).is_err());
+    }
+}
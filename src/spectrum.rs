@@ -0,0 +1,405 @@
+//! FFT-based spectral analysis.
+//!
+//! With the `rayon` feature, transforms of at least [`PARALLEL_MIN`] points
+//! are split into row and column transforms run across threads, and
+//! [`psd`] averages its frames in parallel.
+
+pub mod sweep;
+
+use crate::dsp::complex::phase_difference;
+use num_complex::Complex;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use rustfft::{FftDirection, FftPlanner};
+use std::f64::consts::PI;
+
+/// Window functions applied before a transform to reduce spectral leakage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// The window's coefficients for a transform of `length` points.
+    pub fn coefficients(self, length: usize) -> Vec<f64> {
+        let denominator = length.saturating_sub(1).max(1) as f64;
+        (0..length)
+            .map(|n| {
+                let x = 2.0 * PI * n as f64 / denominator;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The smallest transform that [`fft`] and [`ifft`] split across threads
+/// when the `rayon` feature is enabled.
+pub const PARALLEL_MIN: usize = 1 << 16;
+
+/// Transforms `buffer` in place with a forward, unnormalized FFT.
+pub fn fft(buffer: &mut [Complex<f64>]) {
+    transform(buffer, FftDirection::Forward);
+}
+
+/// Transforms `buffer` in place with an inverse FFT scaled by `1 / len`, so
+/// that it undoes [`fft`].
+pub fn ifft(buffer: &mut [Complex<f64>]) {
+    if buffer.is_empty() {
+        return;
+    }
+    transform(buffer, FftDirection::Inverse);
+    let scale = 1.0 / buffer.len() as f64;
+    buffer.iter_mut().for_each(|x| *x *= scale);
+}
+
+fn transform(buffer: &mut [Complex<f64>], direction: FftDirection) {
+    if buffer.is_empty() {
+        return;
+    }
+    #[cfg(feature = "rayon")]
+    if buffer.len() >= PARALLEL_MIN && four_step(buffer, direction) {
+        return;
+    }
+    FftPlanner::new()
+        .plan_fft(buffer.len(), direction)
+        .process(buffer);
+}
+
+/// Bailey's four-step FFT: the `len = rows * columns` points are treated as
+/// a matrix, and each row and each column transform runs on the thread pool.
+/// Returns `false`, leaving `buffer` untouched, if the length is prime.
+#[cfg(feature = "rayon")]
+fn four_step(buffer: &mut [Complex<f64>], direction: FftDirection) -> bool {
+    let length = buffer.len();
+    // The largest divisor no greater than the square root, for the most even
+    // split.
+    let Some(rows) = (2..=length.isqrt())
+        .rev()
+        .find(|rows| length.is_multiple_of(*rows))
+    else {
+        return false;
+    };
+    let columns = length / rows;
+    let mut planner = FftPlanner::new();
+    let column_fft = planner.plan_fft(columns, direction);
+    let row_fft = planner.plan_fft(rows, direction);
+    let sign = match direction {
+        FftDirection::Forward => -1.0,
+        FftDirection::Inverse => 1.0,
+    };
+    let zero = Complex::new(0.0, 0.0);
+
+    // Sample n1 + rows * n2 goes to row n1, column n2. Each row is
+    // transformed and multiplied by the twiddle factors.
+    let mut matrix = vec![zero; length];
+    let input: &[Complex<f64>] = buffer;
+    matrix.par_chunks_mut(columns).enumerate().for_each_init(
+        || vec![zero; column_fft.get_inplace_scratch_len()],
+        |scratch, (n1, row)| {
+            for (n2, value) in row.iter_mut().enumerate() {
+                *value = input[n1 + rows * n2];
+            }
+            column_fft.process_with_scratch(row, scratch);
+            for (k2, value) in row.iter_mut().enumerate() {
+                let turns = (n1 * k2 % length) as f64 / length as f64;
+                *value *= Complex::from_polar(1.0, sign * 2.0 * PI * turns);
+            }
+        },
+    );
+    // Transposed, so that each of the other transforms is contiguous.
+    buffer.par_chunks_mut(rows).enumerate().for_each_init(
+        || vec![zero; row_fft.get_inplace_scratch_len()],
+        |scratch, (k2, row)| {
+            for (n1, value) in row.iter_mut().enumerate() {
+                *value = matrix[n1 * columns + k2];
+            }
+            row_fft.process_with_scratch(row, scratch);
+        },
+    );
+    // Bin k2 + columns * k1 is at k2 * rows + k1.
+    let transposed: &[Complex<f64>] = buffer;
+    matrix
+        .par_chunks_mut(columns)
+        .enumerate()
+        .for_each(|(k1, row)| {
+            for (k2, value) in row.iter_mut().enumerate() {
+                *value = transposed[k2 * rows + k1];
+            }
+        });
+    buffer.copy_from_slice(&matrix);
+    true
+}
+
+/// The power of real `samples` at one frequency in Hz by the Goertzel
+/// algorithm, scaled so that a sine of amplitude `a` at that frequency reads
+/// `a² / 2`. Cheaper than a transform when only a few tones matter, and not
+/// tied to bin centres. Returns zero for no samples.
+pub fn tone_power(samples: &[f64], frequency: f64, sample_rate: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate).cos();
+    let (mut previous, mut before) = (0.0, 0.0);
+    for &sample in samples {
+        let current = sample + coefficient * previous - before;
+        before = previous;
+        previous = current;
+    }
+    let power = previous * previous + before * before - coefficient * previous * before;
+    2.0 * power / (samples.len() as f64).powi(2)
+}
+
+/// The frequency in Hz of FFT bin `bin` (which may be fractional) for a
+/// transform of `length` points, mapping the upper half to negative values.
+pub fn bin_frequency(bin: f64, length: usize, sample_rate: f64) -> f64 {
+    let length = length as f64;
+    let bin = if bin >= length / 2.0 {
+        bin - length
+    } else {
+        bin
+    };
+    bin * sample_rate / length
+}
+
+/// Transforms `samples` after weighting them by `window`, whose length must
+/// match.
+pub(crate) fn windowed_spectrum(samples: &[Complex<f64>], window: &[f64]) -> Vec<Complex<f64>> {
+    let mut spectrum: Vec<Complex<f64>> = samples
+        .iter()
+        .zip(window)
+        .map(|(&sample, &weight)| sample * weight)
+        .collect();
+    fft(&mut spectrum);
+    spectrum
+}
+
+/// The power spectral density of `samples` by Welch's method, in squared
+/// units per hertz and FFT bin order: Hann-windowed frames of `fft_size`
+/// points, or fewer if `samples` is shorter, overlapping by half.
+///
+/// Frames are averaged across threads with the `rayon` feature.
+pub fn psd(samples: &[Complex<f64>], fft_size: usize, sample_rate: f64) -> Vec<f64> {
+    let size = fft_size.min(samples.len());
+    if size == 0 {
+        return Vec::new();
+    }
+    let window = Window::Hann.coefficients(size);
+    let scale = sample_rate * window.iter().map(|w| w * w).sum::<f64>();
+    let step = (size / 2).max(1);
+    let frames = (samples.len() - size) / step + 1;
+    let add_frame = |mut totals: Vec<f64>, frame: usize| {
+        let start = frame * step;
+        let spectrum = windowed_spectrum(&samples[start..start + size], &window);
+        for (total, bin) in totals.iter_mut().zip(&spectrum) {
+            *total += bin.norm_sqr();
+        }
+        totals
+    };
+    #[cfg(feature = "rayon")]
+    let totals = (0..frames)
+        .into_par_iter()
+        .fold(|| vec![0.0; size], add_frame)
+        .reduce(
+            || vec![0.0; size],
+            |mut totals, other| {
+                totals.iter_mut().zip(&other).for_each(|(a, b)| *a += b);
+                totals
+            },
+        );
+    #[cfg(not(feature = "rayon"))]
+    let totals = (0..frames).fold(vec![0.0; size], add_frame);
+    totals
+        .into_iter()
+        .map(|total| total / (scale * frames as f64))
+        .collect()
+}
+
+fn peak_bin(spectrum: &[Complex<f64>]) -> Option<usize> {
+    spectrum
+        .iter()
+        .map(|x| x.norm_sqr())
+        .enumerate()
+        .filter(|&(_, power)| power > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(bin, _)| bin)
+}
+
+/// How [`estimate_frequency_with`] refines the FFT peak below one bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Fits a parabola through the log magnitudes of the peak and its two
+    /// neighbours. Robust in noise; typically within a few hundredths of a bin.
+    Parabolic,
+    /// Compares the peak's phase in two transforms offset by one sample.
+    /// Near exact for a clean tone, but noisier at low SNR.
+    Phase,
+}
+
+/// Estimates the frequency in Hz of the strongest tone in `buffer` with
+/// sub-bin accuracy, using a Hann-windowed FFT and parabolic interpolation.
+///
+/// Returns `None` if the buffer is shorter than three samples or silent.
+pub fn estimate_frequency(buffer: &[Complex<f64>], sample_rate: f64) -> Option<f64> {
+    estimate_frequency_with(buffer, sample_rate, Interpolation::Parabolic)
+}
+
+/// Like [`estimate_frequency`], with a choice of interpolation.
+pub fn estimate_frequency_with(
+    buffer: &[Complex<f64>],
+    sample_rate: f64,
+    interpolation: Interpolation,
+) -> Option<f64> {
+    if buffer.len() < 3 {
+        return None;
+    }
+    match interpolation {
+        Interpolation::Parabolic => {
+            let spectrum = windowed_spectrum(buffer, &Window::Hann.coefficients(buffer.len()));
+            let length = spectrum.len();
+            let peak = peak_bin(&spectrum)?;
+            let magnitude = |bin: usize| spectrum[bin % length].norm().max(f64::MIN_POSITIVE).ln();
+            let (left, center, right) = (
+                magnitude(peak + length - 1),
+                magnitude(peak),
+                magnitude(peak + 1),
+            );
+            let curvature = left - 2.0 * center + right;
+            let offset = if curvature < 0.0 {
+                (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            };
+            Some(bin_frequency(
+                (peak as f64 + offset).rem_euclid(length as f64),
+                length,
+                sample_rate,
+            ))
+        }
+        Interpolation::Phase => {
+            let length = buffer.len() - 1;
+            let window = Window::Hann.coefficients(length);
+            let first = windowed_spectrum(&buffer[..length], &window);
+            let second = windowed_spectrum(&buffer[1..], &window);
+            let peak = peak_bin(&first)?;
+            let step = phase_difference(second[peak], first[peak]);
+            Some(step / (2.0 * PI) * sample_rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_tone_power() {
+        let sine: Vec<f64> = (0..4_000)
+            .map(|n| 0.5 * (2.0 * PI * 123.0 * n as f64 / 8_000.0 + 0.3).sin())
+            .collect();
+        assert_relative_eq!(
+            tone_power(&sine, 123.0, 8_000.0),
+            0.125,
+            max_relative = 1e-3
+        );
+        assert!(tone_power(&sine, 129.0, 8_000.0) < 1e-6);
+        assert_eq!(tone_power(&[], 100.0, 8_000.0), 0.0);
+    }
+
+    fn tone(frequency: f64, sample_rate: f64, length: usize) -> Vec<Complex<f64>> {
+        (0..length)
+            .map(|n| Complex::from_polar(1.0, 2.0 * PI * frequency * n as f64 / sample_rate))
+            .collect()
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        let original = tone(3.0, 16.0, 16);
+        let mut buffer = original.clone();
+        fft(&mut buffer);
+        assert_relative_eq!(buffer[3].re, 16.0, epsilon = 1e-9);
+        ifft(&mut buffer);
+        for (a, b) in buffer.iter().zip(&original) {
+            assert_relative_eq!((a - b).norm(), 0.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_psd_integrates_to_signal_power() {
+        let samples: Vec<Complex<f64>> = tone(1000.0, 8000.0, 10_000)
+            .into_iter()
+            .map(|sample| sample * 2.0)
+            .collect();
+        let density = psd(&samples, 256, 8000.0);
+        assert_eq!(density.len(), 256);
+        let power: f64 = density.iter().sum::<f64>() * 8000.0 / 256.0;
+        assert_relative_eq!(power, 4.0, epsilon = 1e-9);
+        let peak = peak_bin(
+            &density
+                .iter()
+                .map(|&d| Complex::new(d, 0.0))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(peak, Some(32));
+        assert!(psd(&[], 256, 8000.0).is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_four_step_matches_direct_transform() {
+        for length in [12, 1024, 3 * 5 * 7 * 11] {
+            let original: Vec<Complex<f64>> = (0..length)
+                .map(|n| Complex::new((0.37 * n as f64).sin(), (n % 7) as f64))
+                .collect();
+            for direction in [FftDirection::Forward, FftDirection::Inverse] {
+                let mut expected = original.clone();
+                FftPlanner::new()
+                    .plan_fft(length, direction)
+                    .process(&mut expected);
+                let mut buffer = original.clone();
+                assert!(four_step(&mut buffer, direction));
+                for (a, b) in buffer.iter().zip(&expected) {
+                    assert_relative_eq!((a - b).norm(), 0.0, epsilon = 1e-9);
+                }
+            }
+        }
+        assert!(!four_step(
+            &mut [Complex::new(1.0, 0.0); 13],
+            FftDirection::Forward
+        ));
+    }
+
+    #[test]
+    fn test_parabolic_estimate_is_sub_bin() {
+        // Bins are 11.7 Hz wide here.
+        let estimate = estimate_frequency(&tone(1234.5, 48_000.0, 4096), 48_000.0).unwrap();
+        assert_relative_eq!(estimate, 1234.5, epsilon = 0.5);
+        let negative = estimate_frequency(&tone(-5000.3, 48_000.0, 4096), 48_000.0).unwrap();
+        assert_relative_eq!(negative, -5000.3, epsilon = 0.5);
+    }
+
+    #[test]
+    fn test_phase_estimate_is_near_exact_for_clean_tone() {
+        let estimate = estimate_frequency_with(
+            &tone(1234.5, 48_000.0, 1024),
+            48_000.0,
+            Interpolation::Phase,
+        )
+        .unwrap();
+        assert_relative_eq!(estimate, 1234.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_silence_has_no_estimate() {
+        assert_eq!(estimate_frequency(&[Complex::new(0.0, 0.0); 64], 1.0), None);
+        assert_eq!(estimate_frequency(&[Complex::new(1.0, 0.0); 2], 1.0), None);
+    }
+}
@@ -0,0 +1,381 @@
+//! Power sweeps across more spectrum than one tuning covers, written as
+//! `rtl_power` writes them.
+//!
+//! A [`SweepPlan`] splits the range into hops a little narrower than the
+//! sample rate, so the edges of each hop, where the receiver's filters roll
+//! off, can be cropped away. Each hop is tuned in turn and its samples are
+//! integrated into one Welch PSD, and each becomes one [`SweepRow`] in the
+//! CSV format of `rtl_power`:
+//!
+//! ```text
+//! date, time, Hz low, Hz high, Hz step, samples, dB, dB, ...
+//! ```
+//!
+//! so heatmap tools written for it, such as `heatmap.py`, read the output
+//! unchanged.
+//!
+//! `rtl_power` writes its date and time columns in the machine's local
+//! time. This crate has no time-zone database, so rows are written in UTC
+//! unless [`write_csv`] is given the local offset from UTC; with an offset
+//! of zero the times differ from `rtl_power`'s by the local offset, though
+//! the rows still parse the same way.
+
+use crate::dsp::db::power_to_db;
+use crate::error::{DspError, Result, SdrError};
+use crate::source::control::SdrDevice;
+use crate::source::Source;
+use crate::spectrum::psd;
+use num_complex::Complex;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lowest power written, in dB, for bins that measure nothing at all.
+const FLOOR_DB: f64 = -200.0;
+
+/// How a range is split into hops.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SweepPlan {
+    /// Low and high edges of the range, in Hz.
+    pub start: f64,
+    pub stop: f64,
+    /// Complex sample rate in Hz.
+    pub sample_rate: f64,
+    /// Points in each FFT, a power of two.
+    pub fft_size: usize,
+    /// The share of each hop's bins thrown away, half at each edge.
+    pub crop: f64,
+    /// Samples discarded after each retune while the tuner settles.
+    pub settle_samples: usize,
+}
+
+impl SweepPlan {
+    /// A plan with bins no wider than `bin_width` Hz, the FFT size rounded
+    /// up to a power of two, and a quarter of each hop cropped.
+    pub fn new(start: f64, stop: f64, bin_width: f64, sample_rate: f64) -> Result<Self> {
+        if !(start.is_finite() && stop.is_finite() && stop > start) {
+            return Err(DspError::InvalidArgument(format!(
+                "sweep range {start} to {stop} Hz is empty"
+            ))
+            .into());
+        }
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(DspError::InvalidArgument(format!(
+                "sample rate must be positive, got {sample_rate}"
+            ))
+            .into());
+        }
+        if !(bin_width > 0.0 && bin_width <= sample_rate) {
+            return Err(DspError::InvalidArgument(format!(
+                "bin width must be positive and at most the sample rate, got {bin_width}"
+            ))
+            .into());
+        }
+        let fft_size = ((sample_rate / bin_width).ceil() as usize)
+            .next_power_of_two()
+            .max(2);
+        Ok(SweepPlan {
+            start,
+            stop,
+            sample_rate,
+            fft_size,
+            crop: 0.25,
+            settle_samples: (sample_rate * 0.005) as usize,
+        })
+    }
+
+    /// The width of each bin, in Hz.
+    pub fn bin_width(&self) -> f64 {
+        self.sample_rate / self.fft_size as f64
+    }
+
+    /// Bins kept from each hop.
+    pub fn bins(&self) -> usize {
+        let kept = (self.fft_size as f64 * (1.0 - self.crop.clamp(0.0, 1.0))).round() as usize;
+        kept.clamp(1, self.fft_size)
+    }
+
+    /// The spectrum each hop contributes, in Hz.
+    pub fn hop_width(&self) -> f64 {
+        self.bins() as f64 * self.bin_width()
+    }
+
+    /// The centre frequency of every hop, lowest first. The last hop may
+    /// run past [`stop`](SweepPlan::stop).
+    pub fn centers(&self) -> Vec<f64> {
+        let width = self.hop_width();
+        let hops = ((self.stop - self.start) / width).ceil().max(1.0) as usize;
+        (0..hops)
+            .map(|hop| self.start + width * (hop as f64 + 0.5))
+            .collect()
+    }
+
+    /// Integrates samples taken at `center` into a row stamped `time`, in
+    /// UNIX seconds. The PSD keeps the bins between the cropped edges,
+    /// lowest frequency first, as power per bin in dB.
+    pub fn integrate(&self, center: f64, samples: &[Complex<f64>], time: f64) -> SweepRow {
+        let size = self.fft_size;
+        let density = psd(samples, size, self.sample_rate);
+        let bins = self.bins();
+        let first = (size - bins) / 2;
+        let step = self.bin_width();
+        let db = (first..first + bins)
+            .map(|bin| {
+                let power = density
+                    .get((bin + size / 2) % size)
+                    .map_or(0.0, |density| density * step);
+                power_to_db(power, FLOOR_DB)
+            })
+            .collect();
+        let low = center - size as f64 / 2.0 * step + first as f64 * step;
+        SweepRow {
+            time,
+            low,
+            high: low + bins as f64 * step,
+            step,
+            samples: samples.len(),
+            db,
+        }
+    }
+
+    /// Sweeps `radio` across the plan once, integrating `samples` samples
+    /// at every hop.
+    pub fn sweep<R>(&self, radio: &mut R, samples: usize) -> Result<Vec<SweepRow>>
+    where
+        R: SdrDevice + Source<Sample = Complex<f64>>,
+        R::Error: Into<SdrError>,
+    {
+        let mut rows = Vec::new();
+        let mut buffer = vec![Complex::new(0.0, 0.0); self.settle_samples + samples];
+        for center in self.centers() {
+            radio.set_frequency(center)?;
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+            let mut filled = 0;
+            while filled < buffer.len() {
+                match radio.read(&mut buffer[filled..]).map_err(Into::into)? {
+                    0 => return Ok(rows),
+                    read => filled += read,
+                }
+            }
+            rows.push(self.integrate(center, &buffer[self.settle_samples..], time));
+        }
+        trace_event!(debug, hops = rows.len(), "sweep complete");
+        Ok(rows)
+    }
+}
+
+/// One hop of a sweep, an `rtl_power` CSV row.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SweepRow {
+    /// When the hop was tuned, in UNIX seconds.
+    pub time: f64,
+    /// The low edge of the first bin and the high edge of the last, in Hz.
+    pub low: f64,
+    pub high: f64,
+    /// The bin width in Hz.
+    pub step: f64,
+    /// Samples integrated.
+    pub samples: usize,
+    /// Power in each bin, in dB.
+    pub db: Vec<f64>,
+}
+
+/// Year, month and day of a count of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl SweepRow {
+    /// The row as `rtl_power` prints it, without the newline, with the time
+    /// shifted by `utc_offset` seconds; pass the local offset to match
+    /// `rtl_power`, or zero for UTC.
+    pub fn to_csv(&self, utc_offset: i32) -> String {
+        let seconds = self.time.floor() as i64 + i64::from(utc_offset);
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+        let of_day = seconds.rem_euclid(86_400);
+        let mut row = format!(
+            "{year:04}-{month:02}-{day:02}, {:02}:{:02}:{:02}, {}, {}, {:.2}, {}",
+            of_day / 3600,
+            of_day / 60 % 60,
+            of_day % 60,
+            self.low.round() as i64,
+            self.high.round() as i64,
+            self.step,
+            self.samples
+        );
+        for db in &self.db {
+            row.push_str(&format!(", {db:.2}"));
+        }
+        row
+    }
+}
+
+/// Writes `rows` as `rtl_power` CSV, one line each, with times shifted by
+/// `utc_offset` seconds as by [`SweepRow::to_csv`].
+pub fn write_csv<W: Write>(
+    output: &mut W,
+    rows: &[SweepRow],
+    utc_offset: i32,
+) -> std::io::Result<()> {
+    for row in rows {
+        writeln!(output, "{}", row.to_csv(utc_offset))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DeviceError;
+    use crate::source::control::GainStage;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_plan_hops() {
+        let plan = SweepPlan::new(88e6, 108e6, 1e3, 2.4e6).unwrap();
+        assert_eq!(plan.fft_size, 4096);
+        assert_eq!(plan.bins(), 3072);
+        assert_relative_eq!(plan.hop_width(), 1.8e6, epsilon = 1e-6);
+        let centers = plan.centers();
+        assert_eq!(centers.len(), 12);
+        assert_relative_eq!(centers[0], 88.9e6, epsilon = 1e-3);
+        assert!(*centers.last().unwrap() + plan.hop_width() / 2.0 >= 108e6);
+
+        assert!(SweepPlan::new(108e6, 88e6, 1e3, 2.4e6).is_err());
+        assert!(SweepPlan::new(88e6, 108e6, 0.0, 2.4e6).is_err());
+        assert!(SweepPlan::new(88e6, 108e6, 1e3, -1.0).is_err());
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let row = SweepRow {
+            time: 1_714_566_896.7,
+            low: 88e6,
+            high: 88.0035e6,
+            step: 976.5625,
+            samples: 262_144,
+            db: vec![-45.2, -44.125, -30.0],
+        };
+        assert_eq!(
+            row.to_csv(0),
+            "2024-05-01, 12:34:56, 88000000, 88003500, 976.56, 262144, -45.20, -44.12, -30.00"
+        );
+        // Thirteen hours behind UTC puts the row on the previous day.
+        assert!(row.to_csv(-13 * 3600).starts_with("2024-04-30, 23:34:56, "));
+        let mut output = Vec::new();
+        write_csv(&mut output, &[row.clone(), row], 3600).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with("2024-05-01, 13:34:56, "));
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    /// One carrier on `tone` Hz seen through whatever the radio is tuned to.
+    struct Carrier {
+        rate: f64,
+        tone: f64,
+        frequency: f64,
+        position: u64,
+    }
+
+    impl Source for Carrier {
+        type Sample = Complex<f64>;
+        type Error = SdrError;
+
+        fn read(&mut self, buffer: &mut [Complex<f64>]) -> Result<usize, SdrError> {
+            let offset = self.tone - self.frequency;
+            for slot in buffer.iter_mut() {
+                let t = self.position as f64 / self.rate;
+                *slot = if offset.abs() < self.rate / 2.0 {
+                    Complex::from_polar(1.0, 2.0 * PI * offset * t)
+                } else {
+                    Complex::new(1e-6, 0.0)
+                };
+                self.position += 1;
+            }
+            Ok(buffer.len())
+        }
+    }
+
+    impl SdrDevice for Carrier {
+        fn name(&self) -> String {
+            "carrier".to_string()
+        }
+
+        fn frequency_ranges(&self) -> Vec<(f64, f64)> {
+            vec![(0.0, 1e9)]
+        }
+
+        fn set_frequency(&mut self, frequency: f64) -> Result<f64, DeviceError> {
+            self.frequency = frequency;
+            Ok(frequency)
+        }
+
+        fn set_sample_rate(&mut self, rate: f64) -> Result<f64, DeviceError> {
+            Ok(rate)
+        }
+
+        fn gain_stages(&self) -> Vec<GainStage> {
+            Vec::new()
+        }
+
+        fn set_gain(&mut self, stage: &str, _gain: f64) -> Result<(), DeviceError> {
+            Err(DeviceError::Unsupported(stage.to_string()))
+        }
+
+        fn set_ppm(&mut self, _ppm: f64) -> Result<(), DeviceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sweep_finds_carrier() {
+        let plan = SweepPlan::new(100e3, 400e3, 500.0, 64e3).unwrap();
+        let mut radio = Carrier {
+            rate: 64e3,
+            tone: 251_234.0,
+            frequency: 0.0,
+            position: 0,
+        };
+        let rows = plan.sweep(&mut radio, 8192).unwrap();
+        assert_eq!(rows.len(), plan.centers().len());
+        // Rows tile the range without gaps.
+        for pair in rows.windows(2) {
+            assert_relative_eq!(pair[0].high, pair[1].low, epsilon = 1e-6);
+        }
+        let (frequency, peak) = rows
+            .iter()
+            .flat_map(|row| {
+                row.db
+                    .iter()
+                    .enumerate()
+                    .map(move |(bin, &db)| (row.low + (bin as f64 + 0.5) * row.step, db))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert!(
+            (frequency - 251_234.0).abs() < plan.bin_width(),
+            "{frequency}"
+        );
+        assert!(peak > -10.0, "{peak}");
+    }
+}
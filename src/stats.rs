@@ -0,0 +1,1038 @@
+//! Circular statistics for bearings.
+//!
+//! Angles are in degrees. Each reading carries a weight, so stronger or more
+//! trusted readings can count for more; pass 1.0 for an unweighted summary.
+
+use std::f64::consts::PI;
+
+/// Summary statistics of a set of weighted bearings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CircularSummary {
+    /// Number of readings.
+    pub count: usize,
+    /// Kish's effective sample size, `(Σw)² / Σw²`, which equals `count`
+    /// when all weights are equal.
+    pub effective_count: f64,
+    /// Mean direction in `[0, 360)`.
+    pub mean: f64,
+    /// Length of the mean resultant vector, from 0 (no preferred direction)
+    /// to 1 (all readings identical).
+    pub resultant_length: f64,
+    /// Circular skewness, `b̄₂ / (1 - R̄)^1.5` after Fisher: zero for a
+    /// symmetric distribution and, with this definition, negative when the
+    /// readings trail off towards larger angles (clockwise of the mean).
+    pub skewness: f64,
+    /// Circular kurtosis, `(ā₂ - R̄⁴) / (1 - R̄)²` after Fisher: zero for a
+    /// wrapped normal distribution, positive when more sharply peaked.
+    pub kurtosis: f64,
+}
+
+impl CircularSummary {
+    /// Circular variance, `1 - R̄`.
+    pub fn variance(&self) -> f64 {
+        1.0 - self.resultant_length
+    }
+
+    /// Circular standard deviation in degrees, `sqrt(-2 ln R̄)`.
+    pub fn standard_deviation(&self) -> f64 {
+        (-2.0 * self.resultant_length.ln()).sqrt().to_degrees()
+    }
+
+    /// Half-width in degrees of the `confidence` interval (0.95 for 95%)
+    /// around the mean direction.
+    ///
+    /// Uses the approximations given by Zar for unimodal data. Returns `None`
+    /// when the readings are too dispersed for the mean to be meaningful at
+    /// that level.
+    pub fn confidence_interval(&self, confidence: f64) -> Option<f64> {
+        if !(0.0..1.0).contains(&confidence) || self.resultant_length <= 0.0 {
+            return None;
+        }
+        let chi2 = normal_quantile(0.5 + confidence / 2.0).powi(2);
+        let n = self.effective_count;
+        let r = n * self.resultant_length;
+        let cosine = if self.resultant_length <= 0.9 {
+            if self.resultant_length <= (chi2 / (2.0 * n)).sqrt() {
+                return None;
+            }
+            (2.0 * n * (2.0 * r * r - n * chi2) / (4.0 * n - chi2)).sqrt() / r
+        } else {
+            (n * n - (n * n - r * r) * (chi2 / n).exp()).sqrt() / r
+        };
+        if cosine.is_nan() {
+            return None;
+        }
+        Some(cosine.clamp(-1.0, 1.0).acos().to_degrees())
+    }
+}
+
+/// A percentile interval for a mean bearing, from [`bootstrap_interval`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BootstrapInterval {
+    /// The readings' mean direction in `[0, 360)`.
+    pub mean: f64,
+    /// The interval runs clockwise from `lower` to `upper`, both in
+    /// `[0, 360)` and not necessarily symmetric about the mean.
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl BootstrapInterval {
+    /// The interval's width in degrees.
+    pub fn width(&self) -> f64 {
+        (self.upper - self.lower).rem_euclid(360.0)
+    }
+}
+
+/// A `confidence` interval (0.95 for 95%) for the mean direction of
+/// `(angle, weight)` readings by bootstrap resampling, for small or
+/// non-von-Mises samples where
+/// [`confidence_interval`](CircularSummary::confidence_interval) is
+/// unreliable.
+///
+/// The readings are resampled with replacement `resamples` times, a
+/// thousand or more for 95% intervals, and the interval spans the central
+/// `confidence` of the resampled means. `seed` makes the result repeatable.
+/// Returns `None` for no readings, zero total weight, a confidence outside
+/// `(0, 1)`, or when the readings have no mean direction.
+pub fn bootstrap_interval(
+    readings: &[(f64, f64)],
+    confidence: f64,
+    resamples: usize,
+    seed: u64,
+) -> Option<BootstrapInterval> {
+    if !(confidence > 0.0 && confidence < 1.0) || resamples == 0 {
+        return None;
+    }
+    let summary = summarize(readings)?;
+    if summary.resultant_length <= 1e-12 {
+        return None;
+    }
+    let vectors: Vec<(f64, f64)> = readings
+        .iter()
+        .map(|&(angle, weight)| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            (weight * sin, weight * cos)
+        })
+        .collect();
+    let mut random = crate::bench::lcg(seed);
+    let mut deviations: Vec<f64> = (0..resamples)
+        .filter_map(|_| {
+            let (mut y, mut x) = (0.0, 0.0);
+            for _ in 0..vectors.len() {
+                let unit = (random.next()? + 1.0) / 2.0;
+                let index = ((unit * vectors.len() as f64) as usize).min(vectors.len() - 1);
+                y += vectors[index].0;
+                x += vectors[index].1;
+            }
+            // A resample whose readings cancel has no mean to count.
+            (x != 0.0 || y != 0.0).then(|| {
+                let mean = y.atan2(x).to_degrees();
+                (mean - summary.mean + 180.0).rem_euclid(360.0) - 180.0
+            })
+        })
+        .collect();
+    if deviations.is_empty() {
+        return None;
+    }
+    deviations.sort_by(f64::total_cmp);
+    let quantile = |p: f64| {
+        let position = p * (deviations.len() - 1) as f64;
+        let below = position.floor() as usize;
+        let above = position.ceil() as usize;
+        let fraction = position - below as f64;
+        deviations[below] + fraction * (deviations[above] - deviations[below])
+    };
+    let tail = (1.0 - confidence) / 2.0;
+    Some(BootstrapInterval {
+        mean: summary.mean,
+        lower: (summary.mean + quantile(tail)).rem_euclid(360.0),
+        upper: (summary.mean + quantile(1.0 - tail)).rem_euclid(360.0),
+    })
+}
+
+/// Summarizes `(angle, weight)` readings, or returns `None` if there are
+/// none or their weights sum to zero.
+///
+/// Skewness and kurtosis are reported as zero when all readings coincide.
+pub fn summarize(readings: &[(f64, f64)]) -> Option<CircularSummary> {
+    let (mut x, mut y, mut total, mut squares) = (0.0, 0.0, 0.0, 0.0);
+    // The second trigonometric moment, of the doubled angles.
+    let (mut x2, mut y2) = (0.0, 0.0);
+    for &(angle, weight) in readings {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        x += weight * cos;
+        y += weight * sin;
+        x2 += weight * (cos * cos - sin * sin);
+        y2 += weight * 2.0 * sin * cos;
+        total += weight;
+        squares += weight * weight;
+    }
+    if readings.is_empty() || total <= 0.0 {
+        return None;
+    }
+    let mean = y.atan2(x);
+    let resultant_length = (x * x + y * y).sqrt() / total;
+    // The second moment about the mean direction.
+    let (sin, cos) = (2.0 * mean).sin_cos();
+    let central_cos = (x2 * cos + y2 * sin) / total;
+    let central_sin = (y2 * cos - x2 * sin) / total;
+    let spread = 1.0 - resultant_length;
+    let (skewness, kurtosis) = if spread > 1e-12 {
+        (
+            central_sin / spread.powf(1.5),
+            (central_cos - resultant_length.powi(4)) / (spread * spread),
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    Some(CircularSummary {
+        count: readings.len(),
+        effective_count: total * total / squares,
+        mean: mean.to_degrees().rem_euclid(360.0),
+        resultant_length,
+        skewness,
+        kurtosis,
+    })
+}
+
+/// The weighted circular median of `(angle, weight)` readings in `[0, 360)`:
+/// the direction minimizing the weighted sum of arc distances to the
+/// readings. Pass equal weights for the plain median.
+///
+/// Unlike the mean it is barely moved by a few wild readings, yet a strong
+/// reading still counts for more than a weak one. When the minimum is
+/// reached along the arc between two neighbouring readings, as with an even
+/// number of equally weighted readings, its midpoint is returned. Returns
+/// `None` if no reading has a positive weight.
+pub fn circular_median(readings: &[(f64, f64)]) -> Option<f64> {
+    let mut sorted: Vec<(f64, f64)> = readings
+        .iter()
+        .filter(|&&(_, weight)| weight > 0.0)
+        .map(|&(angle, weight)| (angle.rem_euclid(360.0), weight))
+        .collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let count = sorted.len();
+    // Twice round the circle, so the readings within half a turn ahead of
+    // any candidate are contiguous, with running sums of weight and
+    // weighted angle.
+    let angle = |index: usize| sorted[index % count].0 + 360.0 * (index / count) as f64;
+    let (mut weights, mut moments) = (vec![0.0], vec![0.0]);
+    for index in 0..2 * count {
+        let weight = sorted[index % count].1;
+        weights.push(weights[index] + weight);
+        moments.push(moments[index] + weight * angle(index));
+    }
+    let range = |sums: &[f64], from: usize, to: usize| sums[to] - sums[from];
+
+    // The minimum is always at a reading. Readings from `candidate` up to
+    // `ahead` are at most half a turn ahead of it; the rest are nearer
+    // going the other way.
+    let mut costs = Vec::with_capacity(count);
+    let mut ahead = 0;
+    for candidate in 0..count {
+        let center = angle(candidate);
+        ahead = ahead.max(candidate);
+        while ahead < candidate + count && angle(ahead) - center <= 180.0 {
+            ahead += 1;
+        }
+        let end = candidate + count;
+        costs.push(
+            range(&moments, candidate, ahead) - center * range(&weights, candidate, ahead)
+                + (center + 360.0) * range(&weights, ahead, end)
+                - range(&moments, ahead, end),
+        );
+    }
+    let best = (0..count).min_by(|&a, &b| costs[a].total_cmp(&costs[b]))?;
+    let cost = |center: f64| -> f64 {
+        sorted
+            .iter()
+            .map(|&(angle, weight)| weight * wrap_degrees(angle - center).abs())
+            .sum()
+    };
+    // A flat stretch runs to one of the neighbours, if the cost halfway
+    // there is no higher.
+    let tolerance = 1e-9 * weights[count] * 180.0;
+    let start = sorted[best].0;
+    let forward = (sorted[(best + 1) % count].0 - start).rem_euclid(360.0) / 2.0;
+    let backward = -(start - sorted[(best + count - 1) % count].0).rem_euclid(360.0) / 2.0;
+    let median = [forward, backward]
+        .into_iter()
+        .map(|offset| start + offset)
+        .find(|&midpoint| cost(midpoint) - costs[best] <= tolerance)
+        .unwrap_or(start);
+    Some(median.rem_euclid(360.0))
+}
+
+/// Total weight of bearings in equal sectors, the first centred on north.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CircularHistogram {
+    totals: Vec<f64>,
+}
+
+impl CircularHistogram {
+    /// An empty histogram of `bins` sectors, at least one.
+    pub fn new(bins: usize) -> Self {
+        CircularHistogram {
+            totals: vec![0.0; bins.max(1)],
+        }
+    }
+
+    /// A histogram of `(angle, weight)` readings in `bins` sectors.
+    pub fn from_readings(readings: &[(f64, f64)], bins: usize) -> Self {
+        let mut histogram = CircularHistogram::new(bins);
+        for &(angle, weight) in readings {
+            histogram.add(angle, weight);
+        }
+        histogram
+    }
+
+    pub fn add(&mut self, angle: f64, weight: f64) {
+        let width = self.sector_width();
+        let index = ((angle + width / 2.0).rem_euclid(360.0) / width) as usize;
+        let last = self.totals.len() - 1;
+        self.totals[index.min(last)] += weight;
+    }
+
+    /// The weight in each sector, clockwise from north.
+    pub fn totals(&self) -> &[f64] {
+        &self.totals
+    }
+
+    /// The width of each sector in degrees.
+    pub fn sector_width(&self) -> f64 {
+        360.0 / self.totals.len() as f64
+    }
+
+    /// The bearing at the centre of sector `index`.
+    pub fn sector_center(&self, index: usize) -> f64 {
+        index as f64 * self.sector_width()
+    }
+
+    /// The histogram as rose-diagram wedges, ready to draw in any UI.
+    ///
+    /// Each wedge's outline has `arc_points` points along its arc, at least
+    /// two, in unit-circle coordinates with north up and east to the right:
+    /// `x` is `radius * sin(bearing)` and `y` is `radius * cos(bearing)`.
+    pub fn rose(&self, arc_points: usize) -> Vec<RoseSector> {
+        let peak = self.totals.iter().copied().fold(0.0, f64::max);
+        let total: f64 = self.totals.iter().sum();
+        let width = self.sector_width();
+        let arc_points = arc_points.max(2);
+        self.totals
+            .iter()
+            .enumerate()
+            .map(|(index, &weight)| {
+                let center = self.sector_center(index);
+                let start = center - width / 2.0;
+                let radius = if peak > 0.0 { weight / peak } else { 0.0 };
+                let mut outline = vec![(0.0, 0.0)];
+                outline.extend((0..arc_points).map(|step| {
+                    let bearing = start + width * step as f64 / (arc_points - 1) as f64;
+                    let (sin, cos) = bearing.to_radians().sin_cos();
+                    (radius * sin, radius * cos)
+                }));
+                RoseSector {
+                    center,
+                    start: start.rem_euclid(360.0),
+                    end: (start + width).rem_euclid(360.0),
+                    weight,
+                    fraction: if total > 0.0 { weight / total } else { 0.0 },
+                    radius,
+                    outline,
+                }
+            })
+            .collect()
+    }
+}
+
+/// One wedge of a rose diagram, from [`CircularHistogram::rose`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoseSector {
+    /// The bearing at the middle of the sector.
+    pub center: f64,
+    /// The sector's first bearing, in `[0, 360)`; the first sector starts
+    /// just west of north.
+    pub start: f64,
+    /// The sector's last bearing, in `[0, 360)`.
+    pub end: f64,
+    /// The total weight in the sector.
+    pub weight: f64,
+    /// The sector's share of the histogram's total weight.
+    pub fraction: f64,
+    /// The wedge's length, 1 for the heaviest sector.
+    pub radius: f64,
+    /// The wedge's outline, starting at the centre and returning to it
+    /// implicitly after the arc.
+    pub outline: Vec<(f64, f64)>,
+}
+
+/// How widely [`CircularKde`] spreads each reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bandwidth {
+    /// The von Mises kernel's concentration κ. Larger is narrower: the
+    /// kernel's spread is roughly `57 / sqrt(κ)` degrees.
+    Concentration(f64),
+    /// Taylor's rule of thumb, from a von Mises fit to the readings. It
+    /// assumes roughly unimodal data and oversmooths several separate
+    /// clusters, so choose a concentration for those.
+    Automatic,
+}
+
+/// A peak of a [`CircularKde`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CircularMode {
+    /// Degrees in `[0, 360)`.
+    pub angle: f64,
+    /// Probability density per degree at the peak.
+    pub density: f64,
+}
+
+/// A kernel density estimate of the distribution of weighted bearings, with
+/// a von Mises kernel.
+///
+/// For multimodal bearings, such as a direct path and a strong reflection,
+/// the [`modes`](CircularKde::modes) say far more than the mean, which falls
+/// somewhere in between.
+#[derive(Debug, Clone)]
+pub struct CircularKde {
+    /// `(radians, weight)` with positive weights.
+    readings: Vec<(f64, f64)>,
+    total: f64,
+    concentration: f64,
+}
+
+impl CircularKde {
+    /// Estimates the density of `(angle, weight)` readings. Returns `None`
+    /// if no reading has a positive weight or the concentration is negative
+    /// or not finite.
+    pub fn new(readings: &[(f64, f64)], bandwidth: Bandwidth) -> Option<Self> {
+        let kept: Vec<(f64, f64)> = readings
+            .iter()
+            .filter(|&&(_, weight)| weight > 0.0)
+            .map(|&(angle, weight)| (angle.to_radians(), weight))
+            .collect();
+        let total: f64 = kept.iter().map(|&(_, weight)| weight).sum();
+        if kept.is_empty() || !total.is_finite() {
+            return None;
+        }
+        let concentration = match bandwidth {
+            Bandwidth::Concentration(concentration) => concentration,
+            Bandwidth::Automatic => {
+                let summary = summarize(readings)?;
+                let fitted = von_mises_concentration(summary.resultant_length);
+                // Taylor (2008): [3n κ² I₂(2κ) / (4√π I₀(κ)²)]^(2/5), with
+                // the exponential growth of the Bessel functions cancelled.
+                let ratio = scaled_bessel(2, 2.0 * fitted) / scaled_bessel(0, fitted).powi(2);
+                (3.0 * summary.effective_count * fitted * fitted * ratio / (4.0 * PI.sqrt()))
+                    .powf(0.4)
+            }
+        };
+        if !(concentration >= 0.0 && concentration.is_finite()) {
+            return None;
+        }
+        Some(CircularKde {
+            readings: kept,
+            total,
+            concentration,
+        })
+    }
+
+    /// The kernel's concentration κ.
+    pub fn concentration(&self) -> f64 {
+        self.concentration
+    }
+
+    /// The estimated probability density per degree at `angle`.
+    pub fn density(&self, angle: f64) -> f64 {
+        self.derivatives(angle.to_radians()).0
+    }
+
+    /// The density and its first two derivatives by angle in radians, all
+    /// per degree.
+    fn derivatives(&self, radians: f64) -> (f64, f64, f64) {
+        let kappa = self.concentration;
+        let (mut value, mut slope, mut curvature) = (0.0, 0.0, 0.0);
+        for &(angle, weight) in &self.readings {
+            let (sin, cos) = (radians - angle).sin_cos();
+            // exp(κ cos) scaled by exp(-κ), to match the scaled I₀ below.
+            let kernel = weight * (kappa * (cos - 1.0)).exp();
+            value += kernel;
+            slope -= kernel * kappa * sin;
+            curvature += kernel * kappa * (kappa * sin * sin - cos);
+        }
+        let scale = PI / 180.0 / (2.0 * PI * scaled_bessel(0, kappa) * self.total);
+        (value * scale, slope * scale, curvature * scale)
+    }
+
+    /// The local maxima of the density, most probable first.
+    ///
+    /// The density is searched on a grid fine enough for the kernel and
+    /// each peak is refined with Newton steps. A flat density, as from a
+    /// concentration of zero, has no modes.
+    pub fn modes(&self) -> Vec<CircularMode> {
+        let points = (720.0f64).max(16.0 * PI * self.concentration.sqrt()).ceil() as usize;
+        let step = 2.0 * PI / points as f64;
+        let grid: Vec<f64> = (0..points)
+            .map(|index| self.derivatives(index as f64 * step).0)
+            .collect();
+        let mut modes: Vec<CircularMode> = (0..points)
+            .filter(|&index| {
+                let value = grid[index];
+                value > grid[(index + points - 1) % points] && value > grid[(index + 1) % points]
+            })
+            .map(|index| {
+                let mut radians = index as f64 * step;
+                for _ in 0..20 {
+                    let (_, slope, curvature) = self.derivatives(radians);
+                    if curvature >= 0.0 {
+                        break;
+                    }
+                    let update = (slope / curvature).clamp(-step, step);
+                    radians -= update;
+                    if update.abs() < 1e-12 {
+                        break;
+                    }
+                }
+                CircularMode {
+                    angle: radians.to_degrees().rem_euclid(360.0),
+                    density: self.derivatives(radians).0,
+                }
+            })
+            .collect();
+        modes.sort_by(|a, b| b.density.total_cmp(&a.density));
+        modes
+    }
+}
+
+/// The maximum-likelihood von Mises concentration for a mean resultant
+/// length, by Fisher's approximation to the inverse of `I₁(κ) / I₀(κ)`.
+fn von_mises_concentration(resultant_length: f64) -> f64 {
+    let r = resultant_length.clamp(0.0, 1.0 - 1e-12);
+    if r < 0.53 {
+        2.0 * r + r.powi(3) + 5.0 * r.powi(5) / 6.0
+    } else if r < 0.85 {
+        -0.4 + 1.39 * r + 0.43 / (1.0 - r)
+    } else {
+        1.0 / (r.powi(3) - 4.0 * r * r + 3.0 * r)
+    }
+}
+
+/// The modified Bessel function `I_order(x) · exp(-x)` for `x ≥ 0`, by its
+/// power series for small `x` and its asymptotic expansion beyond.
+fn scaled_bessel(order: u32, x: f64) -> f64 {
+    if x < 30.0 {
+        let half = x / 2.0;
+        let mut term = half.powi(order as i32) / (1..=order).product::<u32>() as f64;
+        let mut total = term;
+        for k in 1..200 {
+            term *= half * half / (k as f64 * (k + order) as f64);
+            total += term;
+            if term < total * 1e-17 {
+                break;
+            }
+        }
+        total * (-x).exp()
+    } else {
+        let mu = 4.0 * (order * order) as f64;
+        let mut term = 1.0;
+        let mut total = 1.0;
+        for k in 1..8 {
+            let odd = (2 * k - 1) as f64;
+            term *= -(mu - odd * odd) / (k as f64 * 8.0 * x);
+            total += term;
+        }
+        total / (2.0 * PI * x).sqrt()
+    }
+}
+
+/// A bearing that changes linearly with a covariate such as time or
+/// frequency, fitted by [`circular_linear_regression`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CircularRegression {
+    /// The bearing in `[0, 360)` when the covariate is zero.
+    pub intercept: f64,
+    /// Degrees of bearing per unit of covariate.
+    pub slope: f64,
+    /// Weighted root mean square of the wrapped residuals, in degrees.
+    pub residual_deviation: f64,
+}
+
+impl CircularRegression {
+    /// The fitted bearing in `[0, 360)` at `covariate`, which may lie outside
+    /// the fitted range to extrapolate.
+    pub fn predict(&self, covariate: f64) -> f64 {
+        (self.intercept + self.slope * covariate).rem_euclid(360.0)
+    }
+
+    /// How far `angle` lies from the fitted bearing at `covariate`, in
+    /// `[-180, 180)` degrees.
+    pub fn residual(&self, covariate: f64, angle: f64) -> f64 {
+        wrap_degrees(angle - self.intercept - self.slope * covariate)
+    }
+}
+
+/// Fits `angle = intercept + slope * covariate` to `(angle, weight)`
+/// readings taken at `covariates`, modeling slow bearing drift such as from
+/// a moving transmitter.
+///
+/// Residuals are measured the short way round the circle, so a track that
+/// crosses north fits as well as any other. The first guess unwraps the
+/// bearings in covariate order, which assumes successive readings differ by
+/// well under 180 degrees; Gauss-Newton steps on the wrapped residuals then
+/// refine it. Returns `None` unless there are at least two readings, one
+/// covariate for each, positive total weight and more than one distinct
+/// covariate.
+pub fn circular_linear_regression(
+    covariates: &[f64],
+    readings: &[(f64, f64)],
+) -> Option<CircularRegression> {
+    if readings.len() < 2 || covariates.len() != readings.len() {
+        return None;
+    }
+    let total: f64 = readings.iter().map(|&(_, weight)| weight).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    // Centering the covariate keeps timestamps such as UNIX seconds from
+    // swamping the intercept.
+    let center = covariates
+        .iter()
+        .zip(readings)
+        .map(|(&x, &(_, weight))| weight * x)
+        .sum::<f64>()
+        / total;
+    let mut points: Vec<(f64, f64, f64)> = covariates
+        .iter()
+        .zip(readings)
+        .map(|(&x, &(angle, weight))| (x - center, angle, weight))
+        .collect();
+    let spread: f64 = points.iter().map(|&(x, _, weight)| weight * x * x).sum();
+    if spread <= 0.0 {
+        return None;
+    }
+    // The weighted least-squares slope of `values` against the centered
+    // covariate, and their weighted mean.
+    let fit = |values: &mut dyn Iterator<Item = (f64, f64, f64)>| {
+        let (mut mean, mut moment) = (0.0, 0.0);
+        for (x, value, weight) in values {
+            mean += weight * value;
+            moment += weight * x * value;
+        }
+        (mean / total, moment / spread)
+    };
+
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut previous = points[0].1;
+    let (mut level, mut slope) = fit(&mut points.iter().map(|&(x, angle, weight)| {
+        previous += wrap_degrees(angle - previous);
+        (x, previous, weight)
+    }));
+    for _ in 0..50 {
+        let (step_level, step_slope) = fit(&mut points
+            .iter()
+            .map(|&(x, angle, weight)| (x, wrap_degrees(angle - level - slope * x), weight)));
+        level += step_level;
+        slope += step_slope;
+        // The change in fitted bearing at a typical distance from the center.
+        let radius = (spread / total).sqrt();
+        if step_level.abs() + (step_slope * radius).abs() < 1e-12 {
+            break;
+        }
+    }
+    let squares: f64 = points
+        .iter()
+        .map(|&(x, angle, weight)| weight * wrap_degrees(angle - level - slope * x).powi(2))
+        .sum();
+    Some(CircularRegression {
+        intercept: (level - slope * center).rem_euclid(360.0),
+        slope,
+        residual_deviation: (squares / total).sqrt(),
+    })
+}
+
+/// `degrees` wrapped into `[-180, 180)`.
+fn wrap_degrees(degrees: f64) -> f64 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// The result of [`watson_u2`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatsonTest {
+    /// Watson's U² statistic; larger means the samples differ more.
+    pub statistic: f64,
+    /// The probability of a statistic at least this large if both samples
+    /// come from the same distribution, from the asymptotic distribution of
+    /// U². Reliable once each sample has about ten readings.
+    pub p_value: f64,
+}
+
+/// Watson's U² two-sample test of whether two sets of bearings in degrees,
+/// such as two sessions or two antennas, come from the same distribution.
+///
+/// Unlike a comparison of means it is sensitive to any difference in shape,
+/// and it does not depend on where the circle is cut. Readings are
+/// unweighted. Returns `None` if either sample is empty.
+pub fn watson_u2(first: &[f64], second: &[f64]) -> Option<WatsonTest> {
+    if first.is_empty() || second.is_empty() {
+        return None;
+    }
+    let (n, m) = (first.len() as f64, second.len() as f64);
+    let total = n + m;
+    let mut combined: Vec<(f64, bool)> = first
+        .iter()
+        .map(|&angle| (angle.rem_euclid(360.0), true))
+        .chain(second.iter().map(|&angle| (angle.rem_euclid(360.0), false)))
+        .collect();
+    combined.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // Differences between the empirical distribution functions, taken after
+    // each run of tied angles and weighted by its length.
+    let (mut in_first, mut in_second) = (0.0, 0.0);
+    let (mut sum, mut squares) = (0.0, 0.0);
+    let mut index = 0;
+    while index < combined.len() {
+        let angle = combined[index].0;
+        let mut ties = 0.0;
+        while index < combined.len() && combined[index].0 == angle {
+            if combined[index].1 {
+                in_first += 1.0;
+            } else {
+                in_second += 1.0;
+            }
+            ties += 1.0;
+            index += 1;
+        }
+        let difference = in_first / n - in_second / m;
+        sum += ties * difference;
+        squares += ties * difference * difference;
+    }
+    let statistic = n * m / (total * total) * (squares - sum * sum / total);
+    Some(WatsonTest {
+        statistic,
+        p_value: watson_tail(statistic),
+    })
+}
+
+/// `P(U² > u)` for the limiting distribution,
+/// `2 Σ (-1)^(k-1) exp(-2 k² π² u)`.
+fn watson_tail(u: f64) -> f64 {
+    if u <= 0.0 {
+        return 1.0;
+    }
+    let mut total = 0.0;
+    for k in 1..=100 {
+        let term = (-2.0 * (k * k) as f64 * PI * PI * u).exp();
+        total += if k % 2 == 1 { term } else { -term };
+        if term < 1e-16 {
+            break;
+        }
+    }
+    (2.0 * total).clamp(0.0, 1.0)
+}
+
+/// The standard normal quantile, by Acklam's rational approximation
+/// (relative error below 1.2e-9).
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_summary_across_north() {
+        let readings = [(350.0, 1.0), (10.0, 1.0), (0.0, 2.0)];
+        let summary = summarize(&readings).unwrap();
+        assert_relative_eq!(
+            (summary.mean + 180.0).rem_euclid(360.0),
+            180.0,
+            epsilon = 1e-9
+        );
+        let expected = (2.0 * 10f64.to_radians().cos() + 2.0) / 4.0;
+        assert_relative_eq!(summary.resultant_length, expected, epsilon = 1e-12);
+        assert_relative_eq!(summary.effective_count, 16.0 / 6.0);
+        assert_eq!(summarize(&[]), None);
+    }
+
+    #[test]
+    fn test_skewness_and_kurtosis() {
+        let symmetric = summarize(&[(350.0, 1.0), (0.0, 2.0), (10.0, 1.0)]).unwrap();
+        assert_relative_eq!(symmetric.skewness, 0.0, epsilon = 1e-9);
+        // A tail towards larger angles, as from a multipath reflection.
+        let tailed: Vec<(f64, f64)> = [0.0, 0.0, 0.0, 5.0, 15.0, 40.0]
+            .iter()
+            .map(|&angle| (angle + 355.0, 1.0))
+            .collect();
+        let summary = summarize(&tailed).unwrap();
+        assert!(summary.skewness < -0.5, "{}", summary.skewness);
+        let mirrored: Vec<(f64, f64)> = tailed.iter().map(|&(a, w)| (-a, w)).collect();
+        assert_relative_eq!(
+            summarize(&mirrored).unwrap().skewness,
+            -summary.skewness,
+            epsilon = 1e-9
+        );
+        // Mostly tight with a few wide readings is more peaked than a spread
+        // of equal dispersion.
+        let peaked = summarize(&[
+            (0.0, 1.0),
+            (1.0, 1.0),
+            (359.0, 1.0),
+            (0.0, 1.0),
+            (60.0, 1.0),
+            (300.0, 1.0),
+        ])
+        .unwrap();
+        let flat = summarize(&[(340.0, 1.0), (350.0, 1.0), (10.0, 1.0), (20.0, 1.0)]).unwrap();
+        assert!(peaked.kurtosis > flat.kurtosis);
+        let identical = summarize(&[(45.0, 1.0); 3]).unwrap();
+        assert_eq!((identical.skewness, identical.kurtosis), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_circular_median() {
+        let unweighted = |angles: &[f64]| {
+            let readings: Vec<(f64, f64)> = angles.iter().map(|&angle| (angle, 1.0)).collect();
+            circular_median(&readings).unwrap()
+        };
+        assert_relative_eq!(unweighted(&[350.0, 355.0, 0.0, 5.0, 100.0]), 0.0);
+        assert_relative_eq!(unweighted(&[350.0, 10.0]), 0.0);
+        assert_relative_eq!(unweighted(&[10.0, 20.0, 30.0, 40.0]), 25.0);
+        // A strong reading pulls the median where the plain one would not go.
+        assert_relative_eq!(
+            circular_median(&[(0.0, 1.0), (10.0, 1.0), (20.0, 5.0)]).unwrap(),
+            20.0
+        );
+        assert_eq!(circular_median(&[(10.0, 0.0)]), None);
+
+        // Against a direct search over every reading.
+        let readings: Vec<(f64, f64)> = (0..101)
+            .map(|n| ((n * 97 % 360) as f64 * 0.4 + 300.0, 1.0 + (n % 3) as f64))
+            .collect();
+        let cost = |center: f64| {
+            readings
+                .iter()
+                .map(|&(angle, weight)| weight * wrap_degrees(angle - center).abs())
+                .sum::<f64>()
+        };
+        let direct = readings
+            .iter()
+            .map(|&(angle, _)| angle.rem_euclid(360.0))
+            .min_by(|&a, &b| cost(a).total_cmp(&cost(b)))
+            .unwrap();
+        assert_relative_eq!(cost(circular_median(&readings).unwrap()), cost(direct));
+    }
+
+    #[test]
+    fn test_histogram_wraps_around_north() {
+        let histogram = CircularHistogram::from_readings(
+            &[(355.0, 1.0), (5.0, 2.0), (90.0, 1.0), (44.9, 1.0)],
+            4,
+        );
+        assert_eq!(histogram.totals(), [4.0, 1.0, 0.0, 0.0]);
+        let rose = histogram.rose(3);
+        assert_eq!((rose[0].start, rose[0].end), (315.0, 45.0));
+        assert_relative_eq!(rose[0].fraction, 0.8);
+        assert_relative_eq!(rose[1].radius, 0.25);
+        // East is to the right.
+        let (x, y) = rose[1].outline[2];
+        assert_relative_eq!(x, 0.25, epsilon = 1e-12);
+        assert_relative_eq!(y, 0.0, epsilon = 1e-12);
+        assert_eq!(rose[0].outline.len(), 4);
+        assert!(CircularHistogram::new(0).rose(8)[0].radius == 0.0);
+    }
+
+    #[test]
+    fn test_kde_finds_both_modes() {
+        assert_relative_eq!(
+            scaled_bessel(0, 1.0),
+            1.266_065_877_752_008 * (-1.0f64).exp()
+        );
+        assert_relative_eq!(
+            scaled_bessel(2, 40.0),
+            scaled_bessel(0, 40.0) - 2.0 / 40.0 * scaled_bessel(1, 40.0),
+            epsilon = 1e-12
+        );
+        // The series and the asymptotic expansion agree where they meet.
+        assert_relative_eq!(
+            scaled_bessel(1, 29.999),
+            scaled_bessel(1, 30.0),
+            max_relative = 1e-4
+        );
+
+        // A direct path near 30 degrees and a weaker reflection near 200.
+        let readings: Vec<(f64, f64)> = (0..30)
+            .map(|n| (30.0 + (n % 7) as f64 - 3.0, 1.0))
+            .chain((0..12).map(|n| (200.0 + (n % 5) as f64 - 2.0, 1.0)))
+            .collect();
+        let kde = CircularKde::new(&readings, Bandwidth::Concentration(50.0)).unwrap();
+        let total: f64 = (0..3600).map(|n| kde.density(n as f64 / 10.0) / 10.0).sum();
+        assert_relative_eq!(total, 1.0, epsilon = 1e-9);
+        let modes = kde.modes();
+        assert_eq!(modes.len(), 2, "{modes:?}");
+        assert_relative_eq!(modes[0].angle, 30.0, epsilon = 0.5);
+        assert_relative_eq!(modes[1].angle, 200.0, epsilon = 0.5);
+        assert!(modes[0].density > 2.0 * modes[1].density);
+
+        let across_north: Vec<(f64, f64)> = (0..20)
+            .map(|n| ((n as f64 * 1.5 - 15.0).rem_euclid(360.0), 1.0))
+            .collect();
+        let kde = CircularKde::new(&across_north, Bandwidth::Automatic).unwrap();
+        assert!(kde.concentration() > 50.0, "{}", kde.concentration());
+        let modes = kde.modes();
+        assert_eq!(modes.len(), 1, "{modes:?}");
+        assert_relative_eq!(
+            (modes[0].angle + 180.0).rem_euclid(360.0),
+            180.0 - 0.75,
+            epsilon = 0.5
+        );
+        assert!(CircularKde::new(&readings, Bandwidth::Concentration(0.0))
+            .unwrap()
+            .modes()
+            .is_empty());
+        assert!(CircularKde::new(&[], Bandwidth::Automatic).is_none());
+    }
+
+    #[test]
+    fn test_regression_tracks_drift_across_north() {
+        // A transmitter moving at half a degree a second, seen through a few
+        // degrees of alternating error, crossing north around t = 20.
+        let times: Vec<f64> = (0..60).map(|n| 1.7e9 + n as f64).collect();
+        let readings: Vec<(f64, f64)> = (0..60)
+            .map(|n| {
+                let noise = [2.0, -1.0, -2.0, 1.0][n % 4];
+                ((350.0 + 0.5 * n as f64 + noise).rem_euclid(360.0), 1.0)
+            })
+            .collect();
+        let fit = circular_linear_regression(&times, &readings).unwrap();
+        assert_relative_eq!(fit.slope, 0.5, epsilon = 0.01);
+        assert_relative_eq!(fit.predict(1.7e9 + 100.0), 40.0, epsilon = 1.0);
+        assert_relative_eq!(fit.residual_deviation, 2.5f64.sqrt(), epsilon = 0.1);
+        assert_relative_eq!(fit.residual(1.7e9 + 20.0, 1.0), 1.0, epsilon = 0.5);
+        assert_eq!(circular_linear_regression(&times[..2], &readings), None);
+        assert_eq!(
+            circular_linear_regression(&[5.0, 5.0], &readings[..2]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bootstrap_interval_agrees_with_analytic() {
+        let readings: Vec<(f64, f64)> = (0..40)
+            .map(|n| ((n * 37 % 21) as f64 - 10.0 + 360.0, 1.0))
+            .collect();
+        let analytic = summarize(&readings)
+            .unwrap()
+            .confidence_interval(0.95)
+            .unwrap();
+        let interval = bootstrap_interval(&readings, 0.95, 2000, 7).unwrap();
+        assert_eq!(
+            interval,
+            bootstrap_interval(&readings, 0.95, 2000, 7).unwrap()
+        );
+        // The interval straddles north and roughly matches Zar's formula.
+        assert!(
+            interval.lower > 350.0 && interval.upper < 10.0,
+            "{interval:?}"
+        );
+        assert_relative_eq!(interval.width(), 2.0 * analytic, max_relative = 0.25);
+        let narrower = bootstrap_interval(&readings, 0.5, 2000, 7).unwrap();
+        assert!(narrower.width() < interval.width());
+        assert_eq!(bootstrap_interval(&readings, 1.0, 100, 7), None);
+        assert_eq!(
+            bootstrap_interval(&[(0.0, 1.0), (180.0, 1.0)], 0.9, 100, 7),
+            None
+        );
+    }
+
+    #[test]
+    fn test_watson_u2() {
+        // The tabulated 5% and 1% points of the limiting distribution.
+        assert_relative_eq!(watson_tail(0.187), 0.05, epsilon = 1e-3);
+        assert_relative_eq!(watson_tail(0.268), 0.01, epsilon = 1e-3);
+
+        let session: Vec<f64> = (0..40).map(|n| (n * 37 % 60) as f64 - 30.0).collect();
+        let same: Vec<f64> = session.iter().map(|angle| angle + 0.5).collect();
+        let drifted: Vec<f64> = session.iter().map(|angle| angle + 25.0).collect();
+        let result = watson_u2(&session, &same).unwrap();
+        assert!(result.p_value > 0.5, "{result:?}");
+        let result = watson_u2(&session, &drifted).unwrap();
+        assert!(result.p_value < 0.01, "{result:?}");
+        // Where the circle is cut makes no difference.
+        let rotate = |angles: &[f64]| angles.iter().map(|a| a + 123.0).collect::<Vec<_>>();
+        let rotated = watson_u2(&rotate(&session), &rotate(&drifted)).unwrap();
+        assert_relative_eq!(rotated.statistic, result.statistic, epsilon = 1e-12);
+        assert_eq!(watson_u2(&session, &[]), None);
+    }
+
+    #[test]
+    fn test_confidence_interval() {
+        assert_relative_eq!(normal_quantile(0.975), 1.959964, epsilon = 1e-6);
+        // Tight readings give a narrow interval that widens with confidence.
+        let tight: Vec<(f64, f64)> = (0..20)
+            .map(|n| (90.0 + (n % 5) as f64 - 2.0, 1.0))
+            .collect();
+        let summary = summarize(&tight).unwrap();
+        let ci95 = summary.confidence_interval(0.95).unwrap();
+        let ci99 = summary.confidence_interval(0.99).unwrap();
+        assert!(ci95 > 0.0 && ci95 < 1.0, "{ci95}");
+        assert!(ci99 > ci95);
+        // Opposite readings have no mean direction.
+        let split = summarize(&[(0.0, 1.0), (180.0, 1.0), (1.0, 1.0)]).unwrap();
+        assert_eq!(split.confidence_interval(0.95), None);
+    }
+}
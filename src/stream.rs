@@ -0,0 +1,174 @@
+//! Async wrappers that run sources, blocks and sinks as [`Stream`]s.
+//!
+//! Sources run on tokio's blocking thread pool and hand chunks to the async
+//! side over a bounded channel, so a slow consumer holds the source back
+//! instead of letting memory grow. Items are `Result<Vec<T>, E>` chunks.
+
+use crate::block::Block;
+use crate::flowgraph::FlowgraphError;
+use crate::sink::Sink;
+use crate::source::Source;
+use futures_core::Stream;
+use std::error::Error;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// A stream of sample chunks read from a [`Source`].
+pub struct SourceStream<T, E> {
+    receiver: mpsc::Receiver<Result<Vec<T>, E>>,
+}
+
+/// Starts reading `source` on the blocking thread pool, `chunk_size` samples
+/// at a time, with at most `capacity` chunks waiting to be consumed.
+///
+/// Must be called from within a tokio runtime. The source stops when the
+/// stream is dropped, after its current read returns.
+pub fn source_stream<S>(
+    mut source: S,
+    chunk_size: usize,
+    capacity: usize,
+) -> SourceStream<S::Sample, S::Error>
+where
+    S: Source + Send + 'static,
+    S::Sample: Clone + Default + Send + 'static,
+    S::Error: Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    tokio::task::spawn_blocking(move || {
+        let mut buffer = vec![S::Sample::default(); chunk_size.max(1)];
+        loop {
+            let chunk = match source.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(count) => Ok(buffer[..count].to_vec()),
+                Err(error) => Err(error),
+            };
+            let failed = chunk.is_err();
+            if sender.blocking_send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    });
+    SourceStream { receiver }
+}
+
+impl<T, E> Stream for SourceStream<T, E> {
+    type Item = Result<Vec<T>, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// A stream whose chunks have been passed through a [`Block`].
+pub struct BlockStream<St, B: Block> {
+    inner: St,
+    block: B,
+    pending: Vec<B::Input>,
+}
+
+impl<St, B, E> Stream for BlockStream<St, B>
+where
+    St: Stream<Item = Result<Vec<B::Input>, E>> + Unpin,
+    B: Block + Unpin,
+    B::Input: Unpin,
+{
+    type Item = Result<Vec<B::Output>, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.pending.extend(chunk);
+                    let mut output = Vec::new();
+                    let consumed = this.block.work(&this.pending, &mut output);
+                    this.pending.drain(..consumed.min(this.pending.len()));
+                    // Keep pulling until the block has something to hand on.
+                    if !output.is_empty() {
+                        return Poll::Ready(Some(Ok(output)));
+                    }
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Some(Err(error))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Adds [`through`](BlockStreamExt::through) to streams of sample chunks.
+pub trait BlockStreamExt<T, E>: Stream<Item = Result<Vec<T>, E>> + Sized {
+    /// Passes every chunk through `block`.
+    fn through<B>(self, block: B) -> BlockStream<Self, B>
+    where
+        B: Block<Input = T>,
+    {
+        BlockStream {
+            inner: self,
+            block,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<St, T, E> BlockStreamExt<T, E> for St where St: Stream<Item = Result<Vec<T>, E>> {}
+
+/// Writes every chunk of `stream` to `sink` until the stream ends.
+pub async fn forward<St, K, E>(mut stream: St, sink: &mut K) -> Result<(), FlowgraphError>
+where
+    St: Stream<Item = Result<Vec<K::Input>, E>> + Unpin,
+    K: Sink,
+    K::Error: Error + Send + Sync + 'static,
+    E: Error + Send + Sync + 'static,
+{
+    while let Some(chunk) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+        let chunk = chunk.map_err(|error| FlowgraphError::Source(Box::new(error)))?;
+        sink.write(&chunk)
+            .map_err(|error| FlowgraphError::Sink(Box::new(error)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::map;
+    use std::convert::Infallible;
+
+    struct Counter(u32);
+
+    impl Source for Counter {
+        type Sample = u32;
+        type Error = Infallible;
+
+        fn read(&mut self, buffer: &mut [u32]) -> Result<usize, Infallible> {
+            let count = buffer.len().min(self.0 as usize);
+            for slot in &mut buffer[..count] {
+                self.0 -= 1;
+                *slot = self.0;
+            }
+            Ok(count)
+        }
+    }
+
+    struct Collect(Vec<u32>);
+
+    impl Sink for Collect {
+        type Input = u32;
+        type Error = Infallible;
+
+        fn write(&mut self, input: &[u32]) -> Result<(), Infallible> {
+            self.0.extend_from_slice(input);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_source_through_block_into_sink() {
+        let stream = source_stream(Counter(10), 3, 1).through(map(|x: &u32| x * 2));
+        let mut sink = Collect(Vec::new());
+        forward(stream, &mut sink).await.unwrap();
+        assert_eq!(sink.0, vec![18, 16, 14, 12, 10, 8, 6, 4, 2, 0]);
+    }
+}
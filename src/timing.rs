@@ -0,0 +1,355 @@
+//! Time of day for samples, from a pulse-per-second reference.
+//!
+//! A GPS receiver or GPSDO marks the start of every UTC second with a pulse
+//! and then names that second in an NMEA sentence. The pulse reaches the
+//! sample stream one of two ways: as an edge on a trigger or spare input of
+//! the receiver, which [`PulseDetector`] finds to a fraction of a sample, or
+//! on a serial port's carrier-detect line, which `SerialPps` (with the
+//! `serial` feature) watches alongside the NMEA on the same port. Either
+//! way [`Timestamper`] counts the pulses off against sample indices, measures
+//! the true sample rate between them, and from then gives the UTC time of
+//! any sample. Stamping a burst's first sample, or the sample a bearing was
+//! taken at, lets readings from stations at different sites be lined up for
+//! TDOA and [`Fusion`](crate::fusion::Fusion).
+
+use std::collections::VecDeque;
+
+#[cfg(feature = "serial")]
+mod device;
+#[cfg(feature = "serial")]
+pub use device::{PpsEvent, SerialPps};
+
+/// Finds the rising edges of a pulse in a trigger channel.
+#[derive(Debug, Clone)]
+pub struct PulseDetector {
+    threshold: f32,
+    high: bool,
+    previous: f32,
+    position: u64,
+}
+
+impl PulseDetector {
+    /// Detects crossings of `threshold` upwards, with half of it as
+    /// hysteresis.
+    pub fn new(threshold: f32) -> Self {
+        PulseDetector {
+            threshold,
+            high: false,
+            previous: 0.0,
+            position: 0,
+        }
+    }
+
+    /// Takes the next trigger samples and returns where each rising edge
+    /// crossed the threshold, as a sample index with the fraction between
+    /// samples interpolated.
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f64> {
+        let mut edges = Vec::new();
+        for &sample in samples {
+            if !self.high && sample >= self.threshold {
+                self.high = true;
+                let rise = sample - self.previous;
+                let fraction = if rise > 0.0 {
+                    f64::from((self.threshold - self.previous) / rise)
+                } else {
+                    1.0
+                };
+                edges.push(self.position as f64 - 1.0 + fraction);
+            } else if self.high && sample < self.threshold / 2.0 {
+                self.high = false;
+            }
+            self.previous = sample;
+            self.position += 1;
+        }
+        edges
+    }
+}
+
+/// Checks an NMEA sentence's `*hh` checksum, returning the fields between
+/// the `$` and the `*`.
+fn nmea_fields(sentence: &str) -> Option<Vec<&str>> {
+    let body = sentence.trim().strip_prefix('$')?;
+    let (body, checksum) = body.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    let computed = body.bytes().fold(0, |sum, byte| sum ^ byte);
+    (computed == expected).then(|| body.split(',').collect())
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The UNIX time, in whole seconds, named by an RMC or ZDA sentence from
+/// any talker, or `None` for other sentences, bad checksums and RMC
+/// sentences the receiver flags as void.
+pub fn parse_nmea_time(sentence: &str) -> Option<i64> {
+    let fields = nmea_fields(sentence)?;
+    let kind = fields.first()?.get(2..)?;
+    let number = |text: Option<&&str>, range: std::ops::Range<usize>| -> Option<i64> {
+        text?.get(range)?.parse().ok()
+    };
+    let clock = fields.get(1);
+    let (hour, minute, second) = (
+        number(clock, 0..2)?,
+        number(clock, 2..4)?,
+        number(clock, 4..6)?,
+    );
+    let (year, month, day) = match kind {
+        "RMC" => {
+            if fields.get(2) != Some(&"A") {
+                return None;
+            }
+            let date = fields.get(9);
+            let year = number(date, 4..6)?;
+            let century = if year < 80 { 2000 } else { 1900 };
+            (century + year, number(date, 2..4)?, number(date, 0..2)?)
+        }
+        "ZDA" => (
+            fields.get(4)?.parse().ok()?,
+            fields.get(3)?.parse().ok()?,
+            fields.get(2)?.parse().ok()?,
+        ),
+        _ => return None,
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Settings for [`Timestamper`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimestamperConfig {
+    /// The nominal sample rate in Hz, until pulses measure it.
+    pub sample_rate: f64,
+    /// Seconds a pulse may fall from a whole number of seconds after the
+    /// last before it is taken for a glitch.
+    pub tolerance: f64,
+    /// Recent pulses the sample rate is measured over.
+    pub window: usize,
+    /// Seconds after the last pulse that times are still given for.
+    pub max_holdover: f64,
+}
+
+impl TimestamperConfig {
+    pub fn new(sample_rate: f64) -> Self {
+        TimestamperConfig {
+            sample_rate,
+            tolerance: 1e-3,
+            window: 16,
+            max_holdover: 60.0,
+        }
+    }
+}
+
+/// Maps sample indices to UTC from pulses and the seconds they mark.
+#[derive(Debug, Clone)]
+pub struct Timestamper {
+    config: TimestamperConfig,
+    /// (second, sample) of recent pulses, the second counted from the
+    /// first pulse.
+    pulses: VecDeque<(i64, f64)>,
+    /// The UNIX time of pulse second zero, once a pulse has been named.
+    epoch: Option<i64>,
+}
+
+impl Timestamper {
+    pub fn new(config: TimestamperConfig) -> Self {
+        Timestamper {
+            config,
+            pulses: VecDeque::new(),
+            epoch: None,
+        }
+    }
+
+    pub fn config(&self) -> &TimestamperConfig {
+        &self.config
+    }
+
+    /// Forgets every pulse and the time, as after the reference loses lock.
+    pub fn reset(&mut self) {
+        self.pulses.clear();
+        self.epoch = None;
+    }
+
+    /// Takes a pulse at `sample`, returning false if it fell too far from
+    /// a whole second after the last to be believed. A pulse after a gap
+    /// longer than the holdover starts the count again.
+    pub fn pulse(&mut self, sample: f64) -> bool {
+        let Some(&(second, last)) = self.pulses.back() else {
+            self.pulses.push_back((0, sample));
+            return true;
+        };
+        let elapsed = (sample - last) / self.sample_rate();
+        if elapsed > self.config.max_holdover {
+            trace_event!(warn, elapsed, "PPS lost, timestamps restarted");
+            self.reset();
+            return self.pulse(sample);
+        }
+        let seconds = elapsed.round();
+        if seconds < 1.0 || (elapsed - seconds).abs() > self.config.tolerance {
+            trace_event!(debug, elapsed, "PPS glitch ignored");
+            return false;
+        }
+        self.pulses.push_back((second + seconds as i64, sample));
+        while self.pulses.len() > self.config.window.max(2) {
+            self.pulses.pop_front();
+        }
+        true
+    }
+
+    /// Names the second the latest pulse marked, as an NMEA sentence
+    /// following it does.
+    pub fn set_time(&mut self, unix_second: i64) {
+        if let Some(&(second, _)) = self.pulses.back() {
+            if self
+                .epoch
+                .is_some_and(|epoch| epoch != unix_second - second)
+            {
+                trace_event!(warn, unix_second, "PPS label changed");
+            }
+            self.epoch = Some(unix_second - second);
+        }
+    }
+
+    /// Whether pulses have been seen and named.
+    pub fn is_locked(&self) -> bool {
+        self.epoch.is_some() && !self.pulses.is_empty()
+    }
+
+    /// The sample rate measured between pulses, or the nominal one before
+    /// two have been seen.
+    pub fn sample_rate(&self) -> f64 {
+        self.fit().map_or(self.config.sample_rate, |(rate, _)| rate)
+    }
+
+    /// Samples per second and the sample at second zero, by least squares
+    /// over the recent pulses.
+    fn fit(&self) -> Option<(f64, f64)> {
+        let (first, last) = (self.pulses.front()?, self.pulses.back()?);
+        if last.0 == first.0 {
+            return None;
+        }
+        let count = self.pulses.len() as f64;
+        // Centred on the last pulse, where the sample indices are small.
+        let points = || {
+            self.pulses
+                .iter()
+                .map(move |&(second, sample)| ((second - last.0) as f64, sample - last.1))
+        };
+        let mean_x = points().map(|p| p.0).sum::<f64>() / count;
+        let mean_y = points().map(|p| p.1).sum::<f64>() / count;
+        let (sxx, sxy) = points().fold((0.0, 0.0), |(sxx, sxy), (x, y)| {
+            let (dx, dy) = (x - mean_x, y - mean_y);
+            (sxx + dx * dx, sxy + dx * dy)
+        });
+        let rate = sxy / sxx;
+        let at_last = last.1 + mean_y - rate * mean_x;
+        Some((rate, at_last - rate * last.0 as f64))
+    }
+
+    /// The sample at pulse second zero and the rate, falling back on the
+    /// one pulse and the nominal rate.
+    fn line(&self) -> Option<(f64, f64)> {
+        self.fit().or_else(|| {
+            let &(second, sample) = self.pulses.back()?;
+            let rate = self.config.sample_rate;
+            Some((rate, sample - rate * second as f64))
+        })
+    }
+
+    /// The UNIX time of `sample`, within the holdover of the last pulse.
+    pub fn timestamp(&self, sample: f64) -> Option<f64> {
+        let epoch = self.epoch?;
+        let (rate, zero) = self.line()?;
+        let &(_, last) = self.pulses.back()?;
+        if (sample - last) / rate > self.config.max_holdover {
+            return None;
+        }
+        Some(epoch as f64 + (sample - zero) / rate)
+    }
+
+    /// The sample taken at UNIX time `time`, the inverse of
+    /// [`timestamp`](Timestamper::timestamp).
+    pub fn sample_at(&self, time: f64) -> Option<f64> {
+        let epoch = self.epoch?;
+        let (rate, zero) = self.line()?;
+        Some(zero + (time - epoch as f64) * rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_nmea_times() {
+        let rmc = "$GPRMC,123519.00,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*44";
+        assert_eq!(parse_nmea_time(rmc), Some(764_426_119));
+        let zda = "$GNZDA,201530.00,04,07,2002,00,00*7E";
+        assert_eq!(parse_nmea_time(zda), Some(1_025_813_730));
+        // A void fix, a corrupted sentence and a sentence without a time.
+        assert_eq!(
+            parse_nmea_time("$GPRMC,123519.00,V,,,,,,,230394,,*1D"),
+            None
+        );
+        assert_eq!(parse_nmea_time(&rmc.replace("A,4807", "A,4808")), None);
+        assert_eq!(
+            parse_nmea_time("$GPGSA,A,3,,,,,,,,,,,,,1.0,1.0,1.0*33"),
+            None
+        );
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+    }
+
+    #[test]
+    fn test_pulse_edges() {
+        let mut detector = PulseDetector::new(0.5);
+        let mut trigger = vec![0.0f32; 300];
+        trigger[100] = 0.25;
+        trigger[101..150].fill(1.0);
+        trigger[250..].fill(1.0);
+        let edges = detector.process(&trigger[..200]);
+        assert_eq!(edges.len(), 1);
+        assert_relative_eq!(edges[0], 100.0 + 1.0 / 3.0, epsilon = 1e-6);
+        assert_eq!(detector.process(&trigger[200..]), [249.5]);
+    }
+
+    #[test]
+    fn test_timestamps_follow_the_measured_rate() {
+        // A receiver clock 20 ppm slow, started 0.3 s before a second.
+        let rate = 1e6 * (1.0 - 20e-6);
+        let mut stamper = Timestamper::new(TimestamperConfig::new(1e6));
+        assert!(stamper.pulse(0.3 * rate));
+        assert_eq!(stamper.timestamp(0.0), None);
+        stamper.set_time(1_700_000_000);
+        for second in 1..10 {
+            if second == 4 {
+                // A missed pulse, then a glitch half a second out.
+                assert!(!stamper.pulse((second as f64 + 0.8) * rate));
+                continue;
+            }
+            assert!(stamper.pulse((second as f64 + 0.3) * rate));
+        }
+        assert!(stamper.is_locked());
+        assert_relative_eq!(stamper.sample_rate(), rate, epsilon = 1e-6);
+        let time = stamper.timestamp(5.3 * rate + 250.0).unwrap();
+        assert_relative_eq!(time, 1_700_000_005.0 + 250.0 / rate, epsilon = 1e-7);
+        assert_relative_eq!(
+            stamper.sample_at(1_700_000_009.5).unwrap(),
+            9.8 * rate,
+            epsilon = 1e-3
+        );
+        // Past the holdover, and after the reference comes back.
+        assert_eq!(stamper.timestamp(100.0 * rate), None);
+        assert!(stamper.pulse(100.3 * rate));
+        assert!(!stamper.is_locked());
+    }
+}
@@ -0,0 +1,79 @@
+use super::parse_nmea_time;
+use crate::error::DeviceError;
+use serialport::SerialPort;
+use std::io::{ErrorKind, Read};
+use std::time::{Duration, Instant};
+
+/// Something seen on the GPS receiver's port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpsEvent {
+    /// The carrier-detect line rose, at about this instant.
+    Pulse(Instant),
+    /// An RMC or ZDA sentence named this UNIX second, normally that of the
+    /// pulse before it.
+    Time(i64),
+}
+
+/// A GPS receiver on a serial port, with its PPS wired to carrier detect.
+///
+/// The line is polled, so each pulse is only placed to within the polling
+/// interval, a millisecond or so when [`poll`](SerialPps::poll) is called
+/// in a loop of its own. Convert each instant into the samples read by
+/// then and give it to [`Timestamper::pulse`](super::Timestamper::pulse)
+/// with the tolerance opened up to match; the fit over many pulses averages
+/// the jitter down.
+pub struct SerialPps {
+    port: Box<dyn SerialPort>,
+    carrier: bool,
+    /// Bytes of a sentence not yet ended.
+    partial: Vec<u8>,
+}
+
+impl SerialPps {
+    /// Opens the receiver at `path` at `baud`, 9600 for most receivers.
+    pub fn open(path: &str, baud: u32) -> Result<Self, DeviceError> {
+        let mut port = serialport::new(path, baud)
+            .timeout(Duration::from_millis(1))
+            .open()
+            .map_err(|error| DeviceError::Driver(error.to_string()))?;
+        let carrier = port
+            .read_carrier_detect()
+            .map_err(|error| DeviceError::Driver(error.to_string()))?;
+        trace_event!(info, path, baud, "serial PPS opened");
+        Ok(SerialPps {
+            port,
+            carrier,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Checks the line and the sentences that have arrived, without
+    /// waiting.
+    pub fn poll(&mut self) -> Result<Vec<PpsEvent>, DeviceError> {
+        let now = Instant::now();
+        let driver = |error: serialport::Error| DeviceError::Driver(error.to_string());
+        let mut events = Vec::new();
+        let carrier = self.port.read_carrier_detect().map_err(driver)?;
+        if carrier && !self.carrier {
+            events.push(PpsEvent::Pulse(now));
+        }
+        self.carrier = carrier;
+        let waiting = self.port.bytes_to_read().map_err(driver)? as usize;
+        let mut bytes = vec![0; waiting];
+        let read = match self.port.read(&mut bytes) {
+            Ok(read) => read,
+            Err(error) if error.kind() == ErrorKind::TimedOut => 0,
+            Err(error) => return Err(DeviceError::Driver(error.to_string())),
+        };
+        for &byte in &bytes[..read] {
+            if byte != b'\n' {
+                self.partial.push(byte);
+                continue;
+            }
+            let sentence = String::from_utf8_lossy(&self.partial).into_owned();
+            self.partial.clear();
+            events.extend(parse_nmea_time(&sentence).map(PpsEvent::Time));
+        }
+        Ok(events)
+    }
+}
@@ -0,0 +1,408 @@
+//! Bearings as they change over time.
+//!
+//! Timestamps are seconds, typically since the UNIX epoch, and angles are
+//! degrees, as in [`Reading`].
+
+use crate::reading::Reading;
+use crate::stats::circular_linear_regression;
+use std::collections::VecDeque;
+
+/// How fast a bearing is changing, from [`RateEstimator::estimate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BearingRate {
+    /// Degrees per second, positive when the bearing increases (clockwise).
+    pub rate: f64,
+    /// The fitted bearing in `[0, 360)` at the newest reading.
+    pub bearing: f64,
+    /// The newest reading's timestamp.
+    pub timestamp: f64,
+    /// Weighted root mean square of the readings about the fit, in degrees.
+    pub residual_deviation: f64,
+}
+
+impl BearingRate {
+    /// The bearing in `[0, 360)` extrapolated to `timestamp`, such as the
+    /// time a rotator will arrive, to lead a moving emitter.
+    pub fn predict(&self, timestamp: f64) -> f64 {
+        (self.bearing + self.rate * (timestamp - self.timestamp)).rem_euclid(360.0)
+    }
+}
+
+/// Fits the rate of change of bearing over a sliding window of timestamped
+/// readings, wrap-aware so a track crossing north is handled like any other.
+#[derive(Debug, Clone)]
+pub struct RateEstimator {
+    span: f64,
+    /// `(timestamp, angle, weight)`, oldest first.
+    readings: VecDeque<(f64, f64, f64)>,
+}
+
+impl RateEstimator {
+    /// Keeps readings no more than `span` seconds older than the newest.
+    pub fn new(span: f64) -> Self {
+        RateEstimator {
+            span,
+            readings: VecDeque::new(),
+        }
+    }
+
+    /// Adds a reading, weighted by its magnitude and quality. Readings are
+    /// expected in time order.
+    pub fn push(&mut self, reading: impl Into<Reading>) {
+        let reading = reading.into();
+        let timestamp = reading.timestamp;
+        self.readings
+            .push_back((timestamp, reading.angle, reading.weight()));
+        while let Some(&(oldest, _, _)) = self.readings.front() {
+            if timestamp - oldest <= self.span {
+                break;
+            }
+            self.readings.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.readings.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    /// The rate fitted to the readings in the window, or `None` until it
+    /// holds readings at two or more distinct times.
+    pub fn estimate(&self) -> Option<BearingRate> {
+        let &(timestamp, _, _) = self.readings.back()?;
+        let times: Vec<f64> = self.readings.iter().map(|&(time, _, _)| time).collect();
+        let readings: Vec<(f64, f64)> = self
+            .readings
+            .iter()
+            .map(|&(_, angle, weight)| (angle, weight))
+            .collect();
+        let fit = circular_linear_regression(&times, &readings)?;
+        Some(BearingRate {
+            rate: fit.slope,
+            bearing: fit.predict(timestamp),
+            timestamp,
+            residual_deviation: fit.residual_deviation,
+        })
+    }
+}
+
+/// A significant shift in bearing found by [`ChangeDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BearingChange {
+    /// The timestamp of the reading that triggered detection.
+    pub timestamp: f64,
+    /// The reference bearing before the change.
+    pub from: f64,
+    /// The estimated shift in degrees, positive clockwise.
+    pub shift: f64,
+}
+
+/// An online change-point detector for a bearing stream, such as to notice
+/// a tracked transmitter starting to move.
+///
+/// The first `warmup` readings set a reference bearing. Two one-sided CUSUMs
+/// then accumulate each reading's wrapped deviation from it, less an
+/// allowance of `drift` degrees, and a change is reported when either
+/// exceeds `threshold` degrees. Detection restarts with a new reference
+/// learned from the readings that follow. A larger `drift` ignores smaller
+/// shifts; a larger `threshold` trades slower detection for fewer false
+/// alarms.
+#[derive(Debug, Clone)]
+pub struct ChangeDetector {
+    drift: f64,
+    threshold: f64,
+    warmup: usize,
+    /// Sum of unit vectors of the readings learning the reference.
+    learning: (f64, f64),
+    learned: usize,
+    reference: Option<f64>,
+    /// The clockwise and anticlockwise sums, with how many readings each
+    /// has risen over.
+    clockwise: (f64, usize),
+    anticlockwise: (f64, usize),
+}
+
+impl ChangeDetector {
+    pub fn new(drift: f64, threshold: f64, warmup: usize) -> Self {
+        ChangeDetector {
+            drift,
+            threshold,
+            warmup: warmup.max(1),
+            learning: (0.0, 0.0),
+            learned: 0,
+            reference: None,
+            clockwise: (0.0, 0),
+            anticlockwise: (0.0, 0),
+        }
+    }
+
+    /// The reference bearing in `[0, 360)`, once learned.
+    pub fn reference(&self) -> Option<f64> {
+        self.reference
+    }
+
+    /// Forgets the reference and starts learning a new one.
+    pub fn reset(&mut self) {
+        *self = ChangeDetector::new(self.drift, self.threshold, self.warmup);
+    }
+
+    /// Adds a reading, returning the change it reveals, if any.
+    pub fn push(&mut self, reading: impl Into<Reading>) -> Option<BearingChange> {
+        let reading = reading.into();
+        let Some(reference) = self.reference else {
+            let (sin, cos) = reading.angle.to_radians().sin_cos();
+            self.learning.0 += sin;
+            self.learning.1 += cos;
+            self.learned += 1;
+            if self.learned >= self.warmup {
+                let mean = self.learning.0.atan2(self.learning.1);
+                self.reference = Some(mean.to_degrees().rem_euclid(360.0));
+            }
+            return None;
+        };
+        let deviation = shortest_arc(reference, reading.angle);
+        let rise = |(sum, count): (f64, usize), step: f64| {
+            let sum = (sum + step).max(0.0);
+            (sum, if sum > 0.0 { count + 1 } else { 0 })
+        };
+        self.clockwise = rise(self.clockwise, deviation - self.drift);
+        self.anticlockwise = rise(self.anticlockwise, -deviation - self.drift);
+        // The CUSUM's estimate of the new level: the allowance plus the
+        // average excess over it since the sum last left zero.
+        let shift = if self.clockwise.0 > self.threshold {
+            self.drift + self.clockwise.0 / self.clockwise.1 as f64
+        } else if self.anticlockwise.0 > self.threshold {
+            -(self.drift + self.anticlockwise.0 / self.anticlockwise.1 as f64)
+        } else {
+            return None;
+        };
+        trace_event!(info, from = reference, shift, "bearing change detected");
+        self.reset();
+        Some(BearingChange {
+            timestamp: reading.timestamp,
+            from: reference,
+            shift,
+        })
+    }
+}
+
+/// The bearing at `timestamp` by shortest-arc interpolation between the
+/// angles of `readings` sorted by time, such as to line bearings up with a
+/// GPS track sampled at a different rate.
+///
+/// Returns the bearing in `[0, 360)`, or `None` if `timestamp` is outside
+/// the readings' time span. Between readings half a turn apart the
+/// direction is ambiguous, and the interpolation goes anticlockwise.
+pub fn interpolate_bearing(timestamp: f64, readings: &[Reading]) -> Option<f64> {
+    let after = readings.partition_point(|reading| reading.timestamp < timestamp);
+    let end = readings.get(after)?;
+    if end.timestamp == timestamp {
+        return Some(end.angle.rem_euclid(360.0));
+    }
+    let start = readings.get(after.checked_sub(1)?)?;
+    let fraction = (timestamp - start.timestamp) / (end.timestamp - start.timestamp);
+    Some((start.angle + fraction * shortest_arc(start.angle, end.angle)).rem_euclid(360.0))
+}
+
+/// Resamples irregularly timestamped readings, sorted by time, onto the
+/// uniform grid `start + k * step`, so they can be correlated against other
+/// uniformly sampled data.
+///
+/// Angles are interpolated along the shortest arc as in
+/// [`interpolate_bearing`], and magnitudes and qualities linearly. Only grid points within
+/// the readings' time span are produced, so points before the first reading
+/// are skipped. Returns nothing if `step` is not positive.
+pub fn resample(readings: &[Reading], start: f64, step: f64) -> Vec<Reading> {
+    let (Some(first), Some(last)) = (readings.first(), readings.last()) else {
+        return Vec::new();
+    };
+    if step.is_nan() || step <= 0.0 {
+        return Vec::new();
+    }
+    let first_index = ((first.timestamp - start) / step).ceil().max(0.0) as u64;
+    let mut resampled = Vec::new();
+    let mut segment = 0;
+    for index in first_index.. {
+        let timestamp = start + index as f64 * step;
+        if timestamp > last.timestamp {
+            break;
+        }
+        while segment + 1 < readings.len() && readings[segment + 1].timestamp < timestamp {
+            segment += 1;
+        }
+        let before = &readings[segment];
+        let reading = match readings.get(segment + 1) {
+            Some(after) if timestamp > before.timestamp => {
+                let fraction =
+                    (timestamp - before.timestamp) / (after.timestamp - before.timestamp);
+                Reading {
+                    timestamp,
+                    angle: (before.angle + fraction * shortest_arc(before.angle, after.angle))
+                        .rem_euclid(360.0),
+                    magnitude: before.magnitude + fraction * (after.magnitude - before.magnitude),
+                    quality: before.quality + fraction * (after.quality - before.quality),
+                }
+            }
+            _ => Reading {
+                timestamp,
+                angle: before.angle.rem_euclid(360.0),
+                ..*before
+            },
+        };
+        resampled.push(reading);
+    }
+    resampled
+}
+
+/// The autocorrelation of evenly spaced bearings at lags `0..=max_lag`,
+/// revealing periodic errors such as artifacts locked to an antenna's
+/// rotation rate.
+///
+/// Each lag uses Fisher and Lee's circular correlation between the series
+/// and itself shifted, `Σ sin(θₜ - μ) sin(θₜ₊ₖ - μ)` normalized by the
+/// overlapping parts' sums of squares, where `μ` is the series' mean direction. Values
+/// run from -1 to 1, and the lag-0 value is 1. Lags of the series' length or
+/// more are left out, and nothing is returned for fewer than two bearings
+/// or bearings that do not vary.
+pub fn autocorrelation(angles: &[f64], max_lag: usize) -> Vec<f64> {
+    if angles.len() < 2 {
+        return Vec::new();
+    }
+    let (sin, cos) = angles.iter().fold((0.0, 0.0), |(sin, cos), angle| {
+        let (s, c) = angle.to_radians().sin_cos();
+        (sin + s, cos + c)
+    });
+    let mean = f64::atan2(sin, cos);
+    let deviations: Vec<f64> = angles
+        .iter()
+        .map(|angle| (angle.to_radians() - mean).sin())
+        .collect();
+    let power: f64 = deviations.iter().map(|d| d * d).sum();
+    if power <= 1e-24 * angles.len() as f64 {
+        return Vec::new();
+    }
+    (0..=max_lag.min(angles.len() - 1))
+        .map(|lag| {
+            let (head, tail) = (&deviations[..angles.len() - lag], &deviations[lag..]);
+            let product: f64 = head.iter().zip(tail).map(|(a, b)| a * b).sum();
+            let norm =
+                head.iter().map(|d| d * d).sum::<f64>() * tail.iter().map(|d| d * d).sum::<f64>();
+            product / norm.sqrt()
+        })
+        .collect()
+}
+
+/// The signed turn in `[-180, 180)` degrees from `from` to `to`.
+fn shortest_arc(from: f64, to: f64) -> f64 {
+    (to - from + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_interpolation_takes_the_short_way() {
+        let samples: Vec<Reading> = [(0.0, 350.0), (10.0, 10.0), (20.0, 90.0), (30.0, 270.0)]
+            .iter()
+            .map(|&(timestamp, angle)| Reading::new(timestamp, angle, 1.0))
+            .collect();
+        assert_relative_eq!(interpolate_bearing(5.0, &samples).unwrap(), 0.0);
+        assert_relative_eq!(interpolate_bearing(2.5, &samples).unwrap(), 355.0);
+        assert_relative_eq!(interpolate_bearing(15.0, &samples).unwrap(), 50.0);
+        assert_relative_eq!(interpolate_bearing(20.0, &samples).unwrap(), 90.0);
+        assert_relative_eq!(interpolate_bearing(25.0, &samples).unwrap(), 0.0);
+        assert_eq!(interpolate_bearing(0.0, &samples), Some(350.0));
+        assert_eq!(interpolate_bearing(-1.0, &samples), None);
+        assert_eq!(interpolate_bearing(30.5, &samples), None);
+        assert_eq!(interpolate_bearing(0.0, &[]), None);
+    }
+
+    #[test]
+    fn test_resample_onto_a_grid() {
+        let readings = [
+            Reading::new(0.3, 350.0, 1.0),
+            Reading::new(1.1, 358.0, 2.0).with_quality(0.5),
+            Reading::new(2.7, 14.0, 0.4),
+            Reading::new(3.0, 20.0, 1.0),
+        ];
+        let grid = resample(&readings, 0.0, 0.5);
+        let times: Vec<f64> = grid.iter().map(|reading| reading.timestamp).collect();
+        assert_eq!(times, [0.5, 1.0, 1.5, 2.0, 2.5, 3.0]);
+        assert_relative_eq!(grid[0].angle, 352.0, epsilon = 1e-9);
+        assert_relative_eq!(grid[0].magnitude, 1.25, epsilon = 1e-9);
+        assert_relative_eq!(grid[0].quality, 0.875, epsilon = 1e-9);
+        assert_relative_eq!(grid[3].angle, 7.0, epsilon = 1e-9);
+        assert_relative_eq!(grid[5].angle, 20.0);
+        assert_eq!(resample(&readings, 0.0, 0.0), []);
+        assert_eq!(resample(&[], 0.0, 1.0), []);
+    }
+
+    #[test]
+    fn test_autocorrelation_finds_rotation_artifact() {
+        // A 4 degree error repeating every 12 readings, around north.
+        let angles: Vec<f64> = (0..240)
+            .map(|n| (4.0 * (2.0 * std::f64::consts::PI * n as f64 / 12.0).sin()).rem_euclid(360.0))
+            .collect();
+        let correlation = autocorrelation(&angles, 24);
+        assert_eq!(correlation.len(), 25);
+        assert_relative_eq!(correlation[0], 1.0, epsilon = 1e-12);
+        assert_relative_eq!(correlation[6], -1.0, epsilon = 1e-3);
+        assert_relative_eq!(correlation[12], 1.0, epsilon = 1e-3);
+        assert!(correlation[3].abs() < 0.05);
+        assert_eq!(autocorrelation(&angles[..3], 10).len(), 3);
+        assert!(autocorrelation(&[45.0; 10], 3).is_empty());
+    }
+
+    #[test]
+    fn test_change_detector_flags_the_shift() {
+        let mut detector = ChangeDetector::new(2.0, 20.0, 10);
+        let noise = |n: usize| [1.5f64, -2.0, 0.5, -1.0, 2.0, -0.5, 1.0, -1.5][n % 8];
+        for n in 0..100 {
+            let angle = (355.0 + noise(n)).rem_euclid(360.0);
+            assert_eq!(detector.push((n as f64, angle, 1.0)), None, "at {n}");
+        }
+        assert_relative_eq!(detector.reference().unwrap(), 355.0, epsilon = 0.5);
+        let change = (100..120)
+            .find_map(|n| detector.push((n as f64, 5.0 + noise(n), 1.0)))
+            .unwrap();
+        assert!(change.timestamp < 105.0, "{change:?}");
+        assert_relative_eq!(change.from, 355.0, epsilon = 0.5);
+        assert_relative_eq!(change.shift, 10.0, epsilon = 1.5);
+        assert_eq!(detector.reference(), None);
+    }
+
+    #[test]
+    fn test_rate_across_north_in_a_sliding_window() {
+        let mut estimator = RateEstimator::new(10.0);
+        assert_eq!(estimator.estimate(), None);
+        // Stationary at 100 degrees, then moving at -2 degrees a second
+        // from 10 degrees, across north.
+        for n in 0..20 {
+            estimator.push((n as f64, 100.0, 1.0));
+        }
+        assert_relative_eq!(estimator.estimate().unwrap().rate, 0.0, epsilon = 1e-9);
+        for n in 0..40 {
+            let time = 20.0 + n as f64 * 0.5;
+            let noise = if n % 2 == 0 { 0.5 } else { -0.5 };
+            let angle = (10.0 - 2.0 * n as f64 * 0.5 + noise).rem_euclid(360.0);
+            estimator.push(Reading::new(time, angle, 1.0));
+        }
+        assert_eq!(estimator.len(), 21);
+        let rate = estimator.estimate().unwrap();
+        assert_relative_eq!(rate.rate, -2.0, epsilon = 0.05);
+        assert_relative_eq!(rate.bearing, 331.0, epsilon = 0.5);
+        // Two seconds ahead, as for a slow rotator.
+        assert_relative_eq!(rate.predict(rate.timestamp + 2.0), 327.0, epsilon = 0.5);
+    }
+}
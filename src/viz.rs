@@ -0,0 +1,270 @@
+//! Quick-look plots of results, rendered to PNG or SVG.
+//!
+//! The format follows the file extension. These are meant for inspecting a
+//! recording offline, not for publication: sizes and colours are fixed.
+
+use crate::constellation::Constellation;
+use crate::dsp::db::power_to_db;
+use crate::spectrum::{bin_frequency, psd};
+use crate::stats::{CircularHistogram, RoseSector};
+use num_complex::Complex;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::path::Path;
+
+const SIZE: (u32, u32) = (800, 600);
+const SQUARE: (u32, u32) = (700, 700);
+
+/// Errors from rendering a plot.
+#[derive(Debug, thiserror::Error)]
+pub enum VizError {
+    /// The path's extension is not `png` or `svg`.
+    #[error("cannot tell the image format of {0}; use .png or .svg")]
+    UnsupportedFormat(String),
+    /// There was nothing to plot.
+    #[error("nothing to plot")]
+    NoData,
+    /// The backend failed to draw or write the file.
+    #[error("plotting failed: {0}")]
+    Drawing(String),
+}
+
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for VizError {
+    fn from(error: DrawingAreaErrorKind<E>) -> Self {
+        VizError::Drawing(error.to_string())
+    }
+}
+
+/// Calls `draw` with a drawing area for the backend matching `path`.
+macro_rules! render {
+    ($path:expr, $size:expr, $draw:ident($($arg:expr),*)) => {{
+        let path: &Path = $path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("png") => {
+                let root = BitMapBackend::new(path, $size).into_drawing_area();
+                $draw(&root, $($arg),*)?;
+                root.present()?;
+                Ok(())
+            }
+            Some(extension) if extension.eq_ignore_ascii_case("svg") => {
+                let root = SVGBackend::new(path, $size).into_drawing_area();
+                $draw(&root, $($arg),*)?;
+                root.present()?;
+                Ok(())
+            }
+            _ => Err(VizError::UnsupportedFormat(path.display().to_string())),
+        }
+    }};
+}
+
+/// Renders a rose diagram of `(angle, weight)` bearings in `bins` sectors,
+/// with north up and angles increasing clockwise.
+pub fn rose_diagram(
+    path: impl AsRef<Path>,
+    readings: &[(f64, f64)],
+    bins: usize,
+) -> Result<(), VizError> {
+    let sectors = rose_sectors(&CircularHistogram::from_readings(readings, bins))?;
+    render!(path, SQUARE, draw_rose(&sectors))
+}
+
+/// Renders `histogram` as a rose diagram and returns the SVG document, for
+/// embedding in a web page or report without a temporary file.
+pub fn rose_svg(histogram: &CircularHistogram) -> Result<String, VizError> {
+    let sectors = rose_sectors(histogram)?;
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SQUARE).into_drawing_area();
+        draw_rose(&root, &sectors)?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+fn rose_sectors(histogram: &CircularHistogram) -> Result<Vec<RoseSector>, VizError> {
+    if !histogram.totals().iter().any(|&total| total > 0.0) {
+        return Err(VizError::NoData);
+    }
+    Ok(histogram.rose(9))
+}
+
+fn draw_rose<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    sectors: &[RoseSector],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(root)
+        .margin(30)
+        .build_cartesian_2d(-1.15..1.15, -1.15..1.15)?;
+    // Compass angles: 0 at the top, increasing clockwise.
+    let point = |degrees: f64, radius: f64| {
+        let radians = degrees.to_radians();
+        (radius * radians.sin(), radius * radians.cos())
+    };
+    for ring in 1..=4 {
+        let radius = ring as f64 / 4.0;
+        chart.draw_series(LineSeries::new(
+            (0..=72).map(|step| point(step as f64 * 5.0, radius)),
+            BLACK.mix(0.2),
+        ))?;
+    }
+    for sector in sectors {
+        let mut outline = sector.outline.clone();
+        chart.draw_series(std::iter::once(Polygon::new(
+            outline.clone(),
+            BLUE.mix(0.5),
+        )))?;
+        outline.push((0.0, 0.0));
+        chart.draw_series(LineSeries::new(outline, BLUE))?;
+    }
+    for (label, degrees) in [("N", 0.0), ("E", 90.0), ("S", 180.0), ("W", 270.0)] {
+        let (x, y) = point(degrees, 1.08);
+        chart.draw_series(std::iter::once(Text::new(
+            label,
+            (x - 0.02, y + 0.03),
+            ("sans-serif", 20),
+        )))?;
+    }
+    Ok(())
+}
+
+/// Welch power spectral density in dB per hertz, ordered from the most
+/// negative frequency up, using Hann-windowed frames overlapping by half.
+fn welch_psd(samples: &[Complex<f64>], sample_rate: f64, fft_size: usize) -> Vec<(f64, f64)> {
+    let density = psd(samples, fft_size, sample_rate);
+    let size = density.len();
+    (0..size)
+        .map(|index| {
+            let bin = (index + size / 2) % size;
+            let density = density[bin];
+            (
+                bin_frequency(bin as f64, size, sample_rate),
+                power_to_db(density, -300.0),
+            )
+        })
+        .collect()
+}
+
+/// Renders the Welch power spectral density of `samples` with `fft_size`
+/// point frames.
+pub fn psd_plot(
+    path: impl AsRef<Path>,
+    samples: &[Complex<f64>],
+    sample_rate: f64,
+    fft_size: usize,
+) -> Result<(), VizError> {
+    let psd = welch_psd(samples, sample_rate, fft_size);
+    if psd.len() < 2 {
+        return Err(VizError::NoData);
+    }
+    render!(path, SIZE, draw_psd(&psd))
+}
+
+fn draw_psd<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    psd: &[(f64, f64)],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&WHITE)?;
+    let low = psd.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let high = psd.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let mut chart = ChartBuilder::on(root)
+        .caption("Power spectral density", ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(psd[0].0..psd[psd.len() - 1].0, low - 3.0..high + 3.0)?;
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (Hz)")
+        .y_desc("dB/Hz")
+        .draw()?;
+    chart.draw_series(LineSeries::new(psd.iter().copied(), BLUE))?;
+    Ok(())
+}
+
+/// Renders `symbols` as a scatter plot, overlaying the ideal points of
+/// `reference` if given.
+pub fn constellation_plot(
+    path: impl AsRef<Path>,
+    symbols: &[Complex<f64>],
+    reference: Option<&Constellation>,
+) -> Result<(), VizError> {
+    if symbols.is_empty() {
+        return Err(VizError::NoData);
+    }
+    render!(path, SQUARE, draw_constellation(symbols, reference))
+}
+
+fn draw_constellation<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    symbols: &[Complex<f64>],
+    reference: Option<&Constellation>,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&WHITE)?;
+    let ideal = reference.map_or(&[][..], |constellation| constellation.points());
+    let extent = symbols
+        .iter()
+        .chain(ideal)
+        .map(|s| s.re.abs().max(s.im.abs()))
+        .fold(0.0, f64::max)
+        .max(f64::MIN_POSITIVE)
+        * 1.1;
+    let mut chart = ChartBuilder::on(root)
+        .caption("Constellation", ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(-extent..extent, -extent..extent)?;
+    chart.configure_mesh().x_desc("I").y_desc("Q").draw()?;
+    chart.draw_series(
+        symbols
+            .iter()
+            .map(|s| Circle::new((s.re, s.im), 2, BLUE.mix(0.4).filled())),
+    )?;
+    chart.draw_series(
+        ideal
+            .iter()
+            .map(|p| Cross::new((p.re, p.im), 6, RED.stroke_width(2))),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::complex_noise;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("sdr-rust-viz-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_renders_each_plot() {
+        let symbols: Vec<Complex<f64>> = complex_noise(200, 1)
+            .into_iter()
+            .zip(Constellation::qpsk().points().iter().cycle())
+            .map(|(noise, point)| point + noise * 0.05)
+            .collect();
+        let rose = temp_path("rose.svg");
+        rose_diagram(&rose, &[(10.0, 1.0), (20.0, 2.0), (200.0, 0.5)], 36).unwrap();
+        assert!(std::fs::read_to_string(&rose).unwrap().contains("<svg"));
+        let histogram = CircularHistogram::from_readings(&[(10.0, 1.0), (200.0, 0.5)], 12);
+        assert!(rose_svg(&histogram).unwrap().contains("<svg"));
+        assert!(matches!(
+            rose_svg(&CircularHistogram::new(12)),
+            Err(VizError::NoData)
+        ));
+        let psd = temp_path("psd.png");
+        psd_plot(&psd, &symbols, 1000.0, 64).unwrap();
+        assert!(std::fs::metadata(&psd).unwrap().len() > 0);
+        let scatter = temp_path("constellation.svg");
+        constellation_plot(&scatter, &symbols, Some(&Constellation::qpsk())).unwrap();
+        for path in [rose, psd, scatter] {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(matches!(
+            rose_diagram("plot.gif", &[(0.0, 1.0)], 8),
+            Err(VizError::UnsupportedFormat(_))
+        ));
+    }
+}
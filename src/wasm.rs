@@ -0,0 +1,156 @@
+//! JavaScript bindings, so browser dashboards run the same math as the
+//! native tools.
+//!
+//! Build a cdylib with `cargo rustc --lib --crate-type cdylib --target
+//! wasm32-unknown-unknown --features wasm` and run `wasm-bindgen` on it, as
+//! the README shows. Complex buffers cross the boundary as separate real and
+//! imaginary `Float64Array`s.
+
+use crate::dsp::db::power_to_db;
+use crate::spectrum::{estimate_frequency, windowed_spectrum, Window};
+use crate::stats::{summarize, CircularSummary};
+use num_complex::Complex;
+use wasm_bindgen::prelude::*;
+
+fn complex_samples(re: &[f64], im: &[f64]) -> Result<Vec<Complex<f64>>, JsError> {
+    if re.len() != im.len() {
+        return Err(JsError::new("real and imaginary parts differ in length"));
+    }
+    Ok(re
+        .iter()
+        .zip(im)
+        .map(|(&re, &im)| Complex::new(re, im))
+        .collect())
+}
+
+/// Averages bearings as [`crate::average`] does, returning
+/// `[angle, magnitude]`.
+#[wasm_bindgen]
+pub fn average(angles: &[f64], magnitudes: &[f64]) -> Result<Vec<f64>, JsError> {
+    if angles.len() != magnitudes.len() {
+        return Err(JsError::new("angles and magnitudes differ in length"));
+    }
+    let readings: Vec<(f64, f64)> = angles
+        .iter()
+        .copied()
+        .zip(magnitudes.iter().copied())
+        .collect();
+    let (angle, magnitude) = crate::average(&readings);
+    Ok(vec![angle, magnitude])
+}
+
+/// Circular statistics of a set of bearings.
+#[wasm_bindgen]
+pub struct BearingSummary(CircularSummary);
+
+#[wasm_bindgen]
+impl BearingSummary {
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.0.count
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mean(&self) -> f64 {
+        self.0.mean
+    }
+
+    #[wasm_bindgen(getter, js_name = resultantLength)]
+    pub fn resultant_length(&self) -> f64 {
+        self.0.resultant_length
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn variance(&self) -> f64 {
+        self.0.variance()
+    }
+
+    #[wasm_bindgen(getter, js_name = standardDeviation)]
+    pub fn standard_deviation(&self) -> f64 {
+        self.0.standard_deviation()
+    }
+
+    /// Half-width in degrees of the interval around the mean, or `undefined`
+    /// if the bearings are too dispersed.
+    #[wasm_bindgen(js_name = confidenceInterval)]
+    pub fn confidence_interval(&self, confidence: f64) -> Option<f64> {
+        self.0.confidence_interval(confidence)
+    }
+}
+
+/// Summarizes weighted bearings, or returns `undefined` if there are none.
+#[wasm_bindgen(js_name = summarizeBearings)]
+pub fn summarize_bearings(
+    angles: &[f64],
+    weights: &[f64],
+) -> Result<Option<BearingSummary>, JsError> {
+    if angles.len() != weights.len() {
+        return Err(JsError::new("angles and weights differ in length"));
+    }
+    let readings: Vec<(f64, f64)> = angles
+        .iter()
+        .copied()
+        .zip(weights.iter().copied())
+        .collect();
+    Ok(summarize(&readings).map(BearingSummary))
+}
+
+/// Power spectrum in dB, ordered from the most negative frequency up and
+/// scaled so a full-scale tone reads 0 dB. `window` is one of
+/// `"rectangular"`, `"hann"`, `"hamming"` or `"blackman"`.
+#[wasm_bindgen(js_name = powerSpectrum)]
+pub fn power_spectrum(re: &[f64], im: &[f64], window: &str) -> Result<Vec<f64>, JsError> {
+    let samples = complex_samples(re, im)?;
+    let window = match window {
+        "rectangular" => Window::Rectangular,
+        "hann" => Window::Hann,
+        "hamming" => Window::Hamming,
+        "blackman" => Window::Blackman,
+        other => return Err(JsError::new(&format!("unknown window '{other}'"))),
+    };
+    let coefficients = window.coefficients(samples.len());
+    let gain: f64 = coefficients.iter().sum();
+    let spectrum = windowed_spectrum(&samples, &coefficients);
+    let length = spectrum.len();
+    Ok((0..length)
+        .map(|index| {
+            let bin = spectrum[(index + length / 2) % length] / gain;
+            power_to_db(bin.norm_sqr(), -300.0)
+        })
+        .collect())
+}
+
+/// Estimates the strongest tone's frequency with [`estimate_frequency`].
+#[wasm_bindgen(js_name = estimateFrequency)]
+pub fn estimate_frequency_js(
+    re: &[f64],
+    im: &[f64],
+    sample_rate: f64,
+) -> Result<Option<f64>, JsError> {
+    Ok(estimate_frequency(&complex_samples(re, im)?, sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_bindings_match_native() {
+        let result = average(&[10.0, 20.0], &[1.0, 1.0]).unwrap();
+        assert_relative_eq!(result[0], 15.0, epsilon = 1e-9);
+        let summary = summarize_bearings(&[10.0, 20.0], &[1.0, 1.0])
+            .unwrap()
+            .unwrap();
+        assert_relative_eq!(summary.mean(), 15.0, epsilon = 1e-9);
+        let re: Vec<f64> = (0..64)
+            .map(|n| (std::f64::consts::PI * n as f64 / 4.0).cos())
+            .collect();
+        let im: Vec<f64> = (0..64)
+            .map(|n| (std::f64::consts::PI * n as f64 / 4.0).sin())
+            .collect();
+        let spectrum = power_spectrum(&re, &im, "hann").unwrap();
+        // The tone sits in bin 8, which is index 40 once shifted.
+        assert_relative_eq!(spectrum[40], 0.0, epsilon = 1e-9);
+    }
+}